@@ -0,0 +1,264 @@
+//! Attribute macros for `util_lib_rs`, re-exported from the main crate with the `macros` feature.
+
+#![warn(clippy::all, clippy::pedantic)]
+
+use proc_macro::{Delimiter, Group, Span, TokenStream, TokenTree};
+
+/// Instrument every function in an `impl` block or inline module with `profile!()`, naming each
+/// profile block after its fully qualified function name. Nested modules and `impl` blocks are
+/// instrumented too.
+///
+/// `const fn` and `async fn` items are skipped, since profile blocks can't run in a constant
+/// context or be held across an `.await`. Mark other functions, modules or `impl` blocks with
+/// `#[no_profile]` to skip them as well. Without the `perf` feature, the functions are left as
+/// written.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::profile_all;
+///
+/// struct Parser;
+///
+/// #[profile_all]
+/// impl Parser {
+///     fn parse(&self, input: &str) -> usize {
+///         self.tokens(input).count()
+///     }
+///
+///     #[no_profile]
+///     fn tokens<'a>(&self, input: &'a str) -> impl Iterator<Item = &'a str> {
+///         input.split_whitespace()
+///     }
+/// }
+///
+/// #[profile_all]
+/// mod net {
+///     pub fn send() {}
+///     pub fn receive() {}
+/// }
+///
+/// assert_eq!(Parser.parse("a b"), 2);
+/// ```
+#[proc_macro_attribute]
+pub fn profile_all(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if let Some(token) = attr.into_iter().next() {
+        return compile_error("`profile_all` takes no arguments", token.span());
+    }
+    match container_body(&item) {
+        Ok(()) => instrument(item, true),
+        Err(span) => compile_error(
+            "`profile_all` can only be applied to an `impl` block or inline module",
+            span,
+        ),
+    }
+}
+
+/// Instrument a single function with `profile!()`, naming its profile block after the function's
+/// fully qualified name. Works on free functions, methods and generic functions, which share one
+/// anchor for every instantiation unless monomorphized names are enabled.
+///
+/// This is `#[util_lib_rs::profile_fn]` rather than `#[util_lib_rs::profile]` since attribute
+/// macros share a namespace with the `profile!` macro, and the two couldn't both be exported.
+/// `const fn` and `async fn` items are rejected, since profile blocks can't run in a constant
+/// context or be held across an `.await`. Without the `perf` feature, the function is left as
+/// written.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::profile_fn;
+///
+/// struct Parser;
+///
+/// impl Parser {
+///     #[profile_fn]
+///     fn parse<T: AsRef<str>>(&self, input: T) -> usize {
+///         input.as_ref().split_whitespace().count()
+///     }
+/// }
+///
+/// #[profile_fn]
+/// fn run() -> usize {
+///     Parser.parse("a b")
+/// }
+///
+/// assert_eq!(run(), 2);
+/// ```
+#[proc_macro_attribute]
+pub fn profile_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if let Some(token) = attr.into_iter().next() {
+        return compile_error("`profile_fn` takes no arguments", token.span());
+    }
+    match function_signature(&item) {
+        Ok(()) => instrument(item, true),
+        Err((message, span)) => compile_error(message, span),
+    }
+}
+
+/// Checks that `item` is a function with a body which can hold a profile block, returning the error
+/// to report otherwise.
+fn function_signature(item: &TokenStream) -> Result<(), (&'static str, Span)> {
+    const NOT_A_FUNCTION: &str = "`profile_fn` can only be applied to a function with a body";
+
+    let mut tokens = item.clone().into_iter();
+    let mut span = Span::call_site();
+    while let Some(token) = tokens.next() {
+        span = token.span();
+        match token {
+            // Attributes on the item itself.
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                tokens.next();
+            }
+            TokenTree::Ident(ident) => match ident.to_string().as_str() {
+                "const" | "async" => {
+                    return Err((
+                        "`profile_fn` can't be applied to a `const fn` or `async fn`",
+                        span,
+                    ))
+                }
+                "fn" => {
+                    return if tokens.any(|token| is_group(&token, Delimiter::Brace)) {
+                        Ok(())
+                    } else {
+                        Err((NOT_A_FUNCTION, span))
+                    };
+                }
+                "pub" | "crate" | "unsafe" | "extern" | "default" => {}
+                _ => return Err((NOT_A_FUNCTION, span)),
+            },
+            // `pub(crate)` visibility and `extern "C"` ABIs.
+            TokenTree::Group(_) | TokenTree::Literal(_) => {}
+            TokenTree::Punct(_) => return Err((NOT_A_FUNCTION, span)),
+        }
+    }
+    Err((NOT_A_FUNCTION, span))
+}
+
+/// Checks that `item` is an `impl` block or a module with a body, returning the span to report an
+/// error at otherwise.
+fn container_body(item: &TokenStream) -> Result<(), Span> {
+    let mut tokens = item.clone().into_iter();
+    let mut span = Span::call_site();
+    while let Some(token) = tokens.next() {
+        span = token.span();
+        match token {
+            // Attributes on the item itself.
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                tokens.next();
+            }
+            TokenTree::Ident(ident) if matches!(ident.to_string().as_str(), "impl" | "mod") => {
+                return if tokens.any(|token| is_group(&token, Delimiter::Brace)) {
+                    Ok(())
+                } else {
+                    Err(span)
+                };
+            }
+            TokenTree::Ident(_) | TokenTree::Group(_) => {}
+            _ => return Err(span),
+        }
+    }
+    Err(span)
+}
+
+/// Inserts `profile!()` at the start of every function body among `items`, recursing into `impl`
+/// blocks and modules. Only strips `#[no_profile]` attributes unless `enabled`.
+fn instrument(items: TokenStream, enabled: bool) -> TokenStream {
+    let mut output = Vec::new();
+    let mut tokens = items.into_iter().peekable();
+    let mut skip = false;
+    while let Some(token) = tokens.next() {
+        match &token {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                if let Some(TokenTree::Group(attr)) = tokens.peek() {
+                    if attr.delimiter() == Delimiter::Bracket
+                        && attr.stream().to_string() == "no_profile"
+                    {
+                        tokens.next();
+                        skip = true;
+                        continue;
+                    }
+                }
+                output.push(token);
+            }
+            TokenTree::Punct(punct) if punct.as_char() == ';' => {
+                skip = false;
+                output.push(token);
+            }
+            TokenTree::Ident(ident) => {
+                let keyword = ident.to_string();
+                output.push(token);
+                match keyword.as_str() {
+                    "const" | "async" => skip = true,
+                    "fn" | "impl" | "mod" => {
+                        let enabled = enabled && !skip;
+                        copy_item(&mut tokens, &mut output, |body| match keyword.as_str() {
+                            "fn" if enabled => profiled(body),
+                            "fn" => body,
+                            _ => instrument(body, enabled),
+                        });
+                        skip = false;
+                    }
+                    _ => {}
+                }
+            }
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                skip = false;
+                output.push(token);
+            }
+            _ => output.push(token),
+        }
+    }
+    output.into_iter().collect()
+}
+
+/// Copies the rest of an item's signature to `output`, replacing its body with the result of
+/// `body`. Items without a body end at the first `;`.
+fn copy_item(
+    tokens: &mut impl Iterator<Item = TokenTree>,
+    output: &mut Vec<TokenTree>,
+    body: impl FnOnce(TokenStream) -> TokenStream,
+) {
+    for token in tokens.by_ref() {
+        match token {
+            TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                let mut replaced = Group::new(Delimiter::Brace, body(group.stream()));
+                replaced.set_span(group.span());
+                output.push(TokenTree::Group(replaced));
+                return;
+            }
+            TokenTree::Punct(punct) if punct.as_char() == ';' => {
+                output.push(TokenTree::Punct(punct));
+                return;
+            }
+            token => output.push(token),
+        }
+    }
+}
+
+/// Returns a function body starting with a profile block, which is configured out unless the `perf`
+/// feature is enabled, as with `profile!`, leaving the body as written.
+fn profiled(body: TokenStream) -> TokenStream {
+    let mut profiled: TokenStream = "#[cfg(feature = \"perf\")] ::util_lib_rs::profile!();"
+        .parse()
+        .expect("valid profile invocation");
+    profiled.extend(body);
+    profiled
+}
+
+fn is_group(token: &TokenTree, delimiter: Delimiter) -> bool {
+    matches!(token, TokenTree::Group(group) if group.delimiter() == delimiter)
+}
+
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let error: TokenStream = format!("::core::compile_error!({message:?});")
+        .parse()
+        .expect("valid compile_error invocation");
+    error
+        .into_iter()
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
@@ -0,0 +1,279 @@
+//! Bump allocation.
+//!
+//! An [`Arena`] hands out memory by bumping an offset into large chunks, and frees all of it at
+//! once with [`reset`](Arena::reset), which suits data living for one frame or request. After the
+//! first few resets the arena settles into a single chunk big enough for the busiest frame, so
+//! steady-state allocation never touches the global allocator.
+
+use crate::bits;
+use std::{
+    alloc::{self, Layout},
+    cell::{Cell, RefCell},
+    ptr::NonNull,
+};
+
+/// Size of the chunks allocated by [`Arena::new`].
+const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// Alignment of every chunk, which allocations with smaller alignments never need padding for at
+/// the start of a chunk.
+const CHUNK_ALIGN: usize = 16;
+
+/// One block of memory obtained from the global allocator.
+struct Chunk {
+    ptr: NonNull<u8>,
+    capacity: usize,
+}
+
+impl Chunk {
+    fn new(capacity: usize) -> Self {
+        let layout = Self::layout(capacity);
+        // SAFETY: `layout` has a non-zero size.
+        let ptr = unsafe { alloc::alloc(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self { ptr, capacity }
+    }
+
+    fn layout(capacity: usize) -> Layout {
+        Layout::from_size_align(capacity.max(1), CHUNK_ALIGN).expect("valid chunk layout")
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` was allocated with the same layout in `Chunk::new`.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.capacity)) };
+    }
+}
+
+/// Memory use of an [`Arena`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct ArenaStats {
+    /// Bytes allocated since the last reset, including alignment padding.
+    pub used: usize,
+    /// Most bytes allocated between two resets.
+    pub high_water: usize,
+    /// Bytes obtained from the global allocator.
+    pub capacity: usize,
+}
+
+/// A bump allocator which frees everything it allocated at once.
+///
+/// Values are never dropped, so types owning other resources, like `String` or `Vec`, leak them
+/// when the arena is reset.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::arena::Arena;
+///
+/// let mut arena = Arena::new();
+/// for frame in 0..3 {
+///     let position = arena.alloc((frame, 2.0f32));
+///     let indices = arena.alloc_slice(&[1u16, 2, 3]);
+///     position.1 *= 2.0;
+///     indices[0] = 0;
+///     arena.reset();
+/// }
+/// assert!(arena.stats().high_water > 0);
+/// ```
+#[must_use]
+pub struct Arena {
+    /// Chunks allocated since the last reset, allocating from the last.
+    chunks: RefCell<Vec<Chunk>>,
+    /// Bytes allocated from the last chunk.
+    offset: Cell<usize>,
+    used: Cell<usize>,
+    high_water: Cell<usize>,
+    chunk_size: usize,
+    /// Counter each allocation adds its size to, if any.
+    counter: Option<&'static str>,
+}
+
+impl Default for Arena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Debug for Arena {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Arena")
+            .field("stats", &self.stats())
+            .field("counter", &self.counter)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Arena {
+    /// Creates an empty arena which allocates memory in 64KiB chunks, or larger for allocations
+    /// which don't fit.
+    pub fn new() -> Self {
+        Self::with_chunk_size(DEFAULT_CHUNK_SIZE)
+    }
+
+    /// Creates an empty arena which allocates memory in chunks of `chunk_size` bytes.
+    pub fn with_chunk_size(chunk_size: usize) -> Self {
+        Self {
+            chunks: RefCell::new(Vec::new()),
+            offset: Cell::new(0),
+            used: Cell::new(0),
+            high_water: Cell::new(0),
+            chunk_size: chunk_size.max(1),
+            counter: None,
+        }
+    }
+
+    /// Adds the size of every allocation to the profiler counter `name`, as with `counter_add!`,
+    /// so the report lists the bytes allocated from the arena during the session.
+    pub fn counter(mut self, name: &'static str) -> Self {
+        self.counter = Some(name);
+        self
+    }
+
+    /// Moves `value` into the arena, returning a reference to it.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, value: T) -> &mut T {
+        let ptr = self.alloc_layout(Layout::new::<T>()).cast::<T>();
+        // SAFETY: `ptr` is valid for writes of a `T` and aligned for it, and this memory isn't
+        // handed out again until `reset` or `drop` take the arena mutably, ending the borrow.
+        unsafe {
+            ptr.as_ptr().write(value);
+            &mut *ptr.as_ptr()
+        }
+    }
+
+    /// Copies `values` into the arena, returning a reference to the copy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the copy would be larger than `isize::MAX` bytes.
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc_slice<T: Copy>(&self, values: &[T]) -> &mut [T] {
+        let layout = Layout::array::<T>(values.len()).expect("valid slice layout");
+        let ptr = self.alloc_layout(layout).cast::<T>();
+        // SAFETY: As in `alloc`, for `values.len()` elements, which can't overlap `values` as the
+        // memory was unused.
+        unsafe {
+            std::ptr::copy_nonoverlapping(values.as_ptr(), ptr.as_ptr(), values.len());
+            std::slice::from_raw_parts_mut(ptr.as_ptr(), values.len())
+        }
+    }
+
+    /// Frees everything allocated from the arena. If allocations needed more than one chunk since
+    /// the last reset, they are replaced by one chunk as large as all of them.
+    pub fn reset(&mut self) {
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            let capacity = chunks.iter().map(|chunk| chunk.capacity).sum();
+            chunks.clear();
+            chunks.push(Chunk::new(capacity));
+        }
+        self.offset.set(0);
+        self.used.set(0);
+    }
+
+    /// Returns the bytes used, the high-water mark and the capacity of the arena.
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            used: self.used.get(),
+            high_water: self.high_water.get(),
+            capacity: self
+                .chunks
+                .borrow()
+                .iter()
+                .map(|chunk| chunk.capacity)
+                .sum(),
+        }
+    }
+
+    /// Returns unused memory fitting `layout`, allocating a new chunk if the last one is full.
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        let mut chunks = self.chunks.borrow_mut();
+        let fits = |chunk: &Chunk, offset: usize| {
+            let address = chunk.ptr.as_ptr().addr().checked_add(offset)?;
+            let padding = bits::checked_align_up(address, layout.align())? - address;
+            let end = offset.checked_add(padding)?.checked_add(layout.size())?;
+            (end <= chunk.capacity).then_some((padding, end))
+        };
+
+        let (padding, end) = chunks
+            .last()
+            .and_then(|chunk| fits(chunk, self.offset.get()))
+            .unwrap_or_else(|| {
+                let capacity = layout
+                    .size()
+                    .checked_add(layout.align())
+                    .expect("valid allocation size")
+                    .max(self.chunk_size);
+                let chunk = Chunk::new(capacity);
+                let fit = fits(&chunk, 0).expect("valid chunk size");
+                chunks.push(chunk);
+                self.offset.set(0);
+                fit
+            });
+
+        let start = self.offset.get() + padding;
+        self.offset.set(end);
+        let used = self.used.get() + padding + layout.size();
+        self.used.set(used);
+        self.high_water.set(self.high_water.get().max(used));
+        if let Some(name) = self.counter {
+            crate::counter_add!(name, layout.size() as u64);
+        }
+
+        let chunk = chunks.last().expect("valid chunk");
+        // SAFETY: `start` is within the chunk, as `fits` checked its end.
+        unsafe { NonNull::new_unchecked(chunk.ptr.as_ptr().add(start)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations() {
+        let mut arena = Arena::with_chunk_size(64);
+        let byte = arena.alloc(1u8);
+        let word = arena.alloc(2u64);
+        assert_eq!((*byte, *word), (1, 2));
+        assert_eq!(std::ptr::from_mut(word).addr() % 8, 0);
+        assert_eq!(arena.stats().used, 16);
+
+        let large = arena.alloc_slice(&[7u32; 100]);
+        assert_eq!(large.len(), 100);
+        assert!(large.iter().all(|&value| value == 7));
+        let after = arena.alloc(3u16);
+        *after += 1;
+        let stats = arena.stats();
+        assert_eq!(stats.high_water, stats.used);
+        assert_eq!(stats.capacity, 64 + 404);
+        assert!(arena.alloc_slice::<u8>(&[]).is_empty());
+
+        arena.reset();
+        let stats = arena.stats();
+        assert_eq!((stats.used, stats.capacity), (0, 468));
+        assert_eq!(stats.high_water, 418);
+        assert_eq!(*arena.alloc(5i32), 5);
+        assert_eq!(arena.stats().capacity, 468);
+    }
+
+    #[cfg(feature = "perf")]
+    #[test]
+    fn counted_allocations() {
+        use crate::performance::{profile_begin, profile_end};
+
+        profile_begin();
+        let arena = Arena::new().counter("frame_arena");
+        arena.alloc(0u64);
+        arena.alloc_slice(&[0u8; 10]);
+        let report = profile_end();
+        let counter = report
+            .event_counters
+            .iter()
+            .find(|counter| counter.name == "frame_arena")
+            .expect("valid counter");
+        assert_eq!(counter.count, 18);
+    }
+}
@@ -0,0 +1,307 @@
+//! Benchmarking.
+//!
+//! A [`Bench`] times a closure the way a benchmark harness would, without needing one: it warms
+//! up for a while to estimate how long a call takes, picks how many calls to time together so each
+//! sample is long enough to measure, rejects outlying samples disturbed by interrupts or other
+//! processes, and reports the mean, median and standard deviation per call, with the throughput
+//! if the bytes or items each call processes are set. Calls are timed with the
+//! [`Stopwatch`] clock, which reads the profiler's timestamp counter with
+//! the `perf` feature.
+//!
+//! Use [`profile!`](crate::profile) to see where a program spends its time, and a [`Bench`] to
+//! measure one routine in isolation while optimizing it.
+
+use crate::{
+    fmt::{format_duration, format_rate},
+    stats::OnlineStats,
+    time::Stopwatch,
+};
+use std::{fmt, hint::black_box, time::Duration};
+
+/// Settings for benchmarking a closure.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::bench::Bench;
+///
+/// let data = vec![1u8; 4096];
+/// let result = Bench::new("sum")
+///     .warmup(Duration::from_millis(10))
+///     .measurement_time(Duration::from_millis(50))
+///     .bytes(data.len() as u64)
+///     .run(|| data.iter().map(|&b| u64::from(b)).sum::<u64>());
+/// println!("{result}");
+/// assert!(result.min <= result.median && result.median <= result.max);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Bench {
+    name: String,
+    warmup: Duration,
+    measurement_time: Duration,
+    samples: u32,
+    byte_count: u64,
+    item_count: u64,
+}
+
+impl Bench {
+    /// Creates a benchmark named `name`, which warms up for 200ms and then takes 50 samples over
+    /// about a second.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            warmup: Duration::from_millis(200),
+            measurement_time: Duration::from_secs(1),
+            samples: 50,
+            byte_count: 0,
+            item_count: 0,
+        }
+    }
+
+    /// Sets how long to run the closure before measuring, to fill caches and estimate its time.
+    pub fn warmup(mut self, warmup: Duration) -> Self {
+        self.warmup = warmup;
+        self
+    }
+
+    /// Sets roughly how long to spend taking samples. A closure slower than this per sample takes
+    /// longer, since every sample calls it at least once.
+    pub fn measurement_time(mut self, measurement_time: Duration) -> Self {
+        self.measurement_time = measurement_time;
+        self
+    }
+
+    /// Sets how many samples to take, at least 1.
+    pub fn samples(mut self, samples: u32) -> Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// Sets the number of bytes each call processes, to report its throughput in bytes.
+    pub fn bytes(mut self, byte_count: u64) -> Self {
+        self.byte_count = byte_count;
+        self
+    }
+
+    /// Sets the number of items each call processes, to report its throughput in items.
+    pub fn items(mut self, item_count: u64) -> Self {
+        self.item_count = item_count;
+        self
+    }
+
+    /// Benchmarks `routine`, passing what it returns to [`black_box`] so the work isn't
+    /// optimized away.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn run<R>(&self, mut routine: impl FnMut() -> R) -> BenchResult {
+        // Warm up in doubling batches, so timing doesn't dominate fast closures.
+        let mut stopwatch = Stopwatch::start_new();
+        let mut batch = 1u64;
+        let mut calls = 0u64;
+        loop {
+            for _ in 0..batch {
+                black_box(routine());
+            }
+            calls += batch;
+            if stopwatch.elapsed() >= self.warmup {
+                break;
+            }
+            batch = batch.saturating_mul(2);
+        }
+        let per_call = stopwatch.stop().as_nanos() as f64 / calls as f64;
+        let per_sample = self.measurement_time.as_nanos() as f64 / f64::from(self.samples);
+        let iterations = (per_sample / per_call.max(1.0)).ceil().max(1.0) as u64;
+
+        let mut samples: Vec<f64> = (0..self.samples)
+            .map(|_| {
+                let mut stopwatch = Stopwatch::start_new();
+                for _ in 0..iterations {
+                    black_box(routine());
+                }
+                stopwatch.stop().as_nanos() as f64 / iterations as f64
+            })
+            .collect();
+        let outliers = reject_outliers(&mut samples);
+        let stats: OnlineStats = samples.iter().copied().collect();
+        let nanos = |nanos: f64| Duration::from_secs_f64(nanos.max(0.0) / 1e9);
+        BenchResult {
+            name: self.name.clone(),
+            samples: samples.len(),
+            iterations,
+            outliers,
+            mean: nanos(stats.mean()),
+            median: nanos(quantile(&samples, 0.5)),
+            std_dev: nanos(stats.sample_std_dev()),
+            min: nanos(stats.min().unwrap_or_default()),
+            max: nanos(stats.max().unwrap_or_default()),
+            byte_count: self.byte_count,
+            item_count: self.item_count,
+        }
+    }
+}
+
+/// Returns the `q` quantile of sorted `values`, interpolating between the nearest two.
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss
+)]
+fn quantile(values: &[f64], q: f64) -> f64 {
+    let Some(last) = values.len().checked_sub(1) else {
+        return 0.0;
+    };
+    let position = q * last as f64;
+    let below = position.floor() as usize;
+    let above = position.ceil() as usize;
+    values[below] + (values[above] - values[below]) * (position - below as f64)
+}
+
+/// Sorts `samples` and removes those beyond 1.5 interquartile ranges from the middle half,
+/// returning how many were removed.
+fn reject_outliers(samples: &mut Vec<f64>) -> usize {
+    samples.sort_by(f64::total_cmp);
+    let q1 = quantile(samples, 0.25);
+    let q3 = quantile(samples, 0.75);
+    let fence = 1.5 * (q3 - q1);
+    let count = samples.len();
+    samples.retain(|&sample| sample >= q1 - fence && sample <= q3 + fence);
+    count - samples.len()
+}
+
+/// Times of a closure measured by a [`Bench`], per call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct BenchResult {
+    /// Name of the benchmark.
+    pub name: String,
+    /// Samples kept after rejecting outliers.
+    pub samples: usize,
+    /// Calls timed together in each sample.
+    pub iterations: u64,
+    /// Samples rejected as outliers.
+    pub outliers: usize,
+    /// Mean time of a call.
+    pub mean: Duration,
+    /// Median time of a call.
+    pub median: Duration,
+    /// Sample standard deviation of the time of a call.
+    pub std_dev: Duration,
+    /// Time of a call in the fastest sample.
+    pub min: Duration,
+    /// Time of a call in the slowest sample kept.
+    pub max: Duration,
+    /// Bytes processed per call.
+    pub byte_count: u64,
+    /// Items processed per call.
+    pub item_count: u64,
+}
+
+impl BenchResult {
+    /// Bytes processed per second at the median time, or `None` if no bytes were set.
+    #[must_use]
+    pub fn bytes_per_second(&self) -> Option<f64> {
+        self.per_second(self.byte_count)
+    }
+
+    /// Items processed per second at the median time, or `None` if no items were set.
+    #[must_use]
+    pub fn items_per_second(&self) -> Option<f64> {
+        self.per_second(self.item_count)
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn per_second(&self, count: u64) -> Option<f64> {
+        (count > 0 && !self.median.is_zero()).then(|| count as f64 / self.median.as_secs_f64())
+    }
+}
+
+impl fmt::Display for BenchResult {
+    /// Formats the result on one line, such as
+    /// `sum: 1.24µs median, 1.26µs ± 40ns mean, 3.3 GB/s (48 samples of 810 calls, 2 outliers)`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} median, {} ± {} mean",
+            self.name,
+            format_duration(self.median),
+            format_duration(self.mean),
+            format_duration(self.std_dev)
+        )?;
+        if let Some(rate) = self.bytes_per_second() {
+            write!(f, ", {}", format_rate(rate))?;
+        }
+        if let Some(rate) = self.items_per_second() {
+            write!(f, ", {rate:.0} items/s")?;
+        }
+        write!(
+            f,
+            " ({} samples of {} calls, {} outliers)",
+            self.samples, self.iterations, self.outliers
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+
+    #[test]
+    fn benchmarks() {
+        let mut calls = 0u64;
+        let result = Bench::new("spin")
+            .warmup(Duration::from_millis(5))
+            .measurement_time(Duration::from_millis(40))
+            .samples(20)
+            .bytes(1000)
+            .items(10)
+            .run(|| {
+                calls += 1;
+                let start = Instant::now();
+                while start.elapsed() < Duration::from_micros(20) {
+                    black_box(0);
+                }
+            });
+        assert_eq!(result.samples + result.outliers, 20);
+        assert!(calls >= 20 * result.iterations);
+        assert!(result.iterations > 1);
+        assert!(result.min >= Duration::from_micros(19));
+        assert!(result.min <= result.median && result.median <= result.max);
+        assert!(result.min <= result.mean && result.mean <= result.max);
+        let bytes = result.bytes_per_second().expect("bytes set");
+        let items = result.items_per_second().expect("items set");
+        assert!((bytes / items - 100.0).abs() < 1e-6);
+
+        let printed = result.to_string();
+        assert!(printed.starts_with("spin: "), "{printed}");
+        assert!(printed.contains("B/s") && printed.contains("items/s"));
+        assert!(printed.ends_with(&format!(
+            "({} samples of {} calls, {} outliers)",
+            result.samples, result.iterations, result.outliers
+        )));
+
+        let once = Bench::new("slow")
+            .warmup(Duration::ZERO)
+            .measurement_time(Duration::ZERO)
+            .samples(0)
+            .run(|| ());
+        assert_eq!((once.samples, once.iterations), (1, 1));
+        assert_eq!(once.bytes_per_second(), None);
+    }
+
+    #[test]
+    fn outliers() {
+        let mut samples = vec![10.0, 11.0, 9.0, 10.0, 50.0, 10.5, 0.0, 9.5];
+        assert_eq!(reject_outliers(&mut samples), 2);
+        assert_eq!(samples, [9.0, 9.5, 10.0, 10.0, 10.5, 11.0]);
+        assert!((quantile(&samples, 0.5) - 10.0).abs() < 1e-9);
+        assert!((quantile(&[1.0, 2.0], 0.25) - 1.25).abs() < 1e-9);
+        assert!(quantile(&[], 0.5).abs() < 1e-9);
+        assert_eq!(reject_outliers(&mut vec![3.0; 5]), 0);
+    }
+}
@@ -0,0 +1,207 @@
+//! Prints saved profile dumps.
+//!
+//! Loads one dump saved with `ProfileDump::save` or `profile_save_baseline` and prints its report,
+//! optionally filtered, sorted and cut down to the top anchors, or loads two and prints how each
+//! anchor changed from the first to the second. Trace events saved in a dump can be written out as
+//! Chrome Trace Event JSON with `--chrome`.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+use util_lib_rs::{
+    cli::{self, ArgsError},
+    path_util,
+    performance::{
+        chrome,
+        compare::Comparison,
+        dump::ProfileDump,
+        filter::AnchorFilter,
+        options::{ReportOptions, SortOrder},
+    },
+};
+
+/// Parsed command line arguments.
+#[derive(Debug, Default)]
+struct Args {
+    paths: Vec<PathBuf>,
+    options: ReportOptions,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    diff: bool,
+    chrome: Option<PathBuf>,
+}
+
+impl Args {
+    fn declare() -> cli::Args {
+        cli::Args::new("profview")
+            .about(
+                "Prints the report saved in DUMP, or how each anchor changed between two dumps \
+                 with --diff.",
+            )
+            .option(
+                "sort",
+                None,
+                "ORDER",
+                "Sort anchors by exclusive, inclusive, hits, name or discovery",
+            )
+            .option(
+                "top",
+                None,
+                "COUNT",
+                "Keep only the first COUNT anchors, after sorting",
+            )
+            .option(
+                "include",
+                None,
+                "PATTERN",
+                "Keep only anchors matching PATTERN, repeatable",
+            )
+            .option(
+                "exclude",
+                None,
+                "PATTERN",
+                "Drop anchors matching PATTERN, repeatable",
+            )
+            .option(
+                "chrome",
+                None,
+                "PATH",
+                "Also write the dump's trace events to PATH as Chrome Trace Event JSON",
+            )
+            .flag("diff", None, "Compare the second dump against the first")
+            .positional("DUMP", "Dump to print, or the baseline to compare against")
+            .optional_positional("CURRENT", "Dump to compare against the baseline")
+    }
+
+    fn parse(args: impl Iterator<Item = String>) -> Result<Self, ArgsError> {
+        let args = Self::declare().parse(args)?;
+        let diff = args.is_set("diff");
+        let paths: Vec<PathBuf> = args.positionals().iter().map(PathBuf::from).collect();
+        if paths.len() == 2 && !diff {
+            return Err(ArgsError::Invalid(
+                "two dumps can only be compared with --diff".to_string(),
+            ));
+        }
+        if paths.len() != 2 && diff {
+            return Err(ArgsError::Invalid(
+                "--diff needs exactly two dumps".to_string(),
+            ));
+        }
+        let chrome = args.value("chrome").map(PathBuf::from);
+        if chrome.is_some() && diff {
+            return Err(ArgsError::Invalid(
+                "--chrome can't be used with --diff".to_string(),
+            ));
+        }
+
+        let mut options = ReportOptions::default();
+        if let Some(order) = args.value("sort") {
+            options = options.sort_by(parse_sort(order).map_err(ArgsError::Invalid)?);
+        }
+        if let Some(count) = args.value("top") {
+            let count = count
+                .parse()
+                .map_err(|_| ArgsError::Invalid(format!("invalid --top count: {count}")))?;
+            options = options.top(count);
+        }
+        Ok(Self {
+            paths,
+            options,
+            include: args.values_of("include").map(String::from).collect(),
+            exclude: args.values_of("exclude").map(String::from).collect(),
+            diff,
+            chrome,
+        })
+    }
+}
+
+fn parse_sort(order: &str) -> Result<SortOrder, String> {
+    match order {
+        "exclusive" => Ok(SortOrder::Exclusive),
+        "inclusive" => Ok(SortOrder::Inclusive),
+        "hits" => Ok(SortOrder::Hits),
+        "name" => Ok(SortOrder::Name),
+        "discovery" => Ok(SortOrder::Discovery),
+        _ => Err(format!("invalid --sort order: {order}")),
+    }
+}
+
+/// Loads the dump at `path`, keeping only the anchors and trace events which pass `filter`.
+fn load(path: &PathBuf, filter: Option<&AnchorFilter>) -> Result<ProfileDump, String> {
+    let mut dump = ProfileDump::load(path).map_err(|err| {
+        let path = path_util::collapse_home(path);
+        format!("failed to load {}: {err}", path.display())
+    })?;
+    if let Some(filter) = filter {
+        dump.report = dump.report.filtered(filter);
+        dump.events.retain(|event| filter.matches(event.name));
+    }
+    Ok(dump)
+}
+
+/// Writes the trace events of `dump` to the file at `path` as Chrome Trace Event JSON.
+fn write_chrome(dump: &ProfileDump, path: &PathBuf) -> Result<(), String> {
+    let write = || {
+        let mut writer = BufWriter::new(File::create(path)?);
+        chrome::write_chrome_trace(
+            &dump.events,
+            "thread",
+            dump.start_tsc,
+            dump.report.timer_freq,
+            &mut writer,
+        )?;
+        writer.flush()
+    };
+    write().map_err(|err| {
+        let path = path_util::collapse_home(path);
+        format!("failed to write {}: {err}", path.display())
+    })
+}
+
+fn run(args: &Args) -> Result<String, String> {
+    let filter = if args.include.is_empty() && args.exclude.is_empty() {
+        None
+    } else {
+        let include: Vec<&str> = args.include.iter().map(String::as_str).collect();
+        let exclude: Vec<&str> = args.exclude.iter().map(String::as_str).collect();
+        Some(AnchorFilter::new(&include, &exclude).map_err(|err| err.to_string())?)
+    };
+    let filter = filter.as_ref();
+    if args.diff {
+        let baseline = load(&args.paths[0], filter)?.report;
+        let current = load(&args.paths[1], filter)?.report;
+        Ok(Comparison::new(&baseline, &current).to_string())
+    } else {
+        let dump = load(&args.paths[0], filter)?;
+        if let Some(path) = &args.chrome {
+            write_chrome(&dump, path)?;
+        }
+        Ok(dump.report.with_options(&args.options).to_string())
+    }
+}
+
+fn main() -> ExitCode {
+    match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => match run(&args) {
+            Ok(output) => {
+                print!("{output}");
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("profview: {err}");
+                ExitCode::FAILURE
+            }
+        },
+        Err(ArgsError::Help(help)) => {
+            println!("{help}");
+            ExitCode::SUCCESS
+        }
+        Err(ArgsError::Invalid(err)) => {
+            eprintln!("profview: {err}\n\n{}", Args::declare().help());
+            ExitCode::from(2)
+        }
+    }
+}
@@ -0,0 +1,575 @@
+//! Bit sets and bit manipulation.
+//!
+//! A [`BitSet`] stores a set of small integers as one bit each, growing as larger ones are set, for
+//! dense flags such as which anchors were hit in a frame. The free functions align and round
+//! integers, as allocators and binary formats need, and read and write bit fields packed into an
+//! integer. They work on every unsigned integer type through the [`Unsigned`] trait.
+
+use std::{
+    fmt,
+    iter::FusedIterator,
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Not, Shl, Shr, Sub},
+};
+
+/// An unsigned integer type, for the bit manipulation functions of this module.
+pub trait Unsigned:
+    Copy
+    + Eq
+    + Ord
+    + fmt::Debug
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + Not<Output = Self>
+    + Shl<u32, Output = Self>
+    + Shr<u32, Output = Self>
+    + Sub<Output = Self>
+{
+    /// The value 0.
+    const ZERO: Self;
+    /// The value 1.
+    const ONE: Self;
+    /// The number of bits of the type.
+    const BITS: u32;
+
+    /// Whether exactly one bit is set.
+    fn is_power_of_two(self) -> bool;
+
+    /// The smallest power of two no smaller than the value, or `None` if it would overflow.
+    fn checked_next_power_of_two(self) -> Option<Self>;
+
+    /// The sum of the values, or `None` if it would overflow.
+    fn checked_add(self, other: Self) -> Option<Self>;
+
+    /// The remainder of dividing the value by `other`.
+    #[must_use]
+    fn rem(self, other: Self) -> Self;
+}
+
+/// Implements [`Unsigned`] for each type with its inherent methods.
+macro_rules! impl_unsigned {
+    ($($ty:ty),* $(,)?) => {$(
+        impl Unsigned for $ty {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+            const BITS: u32 = <$ty>::BITS;
+
+            fn is_power_of_two(self) -> bool {
+                <$ty>::is_power_of_two(self)
+            }
+
+            fn checked_next_power_of_two(self) -> Option<Self> {
+                <$ty>::checked_next_power_of_two(self)
+            }
+
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$ty>::checked_add(self, other)
+            }
+
+            fn rem(self, other: Self) -> Self {
+                self % other
+            }
+        }
+    )*};
+}
+
+impl_unsigned!(u8, u16, u32, u64, u128, usize);
+
+/// Returns the smallest power of two no smaller than `value`, or 1 for 0.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::bits::next_power_of_two;
+///
+/// assert_eq!(next_power_of_two(100_u32), 128);
+/// assert_eq!(next_power_of_two(64_u32), 64);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the result doesn't fit in the type.
+pub fn next_power_of_two<T: Unsigned>(value: T) -> T {
+    value
+        .checked_next_power_of_two()
+        .unwrap_or_else(|| panic!("next power of two of {value:?} overflows"))
+}
+
+/// Rounds `value` up to a multiple of `align`, which must be a power of two, or returns `None` if
+/// the result doesn't fit in the type.
+///
+/// # Panics
+///
+/// Panics if `align` isn't a power of two.
+pub fn checked_align_up<T: Unsigned>(value: T, align: T) -> Option<T> {
+    assert_power_of_two(align);
+    Some(value.checked_add(align - T::ONE)? & !(align - T::ONE))
+}
+
+/// Rounds `value` up to a multiple of `align`, which must be a power of two.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::bits::{align_down, align_up};
+///
+/// assert_eq!(align_up(13_usize, 8), 16);
+/// assert_eq!(align_up(16_usize, 8), 16);
+/// assert_eq!(align_down(13_usize, 8), 8);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `align` isn't a power of two, or the result doesn't fit in the type.
+pub fn align_up<T: Unsigned>(value: T, align: T) -> T {
+    checked_align_up(value, align)
+        .unwrap_or_else(|| panic!("aligning {value:?} up to {align:?} overflows"))
+}
+
+/// Rounds `value` down to a multiple of `align`, which must be a power of two.
+///
+/// # Panics
+///
+/// Panics if `align` isn't a power of two.
+pub fn align_down<T: Unsigned>(value: T, align: T) -> T {
+    assert_power_of_two(align);
+    value & !(align - T::ONE)
+}
+
+/// Whether `value` is a multiple of `align`, which must be a power of two.
+///
+/// # Panics
+///
+/// Panics if `align` isn't a power of two.
+pub fn is_aligned<T: Unsigned>(value: T, align: T) -> bool {
+    assert_power_of_two(align);
+    value & (align - T::ONE) == T::ZERO
+}
+
+/// Rounds `value` up to a multiple of `multiple`, which needn't be a power of two.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::bits::{round_down, round_up};
+///
+/// assert_eq!(round_up(1000_u64, 60), 1020);
+/// assert_eq!(round_down(1000_u64, 60), 960);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `multiple` is 0, or the result doesn't fit in the type.
+pub fn round_up<T: Unsigned>(value: T, multiple: T) -> T {
+    match round_down(value, multiple) {
+        rounded if rounded == value => value,
+        rounded => rounded
+            .checked_add(multiple)
+            .unwrap_or_else(|| panic!("rounding {value:?} up to {multiple:?} overflows")),
+    }
+}
+
+/// Rounds `value` down to a multiple of `multiple`, which needn't be a power of two.
+///
+/// # Panics
+///
+/// Panics if `multiple` is 0.
+pub fn round_down<T: Unsigned>(value: T, multiple: T) -> T {
+    assert!(multiple != T::ZERO, "can't round to a multiple of 0");
+    value - value.rem(multiple)
+}
+
+/// Returns the `width` bits of `value` starting `offset` bits from the least significant.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::bits::{extract, insert};
+///
+/// // An RGB565 pixel: 5 bits of red, 6 of green and 5 of blue.
+/// let pixel = 0b10101_110011_01100_u16;
+/// assert_eq!(extract(pixel, 5, 6), 0b110011);
+/// assert_eq!(insert(pixel, 0, 5, 0b11111), 0b10101_110011_11111);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the field extends past the most significant bit.
+pub fn extract<T: Unsigned>(value: T, offset: u32, width: u32) -> T {
+    match field_mask::<T>(offset, width) {
+        mask if mask == T::ZERO => T::ZERO,
+        mask => (value >> offset) & mask,
+    }
+}
+
+/// Returns `value` with the `width` bits starting `offset` bits from the least significant
+/// replaced by the low bits of `field`.
+///
+/// # Panics
+///
+/// Panics if the field extends past the most significant bit.
+pub fn insert<T: Unsigned>(value: T, offset: u32, width: u32, field: T) -> T {
+    match field_mask::<T>(offset, width) {
+        mask if mask == T::ZERO => value,
+        mask => (value & !(mask << offset)) | ((field & mask) << offset),
+    }
+}
+
+/// A mask of the low `width` bits, checking that the field fits at `offset`.
+fn field_mask<T: Unsigned>(offset: u32, width: u32) -> T {
+    assert!(
+        offset.checked_add(width).is_some_and(|end| end <= T::BITS),
+        "bit field {offset}..{} out of range of {} bits",
+        offset.saturating_add(width),
+        T::BITS
+    );
+    match width {
+        0 => T::ZERO,
+        width => !T::ZERO >> (T::BITS - width),
+    }
+}
+
+fn assert_power_of_two<T: Unsigned>(align: T) {
+    assert!(
+        align.is_power_of_two(),
+        "alignment must be a power of two, not {align:?}"
+    );
+}
+
+/// Number of bits in each word of a [`BitSet`].
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// A set of `usize` values stored as one bit each, growing to fit the largest one.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::bits::BitSet;
+///
+/// let mut even: BitSet = (0..10).step_by(2).collect();
+/// let small: BitSet = (0..4).collect();
+/// assert!(even.test(4) && !even.test(5));
+/// assert_eq!((&even & &small).iter().collect::<Vec<_>>(), [0, 2]);
+/// even.clear(0);
+/// assert_eq!(even.count(), 4);
+/// ```
+#[derive(Clone, Default)]
+#[must_use]
+pub struct BitSet {
+    words: Vec<u64>,
+}
+
+impl BitSet {
+    /// Creates an empty set.
+    pub const fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    /// Creates an empty set with room for values up to `bits` before it reallocates.
+    pub fn with_capacity(bits: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(bits.div_ceil(WORD_BITS)),
+        }
+    }
+
+    /// Number of values the set holds before it reallocates.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.words.capacity() * WORD_BITS
+    }
+
+    /// Adds `index`, returning whether it was new.
+    #[allow(clippy::must_use_candidate)]
+    pub fn set(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / WORD_BITS, 1 << (index % WORD_BITS));
+        if word >= self.words.len() {
+            self.words.resize(word + 1, 0);
+        }
+        let was_set = self.words[word] & bit != 0;
+        self.words[word] |= bit;
+        !was_set
+    }
+
+    /// Removes `index`, returning whether it was in the set.
+    #[allow(clippy::must_use_candidate)]
+    pub fn clear(&mut self, index: usize) -> bool {
+        let (word, bit) = (index / WORD_BITS, 1 << (index % WORD_BITS));
+        let Some(word) = self.words.get_mut(word) else {
+            return false;
+        };
+        let was_set = *word & bit != 0;
+        *word &= !bit;
+        was_set
+    }
+
+    /// Adds `index` if it isn't in the set, or removes it if it is, returning whether it's now in
+    /// the set.
+    #[allow(clippy::must_use_candidate)]
+    pub fn toggle(&mut self, index: usize) -> bool {
+        if self.clear(index) {
+            false
+        } else {
+            self.set(index)
+        }
+    }
+
+    /// Whether `index` is in the set.
+    #[must_use]
+    pub fn test(&self, index: usize) -> bool {
+        self.words
+            .get(index / WORD_BITS)
+            .is_some_and(|word| word & (1 << (index % WORD_BITS)) != 0)
+    }
+
+    /// Number of values in the set.
+    #[must_use]
+    pub fn count(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Whether the set has no values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|&word| word == 0)
+    }
+
+    /// Removes every value, keeping the memory.
+    pub fn reset(&mut self) {
+        self.words.clear();
+    }
+
+    /// Iterates over the values in ascending order.
+    #[must_use]
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            words: &self.words,
+            word: 0,
+            bits: self.words.first().copied().unwrap_or(0),
+        }
+    }
+
+    /// Adds every value of `other`.
+    pub fn union_with(&mut self, other: &BitSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    /// Removes every value not in `other`.
+    pub fn intersect_with(&mut self, other: &BitSet) {
+        self.words.truncate(other.words.len());
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word &= other;
+        }
+    }
+
+    /// Keeps the values in exactly one of `self` and `other`.
+    pub fn symmetric_difference_with(&mut self, other: &BitSet) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), 0);
+        }
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word ^= other;
+        }
+    }
+
+    /// Removes every value of `other`.
+    pub fn difference_with(&mut self, other: &BitSet) {
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word &= !other;
+        }
+    }
+
+    /// Whether every value of `self` is in `other`.
+    #[must_use]
+    pub fn is_subset(&self, other: &BitSet) -> bool {
+        self.words
+            .iter()
+            .enumerate()
+            .all(|(index, word)| word & !other.words.get(index).copied().unwrap_or(0) == 0)
+    }
+
+    /// The words of the set without trailing zero words, so equal sets compare equal.
+    fn trimmed(&self) -> &[u64] {
+        let len = self
+            .words
+            .iter()
+            .rposition(|&word| word != 0)
+            .map_or(0, |last| last + 1);
+        &self.words[..len]
+    }
+}
+
+impl PartialEq for BitSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.trimmed() == other.trimmed()
+    }
+}
+
+impl Eq for BitSet {}
+
+impl fmt::Debug for BitSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl Extend<usize> for BitSet {
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for index in iter {
+            self.set(index);
+        }
+    }
+}
+
+impl FromIterator<usize> for BitSet {
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<'a> IntoIterator for &'a BitSet {
+    type Item = usize;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Implements a set operator and its assigning form for bit sets.
+macro_rules! set_operator {
+    ($trait:ident, $method:ident, $assign_trait:ident, $assign_method:ident, $with:ident) => {
+        impl $trait for &BitSet {
+            type Output = BitSet;
+
+            fn $method(self, other: &BitSet) -> BitSet {
+                let mut set = self.clone();
+                set.$with(other);
+                set
+            }
+        }
+
+        impl $assign_trait<&BitSet> for BitSet {
+            fn $assign_method(&mut self, other: &BitSet) {
+                self.$with(other);
+            }
+        }
+    };
+}
+
+set_operator!(BitAnd, bitand, BitAndAssign, bitand_assign, intersect_with);
+set_operator!(BitOr, bitor, BitOrAssign, bitor_assign, union_with);
+set_operator!(
+    BitXor,
+    bitxor,
+    BitXorAssign,
+    bitxor_assign,
+    symmetric_difference_with
+);
+
+/// An iterator over the values of a [`BitSet`] in ascending order.
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    words: &'a [u64],
+    /// Index of the word `bits` came from.
+    word: usize,
+    /// Bits of the current word not yet returned.
+    bits: u64,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.bits == 0 {
+            self.word += 1;
+            self.bits = *self.words.get(self.word)?;
+        }
+        let bit = self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some(self.word * WORD_BITS + bit)
+    }
+}
+
+impl FusedIterator for Iter<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alignment() {
+        assert_eq!(next_power_of_two(0_u8), 1);
+        assert_eq!(next_power_of_two(129_u16), 256);
+        assert_eq!(align_up(0_u32, 16), 0);
+        assert_eq!(align_up(17_u32, 16), 32);
+        assert_eq!(checked_align_up(u8::MAX, 4), None);
+        assert_eq!(checked_align_up(250_u8, 4), Some(252));
+        assert_eq!(align_down(31_u64, 16), 16);
+        assert!(is_aligned(4096_usize, 4096) && !is_aligned(4097_usize, 4096));
+        assert_eq!(round_up(10_u32, 3), 12);
+        assert_eq!(round_up(9_u32, 3), 9);
+        assert_eq!(round_down(11_u128, 3), 9);
+    }
+
+    #[test]
+    #[should_panic = "alignment must be a power of two, not 12"]
+    fn invalid_alignment() {
+        align_up(5_usize, 12);
+    }
+
+    #[test]
+    fn bit_fields() {
+        assert_eq!(extract(0xabcd_u16, 4, 8), 0xbc);
+        assert_eq!(extract(0xabcd_u16, 0, 16), 0xabcd);
+        assert_eq!(extract(0xabcd_u16, 16, 0), 0);
+        assert_eq!(insert(0xabcd_u16, 4, 8, 0x12), 0xa12d);
+        assert_eq!(insert(0_u32, 28, 4, 0xff), 0xf000_0000);
+        assert_eq!(insert(u64::MAX, 0, 64, 0), 0);
+    }
+
+    #[test]
+    #[should_panic = "bit field 12..20 out of range of 16 bits"]
+    fn invalid_bit_field() {
+        extract(0_u16, 12, 8);
+    }
+
+    #[test]
+    fn bit_sets() {
+        let mut set = BitSet::new();
+        assert!(set.is_empty());
+        assert!(set.set(3) && !set.set(3));
+        assert!(set.set(64) && set.set(200));
+        assert!(set.test(64) && !set.test(65) && !set.test(10_000));
+        assert_eq!(set.count(), 3);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [3, 64, 200]);
+        assert_eq!(format!("{set:?}"), "{3, 64, 200}");
+        assert!(set.clear(200) && !set.clear(200) && !set.clear(10_000));
+        assert!(!set.toggle(3) && set.toggle(3));
+        assert_eq!(set, [3, 64].into_iter().collect());
+
+        let other: BitSet = [1, 3, 500].into_iter().collect();
+        assert_eq!((&set | &other).iter().collect::<Vec<_>>(), [1, 3, 64, 500]);
+        assert_eq!((&set & &other).iter().collect::<Vec<_>>(), [3]);
+        assert_eq!((&set ^ &other).iter().collect::<Vec<_>>(), [1, 64, 500]);
+        let mut difference = set.clone();
+        difference.difference_with(&other);
+        assert_eq!(difference.iter().collect::<Vec<_>>(), [64]);
+        assert!(difference.is_subset(&set) && !set.is_subset(&other));
+
+        set &= &other;
+        set |= &BitSet::from_iter([7]);
+        set ^= &BitSet::from_iter([3]);
+        assert_eq!(set.iter().collect::<Vec<_>>(), [7]);
+        set.reset();
+        assert!(set.is_empty() && set.iter().next().is_none());
+    }
+}
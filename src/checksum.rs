@@ -0,0 +1,397 @@
+//! Checksums for validating data.
+//!
+//! [`Crc32`] computes the IEEE CRC-32 of zip, gzip and PNG, or the Castagnoli CRC-32C of iSCSI and
+//! ext4, and [`Adler32`] the cheaper, weaker checksum of zlib. Each is fed incrementally with
+//! `update`, or as a writer, e.g. with `io::copy`. CRC-32C uses the SSE4.2 `crc32` instruction on
+//! `x86_64`, and both CRCs use the CRC instructions on `aarch64`, when the CPU has them.
+
+use std::io::{self, Write};
+
+/// Reversed polynomial of the IEEE CRC-32.
+const IEEE: u32 = 0xedb8_8320;
+/// Reversed polynomial of the Castagnoli CRC-32C.
+const CASTAGNOLI: u32 = 0x82f6_3b78;
+
+/// Tables for computing a CRC 8 bytes at a time: entry `i` of table `n` is the CRC of byte `i`
+/// followed by `n` zero bytes.
+type Tables = [[u32; 256]; 8];
+
+#[allow(clippy::cast_possible_truncation)]
+const fn tables(polynomial: u32) -> Tables {
+    let mut tables = [[0; 256]; 8];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ polynomial
+            };
+            bit += 1;
+        }
+        tables[0][i] = crc;
+        i += 1;
+    }
+    let mut i = 0;
+    while i < 256 {
+        let mut n = 1;
+        while n < 8 {
+            let previous = tables[n - 1][i];
+            tables[n][i] = (previous >> 8) ^ tables[0][(previous & 0xff) as usize];
+            n += 1;
+        }
+        i += 1;
+    }
+    tables
+}
+
+static IEEE_TABLES: Tables = tables(IEEE);
+static CASTAGNOLI_TABLES: Tables = tables(CASTAGNOLI);
+
+/// Returns the IEEE CRC-32 of `bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::checksum::crc32;
+///
+/// assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+/// ```
+#[must_use]
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// Returns the Castagnoli CRC-32C of `bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::checksum::crc32c;
+///
+/// assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+/// ```
+#[must_use]
+pub fn crc32c(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::castagnoli();
+    crc.update(bytes);
+    crc.finish()
+}
+
+/// Returns the Adler-32 checksum of `bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::checksum::adler32;
+///
+/// assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+/// ```
+#[must_use]
+pub fn adler32(bytes: &[u8]) -> u32 {
+    let mut adler = Adler32::new();
+    adler.update(bytes);
+    adler.finish()
+}
+
+/// An incremental CRC-32, IEEE unless created with [`castagnoli`](Self::castagnoli).
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::checksum::{crc32, Crc32};
+///
+/// let mut crc = Crc32::new();
+/// crc.update(b"1234");
+/// crc.update(b"56789");
+/// assert_eq!(crc.finish(), crc32(b"123456789"));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Crc32 {
+    /// Complement of the CRC so far.
+    state: u32,
+    castagnoli: bool,
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Crc32 {
+    /// Creates an IEEE CRC-32 of no bytes.
+    pub const fn new() -> Self {
+        Self {
+            state: !0,
+            castagnoli: false,
+        }
+    }
+
+    /// Creates a Castagnoli CRC-32C of no bytes.
+    pub const fn castagnoli() -> Self {
+        Self {
+            state: !0,
+            castagnoli: true,
+        }
+    }
+
+    /// Adds `bytes` to the checksum.
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.state = if self.castagnoli {
+            update_castagnoli(self.state, bytes)
+        } else {
+            update_ieee(self.state, bytes)
+        };
+    }
+
+    /// Returns the CRC of every byte added so far.
+    #[must_use]
+    pub const fn finish(&self) -> u32 {
+        !self.state
+    }
+
+    /// Forgets every byte added so far.
+    pub fn reset(&mut self) {
+        self.state = !0;
+    }
+}
+
+impl Write for Crc32 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn update_ieee(state: u32, bytes: &[u8]) -> u32 {
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("crc") {
+        // SAFETY: the CPU supports the CRC instructions.
+        return unsafe { arm::ieee(state, bytes) };
+    }
+    update_tables(state, &IEEE_TABLES, bytes)
+}
+
+fn update_castagnoli(state: u32, bytes: &[u8]) -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    if std::arch::is_x86_feature_detected!("sse4.2") {
+        // SAFETY: the CPU supports SSE4.2.
+        return unsafe { x86::castagnoli(state, bytes) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    if std::arch::is_aarch64_feature_detected!("crc") {
+        // SAFETY: the CPU supports the CRC instructions.
+        return unsafe { arm::castagnoli(state, bytes) };
+    }
+    update_tables(state, &CASTAGNOLI_TABLES, bytes)
+}
+
+/// Updates `state` with `bytes` 8 at a time through `tables`.
+fn update_tables(mut state: u32, tables: &Tables, bytes: &[u8]) -> u32 {
+    let entry =
+        |table: usize, value: u32, shift: u32| tables[table][((value >> shift) & 0xff) as usize];
+    let mut chunks = bytes.chunks_exact(8);
+    for chunk in &mut chunks {
+        let low = state ^ u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let high = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+        state = entry(7, low, 0)
+            ^ entry(6, low, 8)
+            ^ entry(5, low, 16)
+            ^ entry(4, low, 24)
+            ^ entry(3, high, 0)
+            ^ entry(2, high, 8)
+            ^ entry(1, high, 16)
+            ^ entry(0, high, 24);
+    }
+    for &byte in chunks.remainder() {
+        state = (state >> 8) ^ entry(0, state ^ u32::from(byte), 0);
+    }
+    state
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::{_mm_crc32_u64, _mm_crc32_u8};
+
+    #[target_feature(enable = "sse4.2")]
+    #[allow(clippy::cast_possible_truncation)]
+    pub(super) unsafe fn castagnoli(state: u32, bytes: &[u8]) -> u32 {
+        let mut chunks = bytes.chunks_exact(8);
+        let mut wide = u64::from(state);
+        for chunk in &mut chunks {
+            wide = _mm_crc32_u64(wide, u64::from_le_bytes(chunk.try_into().expect("8 bytes")));
+        }
+        let mut state = wide as u32;
+        for &byte in chunks.remainder() {
+            state = _mm_crc32_u8(state, byte);
+        }
+        state
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod arm {
+    use std::arch::aarch64::{__crc32b, __crc32cb, __crc32cd, __crc32d};
+
+    #[target_feature(enable = "crc")]
+    pub(super) unsafe fn ieee(mut state: u32, bytes: &[u8]) -> u32 {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            state = __crc32d(
+                state,
+                u64::from_le_bytes(chunk.try_into().expect("8 bytes")),
+            );
+        }
+        for &byte in chunks.remainder() {
+            state = __crc32b(state, byte);
+        }
+        state
+    }
+
+    #[target_feature(enable = "crc")]
+    pub(super) unsafe fn castagnoli(mut state: u32, bytes: &[u8]) -> u32 {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            state = __crc32cd(
+                state,
+                u64::from_le_bytes(chunk.try_into().expect("8 bytes")),
+            );
+        }
+        for &byte in chunks.remainder() {
+            state = __crc32cb(state, byte);
+        }
+        state
+    }
+}
+
+/// Modulus of the Adler-32 sums.
+const ADLER_MODULUS: u32 = 65_521;
+/// Most bytes summed before the sums may overflow a `u32`.
+const ADLER_BLOCK: usize = 5552;
+
+/// An incremental Adler-32 checksum.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use util_lib_rs::checksum::{adler32, Adler32};
+///
+/// let mut adler = Adler32::new();
+/// write!(adler, "Wiki{}", "pedia").unwrap();
+/// assert_eq!(adler.finish(), adler32(b"Wikipedia"));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Adler32 {
+    a: u32,
+    b: u32,
+}
+
+impl Default for Adler32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Adler32 {
+    /// Creates a checksum of no bytes.
+    pub const fn new() -> Self {
+        Self { a: 1, b: 0 }
+    }
+
+    /// Adds `bytes` to the checksum.
+    pub fn update(&mut self, bytes: &[u8]) {
+        for block in bytes.chunks(ADLER_BLOCK) {
+            for &byte in block {
+                self.a += u32::from(byte);
+                self.b += self.a;
+            }
+            self.a %= ADLER_MODULUS;
+            self.b %= ADLER_MODULUS;
+        }
+    }
+
+    /// Returns the checksum of every byte added so far.
+    #[must_use]
+    pub const fn finish(&self) -> u32 {
+        (self.b << 16) | self.a
+    }
+
+    /// Forgets every byte added so far.
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+impl Write for Adler32 {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc_vectors() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(
+            crc32(b"The quick brown fox jumps over the lazy dog"),
+            0x414f_a339
+        );
+        assert_eq!(crc32c(b""), 0);
+        assert_eq!(crc32c(&[0; 32]), 0x8a91_36aa);
+        assert_eq!(crc32c(&[0xff; 32]), 0x62a8_ab43);
+
+        let bytes: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 251) as u8).collect();
+        for (tables, start) in [
+            (&IEEE_TABLES, Crc32::new()),
+            (&CASTAGNOLI_TABLES, Crc32::castagnoli()),
+        ] {
+            let mut crc = start;
+            for chunk in bytes.chunks(13) {
+                crc.update(chunk);
+            }
+            assert_eq!(crc.finish(), !update_tables(!0, tables, &bytes));
+            crc.reset();
+            crc.update(b"123456789");
+            assert!([0xcbf4_3926, 0xe306_9283].contains(&crc.finish()));
+        }
+    }
+
+    #[test]
+    fn adler_vectors() {
+        assert_eq!(adler32(b""), 1);
+        let bytes = vec![0xff; 100_000];
+        let mut adler = Adler32::new();
+        for chunk in bytes.chunks(9999) {
+            adler.update(chunk);
+        }
+        let (mut a, mut b) = (1u64, 0u64);
+        for &byte in &bytes {
+            a = (a + u64::from(byte)) % 65_521;
+            b = (b + a) % 65_521;
+        }
+        assert_eq!(u64::from(adler.finish()), (b << 16) | a);
+        std::io::copy(&mut &b"more"[..], &mut adler).expect("valid copy");
+        adler.reset();
+        assert_eq!(adler.finish(), 1);
+    }
+}
@@ -0,0 +1,496 @@
+//! Command line argument parsing.
+//!
+//! [`Args`] declares a tool's flags, options and positional arguments, parses them and generates
+//! the `--help` text, with typed getters for the values, for tools which only need a few flags.
+//! Options accept their value as `--name value`, `--name=value` or `-n value`, and `--` ends the
+//! options, so every later argument is positional. A mistyped long option is reported with the
+//! closest declared one as a suggestion.
+
+use crate::str_util::closest_match;
+use std::{error::Error, fmt, fmt::Write as _, str::FromStr};
+
+/// A declared flag or option.
+#[derive(Debug, Clone)]
+struct OptionSpec {
+    long: &'static str,
+    short: Option<char>,
+    /// Name of the value shown in the help, or `None` for flags, which take no value.
+    value_name: Option<&'static str>,
+    help: &'static str,
+}
+
+impl OptionSpec {
+    /// Left column of the option's line in the help.
+    fn usage(&self) -> String {
+        let mut usage = match self.short {
+            Some(short) => format!("-{short}, --{}", self.long),
+            None => format!("--{}", self.long),
+        };
+        if let Some(value_name) = self.value_name {
+            let _ = write!(usage, " <{value_name}>");
+        }
+        usage
+    }
+}
+
+/// A declared positional argument.
+#[derive(Debug, Clone)]
+struct PositionalSpec {
+    name: &'static str,
+    help: &'static str,
+    required: bool,
+}
+
+impl PositionalSpec {
+    fn usage(&self) -> String {
+        if self.required {
+            format!("<{}>", self.name)
+        } else {
+            format!("[{}]", self.name)
+        }
+    }
+}
+
+/// Why parsing or reading arguments failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgsError {
+    /// `-h` or `--help` was passed. Holds the help text.
+    Help(String),
+    /// The arguments were invalid, such as an unknown option or a value which doesn't parse.
+    Invalid(String),
+}
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Help(help) => f.write_str(help),
+            Self::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl Error for ArgsError {}
+
+/// Declared and parsed command line arguments.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::cli::Args;
+///
+/// # fn main() -> Result<(), util_lib_rs::cli::ArgsError> {
+/// let args = Args::new("bench")
+///     .about("Runs the benchmarks.")
+///     .flag("verbose", Some('v'), "Print every run")
+///     .option("threads", Some('t'), "COUNT", "Number of threads to run on")
+///     .positional("FILTER", "Only run benchmarks containing FILTER")
+///     .parse(["-v", "--threads=4", "parse"])?;
+/// assert!(args.is_set("verbose"));
+/// assert_eq!(args.get::<u64>("threads")?, Some(4));
+/// assert_eq!(args.value("FILTER"), Some("parse"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+#[must_use]
+pub struct Args {
+    name: &'static str,
+    about: Option<&'static str>,
+    options: Vec<OptionSpec>,
+    positional_specs: Vec<PositionalSpec>,
+    /// Long name of each flag passed.
+    flags: Vec<&'static str>,
+    /// Long name and value of each option passed, in order.
+    values: Vec<(&'static str, String)>,
+    positionals: Vec<String>,
+}
+
+impl Args {
+    /// Declares the arguments of the tool `name`, with only `-h` and `--help`.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the description shown in the help.
+    pub fn about(mut self, about: &'static str) -> Self {
+        self.about = Some(about);
+        self
+    }
+
+    /// Declares the flag `--long`, also written `-short` if given.
+    pub fn flag(mut self, long: &'static str, short: Option<char>, help: &'static str) -> Self {
+        self.options.push(OptionSpec {
+            long,
+            short,
+            value_name: None,
+            help,
+        });
+        self
+    }
+
+    /// Declares the option `--long` taking a value shown as `value_name` in the help, also written
+    /// `-short` if given. Options may be passed more than once.
+    pub fn option(
+        mut self,
+        long: &'static str,
+        short: Option<char>,
+        value_name: &'static str,
+        help: &'static str,
+    ) -> Self {
+        self.options.push(OptionSpec {
+            long,
+            short,
+            value_name: Some(value_name),
+            help,
+        });
+        self
+    }
+
+    /// Declares the required positional argument `name`, after any declared before it.
+    pub fn positional(mut self, name: &'static str, help: &'static str) -> Self {
+        self.positional_specs.push(PositionalSpec {
+            name,
+            help,
+            required: true,
+        });
+        self
+    }
+
+    /// Declares the optional positional argument `name`, after any declared before it. Optional
+    /// positional arguments should follow every required one.
+    pub fn optional_positional(mut self, name: &'static str, help: &'static str) -> Self {
+        self.positional_specs.push(PositionalSpec {
+            name,
+            help,
+            required: false,
+        });
+        self
+    }
+
+    /// Parses `args`, which shouldn't include the program name.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ArgsError::Help`] if `-h` or `--help` is passed, or [`ArgsError::Invalid`] for
+    /// unknown options, options missing their value, flags given a value, and missing or extra
+    /// positional arguments.
+    pub fn parse<I>(mut self, args: I) -> Result<Self, ArgsError>
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let invalid = |message: String| Err(ArgsError::Invalid(message));
+        let mut args = args.into_iter().map(Into::into);
+        let mut options_ended = false;
+        while let Some(arg) = args.next() {
+            if options_ended || arg == "-" || !arg.starts_with('-') {
+                self.positionals.push(arg);
+                continue;
+            }
+            if arg == "--" {
+                options_ended = true;
+                continue;
+            }
+            if arg == "-h" || arg == "--help" {
+                return Err(ArgsError::Help(self.help()));
+            }
+
+            let (name, inline_value) = match arg.strip_prefix("--") {
+                Some(long) => match long.split_once('=') {
+                    Some((long, value)) => (long, Some(value.to_string())),
+                    None => (long, None),
+                },
+                None => (&arg[1..], None),
+            };
+            let spec = self.options.iter().find(|spec| {
+                if arg.starts_with("--") {
+                    spec.long == name
+                } else {
+                    let mut chars = name.chars();
+                    spec.short.is_some() && chars.next() == spec.short && chars.next().is_none()
+                }
+            });
+            let Some(spec) = spec else {
+                let longs = self.options.iter().map(|spec| spec.long);
+                return invalid(match closest_match(name, longs.chain(["help"])) {
+                    Some(long) if arg.starts_with("--") => {
+                        format!("unknown option: {arg}, did you mean --{long}?")
+                    }
+                    _ => format!("unknown option: {arg}"),
+                });
+            };
+            let long = spec.long;
+            if spec.value_name.is_none() {
+                if inline_value.is_some() {
+                    return invalid(format!("--{long} doesn't take a value"));
+                }
+                self.flags.push(long);
+            } else {
+                let Some(value) = inline_value.or_else(|| args.next()) else {
+                    return invalid(format!("--{long} needs a value"));
+                };
+                self.values.push((long, value));
+            }
+        }
+
+        if let Some(missing) = self
+            .positional_specs
+            .iter()
+            .filter(|spec| spec.required)
+            .nth(self.positionals.len())
+        {
+            return invalid(format!("missing {}", missing.usage()));
+        }
+        if let Some(extra) = self.positionals.get(self.positional_specs.len()) {
+            return invalid(format!("unexpected argument: {extra}"));
+        }
+        Ok(self)
+    }
+
+    /// Parses the arguments of the current process. Prints the help and exits successfully if
+    /// it's requested, or prints the error and the help and exits with status 2 if the arguments
+    /// are invalid.
+    pub fn parse_env(self) -> Self {
+        let name = self.name;
+        match self.parse(std::env::args().skip(1)) {
+            Ok(args) => args,
+            Err(ArgsError::Help(help)) => {
+                println!("{help}");
+                std::process::exit(0);
+            }
+            Err(ArgsError::Invalid(message)) => {
+                eprintln!("{name}: {message}\n\nRun with --help for usage.");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// Whether the flag `long` was passed.
+    #[must_use]
+    pub fn is_set(&self, long: &str) -> bool {
+        self.flags.contains(&long)
+    }
+
+    /// Returns the last value of the option `long`, or the positional argument `name`.
+    #[must_use]
+    pub fn value(&self, name: &str) -> Option<&str> {
+        if let Some(index) = self
+            .positional_specs
+            .iter()
+            .position(|spec| spec.name == name)
+        {
+            return self.positionals.get(index).map(String::as_str);
+        }
+        self.values
+            .iter()
+            .rev()
+            .find(|(long, _)| *long == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns every value of the option `long`, in the order passed.
+    pub fn values_of<'a>(&'a self, long: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.values
+            .iter()
+            .filter(move |(name, _)| *name == long)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Parses the value returned by [`value`](Args::value) as a `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ArgsError::Invalid`] if the value doesn't parse.
+    pub fn get<T>(&self, name: &str) -> Result<Option<T>, ArgsError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.value(name)
+            .map(|value| parse_value(name, value))
+            .transpose()
+    }
+
+    /// Parses every value of the option `long` as a `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ArgsError::Invalid`] if any value doesn't parse.
+    pub fn get_all<T>(&self, long: &str) -> Result<Vec<T>, ArgsError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.values_of(long)
+            .map(|value| parse_value(long, value))
+            .collect()
+    }
+
+    /// Every positional argument, in order.
+    #[must_use]
+    pub fn positionals(&self) -> &[String] {
+        &self.positionals
+    }
+
+    /// Returns the help text, listing every declared argument.
+    #[must_use]
+    pub fn help(&self) -> String {
+        let mut help = format!("Usage: {}", self.name);
+        help.push_str(" [OPTIONS]");
+        for spec in &self.positional_specs {
+            help.push(' ');
+            help.push_str(&spec.usage());
+        }
+        help.push('\n');
+        if let Some(about) = self.about {
+            let _ = writeln!(help, "\n{about}");
+        }
+
+        let positionals: Vec<_> = self
+            .positional_specs
+            .iter()
+            .map(|spec| (spec.usage(), spec.help))
+            .collect();
+        let options: Vec<_> = self
+            .options
+            .iter()
+            .map(|spec| (spec.usage(), spec.help))
+            .chain(std::iter::once((
+                "-h, --help".to_string(),
+                "Print this help",
+            )))
+            .collect();
+        let width = positionals
+            .iter()
+            .chain(&options)
+            .map(|(usage, _)| usage.chars().count())
+            .max()
+            .unwrap_or(0);
+        for (title, lines) in [("Arguments", positionals), ("Options", options)] {
+            if lines.is_empty() {
+                continue;
+            }
+            let _ = writeln!(help, "\n{title}:");
+            for (usage, text) in lines {
+                let _ = writeln!(help, "  {usage:width$}  {text}");
+            }
+        }
+        help.pop();
+        help
+    }
+}
+
+fn parse_value<T>(name: &str, value: &str) -> Result<T, ArgsError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|err| ArgsError::Invalid(format!("invalid {name} `{value}`: {err}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args() -> Args {
+        Args::new("tool")
+            .about("Does things.")
+            .flag("verbose", Some('v'), "Print more")
+            .option("threads", Some('t'), "COUNT", "Threads to use")
+            .option("include", None, "PATTERN", "Keep matches")
+            .positional("INPUT", "File to read")
+            .optional_positional("OUTPUT", "File to write")
+    }
+
+    #[test]
+    fn parsed_arguments() {
+        let parsed = args()
+            .parse([
+                "in.txt",
+                "-v",
+                "--include=a",
+                "-t",
+                "8",
+                "--include",
+                "b",
+                "--",
+                "-out",
+            ])
+            .expect("valid args");
+        assert!(parsed.is_set("verbose"));
+        assert_eq!(parsed.get::<u64>("threads"), Ok(Some(8)));
+        assert_eq!(
+            parsed.get_all::<String>("include"),
+            Ok(vec!["a".into(), "b".into()])
+        );
+        assert_eq!(parsed.value("INPUT"), Some("in.txt"));
+        assert_eq!(parsed.value("OUTPUT"), Some("-out"));
+        assert_eq!(parsed.positionals(), ["in.txt", "-out"]);
+
+        let parsed = args().parse(["in.txt"]).expect("valid args");
+        assert!(!parsed.is_set("verbose"));
+        assert_eq!(parsed.get::<u64>("threads"), Ok(None));
+        assert_eq!(parsed.value("OUTPUT"), None);
+    }
+
+    #[test]
+    fn invalid_arguments() {
+        let invalid = |args: &[&str]| match super::tests::args().parse(args.iter().copied()) {
+            Err(ArgsError::Invalid(message)) => message,
+            other => panic!("expected an error, got {other:?}"),
+        };
+        assert_eq!(invalid(&["in", "--fast"]), "unknown option: --fast");
+        assert_eq!(invalid(&["in", "-vt"]), "unknown option: -vt");
+        assert_eq!(
+            invalid(&["in", "--thread=2"]),
+            "unknown option: --thread=2, did you mean --threads?"
+        );
+        assert_eq!(
+            invalid(&["in", "--hlp"]),
+            "unknown option: --hlp, did you mean --help?"
+        );
+        assert_eq!(invalid(&["in", "--threads"]), "--threads needs a value");
+        assert_eq!(
+            invalid(&["in", "--verbose=1"]),
+            "--verbose doesn't take a value"
+        );
+        assert_eq!(invalid(&[]), "missing <INPUT>");
+        assert_eq!(invalid(&["a", "b", "c"]), "unexpected argument: c");
+
+        let parsed = args().parse(["in", "-t", "many"]).expect("valid args");
+        assert_eq!(
+            parsed.get::<u64>("threads"),
+            Err(ArgsError::Invalid(
+                "invalid threads `many`: invalid digit found in string".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn help_text() {
+        let Err(ArgsError::Help(help)) = args().parse(["--help"]) else {
+            panic!("expected help");
+        };
+        assert_eq!(
+            help,
+            "Usage: tool [OPTIONS] <INPUT> [OUTPUT]\n\
+             \n\
+             Does things.\n\
+             \n\
+             Arguments:\n  \
+             <INPUT>                File to read\n  \
+             [OUTPUT]               File to write\n\
+             \n\
+             Options:\n  \
+             -v, --verbose          Print more\n  \
+             -t, --threads <COUNT>  Threads to use\n  \
+             --include <PATTERN>    Keep matches\n  \
+             -h, --help             Print this help"
+        );
+    }
+}
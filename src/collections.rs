@@ -0,0 +1,1118 @@
+//! Fixed-capacity collections.
+//!
+//! A [`RingBuffer`] stores up to `N` values inline, without heap allocation, for hot paths where a
+//! growing `VecDeque` would be unacceptable, such as keeping the last few samples of a measurement.
+//! An [`InlineVec`] stores its first `N` values inline too, moving to the heap only once it
+//! outgrows them. An [`LruCache`] keeps the most recently used entries of a map up to a fixed
+//! capacity. A [`Slab`] stores values under small integer keys which it reuses once they're
+//! removed, and a [`GenSlab`] detects keys to values which have since been removed.
+
+mod slab;
+
+pub use slab::{GenKey, GenSlab, Slab};
+
+use std::{
+    borrow::Borrow, collections::HashMap, fmt, hash::Hash, iter::FusedIterator, mem::MaybeUninit,
+};
+
+/// A first-in first-out queue of up to `N` values stored inline.
+///
+/// [`push`](RingBuffer::push) rejects values once the buffer is full, while
+/// [`force_push`](RingBuffer::force_push) overwrites the oldest value instead, keeping the most
+/// recent `N`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::collections::RingBuffer;
+///
+/// let mut recent: RingBuffer<u32, 3> = RingBuffer::new();
+/// for value in 1..=5 {
+///     recent.force_push(value);
+/// }
+/// assert_eq!(recent.iter().copied().collect::<Vec<_>>(), [3, 4, 5]);
+/// assert_eq!(recent.push(6), Err(6));
+/// assert_eq!(recent.pop(), Some(3));
+/// ```
+pub struct RingBuffer<T, const N: usize> {
+    slots: [MaybeUninit<T>; N],
+    /// Index of the oldest value.
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    /// Creates an empty buffer.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { MaybeUninit::uninit() }; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Maximum number of values the buffer holds.
+    #[must_use]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Number of values in the buffer.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the buffer holds no values.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the buffer holds `N` values.
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    /// Index of the slot holding the value `offset` places after the oldest.
+    const fn slot(&self, offset: usize) -> usize {
+        (self.head + offset) % N
+    }
+
+    /// Appends `value` as the newest value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `value` back if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        if self.is_full() {
+            return Err(value);
+        }
+        let slot = self.slot(self.len);
+        self.slots[slot].write(value);
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Appends `value` as the newest value, returning the oldest value it overwrote if the buffer
+    /// was full. A buffer with no capacity returns `value` itself.
+    pub fn force_push(&mut self, value: T) -> Option<T> {
+        if N == 0 {
+            return Some(value);
+        }
+        if !self.is_full() {
+            let _ = self.push(value);
+            return None;
+        }
+        // SAFETY: A full buffer has a value in every slot, including the oldest at `head`.
+        let oldest = unsafe {
+            std::mem::replace(&mut self.slots[self.head], MaybeUninit::new(value)).assume_init()
+        };
+        self.head = self.slot(1);
+        Some(oldest)
+    }
+
+    /// Removes and returns the oldest value.
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        // SAFETY: A non-empty buffer holds its oldest value at `head`, which is no longer counted
+        // as initialized afterwards.
+        let value = unsafe { self.slots[self.head].assume_init_read() };
+        self.head = self.slot(1);
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Returns the value `index` places after the oldest.
+    #[must_use]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        // SAFETY: The `len` values from `head` are initialized.
+        (index < self.len).then(|| unsafe { self.slots[self.slot(index)].assume_init_ref() })
+    }
+
+    /// Returns the oldest value.
+    #[must_use]
+    pub fn front(&self) -> Option<&T> {
+        self.get(0)
+    }
+
+    /// Returns the newest value.
+    #[must_use]
+    pub fn back(&self) -> Option<&T> {
+        self.get(self.len.checked_sub(1)?)
+    }
+
+    /// Drops every value.
+    pub fn clear(&mut self) {
+        while self.pop().is_some() {}
+        self.head = 0;
+    }
+
+    /// Returns an iterator over the values, oldest first.
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter {
+            buffer: self,
+            front: 0,
+            back: self.len,
+        }
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for RingBuffer<T, N> {
+    fn clone(&self) -> Self {
+        let mut clone = Self::new();
+        for value in self {
+            let _ = clone.push(value.clone());
+        }
+        clone
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for RingBuffer<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for RingBuffer<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other)
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for RingBuffer<T, N> {}
+
+impl<'a, T, const N: usize> IntoIterator for &'a RingBuffer<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the values of a [`RingBuffer`], oldest first.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Iter<'a, T, const N: usize> {
+    buffer: &'a RingBuffer<T, N>,
+    /// Offsets from the oldest value of the next values returned from each end.
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.buffer.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Iter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.buffer.get(self.back)
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for Iter<'_, T, N> {}
+
+impl<T, const N: usize> FusedIterator for Iter<'_, T, N> {}
+
+/// Index marking the end of the recency list of an [`LruCache`].
+const NIL: usize = usize::MAX;
+
+/// An entry of an [`LruCache`], linked to the entries used just before and after it.
+#[derive(Debug, Clone)]
+struct LruEntry<K, V> {
+    key: K,
+    value: V,
+    /// Index of the entry used more recently, or `NIL` for the most recent.
+    newer: usize,
+    /// Index of the entry used less recently, or `NIL` for the least recent.
+    older: usize,
+}
+
+/// Hits, misses and evictions of an [`LruCache`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct CacheStats {
+    /// Lookups with [`get`](LruCache::get) which found their key.
+    pub hits: u64,
+    /// Lookups with [`get`](LruCache::get) which didn't.
+    pub misses: u64,
+    /// Entries removed to make room for new ones.
+    pub evictions: u64,
+}
+
+impl CacheStats {
+    /// Fraction of lookups which hit, or 0 if there were none.
+    #[must_use]
+    pub fn hit_rate(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        match self.hits + self.misses {
+            0 => 0.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+/// A map of up to `capacity` entries which evicts the least recently used entry to make room.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::collections::LruCache;
+///
+/// let mut cache = LruCache::new(2);
+/// cache.put("a", 1);
+/// cache.put("b", 2);
+/// assert_eq!(cache.get(&"a"), Some(&1));
+/// // "b" is now the least recently used, so it's evicted.
+/// cache.put("c", 3);
+/// assert_eq!(cache.peek(&"b"), None);
+/// assert_eq!(cache.stats().hits, 1);
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct LruCache<K, V> {
+    indices: HashMap<K, usize>,
+    entries: Vec<LruEntry<K, V>>,
+    /// Index of the most recently used entry.
+    newest: usize,
+    /// Index of the least recently used entry.
+    oldest: usize,
+    capacity: usize,
+    stats: CacheStats,
+    /// Profiler counters hits and misses are added to, if any.
+    counters: Option<(&'static str, &'static str)>,
+}
+
+impl<K: Hash + Eq + Clone, V> LruCache<K, V> {
+    /// Creates an empty cache holding up to `capacity` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is 0.
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "invalid LRU cache capacity: 0");
+        Self {
+            indices: HashMap::new(),
+            entries: Vec::new(),
+            newest: NIL,
+            oldest: NIL,
+            capacity,
+            stats: CacheStats::default(),
+            counters: None,
+        }
+    }
+
+    /// Adds every hit and miss of [`get`](Self::get) to the profiler counters `hits` and `misses`,
+    /// as with `counter!`, so the report shows how well the cache works.
+    pub fn counters(mut self, hits: &'static str, misses: &'static str) -> Self {
+        self.counters = Some((hits, misses));
+        self
+    }
+
+    /// Maximum number of entries.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of entries.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Whether the cache has no entries.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Hits, misses and evictions so far.
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Whether `key` has an entry, without marking it as used.
+    #[must_use]
+    pub fn contains<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+    {
+        self.indices.contains_key(key)
+    }
+
+    /// Returns the value of `key`, marking it as the most recently used and counting a hit or miss.
+    pub fn get<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.get_mut(key).map(|value| &*value)
+    }
+
+    /// Returns the value of `key` for modification, as with [`get`](Self::get).
+    pub fn get_mut<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        let index = self.indices.get(key).copied();
+        if let Some((hits, misses)) = self.counters {
+            crate::counter!(if index.is_some() { hits } else { misses });
+        }
+        let Some(index) = index else {
+            self.stats.misses += 1;
+            return None;
+        };
+        self.stats.hits += 1;
+        self.unlink(index);
+        self.link_newest(index);
+        Some(&mut self.entries[index].value)
+    }
+
+    /// Returns the value of `key` without marking it as used or counting a hit or miss.
+    #[must_use]
+    pub fn peek<Q: Hash + Eq + ?Sized>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.indices
+            .get(key)
+            .map(|&index| &self.entries[index].value)
+    }
+
+    /// Sets the value of `key`, marking it as the most recently used, and returns its previous
+    /// value. If the cache is full, the least recently used entry is evicted to make room.
+    pub fn put(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(&index) = self.indices.get(&key) {
+            self.unlink(index);
+            self.link_newest(index);
+            return Some(std::mem::replace(&mut self.entries[index].value, value));
+        }
+        let entry = LruEntry {
+            key: key.clone(),
+            value,
+            newer: NIL,
+            older: NIL,
+        };
+        let index = if self.entries.len() < self.capacity {
+            self.entries.push(entry);
+            self.entries.len() - 1
+        } else {
+            // Reuses the slot of the least recently used entry.
+            let index = self.oldest;
+            self.unlink(index);
+            let evicted = std::mem::replace(&mut self.entries[index], entry);
+            self.indices.remove(&evicted.key);
+            self.stats.evictions += 1;
+            index
+        };
+        self.indices.insert(key, index);
+        self.link_newest(index);
+        None
+    }
+
+    /// Removes the entry of `key`, returning its value.
+    pub fn remove<Q: Hash + Eq + ?Sized>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let index = self.indices.remove(key)?;
+        self.unlink(index);
+        // Moves the last entry into the removed slot, so the slots stay contiguous.
+        let last = self.entries.len() - 1;
+        if index != last {
+            let (newer, older) = (self.entries[last].newer, self.entries[last].older);
+            self.relink(newer, older, index);
+            if let Some(moved) = self.indices.get_mut::<K>(&self.entries[last].key) {
+                *moved = index;
+            }
+        }
+        let entry = self.entries.swap_remove(index);
+        Some(entry.value)
+    }
+
+    /// Removes every entry, keeping the statistics.
+    pub fn clear(&mut self) {
+        self.indices.clear();
+        self.entries.clear();
+        self.newest = NIL;
+        self.oldest = NIL;
+    }
+
+    /// Returns an iterator over the entries, most recently used first.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> + '_ {
+        std::iter::successors(
+            (self.newest != NIL).then_some(self.newest),
+            |&index| match self.entries[index].older {
+                NIL => None,
+                older => Some(older),
+            },
+        )
+        .map(|index| (&self.entries[index].key, &self.entries[index].value))
+    }
+
+    /// Detaches the entry at `index` from the recency list.
+    fn unlink(&mut self, index: usize) {
+        let LruEntry { newer, older, .. } = self.entries[index];
+        match newer {
+            NIL => self.newest = older,
+            newer => self.entries[newer].older = older,
+        }
+        match older {
+            NIL => self.oldest = newer,
+            older => self.entries[older].newer = newer,
+        }
+    }
+
+    /// Attaches the detached entry at `index` as the most recently used.
+    fn link_newest(&mut self, index: usize) {
+        self.entries[index].newer = NIL;
+        self.entries[index].older = self.newest;
+        match self.newest {
+            NIL => self.oldest = index,
+            newest => self.entries[newest].newer = index,
+        }
+        self.newest = index;
+    }
+
+    /// Points the neighbours `newer` and `older` of an entry moving to the slot `to` at it.
+    fn relink(&mut self, newer: usize, older: usize, to: usize) {
+        match newer {
+            NIL => self.newest = to,
+            newer => self.entries[newer].older = to,
+        }
+        match older {
+            NIL => self.oldest = to,
+            older => self.entries[older].newer = to,
+        }
+    }
+}
+
+/// The values of an [`InlineVec`], inline until they outgrow it.
+enum InlineStorage<T, const N: usize> {
+    Inline {
+        values: [MaybeUninit<T>; N],
+        /// Number of initialized values at the start of `values`.
+        len: usize,
+    },
+    Heap(Vec<T>),
+}
+
+/// A vector storing up to `N` values inline, and moving them to the heap once it outgrows them.
+///
+/// It dereferences to a slice, so slice methods such as `iter`, `len`, `sort` and indexing work as
+/// for a `Vec`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::collections::InlineVec;
+///
+/// let mut path: InlineVec<&str, 4> = InlineVec::new();
+/// path.extend(["usr", "local", "bin"]);
+/// assert!(!path.spilled());
+/// path.push("tool");
+/// path.push("extra");
+/// assert!(path.spilled());
+/// assert_eq!(path.pop(), Some("extra"));
+/// assert_eq!(path.join("/"), "usr/local/bin/tool");
+/// ```
+pub struct InlineVec<T, const N: usize> {
+    storage: InlineStorage<T, N>,
+}
+
+impl<T, const N: usize> InlineVec<T, N> {
+    /// Creates an empty vector.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            storage: InlineStorage::Inline {
+                values: [const { MaybeUninit::uninit() }; N],
+                len: 0,
+            },
+        }
+    }
+
+    /// Creates an empty vector with room for `capacity` values, on the heap if more than `N`.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> Self {
+        if capacity <= N {
+            return Self::new();
+        }
+        Self {
+            storage: InlineStorage::Heap(Vec::with_capacity(capacity)),
+        }
+    }
+
+    /// Number of values.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            InlineStorage::Inline { len, .. } => *len,
+            InlineStorage::Heap(values) => values.len(),
+        }
+    }
+
+    /// Whether the vector holds no values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of values the vector holds without allocating.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        match &self.storage {
+            InlineStorage::Inline { .. } => N,
+            InlineStorage::Heap(values) => values.capacity(),
+        }
+    }
+
+    /// Whether the values have moved to the heap.
+    #[must_use]
+    pub fn spilled(&self) -> bool {
+        matches!(self.storage, InlineStorage::Heap(_))
+    }
+
+    /// Returns the values as a slice.
+    #[must_use]
+    pub fn as_slice(&self) -> &[T] {
+        match &self.storage {
+            // SAFETY: The first `len` values are initialized.
+            InlineStorage::Inline { values, len } => unsafe {
+                std::slice::from_raw_parts(values.as_ptr().cast(), *len)
+            },
+            InlineStorage::Heap(values) => values,
+        }
+    }
+
+    /// Returns the values as a mutable slice.
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> &mut [T] {
+        match &mut self.storage {
+            // SAFETY: The first `len` values are initialized.
+            InlineStorage::Inline { values, len } => unsafe {
+                std::slice::from_raw_parts_mut(values.as_mut_ptr().cast(), *len)
+            },
+            InlineStorage::Heap(values) => values,
+        }
+    }
+
+    /// Moves the inline values to a vector with room for `capacity` values.
+    fn spill(&mut self, capacity: usize) {
+        let InlineStorage::Inline { values, len } = &mut self.storage else {
+            return;
+        };
+        let mut heap = Vec::with_capacity(capacity.max(*len));
+        // SAFETY: The first `len` values are initialized, and are no longer counted as such once
+        // moved out.
+        heap.extend(
+            values[..*len]
+                .iter()
+                .map(|value| unsafe { value.assume_init_read() }),
+        );
+        *len = 0;
+        self.storage = InlineStorage::Heap(heap);
+    }
+
+    /// Makes room for at least `additional` more values.
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len().saturating_add(additional);
+        match &mut self.storage {
+            InlineStorage::Inline { .. } if needed > N => self.spill(needed),
+            InlineStorage::Inline { .. } => {}
+            InlineStorage::Heap(values) => values.reserve(additional),
+        }
+    }
+
+    /// Appends `value`, moving the values to the heap if the vector is full.
+    pub fn push(&mut self, value: T) {
+        if let InlineStorage::Inline { values, len } = &mut self.storage {
+            if *len < N {
+                values[*len].write(value);
+                *len += 1;
+                return;
+            }
+            self.spill(N.max(1) * 2);
+        }
+        if let InlineStorage::Heap(values) = &mut self.storage {
+            values.push(value);
+        }
+    }
+
+    /// Removes and returns the last value.
+    pub fn pop(&mut self) -> Option<T> {
+        match &mut self.storage {
+            InlineStorage::Inline { values, len } => {
+                *len = len.checked_sub(1)?;
+                // SAFETY: The value at the old last index is initialized, and no longer counted as
+                // such.
+                Some(unsafe { values[*len].assume_init_read() })
+            }
+            InlineStorage::Heap(values) => values.pop(),
+        }
+    }
+
+    /// Inserts `value` at `index`, shifting later values along.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is greater than the length.
+    pub fn insert(&mut self, index: usize, value: T) {
+        let len = self.len();
+        assert!(
+            index <= len,
+            "insertion index {index} out of bounds of {len}"
+        );
+        self.push(value);
+        self.as_mut_slice()[index..].rotate_right(1);
+    }
+
+    /// Removes and returns the value at `index`, shifting later values back.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index {index} out of bounds of {len}");
+        self.as_mut_slice()[index..].rotate_left(1);
+        self.pop().expect("valid last value")
+    }
+
+    /// Removes and returns the value at `index`, replacing it with the last value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn swap_remove(&mut self, index: usize) -> T {
+        let len = self.len();
+        assert!(index < len, "removal index {index} out of bounds of {len}");
+        self.as_mut_slice().swap(index, len - 1);
+        self.pop().expect("valid last value")
+    }
+
+    /// Drops the values after the first `len`.
+    pub fn truncate(&mut self, len: usize) {
+        while self.len() > len {
+            self.pop();
+        }
+    }
+
+    /// Drops every value, keeping the capacity.
+    pub fn clear(&mut self) {
+        self.truncate(0);
+    }
+
+    /// Returns the values as a `Vec`, allocating if they're inline.
+    #[must_use]
+    pub fn into_vec(mut self) -> Vec<T> {
+        self.spill(0);
+        match std::mem::take(&mut self.storage) {
+            InlineStorage::Heap(values) => values,
+            InlineStorage::Inline { .. } => Vec::new(),
+        }
+    }
+}
+
+impl<T, const N: usize> Default for InlineStorage<T, N> {
+    fn default() -> Self {
+        Self::Inline {
+            values: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+        }
+    }
+}
+
+impl<T, const N: usize> Default for InlineVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> Drop for InlineVec<T, N> {
+    fn drop(&mut self) {
+        if let InlineStorage::Inline { values, len } = &mut self.storage {
+            let initialized =
+                std::ptr::slice_from_raw_parts_mut(values.as_mut_ptr().cast::<T>(), *len);
+            *len = 0;
+            // SAFETY: The first `len` values were initialized, and are no longer counted as such.
+            unsafe { std::ptr::drop_in_place(initialized) };
+        }
+    }
+}
+
+impl<T, const N: usize> std::ops::Deref for InlineVec<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T, const N: usize> std::ops::DerefMut for InlineVec<T, N> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.as_mut_slice()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for InlineVec<T, N> {
+    fn clone(&self) -> Self {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for InlineVec<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for InlineVec<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl<T: Eq, const N: usize> Eq for InlineVec<T, N> {}
+
+impl<T, const N: usize> Extend<T> for InlineVec<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for InlineVec<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<T, const N: usize> From<Vec<T>> for InlineVec<T, N> {
+    fn from(values: Vec<T>) -> Self {
+        Self {
+            storage: InlineStorage::Heap(values),
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a InlineVec<T, N> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a mut InlineVec<T, N> {
+    type Item = &'a mut T;
+    type IntoIter = std::slice::IterMut<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+impl<T, const N: usize> IntoIterator for InlineVec<T, N> {
+    type Item = T;
+    type IntoIter = InlineIntoIter<T, N>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        // Leaves an empty vector behind, so dropping it drops nothing.
+        let inner = match std::mem::take(&mut self.storage) {
+            InlineStorage::Inline { values, len } => IntoIterInner::Inline {
+                values,
+                front: 0,
+                back: len,
+            },
+            InlineStorage::Heap(values) => IntoIterInner::Heap(values.into_iter()),
+        };
+        InlineIntoIter { inner }
+    }
+}
+
+/// Iterator moving the values out of an [`InlineVec`].
+#[must_use]
+pub struct InlineIntoIter<T, const N: usize> {
+    inner: IntoIterInner<T, N>,
+}
+
+enum IntoIterInner<T, const N: usize> {
+    Inline {
+        values: [MaybeUninit<T>; N],
+        /// Range of the values not yet returned.
+        front: usize,
+        back: usize,
+    },
+    Heap(std::vec::IntoIter<T>),
+}
+
+impl<T, const N: usize> Iterator for InlineIntoIter<T, N> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline {
+                values,
+                front,
+                back,
+            } => {
+                if front == back {
+                    return None;
+                }
+                *front += 1;
+                // SAFETY: The values in `front..back` are initialized, and this one is no longer
+                // counted as such.
+                Some(unsafe { values[*front - 1].assume_init_read() })
+            }
+            IntoIterInner::Heap(values) => values.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.inner {
+            IntoIterInner::Inline { front, back, .. } => (back - front, Some(back - front)),
+            IntoIterInner::Heap(values) => values.size_hint(),
+        }
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for InlineIntoIter<T, N> {
+    fn next_back(&mut self) -> Option<T> {
+        match &mut self.inner {
+            IntoIterInner::Inline {
+                values,
+                front,
+                back,
+            } => {
+                if front == back {
+                    return None;
+                }
+                *back -= 1;
+                // SAFETY: As in `next`.
+                Some(unsafe { values[*back].assume_init_read() })
+            }
+            IntoIterInner::Heap(values) => values.next_back(),
+        }
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for InlineIntoIter<T, N> {}
+
+impl<T, const N: usize> FusedIterator for InlineIntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for InlineIntoIter<T, N> {
+    fn drop(&mut self) {
+        for _ in self.by_ref() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn queue_order() {
+        let mut buffer: RingBuffer<u32, 4> = RingBuffer::new();
+        assert!(buffer.is_empty());
+        for value in 0..4 {
+            assert_eq!(buffer.push(value), Ok(()));
+        }
+        assert!(buffer.is_full());
+        assert_eq!(buffer.push(4), Err(4));
+        assert_eq!(buffer.pop(), Some(0));
+        assert_eq!(buffer.push(4), Ok(()));
+        assert_eq!(buffer.force_push(5), Some(1));
+        assert_eq!((buffer.front(), buffer.back()), (Some(&2), Some(&5)));
+        assert_eq!(buffer.iter().copied().collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert_eq!(
+            buffer.iter().rev().copied().collect::<Vec<_>>(),
+            [5, 4, 3, 2]
+        );
+        assert_eq!(buffer.iter().len(), 4);
+        assert_eq!(format!("{buffer:?}"), "[2, 3, 4, 5]");
+        assert_eq!(buffer.clone(), buffer);
+        assert_eq!(buffer.get(4), None);
+
+        buffer.clear();
+        assert_eq!(buffer.pop(), None);
+        assert_eq!(buffer.back(), None);
+
+        let mut empty: RingBuffer<u32, 0> = RingBuffer::default();
+        assert_eq!(empty.push(1), Err(1));
+        assert_eq!(empty.force_push(1), Some(1));
+    }
+
+    #[test]
+    fn drops_values() {
+        let value = Rc::new(());
+        {
+            let mut buffer: RingBuffer<Rc<()>, 3> = RingBuffer::new();
+            for _ in 0..5 {
+                buffer.force_push(Rc::clone(&value));
+            }
+            assert_eq!(Rc::strong_count(&value), 4);
+            drop(buffer.pop());
+            assert_eq!(Rc::strong_count(&value), 3);
+        }
+        assert_eq!(Rc::strong_count(&value), 1);
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let mut cache = LruCache::new(3);
+        for (key, value) in [("a", 1), ("b", 2), ("c", 3)] {
+            assert_eq!(cache.put(key, value), None);
+        }
+        assert_eq!(cache.get("a"), Some(&1));
+        assert_eq!(cache.put("d", 4), None);
+        assert!(!cache.contains("b"));
+        assert_eq!(cache.peek("c"), Some(&3));
+        assert_eq!(cache.put("c", 30), Some(3));
+        assert_eq!(
+            cache.iter().collect::<Vec<_>>(),
+            [(&"c", &30), (&"d", &4), (&"a", &1)]
+        );
+        *cache.get_mut("d").expect("valid entry") += 1;
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 1,
+                evictions: 1,
+            }
+        );
+        assert!((cache.stats().hit_rate() - 2.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(cache.remove("c"), Some(30));
+        assert_eq!(cache.remove("c"), None);
+        assert_eq!(cache.len(), 2);
+        cache.put("e", 5);
+        cache.put("f", 6);
+        assert_eq!(
+            cache.iter().map(|(key, _)| *key).collect::<Vec<_>>(),
+            ["f", "e", "d"]
+        );
+        cache.clear();
+        assert!(cache.is_empty());
+        assert_eq!(cache.iter().count(), 0);
+        cache.put("a", 1);
+        assert_eq!(cache.capacity(), 3);
+    }
+
+    #[cfg(feature = "perf")]
+    #[test]
+    fn lru_counters() {
+        use crate::performance::{profile_begin, profile_end};
+
+        profile_begin();
+        let mut cache = LruCache::new(1).counters("lru_test.hits", "lru_test.misses");
+        cache.put(1, ());
+        for key in [1, 1, 2] {
+            let _ = cache.get(&key);
+        }
+        let report = profile_end();
+        let count = |name| {
+            report
+                .event_counters
+                .iter()
+                .find(|counter| counter.name == name)
+                .map(|counter| counter.count)
+        };
+        assert_eq!(
+            (count("lru_test.hits"), count("lru_test.misses")),
+            (Some(2), Some(1))
+        );
+    }
+
+    #[test]
+    fn inline_vec_spills() {
+        let mut vec: InlineVec<u32, 3> = InlineVec::new();
+        assert_eq!(vec.capacity(), 3);
+        vec.extend([1, 2, 3]);
+        assert!(!vec.spilled());
+        vec.insert(1, 10);
+        assert!(vec.spilled());
+        assert_eq!(vec.as_slice(), [1, 10, 2, 3]);
+        assert_eq!(vec.remove(0), 1);
+        assert_eq!(vec.swap_remove(0), 10);
+        assert_eq!(vec[..], [3, 2]);
+        vec.sort_unstable();
+        assert_eq!(format!("{vec:?}"), "[2, 3]");
+        assert_eq!(vec.clone(), vec);
+        assert_eq!(vec.into_vec(), [2, 3]);
+
+        let mut vec: InlineVec<String, 2> = ["a", "b"].iter().map(ToString::to_string).collect();
+        assert!(!vec.spilled());
+        vec.truncate(1);
+        assert_eq!(vec.pop().as_deref(), Some("a"));
+        assert_eq!(vec.pop(), None);
+        assert!(InlineVec::<u8, 2>::with_capacity(5).spilled());
+        assert_eq!(InlineVec::<u8, 0>::from(vec![1]).len(), 1);
+        let mut empty: InlineVec<u8, 0> = InlineVec::default();
+        empty.push(1);
+        assert_eq!(empty.as_slice(), [1]);
+    }
+
+    #[test]
+    fn inline_vec_into_iter() {
+        let value = Rc::new(());
+        let vec: InlineVec<Rc<()>, 4> = (0..3).map(|_| Rc::clone(&value)).collect();
+        let mut iter = vec.into_iter();
+        assert_eq!(iter.len(), 3);
+        drop((iter.next(), iter.next_back()));
+        assert_eq!(Rc::strong_count(&value), 2);
+        drop(iter);
+        assert_eq!(Rc::strong_count(&value), 1);
+
+        let vec: InlineVec<u32, 1> = (0..4).collect();
+        assert_eq!(vec.into_iter().rev().collect::<Vec<_>>(), [3, 2, 1, 0]);
+        let mut vec: InlineVec<u32, 4> = (0..2).collect();
+        for value in &mut vec {
+            *value += 1;
+        }
+        assert_eq!((&vec).into_iter().sum::<u32>(), 3);
+    }
+}
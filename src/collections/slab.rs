@@ -0,0 +1,531 @@
+//! Slabs of values addressed by small integer keys.
+
+use std::{
+    fmt,
+    ops::{Index, IndexMut},
+};
+
+/// Marks the end of a slab's list of vacant slots.
+const NIL: usize = usize::MAX;
+
+#[derive(Clone)]
+enum Slot<T> {
+    Occupied(T),
+    /// A free slot, with the index of the next free one or `NIL`.
+    Vacant(usize),
+}
+
+/// A collection of values addressed by the keys [`insert`](Self::insert) returns.
+///
+/// Keys are indices into a `Vec`, so lookups are as fast as indexing one, and they stay valid
+/// until their value is removed. Removed slots are reused by later inserts, so a key may come to
+/// refer to a different value; use a [`GenSlab`] to detect that.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::collections::Slab;
+///
+/// let mut connections = Slab::new();
+/// let first = connections.insert("10.0.0.1");
+/// let second = connections.insert("10.0.0.2");
+/// assert_eq!(connections[second], "10.0.0.2");
+/// assert_eq!(connections.remove(first), "10.0.0.1");
+/// assert_eq!(connections.get(first), None);
+/// assert_eq!(connections.insert("10.0.0.3"), first);
+/// ```
+#[derive(Clone)]
+#[must_use]
+pub struct Slab<T> {
+    slots: Vec<Slot<T>>,
+    /// Index of the most recently freed slot, or `NIL`.
+    next_free: usize,
+    len: usize,
+}
+
+impl<T> Slab<T> {
+    /// Creates an empty slab.
+    pub const fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            next_free: NIL,
+            len: 0,
+        }
+    }
+
+    /// Creates an empty slab with room for `capacity` values before it reallocates.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            ..Self::new()
+        }
+    }
+
+    /// Number of values in the slab.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the slab has no values.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of values the slab holds before it reallocates.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// The key the next [`insert`](Self::insert) returns.
+    #[must_use]
+    pub fn vacant_key(&self) -> usize {
+        if self.next_free == NIL {
+            self.slots.len()
+        } else {
+            self.next_free
+        }
+    }
+
+    /// Adds `value`, reusing the most recently freed slot if there is one, and returns its key.
+    pub fn insert(&mut self, value: T) -> usize {
+        let key = self.vacant_key();
+        if key == self.slots.len() {
+            self.slots.push(Slot::Occupied(value));
+        } else {
+            let Slot::Vacant(next) = std::mem::replace(&mut self.slots[key], Slot::Occupied(value))
+            else {
+                unreachable!("free list points at an occupied slot");
+            };
+            self.next_free = next;
+        }
+        self.len += 1;
+        key
+    }
+
+    /// Removes and returns the value of `key`, if there is one.
+    pub fn try_remove(&mut self, key: usize) -> Option<T> {
+        let slot = self.slots.get_mut(key)?;
+        if matches!(slot, Slot::Vacant(_)) {
+            return None;
+        }
+        let Slot::Occupied(value) = std::mem::replace(slot, Slot::Vacant(self.next_free)) else {
+            unreachable!("slot was checked to be occupied");
+        };
+        self.next_free = key;
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Removes and returns the value of `key`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `key` has no value.
+    pub fn remove(&mut self, key: usize) -> T {
+        self.try_remove(key)
+            .unwrap_or_else(|| panic!("invalid slab key: {key}"))
+    }
+
+    /// Whether `key` has a value.
+    #[must_use]
+    pub fn contains(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the value of `key`, if there is one.
+    #[must_use]
+    pub fn get(&self, key: usize) -> Option<&T> {
+        match self.slots.get(key)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    /// Returns the value of `key` for modification, if there is one.
+    #[must_use]
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut T> {
+        match self.slots.get_mut(key)? {
+            Slot::Occupied(value) => Some(value),
+            Slot::Vacant(_) => None,
+        }
+    }
+
+    /// Removes every value, freeing their keys.
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.next_free = NIL;
+        self.len = 0;
+    }
+
+    /// Keeps only the values for which `keep` returns `true`.
+    pub fn retain(&mut self, mut keep: impl FnMut(usize, &mut T) -> bool) {
+        for key in 0..self.slots.len() {
+            if let Slot::Occupied(value) = &mut self.slots[key] {
+                if !keep(key, value) {
+                    self.try_remove(key);
+                }
+            }
+        }
+    }
+
+    /// Iterates over the keys and values, in key order.
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (usize, &T)> + '_ {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(key, slot)| match slot {
+                Slot::Occupied(value) => Some((key, value)),
+                Slot::Vacant(_) => None,
+            })
+    }
+
+    /// Iterates over the keys and values for modification, in key order.
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (usize, &mut T)> + '_ {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(key, slot)| match slot {
+                Slot::Occupied(value) => Some((key, value)),
+                Slot::Vacant(_) => None,
+            })
+    }
+}
+
+impl<T> Default for Slab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Slab<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Index<usize> for Slab<T> {
+    type Output = T;
+
+    fn index(&self, key: usize) -> &T {
+        self.get(key)
+            .unwrap_or_else(|| panic!("invalid slab key: {key}"))
+    }
+}
+
+impl<T> IndexMut<usize> for Slab<T> {
+    fn index_mut(&mut self, key: usize) -> &mut T {
+        self.get_mut(key)
+            .unwrap_or_else(|| panic!("invalid slab key: {key}"))
+    }
+}
+
+impl<T> FromIterator<T> for Slab<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let slots: Vec<_> = iter.into_iter().map(Slot::Occupied).collect();
+        Self {
+            len: slots.len(),
+            slots,
+            next_free: NIL,
+        }
+    }
+}
+
+/// A key into a [`GenSlab`]: a slot index and the generation of the value it was issued for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct GenKey {
+    index: u32,
+    generation: u32,
+}
+
+impl GenKey {
+    fn new(index: usize, generation: u32) -> Self {
+        Self {
+            index: u32::try_from(index).expect("fewer than u32::MAX slots"),
+            generation,
+        }
+    }
+
+    /// Index of the slot this key refers to.
+    #[must_use]
+    pub const fn index(self) -> usize {
+        self.index as usize
+    }
+
+    /// Generation of the slot when this key was issued.
+    #[must_use]
+    pub const fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Display for GenKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Clone)]
+struct GenSlot<T> {
+    /// Incremented each time the slot's value is removed, invalidating keys to it.
+    generation: u32,
+    value: Option<T>,
+}
+
+/// A [`Slab`] whose keys detect when their value was removed, even once its slot is reused.
+///
+/// Each slot counts how many times its value has been removed, and each [`GenKey`] records that
+/// count, so lookups with a stale key return `None` instead of another value. This suits entity
+/// and handle systems, where keys are held long after their values may be gone.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::collections::GenSlab;
+///
+/// let mut entities = GenSlab::new();
+/// let player = entities.insert("player");
+/// entities.remove(player);
+/// let enemy = entities.insert("enemy");
+/// assert_eq!(enemy.index(), player.index());
+/// assert_eq!(entities.get(player), None);
+/// assert_eq!(entities[enemy], "enemy");
+/// ```
+#[derive(Clone)]
+#[must_use]
+pub struct GenSlab<T> {
+    slots: Vec<GenSlot<T>>,
+    /// Indices of vacant slots, reused most recently freed first.
+    free: Vec<usize>,
+}
+
+impl<T> GenSlab<T> {
+    /// Creates an empty slab.
+    pub const fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Creates an empty slab with room for `capacity` values before it reallocates.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            slots: Vec::with_capacity(capacity),
+            free: Vec::new(),
+        }
+    }
+
+    /// Number of values in the slab.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    /// Whether the slab has no values.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Adds `value`, reusing the most recently freed slot if there is one, and returns its key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slab would have more than `u32::MAX` slots.
+    pub fn insert(&mut self, value: T) -> GenKey {
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index];
+            slot.value = Some(value);
+            return GenKey::new(index, slot.generation);
+        }
+        let key = GenKey::new(self.slots.len(), 0);
+        self.slots.push(GenSlot {
+            generation: 0,
+            value: Some(value),
+        });
+        key
+    }
+
+    /// Removes and returns the value of `key`, or `None` if it was already removed.
+    pub fn remove(&mut self, key: GenKey) -> Option<T> {
+        let slot = self
+            .slots
+            .get_mut(key.index())
+            .filter(|slot| slot.generation == key.generation)?;
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(key.index());
+        Some(value)
+    }
+
+    /// Whether `key` still has a value.
+    #[must_use]
+    pub fn contains(&self, key: GenKey) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the value of `key`, or `None` if it was removed.
+    #[must_use]
+    pub fn get(&self, key: GenKey) -> Option<&T> {
+        self.slots
+            .get(key.index())
+            .filter(|slot| slot.generation == key.generation)?
+            .value
+            .as_ref()
+    }
+
+    /// Returns the value of `key` for modification, or `None` if it was removed.
+    #[must_use]
+    pub fn get_mut(&mut self, key: GenKey) -> Option<&mut T> {
+        self.slots
+            .get_mut(key.index())
+            .filter(|slot| slot.generation == key.generation)?
+            .value
+            .as_mut()
+    }
+
+    /// Removes every value, invalidating every key.
+    pub fn clear(&mut self) {
+        self.free.clear();
+        for (index, slot) in self.slots.iter_mut().enumerate().rev() {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+            self.free.push(index);
+        }
+    }
+
+    /// Keeps only the values for which `keep` returns `true`.
+    pub fn retain(&mut self, mut keep: impl FnMut(GenKey, &mut T) -> bool) {
+        for (index, slot) in self.slots.iter_mut().enumerate() {
+            let Some(value) = &mut slot.value else {
+                continue;
+            };
+            if !keep(GenKey::new(index, slot.generation), value) {
+                slot.value = None;
+                slot.generation = slot.generation.wrapping_add(1);
+                self.free.push(index);
+            }
+        }
+    }
+
+    /// Iterates over the keys and values, in index order.
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (GenKey, &T)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            Some((GenKey::new(index, slot.generation), slot.value.as_ref()?))
+        })
+    }
+
+    /// Iterates over the keys and values for modification, in index order.
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = (GenKey, &mut T)> + '_ {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                Some((GenKey::new(index, slot.generation), slot.value.as_mut()?))
+            })
+    }
+}
+
+impl<T> Default for GenSlab<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for GenSlab<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<T> Index<GenKey> for GenSlab<T> {
+    type Output = T;
+
+    fn index(&self, key: GenKey) -> &T {
+        self.get(key)
+            .unwrap_or_else(|| panic!("stale or invalid slab key: {key}"))
+    }
+}
+
+impl<T> IndexMut<GenKey> for GenSlab<T> {
+    fn index_mut(&mut self, key: GenKey) -> &mut T {
+        self.get_mut(key)
+            .unwrap_or_else(|| panic!("stale or invalid slab key: {key}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slab() {
+        let mut slab = Slab::new();
+        assert!(slab.is_empty());
+        let keys: Vec<_> = (0..4).map(|value| slab.insert(value * 10)).collect();
+        assert_eq!(keys, [0, 1, 2, 3]);
+        assert_eq!(slab.remove(1), 10);
+        assert_eq!(slab.try_remove(1), None);
+        assert_eq!(slab.remove(3), 30);
+        assert_eq!(slab.len(), 2);
+        assert!(!slab.contains(3) && slab.contains(2));
+        assert_eq!(slab.vacant_key(), 3);
+        assert_eq!(slab.insert(31), 3);
+        assert_eq!(slab.insert(11), 1);
+        assert_eq!(slab.insert(40), 4);
+        slab[2] += 1;
+        assert_eq!(
+            slab.iter().collect::<Vec<_>>(),
+            [(0, &0), (1, &11), (2, &21), (3, &31), (4, &40)]
+        );
+        slab.retain(|key, _| key % 2 == 0);
+        assert_eq!(
+            slab.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+            [0, 2, 4]
+        );
+        assert_eq!(format!("{slab:?}"), "{0: 0, 2: 21, 4: 40}");
+        slab.clear();
+        assert!(slab.is_empty() && slab.get(0).is_none());
+
+        let slab: Slab<_> = ["a", "b"].into_iter().collect();
+        assert_eq!(slab[1], "b");
+    }
+
+    #[test]
+    #[should_panic = "invalid slab key: 5"]
+    fn invalid_key() {
+        Slab::<u8>::new().remove(5);
+    }
+
+    #[test]
+    fn generational_slab() {
+        let mut slab = GenSlab::new();
+        let first = slab.insert("a");
+        let second = slab.insert("b");
+        assert_eq!(slab.remove(first), Some("a"));
+        assert_eq!(slab.remove(first), None);
+        let third = slab.insert("c");
+        assert_eq!((third.index(), third.generation()), (0, 1));
+        assert_eq!(third.to_string(), "0v1");
+        assert!(!slab.contains(first));
+        assert_eq!(slab.get(first), None);
+        assert_eq!(slab[third], "c");
+        assert_eq!(slab.len(), 2);
+
+        slab.retain(|key, _| key != second);
+        assert_eq!(slab.get(second), None);
+        assert_eq!(slab.iter().collect::<Vec<_>>(), [(third, &"c")]);
+        let fourth = slab.insert("d");
+        assert_eq!(fourth.index(), second.index());
+
+        slab.clear();
+        assert!(slab.is_empty() && !slab.contains(third) && !slab.contains(fourth));
+        assert_eq!(slab.insert("e").index(), 0);
+    }
+}
@@ -0,0 +1,481 @@
+//! Configuration files.
+//!
+//! A [`Config`] is loaded from an INI-style file of `key = value` lines grouped under `[section]`
+//! headers, which is also the subset of TOML most tools need:
+//!
+//! ```text
+//! # Comments start with `#` or `;`.
+//! name = "demo"
+//!
+//! [net]
+//! timeout_ms = 500
+//! retry = yes
+//! ```
+//!
+//! Keys are looked up by their section and name joined with a dot, such as `net.timeout_ms`, and
+//! values are parsed on lookup with [`FromStr`]. With an [environment prefix](Config::env_prefix),
+//! variables such as `APP_NET_TIMEOUT_MS` override the file. Errors name the file and line of the
+//! offending value, or the variable it came from.
+
+use crate::env;
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    error::Error,
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Why loading a configuration or reading one of its values failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    path: Option<PathBuf>,
+    line: Option<usize>,
+    message: String,
+}
+
+impl ConfigError {
+    fn new(path: Option<&Path>, line: Option<usize>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.map(Path::to_path_buf),
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// The file the error is in, if the configuration was loaded from one.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The line the error is on, starting from 1, or `None` if it isn't in the file.
+    #[must_use]
+    pub const fn line(&self) -> Option<usize> {
+        self.line
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.path, self.line) {
+            (Some(path), Some(line)) => write!(f, "{}:{line}: ", path.display())?,
+            (Some(path), None) => write!(f, "{}: ", path.display())?,
+            (None, Some(line)) => write!(f, "line {line}: ")?,
+            (None, None) => {}
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for ConfigError {}
+
+/// A value and the line it was set on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    value: String,
+    line: usize,
+}
+
+/// Values loaded from a configuration file, by `section.key`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::config::Config;
+///
+/// # fn main() -> Result<(), util_lib_rs::config::ConfigError> {
+/// let config = Config::parse("name = \"demo\"\n[net]\ntimeout_ms = 500\n")?.env_prefix("DEMO");
+/// assert_eq!(config.get_str("name").as_deref(), Some("demo"));
+/// assert_eq!(config.get_or("net.timeout_ms", 1000)?, 500);
+/// assert_eq!(config.get_or("net.retries", 3)?, 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Config {
+    path: Option<PathBuf>,
+    entries: BTreeMap<String, Entry>,
+    env_prefix: Option<String>,
+}
+
+impl Config {
+    /// Creates an empty configuration, so every lookup falls back to its default or the
+    /// environment.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads and parses the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or isn't valid.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)
+            .map_err(|err| ConfigError::new(Some(path), None, err.to_string()))?;
+        let mut config = Self::parse_from(&text, Some(path))?;
+        config.path = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    /// Parses the configuration in `text`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line of the first invalid line, section or value, or of a key
+    /// set twice.
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        Self::parse_from(text, None)
+    }
+
+    fn parse_from(text: &str, path: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut entries = BTreeMap::new();
+        let mut section = String::new();
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            let error = |message: &str| ConfigError::new(path, Some(number), message);
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[') {
+                let (name, rest) = header
+                    .split_once(']')
+                    .ok_or_else(|| error("expected `]` after the section name"))?;
+                if !is_comment(rest) {
+                    return Err(error("unexpected characters after the section"));
+                }
+                section = parse_key(name).ok_or_else(|| error("invalid section name"))?;
+                continue;
+            }
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| error("expected `key = value`"))?;
+            let key = parse_key(key).ok_or_else(|| error("invalid key"))?;
+            let value = parse_value(value.trim()).map_err(error)?;
+            let key = if section.is_empty() {
+                key
+            } else {
+                format!("{section}.{key}")
+            };
+            if entries.contains_key(&key) {
+                return Err(error(&format!("duplicate key `{key}`")));
+            }
+            entries.insert(
+                key,
+                Entry {
+                    value,
+                    line: number,
+                },
+            );
+        }
+        Ok(Self {
+            path: None,
+            entries,
+            env_prefix: None,
+        })
+    }
+
+    /// Lets environment variables named `PREFIX_SECTION_KEY` override values, such as
+    /// `APP_NET_TIMEOUT_MS` for `net.timeout_ms` with the prefix `APP`. Names are upper case, with
+    /// dots and dashes replaced by underscores.
+    pub fn env_prefix(mut self, prefix: &str) -> Self {
+        self.env_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Returns the name of the environment variable which overrides `key`, if there's a prefix.
+    #[must_use]
+    pub fn env_var(&self, key: &str) -> Option<String> {
+        self.env_prefix.as_ref().map(|prefix| {
+            format!("{prefix}_{key}")
+                .chars()
+                .map(|c| match c {
+                    '.' | '-' => '_',
+                    _ => c.to_ascii_uppercase(),
+                })
+                .collect()
+        })
+    }
+
+    /// Returns the raw value of `key`, from the environment or the file, or `None` if it isn't
+    /// set. Environment values which aren't valid Unicode are ignored.
+    #[must_use]
+    pub fn get_str(&self, key: &str) -> Option<Cow<'_, str>> {
+        let from_env = self.env_var(key).and_then(|name| std::env::var(name).ok());
+        from_env.map(Cow::Owned).or_else(|| {
+            self.entries
+                .get(key)
+                .map(|entry| entry.value.as_str().into())
+        })
+    }
+
+    /// Returns the value of `key` parsed as a `T`, or `None` if it isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line or environment variable of a value which doesn't parse.
+    pub fn get<T>(&self, key: &str) -> Result<Option<T>, ConfigError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get_with(key, env::var_parsed, |value| {
+            value.parse().map_err(|err: T::Err| err.to_string())
+        })
+    }
+
+    /// Returns the value of `key` parsed as a `T`, or `default` if it isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line or environment variable of a value which doesn't parse.
+    pub fn get_or<T>(&self, key: &str, default: T) -> Result<T, ConfigError>
+    where
+        T: FromStr,
+        T::Err: fmt::Display,
+    {
+        self.get(key).map(|value| value.unwrap_or(default))
+    }
+
+    /// Returns the value of `key` parsed with [`env::parse_bool`], so `yes`, `on` and `1` are true,
+    /// or `None` if it isn't set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error naming the line or environment variable of a value which isn't a boolean.
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>, ConfigError> {
+        self.get_with(key, env::flag, |value| {
+            env::parse_bool(value).ok_or_else(|| env::EXPECTED_BOOL.to_string())
+        })
+    }
+
+    fn get_with<T>(
+        &self,
+        key: &str,
+        from_env: impl FnOnce(&str) -> Result<Option<T>, env::VarError>,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Result<Option<T>, ConfigError> {
+        if let Some(name) = self.env_var(key) {
+            match from_env(&name) {
+                Ok(Some(value)) => return Ok(Some(value)),
+                Ok(None) => {}
+                Err(err) => return Err(ConfigError::new(None, None, err.to_string())),
+            }
+        }
+        let Some(entry) = self.entries.get(key) else {
+            return Ok(None);
+        };
+        parse(&entry.value).map(Some).map_err(|message| {
+            ConfigError::new(
+                self.path.as_deref(),
+                Some(entry.line),
+                format!("invalid value `{}` for {key}: {message}", entry.value),
+            )
+        })
+    }
+
+    /// Whether `key` is set in the file or the environment.
+    #[must_use]
+    pub fn contains(&self, key: &str) -> bool {
+        self.get_str(key).is_some()
+    }
+
+    /// Returns the keys set in the file, in sorted order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.keys().map(String::as_str)
+    }
+
+    /// Returns the keys set in the file under `section`, without the section name, in sorted order.
+    pub fn section_keys<'a>(&'a self, section: &'a str) -> impl Iterator<Item = &'a str> {
+        self.keys().filter_map(move |key| {
+            key.strip_prefix(section)
+                .and_then(|rest| rest.strip_prefix('.'))
+        })
+    }
+}
+
+/// Parses a key or section name of letters, digits, `_`, `-` and `.`, such as `net.timeout_ms`.
+fn parse_key(key: &str) -> Option<String> {
+    let key = key.trim();
+    let valid = key.split('.').all(|part| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    });
+    valid.then(|| key.to_string())
+}
+
+/// Whether `rest` of a line is only whitespace or a comment.
+fn is_comment(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    rest.is_empty() || rest.starts_with(['#', ';'])
+}
+
+/// Parses a value: a `"basic"` string with escapes, a `'literal'` string, or a bare value up to a
+/// comment.
+fn parse_value(value: &str) -> Result<String, &'static str> {
+    let mut chars = value.char_indices();
+    match chars.next() {
+        Some((_, '"')) => {
+            let mut parsed = String::new();
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        let rest = chars.as_str();
+                        return if is_comment(rest) {
+                            Ok(parsed)
+                        } else {
+                            Err("unexpected characters after the string")
+                        };
+                    }
+                    '\\' => parsed.push(match chars.next().map(|(_, c)| c) {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        _ => return Err("invalid escape"),
+                    }),
+                    _ => parsed.push(c),
+                }
+            }
+            Err("unterminated string")
+        }
+        Some((_, '\'')) => {
+            let (parsed, rest) = value[1..].split_once('\'').ok_or("unterminated string")?;
+            if is_comment(rest) {
+                Ok(parsed.to_string())
+            } else {
+                Err("unexpected characters after the string")
+            }
+        }
+        _ => {
+            // A comment in a bare value needs whitespace before it, so `#` can appear in values.
+            let end = value
+                .match_indices([' ', '\t'])
+                .find(|(at, _)| is_comment(&value[*at..]))
+                .map_or(value.len(), |(at, _)| at);
+            Ok(value[..end].trim_end().to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::EnvGuard;
+
+    const TEXT: &str = r#"
+# A comment.
+name = "demo app" # The name.
+path = 'C:\tools'
+color = #ff0000 ; red
+
+[net]
+timeout_ms = 500
+retry = yes
+hosts = a, b
+
+[net.tls]
+verify = off
+"#;
+
+    #[test]
+    fn parses_sections_and_values() {
+        let config = Config::parse(TEXT).expect("valid config");
+        assert_eq!(config.get_str("name").as_deref(), Some("demo app"));
+        assert_eq!(config.get_str("path").as_deref(), Some(r"C:\tools"));
+        assert_eq!(config.get_str("color").as_deref(), Some("#ff0000"));
+        assert_eq!(config.get_str("net.hosts").as_deref(), Some("a, b"));
+        assert_eq!(config.get::<u32>("net.timeout_ms"), Ok(Some(500)));
+        assert_eq!(config.get_bool("net.retry"), Ok(Some(true)));
+        assert_eq!(config.get_bool("net.tls.verify"), Ok(Some(false)));
+        assert_eq!(config.get::<u32>("net.missing"), Ok(None));
+        assert_eq!(config.get_or("net.missing", 7), Ok(7));
+        assert!(config.contains("net.timeout_ms") && !config.contains("timeout_ms"));
+        assert_eq!(
+            config.section_keys("net").collect::<Vec<_>>(),
+            ["hosts", "retry", "timeout_ms", "tls.verify"]
+        );
+
+        let escaped = Config::parse(r#"text = "a\t\"b\"\\n""#).expect("valid config");
+        assert_eq!(escaped.get_str("text").as_deref(), Some("a\t\"b\"\\n"));
+    }
+
+    #[test]
+    fn reports_errors_with_lines() {
+        let error = |text: &str| Config::parse(text).expect_err("invalid config").to_string();
+        assert_eq!(error("a = 1\nb"), "line 2: expected `key = value`");
+        assert_eq!(error("[net"), "line 1: expected `]` after the section name");
+        assert_eq!(
+            error("[net] x"),
+            "line 1: unexpected characters after the section"
+        );
+        assert_eq!(error("[a..b]"), "line 1: invalid section name");
+        assert_eq!(error("a b = 1"), "line 1: invalid key");
+        assert_eq!(error("a = \"open"), "line 1: unterminated string");
+        assert_eq!(error("a = \"\\x\""), "line 1: invalid escape");
+        assert_eq!(
+            error("a = 'one' two"),
+            "line 1: unexpected characters after the string"
+        );
+        assert_eq!(error("[s]\na = 1\n\na = 2"), "line 4: duplicate key `s.a`");
+
+        let config = Config::parse(TEXT).expect("valid config");
+        let err = config.get::<u32>("net.hosts").expect_err("invalid number");
+        assert_eq!(err.line(), Some(10));
+        assert_eq!(
+            err.to_string(),
+            "line 10: invalid value `a, b` for net.hosts: invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn loads_files() {
+        let dir = crate::fs::TempDir::new().expect("created dir");
+        let path = dir.path().join("app.toml");
+        fs::write(&path, "[net]\ntimeout_ms = soon\n").expect("valid write");
+        let config = Config::load(&path).expect("valid config");
+        let err = config
+            .get::<u32>("net.timeout_ms")
+            .expect_err("invalid number");
+        assert_eq!(err.path(), Some(path.as_path()));
+        assert!(err
+            .to_string()
+            .starts_with(&format!("{}:2: invalid value `soon`", path.display())));
+        fs::remove_file(&path).expect("valid remove");
+        assert!(Config::load(&path).is_err());
+    }
+
+    #[test]
+    fn environment_overrides() {
+        let _env = EnvGuard::new()
+            .set("UTIL_CONFIG_TEST_NET_TIMEOUT_MS", "250")
+            .set("UTIL_CONFIG_TEST_NET_TLS_VERIFY", "maybe")
+            .set("UTIL_CONFIG_TEST_EXTRA", "1");
+        let config = Config::parse(TEXT)
+            .expect("valid config")
+            .env_prefix("UTIL_CONFIG_TEST");
+        assert_eq!(
+            config.env_var("net.tls.verify").as_deref(),
+            Some("UTIL_CONFIG_TEST_NET_TLS_VERIFY")
+        );
+        assert_eq!(config.get::<u32>("net.timeout_ms"), Ok(Some(250)));
+        assert_eq!(config.get_str("net.timeout_ms").as_deref(), Some("250"));
+        assert_eq!(config.get_bool("extra"), Ok(Some(true)));
+        assert_eq!(config.get::<u32>("extra"), Ok(Some(1)));
+        assert_eq!(
+            config.get_bool("net.tls.verify").map_err(|err| err.to_string()),
+            Err("invalid value `maybe` for UTIL_CONFIG_TEST_NET_TLS_VERIFY: expected 1, true, yes, \
+                 on, 0, false, no or off"
+                .to_string())
+        );
+        assert_eq!(config.get_str("name").as_deref(), Some("demo app"));
+    }
+}
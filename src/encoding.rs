@@ -0,0 +1,242 @@
+//! Base64 and hex encoding.
+//!
+//! [`Base64`] encodes with the standard or the URL-safe alphabet, and [`Hex`] with lower or upper
+//! case digits. Both encode and decode slices, or streams through an [`Encoder`] wrapping a
+//! writer and a [`Decoder`] wrapping a reader. Decoding is [`Mode::Strict`] by default, rejecting
+//! anything but the canonical encoding, or [`Mode::Lenient`] for input typed or pasted by people.
+
+pub mod base64;
+pub mod hex;
+
+pub use base64::Base64;
+pub use hex::Hex;
+
+use std::{
+    error::Error,
+    fmt,
+    io::{self, Read, Write},
+};
+
+/// How forgiving decoding is.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Mode {
+    /// Accept only the canonical encoding.
+    #[default]
+    Strict,
+    /// Skip ASCII whitespace, and for base64, accept either alphabet, missing padding and
+    /// nonzero unused bits.
+    Lenient,
+}
+
+/// Invalid encoded input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    encoding: &'static str,
+    position: usize,
+    message: &'static str,
+}
+
+impl DecodeError {
+    const fn new(encoding: &'static str, position: usize, message: &'static str) -> Self {
+        Self {
+            encoding,
+            position,
+            message,
+        }
+    }
+
+    /// Offset of the invalid byte in the input, or its length if the input ended too early.
+    #[must_use]
+    pub const fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} at byte {}: {}",
+            self.encoding, self.position, self.message
+        )
+    }
+}
+
+impl Error for DecodeError {}
+
+impl From<DecodeError> for io::Error {
+    fn from(err: DecodeError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Encoding of a stream fed in chunks of any size.
+trait Encode {
+    /// Appends the encoding of `bytes` to `out`, keeping bytes which don't fill a group.
+    fn encode(&mut self, bytes: &[u8], out: &mut Vec<u8>);
+
+    /// Appends the encoding of the bytes kept, with any padding, to `out`.
+    fn finish(&mut self, out: &mut Vec<u8>);
+}
+
+/// Decoding of a stream fed in chunks of any size.
+trait Decode {
+    /// Appends the bytes decoded from `input` to `out`.
+    fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), DecodeError>;
+
+    /// Appends the last bytes decoded to `out`, once the input has ended.
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), DecodeError>;
+}
+
+fn encode_slice(mut state: impl Encode, bytes: &[u8], out: &mut String) {
+    let mut encoded = std::mem::take(out).into_bytes();
+    state.encode(bytes, &mut encoded);
+    state.finish(&mut encoded);
+    *out = String::from_utf8(encoded).expect("valid ASCII encoding");
+}
+
+fn decode_slice(mut state: impl Decode, input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+    let mut decoded = Vec::with_capacity(input.len());
+    state.decode(input, &mut decoded)?;
+    state.finish(&mut decoded)?;
+    Ok(decoded)
+}
+
+/// A writer encoding everything written to it into another writer. Created by
+/// [`Base64::encoder`] or [`Hex::encoder`].
+///
+/// Call [`finish`](Self::finish) to write the final group and check for errors. Dropping the
+/// encoder writes it too, but ignores errors.
+#[must_use]
+pub struct Encoder<W: Write> {
+    writer: Option<W>,
+    state: Box<dyn Encode + Send>,
+    encoded: Vec<u8>,
+}
+
+impl<W: Write> fmt::Debug for Encoder<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encoder").finish_non_exhaustive()
+    }
+}
+
+impl<W: Write> Encoder<W> {
+    fn new(writer: W, state: impl Encode + Send + 'static) -> Self {
+        Self {
+            writer: Some(writer),
+            state: Box::new(state),
+            encoded: Vec::new(),
+        }
+    }
+
+    fn writer(&mut self) -> &mut W {
+        self.writer.as_mut().expect("valid writer")
+    }
+
+    /// Writes the final group and padding, and returns the inner writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_final()?;
+        Ok(self.take_writer())
+    }
+
+    /// Takes the writer out, so dropping the encoder doesn't write the final group again.
+    fn take_writer(&mut self) -> W {
+        self.writer.take().expect("valid writer")
+    }
+
+    fn write_final(&mut self) -> io::Result<()> {
+        self.encoded.clear();
+        self.state.finish(&mut self.encoded);
+        let encoded = std::mem::take(&mut self.encoded);
+        self.writer().write_all(&encoded)?;
+        self.writer().flush()
+    }
+}
+
+impl<W: Write> Write for Encoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.encoded.clear();
+        self.state.encode(buf, &mut self.encoded);
+        let encoded = std::mem::take(&mut self.encoded);
+        let result = self.writer().write_all(&encoded);
+        self.encoded = encoded;
+        result.map(|()| buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer().flush()
+    }
+}
+
+impl<W: Write> Drop for Encoder<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.write_final();
+        }
+    }
+}
+
+/// A reader decoding the contents of another reader. Created by [`Base64::decoder`] or
+/// [`Hex::decoder`].
+///
+/// Invalid input is reported as an [`io::ErrorKind::InvalidData`] error wrapping a
+/// [`DecodeError`].
+#[must_use]
+pub struct Decoder<R: Read> {
+    reader: R,
+    state: Box<dyn Decode + Send>,
+    decoded: Vec<u8>,
+    /// Offset of the next decoded byte to read.
+    read: usize,
+    ended: bool,
+}
+
+impl<R: Read> fmt::Debug for Decoder<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decoder").finish_non_exhaustive()
+    }
+}
+
+impl<R: Read> Decoder<R> {
+    fn new(reader: R, state: impl Decode + Send + 'static) -> Self {
+        Self {
+            reader,
+            state: Box::new(state),
+            decoded: Vec::new(),
+            read: 0,
+            ended: false,
+        }
+    }
+
+    /// Returns the inner reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<R: Read> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut input = [0; 4096];
+        while self.read == self.decoded.len() && !self.ended {
+            self.decoded.clear();
+            self.read = 0;
+            match self.reader.read(&mut input) {
+                Ok(0) => {
+                    self.ended = true;
+                    self.state.finish(&mut self.decoded)?;
+                }
+                Ok(len) => self.state.decode(&input[..len], &mut self.decoded)?,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        let len = buf.len().min(self.decoded.len() - self.read);
+        buf[..len].copy_from_slice(&self.decoded[self.read..self.read + len]);
+        self.read += len;
+        Ok(len)
+    }
+}
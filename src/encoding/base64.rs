@@ -0,0 +1,405 @@
+//! Base64 encoding, as defined by [RFC 4648].
+//!
+//! [RFC 4648]: https://www.rfc-editor.org/rfc/rfc4648
+
+use super::{decode_slice, encode_slice, Decode, DecodeError, Decoder, Encode, Encoder, Mode};
+use std::io::{Read, Write};
+
+const STANDARD_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const URL_SAFE_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Marks a byte outside an alphabet in a [`decoding_table`].
+const INVALID: u8 = 0xff;
+
+#[allow(clippy::cast_possible_truncation)]
+const fn decoding_table(alphabet: &[u8; 64]) -> [u8; 256] {
+    let mut table = [INVALID; 256];
+    let mut i = 0;
+    while i < alphabet.len() {
+        table[alphabet[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+const STANDARD_TABLE: [u8; 256] = decoding_table(STANDARD_ALPHABET);
+const URL_SAFE_TABLE: [u8; 256] = decoding_table(URL_SAFE_ALPHABET);
+
+/// A base64 configuration: the alphabet, whether encoding pads with `=`, and the decoding
+/// [`Mode`].
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::encoding::Base64;
+///
+/// assert_eq!(Base64::STANDARD.encode(b"hi?"), "aGk/");
+/// assert_eq!(Base64::URL_SAFE.encode(b"hi?>"), "aGk_Pg");
+/// assert_eq!(Base64::STANDARD.decode("aGk/").unwrap(), b"hi?");
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[must_use]
+pub struct Base64 {
+    url_safe: bool,
+    padding: bool,
+    mode: Mode,
+}
+
+impl Default for Base64 {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+impl Base64 {
+    /// The standard alphabet ending in `+` and `/`, padded with `=`.
+    pub const STANDARD: Self = Self {
+        url_safe: false,
+        padding: true,
+        mode: Mode::Strict,
+    };
+
+    /// The URL and filename safe alphabet ending in `-` and `_`, without padding.
+    pub const URL_SAFE: Self = Self {
+        url_safe: true,
+        padding: false,
+        mode: Mode::Strict,
+    };
+
+    /// Pad encodings to a multiple of 4 characters with `=`, and in strict mode, require decoded
+    /// input to be padded, or not to be.
+    pub const fn padding(mut self, padding: bool) -> Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Decode in `mode`.
+    pub const fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the encoding of `bytes`.
+    #[must_use]
+    pub fn encode(self, bytes: &[u8]) -> String {
+        let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        self.encode_to(bytes, &mut encoded);
+        encoded
+    }
+
+    /// Appends the encoding of `bytes` to `out`.
+    pub fn encode_to(self, bytes: &[u8], out: &mut String) {
+        encode_slice(self.encode_state(), bytes, out);
+    }
+
+    /// Returns the bytes encoded by `input`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `input` isn't valid base64 in this configuration.
+    pub fn decode(self, input: impl AsRef<[u8]>) -> Result<Vec<u8>, DecodeError> {
+        decode_slice(self.decode_state(), input.as_ref())
+    }
+
+    /// Returns a writer encoding everything written to it into `writer`.
+    pub fn encoder<W: Write>(self, writer: W) -> Encoder<W> {
+        Encoder::new(writer, self.encode_state())
+    }
+
+    /// Returns a reader decoding the base64 read from `reader`.
+    pub fn decoder<R: Read>(self, reader: R) -> Decoder<R> {
+        Decoder::new(reader, self.decode_state())
+    }
+
+    fn encode_state(self) -> EncodeState {
+        EncodeState {
+            config: self,
+            pending: [0; 3],
+            len: 0,
+        }
+    }
+
+    fn decode_state(self) -> DecodeState {
+        DecodeState {
+            config: self,
+            group: 0,
+            sextets: 0,
+            padding: 0,
+            position: 0,
+        }
+    }
+}
+
+/// Returns the encoding of `bytes` with [`Base64::STANDARD`].
+#[must_use]
+pub fn encode(bytes: &[u8]) -> String {
+    Base64::STANDARD.encode(bytes)
+}
+
+/// Returns the bytes encoded by `input` with [`Base64::STANDARD`].
+///
+/// # Errors
+///
+/// Returns an error if `input` isn't valid padded base64 with the standard alphabet.
+pub fn decode(input: impl AsRef<[u8]>) -> Result<Vec<u8>, DecodeError> {
+    Base64::STANDARD.decode(input)
+}
+
+struct EncodeState {
+    config: Base64,
+    /// Bytes which don't fill a group of 3 yet.
+    pending: [u8; 3],
+    len: usize,
+}
+
+impl EncodeState {
+    fn alphabet(&self) -> &'static [u8; 64] {
+        if self.config.url_safe {
+            URL_SAFE_ALPHABET
+        } else {
+            STANDARD_ALPHABET
+        }
+    }
+
+    /// Appends the first `chars` characters encoding `group`.
+    fn push_group(&self, group: [u8; 3], chars: usize, out: &mut Vec<u8>) {
+        let alphabet = self.alphabet();
+        let bits = u32::from_be_bytes([0, group[0], group[1], group[2]]);
+        for i in 0..chars {
+            out.push(alphabet[(bits >> (18 - 6 * i) & 0x3f) as usize]);
+        }
+    }
+}
+
+impl Encode for EncodeState {
+    fn encode(&mut self, mut bytes: &[u8], out: &mut Vec<u8>) {
+        out.reserve((self.len + bytes.len()) / 3 * 4);
+        if self.len > 0 {
+            let take = bytes.len().min(3 - self.len);
+            self.pending[self.len..self.len + take].copy_from_slice(&bytes[..take]);
+            self.len += take;
+            bytes = &bytes[take..];
+            if self.len < 3 {
+                return;
+            }
+            self.push_group(self.pending, 4, out);
+            self.len = 0;
+        }
+        let mut groups = bytes.chunks_exact(3);
+        for group in &mut groups {
+            self.push_group([group[0], group[1], group[2]], 4, out);
+        }
+        let rest = groups.remainder();
+        self.pending[..rest.len()].copy_from_slice(rest);
+        self.len = rest.len();
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) {
+        if self.len == 0 {
+            return;
+        }
+        self.pending[self.len..].fill(0);
+        self.push_group(self.pending, self.len + 1, out);
+        if self.config.padding {
+            out.extend(std::iter::repeat_n(b'=', 3 - self.len));
+        }
+        self.len = 0;
+    }
+}
+
+struct DecodeState {
+    config: Base64,
+    /// Bits of the sextets decoded in the current group of 4.
+    group: u32,
+    sextets: u8,
+    /// Number of `=` after the current group.
+    padding: u8,
+    /// Offset of the next input byte.
+    position: usize,
+}
+
+impl DecodeState {
+    fn error(&self, message: &'static str) -> DecodeError {
+        DecodeError::new("base64", self.position, message)
+    }
+
+    fn sextet(&self, byte: u8) -> Option<u8> {
+        let (table, other) = if self.config.url_safe {
+            (&URL_SAFE_TABLE, &STANDARD_TABLE)
+        } else {
+            (&STANDARD_TABLE, &URL_SAFE_TABLE)
+        };
+        let mut value = table[usize::from(byte)];
+        if value == INVALID && self.config.mode == Mode::Lenient {
+            value = other[usize::from(byte)];
+        }
+        (value != INVALID).then_some(value)
+    }
+
+    fn push(&mut self, byte: u8, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let lenient = self.config.mode == Mode::Lenient;
+        if lenient && byte.is_ascii_whitespace() {
+            return Ok(());
+        }
+        if byte == b'=' {
+            if !lenient && !self.config.padding {
+                return Err(self.error("unexpected padding"));
+            }
+            if self.sextets < 2 || self.sextets + self.padding == 4 {
+                return Err(self.error("misplaced padding"));
+            }
+            self.padding += 1;
+            return Ok(());
+        }
+        if self.padding > 0 {
+            return Err(self.error("data after padding"));
+        }
+        let sextet = self
+            .sextet(byte)
+            .ok_or_else(|| self.error("invalid character"))?;
+        self.group = (self.group << 6) | u32::from(sextet);
+        self.sextets += 1;
+        if self.sextets == 4 {
+            out.extend_from_slice(&self.group.to_be_bytes()[1..]);
+            self.group = 0;
+            self.sextets = 0;
+        }
+        Ok(())
+    }
+}
+
+impl Decode for DecodeState {
+    fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        out.reserve(input.len() / 4 * 3);
+        for &byte in input {
+            self.push(byte, out)?;
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        let strict = self.config.mode == Mode::Strict;
+        let (bytes, unused_bits) = match self.sextets {
+            0 => return Ok(()),
+            1 => return Err(self.error("truncated input")),
+            sextets => (usize::from(sextets - 1), 8 - 2 * sextets),
+        };
+        if strict && self.config.padding && self.sextets + self.padding != 4 {
+            return Err(self.error("missing padding"));
+        }
+        if strict && self.group & ((1 << unused_bits) - 1) != 0 {
+            return Err(self.error("nonzero unused bits"));
+        }
+        let bits = self.group << (32 - 6 * u32::from(self.sextets));
+        out.extend_from_slice(&bits.to_be_bytes()[..bytes]);
+        self.sextets = 0;
+        self.padding = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn rfc_vectors() {
+        let vectors = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (plain, encoded) in vectors {
+            assert_eq!(encode(plain.as_bytes()), encoded);
+            assert_eq!(decode(encoded).expect("valid base64"), plain.as_bytes());
+            let unpadded = encoded.trim_end_matches('=');
+            assert_eq!(Base64::URL_SAFE.encode(plain.as_bytes()), unpadded);
+            assert_eq!(
+                Base64::URL_SAFE.decode(unpadded).expect("valid base64"),
+                plain.as_bytes()
+            );
+        }
+        let bytes = [0xfb, 0xff, 0xbf];
+        assert_eq!(encode(&bytes), "+/+/");
+        assert_eq!(Base64::URL_SAFE.encode(&bytes), "-_-_");
+    }
+
+    #[test]
+    fn decoding_modes() {
+        let error = |input: &str| {
+            let err = decode(input).expect_err("invalid base64");
+            (err.position(), err.to_string())
+        };
+        assert_eq!(
+            error("Zm9v!"),
+            (4, "invalid base64 at byte 4: invalid character".to_string())
+        );
+        assert_eq!(error("Zg").1, "invalid base64 at byte 2: missing padding");
+        assert_eq!(
+            error("Zh==").1,
+            "invalid base64 at byte 4: nonzero unused bits"
+        );
+        assert_eq!(
+            error("Z===").1,
+            "invalid base64 at byte 1: misplaced padding"
+        );
+        assert_eq!(
+            error("Zg==Zg==").1,
+            "invalid base64 at byte 4: data after padding"
+        );
+        assert_eq!(
+            error("Zm9vY").1,
+            "invalid base64 at byte 5: truncated input"
+        );
+        assert_eq!(error("Zm9v Yg==").0, 4);
+        assert!(Base64::URL_SAFE.decode("Zg==").is_err());
+        assert!(decode("-_-_").is_err());
+
+        let lenient = Base64::STANDARD.mode(Mode::Lenient);
+        assert_eq!(lenient.decode("Zm9v\r\nYg").expect("valid base64"), b"foob");
+        assert_eq!(lenient.decode("Zh").expect("valid base64"), b"f");
+        assert_eq!(
+            lenient.decode("-_-_").expect("valid base64"),
+            [0xfb, 0xff, 0xbf]
+        );
+        assert!(lenient.decode("Zm9v!").is_err());
+    }
+
+    #[test]
+    fn streams() {
+        let bytes: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let mut writer = Base64::STANDARD.encoder(Vec::new());
+        for chunk in bytes.chunks(7) {
+            writer.write_all(chunk).expect("valid write");
+        }
+        let encoded = writer.finish().expect("valid encoding");
+        assert_eq!(encoded, encode(&bytes).into_bytes());
+
+        let mut dropped = Vec::new();
+        let mut unfinished = Base64::URL_SAFE.encoder(&mut dropped);
+        unfinished.write_all(b"fo").expect("valid write");
+        drop(unfinished);
+        assert_eq!(dropped, b"Zm8");
+
+        let mut decoded = Vec::new();
+        Base64::STANDARD
+            .decoder(encoded.as_slice())
+            .read_to_end(&mut decoded)
+            .expect("valid base64");
+        assert_eq!(decoded, bytes);
+
+        let err = Base64::STANDARD
+            .decoder(&b"Zm9v!"[..])
+            .read_to_end(&mut Vec::new())
+            .expect_err("invalid base64");
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,199 @@
+//! Hex encoding, two digits per byte.
+
+use super::{decode_slice, encode_slice, Decode, DecodeError, Decoder, Encode, Encoder, Mode};
+use std::io::{Read, Write};
+
+/// A hex configuration: the case of encoded digits, and the decoding [`Mode`]. Either case
+/// decodes in both modes.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::encoding::{Hex, Mode};
+///
+/// assert_eq!(Hex::LOWER.encode(b"\x01\xab"), "01ab");
+/// assert_eq!(Hex::UPPER.encode(b"\x01\xab"), "01AB");
+/// assert_eq!(Hex::LOWER.mode(Mode::Lenient).decode("01 AB").unwrap(), b"\x01\xab");
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[must_use]
+pub struct Hex {
+    upper: bool,
+    mode: Mode,
+}
+
+impl Hex {
+    /// Lower case digits.
+    pub const LOWER: Self = Self {
+        upper: false,
+        mode: Mode::Strict,
+    };
+
+    /// Upper case digits.
+    pub const UPPER: Self = Self {
+        upper: true,
+        mode: Mode::Strict,
+    };
+
+    /// Decode in `mode`.
+    pub const fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the encoding of `bytes`.
+    #[must_use]
+    pub fn encode(self, bytes: &[u8]) -> String {
+        let mut encoded = String::with_capacity(2 * bytes.len());
+        self.encode_to(bytes, &mut encoded);
+        encoded
+    }
+
+    /// Appends the encoding of `bytes` to `out`.
+    pub fn encode_to(self, bytes: &[u8], out: &mut String) {
+        encode_slice(self, bytes, out);
+    }
+
+    /// Returns the bytes encoded by `input`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `input` isn't valid hex in this mode.
+    pub fn decode(self, input: impl AsRef<[u8]>) -> Result<Vec<u8>, DecodeError> {
+        decode_slice(self.decode_state(), input.as_ref())
+    }
+
+    /// Returns a writer encoding everything written to it into `writer`.
+    pub fn encoder<W: Write>(self, writer: W) -> Encoder<W> {
+        Encoder::new(writer, self)
+    }
+
+    /// Returns a reader decoding the hex read from `reader`.
+    pub fn decoder<R: Read>(self, reader: R) -> Decoder<R> {
+        Decoder::new(reader, self.decode_state())
+    }
+
+    fn decode_state(self) -> DecodeState {
+        DecodeState {
+            mode: self.mode,
+            high: None,
+            position: 0,
+        }
+    }
+}
+
+/// Returns the lower case encoding of `bytes`.
+#[must_use]
+pub fn encode(bytes: &[u8]) -> String {
+    Hex::LOWER.encode(bytes)
+}
+
+/// Returns the bytes encoded by `input`, in strict mode.
+///
+/// # Errors
+///
+/// Returns an error if `input` has anything but an even number of hex digits.
+pub fn decode(input: impl AsRef<[u8]>) -> Result<Vec<u8>, DecodeError> {
+    Hex::LOWER.decode(input)
+}
+
+impl Encode for Hex {
+    fn encode(&mut self, bytes: &[u8], out: &mut Vec<u8>) {
+        let digits = if self.upper {
+            b"0123456789ABCDEF"
+        } else {
+            b"0123456789abcdef"
+        };
+        out.reserve(2 * bytes.len());
+        for &byte in bytes {
+            out.push(digits[usize::from(byte >> 4)]);
+            out.push(digits[usize::from(byte & 0xf)]);
+        }
+    }
+
+    fn finish(&mut self, _out: &mut Vec<u8>) {}
+}
+
+struct DecodeState {
+    mode: Mode,
+    /// First digit of the current byte.
+    high: Option<u8>,
+    /// Offset of the next input byte.
+    position: usize,
+}
+
+impl DecodeState {
+    fn error(&self, message: &'static str) -> DecodeError {
+        DecodeError::new("hex", self.position, message)
+    }
+}
+
+impl Decode for DecodeState {
+    fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        out.reserve(input.len() / 2);
+        for &byte in input {
+            if !(self.mode == Mode::Lenient && byte.is_ascii_whitespace()) {
+                let digit = char::from(byte)
+                    .to_digit(16)
+                    .ok_or_else(|| self.error("invalid character"))?;
+                #[allow(clippy::cast_possible_truncation)]
+                let digit = digit as u8;
+                match self.high.take() {
+                    Some(high) => out.push((high << 4) | digit),
+                    None => self.high = Some(digit),
+                }
+            }
+            self.position += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self, _out: &mut Vec<u8>) -> Result<(), DecodeError> {
+        match self.high {
+            Some(_) => Err(self.error("odd number of digits")),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn round_trips() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let encoded = encode(&bytes);
+        assert!(encoded.starts_with("000102") && encoded.ends_with("fdfeff"));
+        assert_eq!(decode(&encoded).expect("valid hex"), bytes);
+        assert_eq!(decode(Hex::UPPER.encode(&bytes)).expect("valid hex"), bytes);
+
+        let mut writer = Hex::UPPER.encoder(Vec::new());
+        writer.write_all(&bytes[..100]).expect("valid write");
+        writer.write_all(&bytes[100..]).expect("valid write");
+        let streamed = writer.finish().expect("valid encoding");
+        assert_eq!(streamed, Hex::UPPER.encode(&bytes).into_bytes());
+        let mut decoded = Vec::new();
+        Hex::LOWER
+            .decoder(streamed.as_slice())
+            .read_to_end(&mut decoded)
+            .expect("valid hex");
+        assert_eq!(decoded, bytes);
+    }
+
+    #[test]
+    fn decoding_modes() {
+        let error = |input: &str| decode(input).expect_err("invalid hex").to_string();
+        assert_eq!(error("0g"), "invalid hex at byte 1: invalid character");
+        assert_eq!(error("abc"), "invalid hex at byte 3: odd number of digits");
+        assert_eq!(error("ab cd"), "invalid hex at byte 2: invalid character");
+
+        let lenient = Hex::LOWER.mode(Mode::Lenient);
+        assert_eq!(
+            lenient.decode("de ad\nBE EF").expect("valid hex"),
+            b"\xde\xad\xbe\xef"
+        );
+        assert!(lenient.decode("de:ad").is_err());
+    }
+}
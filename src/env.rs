@@ -0,0 +1,203 @@
+//! Typed environment variables.
+//!
+//! [`var_parsed`] and [`var_or`] parse a variable with [`FromStr`], and [`flag`] reads a boolean
+//! in any of the usual spellings, so configuration knobs don't each hand-roll their parsing. A
+//! value which doesn't parse is a [`VarError`] naming the variable, the raw value and why it was
+//! rejected, rather than being silently ignored.
+
+use std::{error::Error, ffi::OsString, fmt, str::FromStr};
+
+/// What a boolean is expected to be, in errors.
+pub(crate) const EXPECTED_BOOL: &str = "expected 1, true, yes, on, 0, false, no or off";
+
+/// An environment variable whose value is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarError {
+    name: String,
+    value: OsString,
+    message: String,
+}
+
+impl VarError {
+    /// Name of the variable.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Raw value of the variable.
+    #[must_use]
+    pub fn value(&self) -> &OsString {
+        &self.value
+    }
+}
+
+impl fmt::Display for VarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid value `{}` for {}: {}",
+            self.value.to_string_lossy(),
+            self.name,
+            self.message
+        )
+    }
+}
+
+impl Error for VarError {}
+
+/// Returns the value of the variable `name` parsed as a `T`, or `None` if it isn't set.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::env;
+///
+/// # fn main() -> Result<(), env::VarError> {
+/// let threads: Option<usize> = env::var_parsed("EXAMPLE_THREADS")?;
+/// let threads = threads.unwrap_or(4);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the variable isn't valid Unicode or doesn't parse.
+pub fn var_parsed<T>(name: &str) -> Result<Option<T>, VarError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    var_with(name, |value| {
+        value.parse().map_err(|err: T::Err| err.to_string())
+    })
+}
+
+/// Returns the value of the variable `name` parsed as a `T`, or `default` if it isn't set.
+///
+/// # Errors
+///
+/// Returns an error if the variable isn't valid Unicode or doesn't parse.
+pub fn var_or<T>(name: &str, default: T) -> Result<T, VarError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    var_parsed(name).map(|value| value.unwrap_or(default))
+}
+
+/// Returns the value of the variable `name` parsed with [`parse_bool`], or `None` if it isn't set.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::env;
+///
+/// # fn main() -> Result<(), env::VarError> {
+/// let verbose = env::flag("EXAMPLE_VERBOSE")?.unwrap_or(false);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the variable isn't valid Unicode or isn't a boolean.
+pub fn flag(name: &str) -> Result<Option<bool>, VarError> {
+    var_with(name, |value| {
+        parse_bool(value).ok_or_else(|| EXPECTED_BOOL.into())
+    })
+}
+
+/// Parses `1`, `true`, `yes` or `on` as `true`, and `0`, `false`, `no` or `off` as `false`, in any
+/// case and ignoring surrounding whitespace.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::env::parse_bool;
+///
+/// assert_eq!(parse_bool("Yes"), Some(true));
+/// assert_eq!(parse_bool("0"), Some(false));
+/// assert_eq!(parse_bool("maybe"), None);
+/// ```
+#[must_use]
+pub fn parse_bool(value: &str) -> Option<bool> {
+    let value = value.trim();
+    let is = |spellings: &[&str]| {
+        spellings
+            .iter()
+            .any(|spelling| value.eq_ignore_ascii_case(spelling))
+    };
+    if is(&["1", "true", "yes", "on"]) {
+        Some(true)
+    } else if is(&["0", "false", "no", "off"]) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+fn var_with<T>(
+    name: &str,
+    parse: impl FnOnce(&str) -> Result<T, String>,
+) -> Result<Option<T>, VarError> {
+    let Some(value) = std::env::var_os(name) else {
+        return Ok(None);
+    };
+    let error = |message| VarError {
+        name: name.to_string(),
+        value: value.clone(),
+        message,
+    };
+    let text = value
+        .to_str()
+        .ok_or_else(|| error("not valid Unicode".to_string()))?;
+    parse(text).map(Some).map_err(error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::EnvGuard;
+
+    #[test]
+    fn parses_variables() {
+        let _env = EnvGuard::new()
+            .set("UTIL_ENV_TEST_COUNT", "12")
+            .set("UTIL_ENV_TEST_BAD", "twelve")
+            .set("UTIL_ENV_TEST_FLAG", " Off ")
+            .remove("UTIL_ENV_TEST_UNSET");
+
+        assert_eq!(var_parsed::<u32>("UTIL_ENV_TEST_COUNT"), Ok(Some(12)));
+        assert_eq!(var_parsed::<u32>("UTIL_ENV_TEST_UNSET"), Ok(None));
+        assert_eq!(var_or("UTIL_ENV_TEST_UNSET", 3_u32), Ok(3));
+        assert_eq!(var_or("UTIL_ENV_TEST_COUNT", 3_u32), Ok(12));
+        assert_eq!(flag("UTIL_ENV_TEST_FLAG"), Ok(Some(false)));
+        assert_eq!(flag("UTIL_ENV_TEST_UNSET"), Ok(None));
+
+        let err = var_or("UTIL_ENV_TEST_BAD", 3_u32).expect_err("invalid number");
+        assert_eq!(err.name(), "UTIL_ENV_TEST_BAD");
+        assert_eq!(err.value(), "twelve");
+        assert_eq!(
+            err.to_string(),
+            "invalid value `twelve` for UTIL_ENV_TEST_BAD: invalid digit found in string"
+        );
+        assert!(flag("UTIL_ENV_TEST_BAD")
+            .expect_err("invalid flag")
+            .to_string()
+            .ends_with("expected 1, true, yes, on, 0, false, no or off"));
+    }
+
+    #[test]
+    fn parses_bools() {
+        for value in ["1", "true", "TRUE", "yes", "Yes", "on"] {
+            assert_eq!(parse_bool(value), Some(true), "{value}");
+        }
+        for value in ["0", "false", "No", "no", "OFF", " off\n"] {
+            assert_eq!(parse_bool(value), Some(false), "{value}");
+        }
+        for value in ["", "2", "y", "enabled"] {
+            assert_eq!(parse_bool(value), None, "{value}");
+        }
+    }
+}
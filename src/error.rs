@@ -0,0 +1,270 @@
+//! Error context.
+//!
+//! [`Error`] boxes any error, and [`Context`] adds a message describing what was being done when
+//! it happened, such as `reading config`, keeping the original error as its source. Printed with
+//! `{:#}`, an error shows its whole chain on one line. Printed with `{:?}`, as `main` does when it
+//! returns one, it shows each cause on a line of its own:
+//!
+//! ```text
+//! loading settings.conf
+//!
+//! Caused by:
+//!     0: reading config
+//!     1: No such file or directory (os error 2)
+//! ```
+
+use std::{error::Error as StdError, fmt};
+
+/// A boxed error, shared between threads.
+type BoxError = Box<dyn StdError + Send + Sync + 'static>;
+
+/// A `Result` whose error defaults to [`Error`].
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Any error, with the context it was given.
+///
+/// It doesn't implement [`std::error::Error`] itself, so that any error can be converted into it
+/// with `?`, but converts into a `Box<dyn Error + Send + Sync>`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::error::{Context, Result};
+///
+/// fn port(value: &str) -> Result<u16> {
+///     value.parse::<u16>().with_context(|| format!("parsing port `{value}`"))
+/// }
+///
+/// let err = port("http").unwrap_err();
+/// assert_eq!(err.to_string(), "parsing port `http`");
+/// assert_eq!(format!("{err:#}"), "parsing port `http`: invalid digit found in string");
+/// ```
+#[must_use]
+pub struct Error {
+    inner: BoxError,
+}
+
+impl Error {
+    /// Creates an error with `message` and no source.
+    pub fn msg(message: impl fmt::Display) -> Self {
+        Self {
+            inner: Box::new(MessageError(message.to_string())),
+        }
+    }
+
+    /// Wraps this error in one describing `context`.
+    pub fn context(self, context: impl fmt::Display) -> Self {
+        Self {
+            inner: Box::new(ContextError {
+                context: context.to_string(),
+                source: self.inner,
+            }),
+        }
+    }
+
+    /// Returns this error followed by each of its sources, outermost first.
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> + '_ {
+        let outer: &(dyn StdError + 'static) = &*self.inner;
+        std::iter::successors(Some(outer), |&err| err.source())
+    }
+
+    /// Returns the innermost source, the error which started the chain.
+    #[must_use]
+    pub fn root_cause(&self) -> &(dyn StdError + 'static) {
+        self.chain().last().unwrap_or(&*self.inner)
+    }
+
+    /// Returns the first error in the chain of type `E`, if any.
+    #[must_use]
+    pub fn downcast_ref<E: StdError + 'static>(&self) -> Option<&E> {
+        self.chain().find_map(<dyn StdError>::downcast_ref)
+    }
+}
+
+impl<E: StdError + Send + Sync + 'static> From<E> for Error {
+    fn from(err: E) -> Self {
+        Self {
+            inner: Box::new(err),
+        }
+    }
+}
+
+impl From<Error> for Box<dyn StdError + Send + Sync + 'static> {
+    fn from(err: Error) -> Self {
+        err.inner
+    }
+}
+
+impl From<Error> for Box<dyn StdError + 'static> {
+    fn from(err: Error) -> Self {
+        err.inner
+    }
+}
+
+impl fmt::Display for Error {
+    /// Formats the outermost message, or with `{:#}`, every message in the chain joined by `: `.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.inner)?;
+        if f.alternate() {
+            for cause in self.chain().skip(1) {
+                write!(f, ": {cause}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Error {
+    /// Formats the outermost message followed by a numbered list of its causes, or with `{:#?}`,
+    /// the errors' own `Debug` output.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            return f.debug_tuple("Error").field(&self.inner).finish();
+        }
+        write!(f, "{}", self.inner)?;
+        let causes: Vec<_> = self.chain().skip(1).collect();
+        match causes[..] {
+            [] => Ok(()),
+            [cause] => write!(f, "\n\nCaused by:\n    {cause}"),
+            _ => {
+                write!(f, "\n\nCaused by:")?;
+                for (i, cause) in causes.iter().enumerate() {
+                    write!(f, "\n    {i}: {cause}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An error with only a message.
+#[derive(Debug)]
+struct MessageError(String);
+
+impl fmt::Display for MessageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl StdError for MessageError {}
+
+/// An error describing what was being done when its source happened.
+#[derive(Debug)]
+struct ContextError {
+    context: String,
+    source: BoxError,
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.context)
+    }
+}
+
+impl StdError for ContextError {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Adds context to errors, and to missing values.
+pub trait Context<T> {
+    /// Converts the error into an [`Error`] describing `context`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `self` is an error or `None`.
+    fn context(self, context: impl fmt::Display) -> Result<T>;
+
+    /// Converts the error into an [`Error`] describing the context `f` returns, only calling it
+    /// if there's an error.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `self` is an error or `None`.
+    fn with_context<C: fmt::Display>(self, f: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T, E: StdError + Send + Sync + 'static> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl fmt::Display) -> Result<T> {
+        self.map_err(|err| Error::from(err).context(context))
+    }
+
+    fn with_context<C: fmt::Display>(self, f: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|err| Error::from(err).context(f()))
+    }
+}
+
+impl<T> Context<T> for Result<T> {
+    fn context(self, context: impl fmt::Display) -> Result<T> {
+        self.map_err(|err| err.context(context))
+    }
+
+    fn with_context<C: fmt::Display>(self, f: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|err| err.context(f()))
+    }
+}
+
+impl<T> Context<T> for Option<T> {
+    /// Converts `None` into an [`Error`] with `context` as its message.
+    fn context(self, context: impl fmt::Display) -> Result<T> {
+        self.ok_or_else(|| Error::msg(context))
+    }
+
+    fn with_context<C: fmt::Display>(self, f: impl FnOnce() -> C) -> Result<T> {
+        self.ok_or_else(|| Error::msg(f()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, ConfigError};
+    use std::io;
+
+    fn load(path: &str) -> Result<Config> {
+        Config::load(path).context("reading config")
+    }
+
+    #[test]
+    fn chains() {
+        let err = load("/nonexistent/settings.conf")
+            .context("loading settings")
+            .unwrap_err();
+        assert_eq!(err.to_string(), "loading settings");
+        let messages: Vec<_> = err.chain().map(ToString::to_string).collect();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1], "reading config");
+        assert_eq!(
+            format!("{err:#}"),
+            format!("loading settings: reading config: {}", messages[2])
+        );
+        assert_eq!(
+            format!("{err:?}"),
+            format!(
+                "loading settings\n\nCaused by:\n    0: reading config\n    1: {}",
+                messages[2]
+            )
+        );
+        assert!(err.downcast_ref::<ConfigError>().is_some());
+        assert!(err.downcast_ref::<io::Error>().is_none());
+        assert_eq!(err.root_cause().to_string(), messages[2]);
+
+        let err = Error::from(io::Error::other("disk full")).context("saving");
+        assert_eq!(format!("{err:?}"), "saving\n\nCaused by:\n    disk full");
+        assert_eq!(format!("{:?}", Error::msg("plain")), "plain");
+        let boxed: Box<dyn StdError + Send + Sync> = err.into();
+        assert_eq!(boxed.to_string(), "saving");
+    }
+
+    #[test]
+    fn options() {
+        assert_eq!(Some(1).context("missing").ok(), Some(1));
+        let err = None::<u8>
+            .with_context(|| format!("missing `{}`", "net.port"))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "missing `net.port`");
+        assert!(err.chain().nth(1).is_none());
+    }
+}
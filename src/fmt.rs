@@ -0,0 +1,537 @@
+//! Human-readable formatting and parsing of durations, sizes and rates.
+//!
+//! Each value is scaled to the largest unit it has at least one of once rounded and printed with
+//! about three significant digits for durations, or one decimal place for sizes and rates, e.g.
+//! `1.24ms`, `3.5 MiB` and `2.1 GB/s`. [`parse_duration`] and [`parse_bytes`] read durations and
+//! sizes back from config files and command line flags.
+//!
+//! [`hexdump`] shows binary data as offsets, hex bytes and ASCII, like `hexdump -C`.
+
+use std::{
+    error::Error,
+    fmt::{self, Write as _},
+    io,
+    ops::{Bound, RangeBounds},
+    time::Duration,
+};
+
+/// Binary units of [`format_bytes`].
+const BYTE_UNITS: [&str; 7] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"];
+/// Decimal units of [`format_rate`], following the convention for transfer rates.
+const RATE_UNITS: [&str; 6] = ["B/s", "KB/s", "MB/s", "GB/s", "TB/s", "PB/s"];
+
+/// Formats `duration` in nanoseconds, microseconds, milliseconds or seconds.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::fmt::format_duration;
+///
+/// assert_eq!(format_duration(Duration::from_micros(1240)), "1.24ms");
+/// assert_eq!(format_duration(Duration::from_nanos(250)), "250ns");
+/// assert_eq!(format_duration(Duration::from_secs(90)), "90.0s");
+/// ```
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos();
+    if nanos < 1_000 {
+        return format!("{nanos}ns");
+    }
+    // A unit is only used while its value rounds to under 1000, so 999.9µs is 1.00ms.
+    let (value, unit) = [(1e3, "µs"), (1e6, "ms")]
+        .into_iter()
+        .map(|(scale, unit)| (nanos as f64 / scale, unit))
+        .find(|&(value, _)| round_to(value, significant_precision(value)) < 1000.0)
+        .unwrap_or((nanos as f64 / 1e9, "s"));
+    let precision = significant_precision(value);
+    if unit == "s" && precision == 0 {
+        // Whole seconds come from the integer count, as an `f64` can't hold long durations exactly.
+        let secs = duration
+            .as_secs()
+            .saturating_add(u64::from(duration.subsec_nanos() >= 500_000_000));
+        return format!("{secs}s");
+    }
+    format!("{value:.precision$}{unit}")
+}
+
+/// Decimal places which show `value` with three significant digits once rounded, or none at 100 and
+/// over.
+fn significant_precision(value: f64) -> usize {
+    // Rounding can carry into another digit, e.g. 9.996 to 10.00, so compare the rounded value.
+    match value {
+        value if round_to(value, 2) < 10.0 => 2,
+        value if round_to(value, 1) < 100.0 => 1,
+        _ => 0,
+    }
+}
+
+/// Rounds `value` to `precision` decimal places.
+#[allow(clippy::cast_possible_truncation, clippy::cast_possible_wrap)]
+fn round_to(value: f64, precision: usize) -> f64 {
+    let scale = 10f64.powi(precision as i32);
+    (value * scale).round() / scale
+}
+
+/// Formats a size of `bytes` in binary units.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::fmt::format_bytes;
+///
+/// assert_eq!(format_bytes(3_670_016), "3.5 MiB");
+/// assert_eq!(format_bytes(512), "512 B");
+/// ```
+#[must_use]
+#[allow(clippy::cast_precision_loss)]
+pub fn format_bytes(bytes: u64) -> String {
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while unit + 1 < BYTE_UNITS.len() && round_to(value, 1) >= 1024.0 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", BYTE_UNITS[unit])
+}
+
+/// Formats a rate of `bytes_per_second` in decimal units.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::fmt::format_rate;
+///
+/// assert_eq!(format_rate(2.1e9), "2.1 GB/s");
+/// assert_eq!(format_rate(120.0), "120 B/s");
+/// ```
+#[must_use]
+pub fn format_rate(bytes_per_second: f64) -> String {
+    if !bytes_per_second.is_finite() || bytes_per_second.abs().round() < 1000.0 {
+        return format!("{bytes_per_second:.0} {}", RATE_UNITS[0]);
+    }
+    let mut value = bytes_per_second / 1000.0;
+    let mut unit = 1;
+    while unit + 1 < RATE_UNITS.len() && round_to(value.abs(), 1) >= 1000.0 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", RATE_UNITS[unit])
+}
+
+/// Nanoseconds per unit accepted by [`parse_duration`].
+const DURATION_UNITS: [(&str, u128); 9] = [
+    ("ns", 1),
+    ("us", 1_000),
+    ("µs", 1_000),
+    ("ms", 1_000_000),
+    ("s", 1_000_000_000),
+    ("m", 60 * 1_000_000_000),
+    ("h", 60 * 60 * 1_000_000_000),
+    ("d", 24 * 60 * 60 * 1_000_000_000),
+    ("w", 7 * 24 * 60 * 60 * 1_000_000_000),
+];
+
+/// An invalid duration or size string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    input: String,
+    kind: &'static str,
+    message: &'static str,
+}
+
+impl ParseError {
+    fn new(input: &str, kind: &'static str, message: &'static str) -> Self {
+        Self {
+            input: input.to_string(),
+            kind,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid {} `{}`: {}",
+            self.kind, self.input, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// Splits a leading decimal number off `text`, returning its integer and fractional digits and the
+/// rest of `text`.
+fn split_number(text: &str) -> (&str, &str, &str) {
+    let integer_end = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (integer, rest) = text.split_at(integer_end);
+    let Some(fraction) = rest.strip_prefix('.') else {
+        return (integer, "", rest);
+    };
+    let fraction_end = fraction
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(fraction.len());
+    let (fraction, rest) = fraction.split_at(fraction_end);
+    (integer, fraction, rest)
+}
+
+/// Multiplies the decimal number with digits `integer.fraction` by `unit`, truncating to an
+/// integer, or returns `None` on overflow.
+fn scale_decimal(integer: &str, fraction: &str, unit: u128) -> Option<u128> {
+    // Digits past the 18th can't change the result of any unit below 10^20.
+    let fraction = &fraction[..fraction.len().min(18)];
+    let whole = if integer.is_empty() {
+        0
+    } else {
+        integer.parse::<u128>().ok()?.checked_mul(unit)?
+    };
+    let part = if fraction.is_empty() {
+        0
+    } else {
+        let digits = u32::try_from(fraction.len()).ok()?;
+        fraction.parse::<u128>().ok()?.checked_mul(unit)? / 10u128.pow(digits)
+    };
+    whole.checked_add(part)
+}
+
+/// Parses a duration made of one or more numbers, each followed by a unit of `ns`, `us` (or `µs`),
+/// `ms`, `s`, `m`, `h`, `d` or `w`, such as `1h30m15s` or `1.5s`. Spaces between the parts are
+/// allowed.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::fmt::parse_duration;
+///
+/// assert_eq!(parse_duration("1h30m15s"), Ok(Duration::from_secs(5415)));
+/// assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+/// assert!(parse_duration("10").is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the string is empty, a number is missing its unit, a unit is unknown or the
+/// duration overflows [`Duration`].
+pub fn parse_duration(input: &str) -> Result<Duration, ParseError> {
+    let error = |message| ParseError::new(input, "duration", message);
+    let mut rest = input.trim();
+    if rest.is_empty() {
+        return Err(error("empty duration"));
+    }
+    let mut nanos: u128 = 0;
+    while !rest.is_empty() {
+        let (integer, fraction, after) = split_number(rest);
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(error("expected a number"));
+        }
+        let unit_end = after
+            .find(|c: char| c.is_ascii_digit() || c == '.' || c.is_whitespace())
+            .unwrap_or(after.len());
+        let (unit, after) = after.split_at(unit_end);
+        if unit.is_empty() {
+            return Err(error("missing unit, such as `s` or `ms`"));
+        }
+        let &(_, unit) = DURATION_UNITS
+            .iter()
+            .find(|(name, _)| *name == unit)
+            .ok_or_else(|| error("unknown unit, expected ns, us, ms, s, m, h, d or w"))?;
+        nanos = scale_decimal(integer, fraction, unit)
+            .and_then(|part| nanos.checked_add(part))
+            .ok_or_else(|| error("duration is too long"))?;
+        rest = after.trim_start();
+    }
+    let secs = u64::try_from(nanos / 1_000_000_000).map_err(|_| error("duration is too long"))?;
+    let subsec = u32::try_from(nanos % 1_000_000_000).unwrap_or_default();
+    Ok(Duration::new(secs, subsec))
+}
+
+/// Parses a size in bytes from a number followed by an optional unit: `B`, decimal units `KB`
+/// through `EB`, or binary units `KiB` through `EiB`, also written `K` through `E`. Units are case
+/// insensitive and may follow a space, e.g. `10MiB`, `1.5 GB` or `64k`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::fmt::parse_bytes;
+///
+/// assert_eq!(parse_bytes("10MiB"), Ok(10 << 20));
+/// assert_eq!(parse_bytes("1.5 KB"), Ok(1500));
+/// assert_eq!(parse_bytes("4096"), Ok(4096));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the string doesn't start with a number, the unit is unknown or the size
+/// overflows a `u64`.
+pub fn parse_bytes(input: &str) -> Result<u64, ParseError> {
+    let error = |message| ParseError::new(input, "size", message);
+    let (integer, fraction, unit) = split_number(input.trim());
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(error("expected a number"));
+    }
+    let unit = unit.trim_start().to_ascii_lowercase();
+    let (prefix, base) = if let Some(prefix) = unit.strip_suffix("ib") {
+        (prefix, 1024)
+    } else if let Some(prefix) = unit.strip_suffix('b') {
+        (prefix, 1000)
+    } else {
+        (unit.as_str(), 1024)
+    };
+    let power = match prefix {
+        "" if base == 1000 || unit.is_empty() => 0,
+        "k" => 1,
+        "m" => 2,
+        "g" => 3,
+        "t" => 4,
+        "p" => 5,
+        "e" => 6,
+        _ => {
+            return Err(error(
+                "unknown unit, expected B, KB, KiB, MB, MiB, GB, GiB, ...",
+            ))
+        }
+    };
+    let bytes = scale_decimal(integer, fraction, u128::pow(base, power))
+        .and_then(|bytes| u64::try_from(bytes).ok())
+        .ok_or_else(|| error("size is too large"))?;
+    Ok(bytes)
+}
+
+/// Formats `bytes` as lines of an offset, 16 bytes in hex and the same bytes as ASCII, with `.`
+/// for bytes which aren't printable. Returns a [`HexDump`] to change the width or range shown.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::fmt::hexdump;
+///
+/// assert_eq!(
+///     hexdump(b"Hello, world!\n").to_string(),
+///     "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a        |Hello, world!.|\n"
+/// );
+/// ```
+pub fn hexdump(bytes: &[u8]) -> HexDump<'_> {
+    HexDump {
+        bytes,
+        width: 16,
+        start: 0,
+        end: bytes.len(),
+    }
+}
+
+/// Writes [`hexdump`] of `bytes` to `writer`.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_hexdump(writer: &mut impl io::Write, bytes: &[u8]) -> io::Result<()> {
+    hexdump(bytes).write_to(writer)
+}
+
+/// A hex dump of a byte slice. Created by [`hexdump`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    width: usize,
+    start: usize,
+    end: usize,
+}
+
+impl HexDump<'_> {
+    /// Show `width` bytes per line, 16 by default, in groups of 8.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is 0.
+    pub fn width(mut self, width: usize) -> Self {
+        assert!(width > 0, "hex dump width must be positive");
+        self.width = width;
+        self
+    }
+
+    /// Show only the bytes in `range`, clamped to the slice, labeled with their offsets in the
+    /// whole slice.
+    pub fn range(mut self, range: impl RangeBounds<usize>) -> Self {
+        let len = self.bytes.len();
+        self.start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start.saturating_add(1),
+            Bound::Unbounded => 0,
+        }
+        .min(len);
+        self.end = match range.end_bound() {
+            Bound::Included(&end) => end.saturating_add(1),
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => len,
+        }
+        .clamp(self.start, len);
+        self
+    }
+
+    /// Writes the dump to `writer`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn write_to(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        write!(writer, "{self}")
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines = self.bytes[self.start..self.end].chunks(self.width);
+        for (line, chunk) in lines.enumerate() {
+            write!(f, "{:08x} ", self.start + line * self.width)?;
+            for column in 0..self.width {
+                if column % 8 == 0 {
+                    f.write_char(' ')?;
+                }
+                match chunk.get(column) {
+                    Some(byte) => write!(f, "{byte:02x} ")?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str(" |")?;
+            for &byte in chunk {
+                let printable = byte.is_ascii_graphic() || byte == b' ';
+                f.write_char(if printable { char::from(byte) } else { '.' })?;
+            }
+            f.write_str("|\n")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_dumps() {
+        let bytes: Vec<u8> = (0..40).chain(*b"AZ az~").collect();
+        let dump = hexdump(&bytes).to_string();
+        let lines: Vec<_> = dump.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[0],
+            "00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|"
+        );
+        assert_eq!(
+            lines[2],
+            "00000020  20 21 22 23 24 25 26 27  41 5a 20 61 7a 7e        | !\"#$%&'AZ az~|"
+        );
+
+        let narrow = hexdump(&bytes).width(4).range(41..=43).to_string();
+        assert_eq!(narrow, "00000029  5a 20 61     |Z a|\n");
+        assert_eq!(hexdump(&bytes).range(50..).to_string(), "");
+        assert_eq!(hexdump(&[]).to_string(), "");
+
+        let mut written = Vec::new();
+        write_hexdump(&mut written, &bytes).expect("valid write");
+        assert_eq!(written, dump.into_bytes());
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(format_duration(Duration::ZERO), "0ns");
+        assert_eq!(format_duration(Duration::from_nanos(999)), "999ns");
+        assert_eq!(format_duration(Duration::from_nanos(1_800)), "1.80µs");
+        assert_eq!(format_duration(Duration::from_nanos(12_345)), "12.3µs");
+        assert_eq!(format_duration(Duration::from_millis(100)), "100ms");
+        assert_eq!(format_duration(Duration::from_millis(1500)), "1.50s");
+        assert_eq!(format_duration(Duration::from_secs(1234)), "1234s");
+        assert_eq!(format_duration(Duration::from_millis(99_960)), "100s");
+        assert_eq!(format_duration(Duration::from_millis(1_234_500)), "1235s");
+        assert_eq!(
+            format_duration(Duration::from_secs(u64::MAX / 2)),
+            "9223372036854775807s"
+        );
+        assert_eq!(format_duration(Duration::MAX), "18446744073709551615s");
+        // Values which round up to the next unit or digit move to it.
+        assert_eq!(format_duration(Duration::from_nanos(999_499)), "999µs");
+        assert_eq!(format_duration(Duration::from_nanos(999_500)), "1.00ms");
+        assert_eq!(format_duration(Duration::from_nanos(999_999_999)), "1.00s");
+        assert_eq!(format_duration(Duration::from_nanos(9_999)), "10.0µs");
+        assert_eq!(format_duration(Duration::from_nanos(99_999)), "100µs");
+    }
+
+    #[test]
+    fn bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(1023), "1023 B");
+        assert_eq!(format_bytes(1024), "1.0 KiB");
+        assert_eq!(format_bytes(12_288), "12.0 KiB");
+        assert_eq!(format_bytes(5 << 30), "5.0 GiB");
+        assert_eq!(format_bytes(u64::MAX), "16.0 EiB");
+        assert_eq!(format_bytes(1_048_575), "1.0 MiB");
+        assert_eq!(format_bytes(1_048_524), "1023.9 KiB");
+        assert_eq!(format_bytes((1 << 30) - 1), "1.0 GiB");
+    }
+
+    #[test]
+    fn rates() {
+        assert_eq!(format_rate(0.0), "0 B/s");
+        assert_eq!(format_rate(999.4), "999 B/s");
+        assert_eq!(format_rate(999.6), "1.0 KB/s");
+        assert_eq!(format_rate(999_960.0), "1.0 MB/s");
+        assert_eq!(format_rate(999_940.0), "999.9 KB/s");
+        assert_eq!(format_rate(1500.0), "1.5 KB/s");
+        assert_eq!(format_rate(2.5e9), "2.5 GB/s");
+        assert_eq!(format_rate(3e18), "3000.0 PB/s");
+        assert_eq!(format_rate(f64::INFINITY), "inf B/s");
+    }
+
+    #[test]
+    fn parsed_durations() {
+        assert_eq!(parse_duration("1h30m15s"), Ok(Duration::from_secs(5415)));
+        assert_eq!(parse_duration(" 2m 30s "), Ok(Duration::from_secs(150)));
+        assert_eq!(parse_duration("1.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_duration(".25ms"), Ok(Duration::from_micros(250)));
+        assert_eq!(parse_duration("10us"), parse_duration("10µs"));
+        assert_eq!(parse_duration("1w1d"), Ok(Duration::from_hours(8 * 24)));
+        assert_eq!(parse_duration("0ns"), Ok(Duration::ZERO));
+
+        assert_eq!(
+            parse_duration("10").map_err(|err| err.to_string()),
+            Err("invalid duration `10`: missing unit, such as `s` or `ms`".to_string())
+        );
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("1s-").is_err());
+        assert!(parse_duration("99999999999999999999999w").is_err());
+    }
+
+    #[test]
+    fn parsed_sizes() {
+        assert_eq!(parse_bytes("10MiB"), Ok(10 << 20));
+        assert_eq!(parse_bytes("10 mb"), Ok(10_000_000));
+        assert_eq!(parse_bytes("64k"), Ok(64 << 10));
+        assert_eq!(parse_bytes("1.5GiB"), Ok(3 << 29));
+        assert_eq!(parse_bytes("512B"), Ok(512));
+        assert_eq!(
+            parse_bytes("16EiB"),
+            Err(ParseError::new("16EiB", "size", "size is too large"))
+        );
+        assert_eq!(
+            parse_bytes("3 lb").map_err(|err| err.to_string()),
+            Err(
+                "invalid size `3 lb`: unknown unit, expected B, KB, KiB, MB, MiB, GB, GiB, ..."
+                    .to_string()
+            )
+        );
+        assert!(parse_bytes("MiB").is_err());
+        assert!(parse_bytes("1iB").is_err());
+    }
+}
@@ -0,0 +1,634 @@
+//! Filesystem helpers.
+//!
+//! [`TempDir`] and [`TempFile`] create uniquely named paths in the system's temporary directory,
+//! or another parent, and remove them when dropped, so tests and exporters get a scratch location
+//! which doesn't collide with other processes or leak files. [`keep`](TempDir::keep) persists them
+//! instead.
+//!
+//! [`Watcher`] polls files and directories for changes, for tools which rerun something whenever
+//! its inputs are saved, without depending on platform notification APIs.
+
+use crate::signal::ShutdownFlag;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Prefix of temporary file and directory names.
+const PREFIX: &str = ".util-tmp-";
+/// Attempts at finding an unused name before giving up.
+const ATTEMPTS: u32 = 64;
+
+/// Counter making names unique within the process, even with a seeded random generator.
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Calls `create` with unique paths in `parent` until one doesn't exist yet.
+fn create_unique<T>(
+    parent: &Path,
+    suffix: &str,
+    mut create: impl FnMut(&Path) -> io::Result<T>,
+) -> io::Result<(PathBuf, T)> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos();
+    for _ in 0..ATTEMPTS {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        let name = format!(
+            "{PREFIX}{}-{id}-{:016x}{suffix}",
+            std::process::id(),
+            u64::from(nanos) ^ crate::rand::u64()
+        );
+        let path = parent.join(name);
+        match create(&path) {
+            Ok(created) => return Ok((path, created)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        "too many temporary files with the same name",
+    ))
+}
+
+/// A directory removed, with everything in it, when dropped.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::fs::TempDir;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let dir = TempDir::new()?;
+/// let path = dir.path().join("report.csv");
+/// std::fs::write(&path, "anchor,hits\n")?;
+/// drop(dir);
+/// assert!(!path.exists());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct TempDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempDir {
+    /// Creates a directory in the system's temporary directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be created.
+    pub fn new() -> io::Result<Self> {
+        Self::new_in(std::env::temp_dir())
+    }
+
+    /// Creates a directory in `parent`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the directory can't be created.
+    pub fn new_in(parent: impl AsRef<Path>) -> io::Result<Self> {
+        let (path, ()) = create_unique(parent.as_ref(), "", |path| fs::create_dir(path))?;
+        Ok(Self { path, keep: false })
+    }
+
+    /// Returns the path of the directory.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Keeps the directory instead of removing it, and returns its path.
+    #[must_use]
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        std::mem::take(&mut self.path)
+    }
+
+    /// Removes the directory and everything in it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if anything can't be removed, unlike dropping, which ignores errors.
+    pub fn close(self) -> io::Result<()> {
+        fs::remove_dir_all(self.keep())
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// A file, open for reading and writing, removed when dropped.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use util_lib_rs::fs::TempFile;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut file = TempFile::with_suffix(".txt")?;
+/// writeln!(file, "scratch")?;
+/// assert_eq!(std::fs::read_to_string(file.path())?, "scratch\n");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct TempFile {
+    path: PathBuf,
+    file: File,
+    keep: bool,
+}
+
+impl TempFile {
+    /// Creates a file in the system's temporary directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created.
+    pub fn new() -> io::Result<Self> {
+        Self::new_in(std::env::temp_dir(), "")
+    }
+
+    /// Creates a file in the system's temporary directory whose name ends with `suffix`, such as
+    /// an extension.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created.
+    pub fn with_suffix(suffix: &str) -> io::Result<Self> {
+        Self::new_in(std::env::temp_dir(), suffix)
+    }
+
+    /// Creates a file in `parent` whose name ends with `suffix`. Create it next to the file it
+    /// will [replace](Self::persist), so persisting is a rename within one filesystem.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created.
+    pub fn new_in(parent: impl AsRef<Path>, suffix: &str) -> io::Result<Self> {
+        let (path, file) = create_unique(parent.as_ref(), suffix, |path| {
+            OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(path)
+        })?;
+        Ok(Self {
+            path,
+            file,
+            keep: false,
+        })
+    }
+
+    /// Returns the path of the file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the open file.
+    #[must_use]
+    pub fn as_file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns the open file, mutably.
+    #[must_use]
+    pub fn as_file_mut(&mut self) -> &mut File {
+        &mut self.file
+    }
+
+    /// Keeps the file instead of removing it, closes it, and returns its path.
+    #[must_use]
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        std::mem::take(&mut self.path)
+    }
+
+    /// Flushes the file and renames it to `path`, replacing any file there, so readers of `path`
+    /// never see it partly written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be flushed or renamed, in which case it's removed.
+    pub fn persist(mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.path, path)?;
+        let _ = self.keep();
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+impl Write for TempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Read for TempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.file.read(buf)
+    }
+}
+
+impl Seek for TempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.file.seek(pos)
+    }
+}
+
+/// What happened to a watched file.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    /// The file was created.
+    Created,
+    /// The file's contents or modification time changed.
+    Modified,
+    /// The file was removed.
+    Removed,
+}
+
+/// A change to a watched file, reported by [`Watcher`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Event {
+    /// What happened.
+    pub kind: EventKind,
+    /// The file it happened to.
+    pub path: PathBuf,
+}
+
+/// What a file looked like when last polled.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Stamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+/// Watches files, and every file under watched directories, by polling their modification times
+/// and sizes. Paths which don't exist yet can be watched, and report [`EventKind::Created`] once
+/// they do.
+///
+/// Changes are only seen if they change a file's size or modification time, so two writes within
+/// the filesystem's timestamp resolution may be reported as one, or not at all if the size is
+/// unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::fs::{EventKind, TempDir, Watcher};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let dir = TempDir::new()?;
+/// let mut watcher = Watcher::new().watch(dir.path());
+/// assert!(watcher.poll().is_empty());
+/// std::fs::write(dir.path().join("main.rs"), "fn main() {}")?;
+/// let events = watcher.poll();
+/// assert_eq!(events[0].kind, EventKind::Created);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Watcher {
+    paths: Vec<PathBuf>,
+    interval: Duration,
+    /// Files seen by the last poll, or `None` before the first.
+    snapshot: Option<BTreeMap<PathBuf, Stamp>>,
+}
+
+impl Default for Watcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Watcher {
+    /// Creates a watcher with no paths, polling every half second when [spawned](Self::spawn).
+    pub fn new() -> Self {
+        Self {
+            paths: Vec::new(),
+            interval: Duration::from_millis(500),
+            snapshot: None,
+        }
+    }
+
+    /// Watches `path`, and every file under it if it's a directory. Symbolic links to
+    /// directories aren't followed.
+    pub fn watch(mut self, path: impl Into<PathBuf>) -> Self {
+        self.paths.push(path.into());
+        self.snapshot = None;
+        self
+    }
+
+    /// Sets how long a [spawned](Self::spawn) watcher waits between polls.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Returns the changes since the last poll, sorted by path. The first poll records the
+    /// current state of the watched paths and reports nothing.
+    ///
+    /// Files which can't be read, such as those removed while scanning, are skipped.
+    #[must_use]
+    pub fn poll(&mut self) -> Vec<Event> {
+        let mut current = BTreeMap::new();
+        for path in &self.paths {
+            scan(path, &mut current);
+        }
+        let Some(previous) = &self.snapshot else {
+            self.snapshot = Some(current);
+            return Vec::new();
+        };
+        let removed = previous
+            .keys()
+            .filter(|path| !current.contains_key(*path))
+            .map(|path| (path, EventKind::Removed));
+        let changed = current
+            .iter()
+            .filter_map(|(path, stamp)| match previous.get(path) {
+                None => Some((path, EventKind::Created)),
+                Some(previous) if previous != stamp => Some((path, EventKind::Modified)),
+                Some(_) => None,
+            });
+        let mut events: Vec<_> = removed
+            .chain(changed)
+            .map(|(path, kind)| Event {
+                kind,
+                path: path.clone(),
+            })
+            .collect();
+        events.sort_by(|a, b| a.path.cmp(&b.path));
+        self.snapshot = Some(current);
+        events
+    }
+
+    /// Polls on a thread of its own every [interval](Self::interval), calling `handler` with
+    /// each change, until the returned handle is stopped or dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use util_lib_rs::fs::Watcher;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let _watching = Watcher::new()
+    ///     .watch("src")
+    ///     .interval(Duration::from_millis(200))
+    ///     .spawn(|event| eprintln!("{:?} {}, rebuilding", event.kind, event.path.display()))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread can't be spawned.
+    pub fn spawn(
+        mut self,
+        mut handler: impl FnMut(Event) + Send + 'static,
+    ) -> io::Result<WatchHandle> {
+        let stop = ShutdownFlag::new();
+        let _ = self.poll();
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-watcher".to_string())
+            .spawn({
+                let stop = stop.clone();
+                move || {
+                    while !stop.wait_timeout(self.interval) {
+                        self.poll().into_iter().for_each(&mut handler);
+                    }
+                }
+            })?;
+        Ok(WatchHandle {
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    /// Polls on a thread of its own like [`spawn`](Self::spawn), sending each change to the
+    /// returned receiver.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread can't be spawned.
+    pub fn channel(self) -> io::Result<(WatchHandle, Receiver<Event>)> {
+        let (sender, receiver) = mpsc::channel();
+        let handle = self.spawn(move |event| {
+            let _ = sender.send(event);
+        })?;
+        Ok((handle, receiver))
+    }
+}
+
+/// Records `path`, or every file under it if it's a directory, in `files`.
+fn scan(path: &Path, files: &mut BTreeMap<PathBuf, Stamp>) {
+    let Ok(metadata) = fs::metadata(path) else {
+        return;
+    };
+    if !metadata.is_dir() {
+        files.insert(
+            path.to_path_buf(),
+            Stamp {
+                modified: metadata.modified().ok(),
+                len: metadata.len(),
+            },
+        );
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => scan(&entry.path(), files),
+            Ok(_) => {
+                if let Ok(metadata) = entry.metadata() {
+                    files.insert(
+                        entry.path(),
+                        Stamp {
+                            modified: metadata.modified().ok(),
+                            len: metadata.len(),
+                        },
+                    );
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+/// A [`Watcher`] polling on a thread of its own, stopped when dropped.
+#[derive(Debug)]
+#[must_use]
+pub struct WatchHandle {
+    stop: ShutdownFlag,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    /// Stops polling, waiting for a poll in progress to finish.
+    pub fn stop(mut self) {
+        self.join();
+    }
+
+    fn join(&mut self) {
+        self.stop.set();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_dirs() {
+        let dir = TempDir::new().expect("created dir");
+        let other = TempDir::new_in(dir.path()).expect("created dir");
+        assert!(other.path().starts_with(dir.path()));
+        assert_ne!(dir.path(), other.path());
+        fs::write(other.path().join("file"), "data").expect("valid write");
+        let path = other.path().to_path_buf();
+        drop(other);
+        assert!(!path.exists());
+
+        let kept = TempDir::new_in(dir.path()).expect("created dir").keep();
+        assert!(kept.is_dir());
+        let closed = TempDir::new_in(dir.path()).expect("created dir");
+        let closed_path = closed.path().to_path_buf();
+        closed.close().expect("removed dir");
+        assert!(!closed_path.exists());
+
+        let path = dir.path().to_path_buf();
+        drop(dir);
+        assert!(!path.exists() && !kept.exists());
+    }
+
+    #[test]
+    fn temp_files() {
+        let dir = TempDir::new().expect("created dir");
+        let mut file = TempFile::new_in(dir.path(), ".json").expect("created file");
+        assert!(file.path().starts_with(dir.path()));
+        assert!(file.path().to_string_lossy().ends_with(".json"));
+        file.write_all(b"{}").expect("valid write");
+        file.seek(SeekFrom::Start(0)).expect("valid seek");
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).expect("valid read");
+        assert_eq!(contents, "{}");
+        let path = file.path().to_path_buf();
+        drop(file);
+        assert!(!path.exists());
+
+        let kept = TempFile::new_in(dir.path(), "")
+            .expect("created file")
+            .keep();
+        assert!(kept.is_file());
+
+        let target = dir.path().join("report.json");
+        let mut file = TempFile::new_in(dir.path(), "").expect("created file");
+        file.write_all(b"[1]").expect("valid write");
+        let temp = file.path().to_path_buf();
+        file.persist(&target).expect("renamed file");
+        assert_eq!(fs::read_to_string(&target).expect("valid read"), "[1]");
+        assert!(!temp.exists());
+    }
+
+    #[test]
+    fn watches_paths() {
+        let dir = TempDir::new().expect("created dir");
+        let nested = dir.path().join("src");
+        let single = dir.path().join("Cargo.toml");
+        let mut watcher = Watcher::new().watch(&nested).watch(&single);
+        assert!(watcher.poll().is_empty());
+
+        fs::create_dir(&nested).expect("created dir");
+        fs::write(nested.join("lib.rs"), "").expect("valid write");
+        fs::write(&single, "[package]").expect("valid write");
+        fs::write(dir.path().join("ignored"), "").expect("valid write");
+        let event = |kind, path: &Path| Event {
+            kind,
+            path: path.to_path_buf(),
+        };
+        assert_eq!(
+            watcher.poll(),
+            [
+                event(EventKind::Created, &single),
+                event(EventKind::Created, &nested.join("lib.rs")),
+            ]
+        );
+        assert!(watcher.poll().is_empty());
+
+        fs::write(nested.join("lib.rs"), "pub mod fs;").expect("valid write");
+        fs::remove_file(&single).expect("removed file");
+        assert_eq!(
+            watcher.poll(),
+            [
+                event(EventKind::Removed, &single),
+                event(EventKind::Modified, &nested.join("lib.rs")),
+            ]
+        );
+    }
+
+    #[test]
+    fn watches_on_thread() {
+        let dir = TempDir::new().expect("created dir");
+        let (handle, events) = Watcher::new()
+            .watch(dir.path())
+            .interval(Duration::from_millis(10))
+            .channel()
+            .expect("spawned watcher");
+        let path = dir.path().join("file");
+        fs::write(&path, "data").expect("valid write");
+        let event = events
+            .recv_timeout(Duration::from_secs(10))
+            .expect("received event");
+        assert_eq!(
+            event,
+            Event {
+                kind: EventKind::Created,
+                path
+            }
+        );
+        handle.stop();
+        assert!(events.recv().is_err());
+    }
+}
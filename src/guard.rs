@@ -0,0 +1,113 @@
+//! Scope guards.
+//!
+//! A [`ScopeGuard`] runs a closure when it goes out of scope, whether the scope returns normally,
+//! returns early or unwinds from a panic, like the blocks timed by `profile!`.
+//! [`defer!`](crate::defer) is shorthand for a guard which is never dismissed.
+
+use std::{fmt, mem::ManuallyDrop};
+
+/// Runs a closure when dropped, unless [dismissed](ScopeGuard::dismiss).
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::guard::ScopeGuard;
+///
+/// let mut staged = vec!["a", "b"];
+/// {
+///     let rollback = ScopeGuard::new(|| println!("rolling back"));
+///     staged.push("c");
+///     // Committed successfully, so nothing to roll back.
+///     rollback.dismiss();
+/// }
+/// assert_eq!(staged.len(), 3);
+/// ```
+#[must_use = "the closure runs immediately if the guard isn't bound to a variable"]
+pub struct ScopeGuard<F: FnOnce()> {
+    cleanup: ManuallyDrop<F>,
+}
+
+impl<F: FnOnce()> ScopeGuard<F> {
+    /// Creates a guard which calls `cleanup` when dropped.
+    pub fn new(cleanup: F) -> Self {
+        Self {
+            cleanup: ManuallyDrop::new(cleanup),
+        }
+    }
+
+    /// Drops the guard without calling its closure.
+    pub fn dismiss(self) {
+        let mut guard = ManuallyDrop::new(self);
+        // SAFETY: The closure is dropped once, and the guard's own `drop` never runs.
+        unsafe { ManuallyDrop::drop(&mut guard.cleanup) };
+    }
+}
+
+impl<F: FnOnce()> Drop for ScopeGuard<F> {
+    fn drop(&mut self) {
+        // SAFETY: The closure is taken once, here, and never used again.
+        let cleanup = unsafe { ManuallyDrop::take(&mut self.cleanup) };
+        cleanup();
+    }
+}
+
+impl<F: FnOnce()> fmt::Debug for ScopeGuard<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopeGuard").finish_non_exhaustive()
+    }
+}
+
+/// Run the given statements when the enclosing scope exits, including by panicking. Multiple
+/// `defer!`s in one scope run in reverse order.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::defer;
+/// use std::cell::RefCell;
+///
+/// let log = RefCell::new(Vec::new());
+/// {
+///     defer!(log.borrow_mut().push("closed"));
+///     defer! {
+///         log.borrow_mut().push("flushed");
+///     }
+///     log.borrow_mut().push("wrote");
+/// }
+/// assert_eq!(*log.borrow(), ["wrote", "flushed", "closed"]);
+/// ```
+#[macro_export]
+macro_rules! defer {
+    ($($body:tt)*) => {
+        let __guard = $crate::guard::ScopeGuard::new(|| {
+            $($body)*;
+        });
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, panic};
+
+    #[test]
+    fn runs_at_scope_exit() {
+        let runs = Cell::new(0);
+        {
+            let _guard = ScopeGuard::new(|| runs.set(runs.get() + 1));
+            crate::defer!(runs.set(runs.get() + 10));
+            assert_eq!(runs.get(), 0);
+        }
+        assert_eq!(runs.get(), 11);
+
+        ScopeGuard::new(|| runs.set(0)).dismiss();
+        assert_eq!(runs.get(), 11);
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            crate::defer!(runs.set(-1));
+            panic!("unwinding");
+        }));
+        assert!(result.is_err());
+        assert_eq!(runs.get(), -1);
+    }
+}
@@ -0,0 +1,245 @@
+//! Non-cryptographic hashing.
+//!
+//! [`fnv1a`] is tiny and fast for short keys, and [`wyhash`] is fast for keys of any length. Their
+//! [`Hasher`]s plug into `HashMap` and `HashSet` through [`FnvBuildHasher`] and
+//! [`FastBuildHasher`], which is what [`FastHashMap`] and [`FastHashSet`] use.
+//!
+//! Unlike the standard library's default `SipHash`, neither resists deliberately colliding keys, so
+//! keep the standard hasher for maps filled from untrusted input.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasherDefault, Hasher},
+};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Multipliers of the wyhash mixing rounds.
+const WY: [u64; 4] = [
+    0xa076_1d64_78bd_642f,
+    0xe703_7ed1_a0b4_28db,
+    0x8ebc_6af0_9c88_c6e3,
+    0x5899_65cc_7537_4cc3,
+];
+
+/// Returns the 64-bit FNV-1a hash of `bytes`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::hash::fnv1a;
+///
+/// assert_eq!(fnv1a(b"a"), 0xaf63_dc4c_8601_ec8c);
+/// ```
+#[must_use]
+pub const fn fnv1a(bytes: &[u8]) -> u64 {
+    fnv1a_with(FNV_OFFSET_BASIS, bytes)
+}
+
+const fn fnv1a_with(mut hash: u64, bytes: &[u8]) -> u64 {
+    let mut i = 0;
+    while i < bytes.len() {
+        hash = (hash ^ bytes[i] as u64).wrapping_mul(FNV_PRIME);
+        i += 1;
+    }
+    hash
+}
+
+/// Returns a 64-bit wyhash-style hash of `bytes`, varied by `seed`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::hash::wyhash;
+///
+/// assert_eq!(wyhash(b"anchor", 0), wyhash(b"anchor", 0));
+/// assert_ne!(wyhash(b"anchor", 0), wyhash(b"anchor", 1));
+/// ```
+#[must_use]
+pub fn wyhash(bytes: &[u8], seed: u64) -> u64 {
+    let len = bytes.len();
+    let mut seed = seed ^ mix(seed ^ WY[0], WY[1]);
+    let (a, b) = if len <= 16 {
+        if len >= 4 {
+            // Two overlapping 4-byte reads from each end cover every byte.
+            let quarter = (len >> 3) << 2;
+            (
+                (read4(bytes, 0) << 32) | read4(bytes, quarter),
+                (read4(bytes, len - 4) << 32) | read4(bytes, len - 4 - quarter),
+            )
+        } else if len > 0 {
+            let a = (u64::from(bytes[0]) << 16)
+                | (u64::from(bytes[len >> 1]) << 8)
+                | u64::from(bytes[len - 1]);
+            (a, 0)
+        } else {
+            (0, 0)
+        }
+    } else {
+        let mut rest = bytes;
+        if rest.len() > 48 {
+            let (mut lane1, mut lane2) = (seed, seed);
+            while rest.len() > 48 {
+                seed = mix(read8(rest, 0) ^ WY[1], read8(rest, 8) ^ seed);
+                lane1 = mix(read8(rest, 16) ^ WY[2], read8(rest, 24) ^ lane1);
+                lane2 = mix(read8(rest, 32) ^ WY[3], read8(rest, 40) ^ lane2);
+                rest = &rest[48..];
+            }
+            seed ^= lane1 ^ lane2;
+        }
+        while rest.len() > 16 {
+            seed = mix(read8(rest, 0) ^ WY[1], read8(rest, 8) ^ seed);
+            rest = &rest[16..];
+        }
+        // The last 16 bytes of the whole input, overlapping bytes already mixed if need be.
+        (read8(bytes, len - 16), read8(bytes, len - 8))
+    };
+    let (a, b) = multiply(a ^ WY[1], b ^ seed);
+    mix(a ^ WY[0] ^ len as u64, b ^ WY[1])
+}
+
+/// Returns the low and high halves of the 128-bit product of `a` and `b`.
+#[allow(clippy::cast_possible_truncation)]
+const fn multiply(a: u64, b: u64) -> (u64, u64) {
+    let product = a as u128 * b as u128;
+    (product as u64, (product >> 64) as u64)
+}
+
+const fn mix(a: u64, b: u64) -> u64 {
+    let (low, high) = multiply(a, b);
+    low ^ high
+}
+
+fn read4(bytes: &[u8], at: usize) -> u64 {
+    u64::from(u32::from_le_bytes(
+        bytes[at..at + 4].try_into().expect("valid 4 bytes"),
+    ))
+}
+
+fn read8(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().expect("valid 8 bytes"))
+}
+
+/// A [`Hasher`] computing the FNV-1a hash of everything written to it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = fnv1a_with(self.0, bytes);
+    }
+}
+
+/// A [`Hasher`] folding everything written to it through [`wyhash`], with integers mixed in
+/// directly rather than as bytes.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FastHasher(u64);
+
+impl Hasher for FastHasher {
+    fn finish(&self) -> u64 {
+        mix(self.0 ^ WY[0], WY[3])
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = wyhash(bytes, self.0);
+    }
+
+    fn write_u8(&mut self, n: u8) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_u16(&mut self, n: u16) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_u32(&mut self, n: u32) {
+        self.write_u64(u64::from(n));
+    }
+
+    fn write_u64(&mut self, n: u64) {
+        self.0 = mix(self.0 ^ n ^ WY[1], WY[2]);
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write_u64(n as u64);
+    }
+}
+
+/// Builds [`Fnv1aHasher`]s for `HashMap::with_hasher`.
+pub type FnvBuildHasher = BuildHasherDefault<Fnv1aHasher>;
+
+/// Builds [`FastHasher`]s for `HashMap::with_hasher`.
+pub type FastBuildHasher = BuildHasherDefault<FastHasher>;
+
+/// A `HashMap` using [`FastHasher`].
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::hash::FastHashMap;
+///
+/// let mut hits = FastHashMap::default();
+/// *hits.entry("parse").or_insert(0) += 1;
+/// assert_eq!(hits["parse"], 1);
+/// ```
+pub type FastHashMap<K, V> = HashMap<K, V, FastBuildHasher>;
+
+/// A `HashSet` using [`FastHasher`].
+pub type FastHashSet<T> = HashSet<T, FastBuildHasher>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hash::BuildHasher;
+
+    #[test]
+    fn fnv1a_vectors() {
+        assert_eq!(fnv1a(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(b"foobar"), 0x8594_4171_f739_67e8);
+        let mut hasher = Fnv1aHasher::default();
+        hasher.write(b"foo");
+        hasher.write(b"bar");
+        assert_eq!(hasher.finish(), fnv1a(b"foobar"));
+    }
+
+    #[test]
+    fn wyhash_lengths() {
+        let bytes: Vec<u8> = (0..=200).collect();
+        let mut hashes: Vec<u64> = (0..bytes.len())
+            .map(|len| wyhash(&bytes[..len], 0))
+            .collect();
+        hashes.sort_unstable();
+        hashes.dedup();
+        assert_eq!(hashes.len(), bytes.len());
+
+        let mut flipped = bytes.clone();
+        flipped[100] ^= 1;
+        assert_ne!(wyhash(&flipped, 0), wyhash(&bytes, 0));
+    }
+
+    #[test]
+    fn hash_maps() {
+        let build = FastBuildHasher::default();
+        let hash = |value: &(&str, u32)| build.hash_one(value);
+        assert_eq!(hash(&("a", 1)), hash(&("a", 1)));
+        assert_ne!(hash(&("a", 1)), hash(&("a", 2)));
+        assert_ne!(hash(&("ab", 1)), hash(&("a", 1)));
+
+        let mut map: FastHashMap<u32, u32> = (0..1000).map(|n| (n, n * 2)).collect();
+        assert_eq!(map.remove(&500), Some(1000));
+        assert_eq!(map.len(), 999);
+        let set: HashSet<&str, FnvBuildHasher> = ["a", "b", "a"].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+}
@@ -0,0 +1,492 @@
+//! Unique identifiers.
+//!
+//! A [`Uuid`] is either random, for identifiers that only need to be unique, or time-ordered, for
+//! identifiers that also sort by when they were created, such as the names of report files. Both
+//! draw their random bits from the thread's [`rand`] generator, so they're reproducible after
+//! [`rand::seed`] but not unguessable, and shouldn't be used as secrets.
+//!
+//! A [`Snowflake`] is a 64-bit identifier from a [`SnowflakeGenerator`], made of a millisecond
+//! timestamp, a node id and a sequence number, for compact identifiers which sort by time and are
+//! unique across the processes of a system without coordination.
+
+use crate::rand;
+use std::{
+    error::Error,
+    fmt,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Bit offset of the version in a UUID.
+const VERSION_SHIFT: u32 = 76;
+/// Bit offset of the variant in a UUID.
+const VARIANT_SHIFT: u32 = 62;
+/// Bit offset of the millisecond timestamp in a version 7 UUID.
+const TIMESTAMP_SHIFT: u32 = 80;
+/// Bit offset of the counter in a version 7 UUID.
+const COUNTER_SHIFT: u32 = 64;
+/// Largest counter of a version 7 UUID, which is 12 bits.
+const MAX_COUNTER: u16 = 0xfff;
+
+/// Bits of the node id of a snowflake.
+const NODE_BITS: u32 = 10;
+/// Bits of the sequence number of a snowflake.
+const SEQUENCE_BITS: u32 = 12;
+/// Bits of the millisecond timestamp of a snowflake, leaving the sign bit clear.
+const MILLIS_BITS: u32 = 63 - NODE_BITS - SEQUENCE_BITS;
+/// Default epoch of snowflake timestamps, 2024-01-01T00:00:00Z, in Unix milliseconds.
+const SNOWFLAKE_EPOCH: u64 = 1_704_067_200_000;
+
+/// Timestamp and counter of the last version 7 UUID generated.
+static LAST_V7: Mutex<(u64, u16)> = Mutex::new((0, 0));
+
+/// A 128-bit universally unique identifier, as defined by [RFC 9562].
+///
+/// UUIDs order by their bits, so version 7 UUIDs order by when they were generated.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::id::Uuid;
+///
+/// let first = Uuid::new_v7();
+/// let second = Uuid::new_v7();
+/// assert!(first < second);
+/// assert_eq!(first.version(), 7);
+///
+/// let parsed: Uuid = "0190b4ba-6c1a-7a3e-8c1d-3f5e2a9b7d41".parse().unwrap();
+/// assert_eq!(parsed.to_string(), "0190b4ba-6c1a-7a3e-8c1d-3f5e2a9b7d41");
+/// ```
+///
+/// [RFC 9562]: https://www.rfc-editor.org/rfc/rfc9562
+#[derive(Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[must_use]
+pub struct Uuid(u128);
+
+impl Uuid {
+    /// The UUID with every bit zero.
+    pub const NIL: Self = Self(0);
+
+    /// Returns the UUID with the bits of `value`, most significant first.
+    pub const fn from_u128(value: u128) -> Self {
+        Self(value)
+    }
+
+    /// Returns the UUID with the big-endian `bytes`.
+    pub const fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(u128::from_be_bytes(bytes))
+    }
+
+    /// Returns the bits of the UUID, most significant first.
+    #[must_use]
+    pub const fn as_u128(self) -> u128 {
+        self.0
+    }
+
+    /// Returns the big-endian bytes of the UUID.
+    #[must_use]
+    pub const fn to_bytes(self) -> [u8; 16] {
+        self.0.to_be_bytes()
+    }
+
+    /// Generates a random version 4 UUID.
+    pub fn new_v4() -> Self {
+        let random = (u128::from(rand::u64()) << 64) | u128::from(rand::u64());
+        Self::with_version(random, 4)
+    }
+
+    /// Generates a version 7 UUID from the current Unix time in milliseconds and random bits.
+    ///
+    /// UUIDs generated in the same millisecond by this process are ordered by a counter, and still
+    /// increase if the system clock goes back.
+    pub fn new_v7() -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let now = u64::try_from(now.as_millis()).unwrap_or(u64::MAX) & 0xffff_ffff_ffff;
+        let (timestamp, counter) = {
+            let mut last = LAST_V7.lock().unwrap_or_else(PoisonError::into_inner);
+            // A new millisecond starts the counter in its lower half, leaving room to count up.
+            *last = if now > last.0 {
+                (now, rand::range(0..=MAX_COUNTER / 2))
+            } else if last.1 < MAX_COUNTER {
+                (last.0, last.1 + 1)
+            } else {
+                (last.0 + 1, rand::range(0..=MAX_COUNTER / 2))
+            };
+            *last
+        };
+        let bits = (u128::from(timestamp) << TIMESTAMP_SHIFT)
+            | (u128::from(counter) << COUNTER_SHIFT)
+            | u128::from(rand::u64());
+        Self::with_version(bits, 7)
+    }
+
+    /// Sets the version bits of `bits` to `version`, and the variant bits to the RFC variant.
+    const fn with_version(bits: u128, version: u8) -> Self {
+        let bits = (bits & !(0xf << VERSION_SHIFT)) | ((version as u128) << VERSION_SHIFT);
+        Self((bits & !(0b11 << VARIANT_SHIFT)) | (0b10 << VARIANT_SHIFT))
+    }
+
+    /// Returns the version: 4 for random UUIDs, 7 for time-ordered ones.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn version(self) -> u8 {
+        ((self.0 >> VERSION_SHIFT) & 0xf) as u8
+    }
+
+    /// Whether every bit is zero.
+    #[must_use]
+    pub const fn is_nil(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns the time a version 7 UUID was generated, to the millisecond, or `None` for other
+    /// versions.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn timestamp(self) -> Option<SystemTime> {
+        let millis = (self.0 >> TIMESTAMP_SHIFT) as u64;
+        (self.version() == 7).then(|| UNIX_EPOCH + Duration::from_millis(millis))
+    }
+}
+
+impl fmt::Display for Uuid {
+    /// Formats the UUID as 32 lower case hex digits in groups of 8, 4, 4, 4 and 12.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = format!("{:032x}", self.0);
+        write!(
+            f,
+            "{}-{}-{}-{}-{}",
+            &hex[..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..]
+        )
+    }
+}
+
+impl fmt::Debug for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Uuid({self})")
+    }
+}
+
+impl FromStr for Uuid {
+    type Err = ParseError;
+
+    /// Parses 32 hex digits, in either case, optionally hyphenated in groups of 8, 4, 4, 4 and 12.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let error = |message| ParseError {
+            input: input.to_string(),
+            message,
+        };
+        let digits: String = match input.len() {
+            32 => input.to_string(),
+            36 => {
+                let hyphens = [8, 13, 18, 23];
+                if hyphens.iter().any(|&at| input.as_bytes()[at] != b'-') {
+                    return Err(error("expected hyphens between groups 8-4-4-4-12"));
+                }
+                input.chars().filter(|&c| c != '-').collect()
+            }
+            _ => return Err(error("expected 32 hex digits")),
+        };
+        if !digits.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(error("invalid hex digit"));
+        }
+        u128::from_str_radix(&digits, 16)
+            .map(Self)
+            .map_err(|_| error("invalid hex digit"))
+    }
+}
+
+/// A 64-bit identifier from a [`SnowflakeGenerator`].
+///
+/// From the most significant bit, the sign bit is clear, then 41 bits count milliseconds since the
+/// generator's epoch, 10 bits hold the node id and 12 bits a sequence number, so snowflakes order
+/// by when they were generated.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[must_use]
+pub struct Snowflake(u64);
+
+impl Snowflake {
+    /// Returns the snowflake with the bits of `value`.
+    pub const fn from_u64(value: u64) -> Self {
+        Self(value)
+    }
+
+    /// Returns the bits of the snowflake.
+    #[must_use]
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// Milliseconds since the generator's epoch when the snowflake was generated.
+    #[must_use]
+    pub const fn millis(self) -> u64 {
+        self.0 >> (NODE_BITS + SEQUENCE_BITS)
+    }
+
+    /// Id of the node which generated the snowflake.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn node(self) -> u16 {
+        ((self.0 >> SEQUENCE_BITS) & ((1 << NODE_BITS) - 1)) as u16
+    }
+
+    /// Sequence number of the snowflake within its millisecond.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn sequence(self) -> u16 {
+        (self.0 & ((1 << SEQUENCE_BITS) - 1)) as u16
+    }
+}
+
+impl fmt::Display for Snowflake {
+    /// Formats the snowflake as a decimal number.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// Generates [`Snowflake`]s for one node, safely from any number of threads.
+///
+/// Each snowflake is greater than the last one from the same generator. Up to 4096 are generated
+/// per millisecond; beyond that, or if the system clock goes back, the generator runs ahead of the
+/// clock until it catches up rather than blocking or repeating an id.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::id::SnowflakeGenerator;
+///
+/// static IDS: SnowflakeGenerator = SnowflakeGenerator::new(3);
+///
+/// let first = IDS.next();
+/// let second = IDS.next();
+/// assert!(first < second);
+/// assert_eq!(first.node(), 3);
+/// ```
+#[derive(Debug)]
+pub struct SnowflakeGenerator {
+    node: u16,
+    /// Unix time in milliseconds of timestamp 0.
+    epoch: u64,
+    /// Timestamp and sequence number of the last snowflake, with the node id bits removed.
+    last: AtomicU64,
+}
+
+impl SnowflakeGenerator {
+    /// The largest node id.
+    pub const MAX_NODE: u16 = (1 << NODE_BITS) - 1;
+
+    /// Creates a generator for the node `node`, counting time from 2024-01-01 UTC.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `node` is greater than [`MAX_NODE`](Self::MAX_NODE).
+    #[must_use]
+    pub const fn new(node: u16) -> Self {
+        assert!(
+            node <= Self::MAX_NODE,
+            "snowflake node id must be under 1024"
+        );
+        Self {
+            node,
+            epoch: SNOWFLAKE_EPOCH,
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// Counts time from `epoch` instead, which must be in the past. The 41-bit timestamp lasts
+    /// about 69 years from it.
+    #[must_use]
+    pub fn with_epoch(mut self, epoch: SystemTime) -> Self {
+        self.epoch = unix_millis(epoch);
+        self
+    }
+
+    /// Id of the node the generator is for.
+    #[must_use]
+    pub const fn node(&self) -> u16 {
+        self.node
+    }
+
+    /// Generates the next snowflake.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&self) -> Snowflake {
+        self.next_at(unix_millis(SystemTime::now()))
+    }
+
+    /// Returns the time `id` was generated, to the millisecond, if it came from a generator with
+    /// the same epoch.
+    #[must_use]
+    pub fn timestamp(&self, id: Snowflake) -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(self.epoch + id.millis())
+    }
+
+    /// Generates the next snowflake as if the Unix time in milliseconds were `now`.
+    fn next_at(&self, now: u64) -> Snowflake {
+        let millis = now.saturating_sub(self.epoch) & ((1 << MILLIS_BITS) - 1);
+        let now = millis << SEQUENCE_BITS;
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            // A full sequence carries into the timestamp, running ahead of the clock.
+            let next = if now > last { now } else { last + 1 };
+            match self
+                .last
+                .compare_exchange_weak(last, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => {
+                    let timestamp = next >> SEQUENCE_BITS;
+                    let sequence = next & ((1 << SEQUENCE_BITS) - 1);
+                    return Snowflake(
+                        (timestamp << (NODE_BITS + SEQUENCE_BITS))
+                            | (u64::from(self.node) << SEQUENCE_BITS)
+                            | sequence,
+                    );
+                }
+                Err(actual) => last = actual,
+            }
+        }
+    }
+}
+
+/// Unix time in milliseconds of `time`, or 0 before 1970.
+fn unix_millis(time: SystemTime) -> u64 {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    u64::try_from(since.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// An invalid UUID string.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    input: String,
+    message: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid UUID `{}`: {}", self.input, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_and_parses() {
+        let uuid = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        let text = "01234567-89ab-cdef-0123-456789abcdef";
+        assert_eq!(uuid.to_string(), text);
+        assert_eq!(format!("{uuid:?}"), format!("Uuid({text})"));
+        assert_eq!(text.parse(), Ok(uuid));
+        assert_eq!("0123456789ABCDEF0123456789ABCDEF".parse(), Ok(uuid));
+        assert_eq!(Uuid::from_bytes(uuid.to_bytes()), uuid);
+        assert_eq!(
+            Uuid::NIL.to_string(),
+            "00000000-0000-0000-0000-000000000000"
+        );
+        assert!(Uuid::NIL.is_nil());
+
+        let error = |input: &str| input.parse::<Uuid>().expect_err("invalid UUID").to_string();
+        assert_eq!(error("0123"), "invalid UUID `0123`: expected 32 hex digits");
+        assert!(error("01234567-89ab-cdef-0123+456789abcdef").ends_with("8-4-4-4-12"));
+        assert!(error("0123456789abcdef0123456789abcdeg").ends_with("invalid hex digit"));
+        assert!(error("+123456789abcdef0123456789abcdef").ends_with("invalid hex digit"));
+    }
+
+    #[test]
+    fn versions() {
+        rand::seed(7);
+        let v4 = Uuid::new_v4();
+        assert_eq!(v4.version(), 4);
+        assert_eq!(v4.as_u128() >> VARIANT_SHIFT & 0b11, 0b10);
+        assert_eq!(v4.timestamp(), None);
+        rand::seed(7);
+        assert_eq!(Uuid::new_v4(), v4);
+        assert_ne!(Uuid::new_v4(), v4);
+
+        let before = SystemTime::now() - Duration::from_millis(1);
+        let ids: Vec<Uuid> = (0..10_000).map(|_| Uuid::new_v7()).collect();
+        assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+        let first = ids[0];
+        assert_eq!(first.version(), 7);
+        assert_eq!(first.as_u128() >> VARIANT_SHIFT & 0b11, 0b10);
+        let timestamp = first.timestamp().expect("valid timestamp");
+        assert!(timestamp >= before && timestamp <= SystemTime::now() + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn snowflakes() {
+        let generator = SnowflakeGenerator::new(SnowflakeGenerator::MAX_NODE);
+        let now = SNOWFLAKE_EPOCH + 5_000;
+        let first = generator.next_at(now);
+        assert_eq!(
+            (first.millis(), first.node(), first.sequence()),
+            (5_000, 1023, 0)
+        );
+        assert_eq!(
+            generator.timestamp(first),
+            UNIX_EPOCH + Duration::from_millis(now)
+        );
+        assert_eq!(Snowflake::from_u64(first.as_u64()), first);
+        assert_eq!(first.to_string(), first.as_u64().to_string());
+
+        let second = generator.next_at(now);
+        assert_eq!((second.millis(), second.sequence()), (5_000, 1));
+        // The clock going back continues the sequence.
+        let third = generator.next_at(now - 1_000);
+        assert_eq!((third.millis(), third.sequence()), (5_000, 2));
+        // A full sequence borrows the next millisecond.
+        let mut last = third;
+        for _ in 3..4096 {
+            last = generator.next_at(now);
+        }
+        assert_eq!((last.millis(), last.sequence()), (5_000, 4095));
+        let ahead = generator.next_at(now);
+        assert_eq!((ahead.millis(), ahead.sequence()), (5_001, 0));
+        let later = generator.next_at(now + 10);
+        assert_eq!((later.millis(), later.sequence()), (5_010, 0));
+
+        let generator = SnowflakeGenerator::new(7).with_epoch(UNIX_EPOCH);
+        assert!(generator.next().millis() > SNOWFLAKE_EPOCH);
+    }
+
+    #[test]
+    fn concurrent_snowflakes() {
+        let generator = std::sync::Arc::new(SnowflakeGenerator::new(1));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let generator = std::sync::Arc::clone(&generator);
+                std::thread::spawn(move || {
+                    let ids: Vec<_> = (0..5_000).map(|_| generator.next()).collect();
+                    assert!(ids.windows(2).all(|pair| pair[0] < pair[1]));
+                    ids
+                })
+            })
+            .collect();
+        let mut ids: Vec<_> = threads
+            .into_iter()
+            .flat_map(|thread| thread.join().expect("valid thread"))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), 20_000);
+    }
+
+    #[test]
+    #[should_panic = "snowflake node id must be under 1024"]
+    fn invalid_node() {
+        let _ = SnowflakeGenerator::new(1024);
+    }
+}
@@ -0,0 +1,162 @@
+//! String interning.
+//!
+//! An [`Interner`] stores each distinct string once and identifies it by a small [`Symbol`], so
+//! names repeated throughout a parser's tokens or an asset pipeline's paths can be compared and
+//! hashed as integers, and resolved back to the string when needed. [`intern_static`] instead
+//! leaks each distinct string once into a process-wide table, for names which must be `'static`.
+
+use crate::{
+    hash::{FastBuildHasher, FastHashMap, FastHashSet},
+    sync::Lazy,
+};
+use std::{
+    fmt,
+    sync::{Mutex, PoisonError},
+};
+
+/// The ID of a string in an [`Interner`], unique within that interner.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Index of the string in the order it was first interned.
+    #[must_use]
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{}", self.0)
+    }
+}
+
+/// Maps strings to [`Symbol`]s and back, both in constant time.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::intern::Interner;
+///
+/// let mut interner = Interner::new();
+/// let parse = interner.intern("parse");
+/// let render = interner.intern("render");
+/// assert_eq!(interner.intern("parse"), parse);
+/// assert_ne!(parse, render);
+/// assert_eq!(interner.resolve(render), Some("render"));
+/// ```
+#[derive(Debug, Default, Clone)]
+#[must_use]
+pub struct Interner {
+    symbols: FastHashMap<Box<str>, Symbol>,
+    /// Each interned string, indexed by symbol.
+    strings: Vec<Box<str>>,
+}
+
+impl Interner {
+    /// Creates an empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an empty interner with room for `capacity` strings.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            symbols: FastHashMap::with_capacity_and_hasher(capacity, FastBuildHasher::default()),
+            strings: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the symbol of `string`, interning it if it's new.
+    ///
+    /// # Panics
+    ///
+    /// Panics if more than `u32::MAX` strings are interned.
+    pub fn intern(&mut self, string: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(string) {
+            return symbol;
+        }
+        let symbol = Symbol(u32::try_from(self.strings.len()).expect("valid symbol count"));
+        self.strings.push(string.into());
+        self.symbols.insert(string.into(), symbol);
+        symbol
+    }
+
+    /// Returns the symbol of `string`, if it has been interned.
+    #[must_use]
+    pub fn get(&self, string: &str) -> Option<Symbol> {
+        self.symbols.get(string).copied()
+    }
+
+    /// Returns the string of `symbol`, or `None` if it came from another interner.
+    #[must_use]
+    pub fn resolve(&self, symbol: Symbol) -> Option<&str> {
+        self.strings.get(symbol.index()).map(AsRef::as_ref)
+    }
+
+    /// Number of distinct strings interned.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Whether no strings have been interned.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns every symbol and its string, in the order they were first interned.
+    pub fn iter(&self) -> impl Iterator<Item = (Symbol, &str)> + '_ {
+        self.strings
+            .iter()
+            .zip(0..)
+            .map(|(string, index)| (Symbol(index), string.as_ref()))
+    }
+}
+
+/// Returns a `'static` copy of `string`, leaking each distinct string once for the life of the
+/// process. Profile anchor names, labels and thread names all share this table.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::intern::intern_static;
+///
+/// let name = intern_static(&format!("worker-{}", 3));
+/// assert!(std::ptr::eq(name, intern_static("worker-3")));
+/// ```
+pub fn intern_static(string: &str) -> &'static str {
+    static STRINGS: Lazy<Mutex<FastHashSet<&'static str>>> = Lazy::new(Mutex::default);
+    let mut strings = STRINGS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(string) = strings.get(string) {
+        return string;
+    }
+    let string: &'static str = Box::leak(string.into());
+    strings.insert(string);
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn symbols() {
+        let mut interner = Interner::with_capacity(2);
+        assert!(interner.is_empty());
+        let a = interner.intern("a");
+        let b = interner.intern("b");
+        assert_eq!(interner.intern("a"), a);
+        assert_eq!((a.index(), b.index()), (0, 1));
+        assert_eq!(b.to_string(), "#1");
+        assert_eq!(interner.get("b"), Some(b));
+        assert_eq!(interner.get("c"), None);
+        assert_eq!(interner.resolve(a), Some("a"));
+        assert_eq!(interner.resolve(Symbol(7)), None);
+        assert_eq!(interner.len(), 2);
+        let entries: Vec<_> = interner.iter().collect();
+        assert_eq!(entries, [(a, "a"), (b, "b")]);
+    }
+}
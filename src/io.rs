@@ -0,0 +1,6 @@
+//! Input and output helpers.
+
+pub mod bytes;
+pub mod mmap;
+
+pub use mmap::{Advice, Mmap};
@@ -0,0 +1,501 @@
+//! Binary encoding.
+//!
+//! A [`ByteReader`] reads integers, floats and length-prefixed strings from any [`Read`], in the
+//! [`Endian`] order it's configured with, and a [`ByteWriter`] writes them to any [`Write`].
+//! Readers over seekable sources, such as slices wrapped by [`ByteReader::from_slice`], can also
+//! [`seek`](ByteReader::seek), and every reader can peek ahead without consuming bytes.
+
+use crate::bits;
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+/// Byte order of multi-byte values.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Endian {
+    /// Least significant byte first, as on x86 and most ARM systems.
+    #[default]
+    Little,
+    /// Most significant byte first, as in network protocols.
+    Big,
+}
+
+/// Reads binary values from a [`Read`].
+///
+/// Strings are prefixed with their length in bytes as a `u32`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::io::bytes::{ByteReader, Endian};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut reader = ByteReader::from_slice(b"\x00\x2a\x02\x00\x00\x00hi").endian(Endian::Big);
+/// assert_eq!(reader.read_u16()?, 42);
+/// reader.set_endian(Endian::Little);
+/// assert_eq!(reader.read_str()?, "hi");
+/// assert_eq!(reader.position(), 8);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct ByteReader<R> {
+    reader: R,
+    endian: Endian,
+    /// Bytes read from `reader` by a peek, not yet consumed.
+    peeked: Vec<u8>,
+    position: u64,
+}
+
+/// Implements reading each number type in the configured byte order.
+macro_rules! read_numbers {
+    ($($name:ident => $ty:ty),* $(,)?) => {$(
+        #[doc = concat!("Reads a `", stringify!($ty), "`.")]
+        ///
+        /// # Errors
+        ///
+        /// Returns an `UnexpectedEof` error if the input ends first, or the reader's error.
+        pub fn $name(&mut self) -> io::Result<$ty> {
+            let bytes = self.read_array()?;
+            Ok(match self.endian {
+                Endian::Little => <$ty>::from_le_bytes(bytes),
+                Endian::Big => <$ty>::from_be_bytes(bytes),
+            })
+        }
+    )*};
+}
+
+impl<'a> ByteReader<Cursor<&'a [u8]>> {
+    /// Creates a little-endian reader over `bytes`.
+    pub fn from_slice(bytes: &'a [u8]) -> Self {
+        Self::new(Cursor::new(bytes))
+    }
+}
+
+impl<R: Read> ByteReader<R> {
+    /// Creates a little-endian reader over `reader`.
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            endian: Endian::Little,
+            peeked: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /// Reads multi-byte values in `endian` order.
+    pub fn endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Reads later multi-byte values in `endian` order.
+    pub fn set_endian(&mut self, endian: Endian) {
+        self.endian = endian;
+    }
+
+    /// Number of bytes consumed since the reader was created, or the offset sought to.
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the wrapped reader. Peeked bytes are lost.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Fills `buf` with the next bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `UnexpectedEof` error if the input ends first, or the reader's error.
+    pub fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        let from_peeked = self.peeked.len().min(buf.len());
+        buf[..from_peeked].copy_from_slice(&self.peeked[..from_peeked]);
+        self.peeked.drain(..from_peeked);
+        self.reader.read_exact(&mut buf[from_peeked..])?;
+        self.position += buf.len() as u64;
+        Ok(())
+    }
+
+    fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut bytes = [0; N];
+        self.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    read_numbers!(
+        read_u8 => u8, read_u16 => u16, read_u32 => u32, read_u64 => u64, read_u128 => u128,
+        read_i8 => i8, read_i16 => i16, read_i32 => i32, read_i64 => i64, read_i128 => i128,
+        read_f32 => f32, read_f64 => f64,
+    );
+
+    /// Reads the next `len` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `UnexpectedEof` error if the input ends first, or the reader's error. A corrupt
+    /// length fails once the input ends rather than allocating `len` bytes up front.
+    pub fn read_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(len.min(64 * 1024));
+        let from_peeked = self.peeked.len().min(len);
+        bytes.extend(self.peeked.drain(..from_peeked));
+        let rest = (len - from_peeked) as u64;
+        if (&mut self.reader).take(rest).read_to_end(&mut bytes)? as u64 != rest {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        self.position += len as u64;
+        Ok(bytes)
+    }
+
+    /// Skips to the next position which is a multiple of `align`, a power of two, as after the
+    /// padding a binary format inserts before an aligned field.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `UnexpectedEof` error if the input ends first, or the reader's error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` isn't a power of two.
+    pub fn align(&mut self, align: u64) -> io::Result<()> {
+        let padding = bits::align_up(self.position, align) - self.position;
+        let mut skipped = [0; 64];
+        let mut remaining = padding;
+        while remaining > 0 {
+            let len = remaining.min(skipped.len() as u64);
+            #[allow(clippy::cast_possible_truncation)]
+            self.read_exact(&mut skipped[..len as usize])?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    /// Reads a string prefixed with its length in bytes as a `u32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidData` error if the string isn't UTF-8, or any error of
+    /// [`read_bytes`](Self::read_bytes).
+    pub fn read_str(&mut self) -> io::Result<String> {
+        let len = self.read_u32()? as usize;
+        String::from_utf8(self.read_bytes(len)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Returns the next `len` bytes without consuming them.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `UnexpectedEof` error if the input ends first, or the reader's error.
+    pub fn peek_bytes(&mut self, len: usize) -> io::Result<&[u8]> {
+        if self.peeked.len() < len {
+            let missing = (len - self.peeked.len()) as u64;
+            let read = (&mut self.reader)
+                .take(missing)
+                .read_to_end(&mut self.peeked)?;
+            if (read as u64) < missing {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+            }
+        }
+        Ok(&self.peeked[..len])
+    }
+
+    /// Returns the next byte without consuming it, or `None` at the end of the input.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reader's error.
+    pub fn peek_u8(&mut self) -> io::Result<Option<u8>> {
+        match self.peek_bytes(1) {
+            Ok(bytes) => Ok(Some(bytes[0])),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Whether every byte has been consumed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reader's error.
+    pub fn is_at_end(&mut self) -> io::Result<bool> {
+        Ok(self.peek_u8()?.is_none())
+    }
+}
+
+impl<R: Read + Seek> ByteReader<R> {
+    /// Moves to `pos`, returning the new offset from the start.
+    ///
+    /// # Errors
+    ///
+    /// Returns the reader's error, such as seeking before the start.
+    pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let pos = match pos {
+            // The wrapped reader is ahead of this one by the peeked bytes.
+            SeekFrom::Current(offset) => {
+                SeekFrom::Current(offset - i64::try_from(self.peeked.len()).unwrap_or(i64::MAX))
+            }
+            pos => pos,
+        };
+        self.position = self.reader.seek(pos)?;
+        self.peeked.clear();
+        Ok(self.position)
+    }
+}
+
+/// Writes binary values to a [`Write`].
+///
+/// Strings are prefixed with their length in bytes as a `u32`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::io::bytes::{ByteWriter, Endian};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut writer = ByteWriter::new(Vec::new()).endian(Endian::Big);
+/// writer.write_u16(42)?;
+/// writer.write_str("hi")?;
+/// assert_eq!(writer.into_inner(), b"\x00\x2a\x00\x00\x00\x02hi");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct ByteWriter<W> {
+    writer: W,
+    endian: Endian,
+    position: u64,
+}
+
+/// Implements writing each number type in the configured byte order.
+macro_rules! write_numbers {
+    ($($name:ident => $ty:ty),* $(,)?) => {$(
+        #[doc = concat!("Writes a `", stringify!($ty), "`.")]
+        ///
+        /// # Errors
+        ///
+        /// Returns the writer's error.
+        pub fn $name(&mut self, value: $ty) -> io::Result<()> {
+            match self.endian {
+                Endian::Little => self.write_bytes(&value.to_le_bytes()),
+                Endian::Big => self.write_bytes(&value.to_be_bytes()),
+            }
+        }
+    )*};
+}
+
+impl<W: Write> ByteWriter<W> {
+    /// Creates a little-endian writer to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            endian: Endian::Little,
+            position: 0,
+        }
+    }
+
+    /// Writes multi-byte values in `endian` order.
+    pub fn endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Writes later multi-byte values in `endian` order.
+    pub fn set_endian(&mut self, endian: Endian) {
+        self.endian = endian;
+    }
+
+    /// Number of bytes written since the writer was created, or the offset sought to.
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Returns the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes `bytes` as they are.
+    ///
+    /// # Errors
+    ///
+    /// Returns the writer's error.
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.position += bytes.len() as u64;
+        Ok(())
+    }
+
+    write_numbers!(
+        write_u8 => u8, write_u16 => u16, write_u32 => u32, write_u64 => u64, write_u128 => u128,
+        write_i8 => i8, write_i16 => i16, write_i32 => i32, write_i64 => i64, write_i128 => i128,
+        write_f32 => f32, write_f64 => f64,
+    );
+
+    /// Writes zero bytes up to the next position which is a multiple of `align`, a power of two,
+    /// so the next field is aligned.
+    ///
+    /// # Errors
+    ///
+    /// Returns the writer's error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `align` isn't a power of two.
+    pub fn align(&mut self, align: u64) -> io::Result<()> {
+        let mut padding = bits::align_up(self.position, align) - self.position;
+        while padding > 0 {
+            let len = padding.min(64);
+            #[allow(clippy::cast_possible_truncation)]
+            self.write_bytes(&[0; 64][..len as usize])?;
+            padding -= len;
+        }
+        Ok(())
+    }
+
+    /// Writes `value` prefixed with its length in bytes as a `u32`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidInput` error if `value` is 4GiB or longer, or the writer's error.
+    pub fn write_str(&mut self, value: &str) -> io::Result<()> {
+        let len = u32::try_from(value.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "string too long"))?;
+        self.write_u32(len)?;
+        self.write_bytes(value.as_bytes())
+    }
+
+    /// Flushes the wrapped writer.
+    ///
+    /// # Errors
+    ///
+    /// Returns the writer's error.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W: Write + Seek> ByteWriter<W> {
+    /// Moves to `pos`, returning the new offset from the start, e.g. to fill in a length written
+    /// as a placeholder.
+    ///
+    /// # Errors
+    ///
+    /// Returns the writer's error, such as seeking before the start.
+    pub fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.writer.seek(pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() -> io::Result<()> {
+        for endian in [Endian::Little, Endian::Big] {
+            let mut writer = ByteWriter::new(Vec::new()).endian(endian);
+            writer.write_u8(1)?;
+            writer.write_u16(0x0203)?;
+            writer.write_u32(0x0405_0607)?;
+            writer.write_u64(u64::MAX - 1)?;
+            writer.write_i32(-5)?;
+            writer.write_f32(1.5)?;
+            writer.write_f64(-0.25)?;
+            writer.write_u128(7)?;
+            writer.write_str("anchör")?;
+            assert_eq!(writer.position(), 1 + 2 + 4 + 8 + 4 + 4 + 8 + 16 + 4 + 7);
+            let bytes = writer.into_inner();
+
+            let mut reader = ByteReader::from_slice(&bytes).endian(endian);
+            assert_eq!(reader.read_u8()?, 1);
+            assert_eq!(reader.read_u16()?, 0x0203);
+            assert_eq!(reader.read_u32()?, 0x0405_0607);
+            assert_eq!(reader.read_u64()?, u64::MAX - 1);
+            assert_eq!(reader.read_i32()?, -5);
+            assert_eq!(reader.read_f32()?.to_bits(), 1.5f32.to_bits());
+            assert_eq!(reader.read_f64()?.to_bits(), (-0.25f64).to_bits());
+            assert_eq!(reader.read_u128()?, 7);
+            assert_eq!(reader.read_str()?, "anchör");
+            assert!(reader.is_at_end()?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn byte_order() -> io::Result<()> {
+        let mut little = ByteWriter::new(Vec::new());
+        little.write_u32(0x0102_0304)?;
+        assert_eq!(little.into_inner(), [4, 3, 2, 1]);
+        let mut big = ByteWriter::new(Vec::new()).endian(Endian::Big);
+        big.write_u32(0x0102_0304)?;
+        assert_eq!(big.into_inner(), [1, 2, 3, 4]);
+        Ok(())
+    }
+
+    #[test]
+    fn alignment() -> io::Result<()> {
+        let mut writer = ByteWriter::new(Vec::new());
+        writer.write_u8(7)?;
+        writer.align(8)?;
+        writer.write_u64(9)?;
+        writer.align(8)?;
+        writer.align(128)?;
+        assert_eq!(writer.position(), 128);
+        let bytes = writer.into_inner();
+        assert_eq!(bytes[..8], [7, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut reader = ByteReader::from_slice(&bytes);
+        assert_eq!(reader.read_u8()?, 7);
+        reader.align(8)?;
+        assert_eq!(reader.read_u64()?, 9);
+        reader.align(128)?;
+        assert!(reader.is_at_end()?);
+        let err = reader.align(1024).expect_err("short input");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
+    #[test]
+    fn peek_and_seek() -> io::Result<()> {
+        let mut reader = ByteReader::from_slice(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(reader.peek_u8()?, Some(1));
+        assert_eq!(reader.peek_bytes(3)?, [1, 2, 3]);
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.read_u16()?, 0x0201);
+        assert_eq!(reader.peek_bytes(2)?, [3, 4]);
+        assert_eq!(reader.seek(SeekFrom::Current(1))?, 3);
+        assert_eq!(reader.read_u8()?, 4);
+        assert_eq!(reader.seek(SeekFrom::Start(0))?, 0);
+        assert_eq!(reader.read_bytes(6)?, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(reader.peek_u8()?, None);
+
+        let mut writer = ByteWriter::new(Cursor::new(Vec::new()));
+        writer.write_u32(0)?;
+        writer.write_str("body")?;
+        let end = writer.position();
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_u32(u32::try_from(end).expect("valid length"))?;
+        assert_eq!(writer.into_inner().into_inner()[..4], [12, 0, 0, 0]);
+        Ok(())
+    }
+
+    #[test]
+    fn truncated_input() {
+        let mut reader = ByteReader::from_slice(&[1, 2, 3]);
+        let err = reader.read_u32().expect_err("short input");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut reader = ByteReader::from_slice(b"\xff\xff\xff\xffabc");
+        let err = reader.read_str().expect_err("corrupt length");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut reader = ByteReader::from_slice(b"\x01\x00\x00\x00\xff");
+        let err = reader.read_str().expect_err("invalid UTF-8");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,382 @@
+//! Memory-mapped files.
+//!
+//! An [`Mmap`] maps a file into memory with `mmap` or `MapViewOfFile`, so large inputs can be
+//! parsed as one slice without reading them up front or copying them, and pages are loaded as
+//! they're touched.
+
+use std::{fs::File, io, path::Path, ptr::NonNull};
+
+/// Expected access pattern of a mapping, passed to `madvise`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Advice {
+    /// No particular pattern, the default.
+    Normal,
+    /// Pages are accessed in random order, so reading ahead is wasted.
+    Random,
+    /// Pages are accessed in order, so they can be read ahead aggressively and dropped soon after.
+    Sequential,
+    /// Pages will be needed soon, so they can be read in now.
+    WillNeed,
+    /// Pages won't be needed soon, so they can be dropped.
+    DontNeed,
+}
+
+/// A file mapped into memory, read-only or copy-on-write.
+///
+/// The mapped bytes are read directly from the page cache, so if another process modifies or
+/// truncates the file while it's mapped, the slice changes underneath the program or accessing it
+/// crashes. That's why mapping is `unsafe`: only map files which won't change.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{io::{Advice, Mmap}, profile};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let path = std::env::temp_dir().join("util_lib_rs_mmap_doc.txt");
+/// std::fs::write(&path, "a\nb\nc\n")?;
+/// // SAFETY: Nothing else modifies the file while it's mapped.
+/// let map = unsafe { Mmap::open(&path)? };
+/// map.advise(Advice::Sequential)?;
+/// profile!("count_lines", bytes = map.byte_count());
+/// assert_eq!(map.iter().filter(|&&byte| byte == b'\n').count(), 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct Mmap {
+    /// Start of the mapping, dangling for empty files, which can't be mapped.
+    ptr: NonNull<u8>,
+    len: usize,
+    writable: bool,
+}
+
+// SAFETY: The mapping is plain memory owned by this value, accessed through `&self` or `&mut self`.
+unsafe impl Send for Mmap {}
+// SAFETY: As above.
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    /// Opens and maps the file at `path` read-only.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it's mapped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be opened or mapped.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        // SAFETY: Upheld by the caller.
+        unsafe { Self::map(&File::open(path)?) }
+    }
+
+    /// Maps `file` read-only. The mapping stays valid after `file` is closed.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while it's mapped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file is too large for the address space or can't be mapped, such as
+    /// on platforms other than Unix and Windows.
+    pub unsafe fn map(file: &File) -> io::Result<Self> {
+        Self::map_with(file, false)
+    }
+
+    /// Maps `file` copy-on-write: the slice can be modified with
+    /// [`as_mut_slice`](Self::as_mut_slice), but changes are private to this mapping and never
+    /// written back to the file.
+    ///
+    /// # Safety
+    ///
+    /// As for [`map`](Self::map), since unmodified pages are still shared with the file.
+    ///
+    /// # Errors
+    ///
+    /// As for [`map`](Self::map).
+    pub unsafe fn map_copy(file: &File) -> io::Result<Self> {
+        Self::map_with(file, true)
+    }
+
+    fn map_with(file: &File, writable: bool) -> io::Result<Self> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file too large to map"))?;
+        if len == 0 {
+            return Ok(Self {
+                ptr: NonNull::dangling(),
+                len,
+                writable,
+            });
+        }
+        let ptr = sys::map(file, len, writable)?;
+        Ok(Self { ptr, len, writable })
+    }
+
+    /// Length of the mapping in bytes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether the mapped file is empty.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Length of the mapping in bytes, as passed to `profile!(name, bytes = ...)` to report the
+    /// throughput of processing the whole file.
+    #[must_use]
+    pub fn byte_count(&self) -> u64 {
+        self.len as u64
+    }
+
+    /// Returns the mapped bytes.
+    #[must_use]
+    pub fn as_slice(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `len` mapped bytes, or is dangling with a length of 0.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+
+    /// Returns the mapped bytes for modification, or `None` if the mapping is read-only.
+    #[must_use]
+    pub fn as_mut_slice(&mut self) -> Option<&mut [u8]> {
+        // SAFETY: As in `as_slice`, and copy-on-write mappings are writable.
+        self.writable
+            .then(|| unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) })
+    }
+
+    /// Tells the operating system how the mapping will be accessed, so it can read pages ahead or
+    /// drop them. Does nothing on Windows.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `madvise`.
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        sys::advise(self.ptr, self.len, advice)
+    }
+}
+
+impl std::ops::Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for Mmap {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            sys::unmap(self.ptr, self.len);
+        }
+    }
+}
+
+#[cfg(unix)]
+mod sys {
+    use super::Advice;
+    use std::{
+        ffi::c_void,
+        fs::File,
+        io,
+        os::{
+            raw::{c_int, c_long},
+            unix::io::AsRawFd,
+        },
+        ptr::NonNull,
+    };
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_SHARED: c_int = 1;
+    const MAP_PRIVATE: c_int = 2;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: c_long,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+        fn madvise(addr: *mut c_void, len: usize, advice: c_int) -> c_int;
+    }
+
+    pub(super) fn map(file: &File, len: usize, writable: bool) -> io::Result<NonNull<u8>> {
+        let (prot, flags) = if writable {
+            (PROT_READ | PROT_WRITE, MAP_PRIVATE)
+        } else {
+            (PROT_READ, MAP_SHARED)
+        };
+        // SAFETY: A new mapping of an open file, at an address of the kernel's choosing.
+        let ptr = unsafe { mmap(std::ptr::null_mut(), len, prot, flags, file.as_raw_fd(), 0) };
+        // `MAP_FAILED` is -1.
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        NonNull::new(ptr.cast()).ok_or_else(io::Error::last_os_error)
+    }
+
+    pub(super) fn unmap(ptr: NonNull<u8>, len: usize) {
+        // SAFETY: `ptr` and `len` describe a mapping created by `map` and unmapped once.
+        unsafe { munmap(ptr.as_ptr().cast(), len) };
+    }
+
+    pub(super) fn advise(ptr: NonNull<u8>, len: usize, advice: Advice) -> io::Result<()> {
+        let advice = match advice {
+            Advice::Normal => 0,
+            Advice::Random => 1,
+            Advice::Sequential => 2,
+            Advice::WillNeed => 3,
+            Advice::DontNeed => 4,
+        };
+        // SAFETY: `ptr` and `len` describe a live mapping, and advice never changes its contents
+        // for file-backed shared or unmodified private pages.
+        if unsafe { madvise(ptr.as_ptr().cast(), len, advice) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(windows)]
+mod sys {
+    use super::Advice;
+    use std::{ffi::c_void, fs::File, io, os::windows::io::AsRawHandle, ptr::NonNull};
+
+    const PAGE_READONLY: u32 = 0x02;
+    const PAGE_WRITECOPY: u32 = 0x08;
+    const FILE_MAP_COPY: u32 = 0x01;
+    const FILE_MAP_READ: u32 = 0x04;
+
+    extern "system" {
+        fn CreateFileMappingW(
+            file: *mut c_void,
+            attributes: *mut c_void,
+            protect: u32,
+            max_size_high: u32,
+            max_size_low: u32,
+            name: *const u16,
+        ) -> *mut c_void;
+        fn MapViewOfFile(
+            mapping: *mut c_void,
+            access: u32,
+            offset_high: u32,
+            offset_low: u32,
+            len: usize,
+        ) -> *mut c_void;
+        fn UnmapViewOfFile(address: *const c_void) -> i32;
+        fn CloseHandle(handle: *mut c_void) -> i32;
+    }
+
+    pub(super) fn map(file: &File, len: usize, writable: bool) -> io::Result<NonNull<u8>> {
+        let (protect, access) = if writable {
+            (PAGE_WRITECOPY, FILE_MAP_COPY)
+        } else {
+            (PAGE_READONLY, FILE_MAP_READ)
+        };
+        // SAFETY: Creates an unnamed mapping object of the whole open file.
+        let mapping = unsafe {
+            CreateFileMappingW(
+                file.as_raw_handle().cast(),
+                std::ptr::null_mut(),
+                protect,
+                0,
+                0,
+                std::ptr::null(),
+            )
+        };
+        if mapping.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `mapping` is the valid mapping object created above.
+        let ptr = unsafe { MapViewOfFile(mapping, access, 0, 0, len) };
+        let result = NonNull::new(ptr.cast()).ok_or_else(io::Error::last_os_error);
+        // SAFETY: The view keeps the mapping object alive after its handle is closed.
+        unsafe { CloseHandle(mapping) };
+        result
+    }
+
+    pub(super) fn unmap(ptr: NonNull<u8>, _len: usize) {
+        // SAFETY: `ptr` is the start of a view created by `map` and unmapped once.
+        unsafe { UnmapViewOfFile(ptr.as_ptr().cast()) };
+    }
+
+    pub(super) fn advise(_ptr: NonNull<u8>, _len: usize, _advice: Advice) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    use super::Advice;
+    use std::{fs::File, io, ptr::NonNull};
+
+    pub(super) fn map(_file: &File, _len: usize, _writable: bool) -> io::Result<NonNull<u8>> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "memory maps are unsupported on this platform",
+        ))
+    }
+
+    pub(super) fn unmap(_ptr: NonNull<u8>, _len: usize) {}
+
+    pub(super) fn advise(_ptr: NonNull<u8>, _len: usize, _advice: Advice) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_files() -> io::Result<()> {
+        let dir = std::env::temp_dir().join(format!("util_lib_rs_mmap_{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("data.bin");
+        let data: Vec<u8> = (0..10_000u32).map(|n| (n % 251) as u8).collect();
+        std::fs::write(&path, &data)?;
+
+        // SAFETY: Nothing else modifies the file while it's mapped.
+        let mut map = unsafe { Mmap::open(&path)? };
+        assert_eq!(map.as_slice(), data);
+        assert_eq!((map.len(), map.byte_count()), (10_000, 10_000));
+        assert!(map.as_mut_slice().is_none());
+        map.advise(Advice::WillNeed)?;
+
+        let file = File::open(&path)?;
+        // SAFETY: As above.
+        let mut copy = unsafe { Mmap::map_copy(&file)? };
+        copy.as_mut_slice().expect("writable mapping")[0] = 0xff;
+        assert_eq!(copy[0], 0xff);
+        assert_eq!(std::fs::read(&path)?[0], 0);
+        drop((map, copy, file));
+
+        let empty = dir.join("empty.bin");
+        std::fs::write(&empty, [])?;
+        // SAFETY: As above.
+        let map = unsafe { Mmap::open(&empty)? };
+        assert!(map.is_empty());
+        assert_eq!(map.as_slice(), &[] as &[u8]);
+        map.advise(Advice::Random)?;
+        drop(map);
+
+        std::fs::remove_dir_all(&dir)
+    }
+}
@@ -0,0 +1,42 @@
+//! JSON reading and writing.
+//!
+//! A [`JsonWriter`] streams JSON to a writer one value at a time, placing the commas and escaping
+//! strings, which is how the Chrome trace and speedscope exporters write their files. [`parse`]
+//! reads a document into a [`Value`] tree, for saved reports and simple configuration, with a
+//! [`ParseError`] giving the line, column and byte span of invalid input.
+//!
+//! Only what the profiler needs is supported: objects keep their keys sorted, and numbers are
+//! kept as integers when they fit in one.
+
+pub mod value;
+pub mod writer;
+
+pub use value::{parse, Number, ParseError, Value};
+pub use writer::JsonWriter;
+
+use std::fmt::Write as _;
+
+/// Escapes text for use in a JSON string, without the quotes.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::json::escape;
+///
+/// assert_eq!(escape("say \"hi\"\n"), r#"say \"hi\"\u000a"#);
+/// ```
+#[must_use]
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{:04x}", u32::from(c));
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,797 @@
+//! JSON documents.
+
+use super::escape;
+use std::{collections::BTreeMap, error::Error, fmt, ops::Index, ops::Range, str::FromStr};
+
+/// Deepest nesting of arrays and objects [`parse`] accepts.
+const MAX_DEPTH: usize = 128;
+
+/// A JSON number, kept as an integer when it has no fraction or exponent and fits in one.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Number(Kind);
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Kind {
+    Unsigned(u64),
+    /// Always negative.
+    Signed(i64),
+    Float(f64),
+}
+
+impl Number {
+    /// Returns the number as an unsigned integer, if it's one.
+    #[must_use]
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.0 {
+            Kind::Unsigned(n) => Some(n),
+            Kind::Signed(_) | Kind::Float(_) => None,
+        }
+    }
+
+    /// Returns the number as a signed integer, if it's one in range.
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.0 {
+            Kind::Unsigned(n) => i64::try_from(n).ok(),
+            Kind::Signed(n) => Some(n),
+            Kind::Float(_) => None,
+        }
+    }
+
+    /// Returns the number as a float, rounding integers too large to represent exactly.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn as_f64(&self) -> f64 {
+        match self.0 {
+            Kind::Unsigned(n) => n as f64,
+            Kind::Signed(n) => n as f64,
+            Kind::Float(n) => n,
+        }
+    }
+}
+
+impl From<u64> for Number {
+    fn from(n: u64) -> Self {
+        Self(Kind::Unsigned(n))
+    }
+}
+
+impl From<i64> for Number {
+    fn from(n: i64) -> Self {
+        u64::try_from(n).map_or(Self(Kind::Signed(n)), Self::from)
+    }
+}
+
+impl From<f64> for Number {
+    /// Converts a float, which is written as `null` if it isn't finite.
+    fn from(n: f64) -> Self {
+        Self(Kind::Float(n))
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Kind::Unsigned(n) => write!(f, "{n}"),
+            Kind::Signed(n) => write!(f, "{n}"),
+            Kind::Float(n) if !n.is_finite() => f.write_str("null"),
+            // Keeps a point so the number reads back as a float.
+            Kind::Float(n) if n.fract() == 0.0 && n.abs() < 1e16 => write!(f, "{n:.1}"),
+            Kind::Float(n) => write!(f, "{n}"),
+        }
+    }
+}
+
+/// A JSON value.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::json::{self, Value};
+///
+/// # fn main() -> Result<(), json::ParseError> {
+/// let value = json::parse(r#"{"name": "main", "hits": [3, 5], "ratio": 0.5}"#)?;
+/// assert_eq!(value["name"].as_str(), Some("main"));
+/// assert_eq!(value["hits"][1].as_u64(), Some(5));
+/// assert!(value["missing"].is_null());
+/// assert_eq!(value.to_string(), r#"{"hits":[3,5],"name":"main","ratio":0.5}"#);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Value {
+    /// `null`.
+    #[default]
+    Null,
+    /// `true` or `false`.
+    Bool(bool),
+    /// A number.
+    Number(Number),
+    /// A string.
+    String(String),
+    /// An array.
+    Array(Vec<Value>),
+    /// An object, whose keys are kept sorted. The last of duplicate keys wins.
+    Object(BTreeMap<String, Value>),
+}
+
+/// The value indexing returns for missing members.
+static NULL: Value = Value::Null;
+
+impl Value {
+    /// Returns the member `key` of an object, or `None` if it's missing or this isn't an object.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object()?.get(key)
+    }
+
+    /// Whether the value is `null`.
+    #[must_use]
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// Returns the value of a boolean.
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a number.
+    #[must_use]
+    pub fn as_number(&self) -> Option<Number> {
+        match self {
+            Self::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a number which is an unsigned integer.
+    #[must_use]
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number()?.as_u64()
+    }
+
+    /// Returns the value of a number which is a signed integer in range.
+    #[must_use]
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number()?.as_i64()
+    }
+
+    /// Returns the value of any number as a float.
+    #[must_use]
+    pub fn as_f64(&self) -> Option<f64> {
+        Some(self.as_number()?.as_f64())
+    }
+
+    /// Returns the value of a string.
+    #[must_use]
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the elements of an array.
+    #[must_use]
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Self::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    /// Returns the members of an object.
+    #[must_use]
+    pub fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Self::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+}
+
+impl Index<&str> for Value {
+    type Output = Value;
+
+    /// Returns the member `key` of an object, or `null` if it's missing or this isn't an object.
+    fn index(&self, key: &str) -> &Value {
+        self.get(key).unwrap_or(&NULL)
+    }
+}
+
+impl Index<usize> for Value {
+    type Output = Value;
+
+    /// Returns the element `index` of an array, or `null` if it's out of range or this isn't an
+    /// array.
+    fn index(&self, index: usize) -> &Value {
+        self.as_array()
+            .and_then(|values| values.get(index))
+            .unwrap_or(&NULL)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(value: u64) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Self {
+        Self::Number(value.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats the value as compact JSON.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => f.write_str("null"),
+            Self::Bool(value) => write!(f, "{value}"),
+            Self::Number(number) => write!(f, "{number}"),
+            Self::String(value) => write!(f, "\"{}\"", escape(value)),
+            Self::Array(values) => {
+                f.write_str("[")?;
+                for (index, value) in values.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { "," };
+                    write!(f, "{separator}{value}")?;
+                }
+                f.write_str("]")
+            }
+            Self::Object(members) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    let separator = if index == 0 { "" } else { "," };
+                    write!(f, "{separator}\"{}\":{value}", escape(key))?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl FromStr for Value {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Self, ParseError> {
+        parse(text)
+    }
+}
+
+/// Invalid JSON, with where in the input it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    span: Range<usize>,
+    line: usize,
+    column: usize,
+    message: &'static str,
+}
+
+impl ParseError {
+    fn new(text: &str, span: Range<usize>, message: &'static str) -> Self {
+        let before = &text[..span.start];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            span,
+            message,
+        }
+    }
+
+    /// Returns the byte range of the invalid input, which is empty at the end of the input.
+    #[must_use]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    /// Returns the line of the invalid input, starting from 1.
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Returns the column of the invalid input in characters, starting from 1.
+    #[must_use]
+    pub const fn column(&self) -> usize {
+        self.column
+    }
+
+    /// Returns the line of `text`, the input which failed to parse, with the invalid input
+    /// underlined.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::json;
+    ///
+    /// let text = "{\n  \"hits\": tru\n}";
+    /// let err = json::parse(text).unwrap_err();
+    /// assert_eq!(err.to_string(), "line 2, column 11: expected a value");
+    /// assert_eq!(err.snippet(text), "2 |   \"hits\": tru\n  |           ^^^");
+    /// ```
+    #[must_use]
+    pub fn snippet(&self, text: &str) -> String {
+        let start = self.span.start.min(text.len());
+        let line_start = text[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = text[start..]
+            .find('\n')
+            .map_or(text.len(), |end| start + end);
+        let end = self.span.end.clamp(start, line_end);
+        let number = self.line.to_string();
+        format!(
+            "{number} | {}\n{:width$} | {}{}",
+            &text[line_start..line_end],
+            "",
+            " ".repeat(text[line_start..start].chars().count()),
+            "^".repeat(text[start..end].chars().count().max(1)),
+            width = number.len()
+        )
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+/// Parses a JSON document.
+///
+/// # Errors
+///
+/// Returns an error if `text` isn't a single valid JSON value, optionally surrounded by
+/// whitespace, or nests arrays and objects more than 128 deep.
+pub fn parse(text: &str) -> Result<Value, ParseError> {
+    let mut parser = Parser {
+        text,
+        pos: 0,
+        depth: 0,
+    };
+    parser.skip_whitespace();
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error(
+            parser.pos..text.len(),
+            "unexpected characters after the value",
+        ));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn error(&self, span: Range<usize>, message: &'static str) -> ParseError {
+        ParseError::new(self.text, span, message)
+    }
+
+    /// Returns an error spanning the character at the current position.
+    fn error_here(&self, message: &'static str) -> ParseError {
+        let len = self.text[self.pos..]
+            .chars()
+            .next()
+            .map_or(0, char::len_utf8);
+        self.error(self.pos..self.pos + len, message)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &'static str) -> Result<(), ParseError> {
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error_here(message))
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        match self.peek() {
+            None => Err(self.error_here("unexpected end of input")),
+            Some(b'{') => self.nested(Self::object),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => {
+                for (word, value) in [
+                    ("true", Value::Bool(true)),
+                    ("false", Value::Bool(false)),
+                    ("null", Value::Null),
+                ] {
+                    if self.text[self.pos..].starts_with(word) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                // Underlines the whole word, if it's one.
+                let len = self.text[self.pos..]
+                    .find(|c: char| !c.is_alphanumeric())
+                    .unwrap_or(self.text.len() - self.pos);
+                if len == 0 {
+                    Err(self.error_here("expected a value"))
+                } else {
+                    Err(self.error(self.pos..self.pos + len, "expected a value"))
+                }
+            }
+        }
+    }
+
+    fn nested(
+        &mut self,
+        parse: fn(&mut Self) -> Result<Value, ParseError>,
+    ) -> Result<Value, ParseError> {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error_here("too deeply nested"));
+        }
+        self.depth += 1;
+        let value = parse(self);
+        self.depth -= 1;
+        value
+    }
+
+    fn object(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1;
+        let mut members = BTreeMap::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Value::Object(members));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error_here("expected a string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(b':', "expected `:`")?;
+            self.skip_whitespace();
+            let value = self.value()?;
+            members.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Value::Object(members));
+                }
+                _ => return Err(self.error_here("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Value::Array(values));
+        }
+        loop {
+            self.skip_whitespace();
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Value::Array(values));
+                }
+                _ => return Err(self.error_here("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => {
+                    return Err(self.error(start..self.text.len(), "unterminated string"));
+                }
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(value);
+                }
+                Some(b'\\') => value.push(self.escape()?),
+                Some(0..0x20) => return Err(self.error_here("control character in string")),
+                Some(_) => {
+                    // Special characters are all ASCII, so the run ends on a character boundary.
+                    let len = self.text.as_bytes()[self.pos..]
+                        .iter()
+                        .position(|&b| b == b'"' || b == b'\\' || b < 0x20)
+                        .unwrap_or(self.text.len() - self.pos);
+                    value.push_str(&self.text[self.pos..self.pos + len]);
+                    self.pos += len;
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let start = self.pos;
+        self.pos += 1;
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => {
+                self.pos += 1;
+                let high = self.hex4(start)?;
+                let code = if (0xd800..0xdc00).contains(&high) {
+                    if !self.text[self.pos..].starts_with("\\u") {
+                        return Err(self.error(start..self.pos, "unpaired surrogate"));
+                    }
+                    self.pos += 2;
+                    let low = self.hex4(start)?;
+                    if !(0xdc00..0xe000).contains(&low) {
+                        return Err(self.error(start..self.pos, "unpaired surrogate"));
+                    }
+                    0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                } else {
+                    high
+                };
+                return char::from_u32(code)
+                    .ok_or_else(|| self.error(start..self.pos, "unpaired surrogate"));
+            }
+            _ => {
+                let len = self.text[self.pos..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+                return Err(self.error(start..self.pos + len, "invalid escape"));
+            }
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    /// Parses the four hex digits of a `\u` escape starting at `start`.
+    fn hex4(&mut self, start: usize) -> Result<u32, ParseError> {
+        let digits = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| {
+                let end = (self.pos + 4).min(self.text.len());
+                let end = (end..=self.text.len())
+                    .find(|&end| self.text.is_char_boundary(end))
+                    .unwrap_or(self.text.len());
+                self.error(start..end, "invalid escape")
+            })?;
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap_or_default())
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        let negative = self.peek() == Some(b'-');
+        if negative {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(self.error_here("expected digits")),
+        }
+        let mut float = false;
+        if self.peek() == Some(b'.') {
+            float = true;
+            self.pos += 1;
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error_here("expected digits after `.`"));
+            }
+            self.digits();
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            float = true;
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            if !self.peek().is_some_and(|b| b.is_ascii_digit()) {
+                return Err(self.error_here("expected digits in exponent"));
+            }
+            self.digits();
+        }
+
+        let text = &self.text[start..self.pos];
+        if !float {
+            let integer = if negative {
+                text.parse::<i64>().ok().map(Number::from)
+            } else {
+                text.parse::<u64>().ok().map(Number::from)
+            };
+            if let Some(integer) = integer {
+                return Ok(Value::Number(integer));
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Value::Number(n.into())),
+            _ => Err(self.error(start..self.pos, "number out of range")),
+        }
+    }
+
+    fn digits(&mut self) {
+        while self.peek().is_some_and(|b| b.is_ascii_digit()) {
+            self.pos += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let value = parse(
+            r#" {"a": [1, -2, 3.5, 1e3, 18446744073709551615, -0],
+                "b": {"c": null, "d": true, "e": false},
+                "s": "q\"\\\/\b\f\n\r\t\u00e9\ud83d\ude00é", "a": "last"} "#,
+        )
+        .expect("valid JSON");
+        assert_eq!(value["a"].as_str(), Some("last"));
+        assert_eq!(value["s"].as_str(), Some("q\"\\/\u{8}\u{c}\n\r\té😀é"));
+        assert_eq!(value["b"]["d"].as_bool(), Some(true));
+        assert!(value["b"]["c"].is_null() && value["b"].get("c").is_some());
+
+        let numbers = parse("[1, -2, 3.5, 1e3, 18446744073709551615, -0]").expect("valid JSON");
+        assert_eq!(numbers[0].as_u64(), Some(1));
+        assert_eq!(numbers[1].as_i64(), Some(-2));
+        assert_eq!(numbers[1].as_u64(), None);
+        assert_eq!(numbers[2].as_f64(), Some(3.5));
+        assert_eq!(numbers[3].as_u64(), None);
+        assert_eq!(numbers[3].as_f64(), Some(1000.0));
+        assert_eq!(numbers[4].as_u64(), Some(u64::MAX));
+        assert_eq!(numbers[5].as_i64(), Some(0));
+        assert!(numbers[6].is_null() && numbers["key"].is_null());
+        assert_eq!(
+            numbers.to_string(),
+            "[1,-2,3.5,1000.0,18446744073709551615,0]"
+        );
+        assert_eq!(parse(&numbers.to_string()), Ok(numbers));
+
+        assert_eq!("[]".parse::<Value>(), Ok(Value::Array(Vec::new())));
+        assert_eq!(
+            parse("{\"k\\n\":\"\\u0001\"}")
+                .expect("valid JSON")
+                .to_string(),
+            r#"{"k\u000a":"\u0001"}"#
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let error = |text: &str| {
+            let err = parse(text).expect_err("invalid JSON");
+            (err.to_string(), err.span())
+        };
+        let message =
+            |message: &str, line, column| format!("line {line}, column {column}: {message}");
+        assert_eq!(error(""), (message("unexpected end of input", 1, 1), 0..0));
+        assert_eq!(error("[1,]"), (message("expected a value", 1, 4), 3..4));
+        assert_eq!(error("[1 2]"), (message("expected `,` or `]`", 1, 4), 3..4));
+        assert_eq!(
+            error("{1: 2}"),
+            (message("expected a string key", 1, 2), 1..2)
+        );
+        assert_eq!(error("{\"a\" 2}"), (message("expected `:`", 1, 6), 5..6));
+        assert_eq!(
+            error("{\"a\": 1"),
+            (message("expected `,` or `}`", 1, 8), 7..7)
+        );
+        assert_eq!(error("\"abc"), (message("unterminated string", 1, 1), 0..4));
+        assert_eq!(
+            error("\"a\nb\""),
+            (message("control character in string", 1, 3), 2..3)
+        );
+        assert_eq!(error(r#""\x""#), (message("invalid escape", 1, 2), 1..3));
+        assert_eq!(
+            error(r#""\u12g4""#),
+            (message("invalid escape", 1, 2), 1..7)
+        );
+        assert_eq!(
+            error(r#""\ud800""#),
+            (message("unpaired surrogate", 1, 2), 1..7)
+        );
+        assert_eq!(
+            error("01"),
+            (message("unexpected characters after the value", 1, 2), 1..2)
+        );
+        assert_eq!(error("-"), (message("expected digits", 1, 2), 1..1));
+        assert_eq!(
+            error("1."),
+            (message("expected digits after `.`", 1, 3), 2..2)
+        );
+        assert_eq!(
+            error("1e+"),
+            (message("expected digits in exponent", 1, 4), 3..3)
+        );
+        assert_eq!(error("1e400"), (message("number out of range", 1, 1), 0..5));
+        assert_eq!(
+            error("[\n  nul]"),
+            (message("expected a value", 2, 3), 4..7)
+        );
+        assert_eq!(error("é"), (message("expected a value", 1, 1), 0..2));
+        let deep = "[".repeat(MAX_DEPTH + 1);
+        assert_eq!(
+            error(&deep),
+            (
+                message("too deeply nested", 1, MAX_DEPTH + 1),
+                MAX_DEPTH..MAX_DEPTH + 1
+            )
+        );
+        assert!(parse(&format!(
+            "{}{}",
+            "[".repeat(MAX_DEPTH),
+            "]".repeat(MAX_DEPTH)
+        ))
+        .is_ok());
+
+        let text = "[1,\n é x]";
+        let err = parse(text).expect_err("invalid JSON");
+        assert_eq!(err.snippet(text), "2 |  é x]\n  |  ^");
+        let text = "[1";
+        let err = parse(text).expect_err("invalid JSON");
+        assert_eq!(err.snippet(text), "1 | [1\n  |   ^");
+    }
+}
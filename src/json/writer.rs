@@ -0,0 +1,289 @@
+//! Streaming JSON output.
+
+use super::{escape, Number, Value};
+use std::io::{self, Write};
+
+/// A container being written.
+#[derive(Debug, Copy, Clone)]
+struct Open {
+    object: bool,
+    /// Whether anything was written in it yet, so the next element needs a comma.
+    nonempty: bool,
+}
+
+/// Writes JSON to a writer as it's produced, without building a [`Value`] first. Methods
+/// return the writer so calls can be chained with `?`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::json::JsonWriter;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut json = JsonWriter::new(Vec::new());
+/// json.begin_object()?
+///     .key("name")?
+///     .string("main")?
+///     .key("hits")?
+///     .begin_array()?
+///     .u64(3)?
+///     .u64(5)?
+///     .end_array()?
+///     .end_object()?;
+/// assert_eq!(json.into_inner(), br#"{"name":"main","hits":[3,5]}"#);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct JsonWriter<W: Write> {
+    writer: W,
+    open: Vec<Open>,
+    /// Whether a key was just written, so the next value belongs to it.
+    after_key: bool,
+}
+
+impl<W: Write> JsonWriter<W> {
+    /// Creates a writer writing to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            open: Vec::new(),
+            after_key: false,
+        }
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes the comma before a value, if it needs one.
+    fn separate(&mut self) -> io::Result<()> {
+        if self.after_key {
+            self.after_key = false;
+            return Ok(());
+        }
+        if let Some(open) = self.open.last_mut() {
+            if open.object {
+                return Err(invalid("object values need a key"));
+            }
+            if open.nonempty {
+                self.writer.write_all(b",")?;
+            }
+            open.nonempty = true;
+        }
+        Ok(())
+    }
+
+    fn begin(&mut self, object: bool) -> io::Result<&mut Self> {
+        self.separate()?;
+        self.writer.write_all(if object { b"{" } else { b"[" })?;
+        self.open.push(Open {
+            object,
+            nonempty: false,
+        });
+        Ok(self)
+    }
+
+    fn end(&mut self, object: bool) -> io::Result<&mut Self> {
+        match self.open.last() {
+            Some(open) if open.object == object && !self.after_key => {}
+            _ if object => return Err(invalid("no object to end")),
+            _ => return Err(invalid("no array to end")),
+        }
+        self.open.pop();
+        self.writer.write_all(if object { b"}" } else { b"]" })?;
+        Ok(self)
+    }
+
+    /// Begins an object, whose members are written as a [`key`](Self::key) followed by a value.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn begin_object(&mut self) -> io::Result<&mut Self> {
+        self.begin(true)
+    }
+
+    /// Ends the innermost object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if the innermost container isn't an object or has a
+    /// key without a value.
+    pub fn end_object(&mut self) -> io::Result<&mut Self> {
+        self.end(true)
+    }
+
+    /// Begins an array.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn begin_array(&mut self) -> io::Result<&mut Self> {
+        self.begin(false)
+    }
+
+    /// Ends the innermost array.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if the innermost container isn't an array.
+    pub fn end_array(&mut self) -> io::Result<&mut Self> {
+        self.end(false)
+    }
+
+    /// Writes the key of the next member of the innermost object.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it isn't in an object or follows another key.
+    pub fn key(&mut self, key: &str) -> io::Result<&mut Self> {
+        match self.open.last_mut() {
+            Some(open) if open.object && !self.after_key => {
+                if open.nonempty {
+                    self.writer.write_all(b",")?;
+                }
+                open.nonempty = true;
+            }
+            _ => return Err(invalid("keys must be in an object, before a value")),
+        }
+        write!(self.writer, "\"{}\":", escape(key))?;
+        self.after_key = true;
+        Ok(self)
+    }
+
+    fn write_value(&mut self, value: impl std::fmt::Display) -> io::Result<&mut Self> {
+        self.separate()?;
+        write!(self.writer, "{value}")?;
+        Ok(self)
+    }
+
+    /// Writes a string.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn string(&mut self, value: &str) -> io::Result<&mut Self> {
+        self.write_value(format_args!("\"{}\"", escape(value)))
+    }
+
+    /// Writes an unsigned integer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn u64(&mut self, value: u64) -> io::Result<&mut Self> {
+        self.write_value(value)
+    }
+
+    /// Writes a signed integer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn i64(&mut self, value: i64) -> io::Result<&mut Self> {
+        self.write_value(value)
+    }
+
+    /// Writes a floating-point number, or `null` if it isn't finite.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn f64(&mut self, value: f64) -> io::Result<&mut Self> {
+        self.write_value(Number::from(value))
+    }
+
+    /// Writes a floating-point number with `decimals` digits after the point, or `null` if it
+    /// isn't finite.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn fixed(&mut self, value: f64, decimals: usize) -> io::Result<&mut Self> {
+        if value.is_finite() {
+            self.write_value(format_args!("{value:.decimals$}"))
+        } else {
+            self.null()
+        }
+    }
+
+    /// Writes `true` or `false`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn bool(&mut self, value: bool) -> io::Result<&mut Self> {
+        self.write_value(value)
+    }
+
+    /// Writes `null`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn null(&mut self) -> io::Result<&mut Self> {
+        self.write_value("null")
+    }
+
+    /// Writes a whole [`Value`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails, or if it's in an object without a key.
+    pub fn value(&mut self, value: &Value) -> io::Result<&mut Self> {
+        self.write_value(value)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_json() {
+        let mut json = JsonWriter::new(Vec::new());
+        json.begin_array()
+            .and_then(|json| json.begin_object())
+            .and_then(|json| {
+                json.key("a\"")?
+                    .i64(-1)?
+                    .key("b")?
+                    .begin_array()?
+                    .end_array()
+            })
+            .and_then(|json| json.key("c")?.fixed(1.0 / 3.0, 3)?.key("d")?.f64(f64::NAN))
+            .and_then(|json| {
+                json.end_object()?
+                    .bool(true)?
+                    .null()?
+                    .f64(2.5)?
+                    .string("\t")
+            })
+            .and_then(|json| json.value(&Value::from("v"))?.end_array())
+            .expect("valid JSON");
+        assert_eq!(
+            String::from_utf8(json.into_inner()).expect("valid UTF-8"),
+            r#"[{"a\"":-1,"b":[],"c":0.333,"d":null},true,null,2.5,"\u0009","v"]"#
+        );
+    }
+
+    #[test]
+    fn rejects_misuse() {
+        let mut json = JsonWriter::new(Vec::new());
+        assert!(json.key("a").is_err());
+        assert!(json.end_object().is_err());
+        json.begin_object().expect("valid begin");
+        assert!(json.u64(1).is_err());
+        assert!(json.end_array().is_err());
+        json.key("a").expect("valid key");
+        assert!(json.key("b").is_err());
+        assert!(json.end_object().is_err());
+    }
+}
@@ -1,4 +1,77 @@
 //! Utility library. A collection of useful rust utilities.
 
+#[warn(clippy::all, clippy::pedantic)]
+pub mod arena;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod bench;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod bits;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod checksum;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod cli;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod collections;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod config;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod encoding;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod env;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod error;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod fmt;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod fs;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod guard;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod hash;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod id;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod intern;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod io;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod json;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod log;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod mem;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod path_util;
 #[warn(clippy::all, clippy::pedantic)]
 pub mod performance;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod pool;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod process;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod rand;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod rate;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod retry;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod signal;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod stats;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod str_util;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod sync;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod sys;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod term;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod test_util;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod thread;
+#[warn(clippy::all, clippy::pedantic)]
+pub mod time;
+
+#[cfg(feature = "macros")]
+pub use util_lib_rs_macros::{profile_all, profile_fn};
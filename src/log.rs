@@ -0,0 +1,311 @@
+//! Leveled logging.
+//!
+//! The [`error!`](crate::error!), [`warn!`](crate::warn!), [`info!`](crate::info!),
+//! [`debug!`](crate::debug!) and [`trace!`](crate::trace!) macros write a timestamped line per
+//! message to `stderr`, or the writer installed with [`set_writer`], without depending on the
+//! `log` or `tracing` crates.
+//!
+//! Levels are colored when writing to a terminal's `stderr`, following `NO_COLOR` and
+//! `CLICOLOR_FORCE` as [`term::style`](crate::term::style) does.
+//!
+//! Messages above the level set with [`set_max_level`], `Info` by default, are skipped at runtime.
+//! The `log-max-level-*` features set [`STATIC_MAX_LEVEL`], above which the macros compile to
+//! nothing, e.g. `log-max-level-info` removes `debug!` and `trace!` from release builds.
+
+use crate::term::style::{self, Color, Stream, Style};
+use std::{
+    fmt,
+    io::Write,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, OnceLock, PoisonError,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Importance of a message, most important first.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    /// Failures the program couldn't recover from.
+    Error = 1,
+    /// Problems the program worked around.
+    Warn,
+    /// Progress worth seeing by default.
+    Info,
+    /// Details for debugging.
+    Debug,
+    /// Very verbose details, such as every step of a loop.
+    Trace,
+}
+
+impl Level {
+    /// Style of the level in messages written to a terminal.
+    const fn style(self) -> Style {
+        match self {
+            Self::Error => Style::new().fg(Color::Red).bold(),
+            Self::Warn => Style::new().fg(Color::Yellow),
+            Self::Info => Style::new().fg(Color::Green),
+            Self::Debug => Style::new().fg(Color::Blue),
+            Self::Trace => Style::new().dim(),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Error => "ERROR",
+            Self::Warn => "WARN",
+            Self::Info => "INFO",
+            Self::Debug => "DEBUG",
+            Self::Trace => "TRACE",
+        })
+    }
+}
+
+/// Most verbose level logged, or `Off` to log nothing.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LevelFilter {
+    /// Log nothing.
+    Off,
+    /// Log only errors.
+    Error,
+    /// Log warnings and errors.
+    Warn,
+    /// Log info messages, warnings and errors.
+    Info,
+    /// Log everything but trace messages.
+    Debug,
+    /// Log everything.
+    Trace,
+}
+
+impl LevelFilter {
+    /// Whether messages at `level` pass this filter.
+    #[must_use]
+    pub const fn allows(self, level: Level) -> bool {
+        level as usize <= self as usize
+    }
+
+    const fn from_usize(value: usize) -> Self {
+        match value {
+            0 => Self::Off,
+            1 => Self::Error,
+            2 => Self::Warn,
+            3 => Self::Info,
+            4 => Self::Debug,
+            _ => Self::Trace,
+        }
+    }
+}
+
+/// Most verbose level the logging macros are compiled in for, set by the most restrictive
+/// `log-max-level-*` feature enabled.
+pub const STATIC_MAX_LEVEL: LevelFilter = if cfg!(feature = "log-max-level-off") {
+    LevelFilter::Off
+} else if cfg!(feature = "log-max-level-error") {
+    LevelFilter::Error
+} else if cfg!(feature = "log-max-level-warn") {
+    LevelFilter::Warn
+} else if cfg!(feature = "log-max-level-info") {
+    LevelFilter::Info
+} else if cfg!(feature = "log-max-level-debug") {
+    LevelFilter::Debug
+} else {
+    LevelFilter::Trace
+};
+
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(LevelFilter::Info as usize);
+static WRITER: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Sets the most verbose level logged at runtime. Levels above [`STATIC_MAX_LEVEL`] are never
+/// logged.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{debug, log::{set_max_level, LevelFilter}};
+///
+/// set_max_level(LevelFilter::Debug);
+/// debug!("loaded {} entries", 3);
+/// ```
+pub fn set_max_level(level: LevelFilter) {
+    MAX_LEVEL.store(level as usize, Ordering::Relaxed);
+}
+
+/// Returns the most verbose level logged at runtime.
+#[must_use]
+pub fn max_level() -> LevelFilter {
+    LevelFilter::from_usize(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+/// Whether messages at `level` are logged.
+#[inline]
+#[must_use]
+pub fn enabled(level: Level) -> bool {
+    STATIC_MAX_LEVEL.allows(level) && max_level().allows(level)
+}
+
+/// Sets the writer every message is written to. Pass `None` to write to `stderr`.
+pub fn set_writer(writer: Option<Box<dyn Write + Send>>) {
+    *WRITER.lock().unwrap_or_else(PoisonError::into_inner) = writer;
+}
+
+/// Writes a message at `level` from the module `target`. Prefer the logging macros, which skip
+/// formatting disabled messages.
+#[doc(hidden)]
+pub fn write_message(level: Level, target: &str, message: fmt::Arguments<'_>) {
+    static STDERR_STYLED: OnceLock<bool> = OnceLock::new();
+
+    let mut writer = WRITER.lock().unwrap_or_else(PoisonError::into_inner);
+    let styled = writer.is_none() && *STDERR_STYLED.get_or_init(|| style::enabled(Stream::Stderr));
+    let line = format!(
+        "{} {:<5} {target}: {message}\n",
+        fmt_timestamp(SystemTime::now()),
+        level.style().when(styled).paint(level)
+    );
+    let result = match writer.as_mut() {
+        Some(writer) => writer
+            .write_all(line.as_bytes())
+            .and_then(|()| writer.flush()),
+        None => std::io::stderr().write_all(line.as_bytes()),
+    };
+    if let Err(err) = result {
+        eprintln!("failed to write log message: {err}");
+    }
+}
+
+/// Formats `time` as an RFC 3339 UTC timestamp with milliseconds.
+fn fmt_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Converts days since 1970-01-01 to a proleptic Gregorian date, counting years from March so
+    // leap days end each year.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Log a message at `level`, formatted like [`format!`], if the level is enabled.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{log, log::Level};
+///
+/// log!(Level::Warn, "retrying in {}s", 5);
+/// ```
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)+) => {{
+        let level = $level;
+        if $crate::log::enabled(level) {
+            $crate::log::write_message(level, module_path!(), format_args!($($arg)+));
+        }
+    }};
+}
+
+/// Log an error, formatted like [`format!`].
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::error;
+///
+/// error!("failed to open {}", "config.toml");
+/// ```
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Error, $($arg)+)
+    };
+}
+
+/// Log a warning, formatted like [`format!`].
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Warn, $($arg)+)
+    };
+}
+
+/// Log an info message, formatted like [`format!`].
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Info, $($arg)+)
+    };
+}
+
+/// Log a debug message, formatted like [`format!`].
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Debug, $($arg)+)
+    };
+}
+
+/// Log a trace message, formatted like [`format!`].
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        $crate::log!($crate::log::Level::Trace, $($arg)+)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(fmt_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+        assert_eq!(
+            fmt_timestamp(UNIX_EPOCH + Duration::from_millis(951_782_400_250)),
+            "2000-02-29T00:00:00.250Z"
+        );
+        assert_eq!(
+            fmt_timestamp(UNIX_EPOCH + Duration::from_secs(1_792_009_845)),
+            "2026-10-14T20:30:45.000Z"
+        );
+    }
+
+    #[test]
+    fn level_filters() {
+        assert!(LevelFilter::Info.allows(Level::Error));
+        assert!(LevelFilter::Info.allows(Level::Info));
+        assert!(!LevelFilter::Info.allows(Level::Debug));
+        assert!(!LevelFilter::Off.allows(Level::Error));
+        assert_eq!(
+            LevelFilter::from_usize(LevelFilter::Debug as usize),
+            LevelFilter::Debug
+        );
+        assert_eq!(format!("{:<5}|", Level::Warn), "WARN |");
+        assert_eq!(
+            format!("{:<5}|", Level::Info.style().paint(Level::Info)),
+            "\x1b[32mINFO \x1b[0m|"
+        );
+    }
+}
@@ -0,0 +1,295 @@
+//! Byte-slice search and memory utilities.
+//!
+//! [`find_byte`] and [`rfind_byte`] scan eight bytes at a time with bit tricks on every target, and
+//! sixteen at a time with SSE2 on `x86_64`, for the delimiter and newline searches of parsing hot
+//! loops. [`find_subslice`] finds a byte string by scanning for its first byte. [`fill_pattern`]
+//! repeats a pattern through a buffer in doubling copies, and [`prefetch`] asks the processor to
+//! start loading memory which will be read soon.
+
+/// Bytes in each word scanned at a time.
+const WORD: usize = std::mem::size_of::<u64>();
+/// A word with every byte set to 1.
+const LO: u64 = u64::from_ne_bytes([0x01; WORD]);
+/// A word with the high bit of every byte set.
+const HI: u64 = u64::from_ne_bytes([0x80; WORD]);
+
+/// Returns a word with the high bit set in a byte if the byte of `word` in that position is zero,
+/// and possibly in bytes above a zero byte.
+#[inline]
+const fn zero_bytes(word: u64) -> u64 {
+    word.wrapping_sub(LO) & !word & HI
+}
+
+/// Returns the index of the first `needle` in `haystack`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::mem::find_byte;
+///
+/// let line = b"key = value\nnext = line";
+/// assert_eq!(find_byte(line, b'\n'), Some(11));
+/// assert_eq!(find_byte(line, b'#'), None);
+/// ```
+#[must_use]
+#[inline]
+pub fn find_byte(haystack: &[u8], needle: u8) -> Option<usize> {
+    #[cfg(target_arch = "x86_64")]
+    {
+        sse2::find_byte(haystack, needle)
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        find_byte_swar(haystack, needle)
+    }
+}
+
+/// Returns the index of the first `needle` in `haystack`, a word at a time.
+#[inline]
+fn find_byte_swar(haystack: &[u8], needle: u8) -> Option<usize> {
+    let repeated = LO * u64::from(needle);
+    let (words, rest) = haystack.as_chunks::<WORD>();
+    for (index, &word) in words.iter().enumerate() {
+        // Only bytes above a match can be flagged without matching, so the lowest flag is exact.
+        let matches = zero_bytes(u64::from_le_bytes(word) ^ repeated);
+        if matches != 0 {
+            return Some(index * WORD + matches.trailing_zeros() as usize / 8);
+        }
+    }
+    let offset = haystack.len() - rest.len();
+    rest.iter()
+        .position(|&byte| byte == needle)
+        .map(|index| offset + index)
+}
+
+/// Returns the index of the last `needle` in `haystack`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::mem::rfind_byte;
+///
+/// assert_eq!(rfind_byte(b"src/mem/find.rs", b'/'), Some(7));
+/// ```
+#[must_use]
+pub fn rfind_byte(haystack: &[u8], needle: u8) -> Option<usize> {
+    let repeated = LO * u64::from(needle);
+    let (rest, words) = haystack.as_rchunks::<WORD>();
+    for (index, word) in words.iter().enumerate().rev() {
+        if zero_bytes(u64::from_le_bytes(*word) ^ repeated) != 0 {
+            // The highest flag may be a false positive, so check the word's bytes themselves.
+            let start = rest.len() + index * WORD;
+            return word
+                .iter()
+                .rposition(|&byte| byte == needle)
+                .map(|index| start + index);
+        }
+    }
+    rest.iter().rposition(|&byte| byte == needle)
+}
+
+/// Returns the index of the first occurrence of `needle` in `haystack`. An empty `needle` is found
+/// at 0.
+///
+/// Candidates are found with [`find_byte`] on the first byte of `needle` and then compared in
+/// full, so needles whose first byte is rare in the haystack are found fastest.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::mem::find_subslice;
+///
+/// assert_eq!(find_subslice(b"GET /index.html HTTP/1.1", b"HTTP/"), Some(16));
+/// assert_eq!(find_subslice(b"abc", b"abcd"), None);
+/// ```
+#[must_use]
+pub fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    let Some((&first, rest)) = needle.split_first() else {
+        return Some(0);
+    };
+    let last_start = haystack.len().checked_sub(needle.len())?;
+    let mut start = 0;
+    while start <= last_start {
+        let candidate = start + find_byte(&haystack[start..=last_start], first)?;
+        if haystack[candidate + 1..candidate + needle.len()] == *rest {
+            return Some(candidate);
+        }
+        start = candidate + 1;
+    }
+    None
+}
+
+/// Fills `buf` with repeats of `pattern`, ending with part of it if the lengths don't divide.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::mem::fill_pattern;
+///
+/// let mut buf = [0; 7];
+/// fill_pattern(&mut buf, &[0xde, 0xad]);
+/// assert_eq!(buf, [0xde, 0xad, 0xde, 0xad, 0xde, 0xad, 0xde]);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `pattern` is empty and `buf` isn't.
+pub fn fill_pattern(buf: &mut [u8], pattern: &[u8]) {
+    if buf.is_empty() {
+        return;
+    }
+    assert!(!pattern.is_empty(), "fill pattern must not be empty");
+    let first = pattern.len().min(buf.len());
+    buf[..first].copy_from_slice(&pattern[..first]);
+    // Filled lengths stay whole repeats of the pattern until the last copy.
+    let mut filled = first;
+    while filled < buf.len() {
+        let len = filled.min(buf.len() - filled);
+        buf.copy_within(..len, filled);
+        filled += len;
+    }
+}
+
+/// Hints that the memory at `address` will be read soon, so the processor can start loading its
+/// cache line into every cache level. The address isn't dereferenced and needn't be valid, so it
+/// can point past the end of a buffer. Does nothing on targets without a prefetch instruction.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::mem::prefetch;
+///
+/// let values = vec![1u64; 4096];
+/// let mut sum = 0;
+/// for (index, value) in values.iter().enumerate() {
+///     prefetch(values.as_ptr().wrapping_add(index + 64));
+///     sum += value;
+/// }
+/// assert_eq!(sum, 4096);
+/// ```
+#[inline]
+pub fn prefetch<T>(address: *const T) {
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        #[cfg(target_arch = "x86")]
+        use std::arch::x86::{_mm_prefetch, _MM_HINT_T0};
+        #[cfg(target_arch = "x86_64")]
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+
+        // SAFETY: Prefetches don't fault or change memory, whatever the address.
+        unsafe { _mm_prefetch::<_MM_HINT_T0>(address.cast()) };
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        // SAFETY: Prefetches don't fault or change memory, whatever the address.
+        unsafe {
+            std::arch::asm!(
+                "prfm pldl1keep, [{address}]",
+                address = in(reg) address,
+                options(nostack, readonly, preserves_flags),
+            );
+        }
+    }
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+    let _ = address;
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use std::arch::x86_64::{_mm_cmpeq_epi8, _mm_loadu_si128, _mm_movemask_epi8, _mm_set1_epi8};
+
+    /// Bytes compared at a time.
+    const LANES: usize = 16;
+
+    /// Returns the index of the first `needle` in `haystack`, sixteen bytes at a time. SSE2 is
+    /// part of the `x86_64` baseline, so it's always available.
+    #[inline]
+    pub(super) fn find_byte(haystack: &[u8], needle: u8) -> Option<usize> {
+        // SAFETY: SSE2 is always available on `x86_64`.
+        let repeated = unsafe { _mm_set1_epi8(i8::from_ne_bytes([needle])) };
+        let mut chunks = haystack.chunks_exact(LANES);
+        for (index, chunk) in chunks.by_ref().enumerate() {
+            // SAFETY: SSE2 is always available, the chunk is 16 bytes long, and the load doesn't
+            // need to be aligned.
+            let matches = unsafe {
+                let bytes = _mm_loadu_si128(chunk.as_ptr().cast());
+                _mm_movemask_epi8(_mm_cmpeq_epi8(bytes, repeated))
+            };
+            if matches != 0 {
+                return Some(index * LANES + matches.trailing_zeros() as usize);
+            }
+        }
+        let rest = chunks.remainder();
+        let offset = haystack.len() - rest.len();
+        super::find_byte_swar(rest, needle).map(|index| offset + index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn haystacks() -> impl Iterator<Item = Vec<u8>> {
+        (0..70u8).map(|len| (0..len).map(|i| b'a' + i % 7).collect())
+    }
+
+    #[test]
+    fn finds_bytes() {
+        for mut haystack in haystacks() {
+            for position in 0..haystack.len() {
+                let original = haystack[position];
+                haystack[position] = b'x';
+                haystack.push(b'x');
+                assert_eq!(find_byte(&haystack, b'x'), Some(position));
+                assert_eq!(find_byte_swar(&haystack, b'x'), Some(position));
+                assert_eq!(rfind_byte(&haystack, b'x'), Some(haystack.len() - 1));
+                haystack.pop();
+                assert_eq!(rfind_byte(&haystack, b'x'), Some(position));
+                haystack[position] = original;
+            }
+            assert_eq!(find_byte(&haystack, b'x'), None);
+            assert_eq!(rfind_byte(&haystack, b'x'), None);
+            for needle in [b'a', b'c', b'g'] {
+                let expected = haystack.iter().position(|&byte| byte == needle);
+                assert_eq!(find_byte(&haystack, needle), expected);
+                let expected = haystack.iter().rposition(|&byte| byte == needle);
+                assert_eq!(rfind_byte(&haystack, needle), expected);
+            }
+        }
+        // Bytes just above a match would be flagged as zero by a naive high bit check.
+        assert_eq!(rfind_byte(&[0x00, 0x01, 0, 0, 0, 0, 0, 0], 0x01), Some(1));
+        assert_eq!(find_byte(&[0x80; 40], 0x80), Some(0));
+    }
+
+    #[test]
+    fn finds_subslices() {
+        for haystack in haystacks() {
+            for needle in [&b""[..], b"a", b"cd", b"gab", b"efgabcd", b"ba"] {
+                let expected =
+                    (0..=haystack.len()).find(|&start| haystack[start..].starts_with(needle));
+                assert_eq!(find_subslice(&haystack, needle), expected);
+            }
+        }
+        assert_eq!(find_subslice(b"aaab", b"aab"), Some(1));
+        assert_eq!(find_subslice(b"", b"a"), None);
+    }
+
+    #[test]
+    fn fills_patterns() {
+        for len in 0..40 {
+            for pattern in [&[7][..], &[1, 2, 3], &[9; 50]] {
+                let mut buf = vec![0; len];
+                fill_pattern(&mut buf, pattern);
+                assert!(buf.iter().zip(pattern.iter().cycle()).all(|(a, b)| a == b));
+            }
+        }
+        fill_pattern(&mut [], &[]);
+        assert!(std::panic::catch_unwind(|| fill_pattern(&mut [0], &[])).is_err());
+    }
+
+    #[test]
+    fn prefetches_any_address() {
+        prefetch(std::ptr::null::<u8>());
+        prefetch(usize::MAX as *const u64);
+    }
+}
@@ -0,0 +1,242 @@
+//! Path helpers.
+//!
+//! [`normalize`] and [`relative_to`] work on paths lexically, without touching the filesystem, so
+//! they work on paths which don't exist yet and never follow symbolic links. That also means
+//! `a/link/..` becomes `a`, even if `link` points elsewhere. [`expand_home`] and
+//! [`collapse_home`] convert between `~/...` and paths in the home directory, for reading paths
+//! typed by people and showing shorter ones in reports and logs.
+
+use std::{
+    ffi::OsString,
+    path::{Component, Path, PathBuf},
+};
+
+/// Resolves `.` and `..` in `path`. A `..` at the start of a relative path is kept, and one after
+/// the root is dropped, since the root is its own parent. An empty result is `.`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use util_lib_rs::path_util::normalize;
+///
+/// assert_eq!(normalize("src/./performance/../lib.rs"), Path::new("src/lib.rs"));
+/// assert_eq!(normalize("../a/../b"), Path::new("../b"));
+/// assert_eq!(normalize("a/.."), Path::new("."));
+/// ```
+#[must_use]
+pub fn normalize(path: impl AsRef<Path>) -> PathBuf {
+    let mut components: Vec<Component<'_>> = Vec::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match components.last() {
+                Some(Component::Normal(_)) => {
+                    components.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                Some(Component::ParentDir | Component::CurDir) | None => {
+                    components.push(component);
+                }
+            },
+            _ => components.push(component),
+        }
+    }
+    if components.is_empty() {
+        return PathBuf::from(".");
+    }
+    components.iter().collect()
+}
+
+/// Returns the path which leads from the directory `base` to `target`, both
+/// [normalized](normalize) first, such as `../lib.rs` from `src/bin` to `src/lib.rs`.
+///
+/// Returns `None` if there's no such path without looking at the filesystem: if only one is
+/// absolute, if they're on different Windows drives, or if `base` has a `..` which `target`
+/// doesn't, so the name of the directory it leads to is unknown.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+/// use util_lib_rs::path_util::relative_to;
+///
+/// assert_eq!(relative_to("src/bin", "src/lib.rs").as_deref(), Some(Path::new("../lib.rs")));
+/// assert_eq!(relative_to("src", "src").as_deref(), Some(Path::new(".")));
+/// assert_eq!(relative_to("..", "src"), None);
+/// ```
+#[must_use]
+pub fn relative_to(base: impl AsRef<Path>, target: impl AsRef<Path>) -> Option<PathBuf> {
+    let base = normalize(base);
+    let target = normalize(target);
+    if base.has_root() != target.has_root() {
+        return None;
+    }
+    let base: Vec<_> = base
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let target: Vec<_> = target
+        .components()
+        .filter(|c| *c != Component::CurDir)
+        .collect();
+    let common = base
+        .iter()
+        .zip(&target)
+        .take_while(|(base, target)| base == target)
+        .count();
+    let rooted =
+        |component: &Component<'_>| matches!(component, Component::Prefix(_) | Component::RootDir);
+    if base[common..]
+        .iter()
+        .any(|component| rooted(component) || *component == Component::ParentDir)
+        || target[common..].iter().any(rooted)
+    {
+        return None;
+    }
+    let mut relative: PathBuf = std::iter::repeat_n(Component::ParentDir, base.len() - common)
+        .chain(target[common..].iter().copied())
+        .collect();
+    if relative.as_os_str().is_empty() {
+        relative.push(".");
+    }
+    Some(relative)
+}
+
+/// Returns the current user's home directory, from `HOME`, or `USERPROFILE` on Windows.
+#[must_use]
+pub fn home_dir() -> Option<PathBuf> {
+    let name = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(name)
+        .filter(|home| !home.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Replaces a leading `~` component of `path` with the [home directory](home_dir). Paths such as
+/// `~user/...` are returned as they are, as is everything if the home directory is unknown.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::path_util::{expand_home, home_dir};
+///
+/// if let Some(home) = home_dir() {
+///     assert_eq!(expand_home("~/.config/app.conf"), home.join(".config/app.conf"));
+/// }
+/// assert_eq!(expand_home("/etc/app.conf").to_str(), Some("/etc/app.conf"));
+/// ```
+#[must_use]
+pub fn expand_home(path: impl AsRef<Path>) -> PathBuf {
+    expand_home_with(path.as_ref(), home_dir().as_deref())
+}
+
+fn expand_home_with(path: &Path, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) if rest.as_os_str().is_empty() => home.to_path_buf(),
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Replaces the [home directory](home_dir) at the start of `path` with `~`, to display it
+/// shorter. Other paths are returned as they are.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::path_util::{collapse_home, home_dir};
+///
+/// if let Some(home) = home_dir() {
+///     assert_eq!(collapse_home(home.join("trace.json")).to_str(), Some("~/trace.json"));
+/// }
+/// ```
+#[must_use]
+pub fn collapse_home(path: impl AsRef<Path>) -> PathBuf {
+    collapse_home_with(path.as_ref(), home_dir().as_deref())
+}
+
+fn collapse_home_with(path: &Path, home: Option<&Path>) -> PathBuf {
+    match home.map(|home| path.strip_prefix(home)) {
+        Some(Ok(rest)) => {
+            let mut collapsed = OsString::from("~");
+            if !rest.as_os_str().is_empty() {
+                collapsed.push(std::path::MAIN_SEPARATOR_STR);
+                collapsed.push(rest);
+            }
+            collapsed.into()
+        }
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(path: &str) -> PathBuf {
+        PathBuf::from(path)
+    }
+
+    #[test]
+    fn normalizes() {
+        assert_eq!(normalize(""), path("."));
+        assert_eq!(normalize("./"), path("."));
+        assert_eq!(normalize("a/b/../../.."), path(".."));
+        assert_eq!(normalize("../../a"), path("../../a"));
+        assert_eq!(normalize("a//b/./c/"), path("a/b/c"));
+        #[cfg(unix)]
+        {
+            assert_eq!(normalize("/../a/.."), path("/"));
+            assert_eq!(normalize("/usr/lib/../bin"), path("/usr/bin"));
+        }
+    }
+
+    #[test]
+    fn relativizes() {
+        let relative = |base, target| relative_to(base, target);
+        assert_eq!(relative("a/b", "a/b/c/d"), Some(path("c/d")));
+        assert_eq!(relative("a/b/c", "a/d"), Some(path("../../d")));
+        assert_eq!(relative(".", "a"), Some(path("a")));
+        assert_eq!(relative("a", "."), Some(path("..")));
+        assert_eq!(relative("a/./b/..", "a/c"), Some(path("c")));
+        assert_eq!(relative("../x", "../y"), Some(path("../y")));
+        assert_eq!(relative("..", "../y"), Some(path("y")));
+        assert_eq!(relative("a", "../y"), Some(path("../../y")));
+        assert_eq!(relative("../x", "y"), None);
+        #[cfg(unix)]
+        {
+            assert_eq!(relative("/home/me", "/home/me/src"), Some(path("src")));
+            assert_eq!(relative("/home/me", "/tmp"), Some(path("../../tmp")));
+            assert_eq!(relative("/home", "src"), None);
+            assert_eq!(relative("src", "/home"), None);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn home_paths() {
+        let home = Some(Path::new("/home/me"));
+        assert_eq!(expand_home_with(Path::new("~"), home), path("/home/me"));
+        assert_eq!(
+            expand_home_with(Path::new("~/a/b"), home),
+            path("/home/me/a/b")
+        );
+        assert_eq!(expand_home_with(Path::new("~me/a"), home), path("~me/a"));
+        assert_eq!(expand_home_with(Path::new("a/~"), home), path("a/~"));
+        assert_eq!(expand_home_with(Path::new("~/a"), None), path("~/a"));
+
+        assert_eq!(collapse_home_with(Path::new("/home/me"), home), path("~"));
+        assert_eq!(
+            collapse_home_with(Path::new("/home/me/a/b"), home),
+            path("~/a/b")
+        );
+        assert_eq!(
+            collapse_home_with(Path::new("/home/meager"), home),
+            path("/home/meager")
+        );
+        assert_eq!(
+            collapse_home_with(Path::new("/home/me/a"), None),
+            path("/home/me/a")
+        );
+    }
+}
@@ -1,5 +1,73 @@
 //! Performance profiling.
 
+pub use report::{
+    AnchorBacktrace, AnchorStats, BranchArm, BranchStats, CallStackStats, CallStats, CounterStats,
+    Interval, LoopStats, ProfileReport, SampleStats, SlowHit, Snapshot, Summary, ThreadInfo, Tree,
+    TreeNode,
+};
+
+pub use manual::{
+    begin_block, block_begin, block_end, end_block, record_external, span_begin, span_end,
+    timer_frequency, timestamp, BlockHandle, BlockId, Span,
+};
+
+pub use affinity::{pin_current_thread_to_isolated_core, pin_to_core};
+
+pub use export::{add_exporter, clear_exporters, ReportExporter};
+
+pub mod affinity;
+pub mod bandwidth;
+pub mod callgrind;
+#[cfg(feature = "callstacks")]
+pub mod callstack;
+pub mod causal;
+pub mod chrome;
+pub mod clock;
+pub mod color;
+pub mod compare;
+pub mod config;
+pub mod counters;
+mod cputime;
+pub mod csv;
+pub mod dot;
+pub mod dump;
+pub mod export;
+pub mod filter;
+pub mod flight;
+pub mod frames;
+pub mod histogram;
+pub mod html;
+pub mod iter;
+pub mod labels;
+pub mod live;
+#[cfg(feature = "lz4")]
+mod lz4;
+pub mod manual;
+pub mod massif;
+pub mod memory;
+pub mod metrics;
+pub mod names;
+pub mod net;
+pub mod options;
+mod pattern;
+pub mod plot;
+pub mod rename;
+pub mod report;
+pub mod reptest;
+mod rusage;
+pub mod sampling;
+pub mod scheduler;
+pub mod shared;
+pub mod sink;
+pub mod spans;
+pub mod speedscope;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+#[cfg(all(feature = "startup", target_os = "linux"))]
+pub mod startup;
+pub mod threads;
+pub mod watchdog;
+
 /// Begin performance profiling. Call this at the start of your main method or whenever you'd like
 /// the profiling timestamp to begin.
 #[inline]
@@ -8,16 +76,705 @@ pub fn profile_begin() {
     GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().begin());
 }
 
-/// End performance profiling and print the metrics to `stderr`.
+/// End performance profiling, returning the results for the current thread. The report's
+/// [`Display`](std::fmt::Display) implementation formats the same text as
+/// [`profile_end_and_print`].
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{performance::{profile_begin, profile_end}, profile};
+///
+/// profile_begin();
+/// {
+///     profile!("work");
+/// }
+/// let report = profile_end();
+/// for anchor in &report.anchors {
+///     println!("{}: {} hits", anchor.name, anchor.hit_count);
+/// }
+/// ```
+#[inline]
+pub fn profile_end() -> ProfileReport {
+    #[cfg(feature = "perf")]
+    {
+        end_report()
+    }
+    #[cfg(not(feature = "perf"))]
+    ProfileReport::default()
+}
+
+/// Clear the statistics accumulated on the current thread and begin profiling again, so the next
+/// report only covers what runs from now on. Reset outside of profile blocks, since blocks which
+/// are still active include the time before the reset when they end.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{performance::{profile_begin, profile_end, profile_reset}, profile};
+///
+/// profile_begin();
+/// {
+///     profile!("warmup");
+/// }
+/// profile_reset();
+/// {
+///     profile!("measured");
+/// }
+/// let report = profile_end();
+/// assert!(report.anchors.iter().all(|anchor| anchor.name != "warmup"));
+/// ```
+#[inline]
+pub fn profile_reset() {
+    #[cfg(feature = "perf")]
+    GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().reset());
+}
+
+/// End the current phase of profiling on the current thread and begin one called `name`,
+/// returning the report of the phase which ended. Each phase's report only covers the time since
+/// the previous phase began, or since profiling began for the first, and passes to any registered
+/// exporters. Reports of named phases carry the phase name in their `"phase"` metadata, as does
+/// the report of the last phase returned by [`profile_end`].
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{performance::{profile_begin, profile_end, profile_phase}, profile};
+///
+/// profile_begin();
+/// profile_phase("startup");
+/// {
+///     profile!("load_assets");
+/// }
+/// let startup = profile_phase("frame");
+/// {
+///     profile!("render");
+/// }
+/// let frame = profile_end();
+/// println!("{startup}{frame}");
+/// # #[cfg(feature = "perf")]
+/// assert!(frame.metadata.contains(&("phase", "frame".to_string())));
+/// ```
+#[inline]
+#[allow(clippy::must_use_candidate)]
+pub fn profile_phase(name: &'static str) -> ProfileReport {
+    #[cfg(feature = "perf")]
+    {
+        let report = end_report();
+        GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            profiler.reset();
+            profiler.phase = Some(name);
+        });
+        report
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = name;
+        ProfileReport::default()
+    }
+}
+
+/// End performance profiling and print the metrics to the [output sink](sink::set_output_sink),
+/// `stderr` by default, with the anchors sorted and cut off by the
+/// [environment](options::ReportOptions::with_env_overrides).
+#[inline]
+pub fn profile_end_and_print() {
+    profile_end_and_print_with(options::ReportOptions::new());
+}
+
+/// End performance profiling and print the metrics to the [output sink](sink::set_output_sink),
+/// `stderr` by default, with the anchors sorted and cut off by `options`, as overridden by the
+/// [environment](options::ReportOptions::with_env_overrides).
+#[inline]
+pub fn profile_end_and_print_with(options: options::ReportOptions) {
+    #[cfg(feature = "perf")]
+    sink::print(&end_report().with_options(&options.or_env()));
+    #[cfg(not(feature = "perf"))]
+    let _ = options;
+}
+
+/// End performance profiling and write the metrics to `writer`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_write};
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// let mut output = Vec::new();
+/// profile_end_and_write(&mut output)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+#[inline]
+pub fn profile_end_and_write(mut writer: impl std::io::Write) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    write!(writer, "{}", end_report())?;
+    writer.flush()
+}
+
+/// End performance profiling and print a condensed summary of the top `count` anchors by exclusive
+/// time to the [output sink](sink::set_output_sink), `stderr` by default.
+#[inline]
+pub fn profile_end_summary(count: usize) {
+    #[cfg(feature = "perf")]
+    sink::print(&end_report().summary(count));
+    #[cfg(not(feature = "perf"))]
+    let _ = count;
+}
+
+/// End performance profiling and print the [call tree](ProfileReport::tree), which nests each
+/// anchor under its callers, to the [output sink](sink::set_output_sink), `stderr` by default.
+#[inline]
+pub fn profile_end_tree() {
+    #[cfg(feature = "perf")]
+    sink::print(&end_report().tree());
+}
+
+/// End performance profiling and write every block recorded on the current thread to the file at
+/// `path` as Chrome Trace Event JSON, which can be opened in `chrome://tracing` or Perfetto.
+/// Blocks are only recorded once [`chrome::set_record_trace_events`] is enabled before profiling
+/// begins. The report is still passed to any registered exporters.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{
+///     chrome::set_record_trace_events, profile_begin, profile_end_and_export_chrome_trace,
+/// };
+///
+/// # fn main() -> std::io::Result<()> {
+/// set_record_trace_events(true);
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_export_chrome_trace("trace.json")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profile_end_and_export_chrome_trace(
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        use std::io::Write;
+
+        let (events, start_tsc, report) = end_trace();
+        let thread = std::thread::current();
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        chrome::write_chrome_trace(
+            &events,
+            thread.name().unwrap_or("thread"),
+            start_tsc,
+            report.timer_freq,
+            &mut writer,
+        )?;
+        writer.flush()
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// End performance profiling and write every block recorded on the current thread to the file at
+/// `path` as a [speedscope] profile, which can be opened at
+/// <https://www.speedscope.app> for its time-ordered and left-heavy views. Blocks are only recorded
+/// once [`chrome::set_record_trace_events`] is enabled before profiling begins. The report is still
+/// passed to any registered exporters.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{
+///     chrome::set_record_trace_events, profile_begin, profile_end_and_write_speedscope,
+/// };
+///
+/// # fn main() -> std::io::Result<()> {
+/// set_record_trace_events(true);
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_write_speedscope("profile.speedscope.json")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profile_end_and_write_speedscope(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        use std::io::Write;
+
+        let (events, start_tsc, report) = end_trace();
+        let thread = report.thread.map_or("thread", |thread| thread.name);
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        speedscope::write_speedscope(&events, thread, start_tsc, report.timer_freq, &mut writer)?;
+        writer.flush()
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Ends profiling, returning the trace events recorded on the current thread in the order they
+/// started, the timestamp counter when profiling began and the report.
+#[cfg(feature = "perf")]
+fn end_trace() -> (Vec<flight::TraceEvent>, u64, ProfileReport) {
+    let (mut events, start_tsc) = GLOBAL_PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        if profiler.trace.is_none() {
+            profiler.warn(
+                "no trace events were recorded, enable `set_record_trace_events` before \
+                 profiling begins"
+                    .to_string(),
+            );
+        }
+        (
+            profiler.trace.take().unwrap_or_default(),
+            profiler.start_tsc,
+        )
+    });
+    let report = end_report();
+    events.sort_by_key(|event| (event.start_tsc, event.depth));
+    (events, start_tsc, report)
+}
+
+/// End performance profiling and save one row per anchor to the file at `path` as
+/// [CSV](csv::write_csv), for spreadsheets or comparing runs in CI scripts. The report is still
+/// passed to any registered exporters.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_write_csv};
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_write_csv("profile.csv")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profile_end_and_write_csv(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        use std::io::Write;
+
+        let report = end_report();
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        csv::write_csv(&report, &mut writer)?;
+        writer.flush()
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// End performance profiling and save the report to the file at `path` as a standalone
+/// [HTML page](html::write_html) with a flame graph and a sortable table of the anchors, which can
+/// be attached to tickets and opened in any browser. The report is still passed to any registered
+/// exporters.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_write_html};
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_write_html("profile.html")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profile_end_and_write_html(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        use std::io::Write;
+
+        let report = end_report();
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        html::write_html(&report, &mut writer)?;
+        writer.flush()
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// End performance profiling and save the call graph of the report to the file at `path` in
+/// Graphviz DOT format, for rendering with `dot -Tsvg`. The report is still passed to any
+/// registered exporters.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_write_dot};
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_write_dot("profile.dot")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profile_end_and_write_dot(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        use std::io::Write;
+
+        let report = end_report();
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        dot::write_dot(&report, &mut writer)?;
+        writer.flush()
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// End performance profiling and save the report to the file at `path` as a
+/// [binary dump](dump::ProfileDump), along with every block recorded on the current thread if
+/// [`chrome::set_record_trace_events`] was enabled before profiling began. The report is still
+/// passed to any registered exporters.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{dump::Compression, profile_begin, profile_end_and_save_dump};
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_save_dump("profile.dump", Compression::None)?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profile_end_and_save_dump(
+    path: impl AsRef<std::path::Path>,
+    compression: dump::Compression,
+) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        let (mut events, start_tsc) = GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            (
+                profiler.trace.take().unwrap_or_default(),
+                profiler.start_tsc,
+            )
+        });
+        events.sort_by_key(|event| (event.start_tsc, event.depth));
+        dump::ProfileDump::new(end_report())
+            .events(events, start_tsc)
+            .save(path, compression)
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = (path, compression);
+        Ok(())
+    }
+}
+
+/// End performance profiling and save the report to the file at `path` as a baseline for
+/// [`profile_end_and_compare`] in later runs, replacing any earlier baseline.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{profile_begin, profile_save_baseline};
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_save_baseline("baseline.dump")?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profile_save_baseline(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        dump::ProfileDump::new(end_report()).save(path, dump::Compression::None)
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// End performance profiling and print how each anchor changed from the baseline saved at `path`
+/// with [`profile_save_baseline`] to the [output sink](sink::set_output_sink), `stderr` by
+/// default. Returns the comparison, so CI can fail runs whose anchors regress.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_compare};
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// let comparison = profile_end_and_compare("baseline.dump")?;
+/// assert!(comparison.regressions(10.0).is_empty());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the baseline can't be read or is not a valid dump.
+pub fn profile_end_and_compare(
+    path: impl AsRef<std::path::Path>,
+) -> std::io::Result<compare::Comparison> {
+    #[cfg(feature = "perf")]
+    {
+        let report = end_report();
+        let baseline = dump::ProfileDump::load(path)?.report;
+        let comparison = compare::Comparison::new(&baseline, &report);
+        sink::print(&comparison);
+        Ok(comparison)
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(compare::Comparison::default())
+    }
+}
+
+/// End performance profiling and print the metrics to the output sink when the current thread
+/// exits, which for the main thread is when the process terminates normally by returning from
+/// `main` or calling [`std::process::exit`]. Ending profiling earlier, e.g. with
+/// [`profile_end_and_print`], cancels the report at exit.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{performance::{profile_begin, profile_print_at_exit}, profile};
+///
+/// profile_begin();
+/// profile_print_at_exit();
+/// profile!("work");
+/// // any exit path prints the report...
+/// ```
+#[inline]
+pub fn profile_print_at_exit() {
+    #[cfg(feature = "perf")]
+    {
+        // Thread-local destructors run in reverse order of initialization, so initializing the
+        // profiler first keeps it alive until the report is printed.
+        GLOBAL_PROFILER.with(|_| {});
+        PRINT_AT_EXIT.with(|print| print.armed.set(true));
+    }
+}
+
+/// Prints the report when the thread's locals are destroyed, if still armed.
+#[cfg(feature = "perf")]
+struct PrintAtExit {
+    armed: std::cell::Cell<bool>,
+}
+
+#[cfg(feature = "perf")]
+impl Drop for PrintAtExit {
+    fn drop(&mut self) {
+        if self.armed.get() {
+            sink::print(&end_report());
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+thread_local! {
+    static PRINT_AT_EXIT: PrintAtExit = const {
+        PrintAtExit {
+            armed: std::cell::Cell::new(false),
+        }
+    };
+}
+
+/// Ends profiling on the current thread, passing the report to any registered exporters.
+#[cfg(feature = "perf")]
+fn end_report() -> ProfileReport {
+    // Fails while the report at exit is being printed.
+    let _ = PRINT_AT_EXIT.try_with(|print| print.armed.set(false));
+    let (report, thread) = GLOBAL_PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        (profiler.end(), profiler.thread.map(|thread| thread.name))
+    });
+    let mut report = threads::aggregate(report, thread.unwrap_or("<unnamed>"));
+    if REPORT_MEMORY.load(std::sync::atomic::Ordering::Relaxed) {
+        report.memory = crate::sys::memory_usage();
+    }
+    if REPORT_CPU_INFO.load(std::sync::atomic::Ordering::Relaxed) {
+        report.cpu = Some(crate::sys::cpu_info());
+    }
+    if let Some(renames) = rename::anchor_renames() {
+        report = report.renamed(&renames);
+    }
+    if let Some(filter) = filter::anchor_filter() {
+        report = report.filtered(&filter);
+    }
+    if let Some(policy) = names::name_policy() {
+        report = report.shortened(&policy);
+    }
+    export::export_all(&report);
+    report
+}
+
+/// Save the statistics accumulated on the current thread to the file at `path`, so a restarted
+/// process can continue accumulating into the same anchors with [`profiler_state_restore`] and
+/// produce one continuous report. Only the elapsed time and anchor statistics are saved.
+///
+/// Save outside of any profile blocks, e.g. just before restarting, since the time of blocks still
+/// open is incomplete. The file is replaced atomically, so a crash while saving keeps the previous
+/// state.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{
+///     profile_begin, profile_end_and_print, profiler_state_restore, profiler_state_save,
+/// };
+///
+/// # fn main() -> std::io::Result<()> {
+/// profile_begin();
+/// // continue from before the last restart, if any
+/// let _ = profiler_state_restore("profile.state");
+/// // work...
+/// profiler_state_save("profile.state")?;
+/// profile_end_and_print();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the file can't be written.
+pub fn profiler_state_save(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        let path = path.as_ref();
+        let state = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().state());
+        // Written next to `path` and renamed over it, so a crash never leaves a partial state.
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(std::path::Path::new("."));
+        let mut temp = crate::fs::TempFile::new_in(parent, ".tmp")?;
+        let mut writer = std::io::BufWriter::new(temp.as_file_mut());
+        dump::ProfileDump::new(state).write_to(&mut writer, dump::Compression::None)?;
+        std::io::Write::flush(&mut writer)?;
+        drop(writer);
+        temp.persist(path)
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Restore the statistics saved with [`profiler_state_save`] from the file at `path`, adding them
+/// to the current thread's anchors and elapsed time. Timestamp counts are rescaled to this
+/// process's timer frequency.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be read or is not a valid saved state.
+pub fn profiler_state_restore(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    #[cfg(feature = "perf")]
+    {
+        let state = dump::ProfileDump::load(path)?.report;
+        GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().restore(&state));
+        Ok(())
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = path;
+        Ok(())
+    }
+}
+
+/// Take a named snapshot of the current profiling state. When profiling ends, the report includes
+/// the results of each interval between consecutive snapshots, so distinct phases of a run (e.g.
+/// startup, steady-state, and shutdown) can be analyzed separately.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_print, profile_snapshot};
+///
+/// profile_begin();
+/// // warm up caches...
+/// profile_snapshot("after_warmup");
+/// // steady-state work...
+/// profile_end_and_print();
+/// ```
 #[inline]
-pub fn profile_end() {
+#[allow(clippy::must_use_candidate)]
+pub fn profile_snapshot(name: &'static str) -> Snapshot {
     #[cfg(feature = "perf")]
-    GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+    {
+        GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().snapshot(name))
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        Snapshot {
+            name,
+            ..Snapshot::default()
+        }
+    }
 }
 
 /// Profile a given function or block of code. This macro will automatically use the fully
 /// qualified function name when used without arguments. You can also optionally pass a custom name
-/// for this profile block and a number of bytes for measuring bandwidth throughput.
+/// for this profile block and a number of bytes for measuring bandwidth throughput, or name the
+/// counts with `bytes = ` and/or `items = ` to measure item throughput, e.g. records parsed.
+///
+/// The block ends with the enclosing scope. Regions which begin and end in different scopes can use
+/// [`block_begin`] and [`block_end`] instead, or [`span_begin`] and [`span_end`] for regions
+/// carried between functions, such as a request from start to finish. In debug builds, ending a
+/// manual block which isn't active panics, and other unbalanced use is reported as a warning.
 ///
 /// # Examples
 ///
@@ -41,292 +798,3632 @@ pub fn profile_end() {
 ///     profile!("read_data", bytes_read);
 /// }
 /// ```
-#[macro_export]
-macro_rules! profile {
-    () => {
-        #[cfg(feature = "perf")]
-        const fn __f() {}
-        #[cfg(feature = "perf")]
-        profile!($crate::performance::function_name(__f));
-    };
+///
+/// ```
+/// use util_lib_rs::profile;
+///
+/// fn parse_records(data: &[u8], record_count: u64) {
+///     profile!("parse_records", bytes = data.len() as u64, items = record_count);
+/// }
+/// ```
+///
+/// Name a block with `fmt = ` and a format string capturing variables in scope to report each
+/// value, such as a table or file name, as a separate anchor. Distinct [labels](labels) are
+/// limited, after which new ones are recorded under the format string itself.
+///
+/// ```
+/// use util_lib_rs::profile;
+///
+/// fn query(table: &str, rows: u64) {
+///     profile!(fmt = "query:{table}", items = rows);
+/// }
+/// ```
+#[macro_export]
+macro_rules! profile {
+    (@block $name:expr) => {
+        $crate::profile!(@block $name, bytes = 0, items = 0);
+    };
+    (@block $name:expr, bytes = $byte_count:expr) => {
+        $crate::profile!(@block $name, bytes = $byte_count, items = 0);
+    };
+    (@block $name:expr, items = $item_count:expr) => {
+        $crate::profile!(@block $name, bytes = 0, items = $item_count);
+    };
+    (@block $name:expr, items = $item_count:expr, bytes = $byte_count:expr) => {
+        $crate::profile!(@block $name, bytes = $byte_count, items = $item_count);
+    };
+    (@block $name:expr, bytes = $byte_count:expr, items = $item_count:expr) => {
+        #[cfg(feature = "perf")]
+        let __pb = {
+            static __SITE: $crate::performance::AnchorSite = $crate::performance::AnchorSite::new();
+            $crate::performance::ProfileBlock::with_site(&__SITE, $name, $byte_count, $item_count)
+        };
+        // Avoids unused variable warnings without evaluating the counts when profiling is disabled.
+        #[cfg(not(feature = "perf"))]
+        let _ = || ($name, $byte_count, $item_count);
+    };
+    (@block $name:expr, $byte_count:expr) => {
+        $crate::profile!(@block $name, bytes = $byte_count);
+    };
+    () => {
+        #[cfg(feature = "perf")]
+        const fn __f() {}
+        #[cfg(feature = "perf")]
+        $crate::profile_anchor_slot!($crate::performance::function_name(__f));
+        #[cfg(feature = "perf")]
+        $crate::profile!($crate::performance::instantiated_function_name(__f, || {}));
+    };
+    (fmt = $template:literal $(, $($counts:tt)+)?) => {
+        $crate::profile!(
+            @block $crate::performance::labels::label($template, format_args!($template))
+            $(, $($counts)+)?
+        );
+    };
+    ($name:literal $(, $($counts:tt)+)?) => {
+        #[cfg(feature = "perf")]
+        $crate::profile_anchor_slot!($name);
+        $crate::profile!(@block $name $(, $($counts)+)?);
+    };
+    ($name:expr $(, $($counts:tt)+)?) => {
+        $crate::profile!(@block $name $(, $($counts)+)?);
+    };
+}
+
+/// Profile a block which processes `count` items, such as records parsed or requests handled. The
+/// report shows the anchor's items per second and time per item. Shorthand for
+/// `profile!(name, items = count)`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::profile_items;
+///
+/// fn handle(requests: &[&str]) {
+///     profile_items!("handle_requests", requests.len() as u64);
+///     for request in requests {
+///         // ...
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! profile_items {
+    ($name:literal, $item_count:expr) => {
+        $crate::profile!($name, items = $item_count);
+    };
+    ($name:expr, $item_count:expr) => {
+        $crate::profile!($name, items = $item_count);
+    };
+}
+
+/// Profile the expression `body`, which evaluates to the number of bytes it processed, and return
+/// its value. For operations such as reads, where the byte count is only known once they complete.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Read;
+/// use util_lib_rs::profile_bytes;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut input: &[u8] = b"data";
+/// let mut buf = Vec::new();
+/// let bytes_read = profile_bytes!("read", input.read_to_end(&mut buf)?);
+/// assert_eq!(bytes_read, 4);
+/// # Ok(())
+/// # }
+/// ```
+#[macro_export]
+macro_rules! profile_bytes {
+    ($name:expr, $body:expr) => {{
+        #[cfg(feature = "perf")]
+        let __pb = {
+            static __SITE: $crate::performance::AnchorSite = $crate::performance::AnchorSite::new();
+            $crate::performance::ProfileBlock::with_site(&__SITE, $name, 0, 0)
+        };
+        let __bytes = $body;
+        #[cfg(feature = "perf")]
+        __pb.add_byte_count(u64::try_from(__bytes).unwrap_or(u64::MAX));
+        __bytes
+    }};
+}
+
+/// Count a hit on one arm of a decision point, such as a `match` arm or `if`/`else` branch. The
+/// report shows the hit ratio of each arm per decision point, which is useful when optimizing
+/// branchy code found via the profiler.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::profile_branch;
+///
+/// fn classify(c: char) -> u8 {
+///     match c {
+///         'a'..='z' => {
+///             profile_branch!("classify", "lower");
+///             0
+///         }
+///         '0'..='9' => {
+///             profile_branch!("classify", "digit");
+///             1
+///         }
+///         _ => {
+///             profile_branch!("classify", "other");
+///             2
+///         }
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! profile_branch {
+    ($point:expr, $arm:expr) => {
+        #[cfg(feature = "perf")]
+        $crate::performance::record_branch($point, $arm);
+    };
+}
+
+/// Count one occurrence of the event `name`, such as a cache miss or retry. The report lists the
+/// total of each counter next to the timings. Shorthand for `counter_add!(name, 1)`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::counter;
+///
+/// fn lookup(cache: &std::collections::HashMap<u32, String>, key: u32) -> Option<&String> {
+///     let value = cache.get(&key);
+///     if value.is_none() {
+///         counter!("cache_miss");
+///     }
+///     value
+/// }
+/// ```
+#[macro_export]
+macro_rules! counter {
     ($name:expr) => {
-        profile!($name, 0);
+        $crate::counter_add!($name, 1);
+    };
+}
+
+/// Add `count` to the counter `name`, such as the number of rows written. The report lists the
+/// total of each counter next to the timings.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::counter_add;
+///
+/// fn write_rows(rows: &[&str]) {
+///     counter_add!("rows", rows.len() as u64);
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! counter_add {
+    ($name:expr, $count:expr) => {
+        #[cfg(feature = "perf")]
+        $crate::performance::record_counter($name, $count);
+        // Avoids unused variable warnings without evaluating the count when profiling is disabled.
+        #[cfg(not(feature = "perf"))]
+        let _ = || ($name, $count);
+    };
+}
+
+/// Mark the end of a frame of a game or render loop, and the start of the next. Place this once per
+/// iteration of the loop to keep per-frame statistics, such as the average and worst frame time and
+/// the slowest frames, returned by [`frame_stats`](crate::performance::frames::frame_stats).
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{frame_mark, profile};
+///
+/// fn run(frames: usize) {
+///     for _ in 0..frames {
+///         frame_mark!();
+///         profile!("update");
+///         // ...
+///     }
+///     frame_mark!();
+/// }
+/// ```
+#[macro_export]
+macro_rules! frame_mark {
+    () => {
+        #[cfg(feature = "perf")]
+        $crate::performance::frames::frame_mark();
     };
-    ($name:expr, $byte_count:expr) => {
+}
+
+/// Profile the iterations of a loop. Place this at the start of a loop body to record the number of
+/// iterations per hit of the enclosing profile block, min/max/average iteration time, and
+/// iterations per second, without the overhead of a full anchor per iteration.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{profile, profile_loop};
+///
+/// fn sum(values: &[u64]) -> u64 {
+///     profile!();
+///     let mut total = 0;
+///     for value in values {
+///         profile_loop!("sum_values");
+///         total += value;
+///     }
+///     total
+/// }
+/// ```
+#[macro_export]
+macro_rules! profile_loop {
+    ($name:expr) => {
         #[cfg(feature = "perf")]
-        let __pb = $crate::performance::ProfileBlock::new($name, $byte_count);
+        let __pl = $crate::performance::LoopIteration::new($name);
+    };
+}
+
+/// Publish a copy of the current thread's anchor statistics which any thread can read with
+/// [`published_snapshots`] while measurement continues.
+///
+/// Each thread fills a private buffer and swaps it with its shared copy, so readers always see a
+/// consistent set of statistics and writers only hold a lock for the swap. Taking a snapshot with
+/// [`profile_snapshot`] also publishes.
+#[inline]
+pub fn profile_publish() {
+    #[cfg(feature = "perf")]
+    GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().publish());
+}
+
+/// Returns the most recently published statistics of every thread which has called
+/// [`profile_publish`], named after the publishing thread. Threads which have exited keep their
+/// last published statistics.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{profile_publish, published_snapshots};
+///
+/// std::thread::Builder::new()
+///     .name("worker".into())
+///     .spawn(|| {
+///         util_lib_rs::profile!("work");
+///         // ...
+///         profile_publish();
+///     })
+///     .unwrap()
+///     .join()
+///     .unwrap();
+/// for snapshot in published_snapshots() {
+///     println!("{}: {} anchors", snapshot.name, snapshot.anchors.len());
+/// }
+/// ```
+#[must_use]
+pub fn published_snapshots() -> Vec<Snapshot> {
+    #[cfg(feature = "perf")]
+    {
+        PUBLISHED
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .iter()
+            .map(|front| {
+                front
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner)
+                    .clone()
+            })
+            .collect()
+    }
+    #[cfg(not(feature = "perf"))]
+    Vec::new()
+}
+
+/// Returns the statistics of every thread which has published them with [`profile_publish`],
+/// including the current thread, in the Prometheus text exposition format, for serving from a
+/// `/metrics` handler while measurement continues. Returns an empty string without the `perf`
+/// feature.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::metrics_text;
+///
+/// util_lib_rs::profile!("handle_request");
+/// // ...
+/// let body = metrics_text();
+/// # let _ = body;
+/// ```
+#[must_use]
+pub fn metrics_text() -> String {
+    #[cfg(feature = "perf")]
+    {
+        profile_publish();
+        let mut text = String::new();
+        let _ = metrics::write_metrics(&watchdog::published_report(0), &mut text);
+        text
+    }
+    #[cfg(not(feature = "perf"))]
+    String::new()
+}
+
+/// Start reporting every thread's published statistics to the
+/// [output sink](sink::set_output_sink), `stderr` by default, once per `interval` without stopping
+/// collection, until the returned guard is dropped. See [`live::LiveReport`] to handle the reports
+/// yourself.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use util_lib_rs::performance::profile_report_every;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let _reporter = profile_report_every(Duration::from_secs(60))?;
+/// // serve requests...
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the reporter thread can't be spawned.
+pub fn profile_report_every(
+    interval: std::time::Duration,
+) -> std::io::Result<live::LiveReportGuard> {
+    live::LiveReport::new(interval).start()
+}
+
+/// Asks every thread to publish its statistics the next time its outermost block ends.
+#[cfg(feature = "perf")]
+pub(super) fn request_publish() {
+    PUBLISH_REQUESTS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    set_recorder(RECORD_PUBLISH, true);
+}
+
+/// Number of times threads have been asked to publish, compared with the last request each thread
+/// published for.
+#[cfg(feature = "perf")]
+static PUBLISH_REQUESTS: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+
+/// Records the latency histogram of each anchor.
+pub(super) const RECORD_HISTOGRAMS: u8 = 1 << 0;
+/// Publishes statistics for live reports, once any have been requested.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+const RECORD_PUBLISH: u8 = 1 << 1;
+/// Delays blocks for a causal profiling experiment.
+pub(super) const RECORD_CAUSAL: u8 = 1 << 2;
+/// Records events for this thread's flight recorder.
+#[cfg(feature = "perf")]
+const RECORD_EVENTS: u8 = 1 << 3;
+/// Records every block for a trace export.
+#[cfg(feature = "perf")]
+const RECORD_TRACE: u8 = 1 << 4;
+/// Captures the backtraces of slow hits.
+#[cfg(feature = "perf")]
+const RECORD_SLOW_HITS: u8 = 1 << 5;
+
+/// Recorders enabled for every thread, which ending blocks check in one load together with their
+/// own thread's recorders before taking the slow path.
+static RECORDERS: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(0);
+
+/// Enables or disables the global `recorder` for every thread.
+pub(super) fn set_recorder(recorder: u8, enabled: bool) {
+    if enabled {
+        RECORDERS.fetch_or(recorder, std::sync::atomic::Ordering::Relaxed);
+    } else {
+        RECORDERS.fetch_and(!recorder, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// Shared copies of each thread's published anchor statistics.
+#[cfg(feature = "perf")]
+type PublishedSnapshot = std::sync::Arc<std::sync::Mutex<Snapshot>>;
+
+#[cfg(feature = "perf")]
+static PUBLISHED: std::sync::Mutex<Vec<PublishedSnapshot>> = std::sync::Mutex::new(Vec::new());
+
+/// Push a namespace which prefixes the names of all anchors created on the current thread until it
+/// is popped with [`profile_pop_namespace`], so library code instrumented with generic names is
+/// reported per caller. Namespaces nest, e.g. `render::ui::draw`.
+///
+/// Registered anchors are checked by the name of their site, so one hit only within a namespace
+/// isn't listed in [`ProfileReport::never_hit`], while one never hit is listed without a namespace,
+/// since which namespaces it would have run in isn't known.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{profile_pop_namespace, profile_push_namespace};
+///
+/// fn draw() {
+///     util_lib_rs::profile!("draw");
+/// }
+///
+/// profile_push_namespace("render");
+/// draw(); // reported as `render::draw`
+/// profile_pop_namespace();
+/// ```
+#[inline]
+pub fn profile_push_namespace(namespace: &'static str) {
+    #[cfg(feature = "perf")]
+    GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().push_namespace(namespace));
+    #[cfg(not(feature = "perf"))]
+    let _ = namespace;
+}
+
+/// Pop the namespace most recently pushed with [`profile_push_namespace`].
+#[inline]
+pub fn profile_pop_namespace() {
+    #[cfg(feature = "perf")]
+    GLOBAL_PROFILER.with(|profiler| {
+        profiler.borrow_mut().namespaces.pop();
+    });
+}
+
+/// Push a namespace like [`profile_push_namespace`], returning a guard which pops it at the end of
+/// the current scope.
+#[inline]
+pub fn profile_namespace(namespace: &'static str) -> NamespaceGuard {
+    profile_push_namespace(namespace);
+    NamespaceGuard { _private: () }
+}
+
+/// Pops a namespace pushed with [`profile_namespace`] when dropped.
+#[derive(Debug)]
+#[must_use]
+pub struct NamespaceGuard {
+    _private: (),
+}
+
+impl Drop for NamespaceGuard {
+    fn drop(&mut self) {
+        profile_pop_namespace();
+    }
+}
+
+/// Records a hit on `arm` of the decision point `point`. Prefer the `profile_branch!` macro.
+#[cfg(feature = "perf")]
+#[doc(hidden)]
+#[inline]
+pub fn record_branch(point: &'static str, arm: &'static str) {
+    if !is_enabled() {
+        return;
+    }
+    GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().record_branch(point, arm));
+}
+
+/// Adds `count` to the counter `name`. Prefer the `counter!` and `counter_add!` macros.
+#[cfg(feature = "perf")]
+#[doc(hidden)]
+#[inline]
+pub fn record_counter(name: &'static str, count: u64) {
+    if !is_enabled() {
+        return;
+    }
+    GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().record_counter(name, count));
+}
+
+/// Statically registers the name of a `profile!` site so that the report can list anchors which
+/// were never hit. Only supported on Linux, where slots are collected from a linker section.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! profile_anchor_slot {
+    ($name:expr) => {
+        #[cfg(target_os = "linux")]
+        {
+            #[used]
+            #[link_section = "util_lib_rs_anchors"]
+            static __SLOT: $crate::performance::AnchorSlot =
+                $crate::performance::AnchorSlot(|| $name);
+        }
     };
 }
 
+/// A statically registered profile anchor name, created by `profile!` at each call site.
+#[doc(hidden)]
+#[repr(transparent)]
+pub struct AnchorSlot(pub fn() -> &'static str);
+
+/// Next ID to assign to an [`AnchorSite`], starting from 1 so that 0 means unassigned.
 #[cfg(feature = "perf")]
-thread_local! {
-    /// Global profiler object for each thread which tracks start/end timestamp counters and
-    /// list of profile anchors.
-    pub(super) static GLOBAL_PROFILER: std::cell::RefCell<Profiler> = std::cell::RefCell::new(Profiler {
-        start_tsc: 0,
-        end_tsc: 0,
-        anchors: Vec::with_capacity(4096),
-        parent: None,
+static NEXT_SITE_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(1);
+
+/// The call site of a `profile!` block, created as a static by the macro. Each site is assigned a
+/// process-wide ID on its first hit, which indexes each thread's cache of the site's anchor.
+#[cfg(feature = "perf")]
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct AnchorSite(std::sync::atomic::AtomicUsize);
+
+#[cfg(feature = "perf")]
+impl AnchorSite {
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(std::sync::atomic::AtomicUsize::new(0))
+    }
+
+    /// Returns the index of this site in each thread's cache of site anchors.
+    #[inline]
+    fn index(&self) -> usize {
+        use std::sync::atomic::Ordering;
+
+        let id = self.0.load(Ordering::Relaxed);
+        if id != 0 {
+            return id - 1;
+        }
+        let new_id = NEXT_SITE_ID.fetch_add(1, Ordering::Relaxed);
+        match self
+            .0
+            .compare_exchange(0, new_id, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => new_id - 1,
+            // Another thread assigned the site first.
+            Err(id) => id - 1,
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+impl Default for AnchorSite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Register anchor names which are expected to be hit during profiling, for blocks not created
+/// with a literal or auto-generated `profile!` name. Any registered anchors that were never hit are
+/// listed at the end of the report, which doubles as a sanity check that the profiled scenario
+/// covered the intended code paths.
+#[inline]
+pub fn register_anchors(names: &[&'static str]) {
+    #[cfg(feature = "perf")]
+    REGISTERED_ANCHORS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .extend_from_slice(names);
+    #[cfg(not(feature = "perf"))]
+    let _ = names;
+}
+
+/// Set the name the current thread's reports are labeled with, instead of its
+/// [`std::thread::Thread::name`], e.g. to tell apart pool workers by their role. The name is also
+/// the prefix of the thread's anchors in [per-thread](threads::ThreadAggregation::PerThread)
+/// reports.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{profile_begin, profile_end, profile_set_thread_name};
+///
+/// profile_begin();
+/// profile_set_thread_name("renderer");
+/// let report = profile_end();
+/// # #[cfg(feature = "perf")]
+/// assert_eq!(report.thread.map(|thread| thread.name), Some("renderer"));
+/// ```
+#[inline]
+pub fn profile_set_thread_name(name: &'static str) {
+    #[cfg(feature = "perf")]
+    GLOBAL_PROFILER.with(|profiler| {
+        let mut profiler = profiler.borrow_mut();
+        let id = profiler.thread_info().id;
+        profiler.thread = Some(ThreadInfo { name, id });
     });
+    #[cfg(not(feature = "perf"))]
+    let _ = name;
 }
 
-/// Utility function to generate the name of the current function.
-#[cfg(feature = "perf")]
-#[must_use]
-pub fn function_name<T>(_: T) -> &'static str {
-    let name = std::any::type_name::<T>();
-    &name[..name.len() - 3]
-}
+/// Set a `key` describing the profiled run, such as the machine or input it ran on, to `value` in
+/// the metadata of every report, replacing any previous value.
+#[inline]
+pub fn set_run_metadata(key: &'static str, value: impl Into<String>) {
+    #[cfg(feature = "perf")]
+    {
+        let value = value.into();
+        let mut metadata = RUN_METADATA
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        match metadata.iter_mut().find(|(existing, _)| *existing == key) {
+            Some((_, existing)) => *existing = value,
+            None => metadata.push((key, value)),
+        }
+    }
+    #[cfg(not(feature = "perf"))]
+    let _ = (key, value);
+}
+
+#[cfg(feature = "perf")]
+static RUN_METADATA: std::sync::Mutex<Vec<(&'static str, String)>> =
+    std::sync::Mutex::new(Vec::new());
+
+/// Capture a backtrace the first time each anchor is hit on a thread, listed with the report so
+/// readers can find where an unfamiliar anchor lives. Capturing is slow, but only happens once per
+/// anchor.
+#[inline]
+pub fn set_capture_backtraces(enabled: bool) {
+    #[cfg(feature = "perf")]
+    CAPTURE_BACKTRACES.store(enabled, std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "perf"))]
+    let _ = enabled;
+}
+
+/// Capture a backtrace when a block takes longer than `threshold` and longer than any earlier hit
+/// of its anchor on the thread, listed with the report so readers can see what led to the slowest
+/// hits. `Some(Duration::ZERO)` captures every new per-anchor maximum, and `None`, the default,
+/// stops capturing. Capturing is slow and counts towards the enclosing blocks, but only happens
+/// when an anchor's worst case grows. Applies to threads which begin profiling afterwards.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::performance::{profile_begin, profile_end, set_capture_slow_hits};
+///
+/// set_capture_slow_hits(Some(Duration::from_millis(100)));
+/// profile_begin();
+/// util_lib_rs::profile!("request");
+/// let report = profile_end();
+/// // Every hit took less than 100ms.
+/// assert!(report.slow_hits.is_empty());
+/// # set_capture_slow_hits(None);
+/// ```
+#[inline]
+pub fn set_capture_slow_hits(threshold: Option<std::time::Duration>) {
+    #[cfg(feature = "perf")]
+    SLOW_HIT_THRESHOLD_NS.store(
+        threshold.map_or(u64::MAX, |threshold| {
+            u64::try_from(threshold.as_nanos())
+                .map_or(u64::MAX - 1, |nanos| nanos.min(u64::MAX - 1))
+        }),
+        std::sync::atomic::Ordering::Relaxed,
+    );
+    #[cfg(not(feature = "perf"))]
+    let _ = threshold;
+}
+
+/// The instruction used to read the timestamp counter at the start and end of every block, trading
+/// overhead for ordering guarantees. On `aarch64`, which reads the virtual counter `cntvct_el0`,
+/// `Rdtsc` reads it directly and the others read it after an `isb` barrier. Other architectures
+/// read the [`clock`] fallback timer whichever is chosen.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum TimerRead {
+    /// `rdtsc`, the cheapest read, which the processor may reorder with surrounding instructions.
+    /// Fine for coarse blocks, where a few cycles of skew don't matter.
+    Rdtsc,
+    /// `rdtscp`, which waits for earlier instructions to finish before reading.
+    #[default]
+    Rdtscp,
+    /// `lfence; rdtsc`, which also keeps later instructions from starting before the read, for
+    /// nanosecond-scale measurements.
+    Fenced,
+    /// The operating system's monotonic clock, `QueryPerformanceCounter` on Windows and
+    /// `clock_gettime(CLOCK_MONOTONIC_RAW)` elsewhere, which is slower and coarser but keeps a
+    /// steady rate on every core. [`profile_begin`] chooses it on x86 processors without an
+    /// invariant timestamp counter. Its readings aren't comparable with the timestamp counter's,
+    /// so switch to or from it before profiling begins.
+    Os,
+}
+
+/// Set the instruction used to read the timestamp counter in every block on every thread, instead
+/// of the one [`profile_begin`] detects. Blocks which are already running keep the start time they
+/// read.
+#[inline]
+pub fn set_timer_read(read: TimerRead) {
+    #[cfg(feature = "perf")]
+    {
+        TIMER_READ_CHOSEN.store(true, std::sync::atomic::Ordering::Relaxed);
+        TIMER_READ.store(read.mode(), std::sync::atomic::Ordering::Relaxed);
+    }
+    #[cfg(not(feature = "perf"))]
+    let _ = read;
+}
+
+#[cfg(feature = "perf")]
+impl TimerRead {
+    /// The value of [`TIMER_READ`] which reads the timer this way.
+    const fn mode(self) -> u8 {
+        match self {
+            Self::Rdtsc => TIMER_READ_RDTSC,
+            Self::Rdtscp => TIMER_READ_RDTSCP,
+            Self::Fenced => TIMER_READ_FENCED,
+            Self::Os => TIMER_READ_OS,
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+const TIMER_READ_RDTSC: u8 = 0;
+#[cfg(feature = "perf")]
+const TIMER_READ_RDTSCP: u8 = 1;
+#[cfg(feature = "perf")]
+const TIMER_READ_FENCED: u8 = 2;
+#[cfg(feature = "perf")]
+const TIMER_READ_OS: u8 = 3;
+/// Reads the source installed with [`clock::set_clock_source`].
+#[cfg(feature = "perf")]
+const TIMER_READ_CUSTOM: u8 = 4;
+
+#[cfg(feature = "perf")]
+static TIMER_READ: std::sync::atomic::AtomicU8 =
+    std::sync::atomic::AtomicU8::new(TIMER_READ_RDTSCP);
+
+/// Whether the timer read was chosen with [`set_timer_read`], so detection leaves it alone.
+#[cfg(feature = "perf")]
+static TIMER_READ_CHOSEN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Whether the processor supports `rdtscp`, which some virtual machines hide.
+#[cfg(feature = "perf")]
+static RDTSCP_SUPPORTED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(true);
+
+/// Measure the CPU time of the current thread alongside elapsed time in every profile block, so
+/// reports can tell computing apart from time spent blocked or descheduled. Reading the thread CPU
+/// clock costs a system call or vDSO call at both ends of each block.
+#[inline]
+pub fn set_measure_cpu_time(enabled: bool) {
+    #[cfg(feature = "perf")]
+    MEASURE_CPU_TIME.store(enabled, std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "perf"))]
+    let _ = enabled;
+}
+
+#[cfg(feature = "perf")]
+static MEASURE_CPU_TIME: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Count the soft and hard page faults of every profile block, for code dominated by I/O or memory
+/// mapping. Faults are counted per thread on Linux, but only for the whole process on macOS and
+/// Windows, where concurrent threads add to each other's blocks. Reading the counts costs a system
+/// call at both ends of each block.
+#[inline]
+pub fn set_measure_page_faults(enabled: bool) {
+    #[cfg(feature = "perf")]
+    MEASURE_PAGE_FAULTS.store(enabled, std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "perf"))]
+    let _ = enabled;
+}
+
+#[cfg(feature = "perf")]
+static MEASURE_PAGE_FAULTS: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Count the voluntary and involuntary context switches of every profile block, to tell blocks
+/// slowed down by blocking system calls or scheduler preemption apart from CPU-bound ones. Counts
+/// are only available on Linux, where they're per thread, and macOS, where they're for the whole
+/// process. Reading the counts costs a system call at both ends of each block.
+#[inline]
+pub fn set_measure_context_switches(enabled: bool) {
+    #[cfg(feature = "perf")]
+    MEASURE_CONTEXT_SWITCHES.store(enabled, std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "perf"))]
+    let _ = enabled;
+}
+
+#[cfg(feature = "perf")]
+static MEASURE_CONTEXT_SWITCHES: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Record the [memory use](crate::sys::memory_usage) of the process when each report is ended,
+/// so reports show the peak resident set size alongside timing. Reading it costs a system call,
+/// or reading `/proc/self/status` on Linux, per report.
+#[inline]
+pub fn set_report_memory(enabled: bool) {
+    #[cfg(feature = "perf")]
+    REPORT_MEMORY.store(enabled, std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "perf"))]
+    let _ = enabled;
+}
+
+#[cfg(feature = "perf")]
+static REPORT_MEMORY: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Record the [processor](crate::sys::cpu_info) in each report, so its header shows the machine
+/// the timings were taken on. The processor is only detected once.
+#[inline]
+pub fn set_report_cpu_info(enabled: bool) {
+    #[cfg(feature = "perf")]
+    REPORT_CPU_INFO.store(enabled, std::sync::atomic::Ordering::Relaxed);
+    #[cfg(not(feature = "perf"))]
+    let _ = enabled;
+}
+
+#[cfg(feature = "perf")]
+static REPORT_CPU_INFO: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Turn collection on or off at runtime on every thread, for builds with the `perf` feature which
+/// should only profile on request. While disabled, profile blocks, loops, branches and spans record
+/// nothing and cost a single atomic load. Blocks which are already running still record when they
+/// end.
+///
+/// Collection starts enabled unless the `UTIL_PROFILE` environment variable is `0`, `false`, `no`
+/// or `off`. To ship a build which is off unless `UTIL_PROFILE=1`, call this early in `main`:
+///
+/// ```
+/// use util_lib_rs::{env, performance::set_enabled};
+///
+/// set_enabled(env::flag("UTIL_PROFILE").ok().flatten().unwrap_or(false));
+/// ```
+#[inline]
+pub fn set_enabled(enabled: bool) {
+    #[cfg(feature = "perf")]
+    ENABLED.store(
+        if enabled { ENABLED_ON } else { ENABLED_OFF },
+        std::sync::atomic::Ordering::Relaxed,
+    );
+    #[cfg(not(feature = "perf"))]
+    let _ = enabled;
+}
+
+/// Returns whether profiling is collecting, which is never the case without the `perf` feature.
+#[inline]
+#[must_use]
+pub fn is_enabled() -> bool {
+    #[cfg(feature = "perf")]
+    {
+        match ENABLED.load(std::sync::atomic::Ordering::Relaxed) {
+            ENABLED_ON => true,
+            ENABLED_OFF => false,
+            _ => enabled_from_env(),
+        }
+    }
+    #[cfg(not(feature = "perf"))]
+    false
+}
+
+#[cfg(feature = "perf")]
+const ENABLED_UNKNOWN: u8 = 0;
+#[cfg(feature = "perf")]
+const ENABLED_ON: u8 = 1;
+#[cfg(feature = "perf")]
+const ENABLED_OFF: u8 = 2;
+
+#[cfg(feature = "perf")]
+static ENABLED: std::sync::atomic::AtomicU8 = std::sync::atomic::AtomicU8::new(ENABLED_UNKNOWN);
+
+/// Reads the initial state from `UTIL_PROFILE`, unless [`set_enabled`] was called meanwhile.
+#[cfg(feature = "perf")]
+#[cold]
+#[inline(never)]
+fn enabled_from_env() -> bool {
+    let enabled = crate::env::flag("UTIL_PROFILE").unwrap_or_else(|err| {
+        eprintln!("ignoring {err}");
+        None
+    });
+    let initial = if enabled == Some(false) {
+        ENABLED_OFF
+    } else {
+        ENABLED_ON
+    };
+    match ENABLED.compare_exchange(
+        ENABLED_UNKNOWN,
+        initial,
+        std::sync::atomic::Ordering::Relaxed,
+        std::sync::atomic::Ordering::Relaxed,
+    ) {
+        Ok(_) => initial == ENABLED_ON,
+        Err(current) => current == ENABLED_ON,
+    }
+}
+
+#[cfg(feature = "perf")]
+static CAPTURE_BACKTRACES: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+/// Threshold of [`set_capture_slow_hits`] in nanoseconds, or `u64::MAX` while not capturing.
+#[cfg(feature = "perf")]
+static SLOW_HIT_THRESHOLD_NS: std::sync::atomic::AtomicU64 =
+    std::sync::atomic::AtomicU64::new(u64::MAX);
+
+#[cfg(feature = "perf")]
+static REGISTERED_ANCHORS: std::sync::Mutex<Vec<&'static str>> = std::sync::Mutex::new(Vec::new());
+
+/// Returns every anchor name registered either statically by `profile!` or with
+/// [`register_anchors`].
+#[cfg(feature = "perf")]
+fn registered_anchor_names() -> Vec<&'static str> {
+    let mut names = REGISTERED_ANCHORS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+        .clone();
+
+    #[cfg(target_os = "linux")]
+    {
+        // Ensures the section exists even if no `profile!` sites are linked in.
+        #[used]
+        #[link_section = "util_lib_rs_anchors"]
+        static SENTINEL: AnchorSlot = AnchorSlot(|| "");
+
+        extern "C" {
+            #[link_name = "__start_util_lib_rs_anchors"]
+            static START: u8;
+            #[link_name = "__stop_util_lib_rs_anchors"]
+            static STOP: u8;
+        }
+
+        // SAFETY: The linker defines `START` and `STOP` as the bounds of the
+        // `util_lib_rs_anchors` section, which only contains (and is aligned by) `AnchorSlot`
+        // statics.
+        #[allow(clippy::cast_ptr_alignment)]
+        let slots = unsafe {
+            let start = std::ptr::addr_of!(START);
+            let stop = std::ptr::addr_of!(STOP);
+            let len = usize::try_from(stop.offset_from(start)).unwrap_or(0)
+                / std::mem::size_of::<AnchorSlot>();
+            std::slice::from_raw_parts(start.cast::<AnchorSlot>(), len)
+        };
+        names.extend(slots.iter().map(|slot| (slot.0)()));
+    }
+
+    let mut unique = Vec::with_capacity(names.len());
+    for name in names {
+        if !name.is_empty() && !unique.contains(&name) {
+            unique.push(name);
+        }
+    }
+    unique
+}
+
+#[cfg(feature = "perf")]
+thread_local! {
+    /// Global profiler object for each thread which tracks start/end timestamp counters and
+    /// list of profile anchors.
+    pub(super) static GLOBAL_PROFILER: std::cell::RefCell<Profiler> = std::cell::RefCell::new(Profiler {
+        start_tsc: 0,
+        end_tsc: 0,
+        start_os: 0,
+        start_cpu: 0,
+        start_clocks: (0, 0),
+        backwards_reads: 0,
+        restored_tsc: 0,
+        anchors: Vec::new(),
+        anchor_indices: crate::hash::FastHashMap::default(),
+        site_anchors: Vec::new(),
+        stack: Vec::with_capacity(64),
+        snapshots: Vec::new(),
+        branches: Vec::new(),
+        event_counters: Vec::new(),
+        loops: Vec::new(),
+        manual_blocks: Vec::new(),
+        next_block_id: 0,
+        warnings: Vec::new(),
+        namespaces: Vec::new(),
+        namespaced_names: std::collections::HashMap::new(),
+        published: None,
+        publish_buffer: Vec::new(),
+        publish_request: 0,
+        calls: std::collections::HashMap::new(),
+        backtraces: Vec::new(),
+        slow_hit_tsc: None,
+        slow_hits: std::collections::HashMap::new(),
+        recorders: 0,
+        histograms: Vec::new(),
+        #[cfg(feature = "callstacks")]
+        known_stacks: std::collections::HashSet::new(),
+        #[cfg(feature = "callstacks")]
+        call_stacks: std::collections::HashMap::new(),
+        sample_slot: None,
+        events: None,
+        frames: None,
+        trace: None,
+        thread: None,
+        phase: None,
+        #[cfg(feature = "perf-counters")]
+        counter_group: None,
+        #[cfg(feature = "perf-counters")]
+        counter_error: None,
+    });
+}
+
+/// Utility function to generate the name of the current function.
+#[cfg(feature = "perf")]
+#[must_use]
+pub fn function_name<T>(_: T) -> &'static str {
+    let name = std::any::type_name::<T>();
+    name.strip_suffix("::__f").unwrap_or(name)
+}
+
+/// Generates the name of the current function from the nested function `f`, or from `closure`
+/// when [`names::set_monomorphized_names`] is enabled. Unlike nested functions, closures are
+/// generic over the generic parameters of the enclosing function, so their names include the
+/// concrete instantiation.
+#[doc(hidden)]
+#[cfg(feature = "perf")]
+#[must_use]
+pub fn instantiated_function_name<F, C>(f: F, closure: C) -> &'static str {
+    if !names::monomorphized_names() {
+        return function_name(f);
+    }
+    let name = function_name(closure);
+    name.strip_suffix("::{{closure}}").unwrap_or(name)
+}
+
+/// Minimum session length, in OS timer ticks, for the observed timer frequency to be meaningful.
+#[cfg(feature = "perf")]
+const MIN_FREQ_CHECK_OS_ELAPSED: u64 = 50_000;
+
+/// Largest tolerated relative difference between a thread's observed timer frequency and the
+/// calibrated one.
+#[cfg(feature = "perf")]
+const MAX_FREQ_DISCREPANCY: f64 = 0.05;
+
+/// Compares the timer frequency a thread observed over its session of `tsc_elapsed` ticks, taking
+/// `os_elapsed` OS timer ticks, against the once-calibrated `timer_freq`. Heterogeneous cores or an
+/// unstable timestamp counter can make a single global frequency wrong for some threads.
+#[cfg(feature = "perf")]
+#[allow(clippy::cast_precision_loss)]
+fn timer_freq_warning(
+    tsc_elapsed: u64,
+    os_elapsed: u64,
+    os_freq: u64,
+    timer_freq: u64,
+    (start_cpu, end_cpu): (u32, u32),
+) -> Option<String> {
+    if os_elapsed < MIN_FREQ_CHECK_OS_ELAPSED || timer_freq == 0 {
+        return None;
+    }
+    let observed = tsc_elapsed as f64 * os_freq as f64 / os_elapsed as f64;
+    let discrepancy = (observed - timer_freq as f64).abs() / timer_freq as f64;
+    if discrepancy <= MAX_FREQ_DISCREPANCY {
+        return None;
+    }
+    let mut warning = format!(
+        "timer frequency on this thread was {:.1}MHz, {:.1}% off the calibrated {:.1}MHz, so times \
+         may be inaccurate",
+        observed / 1e6,
+        discrepancy * 100.0,
+        timer_freq as f64 / 1e6,
+    );
+    if start_cpu != end_cpu {
+        use std::fmt::Write;
+        let _ = write!(warning, " (moved from CPU {start_cpu} to {end_cpu})");
+    }
+    Some(warning)
+}
+
+/// Shortest gap between the boot and monotonic clocks reported as the system being suspended.
+#[cfg(feature = "perf")]
+const MIN_SUSPEND_NS: u64 = 1_000_000_000;
+
+/// Reads the boot clock, which advances while the system is suspended, and the monotonic clock,
+/// which doesn't, or zeros where they aren't available.
+#[cfg(feature = "perf")]
+fn suspend_clocks() -> (u64, u64) {
+    #[cfg(target_os = "linux")]
+    {
+        (cputime::boot_time_ns(), cputime::monotonic_ns())
+    }
+    #[cfg(not(target_os = "linux"))]
+    (0, 0)
+}
+
+/// Compares how far the boot and monotonic clocks advanced between `start` and `end` to detect
+/// the system being suspended mid-session, during which the timestamp counter may stop or reset.
+#[cfg(feature = "perf")]
+#[allow(clippy::cast_precision_loss)]
+fn suspend_warning(
+    (start_boot, start_mono): (u64, u64),
+    (end_boot, end_mono): (u64, u64),
+) -> Option<String> {
+    if start_boot == 0 || start_mono == 0 {
+        return None;
+    }
+    let suspended = end_boot
+        .saturating_sub(start_boot)
+        .saturating_sub(end_mono.saturating_sub(start_mono));
+    (suspended >= MIN_SUSPEND_NS).then(|| {
+        format!(
+            "system was suspended for {:.1}s while profiling, so blocks spanning it may be \
+             inaccurate",
+            suspended as f64 / 1e9
+        )
+    })
+}
+
+#[cfg(feature = "perf")]
+#[derive(Debug)]
+#[must_use]
+pub(super) struct Profiler {
+    start_tsc: u64,
+    end_tsc: u64,
+    /// OS timer when profiling began, to validate the timer frequency over the whole session.
+    start_os: u64,
+    /// Processor profiling began on, as reported by `rdtscp`.
+    start_cpu: u32,
+    /// Boot and monotonic clocks when profiling began, to detect time spent suspended.
+    start_clocks: (u64, u64),
+    /// Number of blocks which ended at an earlier timestamp counter than they started.
+    backwards_reads: u64,
+    /// Elapsed timestamp counter restored from the saved state of an earlier process.
+    restored_tsc: u64,
+    anchors: Vec<ProfileAnchor>,
+    /// Index of each anchor in `anchors`, keyed by name.
+    anchor_indices: crate::hash::FastHashMap<&'static str, usize>,
+    /// Name and anchor index last hit at each `profile!` site, indexed by [`AnchorSite`].
+    site_anchors: Vec<Option<(&'static str, usize)>>,
+    stack: Vec<ActiveBlock>,
+    snapshots: Vec<Snapshot>,
+    branches: Vec<BranchStats>,
+    event_counters: Vec<CounterStats>,
+    loops: Vec<LoopStats>,
+    manual_blocks: Vec<(BlockId, ProfileBlock)>,
+    next_block_id: u64,
+    warnings: Vec<String>,
+    /// Full prefix of each pushed namespace, innermost last.
+    namespaces: Vec<&'static str>,
+    /// Names of anchors created within a namespace, keyed by namespace prefix and anchor name.
+    namespaced_names: std::collections::HashMap<(&'static str, &'static str), &'static str>,
+    /// This thread's shared copy of its anchor statistics, once first published.
+    published: Option<PublishedSnapshot>,
+    /// Buffer filled with the next statistics to publish, swapped with the shared copy.
+    publish_buffer: Vec<AnchorStats>,
+    /// Value of [`PUBLISH_REQUESTS`] when this thread last published for a request.
+    publish_request: u64,
+    /// Number of calls and total elapsed time of each child anchor per parent, keyed by parent and
+    /// child anchor index.
+    calls: std::collections::HashMap<(usize, usize), (u64, u64)>,
+    /// Backtrace of the first hit of each anchor created while capturing backtraces.
+    backtraces: Vec<(&'static str, std::backtrace::Backtrace)>,
+    /// Elapsed timestamp counter above which hits are captured, while capturing slow hits.
+    slow_hit_tsc: Option<u64>,
+    /// Elapsed timestamp counter and backtrace of the slowest captured hit of each anchor, keyed by
+    /// anchor index.
+    slow_hits: std::collections::HashMap<usize, (u64, std::backtrace::Backtrace)>,
+    /// Recorders enabled on this thread only, kept up to date by [`Profiler::update_recorders`].
+    recorders: u8,
+    /// Latency histogram of each anchor, by anchor index, once enabled.
+    histograms: Vec<histogram::LatencyHistogram>,
+    /// IDs of the call stacks this thread has already interned.
+    #[cfg(feature = "callstacks")]
+    known_stacks: std::collections::HashSet<u64>,
+    /// Hit count and total elapsed time of each anchor per call stack, keyed by anchor index and
+    /// stack ID.
+    #[cfg(feature = "callstacks")]
+    call_stacks: std::collections::HashMap<(usize, u64), (u64, u64)>,
+    /// This thread's innermost anchor and samples, once sampling has started.
+    sample_slot: Option<std::sync::Arc<sampling::SampleSlot>>,
+    /// Events of the current frame, while a flight recorder is running on this thread.
+    events: Option<flight::EventBuffer>,
+    /// Frame statistics of this thread, once the first frame has been marked.
+    frames: Option<frames::FrameTracker>,
+    /// Every block ended since profiling began, while recording trace events.
+    trace: Option<Vec<flight::TraceEvent>>,
+    /// Name and ID of the thread, once it has begun profiling or hit its first anchor.
+    thread: Option<ThreadInfo>,
+    /// Name of the current phase, once one has begun with [`profile_phase`].
+    phase: Option<&'static str>,
+    /// Hardware counters of the thread, once opened by the first block which counts them.
+    #[cfg(feature = "perf-counters")]
+    counter_group: Option<counters::CounterGroup>,
+    /// Why the thread's hardware counters couldn't be opened, if they failed to.
+    #[cfg(feature = "perf-counters")]
+    counter_error: Option<String>,
+}
+
+#[cfg(feature = "perf")]
+impl Drop for Profiler {
+    /// Registers the thread's final statistics for other threads' reports when it exits.
+    fn drop(&mut self) {
+        if self.anchors.is_empty()
+            || threads::thread_aggregation() == threads::ThreadAggregation::Off
+        {
+            return;
+        }
+        self.end_tsc = Self::read_block_timer();
+        let thread = self.thread.map_or("<unnamed>", |thread| thread.name);
+        threads::register(thread, self.report());
+    }
+}
+
+/// A block which has started but not yet ended.
+#[cfg(feature = "perf")]
+#[derive(Debug, Copy, Clone)]
+struct ActiveBlock {
+    id: u64,
+    name: &'static str,
+    /// Index of the block's anchor in `Profiler::anchors`.
+    anchor: usize,
+}
+
+#[cfg(feature = "perf")]
+impl Profiler {
+    pub(super) fn begin(&mut self) {
+        self.thread_info();
+        Self::detect_timer_read();
+        if clock::source().is_some()
+            && !TIMER_READ_CHOSEN.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            TIMER_READ.store(TIMER_READ_CUSTOM, std::sync::atomic::Ordering::Relaxed);
+        }
+        self.reserve_anchors();
+        self.snapshots.clear();
+        self.warnings.clear();
+        self.frames = None;
+        if let Some(slot) = &self.sample_slot {
+            slot.reset();
+        } else if sampling::is_active() {
+            self.start_sampling();
+        }
+        #[cfg(all(feature = "startup", target_os = "linux"))]
+        startup::begin();
+        self.backwards_reads = 0;
+        self.trace = chrome::record_trace_events().then(Vec::new);
+        let threshold_ns = SLOW_HIT_THRESHOLD_NS.load(std::sync::atomic::Ordering::Relaxed);
+        self.slow_hit_tsc = (threshold_ns != u64::MAX).then(|| {
+            let ticks = u128::from(threshold_ns) * u128::from(Self::timer_freq()) / 1_000_000_000;
+            u64::try_from(ticks).unwrap_or(u64::MAX)
+        });
+        self.update_recorders();
+        self.start_clocks = suspend_clocks();
+        self.start_os = Self::read_os_timer();
+        (self.start_tsc, self.start_cpu) = Self::read_block_timer_and_cpu();
+    }
+
+    /// Recomputes the recorders enabled on this thread, after enabling or disabling events, traces
+    /// or slow hits.
+    pub(super) fn update_recorders(&mut self) {
+        self.recorders = 0;
+        if self.events.is_some() {
+            self.recorders |= RECORD_EVENTS;
+        }
+        if self.trace.is_some() {
+            self.recorders |= RECORD_TRACE;
+        }
+        if self.slow_hit_tsc.is_some() {
+            self.recorders |= RECORD_SLOW_HITS;
+        }
+    }
+
+    pub(super) fn end(&mut self) -> ProfileReport {
+        let (end_tsc, end_cpu) = Self::read_block_timer_and_cpu();
+        self.end_tsc = end_tsc;
+        if let Some(warning) = timer_freq_warning(
+            end_tsc.wrapping_sub(self.start_tsc),
+            if self.start_os == 0 {
+                0
+            } else {
+                Self::read_os_timer().saturating_sub(self.start_os)
+            },
+            Self::get_os_timer_freq(),
+            Self::timer_freq(),
+            (self.start_cpu, end_cpu),
+        ) {
+            // Not an API misuse, so reported in release builds too.
+            self.warnings.push(warning);
+        }
+        if labels::overflowed() {
+            self.warnings.push(format!(
+                "more than {} distinct labels were formatted, the rest were recorded under their \
+                 format string",
+                config::max_labels()
+            ));
+        }
+        if Self::detect_timer_read()
+            && TIMER_READ.load(std::sync::atomic::Ordering::Relaxed) == TIMER_READ_OS
+        {
+            self.warnings.push(
+                "the timestamp counter isn't invariant, so blocks read the operating system clock \
+                 instead"
+                    .to_string(),
+            );
+        }
+        if self.backwards_reads > 0 {
+            self.warnings.push(format!(
+                "timestamp counter went backwards in {} blocks, which were recorded as taking no \
+                 time",
+                self.backwards_reads
+            ));
+        }
+        if let Some(warning) = suspend_warning(self.start_clocks, suspend_clocks()) {
+            self.warnings.push(warning);
+        }
+        #[cfg(feature = "perf-counters")]
+        if let Some(err) = &self.counter_error {
+            self.warnings
+                .push(format!("hardware counters are unavailable: {err}"));
+        }
+        if self.anchor_indices.contains_key(config::OVERFLOW_ANCHOR) {
+            self.warnings.push(format!(
+                "more than {} anchors were hit, the rest were recorded as `{}`",
+                config::max_anchors(),
+                config::OVERFLOW_ANCHOR
+            ));
+        }
+        if cfg!(debug_assertions) {
+            for (_, block) in &self.manual_blocks {
+                self.warnings
+                    .push(format!("block `{}` was begun but never ended", block.name));
+            }
+        }
+        self.report()
+    }
+
+    /// Clears the statistics accumulated so far and begins profiling again. Active blocks keep
+    /// the start time they read, so they still include the time before the reset when they end.
+    pub(super) fn reset(&mut self) {
+        for anchor in &mut self.anchors {
+            *anchor = ProfileAnchor {
+                name: anchor.name,
+                tsc_min: u64::MAX,
+                active: anchor.active,
+                ..Default::default()
+            };
+        }
+        self.restored_tsc = 0;
+        self.branches.clear();
+        self.event_counters.clear();
+        self.frames = None;
+        self.loops.clear();
+        self.calls.clear();
+        self.slow_hits.clear();
+        self.histograms.clear();
+        #[cfg(feature = "callstacks")]
+        self.call_stacks.clear();
+        self.begin();
+    }
+
+    /// Returns the elapsed time and anchor statistics accumulated so far, to be saved.
+    fn state(&mut self) -> ProfileReport {
+        if !self.stack.is_empty() {
+            self.warn(format!(
+                "profiler state was saved while `{}` was still open",
+                self.stack[self.stack.len() - 1].name
+            ));
+        }
+        let elapsed = if self.start_tsc == 0 {
+            0
+        } else {
+            Self::read_block_timer().saturating_sub(self.start_tsc)
+        };
+        ProfileReport {
+            elapsed_tsc: elapsed.saturating_add(self.restored_tsc),
+            timer_freq: Self::timer_freq(),
+            anchors: self.anchors.iter().map(AnchorStats::from).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Adds the elapsed time and anchor statistics of a saved `state` to this thread's.
+    fn restore(&mut self, state: &ProfileReport) {
+        let mut rescaled = ProfileReport {
+            timer_freq: Self::timer_freq(),
+            ..Default::default()
+        };
+        rescaled.merge(state);
+        self.restored_tsc = self.restored_tsc.saturating_add(rescaled.elapsed_tsc);
+        for stats in &rescaled.anchors {
+            let index = self.anchor_index(stats.name);
+            let anchor = &mut self.anchors[index];
+            anchor.hit_count = anchor.hit_count.saturating_add(stats.hit_count);
+            anchor.byte_count = anchor.byte_count.saturating_add(stats.byte_count);
+            anchor.item_count = anchor.item_count.saturating_add(stats.item_count);
+            anchor.tsc_elapsed_exclusive = anchor
+                .tsc_elapsed_exclusive
+                .wrapping_add(stats.tsc_elapsed_exclusive);
+            anchor.tsc_elapsed_inclusive = anchor
+                .tsc_elapsed_inclusive
+                .saturating_add(stats.tsc_elapsed_inclusive);
+            anchor.cpu_ns_exclusive = anchor.cpu_ns_exclusive.wrapping_add(stats.cpu_ns_exclusive);
+            anchor.cpu_ns_inclusive = anchor
+                .cpu_ns_inclusive
+                .saturating_add(stats.cpu_ns_inclusive);
+            anchor.tsc_elapsed_squares = anchor
+                .tsc_elapsed_squares
+                .saturating_add(stats.tsc_elapsed_squares);
+            if stats.hit_count > 0 {
+                anchor.tsc_min = anchor.tsc_min.min(stats.tsc_min);
+            }
+            anchor.tsc_max = anchor.tsc_max.max(stats.tsc_max);
+            anchor.alloc_count = anchor.alloc_count.wrapping_add(stats.alloc_count);
+            anchor.alloc_bytes = anchor.alloc_bytes.wrapping_add(stats.alloc_bytes);
+            anchor.freed_bytes = anchor.freed_bytes.wrapping_add(stats.freed_bytes);
+            anchor.counters = anchor.counters.zip(stats.counters, u64::wrapping_add);
+            anchor.soft_page_faults = anchor.soft_page_faults.wrapping_add(stats.soft_page_faults);
+            anchor.hard_page_faults = anchor.hard_page_faults.wrapping_add(stats.hard_page_faults);
+            anchor.voluntary_context_switches = anchor
+                .voluntary_context_switches
+                .wrapping_add(stats.voluntary_context_switches);
+            anchor.involuntary_context_switches = anchor
+                .involuntary_context_switches
+                .wrapping_add(stats.involuntary_context_switches);
+        }
+    }
+
+    /// Records a misuse of the profiling API, which is only checked in debug builds.
+    fn warn(&mut self, warning: String) {
+        if cfg!(debug_assertions) {
+            self.warnings.push(warning);
+        }
+    }
+
+    pub(super) fn snapshot(&mut self, name: &'static str) -> Snapshot {
+        let snapshot = Snapshot {
+            name,
+            tsc: Self::read_block_timer(),
+            anchors: self.anchors.iter().map(AnchorStats::from).collect(),
+        };
+        self.snapshots.push(snapshot.clone());
+        self.publish();
+        snapshot
+    }
+
+    /// Returns the name and ID of the thread, capturing them the first time.
+    fn thread_info(&mut self) -> ThreadInfo {
+        *self.thread.get_or_insert_with(|| {
+            let thread = std::thread::current();
+            ThreadInfo {
+                name: crate::intern::intern_static(thread.name().unwrap_or("<unnamed>")),
+                id: thread.id(),
+            }
+        })
+    }
+
+    fn publish(&mut self) {
+        self.publish_buffer.clear();
+        self.publish_buffer
+            .extend(self.anchors.iter().map(AnchorStats::from));
+        let name = self.thread_info().name;
+        let published = self.published.get_or_insert_with(|| {
+            let published = std::sync::Arc::new(std::sync::Mutex::new(Snapshot {
+                name,
+                ..Snapshot::default()
+            }));
+            PUBLISHED
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .push(std::sync::Arc::clone(&published));
+            published
+        });
+        let tsc = Self::read_block_timer();
+        let mut front = published
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        std::mem::swap(&mut front.anchors, &mut self.publish_buffer);
+        front.tsc = tsc;
+    }
+
+    fn record_branch(&mut self, point: &'static str, arm: &'static str) {
+        let branch = if let Some(branch) = self.branches.iter_mut().find(|b| b.name == point) {
+            branch
+        } else {
+            self.branches.push(BranchStats {
+                name: point,
+                arms: Vec::new(),
+            });
+            self.branches.last_mut().expect("valid branch")
+        };
+        if let Some(branch_arm) = branch.arms.iter_mut().find(|a| a.name == arm) {
+            branch_arm.hit_count += 1;
+        } else {
+            branch.arms.push(BranchArm {
+                name: arm,
+                hit_count: 1,
+            });
+        }
+    }
+
+    fn record_counter(&mut self, name: &'static str, count: u64) {
+        match self
+            .event_counters
+            .iter_mut()
+            .find(|counter| counter.name == name)
+        {
+            Some(counter) => counter.count = counter.count.saturating_add(count),
+            None => self.event_counters.push(CounterStats { name, count }),
+        }
+    }
+
+    fn frame_mark(&mut self) {
+        let tsc = Self::read_block_timer();
+        let anchors = self.anchors.iter().map(AnchorStats::from).collect();
+        match &mut self.frames {
+            Some(tracker) => tracker.mark(tsc, anchors),
+            None => {
+                self.frames = Some(frames::FrameTracker::new(tsc, anchors, Self::timer_freq()));
+            }
+        }
+    }
+
+    fn push_namespace(&mut self, namespace: &'static str) {
+        let prefix = match self.namespaces.last() {
+            Some(outer) => crate::intern::intern_static(&format!("{outer}::{namespace}")),
+            None => namespace,
+        };
+        self.namespaces.push(prefix);
+    }
+
+    /// Returns `name` prefixed with the current namespace, if any.
+    pub(super) fn namespaced(&mut self, name: &'static str) -> &'static str {
+        let Some(&prefix) = self.namespaces.last() else {
+            return name;
+        };
+        let namespaced = self
+            .namespaced_names
+            .entry((prefix, name))
+            .or_insert_with(|| crate::intern::intern_static(&format!("{prefix}::{name}")));
+        namespaced
+    }
+
+    /// Reads the thread's hardware counters if enabled, opening them on first use.
+    #[cfg(feature = "perf-counters")]
+    #[inline]
+    fn read_counters(&mut self) -> Option<counters::HardwareCounters> {
+        if !counters::hardware_counters() || self.counter_error.is_some() {
+            return None;
+        }
+        if self.counter_group.is_none() {
+            match counters::CounterGroup::open() {
+                Ok(group) => self.counter_group = Some(group),
+                Err(err) => {
+                    self.counter_error = Some(err.to_string());
+                    return None;
+                }
+            }
+        }
+        self.counter_group.as_ref()?.read()
+    }
+
+    /// Name of the innermost active block, if any.
+    fn parent(&self) -> Option<&'static str> {
+        self.stack.last().map(|block| block.name)
+    }
+
+    /// Returns the index of the anchor for `name` hit at `site`, creating it if this is its first
+    /// hit.
+    #[inline]
+    fn site_anchor_index(&mut self, site: &AnchorSite, name: &'static str) -> usize {
+        let site = site.index();
+        // Names usually come from the same literal at every hit of a site, so comparing pointers
+        // finds them without hashing. Sites with dynamic names fall back to a lookup by name.
+        if let Some(Some((cached, index))) = self.site_anchors.get(site) {
+            if std::ptr::eq(*cached, name) {
+                return *index;
+            }
+        }
+        let index = self.anchor_index(name);
+        if site >= self.site_anchors.len() {
+            self.site_anchors.resize(site + 1, None);
+        }
+        self.site_anchors[site] = Some((name, index));
+        index
+    }
+
+    /// Returns the index of the anchor for `name`, creating it if this is its first hit.
+    #[inline]
+    fn anchor_index(&mut self, name: &'static str) -> usize {
+        match self.anchor_indices.get(name) {
+            Some(&index) => index,
+            None => self.anchor_index_slow(name),
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn anchor_index_slow(&mut self, mut name: &'static str) -> usize {
+        self.thread_info();
+        self.reserve_anchors();
+        let overflow_index = self.anchor_indices.get(config::OVERFLOW_ANCHOR).copied();
+        if self.anchors.len() - usize::from(overflow_index.is_some()) >= config::max_anchors()
+            && config::aggregate_overflow()
+        {
+            if let Some(index) = overflow_index {
+                return index;
+            }
+            name = config::OVERFLOW_ANCHOR;
+        }
+        if CAPTURE_BACKTRACES.load(std::sync::atomic::Ordering::Relaxed) {
+            self.backtraces
+                .push((name, std::backtrace::Backtrace::force_capture()));
+        }
+        self.anchors.push(ProfileAnchor {
+            name,
+            tsc_min: u64::MAX,
+            ..Default::default()
+        });
+        let index = self.anchors.len() - 1;
+        self.anchor_indices.insert(name, index);
+        index
+    }
+
+    /// Makes room for the configured number of anchors and the overflow anchor before the first is
+    /// created, so the table doesn't reallocate while blocks are measured.
+    fn reserve_anchors(&mut self) {
+        if self.anchors.capacity() == 0 {
+            let capacity = config::max_anchors().saturating_add(1);
+            self.anchors.reserve_exact(capacity);
+            self.anchor_indices.reserve(capacity);
+        }
+    }
+
+    /// Pushes a new active block for the anchor at index `anchor`, returning its ID.
+    fn push_block(&mut self, name: &'static str, anchor: usize) -> u64 {
+        let id = self.next_block_id;
+        self.next_block_id += 1;
+        self.stack.push(ActiveBlock { id, name, anchor });
+        self.update_sampled_anchor();
+        id
+    }
+
+    /// Shares the innermost active anchor with running samplers, registering this thread with them
+    /// on the first block after sampling starts.
+    #[inline]
+    fn update_sampled_anchor(&mut self) {
+        if let Some(slot) = &self.sample_slot {
+            slot.enter(self.stack.last().map(|block| block.anchor));
+        } else if sampling::is_active() {
+            self.start_sampling();
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn start_sampling(&mut self) {
+        let slot = sampling::SampleSlot::register();
+        slot.enter(self.stack.last().map(|block| block.anchor));
+        self.sample_slot = Some(slot);
+    }
+
+    /// Removes the active block `id` from the stack, returning the anchor index of the block below
+    /// it which is credited as its parent.
+    #[inline]
+    fn pop_block(&mut self, id: u64) -> Option<usize> {
+        match self.stack.last() {
+            Some(top) if top.id == id => {
+                self.stack.pop();
+                self.update_sampled_anchor();
+                self.stack.last().map(|parent| parent.anchor)
+            }
+            _ => self.pop_block_out_of_order(id),
+        }
+    }
+
+    /// Removes the active block `id` from wherever it is in the stack. Blocks usually end in LIFO
+    /// order, but manually ended blocks may not.
+    #[cold]
+    #[inline(never)]
+    fn pop_block_out_of_order(&mut self, id: u64) -> Option<usize> {
+        let index = self.stack.iter().rposition(|block| block.id == id)?;
+        self.stack.remove(index);
+        self.update_sampled_anchor();
+        index.checked_sub(1).map(|parent| self.stack[parent].anchor)
+    }
+
+    fn record_loop_iteration(&mut self, name: &'static str, elapsed: u64) {
+        let parent = self.parent();
+        if let Some(stats) = self
+            .loops
+            .iter_mut()
+            .find(|stats| stats.name == name && stats.parent == parent)
+        {
+            stats.iteration_count += 1;
+            stats.tsc_min = stats.tsc_min.min(elapsed);
+            stats.tsc_max = stats.tsc_max.max(elapsed);
+            stats.tsc_total = stats.tsc_total.saturating_add(elapsed);
+        } else {
+            self.loops.push(LoopStats {
+                name,
+                parent,
+                iteration_count: 1,
+                parent_hit_count: 0,
+                tsc_min: elapsed,
+                tsc_max: elapsed,
+                tsc_total: elapsed,
+            });
+        }
+    }
+
+    /// Builds a report of the profiling session, including per-interval results between each
+    /// snapshot.
+    fn report(&self) -> ProfileReport {
+        let timer_freq = Self::timer_freq();
+        // Anchors are kept when reset, but leave out those not hit since.
+        let anchors: Vec<AnchorStats> = self
+            .anchors
+            .iter()
+            .filter(|anchor| anchor.hit_count > 0)
+            .map(AnchorStats::from)
+            .collect();
+
+        let mut intervals = Vec::new();
+        if !self.snapshots.is_empty() {
+            let begin = Snapshot {
+                name: "begin",
+                tsc: self.start_tsc,
+                anchors: Vec::new(),
+            };
+            let end = Snapshot {
+                name: "end",
+                tsc: self.end_tsc,
+                anchors: anchors.clone(),
+            };
+            let boundaries: Vec<&Snapshot> = std::iter::once(&begin)
+                .chain(&self.snapshots)
+                .chain(std::iter::once(&end))
+                .collect();
+            for pair in boundaries.windows(2) {
+                let (earlier, later) = (pair[0], pair[1]);
+                intervals.push(Interval {
+                    from: earlier.name,
+                    to: later.name,
+                    report: later.delta(earlier),
+                });
+            }
+        }
+
+        let never_hit = self.never_hit(&anchors);
+
+        ProfileReport {
+            elapsed_tsc: self
+                .end_tsc
+                .saturating_sub(self.start_tsc)
+                .saturating_add(self.restored_tsc),
+            timer_freq,
+            anchors,
+            intervals,
+            never_hit,
+            branches: self.branches.clone(),
+            event_counters: self.event_counters.clone(),
+            loops: self
+                .loops
+                .iter()
+                .map(|stats| LoopStats {
+                    parent_hit_count: stats.parent.map_or(1, |parent| {
+                        self.anchors
+                            .iter()
+                            .find(|anchor| anchor.name == parent)
+                            .map_or(1, |anchor| anchor.hit_count)
+                    }),
+                    ..*stats
+                })
+                .collect(),
+            samples: self.samples(),
+            calls: self.calls(),
+            call_stacks: self.call_stacks(),
+            backtraces: self
+                .backtraces
+                .iter()
+                .map(|(name, backtrace)| AnchorBacktrace {
+                    name,
+                    backtrace: backtrace.to_string(),
+                })
+                .collect(),
+            slow_hits: self.slow_hits(),
+            histograms: self
+                .histograms
+                .iter()
+                .filter(|histogram| histogram.count() > 0)
+                .cloned()
+                .collect(),
+            metadata: RUN_METADATA
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .iter()
+                .cloned()
+                .chain(self.phase.map(|phase| ("phase", phase.to_string())))
+                .collect(),
+            warnings: self.warnings.clone(),
+            memory: None,
+            cpu: None,
+            thread: self.thread,
+        }
+    }
+
+    /// Counts a block of the anchor at index `anchor` which took `elapsed` ticks in the anchor's
+    /// latency histogram, if enabled.
+    #[inline]
+    fn record_latency(&mut self, anchor: usize, elapsed: u64) {
+        let Some(precision) = config::latency_histograms() else {
+            return;
+        };
+        while self.histograms.len() <= anchor {
+            let name = self.anchors[self.histograms.len()].name;
+            self.histograms
+                .push(histogram::LatencyHistogram::new(name, precision));
+        }
+        self.histograms[anchor].record(elapsed);
+    }
+
+    /// Captures a backtrace of a block of the anchor at index `anchor` which took `elapsed` ticks,
+    /// if slow hits are being captured and it's the slowest above the threshold so far.
+    #[inline]
+    fn record_slow_hit(&mut self, anchor: usize, elapsed: u64) {
+        if self
+            .slow_hit_tsc
+            .is_some_and(|threshold| elapsed > threshold)
+            && self
+                .slow_hits
+                .get(&anchor)
+                .is_none_or(|&(slowest, _)| elapsed > slowest)
+        {
+            self.capture_slow_hit(anchor, elapsed);
+        }
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn capture_slow_hit(&mut self, anchor: usize, elapsed: u64) {
+        self.slow_hits.insert(
+            anchor,
+            (elapsed, std::backtrace::Backtrace::force_capture()),
+        );
+    }
+
+    /// Records a block of the anchor at index `child` which took `elapsed` ticks within a block of
+    /// the anchor at index `parent`.
+    #[inline]
+    pub(super) fn record_call(&mut self, parent: usize, child: usize, elapsed: u64) {
+        let (call_count, tsc_elapsed) = self.calls.entry((parent, child)).or_default();
+        *call_count += 1;
+        *tsc_elapsed = tsc_elapsed.saturating_add(elapsed);
+    }
+
+    /// Returns the calls between anchors, ordered by parent and then child anchor.
+    fn calls(&self) -> Vec<CallStats> {
+        let mut calls: Vec<_> = self.calls.iter().collect();
+        calls.sort_by_key(|(&key, _)| key);
+        calls
+            .into_iter()
+            .map(
+                |(&(parent, child), &(call_count, tsc_elapsed_inclusive))| CallStats {
+                    caller: self.anchors[parent].name,
+                    callee: self.anchors[child].name,
+                    call_count,
+                    tsc_elapsed_inclusive,
+                },
+            )
+            .collect()
+    }
+
+    /// Returns the elapsed time of each anchor per call stack, in anchor order.
+    #[cfg_attr(not(feature = "callstacks"), allow(clippy::unused_self))]
+    fn call_stacks(&self) -> Vec<CallStackStats> {
+        #[cfg(feature = "callstacks")]
+        {
+            let mut call_stacks: Vec<(usize, CallStackStats)> = self
+                .call_stacks
+                .iter()
+                .map(
+                    |(&(anchor, stack_id), &(hit_count, tsc_elapsed_inclusive))| {
+                        let stats = CallStackStats {
+                            name: self.anchors[anchor].name,
+                            stack_id,
+                            hit_count,
+                            tsc_elapsed_inclusive,
+                        };
+                        (anchor, stats)
+                    },
+                )
+                .collect();
+            call_stacks.sort_by_key(|(anchor, stats)| {
+                (*anchor, std::cmp::Reverse(stats.tsc_elapsed_inclusive))
+            });
+            call_stacks.into_iter().map(|(_, stats)| stats).collect()
+        }
+        #[cfg(not(feature = "callstacks"))]
+        Vec::new()
+    }
+
+    /// Returns the registered anchor names which weren't hit by any of `anchors`.
+    fn never_hit(&self, anchors: &[AnchorStats]) -> Vec<&'static str> {
+        // Registered names are those of the sites, so a site hit within a namespace counts as hit.
+        let hit_in_namespace: std::collections::HashSet<&str> = self
+            .namespaced_names
+            .iter()
+            .filter(|(_, &namespaced)| anchors.iter().any(|anchor| anchor.name == namespaced))
+            .map(|(&(_, name), _)| name)
+            .collect();
+        registered_anchor_names()
+            .into_iter()
+            .filter(|name| {
+                !hit_in_namespace.contains(name)
+                    && !anchors.iter().any(|anchor| {
+                        anchor.name == *name
+                            || (anchor.name.contains('<')
+                                && names::is_instantiation_of(anchor.name, name))
+                    })
+            })
+            .collect()
+    }
+
+    /// Returns the slowest captured hit of each anchor, slowest first.
+    fn slow_hits(&self) -> Vec<SlowHit> {
+        let mut slow_hits: Vec<SlowHit> = self
+            .slow_hits
+            .iter()
+            .map(|(&anchor, (tsc_elapsed, backtrace))| SlowHit {
+                name: self.anchors[anchor].name,
+                tsc_elapsed: *tsc_elapsed,
+                backtrace: backtrace.to_string(),
+            })
+            .collect();
+        slow_hits.sort_by_key(|hit| std::cmp::Reverse(hit.tsc_elapsed));
+        slow_hits
+    }
+
+    /// Returns the samples taken of this thread, most sampled first.
+    fn samples(&self) -> Vec<SampleStats> {
+        let Some(slot) = &self.sample_slot else {
+            return Vec::new();
+        };
+        let mut samples: Vec<SampleStats> = slot
+            .counts()
+            .into_iter()
+            .map(|(anchor, sample_count)| SampleStats {
+                name: anchor.map_or(sampling::UNINSTRUMENTED, |anchor| self.anchors[anchor].name),
+                sample_count,
+            })
+            .collect();
+        samples.sort_by_key(|sample| std::cmp::Reverse(sample.sample_count));
+        samples
+    }
+
+    /// Returns the block timer frequency, which is only estimated once per process.
+    pub(super) fn timer_freq() -> u64 {
+        Self::detect_timer_read();
+        match TIMER_READ.load(std::sync::atomic::Ordering::Relaxed) {
+            TIMER_READ_OS => clock::os_frequency(),
+            TIMER_READ_CUSTOM => clock::source().map_or(0, clock::ClockSource::frequency),
+            _ => Self::timestamp_freq(),
+        }
+    }
+
+    /// Returns the calibrated frequency of the timestamp counter, or of the fallback timer.
+    pub(super) fn timestamp_freq() -> u64 {
+        static TIMER_FREQ: std::sync::OnceLock<u64> = std::sync::OnceLock::new();
+        #[cfg(target_arch = "aarch64")]
+        let freq = virtual_counter_freq;
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        let freq = Self::estimated_block_timer_freq;
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+        let freq = || clock::FREQUENCY;
+        *TIMER_FREQ.get_or_init(freq)
+    }
+
+    /// Returns a conversion factor for OS timer. In the case of linux, the units are in microseconds.
+    fn get_os_timer_freq() -> u64 {
+        1_000_000
+    }
+
+    fn read_os_timer() -> u64 {
+        #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+        {
+            use std::time::{SystemTime, UNIX_EPOCH};
+            let since_epoch = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("system time is earlier than Unix Epoch");
+            Self::get_os_timer_freq() * since_epoch.as_secs()
+                + u64::from(since_epoch.subsec_micros())
+        }
+        // The system time isn't available without WASI.
+        #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+        {
+            clock::read() / 1_000
+        }
+    }
+
+    /// Reads the timestamp counter with the instruction chosen by [`set_timer_read`].
+    #[inline]
+    fn read_block_timer() -> u64 {
+        Self::read_timer(TIMER_READ.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    /// Reads the timestamp counter the way given by a [`TIMER_READ`] value.
+    #[inline]
+    fn read_timer(mode: u8) -> u64 {
+        #[cfg(target_arch = "x86")]
+        use std::arch::x86 as arch;
+        #[cfg(target_arch = "x86_64")]
+        use std::arch::x86_64 as arch;
+
+        match mode {
+            // SAFETY: `rdtsc` and `lfence` are available on every x86 processor with SSE2.
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            TIMER_READ_RDTSC => unsafe { arch::_rdtsc() },
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            TIMER_READ_FENCED => unsafe {
+                arch::_mm_lfence();
+                arch::_rdtsc()
+            },
+            #[cfg(target_arch = "aarch64")]
+            TIMER_READ_RDTSC => read_virtual_counter(false),
+            TIMER_READ_OS => clock::read_os(),
+            TIMER_READ_CUSTOM => clock::read_source(),
+            _ => Self::read_timestamp_and_cpu().0,
+        }
+    }
+
+    /// Reads the block timer along with the processor it was read on, if known.
+    #[inline]
+    fn read_block_timer_and_cpu() -> (u64, u32) {
+        match TIMER_READ.load(std::sync::atomic::Ordering::Relaxed) {
+            TIMER_READ_OS => (clock::read_os(), 0),
+            TIMER_READ_CUSTOM => (clock::read_source(), 0),
+            _ => Self::read_timestamp_and_cpu(),
+        }
+    }
+
+    /// Reads the timestamp counter along with the processor it was read on, with `rdtscp` unless
+    /// the processor lacks it.
+    #[inline]
+    pub(super) fn read_timestamp_and_cpu() -> (u64, u32) {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if !RDTSCP_SUPPORTED.load(std::sync::atomic::Ordering::Relaxed) {
+                #[cfg(target_arch = "x86")]
+                use std::arch::x86 as arch;
+                #[cfg(target_arch = "x86_64")]
+                use std::arch::x86_64 as arch;
+                // SAFETY: `rdtsc` and `lfence` are available on every x86 processor with SSE2.
+                return unsafe {
+                    arch::_mm_lfence();
+                    (arch::_rdtsc(), 0)
+                };
+            }
+            let mut aux = 0;
+            #[cfg(target_arch = "x86")]
+            let tsc = unsafe { std::arch::x86::__rdtscp(&raw mut aux) };
+            #[cfg(target_arch = "x86_64")]
+            let tsc = unsafe { std::arch::x86_64::__rdtscp(&raw mut aux) };
+            // Linux stores the CPU number in the low 12 bits of `IA32_TSC_AUX`.
+            (tsc, aux & 0xFFF)
+        }
+        // The virtual counter doesn't report the processor it was read on.
+        #[cfg(target_arch = "aarch64")]
+        {
+            (read_virtual_counter(true), 0)
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64", target_arch = "aarch64")))]
+        {
+            (clock::read(), 0)
+        }
+    }
+
+    /// Checks once per process whether the processor has an invariant timestamp counter and
+    /// `rdtscp`, falling back to the operating system clock without the former unless a timer read
+    /// was chosen with [`set_timer_read`]. Returns whether it fell back.
+    fn detect_timer_read() -> bool {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            static FELL_BACK: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+            *FELL_BACK.get_or_init(|| {
+                use std::sync::atomic::Ordering;
+
+                let (invariant, rdtscp) = x86_timer_features();
+                RDTSCP_SUPPORTED.store(rdtscp, Ordering::Relaxed);
+                let fall_back = !invariant && !TIMER_READ_CHOSEN.load(Ordering::Relaxed);
+                if fall_back {
+                    TIMER_READ.store(TIMER_READ_OS, Ordering::Relaxed);
+                }
+                fall_back
+            })
+        }
+        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+        false
+    }
+
+    #[allow(
+        clippy::cast_sign_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss
+    )]
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    fn estimated_block_timer_freq() -> u64 {
+        let milliseconds_to_wait = 10;
+        let os_freq = Self::get_os_timer_freq();
+
+        let block_start = Self::read_timestamp_and_cpu().0;
+        let os_start = Self::read_os_timer();
+        let mut os_end;
+        let mut os_elapsed = 0;
+        let os_wait_time = os_freq * milliseconds_to_wait / 1000;
+        while os_elapsed < os_wait_time {
+            os_end = Self::read_os_timer();
+            os_elapsed = os_end - os_start;
+        }
+
+        let block_end = Self::read_timestamp_and_cpu().0;
+        let block_elapsed = block_end - block_start;
+
+        (os_freq * block_elapsed)
+            .checked_div(os_elapsed)
+            .unwrap_or(0)
+    }
+}
+
+/// Returns whether the processor reports an invariant timestamp counter, which ticks at a constant
+/// rate in every power state and on every core, and whether it supports `rdtscp`.
+#[cfg(all(feature = "perf", any(target_arch = "x86", target_arch = "x86_64")))]
+fn x86_timer_features() -> (bool, bool) {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86 as arch;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64 as arch;
+
+    // The extended leaves are only read when the processor reports them.
+    let max_extended_leaf = arch::__cpuid(0x8000_0000).eax;
+    let rdtscp =
+        max_extended_leaf >= 0x8000_0001 && arch::__cpuid(0x8000_0001).edx & (1 << 27) != 0;
+    let invariant =
+        max_extended_leaf >= 0x8000_0007 && arch::__cpuid(0x8000_0007).edx & (1 << 8) != 0;
+    (invariant, rdtscp)
+}
+
+/// Reads the `aarch64` virtual counter, after an `isb` barrier if `ordered` so earlier instructions
+/// finish first.
+#[cfg(all(feature = "perf", target_arch = "aarch64"))]
+#[inline]
+fn read_virtual_counter(ordered: bool) -> u64 {
+    let counter: u64;
+    // SAFETY: `cntvct_el0` is readable from user space on Linux and macOS.
+    unsafe {
+        if ordered {
+            std::arch::asm!("isb", "mrs {}, cntvct_el0", out(reg) counter, options(nostack));
+        } else {
+            std::arch::asm!("mrs {}, cntvct_el0", out(reg) counter, options(nomem, nostack));
+        }
+    }
+    counter
+}
+
+/// Returns the frequency of the `aarch64` virtual counter, which the system reports in
+/// `cntfrq_el0` so it doesn't need to be estimated.
+#[cfg(all(feature = "perf", target_arch = "aarch64"))]
+fn virtual_counter_freq() -> u64 {
+    let freq: u64;
+    // SAFETY: `cntfrq_el0` is readable from user space on Linux and macOS.
+    unsafe {
+        std::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq, options(nomem, nostack));
+    }
+    freq
+}
+
+#[cfg(feature = "perf")]
+#[derive(Debug, Default, Copy, Clone)]
+#[must_use]
+struct ProfileAnchor {
+    name: &'static str,
+    hit_count: u64,
+    byte_count: u64,
+    item_count: u64,
+    tsc_elapsed_exclusive: u64,
+    tsc_elapsed_inclusive: u64,
+    cpu_ns_exclusive: u64,
+    cpu_ns_inclusive: u64,
+    tsc_elapsed_squares: u128,
+    /// Shortest hit so far, or `u64::MAX` before the first hit ends.
+    tsc_min: u64,
+    tsc_max: u64,
+    alloc_count: u64,
+    alloc_bytes: u64,
+    freed_bytes: u64,
+    counters: counters::HardwareCounters,
+    soft_page_faults: u64,
+    hard_page_faults: u64,
+    voluntary_context_switches: u64,
+    involuntary_context_switches: u64,
+    /// Number of the anchor's blocks currently active on the thread, more than one when recursing.
+    active: u32,
+}
+
+#[cfg(feature = "perf")]
+impl ProfileAnchor {
+    /// Applies `op` to each exclusive measurement and the matching measurement of `cost`.
+    #[inline]
+    fn add_exclusive(&mut self, cost: &BlockCost, op: fn(u64, u64) -> u64) {
+        self.tsc_elapsed_exclusive = op(self.tsc_elapsed_exclusive, cost.elapsed);
+        self.cpu_ns_exclusive = op(self.cpu_ns_exclusive, cost.cpu_ns);
+        self.alloc_count = op(self.alloc_count, cost.allocations.count);
+        self.alloc_bytes = op(self.alloc_bytes, cost.allocations.allocated_bytes);
+        self.freed_bytes = op(self.freed_bytes, cost.allocations.freed_bytes);
+        self.counters = self.counters.zip(cost.counters, op);
+        self.add_usage(cost.usage, op);
+    }
+
+    /// Applies `op` to each page fault and context switch count and the matching count of `usage`.
+    #[inline]
+    fn add_usage(&mut self, usage: rusage::ResourceUsage, op: fn(u64, u64) -> u64) {
+        self.soft_page_faults = op(self.soft_page_faults, usage.soft_faults);
+        self.hard_page_faults = op(self.hard_page_faults, usage.hard_faults);
+        self.voluntary_context_switches =
+            op(self.voluntary_context_switches, usage.voluntary_switches);
+        self.involuntary_context_switches = op(
+            self.involuntary_context_switches,
+            usage.involuntary_switches,
+        );
+    }
+}
+
+#[cfg(feature = "perf")]
+impl From<&ProfileAnchor> for AnchorStats {
+    fn from(anchor: &ProfileAnchor) -> Self {
+        Self {
+            name: anchor.name,
+            hit_count: anchor.hit_count,
+            byte_count: anchor.byte_count,
+            item_count: anchor.item_count,
+            tsc_elapsed_exclusive: anchor.tsc_elapsed_exclusive,
+            tsc_elapsed_inclusive: anchor.tsc_elapsed_inclusive,
+            cpu_ns_exclusive: anchor.cpu_ns_exclusive,
+            cpu_ns_inclusive: anchor.cpu_ns_inclusive,
+            tsc_elapsed_squares: anchor.tsc_elapsed_squares,
+            tsc_min: if anchor.tsc_min == u64::MAX {
+                0
+            } else {
+                anchor.tsc_min
+            },
+            tsc_max: anchor.tsc_max,
+            alloc_count: anchor.alloc_count,
+            alloc_bytes: anchor.alloc_bytes,
+            freed_bytes: anchor.freed_bytes,
+            counters: anchor.counters,
+            soft_page_faults: anchor.soft_page_faults,
+            hard_page_faults: anchor.hard_page_faults,
+            voluntary_context_switches: anchor.voluntary_context_switches,
+            involuntary_context_switches: anchor.involuntary_context_switches,
+        }
+    }
+}
+
+/// Profile block is created inside each function scope where `profile!()` is called, keeping
+/// track of its position in the profiler's block stack and start time. Only the outermost active
+/// block of each anchor adds to its inclusive time, so recursive calls aren't counted twice.
+///
+/// Once a block's anchor exists, creating and dropping the block performs no heap allocation: the
+/// anchor table is sized for [`config::ProfilerConfig::max_anchors`] anchors when the thread
+/// creates its first, and the block stack only grows on a thread's first use of more than 64
+/// nested blocks.
+#[cfg(feature = "perf")]
+#[derive(Debug)]
+#[must_use]
+pub struct ProfileBlock {
+    name: &'static str,
+    id: u64,
+    /// Index of the block's anchor in the creating thread's profiler.
+    anchor: usize,
+    start_tsc: u64,
+    /// Thread CPU time when the block started, if measured.
+    start_cpu_ns: Option<u64>,
+    /// Allocations counted on the thread when the block started, if tracked.
+    start_allocations: Option<memory::ThreadAllocations>,
+    /// Page faults and context switches when the block started, if measured.
+    start_usage: Option<rusage::ResourceUsage>,
+    /// Hardware counters of the thread when the block started, if counted.
+    #[cfg(feature = "perf-counters")]
+    start_counters: Option<counters::HardwareCounters>,
+    /// ID of the call stack the block was created on.
+    #[cfg(feature = "callstacks")]
+    stack_id: u64,
+    /// Whether profiling was enabled when the block was created, otherwise it records nothing.
+    enabled: bool,
+    /// Blocks refer to their thread's profiler, so must end on the thread they began on.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+#[cfg(feature = "perf")]
+impl ProfileBlock {
+    /// Creates a new profile block which will get dropped at the end of the current scope.
+    pub fn new(name: &'static str, byte_count: u64) -> Self {
+        Self::with_counts(name, byte_count, 0)
+    }
+
+    /// Creates a new profile block which processes `byte_count` bytes and `item_count` items.
+    pub fn with_counts(name: &'static str, byte_count: u64, item_count: u64) -> Self {
+        Self::start(None, name, byte_count, item_count)
+    }
+
+    /// Creates a new profile block at the `profile!` call site `site`, which looks up its anchor
+    /// without comparing names. Prefer the `profile!` macro.
+    #[doc(hidden)]
+    #[inline]
+    pub fn with_site(
+        site: &'static AnchorSite,
+        name: &'static str,
+        byte_count: u64,
+        item_count: u64,
+    ) -> Self {
+        Self::start(Some(site), name, byte_count, item_count)
+    }
+
+    #[inline]
+    fn start(
+        site: Option<&'static AnchorSite>,
+        name: &'static str,
+        byte_count: u64,
+        item_count: u64,
+    ) -> Self {
+        if !is_enabled() {
+            return Self::disabled(name);
+        }
+        #[cfg(feature = "callstacks")]
+        let stack_id = GLOBAL_PROFILER.with(|profiler| {
+            callstack::current(|id| !profiler.borrow_mut().known_stacks.insert(id))
+        });
+        let (name, id, index) = GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            let name = profiler.namespaced(name);
+            let index = match site {
+                Some(site) => profiler.site_anchor_index(site, name),
+                None => profiler.anchor_index(name),
+            };
+            let id = profiler.push_block(name, index);
+            let anchor = &mut profiler.anchors[index];
+            anchor.byte_count += byte_count;
+            anchor.item_count += item_count;
+            anchor.hit_count += 1;
+            anchor.active += 1;
+            (name, id, index)
+        });
+        let start_cpu_ns = MEASURE_CPU_TIME
+            .load(std::sync::atomic::Ordering::Relaxed)
+            .then(cputime::thread_cpu_time_ns);
+        let start_usage = if MEASURE_PAGE_FAULTS.load(std::sync::atomic::Ordering::Relaxed)
+            || MEASURE_CONTEXT_SWITCHES.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            rusage::resource_usage()
+        } else {
+            None
+        };
+        #[cfg(feature = "perf-counters")]
+        let start_counters = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().read_counters());
+
+        Self {
+            name,
+            id,
+            anchor: index,
+            start_cpu_ns,
+            start_allocations: memory::thread_allocations(),
+            start_usage,
+            #[cfg(feature = "perf-counters")]
+            start_counters,
+            start_tsc: Profiler::read_block_timer(),
+            #[cfg(feature = "callstacks")]
+            stack_id,
+            enabled: true,
+            _not_send: std::marker::PhantomData,
+        }
+    }
+
+    /// Returns the ID of the call stack the block was created on, if captured.
+    #[cfg_attr(feature = "callstacks", allow(clippy::unnecessary_wraps))]
+    #[cfg_attr(not(feature = "callstacks"), allow(clippy::unused_self))]
+    fn stack_id(&self) -> Option<u64> {
+        #[cfg(feature = "callstacks")]
+        return Some(self.stack_id);
+        #[cfg(not(feature = "callstacks"))]
+        None
+    }
+
+    #[cold]
+    fn disabled(name: &'static str) -> Self {
+        Self {
+            name,
+            id: 0,
+            anchor: 0,
+            start_cpu_ns: None,
+            start_allocations: None,
+            start_usage: None,
+            #[cfg(feature = "perf-counters")]
+            start_counters: None,
+            start_tsc: 0,
+            #[cfg(feature = "callstacks")]
+            stack_id: 0,
+            enabled: false,
+            _not_send: std::marker::PhantomData,
+        }
+    }
+
+    /// Adds to the byte count of this block's anchor after the block has been created, for
+    /// operations where the number of bytes is only known once they complete.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Read;
+    /// use util_lib_rs::performance::ProfileBlock;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let mut input: &[u8] = b"data";
+    /// let block = ProfileBlock::new("read", 0);
+    /// let mut buf = Vec::new();
+    /// let bytes_read = input.read_to_end(&mut buf)?;
+    /// block.add_byte_count(bytes_read as u64);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_byte_count(&self, byte_count: u64) {
+        if !self.enabled {
+            return;
+        }
+        GLOBAL_PROFILER.with(|profiler| {
+            profiler.borrow_mut().anchors[self.anchor].byte_count += byte_count;
+        });
+    }
+}
+
+/// A single loop iteration created inside a loop body by `profile_loop!`, which records its elapsed
+/// time when dropped at the end of the iteration.
+#[cfg(feature = "perf")]
+#[derive(Debug)]
+#[must_use]
+pub struct LoopIteration {
+    name: &'static str,
+    /// Timestamp counter when the iteration started, unless profiling was disabled.
+    start_tsc: Option<u64>,
+}
+
+#[cfg(feature = "perf")]
+impl LoopIteration {
+    /// Creates a new loop iteration which will get dropped at the end of the loop body.
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            start_tsc: is_enabled().then(Profiler::read_block_timer),
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+impl Drop for LoopIteration {
+    fn drop(&mut self) {
+        let Some(start_tsc) = self.start_tsc else {
+            return;
+        };
+        let elapsed = Profiler::read_block_timer().saturating_sub(start_tsc);
+        GLOBAL_PROFILER.with(|profiler| {
+            profiler
+                .borrow_mut()
+                .record_loop_iteration(self.name, elapsed);
+        });
+    }
+}
+
+/// Everything a block measured between starting and ending.
+#[cfg(feature = "perf")]
+#[derive(Debug, Copy, Clone)]
+struct BlockCost {
+    elapsed: u64,
+    cpu_ns: u64,
+    allocations: memory::ThreadAllocations,
+    counters: counters::HardwareCounters,
+    usage: rusage::ResourceUsage,
+}
+
+#[cfg(feature = "perf")]
+impl Profiler {
+    /// Charges the `cost` of a block of the anchor at index `child` to the parent anchor at index
+    /// `parent`, removing it from the parent's exclusive measurements.
+    #[cold]
+    fn charge_parent(&mut self, parent: usize, child: usize, cost: &BlockCost) {
+        self.record_call(parent, child, cost.elapsed);
+        // Wrapping is intentional: a parent's exclusive time may temporarily underflow until the
+        // parent block itself ends and adds its own elapsed time.
+        self.anchors[parent].add_exclusive(cost, u64::wrapping_sub);
+    }
+}
+
+#[cfg(feature = "perf")]
+impl ProfileBlock {
+    /// Passes the block, which ended at `end_tsc` after `elapsed` ticks, to the optional
+    /// `recorders` enabled: latency histograms, slow hits, events, call stacks, live reports and
+    /// causal experiments.
+    #[cold]
+    #[inline(never)]
+    fn record(
+        &self,
+        mut profiler: std::cell::RefMut<'_, Profiler>,
+        recorders: u8,
+        end_tsc: u64,
+        elapsed: u64,
+    ) {
+        if recorders & RECORD_HISTOGRAMS != 0 {
+            profiler.record_latency(self.anchor, elapsed);
+        }
+        if recorders & RECORD_SLOW_HITS != 0 {
+            profiler.record_slow_hit(self.anchor, elapsed);
+        }
+
+        if recorders & (RECORD_EVENTS | RECORD_TRACE) != 0 {
+            let event = flight::TraceEvent {
+                name: self.name,
+                start_tsc: self.start_tsc,
+                end_tsc,
+                depth: profiler.stack.len(),
+                stack_id: self.stack_id(),
+            };
+            if let Some(events) = &mut profiler.events {
+                events.push(event);
+            }
+            if let Some(trace) = &mut profiler.trace {
+                trace.push(event);
+            }
+        }
+
+        #[cfg(feature = "callstacks")]
+        {
+            let (hit_count, tsc_elapsed) = profiler
+                .call_stacks
+                .entry((self.anchor, self.stack_id))
+                .or_default();
+            *hit_count += 1;
+            *tsc_elapsed = tsc_elapsed.saturating_add(elapsed);
+        }
+
+        // Live reports pick up each thread's statistics between its outermost blocks.
+        if recorders & RECORD_PUBLISH != 0 && profiler.stack.is_empty() {
+            let request = PUBLISH_REQUESTS.load(std::sync::atomic::Ordering::Relaxed);
+            if request != profiler.publish_request {
+                profiler.publish_request = request;
+                profiler.publish();
+            }
+        }
+        drop(profiler);
+
+        if recorders & RECORD_CAUSAL != 0 {
+            let delay = causal::delay_for(self.name, elapsed);
+            if delay > 0 {
+                let end = Profiler::read_block_timer() + delay;
+                while Profiler::read_block_timer() < end {
+                    std::hint::spin_loop();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+impl Drop for ProfileBlock {
+    /// When the `ProfileBlock` is dropped, it will calculate the total elapsed timestamp
+    /// counter and update the matching `ProfileAnchor`.
+    fn drop(&mut self) {
+        if !self.enabled {
+            return;
+        }
+        let end_tsc = Profiler::read_block_timer();
+        // The counter may go backwards after moving between processors whose counters aren't
+        // synchronized, or after the system resumes from suspend.
+        let backwards = end_tsc < self.start_tsc;
+        let elapsed = end_tsc.saturating_sub(self.start_tsc);
+        let cpu_ns = self.start_cpu_ns.map_or(0, |start| {
+            cputime::thread_cpu_time_ns().saturating_sub(start)
+        });
+        let allocations = self
+            .start_allocations
+            .and_then(|start| Some(memory::thread_allocations()?.since(start)))
+            .unwrap_or_default();
+        let usage = self
+            .start_usage
+            .and_then(|start| Some(rusage::resource_usage()?.since(start)))
+            .unwrap_or_default();
+
+        // Blocks still held by the profiler, such as manual blocks which were never ended, are
+        // dropped along with it when the thread exits, at which point there's nothing to update.
+        let _ = GLOBAL_PROFILER.try_with(|profiler| {
+            let Ok(mut profiler) = profiler.try_borrow_mut() else {
+                return;
+            };
+
+            if backwards {
+                profiler.backwards_reads += 1;
+            }
+            #[cfg(feature = "perf-counters")]
+            let counters = self
+                .start_counters
+                .and_then(|start| Some(profiler.read_counters()?.zip(start, u64::wrapping_sub)))
+                .unwrap_or_default();
+            #[cfg(not(feature = "perf-counters"))]
+            let counters = counters::HardwareCounters::default();
+            let cost = BlockCost {
+                elapsed,
+                cpu_ns,
+                allocations,
+                counters,
+                usage,
+            };
+            if let Some(parent) = profiler.pop_block(self.id) {
+                profiler.charge_parent(parent, self.anchor, &cost);
+            }
+
+            let anchor = &mut profiler.anchors[self.anchor];
+            anchor.add_exclusive(&cost, u64::wrapping_add);
+            anchor.active = anchor.active.saturating_sub(1);
+            // Recursive blocks are already covered by the outermost block of their anchor.
+            if anchor.active == 0 {
+                anchor.tsc_elapsed_inclusive = anchor.tsc_elapsed_inclusive.saturating_add(elapsed);
+                anchor.cpu_ns_inclusive = anchor.cpu_ns_inclusive.saturating_add(cpu_ns);
+            }
+            anchor.tsc_elapsed_squares = anchor
+                .tsc_elapsed_squares
+                .saturating_add(u128::from(elapsed) * u128::from(elapsed));
+            anchor.tsc_min = anchor.tsc_min.min(elapsed);
+            anchor.tsc_max = anchor.tsc_max.max(elapsed);
+
+            let recorders =
+                profiler.recorders | RECORDERS.load(std::sync::atomic::Ordering::Relaxed);
+            if recorders != 0 || cfg!(feature = "callstacks") {
+                self.record(profiler, recorders, end_tsc, elapsed);
+            }
+        });
+    }
+}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+
+    fn expensive() {
+        black_box((0..1_000_000).fold(0, |acc, out| black_box(acc) ^ black_box(out)));
+    }
+
+    fn tfn2() {
+        profile!();
+        expensive();
+    }
+
+    fn tfn() {
+        profile!();
+        std::thread::sleep(std::time::Duration::from_millis(100));
+        for _ in 0..5 {
+            profile!("inner", 500_000);
+            tfn2();
+        }
+    }
+
+    #[test]
+    fn item_blocks() {
+        profile_begin();
+        for _ in 0..4 {
+            crate::profile_items!("titems", 250);
+            expensive();
+        }
+        let name = String::from("titems_dynamic");
+        {
+            crate::profile_items!(crate::intern::intern_static(&name), 3);
+        }
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+
+        let items = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| anchor.item_count)
+        };
+        assert_eq!(items("titems"), Some(1000));
+        assert_eq!(items("titems_dynamic"), Some(3));
+        assert!(report.to_string().contains(" 1000 items at "));
+    }
+
+    #[test]
+    fn named_counts() {
+        profile_begin();
+        for _ in 0..2 {
+            profile!("tnamed_both", bytes = 500_000, items = 1000);
+            expensive();
+        }
+        {
+            profile!("tnamed_items", items = 3, bytes = 0);
+        }
+        {
+            profile!("tnamed_bytes", bytes = 64);
+        }
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+
+        let counts = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| (anchor.byte_count, anchor.item_count))
+        };
+        assert_eq!(counts("tnamed_both"), Some((1_000_000, 2000)));
+        assert_eq!(counts("tnamed_items"), Some((0, 3)));
+        assert_eq!(counts("tnamed_bytes"), Some((64, 0)));
+    }
+
+    #[test]
+    fn deferred_byte_counts() {
+        profile_begin();
+        let mut total = 0;
+        for len in [100usize, 200] {
+            total += crate::profile_bytes!("tbytes_read", {
+                expensive();
+                len
+            });
+        }
+        let written: u64 = crate::profile_bytes!("tbytes_write", 64);
+        {
+            let block = ProfileBlock::new("tbytes_block", 8);
+            block.add_byte_count(24);
+        }
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+
+        assert_eq!((total, written), (300, 64));
+        let bytes = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| (anchor.hit_count, anchor.byte_count))
+        };
+        assert_eq!(bytes("tbytes_read"), Some((2, 300)));
+        assert_eq!(bytes("tbytes_write"), Some((1, 64)));
+        assert_eq!(bytes("tbytes_block"), Some((1, 32)));
+    }
+
+    #[test]
+    fn profile_block() {
+        profile_begin();
+
+        for _ in 0..5 {
+            tfn();
+        }
+
+        let report = profile_end();
+        assert!(report.anchors[0].name.ends_with("::tfn"));
+        assert_eq!(report.anchors[0].hit_count, 5);
+        assert!(report.to_string().contains("Total time:"));
+        let tfn = &report.anchors[0];
+        assert!(tfn.tsc_min > 0);
+        assert!(tfn.tsc_min <= tfn.tsc_mean() && tfn.tsc_mean() <= tfn.tsc_max);
+    }
+
+    #[test]
+    fn profile_snapshots() {
+        profile_begin();
+
+        tfn2();
+        let warmup = profile_snapshot("after_warmup");
+        for _ in 0..3 {
+            tfn2();
+        }
+        let steady = profile_snapshot("steady_state");
+
+        let report = GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            profiler.end_tsc = Profiler::read_block_timer();
+            profiler.report()
+        });
+        assert_eq!(warmup.anchors.len(), 1);
+        assert_eq!(warmup.anchors[0].hit_count, 1);
+        assert_eq!(report.intervals.len(), 3);
+        assert_eq!(report.intervals[0].from, "begin");
+        assert_eq!(report.intervals[0].report.anchors[0].hit_count, 1);
+        assert_eq!(report.intervals[1].to, "steady_state");
+        assert_eq!(report.intervals[1].report.anchors[0].hit_count, 3);
+        assert!(report.intervals[2].report.anchors.is_empty());
+
+        assert_eq!(steady.delta(&warmup), report.intervals[1].report);
+    }
+
+    fn never_called() {
+        profile!("never_called");
+    }
+
+    #[test]
+    fn never_hit_anchors() {
+        profile_begin();
+        register_anchors(&["registered_never_hit"]);
+        tfn2();
+        if black_box(false) {
+            never_called();
+        }
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        assert!(report.never_hit.contains(&"registered_never_hit"));
+        assert!(report.never_hit.contains(&"never_called"));
+        assert!(report
+            .anchors
+            .iter()
+            .all(|anchor| !report.never_hit.contains(&anchor.name)));
+    }
+
+    fn tgeneric<T: Default>() -> T {
+        profile!();
+        T::default()
+    }
+
+    #[test]
+    fn monomorphized_anchors() {
+        profile_begin();
+        names::set_monomorphized_names(true);
+        black_box(tgeneric::<u8>());
+        black_box(tgeneric::<u16>());
+        black_box(tgeneric::<u16>());
+        names::set_monomorphized_names(false);
 
-#[cfg(feature = "perf")]
-#[derive(Debug)]
-#[must_use]
-pub(super) struct Profiler {
-    start_tsc: u64,
-    end_tsc: u64,
-    anchors: Vec<ProfileAnchor>,
-    parent: Option<&'static str>,
-}
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let hits = |report: &ProfileReport, suffix| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name.ends_with(suffix))
+                .map(|anchor| anchor.hit_count)
+        };
+        assert_eq!(hits(&report, "::tgeneric<u8>"), Some(1));
+        assert_eq!(hits(&report, "::tgeneric<u16>"), Some(2));
+        assert!(!report
+            .never_hit
+            .iter()
+            .any(|name| name.ends_with("::tgeneric")));
 
-#[cfg(feature = "perf")]
-impl Profiler {
-    pub(super) fn begin(&mut self) {
-        self.start_tsc = Self::read_block_timer();
+        let rolled_up = report.shortened(&names::NamePolicy::new().roll_up_generics(true));
+        assert_eq!(rolled_up.anchors.len(), 1);
+        assert_eq!(hits(&rolled_up, "::tgeneric"), Some(3));
     }
 
-    #[allow(clippy::cast_precision_loss)]
-    pub(super) fn end(&mut self) {
-        self.end_tsc = Self::read_block_timer();
-        let timer_freq = Self::estimated_block_timer_freq();
-
-        let elapsed_tsc = self.end_tsc - self.start_tsc;
-        if elapsed_tsc > 0 {
-            println!(
-                "\nTotal time: {:.4}ms (timer freq {})",
-                1000.0 * elapsed_tsc as f64 / timer_freq as f64,
-                timer_freq
-            );
+    #[test]
+    fn cpu_time_blocks() {
+        profile_begin();
+        set_measure_cpu_time(true);
+        {
+            profile!("cpu_outer");
+            {
+                profile!("cpu_spin");
+                let start = std::time::Instant::now();
+                while start.elapsed() < std::time::Duration::from_millis(20) {
+                    black_box(0);
+                }
+            }
+            profile!("cpu_sleep");
+            std::thread::sleep(std::time::Duration::from_millis(30));
+        }
+        set_measure_cpu_time(false);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let anchor = |name| {
+            *report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .expect("valid anchor")
+        };
+        let (outer, spin, sleep) = (anchor("cpu_outer"), anchor("cpu_spin"), anchor("cpu_sleep"));
+        assert!(spin.cpu_ns_inclusive >= 10_000_000);
+        assert!(sleep.cpu_ns_inclusive < 15_000_000);
+        assert!(outer.cpu_ns_inclusive >= spin.cpu_ns_inclusive + sleep.cpu_ns_inclusive);
+        assert_eq!(
+            outer.cpu_ns_exclusive,
+            outer.cpu_ns_inclusive - spin.cpu_ns_inclusive - sleep.cpu_ns_inclusive
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn page_fault_blocks() {
+        profile_begin();
+        set_measure_page_faults(true);
+        {
+            profile!("faults_outer");
+            let mut pages = {
+                profile!("faults_touch");
+                let mut pages = vec![0u8; 4096 * 4096];
+                for page in pages.chunks_mut(4096) {
+                    page[0] = 1;
+                }
+                pages
+            };
+            black_box(&mut pages);
         }
+        set_measure_page_faults(false);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let touch = report
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == "faults_touch")
+            .expect("valid anchor");
+        assert!(touch.soft_page_faults >= 1024);
+        assert!(report.to_string().contains(" soft, "));
+    }
 
-        for anchor in &self.anchors {
-            if anchor.tsc_elapsed_inclusive > 0 {
-                anchor.print_time_elapsed(elapsed_tsc, timer_freq);
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn context_switch_blocks() {
+        profile_begin();
+        set_measure_context_switches(true);
+        {
+            profile!("switches_outer");
+            for _ in 0..4 {
+                profile!("switches_sleep");
+                std::thread::sleep(std::time::Duration::from_millis(1));
             }
         }
+        set_measure_context_switches(false);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let anchor = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .expect("valid anchor")
+        };
+        assert!(anchor("switches_sleep").voluntary_context_switches >= 4);
+        // The switches happen in the child blocks, so none are left for the parent.
+        assert_eq!(anchor("switches_outer").voluntary_context_switches, 0);
+        assert!(report.to_string().contains(" voluntary, "));
     }
 
-    /// Returns a conversion factor for OS timer. In the case of linux, the units are in microseconds.
-    fn get_os_timer_freq() -> u64 {
-        1_000_000
+    #[test]
+    fn event_counters() {
+        profile_begin();
+        for n in 0..10u64 {
+            if n % 3 == 0 {
+                crate::counter!("tcounter_miss");
+            }
+            crate::counter_add!("tcounter_rows", n);
+        }
+        let mut report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let count = |report: &ProfileReport, name| {
+            report
+                .event_counters
+                .iter()
+                .find(|counter| counter.name == name)
+                .map(|counter| counter.count)
+        };
+        assert_eq!(count(&report, "tcounter_miss"), Some(4));
+        assert_eq!(count(&report, "tcounter_rows"), Some(45));
+        assert!(report
+            .to_string()
+            .contains("\nCounters:\n  tcounter_miss   4\n  tcounter_rows  45\n"));
+
+        let other = report.clone();
+        report.merge(&other);
+        assert_eq!(count(&report, "tcounter_rows"), Some(90));
+        assert_eq!(count(&report.scaled(0.5), "tcounter_miss"), Some(4));
     }
 
-    fn read_os_timer() -> u64 {
-        use std::time::{SystemTime, UNIX_EPOCH};
-        let since_epoch = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("system time is earlier than Unix Epoch");
-        Self::get_os_timer_freq() * since_epoch.as_secs() + u64::from(since_epoch.subsec_micros())
+    #[test]
+    fn run_metadata() {
+        profile_begin();
+        set_run_metadata("test.input", "small");
+        set_run_metadata("test.input", "large");
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let values: Vec<&str> = report
+            .metadata
+            .iter()
+            .filter(|(key, _)| *key == "test.input")
+            .map(|(_, value)| value.as_str())
+            .collect();
+        assert_eq!(values, ["large"]);
+        assert!(report.to_string().contains("\nRun metadata:\n"));
     }
 
-    fn read_block_timer() -> u64 {
-        let mut aux = 0;
-        #[cfg(target_arch = "x86")]
-        unsafe {
-            std::arch::x86::__rdtscp(&mut aux)
+    #[test]
+    fn timer_reads() {
+        // Reads each way directly, since choosing one with `set_timer_read` would change how every
+        // other test running in parallel reads the timer.
+        let reads = [
+            TimerRead::Rdtsc,
+            TimerRead::Fenced,
+            TimerRead::Rdtscp,
+            TimerRead::Os,
+        ];
+        for read in reads {
+            let start = Profiler::read_timer(read.mode());
+            expensive();
+            assert!(Profiler::read_timer(read.mode()) > start, "{read:?}");
         }
-        #[cfg(target_arch = "x86_64")]
-        unsafe {
-            std::arch::x86_64::__rdtscp(&mut aux)
+        let mut modes: Vec<_> = reads.iter().map(|read| read.mode()).collect();
+        modes.sort_unstable();
+        modes.dedup();
+        assert_eq!(modes.len(), reads.len());
+        assert_eq!(TimerRead::default().mode(), TIMER_READ_RDTSCP);
+    }
+
+    #[test]
+    fn branch_hit_ratios() {
+        profile_begin();
+        for i in 0..10 {
+            if i % 5 == 0 {
+                profile_branch!("mod5", "zero");
+            } else {
+                profile_branch!("mod5", "nonzero");
+            }
         }
-        #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
-        compile_error!("performance profiling is not supported on this architecture")
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let branch = &report.branches[0];
+        assert_eq!(branch.name, "mod5");
+        assert_eq!(branch.arms[0].name, "zero");
+        assert_eq!(branch.arms[0].hit_count, 2);
+        assert_eq!(branch.arms[1].hit_count, 8);
+        assert!((branch.ratio(&branch.arms[1]) - 0.8).abs() < f64::EPSILON);
     }
 
-    #[allow(
-        clippy::cast_sign_loss,
-        clippy::cast_possible_truncation,
-        clippy::cast_precision_loss
-    )]
-    fn estimated_block_timer_freq() -> u64 {
-        let milliseconds_to_wait = 10;
-        let os_freq = Self::get_os_timer_freq();
+    fn looped() {
+        profile!();
+        for _ in 0..4 {
+            profile_loop!("looped_loop");
+            expensive();
+        }
+    }
 
-        let block_start = Self::read_block_timer();
-        let os_start = Self::read_os_timer();
-        let mut os_end;
-        let mut os_elapsed = 0;
-        let os_wait_time = os_freq * milliseconds_to_wait / 1000;
-        while os_elapsed < os_wait_time {
-            os_end = Self::read_os_timer();
-            os_elapsed = os_end - os_start;
+    #[test]
+    fn loop_statistics() {
+        profile_begin();
+        for _ in 0..3 {
+            looped();
         }
 
-        let block_end = Self::read_block_timer();
-        let block_elapsed = block_end - block_start;
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let stats = &report.loops[0];
+        assert_eq!(stats.name, "looped_loop");
+        assert!(stats.parent.is_some_and(|parent| parent.contains("looped")));
+        assert_eq!(stats.iteration_count, 12);
+        assert_eq!(stats.parent_hit_count, 3);
+        assert!((stats.iterations_per_hit() - 4.0).abs() < f64::EPSILON);
+        assert!(stats.tsc_min <= stats.tsc_mean() && stats.tsc_mean() <= stats.tsc_max);
+    }
 
-        if os_elapsed > 0 {
-            os_freq * block_elapsed / os_elapsed
-        } else {
-            0
+    fn generic_step() {
+        profile!("step");
+    }
+
+    #[test]
+    fn anchor_sites() {
+        profile_begin();
+        for name in ["tsite_a", "tsite_b", "tsite_a", "tsite_a"] {
+            profile!(crate::intern::intern_static(name));
+            {
+                let _ns = profile_namespace("tsite_ns");
+                profile!("tsite_literal");
+            }
+            profile!("tsite_literal");
         }
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let hits = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| anchor.hit_count)
+        };
+        assert_eq!(hits("tsite_a"), Some(3));
+        assert_eq!(hits("tsite_b"), Some(1));
+        assert_eq!(hits("tsite_literal"), Some(4));
+        assert_eq!(hits("tsite_ns::tsite_literal"), Some(4));
     }
-}
 
-#[cfg(feature = "perf")]
-#[derive(Debug, Default, Copy, Clone)]
-#[must_use]
-struct ProfileAnchor {
-    name: &'static str,
-    hit_count: u64,
-    byte_count: u64,
-    tsc_elapsed_exclusive: u64,
-    tsc_elapsed_inclusive: u64,
-}
+    #[test]
+    fn namespaced_anchors() {
+        profile_begin();
 
-#[cfg(feature = "perf")]
-impl ProfileAnchor {
-    #[allow(clippy::cast_precision_loss)]
-    fn print_time_elapsed(&self, elapsed_tsc: u64, timer_freq: u64) {
-        let percent = 100.0 * (self.tsc_elapsed_exclusive as f64 / elapsed_tsc as f64);
-        eprint!(
-            "  {}[{}]: {} ({percent:.2}%",
-            self.name, self.hit_count, self.tsc_elapsed_exclusive
+        generic_step();
+        profile_push_namespace("render");
+        generic_step();
+        {
+            let _ui = profile_namespace("ui");
+            generic_step();
+            generic_step();
+        }
+        generic_step();
+        profile_pop_namespace();
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let hits: Vec<_> = report
+            .anchors
+            .iter()
+            .filter(|anchor| anchor.name.ends_with("step"))
+            .map(|anchor| (anchor.name, anchor.hit_count))
+            .collect();
+        assert_eq!(
+            hits,
+            [("step", 1), ("render::step", 2), ("render::ui::step", 2)]
         );
-        if self.tsc_elapsed_inclusive != self.tsc_elapsed_exclusive {
-            let percent_with_children =
-                100.0 * (self.tsc_elapsed_inclusive as f64 / elapsed_tsc as f64);
-            eprint!(", {percent_with_children:.2}% w/children");
+    }
+
+    #[test]
+    fn namespaced_never_hit_anchors() {
+        profile_begin();
+        register_anchors(&["ns_hit", "ns_missed"]);
+        {
+            let _ns = profile_namespace("ns_never_hit");
+            profile!("ns_hit");
         }
-        eprint!(")");
 
-        if self.byte_count > 0 {
-            const MB: f64 = 1024.0 * 1024.0;
-            const GB: f64 = MB * 1024.0;
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        assert!(report
+            .anchors
+            .iter()
+            .any(|anchor| anchor.name == "ns_never_hit::ns_hit"));
+        assert!(!report.never_hit.contains(&"ns_hit"));
+        // Which namespaces a site would have run in is unknown, so it's listed without one.
+        assert!(report.never_hit.contains(&"ns_missed"));
+        assert!(!report.never_hit.contains(&"ns_never_hit::ns_missed"));
+    }
+
+    #[test]
+    fn thread_info() {
+        let (report, id) = std::thread::Builder::new()
+            .name("thread_info_worker".to_string())
+            .spawn(|| {
+                profile_begin();
+                {
+                    profile!("thread_info_block");
+                }
+                (profile_end(), std::thread::current().id())
+            })
+            .expect("valid thread")
+            .join()
+            .expect("profiled thread");
+        let thread = report.thread.expect("thread info");
+        assert_eq!((thread.name, thread.id), ("thread_info_worker", id));
+        assert!(report
+            .to_string()
+            .contains(&format!(" on thread thread_info_worker ({id:?})\n")));
 
-            let seconds = self.tsc_elapsed_exclusive as f64 / timer_freq as f64;
-            let bytes_per_second = self.byte_count as f64 / seconds;
-            let megabytes = self.byte_count as f64 / MB;
-            let gigabytes_per_second = bytes_per_second / GB;
+        let report = std::thread::spawn(|| {
+            profile_begin();
+            profile_set_thread_name("thread_info_renamed");
+            profile_end()
+        })
+        .join()
+        .expect("profiled thread");
+        assert_eq!(
+            report.thread.map(|thread| thread.name),
+            Some("thread_info_renamed")
+        );
+    }
 
-            eprint!("  {megabytes:.3}MB at {gigabytes_per_second:.2}GB/s");
-        }
+    #[test]
+    fn published_while_running() {
+        let (published_tx, published_rx) = std::sync::mpsc::channel();
+        let (done_tx, done_rx) = std::sync::mpsc::channel::<()>();
+        let worker = std::thread::Builder::new()
+            .name("published_while_running".to_string())
+            .spawn(move || {
+                for _ in 0..3 {
+                    generic_step();
+                }
+                profile_publish();
+                published_tx.send(()).expect("valid send");
+                while done_rx.try_recv().is_err() {
+                    generic_step();
+                }
+            })
+            .expect("valid thread");
+
+        published_rx.recv().expect("valid recv");
+        let snapshot = published_snapshots()
+            .into_iter()
+            .find(|snapshot| snapshot.name == "published_while_running")
+            .expect("published snapshot");
+        done_tx.send(()).expect("valid send");
+        worker.join().expect("worker thread");
 
-        eprintln!();
+        assert_eq!(snapshot.anchors.len(), 1);
+        assert_eq!(snapshot.anchors[0].hit_count, 3);
     }
-}
 
-/// Profile block is created inside each function scope where `profile!()` is called, keeping
-/// track of it's parent (if any), byte count, and previous elapsed timestamp counter
-/// (inclusive) in order to add up repeat calls to the same block.
-#[cfg(feature = "perf")]
-#[derive(Debug)]
-#[must_use]
-pub struct ProfileBlock {
-    name: &'static str,
-    parent: Option<&'static str>,
-    prev_tsc_elapsed_inclusive: u64,
-    start_tsc: u64,
-}
+    #[test]
+    fn sampled_anchors() {
+        let spin = |duration| {
+            let start = std::time::Instant::now();
+            while start.elapsed() < duration {
+                black_box(0);
+            }
+        };
+        let sampler = sampling::Sampler::start(1000).expect("sampler thread");
+        let report = std::thread::spawn(move || {
+            profile_begin();
+            {
+                profile!("sampled");
+                spin(std::time::Duration::from_millis(50));
+            }
+            spin(std::time::Duration::from_millis(50));
+            GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end())
+        })
+        .join()
+        .expect("sampled thread");
+        drop(sampler);
 
-#[cfg(feature = "perf")]
-impl ProfileBlock {
-    /// Creates a new profile block which will get dropped at the end of the current scope.
-    pub fn new(name: &'static str, byte_count: u64) -> Self {
-        let (parent, prev_tsc_elapsed_inclusive) = GLOBAL_PROFILER.with(|profiler| {
-            let mut profiler = profiler.borrow_mut();
-            let parent = profiler.parent;
-            profiler.parent = Some(name);
-            let tsc_elapsed_inclusive = if let Some(anchor) = profiler
-                .anchors
-                .iter_mut()
-                .find(|anchor| anchor.name == name)
+        let count = |name| {
+            report
+                .samples
+                .iter()
+                .find(|sample| sample.name == name)
+                .map_or(0, |sample| sample.sample_count)
+        };
+        assert!(count("sampled") > 0);
+        assert!(count(sampling::UNINSTRUMENTED) > 0);
+    }
+
+    #[test]
+    fn captured_backtraces() {
+        set_capture_backtraces(true);
+        let report = std::thread::spawn(|| {
+            profile_begin();
             {
-                anchor.byte_count += byte_count;
-                anchor.hit_count += 1;
-                anchor.tsc_elapsed_inclusive
-            } else {
-                profiler.anchors.push(ProfileAnchor {
-                    name,
-                    byte_count,
-                    hit_count: 1,
-                    ..Default::default()
-                });
-                0
-            };
-            (parent, tsc_elapsed_inclusive)
-        });
+                profile!("located");
+            }
+            GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end())
+        })
+        .join()
+        .expect("profiled thread");
+        set_capture_backtraces(false);
 
-        Self {
-            name,
-            parent,
-            prev_tsc_elapsed_inclusive,
-            start_tsc: Profiler::read_block_timer(),
+        let located = report
+            .backtraces
+            .iter()
+            .find(|backtrace| backtrace.name == "located")
+            .expect("captured backtrace");
+        assert!(located.backtrace.contains("captured_backtraces"));
+        assert!(report.to_string().contains("First hits:\n  located:\n"));
+    }
+
+    #[test]
+    fn captured_slow_hits() {
+        let report = std::thread::spawn(|| {
+            profile_begin();
+            // Capture every new maximum on this thread only, rather than through the global
+            // threshold other tests would pick up.
+            GLOBAL_PROFILER.with(|profiler| {
+                let mut profiler = profiler.borrow_mut();
+                profiler.slow_hit_tsc = Some(0);
+                profiler.update_recorders();
+            });
+            for millis in [2, 1, 4, 3] {
+                profile!("slow");
+                std::thread::sleep(std::time::Duration::from_millis(millis));
+            }
+            GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end())
+        })
+        .join()
+        .expect("profiled thread");
+
+        let [slow] = &report.slow_hits[..] else {
+            panic!("one slow hit per anchor: {:?}", report.slow_hits);
+        };
+        assert_eq!(slow.name, "slow");
+        assert_eq!(slow.tsc_elapsed, report.anchors[0].tsc_max);
+        assert!(slow.tsc_elapsed >= report.timer_freq * 4 / 1000);
+        assert!(slow.backtrace.contains("captured_slow_hits"));
+        assert!(report.to_string().contains("Slowest hits:\n  slow ("));
+        let filtered = report.filtered(&filter::AnchorFilter::new(&[], &["slow"]).expect("valid"));
+        assert!(filtered.slow_hits.is_empty());
+    }
+
+    #[cfg(all(feature = "callstacks", target_os = "linux"))]
+    #[test]
+    fn call_stack_ids() {
+        #[inline(never)]
+        fn shared() {
+            profile!("shared");
+        }
+        #[inline(never)]
+        fn first_path() {
+            shared();
         }
+        #[inline(never)]
+        fn second_path() {
+            shared();
+        }
+
+        let report = std::thread::spawn(|| {
+            profile_begin();
+            for _ in 0..3 {
+                first_path();
+                second_path();
+            }
+            GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end())
+        })
+        .join()
+        .expect("profiled thread");
+
+        let paths: Vec<_> = report
+            .call_stacks
+            .iter()
+            .filter(|stats| stats.name == "shared")
+            .collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|stats| stats.hit_count == 3));
+        assert!(callstack::frames(paths[0].stack_id).is_some_and(|frames| !frames.is_empty()));
     }
-}
 
-#[cfg(feature = "perf")]
-impl Drop for ProfileBlock {
-    /// When the `ProfileBlock` is dropped, it will calculate the total elapsed timestamp
-    /// counter and update the matching `ProfileAnchor`.
-    fn drop(&mut self) {
-        let elapsed = Profiler::read_block_timer() - self.start_tsc;
+    #[cfg(all(feature = "callstacks", target_os = "linux"))]
+    #[test]
+    fn exported_stack_ids() {
+        #[inline(never)]
+        fn shared() {
+            profile!("shared");
+        }
+        #[inline(never)]
+        fn first_path() {
+            shared();
+        }
+        #[inline(never)]
+        fn second_path() {
+            shared();
+        }
 
-        GLOBAL_PROFILER.with(|profiler| {
-            let mut profiler = profiler.borrow_mut();
-            profiler.parent = self.parent;
-
-            if let Some(parent) = self.parent {
-                let parent = profiler
-                    .anchors
-                    .iter_mut()
-                    .find(|anchor| anchor.name == parent)
-                    .expect("valid parent anchor");
-                parent.tsc_elapsed_exclusive = parent.tsc_elapsed_exclusive.saturating_sub(elapsed);
+        let (events, start_tsc, timer_freq) = std::thread::spawn(|| {
+            profile_begin();
+            GLOBAL_PROFILER.with(|profiler| {
+                let mut profiler = profiler.borrow_mut();
+                profiler.trace = Some(Vec::new());
+                profiler.update_recorders();
+            });
+            first_path();
+            second_path();
+            GLOBAL_PROFILER.with(|profiler| {
+                let mut profiler = profiler.borrow_mut();
+                let events = profiler.trace.take().unwrap_or_default();
+                (events, profiler.start_tsc, profiler.end().timer_freq)
+            })
+        })
+        .join()
+        .expect("profiled thread");
+
+        let mut output = Vec::new();
+        chrome::write_chrome_trace(&events, "main", start_tsc, timer_freq, &mut output)
+            .expect("valid write");
+        let trace = crate::json::parse(std::str::from_utf8(&output).expect("valid utf-8"))
+            .expect("valid JSON");
+        let stack_ids: Vec<_> = (1..=2)
+            .map(|i| {
+                assert_eq!(trace["traceEvents"][i]["name"].as_str(), Some("shared"));
+                trace["traceEvents"][i]["args"]["stack_id"].as_str()
+            })
+            .collect();
+        assert!(stack_ids.iter().all(Option::is_some));
+        assert_ne!(stack_ids[0], stack_ids[1]);
+    }
+
+    #[test]
+    fn recorded_calls() {
+        let report = std::thread::spawn(|| {
+            profile_begin();
+            {
+                profile!("caller");
+                for _ in 0..2 {
+                    profile!("callee");
+                }
+                let span = span_begin("span");
+                span_end(span);
             }
+            GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end())
+        })
+        .join()
+        .expect("profiled thread");
+
+        let calls: Vec<_> = report
+            .calls
+            .iter()
+            .map(|call| (call.caller, call.callee, call.call_count))
+            .collect();
+        assert_eq!(calls, [("caller", "callee", 2), ("caller", "span", 1)]);
+    }
+
+    #[test]
+    fn timer_freq_discrepancies() {
+        let freq = 3_000_000_000;
+        // One second at the calibrated frequency, and barely within tolerance.
+        assert_eq!(
+            timer_freq_warning(freq, 1_000_000, 1_000_000, freq, (0, 0)),
+            None
+        );
+        assert_eq!(
+            timer_freq_warning(freq * 104 / 100, 1_000_000, 1_000_000, freq, (0, 0)),
+            None
+        );
+        // Too short to judge.
+        assert_eq!(
+            timer_freq_warning(freq, 1_000, 1_000_000, freq * 2, (0, 0)),
+            None
+        );
 
-            let anchor = profiler
+        let warning = timer_freq_warning(freq / 2, 1_000_000, 1_000_000, freq, (2, 5))
+            .expect("discrepancy warning");
+        assert!(warning.starts_with("timer frequency on this thread was 1500.0MHz, 50.0% off"));
+        assert!(warning.ends_with("(moved from CPU 2 to 5)"));
+    }
+
+    fn trecurse(depth: u32) {
+        profile!("trecurse");
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        if depth > 0 {
+            tping(depth - 1);
+        }
+    }
+
+    fn tping(depth: u32) {
+        profile!("tping");
+        trecurse(depth);
+    }
+
+    #[test]
+    fn recursive_blocks() {
+        profile_begin();
+        {
+            profile!("trecurse_outer");
+            trecurse(3);
+        }
+        let report = GLOBAL_PROFILER.with(|p| p.borrow_mut().end());
+        let find = |name| {
+            report
                 .anchors
-                .iter_mut()
-                .find(|anchor| anchor.name == self.name)
-                .expect("valid anchor");
-            anchor.tsc_elapsed_exclusive += elapsed;
-            anchor.tsc_elapsed_inclusive = self.prev_tsc_elapsed_inclusive + elapsed;
-        });
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .expect("valid anchor")
+        };
+        let (outer, recurse, ping) = (find("trecurse_outer"), find("trecurse"), find("tping"));
+        assert_eq!((recurse.hit_count, ping.hit_count), (4, 3));
+        // Only the outermost blocks count, so neither exceeds the block they're nested in.
+        assert!(recurse.tsc_elapsed_inclusive <= outer.tsc_elapsed_inclusive);
+        assert!(ping.tsc_elapsed_inclusive < recurse.tsc_elapsed_inclusive);
+        assert_eq!(
+            outer.tsc_elapsed_inclusive,
+            outer.tsc_elapsed_exclusive + recurse.tsc_elapsed_inclusive
+        );
+        GLOBAL_PROFILER.with(|p| assert!(p.borrow().anchors.iter().all(|a| a.active == 0)));
     }
-}
 
-#[cfg(all(test, feature = "perf"))]
-mod tests {
-    use super::*;
-    use std::hint::black_box;
+    #[test]
+    fn saved_state() {
+        let dir = crate::fs::TempDir::new().expect("created dir");
+        let path = dir.path().join("state.dump");
+        let save_path = path.clone();
+        let saved = std::thread::spawn(move || {
+            profile_begin();
+            for _ in 0..3 {
+                profile!("tstate");
+            }
+            profiler_state_save(&save_path).expect("saved state");
+            GLOBAL_PROFILER.with(|p| p.borrow_mut().end())
+        })
+        .join()
+        .unwrap();
+        let state = dump::ProfileDump::load(&path).expect("saved state").report;
+        let restored = std::thread::spawn(move || {
+            profile_begin();
+            profiler_state_restore(&path).expect("restored state");
+            for _ in 0..2 {
+                profile!("tstate");
+            }
+            GLOBAL_PROFILER.with(|p| p.borrow_mut().end())
+        })
+        .join()
+        .unwrap();
 
-    fn expensive() {
-        black_box((0..1_000_000).fold(0, |acc, out| black_box(acc) ^ black_box(out)));
+        assert_eq!(restored.anchors.len(), 1);
+        assert_eq!(restored.anchors[0].hit_count, 5);
+        let tsc = u128::from(restored.anchors[0].tsc_elapsed_inclusive);
+        // At least the sum of squares of 5 equal hits, at most the square of their sum.
+        assert!(restored.anchors[0].tsc_elapsed_squares * 5 >= tsc * tsc);
+        assert!(restored.anchors[0].tsc_elapsed_squares <= tsc * tsc);
+        assert!(
+            restored.anchors[0].tsc_elapsed_inclusive >= saved.anchors[0].tsc_elapsed_inclusive
+        );
+        assert!(state.elapsed_tsc > 0);
+        assert!(restored.elapsed_tsc >= state.elapsed_tsc);
+        assert!(profiler_state_restore(dir.path().join("missing.dump")).is_err());
+        // The temporary file the state was written to was renamed over it.
+        assert_eq!(std::fs::read_dir(dir.path()).expect("valid dir").count(), 1);
     }
 
-    fn tfn2() {
-        profile!();
-        expensive();
+    #[test]
+    fn chrome_trace_export() {
+        let dir = crate::fs::TempDir::new().expect("created dir");
+        let path = dir.path().join("trace.json");
+        let trace_path = path.clone();
+        std::thread::Builder::new()
+            .name("tracer".into())
+            .spawn(move || {
+                chrome::set_record_trace_events(true);
+                profile_begin();
+                chrome::set_record_trace_events(false);
+                {
+                    profile!("ttrace_outer");
+                    profile!("ttrace_inner");
+                }
+                profile_end_and_export_chrome_trace(&trace_path).expect("exported trace");
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        let trace = std::fs::read_to_string(&path).expect("saved trace");
+
+        assert!(trace.starts_with(r#"{"displayTimeUnit":"ns","traceEvents":["#));
+        assert!(trace.contains(r#""args":{"name":"tracer"}"#));
+        let outer = trace.find(r#""name":"ttrace_outer","ph":"X""#);
+        let inner = trace.find(r#""name":"ttrace_inner","ph":"X""#);
+        assert!(outer.is_some() && inner.is_some() && outer < inner);
     }
 
-    fn tfn() {
-        profile!();
-        std::thread::sleep(std::time::Duration::from_millis(100));
-        for _ in 0..5 {
-            profile!("inner", 500_000);
-            tfn2();
+    #[test]
+    fn csv_export() {
+        let dir = crate::fs::TempDir::new().expect("created dir");
+        let path = dir.path().join("report.csv");
+        profile_begin();
+        {
+            profile!("tcsv_outer", bytes = 64);
+            profile!("tcsv_inner");
         }
+        profile_end_and_write_csv(&path).expect("exported csv");
+        let csv = std::fs::read_to_string(&path).expect("saved csv");
+
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("name,hits,exclusive_tsc,inclusive_tsc,bytes,percent")
+        );
+        let outer = lines
+            .find(|line| line.starts_with("tcsv_outer,"))
+            .expect("valid row");
+        let fields: Vec<_> = outer.split(',').collect();
+        assert_eq!((fields.len(), fields[1], fields[4]), (6, "1", "64"));
     }
 
     #[test]
-    fn profile_block() {
+    fn baseline_comparison() {
+        let dir = crate::fs::TempDir::new().expect("created dir");
+        let path = dir.path().join("profile.baseline");
         profile_begin();
+        {
+            profile!("tbaseline_kept");
+            profile!("tbaseline_removed");
+        }
+        profile_save_baseline(&path).expect("saved baseline");
+        profile_begin();
+        for _ in 0..2 {
+            profile!("tbaseline_kept");
+        }
+        let comparison = profile_end_and_compare(&path);
+        let _ = std::fs::remove_file(&path);
 
-        for _ in 0..5 {
-            tfn();
+        let comparison = comparison.expect("loaded baseline");
+        let kept = comparison
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == "tbaseline_kept")
+            .expect("valid anchor");
+        assert_eq!(kept.baseline.map(|baseline| baseline.hit_count), Some(1));
+        assert_eq!(kept.current.map(|current| current.hit_count), Some(3));
+        assert!(profile_end_and_compare(&path).is_err());
+    }
+
+    #[test]
+    fn time_discontinuities() {
+        profile_begin();
+        {
+            let mut block = ProfileBlock::new("tbackwards", 0);
+            block.start_tsc = u64::MAX;
         }
+        let report = GLOBAL_PROFILER.with(|p| p.borrow_mut().end());
+        let anchor = report
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == "tbackwards")
+            .expect("backwards anchor");
+        assert_eq!(anchor.hit_count, 1);
+        assert_eq!(anchor.tsc_elapsed_inclusive, 0);
+        assert!(report
+            .warnings
+            .iter()
+            .any(|warning| warning.starts_with("timestamp counter went backwards in 1 blocks")));
+
+        let second = 1_000_000_000;
+        assert_eq!(suspend_warning((0, 0), (5 * second, second)), None);
+        assert_eq!(
+            suspend_warning((second, second), (3 * second, 2 * second)),
+            Some(
+                "system was suspended for 1.0s while profiling, so blocks spanning it may be \
+                 inaccurate"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            suspend_warning((second, second), (2 * second, 2 * second)),
+            None
+        );
+    }
 
-        profile_end();
+    #[test]
+    fn generated_names() {
+        profile_begin();
+        tfn2();
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        assert_eq!(
+            report.anchors[0].name,
+            "util_lib_rs::performance::tests::tfn2"
+        );
     }
 }
@@ -0,0 +1,139 @@
+//! Pinning threads to processor cores.
+//!
+//! A thread which migrates between cores mid-measurement loses its warm caches, and its timestamp
+//! counter reads may come from counters which aren't synchronized. Benchmarks can pin themselves
+//! with [`pin_to_core`] before calling [`profile_begin`](super::profile_begin), or with
+//! [`pin_current_thread_to_isolated_core`] to also keep other processes off the core.
+
+use std::io;
+
+/// File listing the cores isolated from the scheduler with the `isolcpus` boot parameter.
+#[cfg(target_os = "linux")]
+const ISOLATED_CORES: &str = "/sys/devices/system/cpu/isolated";
+
+/// Restrict the calling thread to run only on core `core`. Only supported on Linux and Windows.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{pin_to_core, profile_begin};
+///
+/// # fn main() -> std::io::Result<()> {
+/// pin_to_core(2)?;
+/// profile_begin();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the core doesn't exist, the thread isn't allowed to run on it, or the
+/// platform doesn't support pinning.
+pub fn pin_to_core(core: usize) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::raw::{c_int, c_ulong};
+
+        const MASK_BITS: usize = c_ulong::BITS as usize;
+
+        extern "C" {
+            fn sched_setaffinity(pid: c_int, size: usize, mask: *const c_ulong) -> c_int;
+        }
+
+        // The size of glibc's `cpu_set_t`, which is the most cores the kernel is built for.
+        let mut mask = [0 as c_ulong; 1024 / MASK_BITS];
+        let word = mask
+            .get_mut(core / MASK_BITS)
+            .ok_or_else(|| invalid_core(core))?;
+        *word |= 1 << (core % MASK_BITS);
+        // SAFETY: `mask` is a valid CPU set of the size passed for the duration of the call, and
+        // PID 0 is the calling thread.
+        if unsafe { sched_setaffinity(0, size_of_val(&mask), mask.as_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::ffi::c_void;
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentThread() -> *mut c_void;
+            fn SetThreadAffinityMask(thread: *mut c_void, mask: usize) -> usize;
+        }
+
+        let mask = u32::try_from(core)
+            .ok()
+            .and_then(|core| 1usize.checked_shl(core))
+            .ok_or_else(|| invalid_core(core))?;
+        // SAFETY: The current thread handle is always valid and needs no closing.
+        if unsafe { SetThreadAffinityMask(GetCurrentThread(), mask) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        let _ = core;
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "pinning threads to cores is only supported on Linux and Windows",
+        ))
+    }
+}
+
+/// Pin the calling thread with [`pin_to_core`] to the first core isolated from the scheduler with
+/// the Linux `isolcpus` boot parameter, returning the core. Only supported on Linux.
+///
+/// # Errors
+///
+/// Returns an error if no core is isolated or pinning fails.
+pub fn pin_current_thread_to_isolated_core() -> io::Result<usize> {
+    #[cfg(target_os = "linux")]
+    {
+        let cores = crate::sys::parse_core_list(&std::fs::read_to_string(ISOLATED_CORES)?);
+        let &core = cores.first().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                "no cores are isolated, boot with isolcpus= to isolate some",
+            )
+        })?;
+        pin_to_core(core)?;
+        Ok(core)
+    }
+    #[cfg(not(target_os = "linux"))]
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "isolated cores are only supported on Linux",
+    ))
+}
+
+#[cfg_attr(not(any(target_os = "linux", target_os = "windows")), allow(dead_code))]
+fn invalid_core(core: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("core {core} is out of range"),
+    )
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_threads() {
+        // Containers may only allow some cores, so pin a fresh thread to the first which works.
+        let pinned = std::thread::spawn(|| {
+            let cores = std::thread::available_parallelism().map_or(1, usize::from);
+            (0..cores.max(64)).find(|&core| pin_to_core(core).is_ok())
+        })
+        .join()
+        .expect("valid thread");
+        assert!(pinned.is_some());
+        assert_eq!(
+            pin_to_core(4096).map_err(|err| err.kind()),
+            Err(io::ErrorKind::InvalidInput)
+        );
+    }
+}
@@ -0,0 +1,267 @@
+//! Memory bandwidth tests.
+//!
+//! The throughput the profiler reports for an anchor means more next to what the machine can
+//! achieve. A [`BandwidthTest`] reads, writes or copies buffers of increasing size with a
+//! [`RepetitionTester`], so the fastest bandwidth of each size shows where the working set stops
+//! fitting in each cache level.
+
+use super::reptest::{RepetitionResults, RepetitionTester};
+use std::{fmt, time::Duration};
+
+/// Size of the words each test reads or writes.
+const WORD: usize = std::mem::size_of::<u64>();
+
+/// How a [`BandwidthTest`] accesses its buffers.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Access {
+    /// Read every strided word of one buffer.
+    Read,
+    /// Write every strided word of one buffer.
+    Write,
+    /// Copy every strided word of one buffer to another, counting the bytes both read and written.
+    Copy,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Read => "Read",
+            Self::Write => "Write",
+            Self::Copy => "Copy",
+        })
+    }
+}
+
+/// Measures the memory bandwidth achievable at a range of buffer sizes.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::performance::bandwidth::{Access, BandwidthTest};
+///
+/// let results = BandwidthTest::new()
+///     .sizes([16 << 10, 1 << 20])
+///     .window(Duration::from_millis(10))
+///     .run(Access::Read);
+/// println!("{results}");
+/// assert_eq!(results.points.len(), 2);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct BandwidthTest {
+    sizes: Vec<usize>,
+    stride: usize,
+    window: Duration,
+}
+
+impl Default for BandwidthTest {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BandwidthTest {
+    /// Creates a test of every power of two size from 4KiB to 256MiB, accessing every word, which
+    /// stops each size once a second passes without a new fastest run.
+    pub fn new() -> Self {
+        Self {
+            sizes: (12..=28).map(|bits| 1 << bits).collect(),
+            stride: WORD,
+            window: Duration::from_secs(1),
+        }
+    }
+
+    /// Sets the buffer sizes to test, in bytes. Sizes are rounded down to whole words.
+    pub fn sizes(mut self, sizes: impl IntoIterator<Item = usize>) -> Self {
+        self.sizes = sizes.into_iter().collect();
+        self
+    }
+
+    /// Sets the buffer sizes to half of each cache level [detected](crate::sys::cpu_info), which
+    /// fit in it, and four times the outermost, which fits in none. Keeps the sizes if no cache
+    /// sizes are known.
+    pub fn cache_sizes(self) -> Self {
+        let caches: Vec<usize> = crate::sys::cpu_info()
+            .caches()
+            .map(|(_, size)| size)
+            .collect();
+        match caches.last() {
+            Some(&outermost) => {
+                let sizes: Vec<usize> = caches.iter().map(|size| size / 2).collect();
+                self.sizes(sizes.into_iter().chain([outermost * 4]))
+            }
+            None => self,
+        }
+    }
+
+    /// Sets the distance in bytes between the words accessed, rounded up to a whole word. Only
+    /// accessed words count towards the bandwidth, so strides of a cache line or more show the cost
+    /// of fetching a line for each word.
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = stride.max(1).next_multiple_of(WORD);
+        self
+    }
+
+    /// Sets how long each size runs without a new fastest run before moving on to the next.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Runs the test of every size with the `access` pattern.
+    pub fn run(&self, access: Access) -> BandwidthResults {
+        let step = self.stride / WORD;
+        let points = self
+            .sizes
+            .iter()
+            .map(|&size| {
+                let words = (size / WORD).max(1);
+                let mut source = vec![1u64; words];
+                let mut destination = vec![0u64; if access == Access::Copy { words } else { 0 }];
+                let accessed = words.div_ceil(step) * WORD;
+                let byte_count = if access == Access::Copy {
+                    2 * accessed
+                } else {
+                    accessed
+                };
+                let results = RepetitionTester::new(self.window)
+                    .bytes(byte_count as u64)
+                    .run(|| match access {
+                        Access::Read => {
+                            let sum = source
+                                .iter()
+                                .step_by(step)
+                                .fold(0u64, |sum, &word| sum.wrapping_add(word));
+                            std::hint::black_box(sum);
+                        }
+                        Access::Write => {
+                            for word in source.iter_mut().step_by(step) {
+                                *word = std::hint::black_box(2);
+                            }
+                            std::hint::black_box(&mut source);
+                        }
+                        Access::Copy => {
+                            for (to, from) in destination.iter_mut().zip(&source).step_by(step) {
+                                *to = *from;
+                            }
+                            std::hint::black_box(&mut destination);
+                        }
+                    });
+                BandwidthPoint {
+                    size: words * WORD,
+                    results,
+                }
+            })
+            .collect();
+        BandwidthResults {
+            access,
+            stride: self.stride,
+            points,
+        }
+    }
+}
+
+/// The fastest bandwidth of one buffer size.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct BandwidthPoint {
+    /// Size of the buffer, in bytes.
+    pub size: usize,
+    /// Times of every run over the buffer.
+    pub results: RepetitionResults,
+}
+
+impl BandwidthPoint {
+    /// Bytes accessed per second by the fastest run.
+    #[must_use]
+    pub fn bandwidth(&self) -> f64 {
+        self.results.max_bandwidth()
+    }
+}
+
+/// The bandwidth of every buffer size of a [`BandwidthTest`].
+#[derive(Debug, Clone, PartialEq)]
+#[must_use]
+pub struct BandwidthResults {
+    /// How the buffers were accessed.
+    pub access: Access,
+    /// Distance between the words accessed, in bytes.
+    pub stride: usize,
+    /// Results of each size, in the order tested.
+    pub points: Vec<BandwidthPoint>,
+}
+
+/// Formats a size in bytes with the largest binary unit it's a whole multiple of.
+fn fmt_size(size: usize) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = size;
+    let mut unit = 0;
+    while unit + 1 < UNITS.len() && size >= 1024 && size.is_multiple_of(1024) {
+        size /= 1024;
+        unit += 1;
+    }
+    format!("{size}{}", UNITS[unit])
+}
+
+impl fmt::Display for BandwidthResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+        writeln!(
+            f,
+            "\n{} bandwidth, stride {} bytes:",
+            self.access, self.stride
+        )?;
+        for point in &self.points {
+            writeln!(
+                f,
+                "  {}: {:.3}GB/s",
+                fmt_size(point.size),
+                point.bandwidth() / GB
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bandwidth_test() {
+        let test = BandwidthTest::new()
+            .sizes([4096, 100, 64 << 10])
+            .stride(60)
+            .window(Duration::from_millis(2));
+        assert_eq!(test.stride, 64);
+        for access in [Access::Read, Access::Write, Access::Copy] {
+            let results = test.run(access);
+            let sizes: Vec<_> = results.points.iter().map(|point| point.size).collect();
+            assert_eq!(sizes, [4096, 96, 64 << 10]);
+            let factor = if access == Access::Copy { 2 } else { 1 };
+            assert_eq!(results.points[0].results.byte_count, factor * 64 * 8);
+            assert_eq!(results.points[1].results.byte_count, factor * 2 * 8);
+            assert!(results.points.iter().all(|point| point.bandwidth() > 0.0));
+
+            let printed = results.to_string();
+            assert!(printed.starts_with(&format!("\n{access} bandwidth, stride 64 bytes:\n")));
+            assert!(printed.contains("\n  4KiB: "));
+            assert!(printed.contains("\n  96B: "));
+            assert!(printed.ends_with("GB/s\n"));
+        }
+        assert_eq!(fmt_size(256 << 20), "256MiB");
+
+        let caches: Vec<_> = crate::sys::cpu_info().caches().collect();
+        let sizes = BandwidthTest::new().cache_sizes().sizes;
+        if let Some(&(_, outermost)) = caches.last() {
+            assert_eq!(sizes.len(), caches.len() + 1);
+            assert_eq!(sizes[0], caches[0].1 / 2);
+            assert_eq!(sizes.last(), Some(&(outermost * 4)));
+        } else {
+            assert_eq!(sizes, BandwidthTest::new().sizes);
+        }
+    }
+}
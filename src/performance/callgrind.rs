@@ -0,0 +1,148 @@
+//! Callgrind-format output.
+//!
+//! Writes the exclusive and inclusive costs of a [`ProfileReport`] in the
+//! [callgrind format](https://valgrind.org/docs/manual/cl-format.html), so the call relationships
+//! between anchors can be navigated in `KCachegrind` or `QCacheGrind`. Costs are in timestamp
+//! counter ticks, and every anchor is reported as a function without file or line information.
+
+use super::{ProfileReport, ReportExporter};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Writes `report` to `writer` in callgrind format.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{callgrind::write_callgrind, AnchorStats, ProfileReport};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let report = ProfileReport {
+///     elapsed_tsc: 100,
+///     anchors: vec![AnchorStats { name: "main", hit_count: 1, ..AnchorStats::default() }],
+///     ..ProfileReport::default()
+/// };
+/// let mut output = Vec::new();
+/// write_callgrind(&report, &mut output)?;
+/// assert!(String::from_utf8_lossy(&output).contains("fn=(1) main\n"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_callgrind(report: &ProfileReport, mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "# callgrind format")?;
+    writeln!(writer, "version: 1")?;
+    writeln!(writer, "creator: util_lib_rs")?;
+    writeln!(writer, "positions: line")?;
+    writeln!(writer, "events: Ticks")?;
+    writeln!(writer, "summary: {}", report.elapsed_tsc)?;
+
+    let mut names = Names::default();
+    for anchor in &report.anchors {
+        writeln!(writer)?;
+        writeln!(writer, "fn={}", names.compress(anchor.name))?;
+        writeln!(writer, "0 {}", anchor.tsc_elapsed_exclusive)?;
+        for call in report
+            .calls
+            .iter()
+            .filter(|call| call.caller == anchor.name)
+        {
+            writeln!(writer, "cfn={}", names.compress(call.callee))?;
+            writeln!(writer, "calls={} 0", call.call_count)?;
+            writeln!(writer, "0 {}", call.tsc_elapsed_inclusive)?;
+        }
+    }
+    Ok(())
+}
+
+/// Callgrind name compression, which spells out each name once and refers to it by ID afterwards.
+#[derive(Default)]
+struct Names {
+    ids: HashMap<&'static str, usize>,
+}
+
+impl Names {
+    fn compress(&mut self, name: &'static str) -> String {
+        let next = self.ids.len() + 1;
+        match self.ids.entry(name) {
+            Entry::Occupied(id) => format!("({})", id.get()),
+            Entry::Vacant(entry) => {
+                entry.insert(next);
+                format!("({next}) {name}")
+            }
+        }
+    }
+}
+
+/// Saves every finished report as a callgrind file.
+#[derive(Debug)]
+#[must_use]
+pub struct CallgrindExporter {
+    path: PathBuf,
+}
+
+impl CallgrindExporter {
+    /// Creates an exporter saving reports to the file at `path`, replacing it on every export.
+    /// `KCachegrind` recognizes files named `callgrind.out.<suffix>`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ReportExporter for CallgrindExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        write_callgrind(report, &mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::{AnchorStats, CallStats};
+
+    #[test]
+    fn callgrind_output() {
+        let anchor = |name, tsc_elapsed_exclusive| AnchorStats {
+            name,
+            hit_count: 1,
+            tsc_elapsed_exclusive,
+            ..AnchorStats::default()
+        };
+        let call = |caller, callee, call_count, tsc_elapsed_inclusive| CallStats {
+            caller,
+            callee,
+            call_count,
+            tsc_elapsed_inclusive,
+        };
+        let report = ProfileReport {
+            elapsed_tsc: 100,
+            anchors: vec![anchor("main", 20), anchor("parse", 50), anchor("emit", 30)],
+            calls: vec![
+                call("main", "parse", 1, 60),
+                call("main", "emit", 2, 30),
+                call("parse", "emit", 1, 10),
+            ],
+            ..ProfileReport::default()
+        };
+
+        let mut output = Vec::new();
+        write_callgrind(&report, &mut output).expect("valid write");
+        assert_eq!(
+            String::from_utf8(output).expect("valid utf8"),
+            "# callgrind format\nversion: 1\ncreator: util_lib_rs\npositions: line\n\
+             events: Ticks\nsummary: 100\n\
+             \nfn=(1) main\n0 20\ncfn=(2) parse\ncalls=1 0\n0 60\ncfn=(3) emit\ncalls=2 0\n0 30\n\
+             \nfn=(2)\n0 50\ncfn=(3)\ncalls=1 0\n0 10\n\
+             \nfn=(3)\n0 30\n"
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! Call-stack IDs for profile blocks.
+//!
+//! With the `callstacks` feature, every profile block hashes the return addresses of its call stack
+//! when created, so a shared utility anchor reached from different code paths is reported once per
+//! path in [`ProfileReport::call_stacks`](super::ProfileReport::call_stacks). Recorded trace events
+//! carry the same ID in their [`stack_id`](super::flight::TraceEvent::stack_id), so the Chrome,
+//! speedscope and dump exports tell the paths apart too. Each distinct stack is interned the first
+//! time it is seen, and its frames can be looked up with [`frames`].
+//!
+//! Walking the stack costs far more than the rest of a block, so only enable this feature to
+//! investigate where time in an anchor comes from. Stacks are only captured on Linux; elsewhere
+//! every block has the stack ID `0`.
+
+use crate::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::{Mutex, PoisonError},
+};
+
+/// Maximum number of frames hashed per stack.
+const MAX_FRAMES: usize = 64;
+
+/// Frames of every interned stack, keyed by stack ID.
+static STACKS: Lazy<Mutex<HashMap<u64, Box<[usize]>>>> = Lazy::new(Mutex::default);
+
+/// Return addresses of a captured call stack, innermost first.
+struct Frames {
+    ips: [usize; MAX_FRAMES],
+    len: usize,
+}
+
+impl Frames {
+    fn id(&self) -> u64 {
+        // FNV-1a, which is cheap and good enough to tell a handful of paths apart.
+        self.ips[..self.len]
+            .iter()
+            .fold(0xcbf2_9ce4_8422_2325, |hash, &ip| {
+                (hash ^ ip as u64).wrapping_mul(0x0100_0000_01b3)
+            })
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod unwind {
+    use super::{Frames, MAX_FRAMES};
+    use std::ffi::{c_int, c_void};
+
+    #[repr(C)]
+    struct Context {
+        _private: [u8; 0],
+    }
+
+    const URC_NO_REASON: c_int = 0;
+    const URC_END_OF_STACK: c_int = 5;
+
+    extern "C" {
+        fn _Unwind_Backtrace(
+            trace: extern "C" fn(*mut Context, *mut c_void) -> c_int,
+            arg: *mut c_void,
+        ) -> c_int;
+        fn _Unwind_GetIP(context: *mut Context) -> usize;
+    }
+
+    extern "C" fn trace(context: *mut Context, arg: *mut c_void) -> c_int {
+        // SAFETY: `arg` is the `Frames` passed to `_Unwind_Backtrace` by `capture`, and `context`
+        // is a valid unwind context for the duration of this callback.
+        let (frames, ip) = unsafe { (&mut *arg.cast::<Frames>(), _Unwind_GetIP(context)) };
+        frames.ips[frames.len] = ip;
+        frames.len += 1;
+        if frames.len == MAX_FRAMES {
+            URC_END_OF_STACK
+        } else {
+            URC_NO_REASON
+        }
+    }
+
+    pub(super) fn capture(frames: &mut Frames) {
+        // SAFETY: `trace` only writes to `frames`, which outlives the call.
+        unsafe { _Unwind_Backtrace(trace, std::ptr::from_mut(frames).cast()) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod unwind {
+    pub(super) fn capture(_frames: &mut super::Frames) {}
+}
+
+/// Returns the ID of the calling thread's current stack, interning its frames if `known` reports
+/// the ID hasn't been seen before.
+#[inline(never)]
+pub(super) fn current(known: impl FnOnce(u64) -> bool) -> u64 {
+    let mut frames = Frames {
+        ips: [0; MAX_FRAMES],
+        len: 0,
+    };
+    unwind::capture(&mut frames);
+    if frames.len == 0 {
+        return 0;
+    }
+    let id = frames.id();
+    if !known(id) {
+        STACKS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(id)
+            .or_insert_with(|| frames.ips[..frames.len].into());
+    }
+    id
+}
+
+/// Returns the return addresses of the interned stack `id`, innermost first, e.g. to resolve them
+/// with a symbolizer.
+#[must_use]
+pub fn frames(id: u64) -> Option<Vec<usize>> {
+    STACKS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(&id)
+        .map(|frames| frames.to_vec())
+}
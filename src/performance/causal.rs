@@ -0,0 +1,212 @@
+//! Causal profiling experiments.
+//!
+//! Time spent in an anchor doesn't say how much faster the whole program gets when that anchor is
+//! optimized, since other threads or I/O may be the real bottleneck. Inspired by
+//! [coz](https://github.com/plasma-umass/coz), a [`CausalExperiment`] runs a workload repeatedly,
+//! each time delaying every hit of one selected anchor by a percentage of its own elapsed time, and
+//! measures the end-to-end slowdown. Speeding the anchor up by the same amount is estimated to have
+//! the same impact in the other direction.
+//!
+//! Delays are only injected into blocks created with `profile!`, and only with the `perf` feature.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+/// Whether an experiment is running, checked before taking the lock on every block end.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// The anchor being delayed and the delay as a percentage of each hit's elapsed time.
+static EXPERIMENT: Mutex<Option<(&'static str, u32)>> = Mutex::new(None);
+
+/// Returns the delay to inject after a block of the anchor `name` which took `elapsed` ticks.
+#[inline]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn delay_for(name: &'static str, elapsed: u64) -> u64 {
+    if !ACTIVE.load(Ordering::Relaxed) {
+        return 0;
+    }
+    match *EXPERIMENT.lock().unwrap_or_else(PoisonError::into_inner) {
+        Some((anchor, percent)) if anchor == name => elapsed * u64::from(percent) / 100,
+        _ => 0,
+    }
+}
+
+fn set_experiment(experiment: Option<(&'static str, u32)>) {
+    *EXPERIMENT.lock().unwrap_or_else(PoisonError::into_inner) = experiment;
+    ACTIVE.store(experiment.is_some(), Ordering::Relaxed);
+    super::set_recorder(super::RECORD_CAUSAL, experiment.is_some());
+}
+
+/// A set of causal profiling experiments to run against a workload.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{performance::causal::CausalExperiment, profile};
+///
+/// fn parse() {
+///     profile!("parse");
+/// }
+///
+/// let report = CausalExperiment::new(&["parse"]).delays(&[25]).runs(2).run(|| parse());
+/// println!("{report}");
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct CausalExperiment {
+    anchors: Vec<&'static str>,
+    delays: Vec<u32>,
+    runs: usize,
+}
+
+impl CausalExperiment {
+    /// Creates experiments delaying each of `anchors` in turn, by 10%, 25% and 50% by default.
+    pub fn new(anchors: &[&'static str]) -> Self {
+        Self {
+            anchors: anchors.to_vec(),
+            delays: vec![10, 25, 50],
+            runs: 3,
+        }
+    }
+
+    /// Sets the delays to inject, as percentages of each hit's elapsed time.
+    pub fn delays(mut self, delays: &[u32]) -> Self {
+        self.delays = delays.to_vec();
+        self
+    }
+
+    /// Sets how many times the workload runs per experiment. The fastest run is kept to reduce
+    /// noise.
+    pub fn runs(mut self, runs: usize) -> Self {
+        self.runs = runs.max(1);
+        self
+    }
+
+    /// Runs `workload` once without delays for a baseline and then once per anchor and delay,
+    /// returning the estimated impact of speeding up each anchor.
+    pub fn run(&self, mut workload: impl FnMut()) -> CausalReport {
+        let mut measure = |experiment| {
+            set_experiment(experiment);
+            let fastest = (0..self.runs)
+                .map(|_| {
+                    let start = Instant::now();
+                    workload();
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or_default();
+            set_experiment(None);
+            fastest
+        };
+
+        let baseline = measure(None);
+        let mut results = Vec::with_capacity(self.anchors.len() * self.delays.len());
+        for &anchor in &self.anchors {
+            for &delay_percent in &self.delays {
+                results.push(CausalResult {
+                    anchor,
+                    delay_percent,
+                    elapsed: measure(Some((anchor, delay_percent))),
+                });
+            }
+        }
+        CausalReport { baseline, results }
+    }
+}
+
+/// The outcome of delaying one anchor.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct CausalResult {
+    /// Name of the delayed anchor.
+    pub anchor: &'static str,
+    /// Delay injected after each hit, as a percentage of its elapsed time.
+    pub delay_percent: u32,
+    /// Fastest elapsed time of the workload with the delay.
+    pub elapsed: Duration,
+}
+
+/// Results of a [`CausalExperiment`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct CausalReport {
+    /// Fastest elapsed time of the workload without delays.
+    pub baseline: Duration,
+    /// Results of each experiment, in the order they were run.
+    pub results: Vec<CausalResult>,
+}
+
+impl CausalReport {
+    /// Estimated fraction of the baseline run time saved by speeding up the anchor of `result` by
+    /// its delay percentage.
+    #[must_use]
+    pub fn impact(&self, result: &CausalResult) -> f64 {
+        if self.baseline.is_zero() {
+            return 0.0;
+        }
+        (result.elapsed.as_secs_f64() - self.baseline.as_secs_f64()) / self.baseline.as_secs_f64()
+    }
+
+    /// Returns the results ordered by impact, most worth optimizing first.
+    #[must_use]
+    pub fn ranked(&self) -> Vec<&CausalResult> {
+        let mut ranked: Vec<&CausalResult> = self.results.iter().collect();
+        ranked.sort_by(|a, b| self.impact(b).total_cmp(&self.impact(a)));
+        ranked
+    }
+}
+
+impl fmt::Display for CausalReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\nCausal profile, baseline {:?}:", self.baseline)?;
+        for result in self.ranked() {
+            writeln!(
+                f,
+                "  {} sped up {}%: {:.2}% faster overall",
+                result.anchor,
+                result.delay_percent,
+                100.0 * self.impact(result)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+    use crate::profile;
+
+    fn spin(duration: Duration) {
+        let start = Instant::now();
+        while start.elapsed() < duration {
+            std::hint::black_box(0);
+        }
+    }
+
+    #[test]
+    fn causal_impact() {
+        let workload = || {
+            {
+                profile!("causal_slow");
+                spin(Duration::from_millis(4));
+            }
+            profile!("causal_fast");
+            spin(Duration::from_micros(100));
+        };
+        let report = CausalExperiment::new(&["causal_slow", "causal_fast"])
+            .delays(&[50])
+            .run(workload);
+
+        assert_eq!(report.results.len(), 2);
+        assert_eq!(report.ranked()[0].anchor, "causal_slow");
+        assert!(report.impact(&report.results[0]) > 0.2);
+        assert!(!ACTIVE.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,171 @@
+//! Chrome Trace Event output.
+//!
+//! Aggregated anchors hide when each block ran. With [`set_record_trace_events`] enabled, every
+//! profile block ended on a thread is also kept as a [`TraceEvent`], which
+//! [`profile_end_and_export_chrome_trace`](super::profile_end_and_export_chrome_trace) writes in
+//! the [Trace Event format](https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU)
+//! for viewing in `chrome://tracing` or [Perfetto](https://ui.perfetto.dev). Events are kept in
+//! memory until profiling ends, so long sessions with many blocks use a lot of memory.
+
+use super::flight::TraceEvent;
+use crate::json::JsonWriter;
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static RECORD_TRACE_EVENTS: AtomicBool = AtomicBool::new(false);
+
+/// Record every profile block as a trace event on threads which begin profiling after this is
+/// enabled, for export with
+/// [`profile_end_and_export_chrome_trace`](super::profile_end_and_export_chrome_trace).
+pub fn set_record_trace_events(enabled: bool) {
+    RECORD_TRACE_EVENTS.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether threads beginning profiling should record trace events.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn record_trace_events() -> bool {
+    RECORD_TRACE_EVENTS.load(Ordering::Relaxed)
+}
+
+/// Writes `events` recorded on the thread named `thread` to `writer` as Chrome Trace Event JSON.
+/// Timestamps are microseconds since `start_tsc`, converted with the timer frequency `timer_freq`,
+/// and the call stack ID of each event, if captured, is written to its `args` as a hex string.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{chrome::write_chrome_trace, flight::TraceEvent};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let events = [TraceEvent {
+///     name: "main",
+///     start_tsc: 1_000,
+///     end_tsc: 3_000,
+///     ..TraceEvent::default()
+/// }];
+/// let mut output = Vec::new();
+/// write_chrome_trace(&events, "main", 0, 1_000_000, &mut output)?;
+/// assert!(String::from_utf8_lossy(&output).contains(r#""ts":1000.000,"dur":2000.000"#));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_chrome_trace(
+    events: &[TraceEvent],
+    thread: &str,
+    start_tsc: u64,
+    timer_freq: u64,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let pid = std::process::id();
+    let micros = |tsc: u64| {
+        if timer_freq == 0 {
+            0.0
+        } else {
+            #[allow(clippy::cast_precision_loss)]
+            let micros = 1e6 * tsc as f64 / timer_freq as f64;
+            micros
+        }
+    };
+
+    let mut json = JsonWriter::new(&mut writer);
+    json.begin_object()?
+        .key("displayTimeUnit")?
+        .string("ns")?
+        .key("traceEvents")?
+        .begin_array()?;
+    json.begin_object()?
+        .key("name")?
+        .string("thread_name")?
+        .key("ph")?
+        .string("M")?
+        .key("pid")?
+        .u64(pid.into())?
+        .key("tid")?
+        .u64(1)?
+        .key("args")?
+        .begin_object()?
+        .key("name")?
+        .string(thread)?
+        .end_object()?
+        .end_object()?;
+    for event in events {
+        json.begin_object()?
+            .key("name")?
+            .string(event.name)?
+            .key("ph")?
+            .string("X")?
+            .key("ts")?
+            .fixed(micros(event.start_tsc.saturating_sub(start_tsc)), 3)?
+            .key("dur")?
+            .fixed(micros(event.end_tsc.saturating_sub(event.start_tsc)), 3)?
+            .key("pid")?
+            .u64(pid.into())?
+            .key("tid")?
+            .u64(1)?;
+        if let Some(stack_id) = event.stack_id {
+            json.key("args")?
+                .begin_object()?
+                .key("stack_id")?
+                .string(&format!("{stack_id:016x}"))?
+                .end_object()?;
+        }
+        json.end_object()?;
+    }
+    json.end_array()?.end_object()?;
+    writeln!(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chrome_trace_json() {
+        let events = [
+            TraceEvent {
+                name: "outer",
+                start_tsc: 100,
+                end_tsc: 400,
+                depth: 0,
+                stack_id: None,
+            },
+            TraceEvent {
+                name: "inner \"quoted\"\n",
+                start_tsc: 200,
+                end_tsc: 300,
+                depth: 1,
+                stack_id: Some(0xabc),
+            },
+        ];
+        let mut output = Vec::new();
+        write_chrome_trace(&events, "worker", 100, 1_000_000, &mut output).expect("valid write");
+        let trace = crate::json::parse(std::str::from_utf8(&output).expect("valid utf-8"))
+            .expect("valid JSON");
+        assert_eq!(
+            trace["traceEvents"][2]["name"].as_str(),
+            Some("inner \"quoted\"\n")
+        );
+        assert_eq!(trace["traceEvents"][2]["dur"].as_f64(), Some(100.0));
+        let pid = std::process::id();
+        assert_eq!(
+            String::from_utf8(output).expect("valid utf-8"),
+            format!(
+                concat!(
+                    r#"{{"displayTimeUnit":"ns","traceEvents":["#,
+                    r#"{{"name":"thread_name","ph":"M","pid":{pid},"tid":1,"args":{{"name":"worker"}}}},"#,
+                    r#"{{"name":"outer","ph":"X","ts":0.000,"dur":300.000,"pid":{pid},"tid":1}},"#,
+                    r#"{{"name":"inner \"quoted\"\u000a","ph":"X","ts":100.000,"dur":100.000,"#,
+                    r#""pid":{pid},"tid":1,"args":{{"stack_id":"0000000000000abc"}}}}"#,
+                    "]}}\n"
+                ),
+                pid = pid
+            )
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Timers used instead of the timestamp counter.
+//!
+//! On architectures without a timestamp counter the profiler can read, such as `wasm32`, blocks
+//! read nanoseconds since the first read from [`std::time::Instant`], which has lower resolution
+//! and higher overhead but lets browser and WASI builds use the same `profile!` macro. On
+//! `wasm32-unknown-unknown`, where `Instant` isn't available, blocks call the source set with
+//! [`set_timer_source`], typically `performance.now()`, and read `0` until one is set.
+//!
+//! On processors whose timestamp counter changes rate with the power state, or with
+//! [`TimerRead::Os`](super::TimerRead::Os), blocks read the operating system's monotonic clock.
+//!
+//! Any other timer, such as a platform cycle counter or a simulated clock in tests, can be plugged
+//! in by implementing [`ClockSource`] and installing it with [`set_clock_source`] before profiling
+//! begins.
+
+use std::sync::OnceLock;
+
+/// A timer read at the start and end of every block.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Instant;
+/// use util_lib_rs::performance::clock::{set_clock_source, ClockSource};
+///
+/// struct Monotonic(Instant);
+///
+/// impl ClockSource for Monotonic {
+///     fn read(&self) -> u64 {
+///         u64::try_from(self.0.elapsed().as_nanos()).unwrap_or(u64::MAX)
+///     }
+///
+///     fn frequency(&self) -> u64 {
+///         1_000_000_000
+///     }
+/// }
+///
+/// assert!(set_clock_source(Monotonic(Instant::now())));
+/// util_lib_rs::performance::profile_begin();
+/// ```
+pub trait ClockSource: Send + Sync {
+    /// Returns the current time in ticks, which must not decrease on any one thread.
+    fn read(&self) -> u64;
+
+    /// Returns the number of ticks per second, used to convert reports to seconds.
+    fn frequency(&self) -> u64;
+}
+
+/// The built-in timer: the timestamp counter read with `rdtscp` on x86, the virtual counter on
+/// `aarch64`, and the fallback timer elsewhere, with its frequency calibrated on first use.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct TimestampCounter;
+
+impl ClockSource for TimestampCounter {
+    fn read(&self) -> u64 {
+        #[cfg(feature = "perf")]
+        {
+            super::Profiler::read_timestamp_and_cpu().0
+        }
+        #[cfg(not(feature = "perf"))]
+        read()
+    }
+
+    fn frequency(&self) -> u64 {
+        #[cfg(feature = "perf")]
+        {
+            super::Profiler::timestamp_freq()
+        }
+        #[cfg(not(feature = "perf"))]
+        FREQUENCY
+    }
+}
+
+/// The installed clock source, or `None` once profiling began without one.
+static CLOCK_SOURCE: OnceLock<Option<Box<dyn ClockSource>>> = OnceLock::new();
+
+/// Install `source` as the timer of every block on every thread, instead of the
+/// [`TimestampCounter`]. Returns `false` without installing it if a source was already installed
+/// or profiling has already begun on any thread, since readings of different timers can't be
+/// compared. A timer read chosen with [`set_timer_read`](super::set_timer_read) takes precedence.
+pub fn set_clock_source(source: impl ClockSource + 'static) -> bool {
+    CLOCK_SOURCE.set(Some(Box::new(source))).is_ok()
+}
+
+/// Returns the installed clock source, preventing one from being installed later.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn source() -> Option<&'static dyn ClockSource> {
+    CLOCK_SOURCE.get_or_init(|| None).as_deref()
+}
+
+/// Reads the installed clock source, or returns `0` if there is none.
+#[inline]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn read_source() -> u64 {
+    match CLOCK_SOURCE.get() {
+        Some(Some(source)) => source.read(),
+        _ => 0,
+    }
+}
+
+/// Ticks per second of the fallback timer.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) const FREQUENCY: u64 = 1_000_000_000;
+
+static TIMER_SOURCE: OnceLock<fn() -> f64> = OnceLock::new();
+
+/// Set the function blocks call to read the time in milliseconds, such as the browser's
+/// `performance.now()`, on architectures without a supported timestamp counter. Only the first
+/// source set is used, and `x86`, `x86_64` and `aarch64` ignore it.
+///
+/// # Examples
+///
+/// ```ignore
+/// util_lib_rs::performance::clock::set_timer_source(|| {
+///     web_sys::window()
+///         .and_then(|window| window.performance())
+///         .map_or(0.0, |performance| performance.now())
+/// });
+/// ```
+pub fn set_timer_source(now: fn() -> f64) {
+    let _ = TIMER_SOURCE.set(now);
+}
+
+/// Returns the fallback timer in nanoseconds.
+#[inline]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(super) fn read() -> u64 {
+    if let Some(now) = TIMER_SOURCE.get() {
+        return (now() * 1_000_000.0) as u64;
+    }
+    #[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+    {
+        static EPOCH: OnceLock<std::time::Instant> = OnceLock::new();
+        let elapsed = EPOCH.get_or_init(std::time::Instant::now).elapsed();
+        u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+    }
+    #[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+    0
+}
+
+/// Reads the operating system's monotonic clock, in ticks of [`os_frequency`].
+#[inline]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn read_os() -> u64 {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        use std::os::raw::{c_int, c_long};
+
+        /// Unaffected by NTP adjustments, unlike `CLOCK_MONOTONIC`.
+        const CLOCK_MONOTONIC_RAW: c_int = 4;
+
+        #[repr(C)]
+        struct Timespec {
+            tv_sec: c_long,
+            tv_nsec: c_long,
+        }
+
+        extern "C" {
+            fn clock_gettime(clock: c_int, time: *mut Timespec) -> c_int;
+        }
+
+        let mut time = Timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `time` is a valid `struct timespec` for the duration of the call.
+        if unsafe { clock_gettime(CLOCK_MONOTONIC_RAW, &raw mut time) } != 0 {
+            return read();
+        }
+        let secs = u64::try_from(time.tv_sec).unwrap_or(0);
+        let nanos = u64::try_from(time.tv_nsec).unwrap_or(0);
+        secs.wrapping_mul(FREQUENCY).wrapping_add(nanos)
+    }
+    #[cfg(target_os = "windows")]
+    {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn QueryPerformanceCounter(count: *mut i64) -> i32;
+        }
+
+        let mut count = 0;
+        // SAFETY: `count` is valid for writes for the duration of the call.
+        unsafe {
+            QueryPerformanceCounter(&raw mut count);
+        }
+        u64::try_from(count).unwrap_or(0)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    read()
+}
+
+/// Returns the ticks per second of [`read_os`].
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn os_frequency() -> u64 {
+    #[cfg(target_os = "windows")]
+    {
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn QueryPerformanceFrequency(frequency: *mut i64) -> i32;
+        }
+
+        static OS_FREQUENCY: OnceLock<u64> = OnceLock::new();
+        *OS_FREQUENCY.get_or_init(|| {
+            let mut frequency = 0;
+            // SAFETY: `frequency` is valid for writes for the duration of the call.
+            unsafe {
+                QueryPerformanceFrequency(&raw mut frequency);
+            }
+            u64::try_from(frequency).unwrap_or(0)
+        })
+    }
+    #[cfg(not(target_os = "windows"))]
+    FREQUENCY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallback_timer() {
+        let start = read();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(read() - start >= 2_000_000);
+
+        let start = read_os();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(read_os() - start >= os_frequency() / 500);
+
+        let start = TimestampCounter.read();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(TimestampCounter.read() - start >= TimestampCounter.frequency() / 1000);
+
+        set_timer_source(|| 1.5);
+        assert_eq!(read(), 1_500_000);
+        set_timer_source(|| 3.0);
+        assert_eq!(read(), 1_500_000);
+    }
+}
@@ -0,0 +1,134 @@
+//! Colored report output.
+//!
+//! Printed reports highlight hotspots: anchors taking at least the hot share of the total time in
+//! red, and at least the warm share in yellow. Colors are used when reports are printed to a
+//! terminal's `stderr`, following `NO_COLOR` and `CLICOLOR_FORCE` as [`term::style`] does, unless
+//! overridden with [`set_color`]. Reports formatted with `to_string` or written to a writer are
+//! never colored.
+//!
+//! [`term::style`]: crate::term::style
+
+use crate::term::{style, Color, Style};
+use std::{
+    cell::Cell,
+    sync::atomic::{AtomicU64, AtomicU8, Ordering},
+};
+
+/// Style of hot anchors.
+const RED: Style = Style::new().fg(Color::Red);
+/// Style of warm anchors.
+const YELLOW: Style = Style::new().fg(Color::Yellow);
+pub(super) use style::RESET;
+
+/// When printed reports are colored.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color reports printed to a terminal's `stderr`, unless `NO_COLOR` is set, or any printed
+    /// report if `CLICOLOR_FORCE` is set.
+    #[default]
+    Auto,
+    /// Color every printed report, including those written to an
+    /// [output sink](super::sink::set_output_sink).
+    Always,
+    /// Never color reports.
+    Never,
+}
+
+static COLOR: AtomicU8 = AtomicU8::new(0);
+/// Bits of the warm and hot thresholds, in percent of the total time.
+static WARM_PERCENT: AtomicU64 = AtomicU64::new(0x4014_0000_0000_0000);
+static HOT_PERCENT: AtomicU64 = AtomicU64::new(0x4034_0000_0000_0000);
+
+thread_local! {
+    /// Whether the report being printed on this thread is colored.
+    static COLORIZE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Set when printed reports are colored.
+pub fn set_color(choice: ColorChoice) {
+    let choice = match choice {
+        ColorChoice::Auto => 0,
+        ColorChoice::Always => 1,
+        ColorChoice::Never => 2,
+    };
+    COLOR.store(choice, Ordering::Relaxed);
+}
+
+/// Set the shares of the total time, in percent, from which anchors are highlighted as warm in
+/// yellow and hot in red. The defaults are 5% and 20%.
+pub fn set_hotspot_thresholds(warm_percent: f64, hot_percent: f64) {
+    WARM_PERCENT.store(warm_percent.to_bits(), Ordering::Relaxed);
+    HOT_PERCENT.store(hot_percent.to_bits(), Ordering::Relaxed);
+}
+
+/// Returns whether a report printed to a terminal if `is_terminal` should be colored.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn enabled(is_terminal: bool) -> bool {
+    match COLOR.load(Ordering::Relaxed) {
+        1 => true,
+        2 => false,
+        _ => style::should_style(is_terminal),
+    }
+}
+
+/// Runs `print` with reports formatted on this thread colored if `enabled`.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn colored<R>(enabled: bool, print: impl FnOnce() -> R) -> R {
+    let previous = COLORIZE.replace(enabled);
+    let result = print();
+    COLORIZE.set(previous);
+    result
+}
+
+/// Returns the style highlighting an anchor taking `percent` of the total time, if the report being
+/// formatted is colored and the anchor is a hotspot.
+pub(super) fn hotspot(percent: f64) -> Option<Style> {
+    if !COLORIZE.get() {
+        return None;
+    }
+    if percent >= f64::from_bits(HOT_PERCENT.load(Ordering::Relaxed)) {
+        Some(RED)
+    } else if percent >= f64::from_bits(WARM_PERCENT.load(Ordering::Relaxed)) {
+        Some(YELLOW)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::{AnchorStats, ProfileReport};
+
+    #[test]
+    fn hotspot_colors() {
+        assert!((f64::from_bits(WARM_PERCENT.load(Ordering::Relaxed)) - 5.0).abs() < 1e-9);
+        assert!((f64::from_bits(HOT_PERCENT.load(Ordering::Relaxed)) - 20.0).abs() < 1e-9);
+
+        let anchor = |name, tsc| AnchorStats {
+            name,
+            hit_count: 1,
+            tsc_elapsed_exclusive: tsc,
+            tsc_elapsed_inclusive: tsc,
+            ..AnchorStats::default()
+        };
+        let report = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1000,
+            anchors: vec![anchor("hot", 500), anchor("warm", 100), anchor("cold", 10)],
+            ..ProfileReport::default()
+        };
+
+        assert!(!report.to_string().contains('\x1b'));
+        let output = colored(true, || report.to_string());
+        assert!(output.contains(&format!(
+            "\n{RED}  hot        1    500  50.00%  50.00%{RESET}\n"
+        )));
+        assert!(output.contains(&format!(
+            "\n{YELLOW}  warm       1    100  10.00%  10.00%{RESET}\n"
+        )));
+        assert!(output.contains("\n  cold       1     10   1.00%   1.00%\n"));
+        assert!(!COLORIZE.get());
+        assert!(!enabled(false));
+    }
+}
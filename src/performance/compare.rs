@@ -0,0 +1,589 @@
+//! Comparison of a report against a baseline.
+//!
+//! A [`Comparison`] matches the anchors of two reports by name and lists the percent change in
+//! each anchor's exclusive time, hits and throughput, so CI can save a baseline from one run with
+//! [`profile_save_baseline`](super::profile_save_baseline) and fail later runs which regress with
+//! [`profile_end_and_compare`](super::profile_end_and_compare). Times are compared in seconds, so
+//! runs on machines with different timer frequencies can be compared.
+//!
+//! Timer jitter makes every run a little faster or slower than the last, so changes are also
+//! tested for [`Significance`]. Anchors whose per-hit times were recorded are compared with Welch's
+//! t-test, [`Significance::of_repetitions`] does the same for two [`RepetitionResults`], and
+//! [`Significance::of_samples`] compares raw samples with the Mann-Whitney U test. Changes which
+//! could be chance at the 95% level are noise, and [`Comparison::regressions`] leaves them out.
+
+use super::{reptest::RepetitionResults, AnchorStats, ProfileReport};
+use std::fmt;
+
+/// Two-sided critical values of Student's t distribution at the 95% level, for 1 to 30 degrees of
+/// freedom.
+const T_CRITICAL: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+/// Two-sided critical value of the normal distribution at the 95% level.
+const Z_CRITICAL: f64 = 1.96;
+
+/// Whether a change is larger than the run-to-run variation of what was measured.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Significance {
+    /// The change could be timer jitter or other chance variation.
+    Noise,
+    /// The change is unlikely to be chance, at the 95% level.
+    Significant,
+}
+
+impl Significance {
+    /// Tests whether two sets of samples, such as the times of each run of a routine before and
+    /// after a change, differ with the Mann-Whitney U test. The test compares ranks rather than
+    /// values, so a few outliers don't decide it. Returns `None` unless both sets have at least two
+    /// samples and not every sample is equal.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::performance::compare::Significance;
+    ///
+    /// let before = [10.2, 10.4, 9.9, 10.1, 10.3, 10.0, 10.2, 9.8];
+    /// let jittered = [10.1, 10.3, 10.0, 10.2, 9.9, 10.4, 10.1, 10.0];
+    /// let slower = [11.2, 11.0, 11.4, 10.9, 11.3, 11.1, 11.2, 11.0];
+    /// assert_eq!(Significance::of_samples(&before, &jittered), Some(Significance::Noise));
+    /// assert_eq!(Significance::of_samples(&before, &slower), Some(Significance::Significant));
+    /// ```
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn of_samples(baseline: &[f64], current: &[f64]) -> Option<Self> {
+        if baseline.len() < 2 || current.len() < 2 {
+            return None;
+        }
+        let mut samples: Vec<_> = baseline
+            .iter()
+            .map(|&value| (value, true))
+            .chain(current.iter().map(|&value| (value, false)))
+            .collect();
+        samples.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+
+        // Tied samples share the average of their ranks, which shrinks the variance of U.
+        let (mut rank, mut baseline_ranks, mut ties) = (0.0, 0.0, 0.0);
+        for group in samples.chunk_by(|a, b| a.0.total_cmp(&b.0).is_eq()) {
+            let len = group.len() as f64;
+            let in_baseline = group.iter().filter(|sample| sample.1).count() as f64;
+            baseline_ranks += in_baseline * (rank + f64::midpoint(len, 1.0));
+            ties += len * len * len - len;
+            rank += len;
+        }
+        let (n1, n2) = (baseline.len() as f64, current.len() as f64);
+        let n = n1 + n2;
+        let u = baseline_ranks - n1 * (n1 + 1.0) / 2.0;
+        let variance = n1 * n2 / 12.0 * ((n + 1.0) - ties / (n * (n - 1.0)));
+        (variance > 0.0)
+            .then(|| Self::of_statistic((u - n1 * n2 / 2.0) / variance.sqrt(), Z_CRITICAL))
+    }
+
+    /// Tests whether the mean times of two [`RepetitionResults`] differ with Welch's t-test.
+    /// Returns `None` unless both ran at least twice.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use util_lib_rs::performance::{compare::Significance, reptest::RepetitionTester};
+    ///
+    /// let tester = RepetitionTester::new(Duration::from_millis(5));
+    /// let sum = |len| tester.run(|| _ = std::hint::black_box((0..len).sum::<u64>()));
+    /// let significance = Significance::of_repetitions(&sum(1000), &sum(1000));
+    /// println!("{significance:?}");
+    /// ```
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn of_repetitions(
+        baseline: &RepetitionResults,
+        current: &RepetitionResults,
+    ) -> Option<Self> {
+        let sample = |results: &RepetitionResults| {
+            (results.repetitions >= 2).then(|| Sample {
+                count: results.repetitions as f64,
+                mean: results.mean().as_secs_f64(),
+                variance: results.std_dev().as_secs_f64().powi(2),
+            })
+        };
+        Some(Sample::welch(&sample(baseline)?, &sample(current)?))
+    }
+
+    /// `Significant` if `statistic` is further from zero than `critical`.
+    fn of_statistic(statistic: f64, critical: f64) -> Self {
+        if statistic.abs() > critical {
+            Self::Significant
+        } else {
+            Self::Noise
+        }
+    }
+}
+
+impl fmt::Display for Significance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Noise => "noise",
+            Self::Significant => "significant",
+        })
+    }
+}
+
+/// The size, mean and sample variance of a set of samples.
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Sample {
+    count: f64,
+    mean: f64,
+    variance: f64,
+}
+
+impl Sample {
+    /// Tests whether the means of `baseline` and `current` differ with Welch's t-test, which
+    /// doesn't assume their variances are equal.
+    fn welch(baseline: &Self, current: &Self) -> Significance {
+        let (v1, v2) = (
+            baseline.variance / baseline.count,
+            current.variance / current.count,
+        );
+        let difference = current.mean - baseline.mean;
+        if v1 + v2 <= 0.0 {
+            // Without variation, any difference is real.
+            return Significance::of_statistic(difference, 0.0);
+        }
+        let degrees = (v1 + v2).powi(2)
+            / (v1 * v1 / (baseline.count - 1.0) + v2 * v2 / (current.count - 1.0));
+        Significance::of_statistic(difference / (v1 + v2).sqrt(), t_critical(degrees))
+    }
+}
+
+/// Two-sided critical value of Student's t distribution at the 95% level for `degrees` of freedom,
+/// rounded down to a whole number, tending to the normal distribution's with more.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn t_critical(degrees: f64) -> f64 {
+    let whole = degrees.floor().max(1.0);
+    T_CRITICAL
+        .get(whole as usize - 1)
+        .copied()
+        .unwrap_or(Z_CRITICAL + 2.4 / whole)
+}
+
+/// The statistics of one anchor in one of the compared reports.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[must_use]
+pub struct Measurement {
+    /// Number of hits.
+    pub hit_count: u64,
+    /// Exclusive elapsed time, in seconds.
+    pub exclusive_seconds: f64,
+    /// Bytes processed per second of exclusive time, or `0.0` if no bytes were counted.
+    pub bytes_per_second: f64,
+    /// Average time of a hit including child blocks, in seconds.
+    pub hit_mean_seconds: f64,
+    /// Standard deviation of the time of a hit including child blocks, in seconds, or `None` with
+    /// fewer than two hits or if the time of each hit wasn't recorded.
+    pub hit_std_dev_seconds: Option<f64>,
+}
+
+impl Measurement {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(anchor: &AnchorStats, timer_freq: u64) -> Self {
+        let seconds = |tsc: f64| {
+            if timer_freq == 0 {
+                0.0
+            } else {
+                tsc / timer_freq as f64
+            }
+        };
+        let exclusive_seconds = seconds(anchor.tsc_elapsed_exclusive as f64);
+        let bytes_per_second = if anchor.byte_count == 0 || exclusive_seconds == 0.0 {
+            0.0
+        } else {
+            anchor.byte_count as f64 / exclusive_seconds
+        };
+        // Reports saved without the squared hit times would otherwise show no variation at all.
+        let hit_std_dev_seconds =
+            (anchor.hit_count >= 2 && anchor.tsc_elapsed_squares > 0).then(|| {
+                let hits = anchor.hit_count as f64;
+                seconds(anchor.tsc_std_dev() * (hits / (hits - 1.0)).sqrt())
+            });
+        Self {
+            hit_count: anchor.hit_count,
+            exclusive_seconds,
+            bytes_per_second,
+            hit_mean_seconds: seconds(anchor.tsc_elapsed_inclusive as f64)
+                / anchor.hit_count.max(1) as f64,
+            hit_std_dev_seconds,
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn sample(&self) -> Option<Sample> {
+        Some(Sample {
+            count: self.hit_count as f64,
+            mean: self.hit_mean_seconds,
+            variance: self.hit_std_dev_seconds?.powi(2),
+        })
+    }
+}
+
+/// One anchor's statistics in the baseline and current reports.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[must_use]
+pub struct AnchorComparison {
+    /// Name of the anchor.
+    pub name: &'static str,
+    /// Statistics in the baseline, or `None` if the anchor was added since.
+    pub baseline: Option<Measurement>,
+    /// Statistics in the current report, or `None` if the anchor was removed since.
+    pub current: Option<Measurement>,
+}
+
+impl AnchorComparison {
+    /// Percent change in exclusive time, or `None` unless both reports hit the anchor and the
+    /// baseline took some time.
+    #[must_use]
+    pub fn exclusive_change(&self) -> Option<f64> {
+        self.change(|measurement| measurement.exclusive_seconds)
+    }
+
+    /// Percent change in hits, or `None` unless both reports hit the anchor.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hits_change(&self) -> Option<f64> {
+        self.change(|measurement| measurement.hit_count as f64)
+    }
+
+    /// Percent change in bytes processed per second, or `None` unless both reports counted bytes.
+    #[must_use]
+    pub fn throughput_change(&self) -> Option<f64> {
+        self.change(|measurement| measurement.bytes_per_second)
+    }
+
+    /// Whether the change in the time of a hit is larger than the variation between hits, with
+    /// Welch's t-test. Returns `None` unless both reports recorded the time of at least two hits.
+    #[must_use]
+    pub fn significance(&self) -> Option<Significance> {
+        Some(Sample::welch(
+            &self.baseline?.sample()?,
+            &self.current?.sample()?,
+        ))
+    }
+
+    fn change(&self, value: impl Fn(&Measurement) -> f64) -> Option<f64> {
+        let before = value(self.baseline.as_ref()?);
+        let after = value(self.current.as_ref()?);
+        (before != 0.0).then(|| 100.0 * (after - before) / before)
+    }
+}
+
+/// The per-anchor changes from a baseline report to the current one.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{compare::Comparison, AnchorStats, ProfileReport};
+///
+/// let run = |tsc_elapsed_exclusive| ProfileReport {
+///     elapsed_tsc: 1000,
+///     timer_freq: 1000,
+///     anchors: vec![AnchorStats {
+///         name: "parse",
+///         hit_count: 1,
+///         tsc_elapsed_exclusive,
+///         tsc_elapsed_inclusive: tsc_elapsed_exclusive,
+///         ..AnchorStats::default()
+///     }],
+///     ..ProfileReport::default()
+/// };
+/// let comparison = Comparison::new(&run(400), &run(500));
+/// println!("{comparison}");
+/// assert_eq!(comparison.regressions(10.0)[0].name, "parse");
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+#[must_use]
+pub struct Comparison {
+    /// Total elapsed time of the baseline, in seconds.
+    pub baseline_seconds: f64,
+    /// Total elapsed time of the current report, in seconds.
+    pub current_seconds: f64,
+    /// Every anchor of the current report in order, followed by those only in the baseline.
+    pub anchors: Vec<AnchorComparison>,
+}
+
+impl Comparison {
+    /// Compares the anchors of `current` to those of `baseline`, matching them by name.
+    pub fn new(baseline: &ProfileReport, current: &ProfileReport) -> Self {
+        let measure = |report: &ProfileReport, name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| Measurement::new(anchor, report.timer_freq))
+        };
+        let removed = baseline.anchors.iter().filter(|anchor| {
+            !current
+                .anchors
+                .iter()
+                .any(|other| other.name == anchor.name)
+        });
+        let anchors = current
+            .anchors
+            .iter()
+            .chain(removed)
+            .map(|anchor| AnchorComparison {
+                name: anchor.name,
+                baseline: measure(baseline, anchor.name),
+                current: measure(current, anchor.name),
+            })
+            .collect();
+        Self {
+            baseline_seconds: baseline.elapsed_ms() / 1000.0,
+            current_seconds: current.elapsed_ms() / 1000.0,
+            anchors,
+        }
+    }
+
+    /// Percent change in total elapsed time, or `None` if the baseline took no time.
+    #[must_use]
+    pub fn elapsed_change(&self) -> Option<f64> {
+        (self.baseline_seconds != 0.0)
+            .then(|| 100.0 * (self.current_seconds - self.baseline_seconds) / self.baseline_seconds)
+    }
+
+    /// Returns the anchors whose exclusive time grew by more than `threshold_percent`, for failing
+    /// CI runs which regress. Anchors whose [significance](AnchorComparison::significance) is
+    /// [`Noise`](Significance::Noise) are left out, so timer jitter doesn't fail builds.
+    #[must_use]
+    pub fn regressions(&self, threshold_percent: f64) -> Vec<&AnchorComparison> {
+        self.anchors
+            .iter()
+            .filter(|anchor| {
+                anchor
+                    .exclusive_change()
+                    .is_some_and(|change| change > threshold_percent)
+                    && anchor.significance() != Some(Significance::Noise)
+            })
+            .collect()
+    }
+}
+
+/// Formats a percent change with its sign, or `n/a` if there is none.
+fn fmt_change(change: Option<f64>) -> String {
+    change.map_or_else(|| "n/a".to_string(), |change| format!("{change:+.2}%"))
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+        writeln!(
+            f,
+            "\nCompared to baseline: {:.4}ms -> {:.4}ms ({})",
+            1000.0 * self.baseline_seconds,
+            1000.0 * self.current_seconds,
+            fmt_change(self.elapsed_change())
+        )?;
+        for anchor in &self.anchors {
+            match (&anchor.baseline, &anchor.current) {
+                (Some(baseline), Some(current)) => {
+                    write!(
+                        f,
+                        "  {}: {:.4}ms -> {:.4}ms ({}), {} -> {} hits ({})",
+                        anchor.name,
+                        1000.0 * baseline.exclusive_seconds,
+                        1000.0 * current.exclusive_seconds,
+                        fmt_change(anchor.exclusive_change()),
+                        baseline.hit_count,
+                        current.hit_count,
+                        fmt_change(anchor.hits_change()),
+                    )?;
+                    if baseline.bytes_per_second > 0.0 || current.bytes_per_second > 0.0 {
+                        write!(
+                            f,
+                            ", {:.2}GB/s -> {:.2}GB/s ({})",
+                            baseline.bytes_per_second / GB,
+                            current.bytes_per_second / GB,
+                            fmt_change(anchor.throughput_change()),
+                        )?;
+                    }
+                    if let Some(significance) = anchor.significance() {
+                        write!(f, ", {significance}")?;
+                    }
+                    writeln!(f)?;
+                }
+                (None, Some(current)) => writeln!(
+                    f,
+                    "  {}: added, {:.4}ms, {} hits",
+                    anchor.name,
+                    1000.0 * current.exclusive_seconds,
+                    current.hit_count
+                )?,
+                (Some(_), None) => writeln!(f, "  {}: removed", anchor.name)?,
+                (None, None) => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn anchor(name: &'static str, hit_count: u64, tsc: u64, byte_count: u64) -> AnchorStats {
+        AnchorStats {
+            name,
+            hit_count,
+            tsc_elapsed_exclusive: tsc,
+            tsc_elapsed_inclusive: tsc,
+            byte_count,
+            ..AnchorStats::default()
+        }
+    }
+
+    #[test]
+    fn anchor_changes() {
+        let baseline = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1000,
+            anchors: vec![
+                anchor("parse", 10, 400, 1 << 30),
+                anchor("lex", 5, 100, 0),
+                anchor("gone", 1, 50, 0),
+            ],
+            ..ProfileReport::default()
+        };
+        // Twice the timer frequency, so the same ticks take half as long.
+        let current = ProfileReport {
+            elapsed_tsc: 3000,
+            timer_freq: 2000,
+            anchors: vec![
+                anchor("parse", 10, 1000, 1 << 30),
+                anchor("lex", 4, 100, 0),
+                anchor("new", 2, 20, 0),
+            ],
+            ..ProfileReport::default()
+        };
+
+        let comparison = Comparison::new(&baseline, &current);
+        let names: Vec<_> = comparison
+            .anchors
+            .iter()
+            .map(|anchor| anchor.name)
+            .collect();
+        assert_eq!(names, ["parse", "lex", "new", "gone"]);
+        let change = |index: usize| {
+            let anchor = &comparison.anchors[index];
+            (
+                anchor.exclusive_change().map(f64::round),
+                anchor.hits_change().map(f64::round),
+                anchor.throughput_change().map(f64::round),
+            )
+        };
+        assert_eq!(change(0), (Some(25.0), Some(0.0), Some(-20.0)));
+        assert_eq!(change(1), (Some(-50.0), Some(-20.0), None));
+        assert_eq!(change(2), (None, None, None));
+        assert_eq!(comparison.elapsed_change().map(f64::round), Some(50.0));
+
+        let regressions: Vec<_> = comparison
+            .regressions(10.0)
+            .iter()
+            .map(|anchor| anchor.name)
+            .collect();
+        assert_eq!(regressions, ["parse"]);
+
+        let output = comparison.to_string();
+        assert!(
+            output.starts_with("\nCompared to baseline: 1000.0000ms -> 1500.0000ms (+50.00%)\n")
+        );
+        assert!(output.contains(
+            "\n  parse: 400.0000ms -> 500.0000ms (+25.00%), 10 -> 10 hits (+0.00%), 2.50GB/s -> \
+             2.00GB/s (-20.00%)\n"
+        ));
+        assert!(output.contains("\n  new: added, 10.0000ms, 2 hits\n"));
+        assert!(output.ends_with("\n  gone: removed\n"));
+    }
+
+    #[test]
+    fn noise_and_significant_changes() {
+        // 100 hits of each mean, with a standard deviation of about 100 ticks.
+        let hits = |name, mean: u64| AnchorStats {
+            tsc_elapsed_squares: 50 * u128::from(mean - 100).pow(2)
+                + 50 * u128::from(mean + 100).pow(2),
+            ..anchor(name, 100, 100 * mean, 0)
+        };
+        let report = |parse, lex| ProfileReport {
+            elapsed_tsc: 100_000,
+            timer_freq: 1_000_000,
+            anchors: vec![hits("parse", parse), hits("lex", lex)],
+            ..ProfileReport::default()
+        };
+        // Parse jitters by 2%, within the variation between hits, and lex slows by 20%.
+        let comparison = Comparison::new(&report(500, 500), &report(510, 600));
+        let [parse, lex] = &comparison.anchors[..] else {
+            panic!("two anchors");
+        };
+        let std_dev = parse.baseline.and_then(|m| m.hit_std_dev_seconds);
+        assert!(std_dev.is_some_and(|std_dev| (std_dev * 1e6 - 100.5).abs() < 0.1));
+        assert_eq!(parse.significance(), Some(Significance::Noise));
+        assert_eq!(lex.significance(), Some(Significance::Significant));
+        let regressions: Vec<_> = comparison
+            .regressions(1.0)
+            .iter()
+            .map(|anchor| anchor.name)
+            .collect();
+        assert_eq!(regressions, ["lex"]);
+        let output = comparison.to_string();
+        assert!(output.contains("(+2.00%), 100 -> 100 hits (+0.00%), noise\n"));
+        assert!(output.contains("(+20.00%), 100 -> 100 hits (+0.00%), significant\n"));
+
+        // Without squared hit times there's nothing to test, so every change counts.
+        let unrecorded = |tsc| ProfileReport {
+            timer_freq: 1000,
+            anchors: vec![anchor("parse", 10, tsc, 0)],
+            ..ProfileReport::default()
+        };
+        let comparison = Comparison::new(&unrecorded(100), &unrecorded(101));
+        assert_eq!(comparison.anchors[0].significance(), None);
+        assert_eq!(comparison.regressions(0.5).len(), 1);
+    }
+
+    #[test]
+    fn sample_tests() {
+        let before: Vec<_> = (0..20).map(|i| f64::from(i % 5)).collect();
+        let shifted: Vec<_> = before.iter().map(|value| value + 3.0).collect();
+        assert_eq!(
+            Significance::of_samples(&before, &before),
+            Some(Significance::Noise)
+        );
+        assert_eq!(
+            Significance::of_samples(&before, &shifted),
+            Some(Significance::Significant)
+        );
+        assert_eq!(Significance::of_samples(&[1.0], &shifted), None);
+        assert_eq!(Significance::of_samples(&[1.0; 4], &[1.0; 4]), None);
+
+        let runs = |mean: u64, repetitions| RepetitionResults {
+            repetitions,
+            total: Duration::from_nanos(mean * repetitions),
+            squares: u128::from(repetitions) * (u128::from(mean).pow(2) + 100),
+            ..RepetitionResults::default()
+        };
+        let of = |a, b| Significance::of_repetitions(&a, &b);
+        assert_eq!(
+            of(runs(1000, 50), runs(1002, 50)),
+            Some(Significance::Noise)
+        );
+        assert_eq!(
+            of(runs(1000, 50), runs(1010, 50)),
+            Some(Significance::Significant)
+        );
+        // Few runs need a larger difference.
+        assert_eq!(of(runs(1000, 3), runs(1010, 3)), Some(Significance::Noise));
+        assert_eq!(of(runs(1000, 1), runs(1010, 50)), None);
+        assert!((t_critical(0.5) - 12.706).abs() < f64::EPSILON);
+        assert!((t_critical(1000.0) - 1.9624).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,151 @@
+//! Profiler limits.
+//!
+//! Each thread's anchor table is sized for [`ProfilerConfig::max_anchors`] anchors when the thread
+//! creates its first one, so it never reallocates while blocks are being measured. Anchors beyond
+//! the limit are aggregated into one [`OVERFLOW_ANCHOR`] by default, keeping memory use bounded
+//! when names are generated at runtime, or the table can grow instead with
+//! [`AnchorOverflow::Grow`]. [Latency histograms](super::histogram) are off by default, since
+//! each takes a few KiB per anchor and thread.
+
+use super::histogram::DEFAULT_HISTOGRAM_PRECISION;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+/// Name of the anchor which collects the hits of anchors beyond the limit.
+pub const OVERFLOW_ANCHOR: &str = "<other>";
+
+/// Default number of anchors per thread.
+pub const DEFAULT_MAX_ANCHORS: usize = 4096;
+
+/// Default number of distinct [labels](super::labels) formatted by `profile!(fmt = "...")`.
+pub const DEFAULT_MAX_LABELS: usize = 1024;
+
+/// What happens when a thread hits more distinct anchors than the limit.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum AnchorOverflow {
+    /// Record the extra anchors' hits as hits of [`OVERFLOW_ANCHOR`].
+    #[default]
+    Aggregate,
+    /// Grow the anchor table, reallocating it while blocks may be active.
+    Grow,
+}
+
+/// Limits applied to the profiler of every thread.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::config::{set_profiler_config, AnchorOverflow, ProfilerConfig};
+///
+/// set_profiler_config(
+///     ProfilerConfig::new()
+///         .max_anchors(256)
+///         .overflow(AnchorOverflow::Aggregate)
+///         .latency_histograms(true)
+///         .histogram_precision(2),
+/// );
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct ProfilerConfig {
+    max_anchors: usize,
+    overflow: AnchorOverflow,
+    max_labels: usize,
+    latency_histograms: bool,
+    histogram_precision: u32,
+}
+
+impl Default for ProfilerConfig {
+    fn default() -> Self {
+        Self {
+            max_anchors: DEFAULT_MAX_ANCHORS,
+            overflow: AnchorOverflow::default(),
+            max_labels: DEFAULT_MAX_LABELS,
+            latency_histograms: false,
+            histogram_precision: DEFAULT_HISTOGRAM_PRECISION,
+        }
+    }
+}
+
+impl ProfilerConfig {
+    /// Creates the default configuration, with room for [`DEFAULT_MAX_ANCHORS`] anchors.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of distinct anchors each thread makes room for, not counting
+    /// [`OVERFLOW_ANCHOR`].
+    pub fn max_anchors(mut self, max_anchors: usize) -> Self {
+        self.max_anchors = max_anchors;
+        self
+    }
+
+    /// Sets what happens to anchors beyond the limit.
+    pub fn overflow(mut self, overflow: AnchorOverflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Sets the number of distinct labels `profile!(fmt = "...")` formats across all threads. Once
+    /// reached, blocks with new labels are recorded under the label's template instead.
+    pub fn max_labels(mut self, max_labels: usize) -> Self {
+        self.max_labels = max_labels;
+        self
+    }
+
+    /// Sets whether every block's elapsed time is counted in a
+    /// [latency histogram](super::histogram) of its anchor, for percentiles in the report.
+    pub fn latency_histograms(mut self, enabled: bool) -> Self {
+        self.latency_histograms = enabled;
+        self
+    }
+
+    /// Sets the number of histogram buckets per power of two, as a power of two, trading memory
+    /// for accuracy. Histograms created earlier keep their precision.
+    pub fn histogram_precision(mut self, precision: u32) -> Self {
+        self.histogram_precision = precision;
+        self
+    }
+}
+
+static MAX_ANCHORS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_ANCHORS);
+static GROW_ANCHORS: AtomicBool = AtomicBool::new(false);
+static MAX_LABELS: AtomicUsize = AtomicUsize::new(DEFAULT_MAX_LABELS);
+static LATENCY_HISTOGRAMS: AtomicBool = AtomicBool::new(false);
+static HISTOGRAM_PRECISION: AtomicU32 = AtomicU32::new(DEFAULT_HISTOGRAM_PRECISION);
+
+/// Apply `config` to every thread. Threads which have already created anchors keep the size of
+/// their anchor table, but overflow according to the new limit.
+pub fn set_profiler_config(config: ProfilerConfig) {
+    MAX_ANCHORS.store(config.max_anchors, Ordering::Relaxed);
+    GROW_ANCHORS.store(config.overflow == AnchorOverflow::Grow, Ordering::Relaxed);
+    MAX_LABELS.store(config.max_labels, Ordering::Relaxed);
+    HISTOGRAM_PRECISION.store(config.histogram_precision, Ordering::Relaxed);
+    LATENCY_HISTOGRAMS.store(config.latency_histograms, Ordering::Relaxed);
+    super::set_recorder(super::RECORD_HISTOGRAMS, config.latency_histograms);
+}
+
+/// Returns the number of anchors each thread makes room for.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn max_anchors() -> usize {
+    MAX_ANCHORS.load(Ordering::Relaxed)
+}
+
+/// Returns whether anchors beyond the limit are aggregated into [`OVERFLOW_ANCHOR`].
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn aggregate_overflow() -> bool {
+    !GROW_ANCHORS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of distinct labels which may be formatted.
+pub(super) fn max_labels() -> usize {
+    MAX_LABELS.load(Ordering::Relaxed)
+}
+
+/// Returns the precision of new latency histograms, or `None` if they're disabled.
+#[inline]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn latency_histograms() -> Option<u32> {
+    LATENCY_HISTOGRAMS
+        .load(Ordering::Relaxed)
+        .then(|| HISTOGRAM_PRECISION.load(Ordering::Relaxed))
+}
@@ -0,0 +1,302 @@
+//! Hardware performance counters.
+//!
+//! Elapsed time says how long a block took but not why. With the `perf-counters` feature on Linux,
+//! [`set_hardware_counters`] makes every profile block also count CPU cycles, retired
+//! instructions, cache misses and branch mispredictions on its thread through `perf_event_open`,
+//! which the report lists per anchor next to its timestamp counter time. Counting requires
+//! `kernel.perf_event_paranoid` to allow user-space measurement of the process's own threads, and a
+//! processor or hypervisor which exposes the counters; otherwise the report carries a warning.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Hardware events counted while a block was the innermost active block on its thread.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct HardwareCounters {
+    /// CPU cycles.
+    pub cycles: u64,
+    /// Retired instructions.
+    pub instructions: u64,
+    /// Last-level cache misses.
+    pub cache_misses: u64,
+    /// Mispredicted branches.
+    pub branch_misses: u64,
+}
+
+impl HardwareCounters {
+    /// Returns whether no events were counted.
+    #[must_use]
+    pub fn is_zero(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Retired instructions per CPU cycle, or `0.0` if no cycles were counted.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn instructions_per_cycle(&self) -> f64 {
+        if self.cycles == 0 {
+            0.0
+        } else {
+            self.instructions as f64 / self.cycles as f64
+        }
+    }
+
+    /// Applies `op` to each pair of counts of `self` and `other`.
+    pub(super) fn zip(self, other: Self, op: impl Fn(u64, u64) -> u64) -> Self {
+        Self {
+            cycles: op(self.cycles, other.cycles),
+            instructions: op(self.instructions, other.instructions),
+            cache_misses: op(self.cache_misses, other.cache_misses),
+            branch_misses: op(self.branch_misses, other.branch_misses),
+        }
+    }
+
+    /// Applies `op` to each count.
+    pub(super) fn map(self, op: impl Fn(u64) -> u64) -> Self {
+        self.zip(self, |count, _| op(count))
+    }
+}
+
+static HARDWARE_COUNTERS: AtomicBool = AtomicBool::new(false);
+
+/// Count hardware events in every profile block on threads which start a block after this is
+/// enabled. Only supported with the `perf-counters` feature on Linux, and otherwise has no effect.
+/// Reading the counters costs a system call at both ends of each block.
+pub fn set_hardware_counters(enabled: bool) {
+    HARDWARE_COUNTERS.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether profile blocks should count hardware events.
+#[inline]
+#[cfg_attr(not(feature = "perf-counters"), allow(dead_code))]
+pub(super) fn hardware_counters() -> bool {
+    HARDWARE_COUNTERS.load(Ordering::Relaxed)
+}
+
+/// A group of hardware counters opened for the calling thread, read together in one system call.
+#[cfg(feature = "perf-counters")]
+#[derive(Debug)]
+pub(super) struct CounterGroup {
+    /// File descriptors of the opened counters, the group leader first.
+    fds: Vec<std::os::raw::c_int>,
+    /// Event counted by each file descriptor.
+    events: Vec<Event>,
+}
+
+/// The hardware events counted, in the order they're opened.
+#[cfg(feature = "perf-counters")]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Event {
+    Cycles,
+    Instructions,
+    CacheMisses,
+    BranchMisses,
+}
+
+#[cfg(feature = "perf-counters")]
+impl CounterGroup {
+    /// Opens every supported counter for the calling thread. Fails if not even cycles can be
+    /// counted.
+    #[cfg(target_os = "linux")]
+    pub(super) fn open() -> std::io::Result<Self> {
+        let leader = linux::open(Event::Cycles, -1)?;
+        let mut group = Self {
+            fds: vec![leader],
+            events: vec![Event::Cycles],
+        };
+        // Events the processor can't count are left out of the group.
+        for event in [Event::Instructions, Event::CacheMisses, Event::BranchMisses] {
+            if let Ok(fd) = linux::open(event, leader) {
+                group.fds.push(fd);
+                group.events.push(event);
+            }
+        }
+        Ok(group)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(super) fn open() -> std::io::Result<Self> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "hardware counters are only supported on Linux",
+        ))
+    }
+
+    /// Reads the counts since the group was opened.
+    #[inline]
+    pub(super) fn read(&self) -> Option<HardwareCounters> {
+        #[cfg(target_os = "linux")]
+        {
+            let mut values = [0u64; 5];
+            let count = linux::read_group(self.fds[0], &mut values)?;
+            let mut counters = HardwareCounters::default();
+            for (event, &value) in self.events.iter().zip(&values[1..=count]) {
+                match event {
+                    Event::Cycles => counters.cycles = value,
+                    Event::Instructions => counters.instructions = value,
+                    Event::CacheMisses => counters.cache_misses = value,
+                    Event::BranchMisses => counters.branch_misses = value,
+                }
+            }
+            Some(counters)
+        }
+        #[cfg(not(target_os = "linux"))]
+        None
+    }
+}
+
+#[cfg(feature = "perf-counters")]
+impl Drop for CounterGroup {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        for &fd in self.fds.iter().rev() {
+            linux::close(fd);
+        }
+    }
+}
+
+#[cfg(all(feature = "perf-counters", target_os = "linux"))]
+mod linux {
+    use super::Event;
+    use std::os::raw::{c_int, c_long, c_ulong, c_void};
+
+    #[cfg(target_arch = "x86_64")]
+    const SYS_PERF_EVENT_OPEN: c_long = 298;
+    #[cfg(target_arch = "aarch64")]
+    const SYS_PERF_EVENT_OPEN: c_long = 241;
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_FORMAT_GROUP: u64 = 1 << 3;
+    const PERF_FLAG_FD_CLOEXEC: c_ulong = 1 << 3;
+    const EXCLUDE_KERNEL: u64 = 1 << 5;
+    const EXCLUDE_HV: u64 = 1 << 6;
+
+    /// The first version of `struct perf_event_attr`, which every kernel accepts.
+    #[repr(C)]
+    #[derive(Default)]
+    struct PerfEventAttr {
+        kind: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        config1: u64,
+    }
+
+    extern "C" {
+        fn syscall(number: c_long, ...) -> c_long;
+        fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+        #[link_name = "close"]
+        fn close_fd(fd: c_int) -> c_int;
+    }
+
+    /// Opens a counter of `event` for the calling thread on any CPU, in the group led by `leader`
+    /// or as a new group leader if `leader` is `-1`.
+    pub(super) fn open(event: Event, leader: c_int) -> std::io::Result<c_int> {
+        let config = match event {
+            Event::Cycles => 0,
+            Event::Instructions => 1,
+            Event::CacheMisses => 3,
+            Event::BranchMisses => 5,
+        };
+        let attr = PerfEventAttr {
+            kind: PERF_TYPE_HARDWARE,
+            size: u32::try_from(std::mem::size_of::<PerfEventAttr>()).unwrap_or(64),
+            config,
+            read_format: PERF_FORMAT_GROUP,
+            flags: EXCLUDE_KERNEL | EXCLUDE_HV,
+            ..PerfEventAttr::default()
+        };
+        // SAFETY: `attr` is a valid `perf_event_attr` of the size it declares for the duration of
+        // the call.
+        let fd = unsafe {
+            syscall(
+                SYS_PERF_EVENT_OPEN,
+                &raw const attr,
+                0 as c_int,
+                -1 as c_int,
+                leader,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        c_int::try_from(fd).map_err(|_| std::io::Error::other("invalid file descriptor"))
+    }
+
+    /// Reads the counts of the group led by `leader` into `values`, which starts with the number of
+    /// counts read, returning that number.
+    pub(super) fn read_group(leader: c_int, values: &mut [u64; 5]) -> Option<usize> {
+        // SAFETY: `values` is valid for writes of its whole size.
+        let len = unsafe { read(leader, values.as_mut_ptr().cast(), size_of_val(values)) };
+        if len < 8 {
+            return None;
+        }
+        usize::try_from(values[0])
+            .ok()
+            .filter(|&count| count < values.len())
+    }
+
+    pub(super) fn close(fd: c_int) {
+        // SAFETY: `fd` was opened by the counter group and is closed once.
+        unsafe {
+            close_fd(fd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counter_arithmetic() {
+        let start = HardwareCounters {
+            cycles: 100,
+            instructions: 150,
+            cache_misses: 2,
+            branch_misses: 1,
+        };
+        let end = HardwareCounters {
+            cycles: 500,
+            instructions: 950,
+            cache_misses: 5,
+            branch_misses: 4,
+        };
+        let elapsed = end.zip(start, u64::wrapping_sub);
+        assert_eq!(
+            elapsed,
+            HardwareCounters {
+                cycles: 400,
+                instructions: 800,
+                cache_misses: 3,
+                branch_misses: 3,
+            }
+        );
+        assert!((elapsed.instructions_per_cycle() - 2.0).abs() < 1e-9);
+        assert_eq!(elapsed.map(|count| count * 2).cycles, 800);
+        assert!(HardwareCounters::default().is_zero());
+        assert!(HardwareCounters::default().instructions_per_cycle().abs() < 1e-9);
+    }
+
+    #[cfg(all(feature = "perf-counters", target_os = "linux"))]
+    #[test]
+    fn counter_group() {
+        // Counters are unavailable in many containers and virtual machines.
+        let Ok(group) = CounterGroup::open() else {
+            return;
+        };
+        let Some(start) = group.read() else {
+            return;
+        };
+        std::hint::black_box((0..100_000u64).fold(0, |acc, n| acc ^ std::hint::black_box(n)));
+        let end = group.read().expect("valid read");
+        assert!(end.cycles > start.cycles);
+        assert!(group.events.len() < 2 || end.instructions > start.instructions + 100_000);
+    }
+}
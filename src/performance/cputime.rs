@@ -0,0 +1,97 @@
+//! CPU time and other clocks read with `clock_gettime`.
+
+#[cfg(target_os = "linux")]
+use std::os::raw::c_int;
+
+#[cfg(target_os = "linux")]
+const CLOCK_MONOTONIC: c_int = 1;
+#[cfg(target_os = "linux")]
+const CLOCK_PROCESS_CPUTIME_ID: c_int = 2;
+#[cfg(target_os = "linux")]
+const CLOCK_THREAD_CPUTIME_ID: c_int = 3;
+#[cfg(target_os = "linux")]
+const CLOCK_BOOTTIME: c_int = 7;
+
+/// Reads the clock `clock_id` in nanoseconds, or `0` if it can't be read.
+#[cfg(target_os = "linux")]
+fn clock_ns(clock_id: c_int) -> u64 {
+    use std::os::raw::c_long;
+
+    #[repr(C)]
+    struct Timespec {
+        tv_sec: c_long,
+        tv_nsec: c_long,
+    }
+
+    extern "C" {
+        fn clock_gettime(clock_id: c_int, time: *mut Timespec) -> c_int;
+    }
+
+    let mut time = Timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid `timespec` for the duration of the call.
+    if unsafe { clock_gettime(clock_id, &raw mut time) } != 0 {
+        return 0;
+    }
+    let secs = u64::try_from(time.tv_sec).unwrap_or(0);
+    let nanos = u64::try_from(time.tv_nsec).unwrap_or(0);
+    secs * 1_000_000_000 + nanos
+}
+
+/// Returns the CPU time consumed by the calling thread in nanoseconds, or `0` where the thread CPU
+/// clock isn't available.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn thread_cpu_time_ns() -> u64 {
+    #[cfg(target_os = "linux")]
+    {
+        clock_ns(CLOCK_THREAD_CPUTIME_ID)
+    }
+    #[cfg(not(target_os = "linux"))]
+    0
+}
+
+/// Returns the CPU time consumed by every thread of the process in nanoseconds.
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "startup"), allow(dead_code))]
+pub(super) fn process_cpu_time_ns() -> u64 {
+    clock_ns(CLOCK_PROCESS_CPUTIME_ID)
+}
+
+/// Returns the monotonic time in nanoseconds, which doesn't advance while the system is suspended.
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(any(feature = "perf", feature = "startup")), allow(dead_code))]
+pub(super) fn monotonic_ns() -> u64 {
+    clock_ns(CLOCK_MONOTONIC)
+}
+
+/// Returns the time since the system booted in nanoseconds, including time suspended.
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(any(feature = "perf", feature = "startup")), allow(dead_code))]
+pub(super) fn boot_time_ns() -> u64 {
+    clock_ns(CLOCK_BOOTTIME)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn thread_cpu_time() {
+        let start = thread_cpu_time_ns();
+        let spin_start = Instant::now();
+        while spin_start.elapsed() < Duration::from_millis(20) {
+            std::hint::black_box(0);
+        }
+        let spun = thread_cpu_time_ns();
+        std::thread::sleep(Duration::from_millis(50));
+        let slept = thread_cpu_time_ns();
+
+        assert!(spun - start >= 10_000_000);
+        assert!(slept - spun < 25_000_000);
+        assert!(process_cpu_time_ns() >= slept);
+        assert!(monotonic_ns() > 0 && boot_time_ns() > 0);
+    }
+}
@@ -0,0 +1,134 @@
+//! CSV output.
+//!
+//! Writes one row per anchor of a [`ProfileReport`] with its hits, exclusive and inclusive
+//! timestamp counter ticks, bytes processed and percentage of the total elapsed time, so results
+//! can be imported into spreadsheets or compared across runs by CI scripts.
+
+use super::{ProfileReport, ReportExporter};
+use std::{
+    borrow::Cow,
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Column names of the header row.
+const HEADER: &str = "name,hits,exclusive_tsc,inclusive_tsc,bytes,percent";
+
+/// Writes the anchors of `report` to `writer` as CSV, after a header row.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{csv::write_csv, AnchorStats, ProfileReport};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let report = ProfileReport {
+///     elapsed_tsc: 200,
+///     anchors: vec![AnchorStats {
+///         name: "parse",
+///         hit_count: 2,
+///         tsc_elapsed_exclusive: 50,
+///         tsc_elapsed_inclusive: 80,
+///         ..AnchorStats::default()
+///     }],
+///     ..ProfileReport::default()
+/// };
+/// let mut output = Vec::new();
+/// write_csv(&report, &mut output)?;
+/// assert!(String::from_utf8_lossy(&output).ends_with("parse,2,50,80,0,25.0000\n"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_csv(report: &ProfileReport, mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "{HEADER}")?;
+    for anchor in &report.anchors {
+        #[allow(clippy::cast_precision_loss)]
+        let percent = if report.elapsed_tsc == 0 {
+            0.0
+        } else {
+            100.0 * anchor.tsc_elapsed_exclusive as f64 / report.elapsed_tsc as f64
+        };
+        writeln!(
+            writer,
+            "{},{},{},{},{},{percent:.4}",
+            escape(anchor.name),
+            anchor.hit_count,
+            anchor.tsc_elapsed_exclusive,
+            anchor.tsc_elapsed_inclusive,
+            anchor.byte_count,
+        )?;
+    }
+    Ok(())
+}
+
+/// Quotes `field` if it contains a separator, quote or line break, doubling any quotes.
+fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Saves every finished report as a CSV file.
+#[derive(Debug)]
+#[must_use]
+pub struct CsvExporter {
+    path: PathBuf,
+}
+
+impl CsvExporter {
+    /// Creates an exporter saving reports to the file at `path`, replacing it on every export.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ReportExporter for CsvExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        write_csv(report, &mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::AnchorStats;
+
+    #[test]
+    fn csv_output() {
+        let anchor = |name, tsc_elapsed_exclusive, byte_count| AnchorStats {
+            name,
+            hit_count: 1,
+            tsc_elapsed_exclusive,
+            tsc_elapsed_inclusive: 2 * tsc_elapsed_exclusive,
+            byte_count,
+            ..AnchorStats::default()
+        };
+        let report = ProfileReport {
+            elapsed_tsc: 400,
+            anchors: vec![anchor("main", 100, 0), anchor("parse<\"a\", b>", 300, 4096)],
+            ..ProfileReport::default()
+        };
+
+        let mut output = Vec::new();
+        write_csv(&report, &mut output).expect("valid write");
+        assert_eq!(
+            String::from_utf8(output).expect("valid utf8"),
+            "name,hits,exclusive_tsc,inclusive_tsc,bytes,percent\n\
+             main,1,100,200,0,25.0000\n\
+             \"parse<\"\"a\"\", b>\",1,300,600,4096,75.0000\n"
+        );
+
+        let mut empty = Vec::new();
+        write_csv(&ProfileReport::default(), &mut empty).expect("valid write");
+        assert_eq!(empty, format!("{HEADER}\n").into_bytes());
+    }
+}
@@ -0,0 +1,172 @@
+//! Graphviz DOT output.
+//!
+//! Writes the call graph of a [`ProfileReport`] as a DOT digraph, with a node per anchor labelled
+//! and sized by its exclusive time and an edge per caller and callee pair labelled with its calls
+//! and inclusive time, so the call structure can be rendered with `dot -Tsvg`.
+
+use super::{ProfileReport, ReportExporter};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Font size of an anchor taking none of the total time, which grows with the anchor's share.
+const MIN_FONT_SIZE: f64 = 10.0;
+/// Additional font size of an anchor taking all of the total time.
+const FONT_SIZE_RANGE: f64 = 30.0;
+
+/// Writes the call graph of `report` to `writer` in DOT format.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{dot::write_dot, AnchorStats, CallStats, ProfileReport};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let anchor = |name, tsc_elapsed_exclusive| AnchorStats {
+///     name,
+///     hit_count: 1,
+///     tsc_elapsed_exclusive,
+///     ..AnchorStats::default()
+/// };
+/// let report = ProfileReport {
+///     elapsed_tsc: 100,
+///     timer_freq: 1000,
+///     anchors: vec![anchor("main", 20), anchor("parse", 80)],
+///     calls: vec![CallStats {
+///         caller: "main",
+///         callee: "parse",
+///         call_count: 4,
+///         tsc_elapsed_inclusive: 80,
+///     }],
+///     ..ProfileReport::default()
+/// };
+/// let mut output = Vec::new();
+/// write_dot(&report, &mut output)?;
+/// assert!(String::from_utf8_lossy(&output).contains("\"main\" -> \"parse\""));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+#[allow(clippy::cast_precision_loss)]
+pub fn write_dot(report: &ProfileReport, mut writer: impl Write) -> io::Result<()> {
+    let share = |tsc: u64| {
+        if report.elapsed_tsc == 0 {
+            0.0
+        } else {
+            (tsc as f64 / report.elapsed_tsc as f64).min(1.0)
+        }
+    };
+    let ms = |tsc: u64| {
+        if report.timer_freq == 0 {
+            0.0
+        } else {
+            1000.0 * tsc as f64 / report.timer_freq as f64
+        }
+    };
+
+    writeln!(writer, "digraph profile {{")?;
+    writeln!(
+        writer,
+        "  node [shape=box, style=filled, fillcolor=\"#fff3e0\"];"
+    )?;
+    for anchor in &report.anchors {
+        let share = share(anchor.tsc_elapsed_exclusive);
+        writeln!(
+            writer,
+            "  \"{0}\" [label=\"{0}\\n{1:.4}ms ({2:.2}%)\\n{3} hits\", fontsize={4:.1}];",
+            escape(anchor.name),
+            ms(anchor.tsc_elapsed_exclusive),
+            100.0 * share,
+            anchor.hit_count,
+            MIN_FONT_SIZE + FONT_SIZE_RANGE * share,
+        )?;
+    }
+    for call in &report.calls {
+        let share = share(call.tsc_elapsed_inclusive);
+        writeln!(
+            writer,
+            "  \"{}\" -> \"{}\" [label=\"{}x, {:.4}ms\", penwidth={:.2}];",
+            escape(call.caller),
+            escape(call.callee),
+            call.call_count,
+            ms(call.tsc_elapsed_inclusive),
+            1.0 + 4.0 * share,
+        )?;
+    }
+    writeln!(writer, "}}")
+}
+
+/// Escapes quotes and backslashes in a quoted DOT identifier, and writes line breaks as `\n`.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Saves every finished report as a DOT file.
+#[derive(Debug)]
+#[must_use]
+pub struct DotExporter {
+    path: PathBuf,
+}
+
+impl DotExporter {
+    /// Creates an exporter saving reports to the file at `path`, replacing it on every export.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ReportExporter for DotExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        write_dot(report, &mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::{AnchorStats, CallStats};
+
+    #[test]
+    fn dot_output() {
+        let anchor = |name, hit_count, tsc_elapsed_exclusive| AnchorStats {
+            name,
+            hit_count,
+            tsc_elapsed_exclusive,
+            ..AnchorStats::default()
+        };
+        let report = ProfileReport {
+            elapsed_tsc: 400,
+            timer_freq: 1000,
+            anchors: vec![anchor("main", 1, 100), anchor("parse<\"a\">", 3, 300)],
+            calls: vec![CallStats {
+                caller: "main",
+                callee: "parse<\"a\">",
+                call_count: 3,
+                tsc_elapsed_inclusive: 300,
+            }],
+            ..ProfileReport::default()
+        };
+
+        let mut output = Vec::new();
+        write_dot(&report, &mut output).expect("valid write");
+        assert_eq!(
+            String::from_utf8(output).expect("valid utf8"),
+            "digraph profile {\n  \
+             node [shape=box, style=filled, fillcolor=\"#fff3e0\"];\n  \
+             \"main\" [label=\"main\\n100.0000ms (25.00%)\\n1 hits\", fontsize=17.5];\n  \
+             \"parse<\\\"a\\\">\" [label=\"parse<\\\"a\\\">\\n300.0000ms (75.00%)\\n3 hits\", \
+             fontsize=32.5];\n  \
+             \"main\" -> \"parse<\\\"a\\\">\" [label=\"3x, 300.0000ms\", penwidth=4.00];\n\
+             }\n"
+        );
+    }
+}
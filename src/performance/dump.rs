@@ -0,0 +1,662 @@
+//! Binary profile dumps.
+//!
+//! A [`ProfileDump`] stores the anchor statistics of a finished report in a compact binary file,
+//! optionally compressed, so captures can be archived and compared later. Enable the `lz4` feature
+//! for `Compression::Lz4`.
+//!
+//! A dump starts with the magic bytes `ULPD`, a little-endian `u16` version and a compression
+//! byte. The body holds the elapsed time and timer frequency, a table of every name used, the
+//! anchors, which refer to their names by index, and the trace events recorded for each hit, if
+//! any, with their call stack IDs. Large sessions with trace events load far faster than their JSON
+//! equivalents.
+
+use super::{
+    counters::HardwareCounters, flight::TraceEvent, AnchorStats, ProfileReport, ReportExporter,
+};
+use crate::{
+    intern::intern_static,
+    io::bytes::{ByteReader, ByteWriter},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: [u8; 4] = *b"ULPD";
+const VERSION: u16 = 10;
+
+/// Oldest version which can still be read, from before thread CPU time was recorded.
+const MIN_VERSION: u16 = 1;
+
+/// Compression applied to the body of a dump.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub enum Compression {
+    /// Store the dump uncompressed.
+    #[default]
+    None,
+    /// Compress the dump as an LZ4 frame.
+    #[cfg(feature = "lz4")]
+    Lz4,
+}
+
+impl Compression {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::None => 0,
+            #[cfg(feature = "lz4")]
+            Self::Lz4 => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> io::Result<Self> {
+        match byte {
+            0 => Ok(Self::None),
+            #[cfg(feature = "lz4")]
+            1 => Ok(Self::Lz4),
+            #[cfg(not(feature = "lz4"))]
+            1 => Err(invalid("dump is LZ4 compressed, enable the `lz4` feature")),
+            _ => Err(invalid("unknown dump compression")),
+        }
+    }
+}
+
+/// A profile report in a form which can be written to and read back from a binary file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::dump::{Compression, ProfileDump};
+///
+/// # fn main() -> std::io::Result<()> {
+/// # let report = util_lib_rs::performance::ProfileReport::default();
+/// ProfileDump::new(report).save("profile.dump", Compression::None)?;
+/// let report = ProfileDump::load("profile.dump")?.report;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+#[must_use]
+pub struct ProfileDump {
+    /// The stored report. Only the elapsed time, timer frequency and anchor statistics are dumped.
+    pub report: ProfileReport,
+    /// Every profile block recorded while profiling, in the order they started, if trace events
+    /// were recorded.
+    pub events: Vec<TraceEvent>,
+    /// Timestamp counter when profiling began, which event timestamps are stored relative to.
+    pub start_tsc: u64,
+}
+
+impl ProfileDump {
+    /// Creates a dump of `report`.
+    pub fn new(report: ProfileReport) -> Self {
+        Self {
+            report,
+            events: Vec::new(),
+            start_tsc: 0,
+        }
+    }
+
+    /// Adds the trace `events` recorded while profiling, which began at timestamp counter
+    /// `start_tsc`.
+    pub fn events(mut self, events: Vec<TraceEvent>, start_tsc: u64) -> Self {
+        self.events = events;
+        self.start_tsc = start_tsc;
+        self
+    }
+
+    /// Writes the dump to `writer` using the given `compression`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if writing fails.
+    pub fn write_to(&self, writer: impl Write, compression: Compression) -> io::Result<()> {
+        write_dump(
+            &self.report,
+            &self.events,
+            self.start_tsc,
+            writer,
+            compression,
+        )
+    }
+
+    /// Reads a dump written by [`ProfileDump::write_to`] from `reader`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if reading fails or the data is not a valid dump.
+    pub fn read_from(mut reader: impl Read) -> io::Result<Self> {
+        let mut header = [0; 7];
+        reader.read_exact(&mut header)?;
+        if header[..4] != MAGIC {
+            return Err(invalid("not a profile dump"));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(invalid("unsupported profile dump version"));
+        }
+        let compression = Compression::from_byte(header[6])?;
+
+        let mut body = Vec::new();
+        reader.read_to_end(&mut body)?;
+        match compression {
+            Compression::None => Self::decode_body(&body, version),
+            #[cfg(feature = "lz4")]
+            Compression::Lz4 => Self::decode_body(&super::lz4::decompress(&body)?, version),
+        }
+    }
+
+    /// Saves the dump to the file at `path`, replacing it if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be created or written.
+    pub fn save(&self, path: impl AsRef<Path>, compression: Compression) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer, compression)?;
+        writer.flush()
+    }
+
+    /// Loads a dump from the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or is not a valid dump.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::read_from(BufReader::new(File::open(path)?))
+    }
+
+    /// Loads and combines the dumps at `paths` into one aggregate report.
+    ///
+    /// Anchors are matched by name, so the same block recorded by different processes, shards or
+    /// runs is summed into a single entry, in the order each name was first seen. Timestamp counts
+    /// are rescaled to the timer frequency of the first dump.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any dump can't be loaded.
+    pub fn merge<P: AsRef<Path>>(paths: impl IntoIterator<Item = P>) -> io::Result<ProfileReport> {
+        let mut merged = ProfileReport::default();
+        for path in paths {
+            merged.merge(&Self::load(path)?.report);
+        }
+        Ok(merged)
+    }
+
+    fn decode_body(body: &[u8], version: u16) -> io::Result<Self> {
+        let mut body = ByteReader::from_slice(body);
+        let elapsed_tsc = body.read_u64()?;
+        let timer_freq = body.read_u64()?;
+        let mut names = Vec::new();
+        if version >= 9 {
+            let name_count = body.read_u32()? as usize;
+            names.reserve(name_count.min(4096));
+            for _ in 0..name_count {
+                names.push(read_name(&mut body)?);
+            }
+        }
+        let name = |body: &mut ByteReader<_>| {
+            if version >= 9 {
+                let index = body.read_u32()? as usize;
+                names
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| invalid("invalid name index"))
+            } else {
+                read_name(body)
+            }
+        };
+
+        let anchor_count = body.read_u32()? as usize;
+        let mut anchors = Vec::with_capacity(anchor_count.min(4096));
+        for _ in 0..anchor_count {
+            let mut anchor = AnchorStats {
+                name: name(&mut body)?,
+                hit_count: body.read_u64()?,
+                byte_count: body.read_u64()?,
+                item_count: body.read_u64()?,
+                tsc_elapsed_exclusive: body.read_u64()?,
+                tsc_elapsed_inclusive: body.read_u64()?,
+                ..AnchorStats::default()
+            };
+            if version >= 2 {
+                anchor.cpu_ns_exclusive = body.read_u64()?;
+                anchor.cpu_ns_inclusive = body.read_u64()?;
+            }
+            if version >= 3 {
+                anchor.tsc_elapsed_squares = body.read_u128()?;
+            }
+            if version >= 4 {
+                anchor.tsc_min = body.read_u64()?;
+                anchor.tsc_max = body.read_u64()?;
+            }
+            if version >= 5 {
+                anchor.alloc_count = body.read_u64()?;
+                anchor.alloc_bytes = body.read_u64()?;
+                anchor.freed_bytes = body.read_u64()?;
+            }
+            if version >= 6 {
+                anchor.counters = HardwareCounters {
+                    cycles: body.read_u64()?,
+                    instructions: body.read_u64()?,
+                    cache_misses: body.read_u64()?,
+                    branch_misses: body.read_u64()?,
+                };
+            }
+            if version >= 7 {
+                anchor.soft_page_faults = body.read_u64()?;
+                anchor.hard_page_faults = body.read_u64()?;
+            }
+            if version >= 8 {
+                anchor.voluntary_context_switches = body.read_u64()?;
+                anchor.involuntary_context_switches = body.read_u64()?;
+            }
+            anchors.push(anchor);
+        }
+
+        let mut start_tsc = 0;
+        let mut events = Vec::new();
+        if version >= 9 {
+            start_tsc = body.read_u64()?;
+            let event_count = body.read_u32()? as usize;
+            events.reserve(event_count.min(65_536));
+            for _ in 0..event_count {
+                let name = name(&mut body)?;
+                let start = start_tsc.saturating_add(body.read_u64()?);
+                let mut event = TraceEvent {
+                    name,
+                    start_tsc: start,
+                    end_tsc: start.saturating_add(body.read_u64()?),
+                    depth: body.read_u32()? as usize,
+                    stack_id: None,
+                };
+                if version >= 10 && body.read_u8()? != 0 {
+                    event.stack_id = Some(body.read_u64()?);
+                }
+                events.push(event);
+            }
+        }
+        if !body.is_at_end()? {
+            return Err(invalid("trailing data in profile dump"));
+        }
+        let report = ProfileReport {
+            elapsed_tsc,
+            timer_freq,
+            anchors,
+            ..ProfileReport::default()
+        };
+        Ok(Self::new(report).events(events, start_tsc))
+    }
+}
+
+fn write_dump(
+    report: &ProfileReport,
+    events: &[TraceEvent],
+    start_tsc: u64,
+    mut writer: impl Write,
+    compression: Compression,
+) -> io::Result<()> {
+    let body = encode_body(report, events, start_tsc)?;
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())?;
+    writer.write_all(&[compression.to_byte()])?;
+    match compression {
+        Compression::None => writer.write_all(&body),
+        #[cfg(feature = "lz4")]
+        Compression::Lz4 => writer.write_all(&super::lz4::compress(&body)),
+    }
+}
+
+fn encode_body(
+    report: &ProfileReport,
+    events: &[TraceEvent],
+    start_tsc: u64,
+) -> io::Result<Vec<u8>> {
+    let mut names = Vec::new();
+    let mut indices = HashMap::new();
+    for name in report
+        .anchors
+        .iter()
+        .map(|anchor| anchor.name)
+        .chain(events.iter().map(|event| event.name))
+    {
+        indices.entry(name).or_insert_with(|| {
+            names.push(name);
+            names.len() - 1
+        });
+    }
+
+    let mut body = ByteWriter::new(Vec::new());
+    body.write_u64(report.elapsed_tsc)?;
+    body.write_u64(report.timer_freq)?;
+    write_len(&mut body, names.len())?;
+    for name in &names {
+        body.write_str(name)?;
+    }
+    write_len(&mut body, report.anchors.len())?;
+    for anchor in &report.anchors {
+        write_len(&mut body, indices[anchor.name])?;
+        for value in [
+            anchor.hit_count,
+            anchor.byte_count,
+            anchor.item_count,
+            anchor.tsc_elapsed_exclusive,
+            anchor.tsc_elapsed_inclusive,
+            anchor.cpu_ns_exclusive,
+            anchor.cpu_ns_inclusive,
+        ] {
+            body.write_u64(value)?;
+        }
+        body.write_u128(anchor.tsc_elapsed_squares)?;
+        for value in [
+            anchor.tsc_min,
+            anchor.tsc_max,
+            anchor.alloc_count,
+            anchor.alloc_bytes,
+            anchor.freed_bytes,
+            anchor.counters.cycles,
+            anchor.counters.instructions,
+            anchor.counters.cache_misses,
+            anchor.counters.branch_misses,
+            anchor.soft_page_faults,
+            anchor.hard_page_faults,
+            anchor.voluntary_context_switches,
+            anchor.involuntary_context_switches,
+        ] {
+            body.write_u64(value)?;
+        }
+    }
+    body.write_u64(start_tsc)?;
+    write_len(&mut body, events.len())?;
+    for event in events {
+        write_len(&mut body, indices[event.name])?;
+        body.write_u64(event.start_tsc.saturating_sub(start_tsc))?;
+        body.write_u64(event.end_tsc.saturating_sub(event.start_tsc))?;
+        write_len(&mut body, event.depth)?;
+        match event.stack_id {
+            Some(stack_id) => {
+                body.write_u8(1)?;
+                body.write_u64(stack_id)?;
+            }
+            None => body.write_u8(0)?,
+        }
+    }
+    Ok(body.into_inner())
+}
+
+/// Saves every finished report as a dump file.
+#[derive(Debug)]
+#[must_use]
+pub struct DumpExporter {
+    path: PathBuf,
+    compression: Compression,
+}
+
+impl DumpExporter {
+    /// Creates an exporter saving reports to the file at `path`, replacing it on every export.
+    pub fn new(path: impl Into<PathBuf>, compression: Compression) -> Self {
+        Self {
+            path: path.into(),
+            compression,
+        }
+    }
+}
+
+impl ReportExporter for DumpExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        ProfileDump::new(report.clone()).save(&self.path, self.compression)
+    }
+}
+
+/// Writes `report` to `writer` as an uncompressed dump without trace events, for loading with
+/// [`read_binary`].
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{dump, ProfileReport};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let report = ProfileReport {
+///     elapsed_tsc: 1_000,
+///     timer_freq: 1_000,
+///     ..ProfileReport::default()
+/// };
+/// let mut bytes = Vec::new();
+/// dump::write_binary(&report, &mut bytes)?;
+/// assert_eq!(dump::read_binary(&bytes[..])?, report);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_binary(report: &ProfileReport, writer: impl Write) -> io::Result<()> {
+    write_dump(report, &[], 0, writer, Compression::None)
+}
+
+/// Reads the report of a dump written by [`write_binary`] or [`ProfileDump::write_to`] from
+/// `reader`, dropping any trace events.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or the data is not a valid dump.
+pub fn read_binary(reader: impl Read) -> io::Result<ProfileReport> {
+    ProfileDump::read_from(reader).map(|dump| dump.report)
+}
+
+fn read_name(body: &mut ByteReader<impl Read>) -> io::Result<&'static str> {
+    let name = body.read_str().map_err(|err| match err.kind() {
+        io::ErrorKind::InvalidData => invalid("invalid anchor name"),
+        _ => err,
+    })?;
+    Ok(intern_static(&name))
+}
+
+fn write_len(body: &mut ByteWriter<Vec<u8>>, len: usize) -> io::Result<()> {
+    let len = u32::try_from(len).map_err(|_| invalid("profile dump section too large"))?;
+    body.write_u32(len)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ProfileReport {
+        ProfileReport {
+            elapsed_tsc: 1_000,
+            timer_freq: 3_000_000_000,
+            anchors: vec![
+                AnchorStats {
+                    name: "parse",
+                    hit_count: 3,
+                    byte_count: 4096,
+                    item_count: 12,
+                    tsc_elapsed_exclusive: 400,
+                    tsc_elapsed_inclusive: 700,
+                    cpu_ns_exclusive: 90,
+                    cpu_ns_inclusive: 150,
+                    tsc_elapsed_squares: 180_000,
+                    tsc_min: 100,
+                    tsc_max: 400,
+                    alloc_count: 12,
+                    alloc_bytes: 4096,
+                    freed_bytes: 1024,
+                    counters: HardwareCounters {
+                        cycles: 5000,
+                        instructions: 8000,
+                        cache_misses: 30,
+                        branch_misses: 20,
+                    },
+                    soft_page_faults: 64,
+                    hard_page_faults: 2,
+                    voluntary_context_switches: 5,
+                    involuntary_context_switches: 1,
+                },
+                AnchorStats {
+                    name: "dump::tokenize",
+                    hit_count: 12,
+                    tsc_elapsed_exclusive: 300,
+                    tsc_elapsed_inclusive: 300,
+                    ..AnchorStats::default()
+                },
+            ],
+            ..ProfileReport::default()
+        }
+    }
+
+    fn round_trip(compression: Compression) {
+        let events = vec![
+            TraceEvent {
+                name: "parse",
+                start_tsc: 5_100,
+                end_tsc: 5_800,
+                depth: 0,
+                stack_id: Some(0x1234_5678_9abc_def0),
+            },
+            TraceEvent {
+                name: "dump::tokenize",
+                start_tsc: 5_200,
+                end_tsc: 5_500,
+                depth: 1,
+                stack_id: Some(7),
+            },
+            TraceEvent {
+                name: "dump::events_only",
+                start_tsc: 5_900,
+                end_tsc: 5_950,
+                depth: 0,
+                stack_id: None,
+            },
+        ];
+        let dump = ProfileDump::new(report()).events(events, 5_000);
+        let mut buf = Vec::new();
+        dump.write_to(&mut buf, compression).expect("valid write");
+        assert_eq!(buf[6], compression.to_byte());
+        assert_eq!(ProfileDump::read_from(&buf[..]).expect("valid dump"), dump);
+    }
+
+    #[test]
+    fn binary_report() {
+        let mut bytes = Vec::new();
+        write_binary(&report(), &mut bytes).expect("valid write");
+        // Names are stored once in the string table and referred to by index.
+        let text = String::from_utf8_lossy(&bytes);
+        assert_eq!(text.matches("dump::tokenize").count(), 1);
+        assert_eq!(read_binary(&bytes[..]).expect("valid dump"), report());
+    }
+
+    #[test]
+    fn dump_round_trip() {
+        round_trip(Compression::None);
+        #[cfg(feature = "lz4")]
+        round_trip(Compression::Lz4);
+    }
+
+    #[test]
+    fn read_version_1_dumps() {
+        let mut report = report();
+        report.anchors.truncate(1);
+        // Version 1 stores names inline, and anchors end before the CPU times and per-hit
+        // statistics.
+        let anchor = &report.anchors[0];
+        let mut buf = ByteWriter::new(b"ULPD\x01\x00\x00".to_vec());
+        for value in [report.elapsed_tsc, report.timer_freq] {
+            buf.write_u64(value).expect("valid write");
+        }
+        buf.write_u32(1).expect("valid write");
+        buf.write_str(anchor.name).expect("valid write");
+        for value in [
+            anchor.hit_count,
+            anchor.byte_count,
+            anchor.item_count,
+            anchor.tsc_elapsed_exclusive,
+            anchor.tsc_elapsed_inclusive,
+        ] {
+            buf.write_u64(value).expect("valid write");
+        }
+        let buf = buf.into_inner();
+
+        report.anchors[0].cpu_ns_exclusive = 0;
+        report.anchors[0].cpu_ns_inclusive = 0;
+        report.anchors[0].tsc_elapsed_squares = 0;
+        report.anchors[0].tsc_min = 0;
+        report.anchors[0].tsc_max = 0;
+        report.anchors[0].alloc_count = 0;
+        report.anchors[0].alloc_bytes = 0;
+        report.anchors[0].freed_bytes = 0;
+        report.anchors[0].counters = HardwareCounters::default();
+        report.anchors[0].soft_page_faults = 0;
+        report.anchors[0].hard_page_faults = 0;
+        report.anchors[0].voluntary_context_switches = 0;
+        report.anchors[0].involuntary_context_switches = 0;
+        assert_eq!(
+            ProfileDump::read_from(&buf[..]).expect("valid dump").report,
+            report
+        );
+    }
+
+    #[test]
+    fn merge_dumps() {
+        let dir = std::env::temp_dir();
+        let paths: Vec<_> = (0..2)
+            .map(|i| dir.join(format!("util_lib_rs_merge_{}_{i}.dump", std::process::id())))
+            .collect();
+        ProfileDump::new(report())
+            .save(&paths[0], Compression::None)
+            .expect("valid save");
+        let mut shard = report();
+        shard.timer_freq /= 2;
+        shard.anchors.swap(0, 1);
+        shard.anchors[0].name = "shard_only";
+        ProfileDump::new(shard)
+            .save(&paths[1], Compression::None)
+            .expect("valid save");
+
+        let merged = ProfileDump::merge(&paths).expect("valid dumps");
+        for path in &paths {
+            let _ = std::fs::remove_file(path);
+        }
+
+        assert_eq!(merged.timer_freq, 3_000_000_000);
+        assert_eq!(merged.elapsed_tsc, 3_000);
+        let names: Vec<_> = merged.anchors.iter().map(|anchor| anchor.name).collect();
+        assert_eq!(names, ["parse", "dump::tokenize", "shard_only"]);
+        assert_eq!(merged.anchors[0].hit_count, 6);
+        assert_eq!(merged.anchors[0].byte_count, 8192);
+        assert_eq!(merged.anchors[0].tsc_elapsed_exclusive, 1_200);
+        assert_eq!(merged.anchors[1].hit_count, 12);
+        assert_eq!(merged.anchors[2].tsc_elapsed_inclusive, 600);
+    }
+
+    #[test]
+    fn invalid_dumps() {
+        assert!(ProfileDump::read_from(&b"nope"[..]).is_err());
+        assert!(ProfileDump::read_from(&b"ULPD\x0b\x00\x00"[..]).is_err());
+
+        let mut buf = Vec::new();
+        ProfileDump::new(report())
+            .write_to(&mut buf, Compression::None)
+            .expect("valid write");
+        buf.truncate(buf.len() - 1);
+        assert!(ProfileDump::read_from(&buf[..]).is_err());
+
+        // The first anchor refers to a name past the end of the string table.
+        let mut buf = Vec::new();
+        ProfileDump::new(report())
+            .write_to(&mut buf, Compression::None)
+            .expect("valid write");
+        let table_len = 4 + 4 + "parse".len() + 4 + "dump::tokenize".len();
+        let index = 7 + 16 + table_len + 4;
+        buf[index..index + 4].copy_from_slice(&2u32.to_le_bytes());
+        let err = ProfileDump::read_from(&buf[..]).expect_err("invalid dump");
+        assert_eq!(err.to_string(), "invalid name index");
+    }
+}
@@ -0,0 +1,282 @@
+//! Pluggable report exporters.
+//!
+//! Implement [`ReportExporter`] to send finished reports to a custom backend, and register it with
+//! [`add_exporter`] to have it run every time profiling ends.
+
+use super::ProfileReport;
+use crate::sync::spsc::{Consumer, Producer, Ring};
+use std::{
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+};
+
+/// A destination for finished profile reports.
+pub trait ReportExporter: Send {
+    /// Export a finished report.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the report could not be exported.
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()>;
+}
+
+impl<F> ReportExporter for F
+where
+    F: FnMut(&ProfileReport) -> io::Result<()> + Send,
+{
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        self(report)
+    }
+}
+
+/// Exports the full text report to a writer.
+#[derive(Debug)]
+#[must_use]
+pub struct TextExporter<W> {
+    writer: W,
+}
+
+impl<W: Write + Send> TextExporter<W> {
+    /// Creates an exporter writing the full text report to `writer`.
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Unwraps this exporter, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> ReportExporter for TextExporter<W> {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        write!(self.writer, "{report}")?;
+        self.writer.flush()
+    }
+}
+
+/// Exports a condensed summary of the top anchors to a writer. See [`ProfileReport::summary`].
+#[derive(Debug)]
+#[must_use]
+pub struct SummaryExporter<W> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write + Send> SummaryExporter<W> {
+    /// Creates an exporter writing the top `count` anchors to `writer`.
+    pub fn new(writer: W, count: usize) -> Self {
+        Self { writer, count }
+    }
+
+    /// Unwraps this exporter, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write + Send> ReportExporter for SummaryExporter<W> {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        write!(self.writer, "{}", report.summary(self.count))?;
+        self.writer.flush()
+    }
+}
+
+/// Runs another exporter on a dedicated background thread, so slow sinks such as files and sockets
+/// never block the thread which finished profiling.
+///
+/// Reports are handed to the background thread through a bounded lock-free queue. If the queue is
+/// full, the report is dropped and `export` returns an error instead of waiting. Dropping the
+/// exporter, e.g. with [`clear_exporters`], waits for queued reports to finish exporting.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::fs::File;
+/// use util_lib_rs::performance::{add_exporter, export::{BackgroundExporter, TextExporter}};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = TextExporter::new(File::create("profile.txt")?);
+/// add_exporter(BackgroundExporter::new(file, 16)?);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct BackgroundExporter {
+    queue: Producer<ProfileReport>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl BackgroundExporter {
+    /// Spawns a thread running `exporter`, queueing up to `capacity` reports for it, rounded up to a
+    /// power of two.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread can't be spawned.
+    pub fn new(exporter: impl ReportExporter + 'static, capacity: usize) -> io::Result<Self> {
+        let (queue, reports) = Ring::with_capacity(capacity);
+        let shutdown = Arc::new(AtomicBool::new(false));
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-exporter".to_string())
+            .spawn({
+                let shutdown = Arc::clone(&shutdown);
+                move || run_exporter(exporter, reports, &shutdown)
+            })?;
+        Ok(Self {
+            queue,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+}
+
+fn run_exporter(
+    mut exporter: impl ReportExporter,
+    mut reports: Consumer<ProfileReport>,
+    shutdown: &AtomicBool,
+) {
+    loop {
+        // Checked before draining so reports queued before shutdown are still exported.
+        let stopping = shutdown.load(Ordering::Acquire);
+        while let Some(report) = reports.pop() {
+            if let Err(err) = exporter.export(&report) {
+                eprintln!("failed to export profile report: {err}");
+            }
+        }
+        if stopping {
+            break;
+        }
+        thread::park();
+    }
+}
+
+impl ReportExporter for BackgroundExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        let queued = self.queue.push(report.clone());
+        if let Some(thread) = &self.thread {
+            thread.thread().unpark();
+        }
+        queued.map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::WouldBlock,
+                "background export queue is full, report dropped",
+            )
+        })
+    }
+}
+
+impl Drop for BackgroundExporter {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            thread.thread().unpark();
+            let _ = thread.join();
+        }
+    }
+}
+
+static EXPORTERS: Mutex<Vec<Box<dyn ReportExporter>>> = Mutex::new(Vec::new());
+
+/// Register an exporter which receives every report when profiling ends.
+pub fn add_exporter(exporter: impl ReportExporter + 'static) {
+    EXPORTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Box::new(exporter));
+}
+
+/// Remove all registered exporters.
+pub fn clear_exporters() {
+    EXPORTERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clear();
+}
+
+/// Passes `report` to every registered exporter, printing any errors to `stderr` since this runs as
+/// part of ending profiling.
+#[cfg(feature = "perf")]
+pub(super) fn export_all(report: &ProfileReport) {
+    let mut exporters = EXPORTERS.lock().unwrap_or_else(PoisonError::into_inner);
+    for exporter in exporters.iter_mut() {
+        if let Err(err) = exporter.export(report) {
+            eprintln!("failed to export profile report: {err}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::AnchorStats;
+
+    fn report() -> ProfileReport {
+        ProfileReport {
+            elapsed_tsc: 100,
+            timer_freq: 1000,
+            anchors: vec![AnchorStats {
+                name: "exported",
+                hit_count: 1,
+                tsc_elapsed_exclusive: 100,
+                tsc_elapsed_inclusive: 100,
+                ..AnchorStats::default()
+            }],
+            ..ProfileReport::default()
+        }
+    }
+
+    #[test]
+    fn text_exporters() {
+        let report = report();
+        let mut text = TextExporter::new(Vec::new());
+        text.export(&report).expect("valid export");
+        assert_eq!(text.into_inner(), report.to_string().into_bytes());
+
+        let mut summary = SummaryExporter::new(Vec::new(), 1);
+        summary.export(&report).expect("valid export");
+        assert_eq!(
+            summary.into_inner(),
+            report.summary(1).to_string().into_bytes()
+        );
+    }
+
+    #[test]
+    fn background_exporter() {
+        let names = Arc::new(Mutex::new(Vec::new()));
+        let mut exporter = BackgroundExporter::new(
+            {
+                let names = Arc::clone(&names);
+                move |report: &ProfileReport| {
+                    let mut names = names.lock().expect("valid lock");
+                    names.extend(report.anchors.iter().map(|anchor| anchor.name));
+                    Ok(())
+                }
+            },
+            4,
+        )
+        .expect("valid exporter");
+        for _ in 0..3 {
+            exporter.export(&report()).expect("valid export");
+        }
+        drop(exporter);
+        assert_eq!(*names.lock().expect("valid lock"), ["exported"; 3]);
+    }
+
+    #[test]
+    fn closure_exporter() {
+        let mut names = Vec::new();
+        let mut exporter = |report: &ProfileReport| {
+            names.extend(report.anchors.iter().map(|anchor| anchor.name));
+            Ok(())
+        };
+        exporter.export(&report()).expect("valid export");
+        assert_eq!(names, ["exported"]);
+    }
+}
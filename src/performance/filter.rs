@@ -0,0 +1,193 @@
+//! Anchor name filtering.
+//!
+//! An [`AnchorFilter`] narrows a report down to the anchors whose names match a set of include and
+//! exclude patterns, so large instrumented codebases can produce focused reports per subsystem.
+//! Install one with [`set_anchor_filter`], or set the `UTIL_PROFILE_INCLUDE` and
+//! `UTIL_PROFILE_EXCLUDE` environment variables to comma-separated patterns, and it is applied to
+//! every report before it is printed or exported.
+//!
+//! Patterns are a regular expression subset: literals, `.`, character classes, `\d`, `\w`, `\s`,
+//! `*`, `+`, `?`, `^`, `$`, groups and `|`. They match anywhere in the name unless anchored.
+
+use super::pattern::Pattern;
+use std::sync::{Mutex, PoisonError};
+
+pub use super::pattern::PatternError;
+
+/// Environment variable holding comma-separated patterns of anchors to include.
+pub const INCLUDE_ENV: &str = "UTIL_PROFILE_INCLUDE";
+/// Environment variable holding comma-separated patterns of anchors to exclude.
+pub const EXCLUDE_ENV: &str = "UTIL_PROFILE_EXCLUDE";
+
+/// Include and exclude patterns applied to anchor names.
+///
+/// An anchor is kept if it matches any include pattern, or there are none, and matches no exclude
+/// pattern.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::filter::AnchorFilter;
+///
+/// # fn main() -> Result<(), util_lib_rs::performance::filter::PatternError> {
+/// let filter = AnchorFilter::new(&["^net::"], &["::poll$"])?;
+/// assert!(filter.matches("net::read"));
+/// assert!(!filter.matches("net::poll"));
+/// assert!(!filter.matches("json::parse"));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct AnchorFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl AnchorFilter {
+    /// Creates a filter from `include` and `exclude` patterns.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any pattern is invalid.
+    pub fn new(include: &[&str], exclude: &[&str]) -> Result<Self, PatternError> {
+        Self::from_patterns(include.iter().copied(), exclude.iter().copied())
+    }
+
+    /// Creates a filter from the patterns in `UTIL_PROFILE_INCLUDE` and `UTIL_PROFILE_EXCLUDE`, or
+    /// `None` if neither is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any pattern is invalid.
+    pub fn from_env() -> Result<Option<Self>, PatternError> {
+        let include = std::env::var(INCLUDE_ENV).unwrap_or_default();
+        let exclude = std::env::var(EXCLUDE_ENV).unwrap_or_default();
+        if include.trim().is_empty() && exclude.trim().is_empty() {
+            return Ok(None);
+        }
+        Self::from_patterns(split_patterns(&include), split_patterns(&exclude)).map(Some)
+    }
+
+    fn from_patterns<'a>(
+        include: impl IntoIterator<Item = &'a str>,
+        exclude: impl IntoIterator<Item = &'a str>,
+    ) -> Result<Self, PatternError> {
+        Ok(Self {
+            include: include
+                .into_iter()
+                .map(Pattern::new)
+                .collect::<Result<_, _>>()?,
+            exclude: exclude
+                .into_iter()
+                .map(Pattern::new)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Returns whether the anchor `name` passes this filter.
+    #[must_use]
+    pub fn matches(&self, name: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.is_match(name)))
+            && !self.exclude.iter().any(|p| p.is_match(name))
+    }
+}
+
+fn split_patterns(patterns: &str) -> impl Iterator<Item = &str> {
+    patterns
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+}
+
+static ANCHOR_FILTER: Mutex<Option<AnchorFilter>> = Mutex::new(None);
+
+/// Sets the filter applied to every report before it is printed or exported, taking precedence over
+/// the environment. Pass `None` to fall back to the environment again.
+pub fn set_anchor_filter(filter: Option<AnchorFilter>) {
+    *ANCHOR_FILTER.lock().unwrap_or_else(PoisonError::into_inner) = filter;
+}
+
+/// Returns the installed filter, or the one configured in the environment. Invalid environment
+/// patterns are reported and ignored.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn anchor_filter() -> Option<AnchorFilter> {
+    let installed = ANCHOR_FILTER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+    installed.or_else(|| {
+        AnchorFilter::from_env().unwrap_or_else(|err| {
+            eprintln!("ignoring anchor filter: {err}");
+            None
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        performance::{AnchorStats, Interval, ProfileReport},
+        test_util::EnvGuard,
+    };
+
+    fn anchor(name: &'static str) -> AnchorStats {
+        AnchorStats {
+            name,
+            hit_count: 1,
+            ..AnchorStats::default()
+        }
+    }
+
+    #[test]
+    fn filter_reports() {
+        let anchors = vec![
+            anchor("net::read"),
+            anchor("net::poll"),
+            anchor("json::parse"),
+        ];
+        let report = ProfileReport {
+            anchors: anchors.clone(),
+            intervals: vec![Interval {
+                from: "start",
+                to: "end",
+                report: ProfileReport {
+                    anchors,
+                    ..ProfileReport::default()
+                },
+            }],
+            never_hit: vec!["net::write", "json::emit"],
+            ..ProfileReport::default()
+        };
+
+        let filter = AnchorFilter::new(&["^net::"], &["poll"]).expect("valid patterns");
+        let filtered = report.filtered(&filter);
+        let names = |report: &ProfileReport| -> Vec<_> {
+            report.anchors.iter().map(|anchor| anchor.name).collect()
+        };
+        assert_eq!(names(&filtered), ["net::read"]);
+        assert_eq!(names(&filtered.intervals[0].report), ["net::read"]);
+        assert_eq!(filtered.never_hit, ["net::write"]);
+
+        let everything = AnchorFilter::new(&[], &[]).expect("valid patterns");
+        assert_eq!(report.filtered(&everything), report);
+        assert!(AnchorFilter::new(&["("], &[]).is_err());
+    }
+
+    #[test]
+    fn filters_from_env() {
+        // Reports ended by other tests apply this filter too, so only drop a name none of them use.
+        let _env = EnvGuard::new()
+            .set(INCLUDE_ENV, " ")
+            .set(EXCLUDE_ENV, "^filter_env_test$, ^filter_env_other$");
+        let filter = AnchorFilter::from_env()
+            .expect("valid patterns")
+            .expect("patterns set");
+        assert!(filter.matches("net::read"));
+        assert!(!filter.matches("filter_env_other"));
+
+        let _env = EnvGuard::new().remove(EXCLUDE_ENV);
+        assert!(AnchorFilter::from_env().expect("valid patterns").is_none());
+    }
+}
@@ -0,0 +1,329 @@
+//! Flight-recorder capture of slow frames.
+//!
+//! Keeping a detailed timeline of every frame or request is too much data for long runs, but the
+//! aggregate report hides what happened in the few that were slow. A [`FlightRecorder`] keeps the
+//! block events of the current frame in a rolling in-memory buffer and only keeps the full timeline
+//! of frames exceeding a latency threshold, optionally appending each one to a file.
+
+use std::{
+    collections::VecDeque,
+    fmt,
+    fs::OpenOptions,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+/// Events kept per frame by default.
+const DEFAULT_CAPACITY: usize = 65_536;
+
+/// A single profile block recorded in a frame's timeline.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct TraceEvent {
+    /// Name of the block's anchor.
+    pub name: &'static str,
+    /// Timestamp counter when the block started.
+    pub start_tsc: u64,
+    /// Timestamp counter when the block ended.
+    pub end_tsc: u64,
+    /// Number of blocks the block was nested inside.
+    pub depth: usize,
+    /// ID of the call stack the block was created on, if captured with the `callstacks` feature.
+    pub stack_id: Option<u64>,
+}
+
+/// Rolling buffer of the events recorded on a thread since the current frame began.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) struct EventBuffer {
+    events: VecDeque<TraceEvent>,
+    capacity: usize,
+    dropped: u64,
+}
+
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+impl EventBuffer {
+    fn new(capacity: usize) -> Self {
+        Self {
+            events: VecDeque::with_capacity(capacity),
+            capacity,
+            dropped: 0,
+        }
+    }
+
+    /// Records `event`, dropping the oldest event if the buffer is full.
+    #[inline]
+    pub(super) fn push(&mut self, event: TraceEvent) {
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+            self.dropped += 1;
+        }
+        self.events.push_back(event);
+    }
+
+    fn clear(&mut self) {
+        self.events.clear();
+        self.dropped = 0;
+    }
+}
+
+/// The full timeline of a frame which exceeded the latency threshold.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct FrameTrace {
+    /// Number of frames recorded before this one.
+    pub index: u64,
+    /// Timestamp counter when the frame began.
+    pub start_tsc: u64,
+    /// Timestamp counter when the frame ended.
+    pub end_tsc: u64,
+    /// Timestamp counter frequency, in ticks per second.
+    pub timer_freq: u64,
+    /// Blocks started and ended within the frame, in the order they started.
+    pub events: Vec<TraceEvent>,
+    /// Number of the frame's oldest events dropped because the buffer was full.
+    pub dropped_events: u64,
+}
+
+impl FrameTrace {
+    /// Elapsed time of the frame in milliseconds.
+    #[must_use]
+    pub fn elapsed_ms(&self) -> f64 {
+        self.ms(self.end_tsc.saturating_sub(self.start_tsc))
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn ms(&self, tsc: u64) -> f64 {
+        if self.timer_freq == 0 {
+            0.0
+        } else {
+            1000.0 * tsc as f64 / self.timer_freq as f64
+        }
+    }
+}
+
+impl fmt::Display for FrameTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\nFrame {}: {:.4}ms", self.index, self.elapsed_ms())?;
+        if self.dropped_events > 0 {
+            writeln!(f, "  ({} earlier events dropped)", self.dropped_events)?;
+        }
+        for event in &self.events {
+            writeln!(
+                f,
+                "  {:indent$}+{:.4}ms {}: {:.4}ms",
+                "",
+                self.ms(event.start_tsc.saturating_sub(self.start_tsc)),
+                event.name,
+                self.ms(event.end_tsc.saturating_sub(event.start_tsc)),
+                indent = 2 * event.depth,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the timeline of each frame on the current thread, keeping only the slow ones.
+///
+/// Block events are recorded on the creating thread from creation until the recorder is dropped.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::{performance::flight::FlightRecorder, profile};
+///
+/// let mut recorder = FlightRecorder::new(Duration::from_millis(16));
+/// for _ in 0..3 {
+///     recorder.frame_begin();
+///     {
+///         profile!("update");
+///     }
+///     if let Some(frame) = recorder.frame_end() {
+///         eprint!("{frame}");
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+#[must_use]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub struct FlightRecorder {
+    threshold: Duration,
+    path: Option<PathBuf>,
+    frame_start: Option<u64>,
+    frame_count: u64,
+    slow_frames: Vec<FrameTrace>,
+    /// Events are recorded by the creating thread's profiler.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl FlightRecorder {
+    /// Starts recording block events on the current thread, keeping the timeline of frames which
+    /// take longer than `threshold`.
+    pub fn new(threshold: Duration) -> Self {
+        Self::with_capacity(threshold, DEFAULT_CAPACITY)
+    }
+
+    /// Like [`FlightRecorder::new`], keeping up to `capacity` events per frame.
+    pub fn with_capacity(threshold: Duration, capacity: usize) -> Self {
+        #[cfg(feature = "perf")]
+        super::GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            profiler.events = Some(EventBuffer::new(capacity.max(1)));
+            profiler.update_recorders();
+        });
+        #[cfg(not(feature = "perf"))]
+        let _ = capacity;
+        Self {
+            threshold,
+            path: None,
+            frame_start: None,
+            frame_count: 0,
+            slow_frames: Vec::new(),
+            _not_send: std::marker::PhantomData,
+        }
+    }
+
+    /// Appends the timeline of every slow frame to the file at `path` as it's captured.
+    pub fn save_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Begins a frame or request, discarding events recorded since the last one.
+    pub fn frame_begin(&mut self) {
+        #[cfg(feature = "perf")]
+        {
+            super::GLOBAL_PROFILER.with(|profiler| {
+                if let Some(events) = &mut profiler.borrow_mut().events {
+                    events.clear();
+                }
+            });
+            self.frame_start = Some(super::Profiler::read_block_timer());
+        }
+    }
+
+    /// Ends the current frame, returning its timeline if it took longer than the threshold.
+    pub fn frame_end(&mut self) -> Option<&FrameTrace> {
+        #[cfg(feature = "perf")]
+        {
+            let end_tsc = super::Profiler::read_block_timer();
+            let start_tsc = self.frame_start.take()?;
+            let index = self.frame_count;
+            self.frame_count += 1;
+
+            let timer_freq = super::Profiler::timer_freq();
+            let elapsed = end_tsc.saturating_sub(start_tsc);
+            let threshold = self.threshold.as_nanos() * u128::from(timer_freq) / 1_000_000_000;
+            let slow = u128::from(elapsed) > threshold;
+            let (mut events, dropped_events) = super::GLOBAL_PROFILER.with(|profiler| {
+                let mut profiler = profiler.borrow_mut();
+                let buffer = profiler.events.as_mut()?;
+                let taken = slow.then(|| {
+                    let events: Vec<TraceEvent> = buffer
+                        .events
+                        .drain(..)
+                        .filter(|event| event.start_tsc >= start_tsc)
+                        .collect();
+                    (events, buffer.dropped)
+                });
+                buffer.clear();
+                taken
+            })?;
+            events.sort_by_key(|event| (event.start_tsc, event.depth));
+
+            let frame = FrameTrace {
+                index,
+                start_tsc,
+                end_tsc,
+                timer_freq,
+                events,
+                dropped_events,
+            };
+            if let Some(path) = &self.path {
+                if let Err(err) = append(path, &frame) {
+                    eprintln!("failed to save slow frame: {err}");
+                }
+            }
+            self.slow_frames.push(frame);
+            self.slow_frames.last()
+        }
+        #[cfg(not(feature = "perf"))]
+        None
+    }
+
+    /// Returns every frame captured so far.
+    pub fn slow_frames(&self) -> &[FrameTrace] {
+        &self.slow_frames
+    }
+
+    /// Number of frames ended so far.
+    #[must_use]
+    pub fn frame_count(&self) -> u64 {
+        self.frame_count
+    }
+}
+
+impl Drop for FlightRecorder {
+    fn drop(&mut self) {
+        #[cfg(feature = "perf")]
+        let _ = super::GLOBAL_PROFILER.try_with(|profiler| {
+            if let Ok(mut profiler) = profiler.try_borrow_mut() {
+                profiler.events = None;
+                profiler.update_recorders();
+            }
+        });
+    }
+}
+
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+fn append(path: &PathBuf, frame: &FrameTrace) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    write!(file, "{frame}")
+}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+    use crate::profile;
+
+    #[test]
+    fn capture_slow_frames() {
+        let path = std::env::temp_dir().join(format!(
+            "util_lib_rs_slow_frames_{}.txt",
+            std::process::id()
+        ));
+        let mut recorder =
+            FlightRecorder::with_capacity(Duration::from_millis(5), 2).save_to(&path);
+        for sleep in [0, 10] {
+            recorder.frame_begin();
+            {
+                profile!("flight_frame");
+                {
+                    profile!("flight_work");
+                }
+                profile!("flight_sleep");
+                std::thread::sleep(Duration::from_millis(sleep));
+            }
+            recorder.frame_end();
+        }
+        let saved = std::fs::read_to_string(&path);
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(recorder.frame_count(), 2);
+        assert_eq!(recorder.slow_frames().len(), 1);
+        let frame = &recorder.slow_frames()[0];
+        assert_eq!(frame.index, 1);
+        assert!(frame.elapsed_ms() >= 10.0);
+        // The oldest event didn't fit in the buffer.
+        assert_eq!(frame.dropped_events, 1);
+        let names: Vec<_> = frame.events.iter().map(|event| event.name).collect();
+        assert_eq!(names, ["flight_frame", "flight_sleep"]);
+        assert_eq!(frame.events[1].depth, 1);
+        assert_eq!(saved.expect("saved frame"), frame.to_string());
+
+        drop(recorder);
+        assert!(crate::performance::GLOBAL_PROFILER.with(|p| p.borrow().events.is_none()));
+    }
+}
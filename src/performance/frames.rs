@@ -0,0 +1,334 @@
+//! Frame-based profiling.
+//!
+//! Game and render loops care about the time of each frame rather than the total of a run, and an
+//! average hides the occasional frame that stutters. Calling [`frame_mark!`](crate::frame_mark)
+//! once per frame closes out the frame's measurements, keeping the average, rolling average and
+//! worst frame time, each anchor's average per frame, and the slowest frames with the anchor
+//! statistics of each. Blocks are counted in the frame they end in.
+
+use super::{report::accumulate_anchor, AnchorStats};
+use std::{
+    collections::VecDeque,
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Number of slowest frames kept, set with [`set_worst_frame_count`].
+static WORST_FRAME_COUNT: AtomicUsize = AtomicUsize::new(5);
+/// Number of most recent frames averaged, set with [`set_rolling_window`].
+static ROLLING_WINDOW: AtomicUsize = AtomicUsize::new(120);
+
+/// Sets how many of the slowest frames are kept with their anchor statistics. Defaults to 5.
+pub fn set_worst_frame_count(count: usize) {
+    WORST_FRAME_COUNT.store(count, Ordering::Relaxed);
+}
+
+/// Sets how many of the most recent frames the rolling average covers. Defaults to 120.
+pub fn set_rolling_window(frames: usize) {
+    ROLLING_WINDOW.store(frames.max(1), Ordering::Relaxed);
+}
+
+/// Ends the current frame on this thread and begins the next. The first call only begins a frame.
+/// Prefer the `frame_mark!` macro, which compiles to nothing without the `perf` feature.
+#[inline]
+pub fn frame_mark() {
+    #[cfg(feature = "perf")]
+    {
+        if !super::is_enabled() {
+            return;
+        }
+        super::GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().frame_mark());
+    }
+}
+
+/// Returns the frame statistics of the current thread since profiling began.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{frame_mark, performance::frames::frame_stats, profile};
+///
+/// for _ in 0..3 {
+///     frame_mark!();
+///     profile!("update");
+/// }
+/// frame_mark!();
+/// println!("{}", frame_stats());
+/// ```
+pub fn frame_stats() -> FrameStats {
+    #[cfg(feature = "perf")]
+    {
+        super::GLOBAL_PROFILER.with(|profiler| {
+            profiler
+                .borrow()
+                .frames
+                .as_ref()
+                .map(|tracker| tracker.stats.clone())
+                .unwrap_or_default()
+        })
+    }
+    #[cfg(not(feature = "perf"))]
+    FrameStats::default()
+}
+
+/// One frame kept for being among the slowest.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[must_use]
+pub struct Frame {
+    /// Number of frames which ended before this one.
+    pub index: u64,
+    /// Elapsed timestamp counter of the frame.
+    pub elapsed_tsc: u64,
+    /// Statistics of each anchor hit during the frame.
+    pub anchors: Vec<AnchorStats>,
+}
+
+/// Rolling statistics of the frames ended with [`frame_mark`].
+#[derive(Debug, Default, Clone, PartialEq)]
+#[must_use]
+pub struct FrameStats {
+    /// Estimated timestamp counter frequency, in ticks per second.
+    pub timer_freq: u64,
+    /// Number of frames ended.
+    pub frame_count: u64,
+    /// Total elapsed timestamp counter of every frame.
+    pub total_tsc: u64,
+    /// Elapsed timestamp counter of the slowest frame.
+    pub worst_tsc: u64,
+    /// Elapsed timestamp counter of each of the most recent frames, oldest first.
+    pub recent_tsc: VecDeque<u64>,
+    /// Statistics of each anchor summed over every frame.
+    pub anchors: Vec<AnchorStats>,
+    /// The slowest frames, slowest first.
+    pub worst: Vec<Frame>,
+}
+
+impl FrameStats {
+    /// Converts a timestamp count to milliseconds.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn ms(&self, tsc: u64) -> f64 {
+        if self.timer_freq == 0 {
+            0.0
+        } else {
+            1000.0 * tsc as f64 / self.timer_freq as f64
+        }
+    }
+
+    /// Average frame time in milliseconds.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn average_ms(&self) -> f64 {
+        self.ms(self.total_tsc) / self.frame_count.max(1) as f64
+    }
+
+    /// Average time of the most recent frames in milliseconds.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn rolling_average_ms(&self) -> f64 {
+        self.ms(self.recent_tsc.iter().sum()) / self.recent_tsc.len().max(1) as f64
+    }
+
+    /// Slowest frame time in milliseconds.
+    #[must_use]
+    pub fn worst_ms(&self) -> f64 {
+        self.ms(self.worst_tsc)
+    }
+
+    /// Average exclusive time per frame of `anchor` in milliseconds.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn per_frame_ms(&self, anchor: &AnchorStats) -> f64 {
+        self.ms(anchor.tsc_elapsed_exclusive) / self.frame_count.max(1) as f64
+    }
+
+    /// Adds a frame which took `elapsed_tsc` with the anchor statistics `anchors`.
+    #[cfg_attr(not(feature = "perf"), allow(dead_code))]
+    fn record(&mut self, elapsed_tsc: u64, anchors: Vec<AnchorStats>) {
+        let index = self.frame_count;
+        self.frame_count += 1;
+        self.total_tsc = self.total_tsc.saturating_add(elapsed_tsc);
+        self.worst_tsc = self.worst_tsc.max(elapsed_tsc);
+
+        self.recent_tsc.push_back(elapsed_tsc);
+        let window = ROLLING_WINDOW.load(Ordering::Relaxed);
+        while self.recent_tsc.len() > window {
+            self.recent_tsc.pop_front();
+        }
+
+        for anchor in &anchors {
+            accumulate_anchor(&mut self.anchors, *anchor);
+        }
+
+        let keep = WORST_FRAME_COUNT.load(Ordering::Relaxed);
+        self.worst.truncate(keep);
+        if keep > 0
+            && (self.worst.len() < keep
+                || self
+                    .worst
+                    .last()
+                    .is_some_and(|frame| elapsed_tsc > frame.elapsed_tsc))
+        {
+            let at = self
+                .worst
+                .partition_point(|frame| frame.elapsed_tsc >= elapsed_tsc);
+            self.worst.insert(
+                at,
+                Frame {
+                    index,
+                    elapsed_tsc,
+                    anchors,
+                },
+            );
+            self.worst.truncate(keep);
+        }
+    }
+}
+
+impl fmt::Display for FrameStats {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        /// Anchors listed per slow frame.
+        const FRAME_ANCHORS: usize = 3;
+
+        writeln!(
+            f,
+            "\nFrames[{}]: avg {:.4}ms, last {} avg {:.4}ms, worst {:.4}ms",
+            self.frame_count,
+            self.average_ms(),
+            self.recent_tsc.len(),
+            self.rolling_average_ms(),
+            self.worst_ms(),
+        )?;
+        for anchor in &self.anchors {
+            writeln!(
+                f,
+                "  {}: {:.4}ms/frame, {:.2} hits/frame",
+                anchor.name,
+                self.per_frame_ms(anchor),
+                anchor.hit_count as f64 / self.frame_count.max(1) as f64,
+            )?;
+        }
+
+        if !self.worst.is_empty() {
+            writeln!(f, "\nWorst frames:")?;
+            for frame in &self.worst {
+                write!(f, "  #{}: {:.4}ms", frame.index, self.ms(frame.elapsed_tsc))?;
+                let mut anchors: Vec<&AnchorStats> = frame.anchors.iter().collect();
+                anchors.sort_by_key(|anchor| std::cmp::Reverse(anchor.tsc_elapsed_exclusive));
+                for (i, anchor) in anchors.iter().take(FRAME_ANCHORS).enumerate() {
+                    let separator = if i == 0 { " (" } else { ", " };
+                    write!(
+                        f,
+                        "{separator}{} {:.4}ms",
+                        anchor.name,
+                        self.ms(anchor.tsc_elapsed_exclusive)
+                    )?;
+                }
+                if anchors.is_empty() {
+                    writeln!(f)?;
+                } else {
+                    writeln!(f, ")")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// State of the frame in progress on a thread, once the first frame has been marked.
+#[derive(Debug)]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) struct FrameTracker {
+    /// Timestamp counter when the current frame began.
+    start_tsc: u64,
+    /// Statistics of every anchor when the current frame began.
+    start_anchors: Vec<AnchorStats>,
+    pub(super) stats: FrameStats,
+}
+
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+impl FrameTracker {
+    /// Begins the first frame at `tsc`, when the anchors had the statistics `anchors`.
+    pub(super) fn new(tsc: u64, anchors: Vec<AnchorStats>, timer_freq: u64) -> Self {
+        Self {
+            start_tsc: tsc,
+            start_anchors: anchors,
+            stats: FrameStats {
+                timer_freq,
+                ..FrameStats::default()
+            },
+        }
+    }
+
+    /// Ends the current frame and begins the next at `tsc`, when the anchors had the statistics
+    /// `anchors`.
+    pub(super) fn mark(&mut self, tsc: u64, anchors: Vec<AnchorStats>) {
+        let delta = super::report::anchors_delta(&anchors, &self.start_anchors);
+        self.stats.record(tsc.saturating_sub(self.start_tsc), delta);
+        self.start_tsc = tsc;
+        self.start_anchors = anchors;
+    }
+}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+
+    fn anchor(name: &'static str, tsc_elapsed_exclusive: u64) -> AnchorStats {
+        AnchorStats {
+            name,
+            hit_count: 1,
+            tsc_elapsed_exclusive,
+            tsc_elapsed_inclusive: tsc_elapsed_exclusive,
+            ..AnchorStats::default()
+        }
+    }
+
+    #[test]
+    fn worst_frames() {
+        let mut stats = FrameStats {
+            timer_freq: 1000,
+            ..FrameStats::default()
+        };
+        for (i, elapsed) in [10, 40, 20, 30, 50, 5, 60, 15].into_iter().enumerate() {
+            let name = if i % 2 == 0 { "update" } else { "render" };
+            stats.record(elapsed, vec![anchor(name, elapsed / 2)]);
+        }
+
+        assert_eq!(stats.frame_count, 8);
+        assert_eq!(stats.total_tsc, 230);
+        assert_eq!(stats.worst_tsc, 60);
+        let worst: Vec<_> = stats.worst.iter().map(|frame| frame.index).collect();
+        assert_eq!(worst, [6, 4, 1, 3, 2]);
+        assert_eq!(stats.worst[0].anchors, [anchor("update", 30)]);
+        assert_eq!(stats.anchors.len(), 2);
+        assert!((stats.average_ms() - 28.75).abs() < 1e-9);
+        assert!((stats.per_frame_ms(&stats.anchors[0]) - 8.75).abs() < 1e-9);
+
+        let printed = stats.to_string();
+        assert!(printed.starts_with("\nFrames[8]: avg 28.7500ms, last 8 avg 28.7500ms"));
+        assert!(printed.contains("\n  update: 8.7500ms/frame, 0.50 hits/frame\n"));
+        assert!(printed.contains("\nWorst frames:\n  #6: 60.0000ms (update 30.0000ms)\n"));
+    }
+
+    #[test]
+    fn marked_frames() {
+        crate::performance::profile_begin();
+        for _ in 0..4 {
+            frame_mark();
+            crate::profile!("frame_work");
+        }
+        frame_mark();
+
+        let stats = frame_stats();
+        assert_eq!(stats.frame_count, 4);
+        assert_eq!(stats.anchors.len(), 1);
+        assert_eq!(stats.anchors[0].name, "frame_work");
+        assert_eq!(stats.anchors[0].hit_count, 4);
+        assert!(stats.worst.iter().all(|frame| frame.anchors.len() == 1));
+
+        crate::performance::profile_begin();
+        assert_eq!(frame_stats().frame_count, 0);
+    }
+}
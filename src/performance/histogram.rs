@@ -0,0 +1,142 @@
+//! Per-anchor latency histograms.
+//!
+//! Totals and averages hide tail latency. Once enabled with
+//! [`ProfilerConfig::latency_histograms`](super::config::ProfilerConfig::latency_histograms), the
+//! elapsed time of every block, including child blocks, is counted in a log-linear histogram of its
+//! anchor, and the report lists the 50th, 90th, 99th and 99.9th percentiles of each anchor. Every
+//! power of two is split into `2^precision` buckets, so percentiles are within `1 / 2^precision`
+//! of the true value, at a cost of `8 * (65 - precision) * 2^precision` bytes per anchor and
+//! thread: about 4 KiB at the default precision of 3. Each is a [`Histogram`] of ticks.
+
+use super::report::scale;
+use crate::stats::Histogram;
+
+/// Precision of histograms unless configured otherwise.
+pub const DEFAULT_HISTOGRAM_PRECISION: u32 = 3;
+
+/// Highest supported precision, taking 116 KiB per histogram.
+pub const MAX_HISTOGRAM_PRECISION: u32 = Histogram::MAX_PRECISION;
+
+/// The percentiles listed in reports.
+pub(super) const REPORTED_PERCENTILES: [(&str, f64); 4] =
+    [("p50", 50.0), ("p90", 90.0), ("p99", 99.0), ("p999", 99.9)];
+
+/// Number of hits of an anchor by elapsed timestamp counter, including child blocks.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::histogram::LatencyHistogram;
+///
+/// let mut histogram = LatencyHistogram::new("request", 3);
+/// for tsc in 1..=1000 {
+///     histogram.record(tsc);
+/// }
+/// assert_eq!(histogram.count(), 1000);
+/// let p99 = histogram.percentile(99.0);
+/// assert!((960..=1040).contains(&p99));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct LatencyHistogram {
+    /// Name of the anchor.
+    pub name: &'static str,
+    histogram: Histogram,
+}
+
+impl LatencyHistogram {
+    /// Creates an empty histogram splitting every power of two into `2^precision` buckets, up to
+    /// [`MAX_HISTOGRAM_PRECISION`].
+    pub fn new(name: &'static str, precision: u32) -> Self {
+        Self {
+            name,
+            histogram: Histogram::new(precision),
+        }
+    }
+
+    /// Returns the number of buckets per power of two, as a power of two.
+    #[must_use]
+    pub fn precision(&self) -> u32 {
+        self.histogram.precision()
+    }
+
+    /// Counts a hit which took `tsc` ticks.
+    #[inline]
+    pub fn record(&mut self, tsc: u64) {
+        self.histogram.record(tsc);
+    }
+
+    /// Returns the number of hits counted.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.histogram.count()
+    }
+
+    /// Returns the elapsed timestamp counter which `percentile` percent of the hits took at most,
+    /// e.g. `99.0` for the 99th percentile, or `0` if no hits were counted.
+    #[must_use]
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        self.histogram.percentile(percentile)
+    }
+
+    /// Returns the hits as a general-purpose histogram of timestamp counters.
+    pub fn histogram(&self) -> &Histogram {
+        &self.histogram
+    }
+
+    /// Adds the hits of `other` to this histogram.
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        self.histogram.merge(&other.histogram);
+    }
+
+    /// Adds the hits of `other` to this histogram, with each bucket's timestamp counter mapped
+    /// through `rescale`.
+    pub(super) fn merge_rescaled(
+        &mut self,
+        other: &LatencyHistogram,
+        rescale: &dyn Fn(u64) -> u64,
+    ) {
+        for (tsc, count) in other.histogram.buckets() {
+            self.histogram.record_n(rescale(tsc), count);
+        }
+    }
+
+    /// Returns this histogram with every bucket's count multiplied by `factor`.
+    pub(super) fn scaled(&self, factor: f64) -> Self {
+        let mut histogram = Histogram::new(self.precision());
+        for (tsc, count) in self.histogram.buckets() {
+            histogram.record_n(tsc, scale(count, factor));
+        }
+        Self {
+            name: self.name,
+            histogram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles() {
+        let mut histogram = LatencyHistogram::new("request", 3);
+        assert_eq!(histogram.percentile(50.0), 0);
+        for _ in 0..990 {
+            histogram.record(100);
+        }
+        for _ in 0..10 {
+            histogram.record(10_000);
+        }
+        assert_eq!(histogram.count(), 1000);
+        assert!((96..=104).contains(&histogram.percentile(50.0)));
+        assert!((96..=104).contains(&histogram.percentile(99.0)));
+        assert!((9600..=10_400).contains(&histogram.percentile(99.9)));
+
+        let mut merged = LatencyHistogram::new("request", 0);
+        merged.merge_rescaled(&histogram, &|tsc| tsc * 2);
+        assert_eq!(merged.count(), 1000);
+        assert!((128..=256).contains(&merged.percentile(50.0)));
+        assert_eq!(histogram.scaled(0.5).count(), 500);
+    }
+}
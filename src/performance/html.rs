@@ -0,0 +1,247 @@
+//! Standalone HTML reports.
+//!
+//! [`write_html`] renders a [`ProfileReport`] as a single HTML page with no external resources: a
+//! flame graph of the call tree, and a table of the anchors which sorts by any column when its
+//! header is clicked. The file can be attached to a ticket and opened in any browser.
+
+use super::{plot::escape, ProfileReport, ReportExporter};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+};
+
+/// Height of one flame graph frame, in pixels.
+const FRAME_HEIGHT: usize = 22;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse}\
+th,td{padding:4px 10px;border-bottom:1px solid #ddd;text-align:right}\
+th{cursor:pointer;background:#f4f4f4}\
+td:first-child,th:first-child{text-align:left}\
+.share{position:relative;min-width:120px}\
+.share div{position:absolute;left:0;top:2px;bottom:2px;background:#fdd49e;z-index:-1}\
+.flame{position:relative;margin-bottom:2em}\
+.flame div{position:absolute;box-sizing:border-box;height:21px;overflow:hidden;\
+white-space:nowrap;font-size:12px;line-height:21px;padding:0 4px;border:1px solid #fff;\
+background:#f3a35b}";
+
+const SCRIPT: &str = "document.querySelectorAll('th').forEach((th,column)=>{\
+let ascending=false;th.onclick=()=>{\
+const body=th.closest('table').tBodies[0];\
+const key=row=>{const cell=row.cells[column];\
+return cell.dataset.value===undefined?cell.textContent:Number(cell.dataset.value);};\
+ascending=!ascending;\
+[...body.rows].sort((a,b)=>{const x=key(a),y=key(b);\
+return (x<y?-1:x>y?1:0)*(ascending?1:-1);}).forEach(row=>body.appendChild(row));};});";
+
+/// Writes `report` to `writer` as a standalone HTML page.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{html::write_html, AnchorStats, ProfileReport};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let report = ProfileReport {
+///     elapsed_tsc: 200,
+///     timer_freq: 1000,
+///     anchors: vec![AnchorStats {
+///         name: "parse",
+///         hit_count: 2,
+///         tsc_elapsed_exclusive: 50,
+///         tsc_elapsed_inclusive: 80,
+///         ..AnchorStats::default()
+///     }],
+///     ..ProfileReport::default()
+/// };
+/// let mut output = Vec::new();
+/// write_html(&report, &mut output)?;
+/// assert!(String::from_utf8_lossy(&output).contains("<td>parse</td>"));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+#[allow(clippy::cast_precision_loss)]
+pub fn write_html(report: &ProfileReport, mut writer: impl Write) -> io::Result<()> {
+    let share = |tsc: u64| {
+        if report.elapsed_tsc == 0 {
+            0.0
+        } else {
+            100.0 * tsc as f64 / report.elapsed_tsc as f64
+        }
+    };
+    let ms = |tsc: u64| {
+        if report.timer_freq == 0 {
+            0.0
+        } else {
+            1000.0 * tsc as f64 / report.timer_freq as f64
+        }
+    };
+
+    writeln!(writer, "<!DOCTYPE html>")?;
+    writeln!(writer, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(
+        writer,
+        "<title>Profile report</title><style>{STYLE}</style>"
+    )?;
+    writeln!(writer, "</head><body>")?;
+    writeln!(
+        writer,
+        "<h1>Profile report</h1><p>Total time: {:.4}ms (CPU freq {})</p>",
+        report.elapsed_ms(),
+        report.timer_freq
+    )?;
+
+    // Frames are laid out depth-first: each starts where the previous frame at its depth ended, or
+    // at its parent's start if it's the first child.
+    let nodes = report.tree().nodes();
+    let depth = nodes.iter().map(|node| node.depth + 1).max().unwrap_or(0);
+    writeln!(
+        writer,
+        "<h2>Call tree</h2><div class=\"flame\" style=\"height:{}px\">",
+        depth * FRAME_HEIGHT
+    )?;
+    let mut offsets = vec![0.0];
+    for node in &nodes {
+        offsets.truncate(node.depth + 1);
+        let left = offsets[node.depth];
+        let width = share(node.tsc_elapsed_inclusive);
+        offsets[node.depth] += width;
+        offsets.push(left);
+        writeln!(
+            writer,
+            "<div style=\"left:{left:.4}%;width:{width:.4}%;top:{}px\" \
+             title=\"{name}[{}]: {:.4}ms ({width:.2}%)\">{name}</div>",
+            node.depth * FRAME_HEIGHT,
+            node.hit_count,
+            ms(node.tsc_elapsed_inclusive),
+            name = escape(node.name),
+        )?;
+    }
+    writeln!(writer, "</div>")?;
+
+    writeln!(writer, "<h2>Anchors</h2><table><thead><tr>")?;
+    writeln!(
+        writer,
+        "<th>Name</th><th>Hits</th><th>Exclusive (ms)</th><th>Inclusive (ms)</th>\
+         <th>Exclusive share</th><th>Bytes</th>"
+    )?;
+    writeln!(writer, "</tr></thead><tbody>")?;
+    for anchor in report.anchors.iter().filter(|anchor| anchor.hit_count > 0) {
+        let percent = share(anchor.tsc_elapsed_exclusive);
+        writeln!(
+            writer,
+            "<tr><td>{}</td><td data-value=\"{hits}\">{hits}</td>\
+             <td data-value=\"{exclusive}\">{:.4}</td><td data-value=\"{inclusive}\">{:.4}</td>\
+             <td class=\"share\" data-value=\"{percent}\"><div style=\"width:{percent:.2}%\"></div>\
+             {percent:.2}%</td><td data-value=\"{bytes}\">{bytes}</td></tr>",
+            escape(anchor.name),
+            ms(anchor.tsc_elapsed_exclusive),
+            ms(anchor.tsc_elapsed_inclusive),
+            hits = anchor.hit_count,
+            exclusive = anchor.tsc_elapsed_exclusive,
+            inclusive = anchor.tsc_elapsed_inclusive,
+            bytes = anchor.byte_count,
+        )?;
+    }
+    writeln!(writer, "</tbody></table>")?;
+
+    if !report.metadata.is_empty() {
+        writeln!(writer, "<h2>Run metadata</h2><ul>")?;
+        for (key, value) in &report.metadata {
+            writeln!(writer, "<li>{}: {}</li>", escape(key), escape(value))?;
+        }
+        writeln!(writer, "</ul>")?;
+    }
+    if !report.warnings.is_empty() {
+        writeln!(writer, "<h2>Warnings</h2><ul>")?;
+        for warning in &report.warnings {
+            writeln!(writer, "<li>{}</li>", escape(warning))?;
+        }
+        writeln!(writer, "</ul>")?;
+    }
+    writeln!(writer, "<script>{SCRIPT}</script></body></html>")
+}
+
+/// Saves every finished report as a standalone HTML page.
+#[derive(Debug)]
+#[must_use]
+pub struct HtmlExporter {
+    path: PathBuf,
+}
+
+impl HtmlExporter {
+    /// Creates an exporter saving reports to the file at `path`, replacing it on every export.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ReportExporter for HtmlExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        write_html(report, &mut writer)?;
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::{AnchorStats, CallStats};
+
+    #[test]
+    fn html_output() {
+        let anchor = |name, tsc_elapsed_exclusive, tsc_elapsed_inclusive| AnchorStats {
+            name,
+            hit_count: 1,
+            tsc_elapsed_exclusive,
+            tsc_elapsed_inclusive,
+            ..AnchorStats::default()
+        };
+        let report = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1000,
+            anchors: vec![
+                anchor("main", 200, 800),
+                anchor("parse<&a>", 400, 400),
+                anchor("lex", 200, 200),
+            ],
+            calls: vec![
+                CallStats {
+                    caller: "main",
+                    callee: "parse<&a>",
+                    call_count: 1,
+                    tsc_elapsed_inclusive: 400,
+                },
+                CallStats {
+                    caller: "main",
+                    callee: "lex",
+                    call_count: 1,
+                    tsc_elapsed_inclusive: 200,
+                },
+            ],
+            warnings: vec!["x < y".to_string()],
+            ..ProfileReport::default()
+        };
+
+        let mut output = Vec::new();
+        write_html(&report, &mut output).expect("valid write");
+        let output = String::from_utf8(output).expect("valid utf8");
+        assert!(output.starts_with("<!DOCTYPE html>\n"));
+        assert!(output.ends_with("</script></body></html>\n"));
+        assert!(output.contains("<div class=\"flame\" style=\"height:44px\">"));
+        assert!(output.contains("<div style=\"left:0.0000%;width:80.0000%;top:0px\""));
+        assert!(output.contains(
+            "<div style=\"left:0.0000%;width:40.0000%;top:22px\" \
+             title=\"parse&lt;&amp;a&gt;[1]: 400.0000ms (40.00%)\">parse&lt;&amp;a&gt;</div>"
+        ));
+        assert!(output.contains("<div style=\"left:40.0000%;width:20.0000%;top:22px\""));
+        assert!(output.contains("<tr><td>lex</td><td data-value=\"1\">1</td>"));
+        assert!(output.contains("<li>x &lt; y</li>"));
+    }
+}
@@ -0,0 +1,150 @@
+//! Iterator profiling.
+//!
+//! [`ProfileIterExt::profiled`] wraps an iterator so each call to `next` is a hit of an anchor,
+//! which measures the stages of an iterator pipeline without restructuring it into explicit
+//! blocks. Iterator adapters are lazy, so a stage's blocks enclose those of the stages it pulls
+//! from, and each stage's own time is its anchor's exclusive time.
+
+use std::iter::FusedIterator;
+
+/// An iterator which profiles every call to `next`. Created by [`ProfileIterExt::profiled`] and
+/// [`ProfileIterExt::profiled_bytes`].
+#[derive(Debug, Clone)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct Profiled<I: Iterator, F = fn(&<I as Iterator>::Item) -> u64> {
+    inner: I,
+    name: &'static str,
+    byte_count: Option<F>,
+}
+
+impl<I, F> Iterator for Profiled<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item) -> u64,
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        #[cfg(feature = "perf")]
+        {
+            let block = super::ProfileBlock::new(self.name, 0);
+            let item = self.inner.next();
+            if let (Some(item), Some(byte_count)) = (&item, &mut self.byte_count) {
+                block.add_byte_count(byte_count(item));
+            }
+            item
+        }
+        #[cfg(not(feature = "perf"))]
+        {
+            let _ = (self.name, &self.byte_count);
+            self.inner.next()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<I, F> ExactSizeIterator for Profiled<I, F>
+where
+    I: ExactSizeIterator,
+    F: FnMut(&I::Item) -> u64,
+{
+}
+
+impl<I, F> FusedIterator for Profiled<I, F>
+where
+    I: FusedIterator,
+    F: FnMut(&I::Item) -> u64,
+{
+}
+
+/// Extension methods profiling any iterator.
+pub trait ProfileIterExt: Iterator + Sized {
+    /// Profiles every call to `next` under the anchor `name`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::performance::iter::ProfileIterExt;
+    ///
+    /// let total: u64 = (0..100u64)
+    ///     .profiled("generate")
+    ///     .map(|n| n * n)
+    ///     .profiled("square")
+    ///     .sum();
+    /// assert_eq!(total, 328_350);
+    /// ```
+    fn profiled(self, name: &'static str) -> Profiled<Self> {
+        Profiled {
+            inner: self,
+            name,
+            byte_count: None,
+        }
+    }
+
+    /// Profiles every call to `next` under the anchor `name`, crediting the anchor with the number
+    /// of bytes `byte_count` returns for each item, for the stage's throughput.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::performance::iter::ProfileIterExt;
+    ///
+    /// let lines = ["alpha", "beta", "gamma"];
+    /// let words = lines
+    ///     .iter()
+    ///     .profiled_bytes("read_lines", |line| line.len() as u64)
+    ///     .count();
+    /// assert_eq!(words, 3);
+    /// ```
+    fn profiled_bytes<F>(self, name: &'static str, byte_count: F) -> Profiled<Self, F>
+    where
+        F: FnMut(&Self::Item) -> u64,
+    {
+        Profiled {
+            inner: self,
+            name,
+            byte_count: Some(byte_count),
+        }
+    }
+}
+
+impl<I: Iterator> ProfileIterExt for I {}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+    use crate::performance::{profile_begin, GLOBAL_PROFILER};
+
+    #[test]
+    fn profiled_stages() {
+        profile_begin();
+        let words = ["one", "three", "five"];
+        let lengths: Vec<usize> = words
+            .iter()
+            .profiled_bytes("titer_read", |word| word.len() as u64)
+            .map(|word| word.len())
+            .profiled("titer_measure")
+            .collect();
+        assert_eq!(lengths, [3, 5, 4]);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let anchor = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| (anchor.hit_count, anchor.byte_count))
+        };
+        // The final call returning `None` is a hit too.
+        assert_eq!(anchor("titer_read"), Some((4, 12)));
+        assert_eq!(anchor("titer_measure"), Some((4, 0)));
+        assert!(report
+            .calls
+            .iter()
+            .any(|call| call.caller == "titer_measure" && call.callee == "titer_read"));
+    }
+}
@@ -0,0 +1,85 @@
+//! Block names formatted from runtime values.
+//!
+//! `profile!(fmt = "query:{table}")` names its block with a label formatted from variables in
+//! scope, so blocks of the same code for different files, endpoints or tables are reported as
+//! separate anchors. Each distinct label is leaked once and cached per thread, so repeated labels
+//! cost a format and a lookup. To keep memory and the anchor table bounded when the values are
+//! unbounded, such as user IDs, labels beyond
+//! [`ProfilerConfig::max_labels`](super::config::ProfilerConfig::max_labels) fall back to their
+//! unformatted template, combining their hits.
+
+use super::config;
+use crate::{hash::FastHashSet, intern::intern_static, sync::Lazy};
+use std::{
+    cell::RefCell,
+    fmt::{self, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+/// Every distinct label formatted so far, counted against the limit.
+static LABELS: Lazy<Mutex<FastHashSet<&'static str>>> = Lazy::new(Mutex::default);
+/// Whether a label has fallen back to its template.
+static OVERFLOWED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Labels this thread has already looked up, and the buffer labels are formatted into.
+    static CACHE: RefCell<(FastHashSet<&'static str>, String)> =
+        RefCell::new((FastHashSet::default(), String::new()));
+}
+
+/// Returns the label formatted from `args`, or `template` once
+/// [`ProfilerConfig::max_labels`](super::config::ProfilerConfig::max_labels) distinct labels have
+/// been formatted. Prefer `profile!(fmt = "...")`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::labels::label;
+///
+/// let table = "users";
+/// assert_eq!(label("query:{table}", format_args!("query:{table}")), "query:users");
+/// ```
+#[must_use]
+pub fn label(template: &'static str, args: fmt::Arguments<'_>) -> &'static str {
+    if args.as_str() == Some(template) {
+        return template;
+    }
+    CACHE.with(|cache| {
+        let (cached, buffer) = &mut *cache.borrow_mut();
+        buffer.clear();
+        if buffer.write_fmt(args).is_err() {
+            return template;
+        }
+        if let Some(label) = cached.get(buffer.as_str()) {
+            return label;
+        }
+        intern(buffer).map_or(template, |label| {
+            cached.insert(label);
+            label
+        })
+    })
+}
+
+/// Returns the interned copy of `text`, or `None` if the limit of distinct labels is reached.
+fn intern(text: &str) -> Option<&'static str> {
+    let mut labels = LABELS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(label) = labels.get(text) {
+        return Some(label);
+    }
+    if labels.len() >= config::max_labels() {
+        OVERFLOWED.store(true, Ordering::Relaxed);
+        return None;
+    }
+    let label = intern_static(text);
+    labels.insert(label);
+    Some(label)
+}
+
+/// Returns whether any label has fallen back to its template.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn overflowed() -> bool {
+    OVERFLOWED.load(Ordering::Relaxed)
+}
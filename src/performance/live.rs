@@ -0,0 +1,123 @@
+//! Periodic live reports.
+//!
+//! A [`LiveReport`] runs on its own thread and, at every interval, combines the statistics every
+//! thread has published into one report and prints it to the
+//! [output sink](super::sink::set_output_sink) or passes it to a callback, without stopping
+//! collection, so long-running services can be observed without shutting down. While it runs,
+//! each thread publishes its statistics as in [`profile_publish`](super::profile_publish) the next
+//! time its outermost block ends after a report, so each report shows statistics from up to one
+//! interval earlier.
+
+use super::ProfileReport;
+use crate::time::Ticker;
+use std::{
+    fmt, io,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+type ReportCallback = Box<dyn FnMut(&ProfileReport) + Send>;
+
+/// Configuration for periodic live reports.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use util_lib_rs::performance::live::LiveReport;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let _reporter = LiveReport::new(Duration::from_secs(60))
+///     .on_report(|report| println!("{}", report.summary(10)))
+///     .start()?;
+/// // serve requests...
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub struct LiveReport {
+    interval: Duration,
+    on_report: Option<ReportCallback>,
+}
+
+impl LiveReport {
+    /// Creates a reporter which reports once per `interval`.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            on_report: None,
+        }
+    }
+
+    /// Calls `callback` with each report, instead of printing it.
+    pub fn on_report(mut self, callback: impl FnMut(&ProfileReport) + Send + 'static) -> Self {
+        self.on_report = Some(Box::new(callback));
+        self
+    }
+
+    /// Starts the reporter thread. Reports stop when the returned guard is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread can't be spawned.
+    pub fn start(mut self) -> io::Result<LiveReportGuard> {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-live-report".to_string())
+            .spawn(move || {
+                #[cfg(feature = "perf")]
+                let start_tsc = super::Profiler::read_block_timer();
+                #[cfg(not(feature = "perf"))]
+                let start_tsc = 0;
+                #[cfg(feature = "perf")]
+                super::request_publish();
+                let mut ticker = Ticker::new(self.interval);
+                while let Err(RecvTimeoutError::Timeout) =
+                    cancelled.recv_timeout(ticker.until_next())
+                {
+                    if ticker.try_tick().is_none() {
+                        continue;
+                    }
+                    let report = super::watchdog::published_report(start_tsc);
+                    match &mut self.on_report {
+                        Some(callback) => callback(&report),
+                        None => super::sink::print(&report),
+                    }
+                    #[cfg(feature = "perf")]
+                    super::request_publish();
+                }
+            })?;
+        Ok(LiveReportGuard {
+            cancel: Some(cancel),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl fmt::Debug for LiveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LiveReport")
+            .field("interval", &self.interval)
+            .field("on_report", &self.on_report.is_some())
+            .finish()
+    }
+}
+
+/// A running live reporter, stopped when dropped.
+#[derive(Debug)]
+#[must_use]
+pub struct LiveReportGuard {
+    cancel: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for LiveReportGuard {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the reporter thread before its next report.
+        drop(self.cancel.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! LZ4 frame compression for profile dumps.
+//!
+//! A small, dependency-free implementation of the [LZ4 frame format] which produces frames readable
+//! by the `lz4` command line tool and reads frames produced by it. Compression uses a simple greedy
+//! hash-table match finder, favoring speed over ratio.
+//!
+//! [LZ4 frame format]: https://github.com/lz4/lz4/blob/dev/doc/lz4_Frame_format.md
+
+use std::io;
+
+const MAGIC: u32 = 0x184D_2204;
+/// Version `01`, independent blocks, no checksums, no content size, no dictionary.
+const FLG: u8 = 0b0110_0000;
+/// 4MB maximum block size.
+const BD: u8 = 0b0111_0000;
+const MAX_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+const MIN_MATCH: usize = 4;
+/// The last match must start at least this many bytes before the end of a block.
+const MATCH_FIND_LIMIT: usize = 12;
+/// The last bytes of a block are always literals.
+const LAST_LITERALS: usize = 5;
+const MAX_OFFSET: usize = 65_535;
+const HASH_LOG: u32 = 16;
+
+/// Compress `data` into a single LZ4 frame.
+pub(super) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + 16);
+    out.extend_from_slice(&MAGIC.to_le_bytes());
+    out.extend_from_slice(&[FLG, BD, header_checksum(&[FLG, BD])]);
+
+    let mut block = Vec::new();
+    for chunk in data.chunks(MAX_BLOCK_SIZE) {
+        block.clear();
+        compress_block(chunk, &mut block);
+        if block.len() < chunk.len() {
+            out.extend_from_slice(&block_size(block.len(), false));
+            out.extend_from_slice(&block);
+        } else {
+            out.extend_from_slice(&block_size(chunk.len(), true));
+            out.extend_from_slice(chunk);
+        }
+    }
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out
+}
+
+/// Decompress a single LZ4 frame.
+pub(super) fn decompress(frame: &[u8]) -> io::Result<Vec<u8>> {
+    let mut input = Input(frame);
+    if input.u32()? != MAGIC {
+        return Err(invalid("invalid LZ4 frame magic"));
+    }
+    let flg = input.u8()?;
+    let _bd = input.u8()?;
+    if flg >> 6 != 0b01 {
+        return Err(invalid("unsupported LZ4 frame version"));
+    }
+    let has_block_checksum = flg & 0b0001_0000 != 0;
+    let has_content_size = flg & 0b0000_1000 != 0;
+    let has_content_checksum = flg & 0b0000_0100 != 0;
+    let has_dict_id = flg & 0b0000_0001 != 0;
+    if has_content_size {
+        input.take(8)?;
+    }
+    if has_dict_id {
+        input.take(4)?;
+    }
+    let _header_checksum = input.u8()?;
+
+    let mut out = Vec::new();
+    loop {
+        let size = input.u32()?;
+        if size == 0 {
+            break;
+        }
+        let uncompressed = size & 0x8000_0000 != 0;
+        let len = (size & 0x7FFF_FFFF) as usize;
+        let block = input.take(len)?;
+        if uncompressed {
+            out.extend_from_slice(block);
+        } else {
+            decompress_block(block, &mut out)?;
+        }
+        if has_block_checksum {
+            input.take(4)?;
+        }
+    }
+    if has_content_checksum {
+        input.take(4)?;
+    }
+    Ok(out)
+}
+
+fn block_size(len: usize, uncompressed: bool) -> [u8; 4] {
+    let len = u32::try_from(len).expect("block size fits in u32");
+    let flag = if uncompressed { 0x8000_0000 } else { 0 };
+    (len | flag).to_le_bytes()
+}
+
+fn header_checksum(descriptor: &[u8]) -> u8 {
+    (xxh32(descriptor, 0) >> 8).to_le_bytes()[0]
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2_654_435_761) >> (32 - HASH_LOG)) as usize
+}
+
+/// Compress `data` as a single LZ4 block, appending to `out`.
+fn compress_block(data: &[u8], out: &mut Vec<u8>) {
+    let mut table = vec![0usize; 1 << HASH_LOG];
+    let mut literal_start = 0;
+    let mut pos = 0;
+
+    if data.len() > MATCH_FIND_LIMIT {
+        let match_limit = data.len() - MATCH_FIND_LIMIT;
+        while pos < match_limit {
+            let sequence = read_u32(data, pos);
+            let slot = hash(sequence);
+            // Table entries are stored offset by one so zero means empty.
+            let candidate = table[slot].checked_sub(1);
+            table[slot] = pos + 1;
+
+            let Some(candidate) = candidate.filter(|&candidate| {
+                pos - candidate <= MAX_OFFSET && read_u32(data, candidate) == sequence
+            }) else {
+                pos += 1;
+                continue;
+            };
+
+            let end_limit = data.len() - LAST_LITERALS;
+            let mut match_len = MIN_MATCH;
+            while pos + match_len < end_limit
+                && data[candidate + match_len] == data[pos + match_len]
+            {
+                match_len += 1;
+            }
+
+            write_sequence(
+                out,
+                &data[literal_start..pos],
+                Some((pos - candidate, match_len)),
+            );
+            pos += match_len;
+            literal_start = pos;
+        }
+    }
+
+    write_sequence(out, &data[literal_start..], None);
+}
+
+fn write_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(u8::try_from(len).expect("length less than 255"));
+}
+
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let literal_nibble = literals.len().min(15);
+    let match_nibble = matched.map_or(0, |(_, len)| (len - MIN_MATCH).min(15));
+    out.push(u8::try_from(literal_nibble << 4 | match_nibble).expect("token fits in u8"));
+    if literals.len() >= 15 {
+        write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if let Some((offset, len)) = matched {
+        let offset = u16::try_from(offset).expect("offset within window");
+        out.extend_from_slice(&offset.to_le_bytes());
+        if len - MIN_MATCH >= 15 {
+            write_length(out, len - MIN_MATCH - 15);
+        }
+    }
+}
+
+/// Decompress a single LZ4 block, appending to `out`. Matches may reach back into earlier blocks,
+/// which supports frames written with linked blocks.
+fn decompress_block(block: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    let mut input = Input(block);
+    while !input.0.is_empty() {
+        let token = input.u8()?;
+        let literal_len = input.length(usize::from(token >> 4))?;
+        out.extend_from_slice(input.take(literal_len)?);
+        if input.0.is_empty() {
+            break;
+        }
+
+        let offset = usize::from(u16::from_le_bytes([input.u8()?, input.u8()?]));
+        if offset == 0 || offset > out.len() {
+            return Err(invalid("invalid LZ4 match offset"));
+        }
+        let match_len = input.length(usize::from(token & 0x0F))? + MIN_MATCH;
+        let start = out.len() - offset;
+        // Matches may overlap the bytes they produce, so copy one byte at a time.
+        for i in 0..match_len {
+            out.push(out[start + i]);
+        }
+    }
+    Ok(())
+}
+
+/// A cursor over compressed input.
+struct Input<'a>(&'a [u8]);
+
+impl<'a> Input<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.0.len() {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(read_u32(self.take(4)?, 0))
+    }
+
+    /// Reads a length which continues into extra bytes when its 4-bit `nibble` is saturated.
+    fn length(&mut self, nibble: usize) -> io::Result<usize> {
+        let mut len = nibble;
+        if nibble == 15 {
+            loop {
+                let byte = self.u8()?;
+                len += usize::from(byte);
+                if byte != 255 {
+                    break;
+                }
+            }
+        }
+        Ok(len)
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// 32-bit xxHash, used for the frame header checksum.
+fn xxh32(data: &[u8], seed: u32) -> u32 {
+    const PRIME1: u32 = 2_654_435_761;
+    const PRIME2: u32 = 2_246_822_519;
+    const PRIME3: u32 = 3_266_489_917;
+    const PRIME4: u32 = 668_265_263;
+    const PRIME5: u32 = 374_761_393;
+
+    let round = |acc: u32, lane: u32| {
+        acc.wrapping_add(lane.wrapping_mul(PRIME2))
+            .rotate_left(13)
+            .wrapping_mul(PRIME1)
+    };
+
+    let mut stripes = data.chunks_exact(16);
+    let mut hash = if data.len() >= 16 {
+        let mut acc = [
+            seed.wrapping_add(PRIME1).wrapping_add(PRIME2),
+            seed.wrapping_add(PRIME2),
+            seed,
+            seed.wrapping_sub(PRIME1),
+        ];
+        for stripe in &mut stripes {
+            for (i, acc) in acc.iter_mut().enumerate() {
+                *acc = round(*acc, read_u32(stripe, i * 4));
+            }
+        }
+        acc[0]
+            .rotate_left(1)
+            .wrapping_add(acc[1].rotate_left(7))
+            .wrapping_add(acc[2].rotate_left(12))
+            .wrapping_add(acc[3].rotate_left(18))
+    } else {
+        seed.wrapping_add(PRIME5)
+    };
+    #[allow(clippy::cast_possible_truncation)]
+    let len = data.len() as u32;
+    hash = hash.wrapping_add(len);
+
+    let remainder = stripes.remainder();
+    let mut words = remainder.chunks_exact(4);
+    for word in &mut words {
+        hash = hash
+            .wrapping_add(read_u32(word, 0).wrapping_mul(PRIME3))
+            .rotate_left(17)
+            .wrapping_mul(PRIME4);
+    }
+    for &byte in words.remainder() {
+        hash = hash
+            .wrapping_add(u32::from(byte).wrapping_mul(PRIME5))
+            .rotate_left(11)
+            .wrapping_mul(PRIME1);
+    }
+
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(PRIME2);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(PRIME3);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn xxh32_known_values() {
+        assert_eq!(xxh32(b"", 0), 0x02CC_5D05);
+        assert_eq!(xxh32(b"a", 0), 0x550D_7456);
+        assert_eq!(xxh32(b"abc", 0), 0x32D1_53FF);
+        assert_eq!(
+            xxh32(b"Nobody inspects the spammish repetition", 0),
+            0xE229_3B2F
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let repetitive: Vec<u8> = b"profile anchor "
+            .iter()
+            .copied()
+            .cycle()
+            .take(100_000)
+            .collect();
+        let mut state = 0x1234_5678u32;
+        let noisy: Vec<u8> = (0..10_000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state.to_le_bytes()[0]
+            })
+            .collect();
+
+        for data in [&b""[..], b"short", &repetitive, &noisy] {
+            let frame = compress(data);
+            assert_eq!(decompress(&frame).expect("valid frame"), data);
+        }
+        assert!(compress(&repetitive).len() < repetitive.len() / 10);
+    }
+}
@@ -0,0 +1,448 @@
+//! Manually delimited profile blocks and spans.
+//!
+//! `profile!` relies on a `Drop` guard, which only works for regions that begin and end in the same
+//! lexical scope. [`block_begin`] and [`block_end`] instead let timing span non-lexical regions
+//! such as state machines, callback-based code, or FFI boundaries. Blocks started this way become
+//! the parent of any blocks started before they end, just like `profile!`. [`begin_block`] and
+//! [`end_block`] are the same functions, taking a [`BlockHandle`].
+//!
+//! [`span_begin`] and [`span_end`] are for regions which start in one function and finish in
+//! another, such as a request moving through a pipeline. The returned [`Span`] token is carried
+//! through application state, and, unlike a block, a span is never the parent of other blocks since
+//! unrelated work may run while it is open.
+//!
+//! [`record_external`] injects timings measured elsewhere, such as GPU queries, as hits of an
+//! anchor.
+
+/// Identifies an active block started with [`block_begin`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[must_use]
+pub struct BlockId(u64);
+
+/// A handle to an active block started with [`begin_block`], the same as a [`BlockId`].
+pub type BlockHandle = BlockId;
+
+#[doc(inline)]
+pub use self::{block_begin as begin_block, block_end as end_block};
+
+/// Begin a profile block named `name`, returning an ID which must be passed to [`block_end`] to end
+/// it.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{block_begin, block_end};
+///
+/// let id = block_begin("handshake");
+/// // ... callbacks, state transitions ...
+/// block_end(id);
+/// ```
+#[inline]
+pub fn block_begin(name: &'static str) -> BlockId {
+    #[cfg(feature = "perf")]
+    {
+        use super::{ProfileBlock, GLOBAL_PROFILER};
+
+        let block = ProfileBlock::new(name, 0);
+        let id = BlockId(block.id);
+        GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().manual_blocks.push((id, block)));
+        id
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = name;
+        BlockId(0)
+    }
+}
+
+/// End a profile block started with [`block_begin`]. In release builds, ending a block which has
+/// already ended has no effect.
+///
+/// Blocks may end in a different order than they began, such as overlapping requests, in which
+/// case the block is still recorded and, in debug builds, a warning is added to the profile report.
+///
+/// # Panics
+///
+/// Panics in debug builds if the block isn't active, because it has already ended, or because it
+/// began on another thread or before profiling was restarted.
+#[inline]
+pub fn block_end(id: BlockId) {
+    #[cfg(feature = "perf")]
+    {
+        let block = super::GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            let index = profiler
+                .manual_blocks
+                .iter()
+                .rposition(|(block_id, _)| *block_id == id)?;
+            let (_, block) = profiler.manual_blocks.remove(index);
+            if profiler
+                .stack
+                .last()
+                .is_some_and(|active| active.id != block.id)
+            {
+                let innermost = profiler.parent().unwrap_or_default();
+                profiler.warn(format!(
+                    "block `{}` ended out of order while `{innermost}` was active",
+                    block.name
+                ));
+            }
+            Some(block)
+        });
+        debug_assert!(block.is_some(), "ended {id:?} which is not active");
+        // Dropping records the block, which must happen after the profiler borrow is released.
+        drop(block);
+    }
+    #[cfg(not(feature = "perf"))]
+    let _ = id;
+}
+
+/// A profiled region which may begin and end in different scopes. Created by [`span_begin`].
+#[derive(Debug)]
+#[must_use]
+pub struct Span {
+    #[cfg(feature = "perf")]
+    name: &'static str,
+    #[cfg(feature = "perf")]
+    parent: Option<u64>,
+    #[cfg(feature = "perf")]
+    thread: std::thread::ThreadId,
+    #[cfg(feature = "perf")]
+    start_tsc: u64,
+    /// Whether profiling was enabled when the span began, otherwise it records nothing.
+    #[cfg(feature = "perf")]
+    enabled: bool,
+}
+
+/// Begin a span named `name`, returning a token which must be passed to [`span_end`] to end it.
+///
+/// The block active when the span begins is credited as its parent, provided that block is still
+/// active when the span ends on the same thread. Otherwise the span is recorded without a parent,
+/// since it no longer nests inside it.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{span_begin, span_end, Span};
+///
+/// struct Request {
+///     span: Span,
+/// }
+///
+/// fn accept() -> Request {
+///     Request {
+///         span: span_begin("request"),
+///     }
+/// }
+///
+/// fn respond(request: Request) {
+///     span_end(request.span);
+/// }
+///
+/// respond(accept());
+/// ```
+#[inline]
+pub fn span_begin(name: &'static str) -> Span {
+    #[cfg(feature = "perf")]
+    {
+        use super::{Profiler, GLOBAL_PROFILER};
+
+        if !super::is_enabled() {
+            return Span {
+                name,
+                parent: None,
+                thread: std::thread::current().id(),
+                start_tsc: 0,
+                enabled: false,
+            };
+        }
+        let (name, parent) = GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            let parent = profiler.stack.last().map(|block| block.id);
+            (profiler.namespaced(name), parent)
+        });
+        Span {
+            name,
+            parent,
+            thread: std::thread::current().id(),
+            start_tsc: Profiler::read_block_timer(),
+            enabled: true,
+        }
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = name;
+        Span {}
+    }
+}
+
+/// End a span started with [`span_begin`], recording its elapsed time.
+#[inline]
+#[allow(clippy::needless_pass_by_value)]
+pub fn span_end(span: Span) {
+    #[cfg(feature = "perf")]
+    {
+        use super::{Profiler, GLOBAL_PROFILER};
+
+        if !span.enabled {
+            return;
+        }
+        let end_tsc = Profiler::read_block_timer();
+        let same_thread = span.thread == std::thread::current().id();
+        GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            let parent = span
+                .parent
+                .filter(|_| same_thread)
+                .and_then(|id| profiler.stack.iter().find(|block| block.id == id))
+                .map(|block| block.anchor);
+            record_hit(&mut profiler, span.name, parent, span.start_tsc, end_tsc);
+        });
+    }
+    #[cfg(not(feature = "perf"))]
+    let _ = span;
+}
+
+/// Record a hit of the anchor `name` which started at `start_tsc` and ended at `end_tsc`, for
+/// timings measured outside the profiler such as GPU queries, kernel events or another process's
+/// data. The hit is nested in the anchor named `parent` in reports, if any, just like a block
+/// started within it.
+///
+/// Timestamps are in timestamp counter ticks, as returned by [`timestamp`], so timings from other
+/// clocks must be converted using [`timer_frequency`] first.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{record_external, timer_frequency, timestamp};
+///
+/// // A GPU query reporting 2ms of work which finished just now.
+/// let end = timestamp();
+/// let start = end.saturating_sub(2 * timer_frequency() / 1000);
+/// record_external("gpu::shadow_pass", start, end, Some("render"));
+/// ```
+#[inline]
+pub fn record_external(
+    name: &'static str,
+    start_tsc: u64,
+    end_tsc: u64,
+    parent: Option<&'static str>,
+) {
+    #[cfg(feature = "perf")]
+    if super::is_enabled() {
+        super::GLOBAL_PROFILER.with(|profiler| {
+            let mut profiler = profiler.borrow_mut();
+            let name = profiler.namespaced(name);
+            let parent = parent.map(|parent| profiler.anchor_index(parent));
+            record_hit(&mut profiler, name, parent, start_tsc, end_tsc);
+        });
+    }
+    #[cfg(not(feature = "perf"))]
+    let _ = (name, start_tsc, end_tsc, parent);
+}
+
+/// Returns the current timestamp counter, or `0` without the `perf` feature.
+#[inline]
+#[must_use]
+pub fn timestamp() -> u64 {
+    #[cfg(feature = "perf")]
+    {
+        super::Profiler::read_block_timer()
+    }
+    #[cfg(not(feature = "perf"))]
+    0
+}
+
+/// Returns the timestamp counter frequency in ticks per second, or `0` without the `perf`
+/// feature.
+#[must_use]
+pub fn timer_frequency() -> u64 {
+    #[cfg(feature = "perf")]
+    {
+        super::Profiler::timer_freq()
+    }
+    #[cfg(not(feature = "perf"))]
+    0
+}
+
+/// Records a hit of the anchor `name` from `start_tsc` to `end_tsc`, nested in the anchor at index
+/// `parent`.
+#[cfg(feature = "perf")]
+fn record_hit(
+    profiler: &mut super::Profiler,
+    name: &'static str,
+    parent: Option<usize>,
+    start_tsc: u64,
+    end_tsc: u64,
+) {
+    if end_tsc < start_tsc {
+        profiler.backwards_reads += 1;
+    }
+    let elapsed = end_tsc.saturating_sub(start_tsc);
+    let index = profiler.anchor_index(name);
+    if let Some(parent) = parent {
+        profiler.record_call(parent, index, elapsed);
+        let parent = &mut profiler.anchors[parent];
+        parent.tsc_elapsed_exclusive = parent.tsc_elapsed_exclusive.wrapping_sub(elapsed);
+    }
+
+    let anchor = &mut profiler.anchors[index];
+    anchor.hit_count += 1;
+    anchor.tsc_elapsed_exclusive = anchor.tsc_elapsed_exclusive.wrapping_add(elapsed);
+    anchor.tsc_elapsed_inclusive = anchor.tsc_elapsed_inclusive.saturating_add(elapsed);
+    anchor.tsc_elapsed_squares = anchor
+        .tsc_elapsed_squares
+        .saturating_add(u128::from(elapsed) * u128::from(elapsed));
+    anchor.tsc_min = anchor.tsc_min.min(elapsed);
+    anchor.tsc_max = anchor.tsc_max.max(elapsed);
+}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+    use crate::{
+        performance::{profile_begin, GLOBAL_PROFILER},
+        profile,
+    };
+
+    #[test]
+    fn manual_blocks() {
+        profile_begin();
+        let outer = block_begin("manual_outer");
+        let inner = block_begin("manual_inner");
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        block_end(inner);
+        block_end(outer);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        assert!(report.warnings.is_empty());
+        let outer = &report.anchors[0];
+        let inner = &report.anchors[1];
+        assert_eq!((outer.name, outer.hit_count), ("manual_outer", 1));
+        assert_eq!((inner.name, inner.hit_count), ("manual_inner", 1));
+        assert!(outer.tsc_elapsed_inclusive >= inner.tsc_elapsed_inclusive);
+        assert_eq!(
+            outer.tsc_elapsed_exclusive,
+            outer.tsc_elapsed_inclusive - inner.tsc_elapsed_inclusive
+        );
+    }
+
+    #[test]
+    fn out_of_order_blocks() {
+        profile_begin();
+        let first = block_begin("ooo_first");
+        let second = block_begin("ooo_second");
+        block_end(first);
+        {
+            profile!("ooo_child");
+        }
+        block_end(second);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let second = &report.anchors[1];
+        let child = &report.anchors[2];
+        assert_eq!(second.name, "ooo_second");
+        assert_eq!(
+            second.tsc_elapsed_exclusive,
+            second.tsc_elapsed_inclusive - child.tsc_elapsed_inclusive
+        );
+        GLOBAL_PROFILER.with(|profiler| assert!(profiler.borrow().stack.is_empty()));
+        #[cfg(debug_assertions)]
+        assert_eq!(
+            report.warnings,
+            ["block `ooo_first` ended out of order while `ooo_second` was active"]
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    fn unbalanced_blocks() {
+        profile_begin();
+        let ended = begin_block("unbalanced_ended");
+        end_block(ended);
+        let ended_twice = std::panic::catch_unwind(|| end_block(ended));
+        assert!(ended_twice.is_err());
+        let _leaked: BlockHandle = begin_block("unbalanced_leaked");
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        assert_eq!(report.anchors[0].hit_count, 1);
+        assert_eq!(
+            report.warnings,
+            ["block `unbalanced_leaked` was begun but never ended"]
+        );
+    }
+
+    #[test]
+    fn external_timings() {
+        profile_begin();
+        {
+            profile!("external_parent");
+            let end = timestamp();
+            record_external("external_gpu", end - 300, end, Some("external_parent"));
+            record_external("external_gpu", end - 100, end, Some("external_parent"));
+        }
+        record_external("external_orphan", 50, 10, None);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let find = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .expect("valid anchor")
+        };
+        let (parent, gpu) = (find("external_parent"), find("external_gpu"));
+        assert_eq!((gpu.hit_count, gpu.tsc_elapsed_inclusive), (2, 400));
+        assert_eq!(gpu.tsc_elapsed_squares, 300 * 300 + 100 * 100);
+        assert_eq!(
+            parent.tsc_elapsed_exclusive,
+            parent.tsc_elapsed_inclusive - 400
+        );
+        assert!(report.calls.iter().any(|call| {
+            (call.caller, call.callee, call.call_count) == ("external_parent", "external_gpu", 2)
+        }));
+        assert_eq!(find("external_orphan").tsc_elapsed_inclusive, 0);
+        assert!(report
+            .warnings
+            .iter()
+            .any(|warning| warning.starts_with("timestamp counter went backwards in 1 blocks")));
+        assert!(timer_frequency() > 0);
+    }
+
+    fn start_request() -> Span {
+        profile!("request_start");
+        span_begin("request")
+    }
+
+    #[test]
+    fn cross_scope_spans() {
+        profile_begin();
+        let span = start_request();
+        let nested = {
+            profile!("request_parent");
+            let nested = span_begin("nested_span");
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            span_end(nested);
+            span
+        };
+        span_end(nested);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let find = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .expect("valid anchor")
+        };
+        let (start, parent) = (find("request_start"), find("request_parent"));
+        let (request, nested) = (find("request"), find("nested_span"));
+        assert_eq!(request.hit_count, 1);
+        assert_eq!(start.tsc_elapsed_exclusive, start.tsc_elapsed_inclusive);
+        assert_eq!(
+            parent.tsc_elapsed_exclusive,
+            parent.tsc_elapsed_inclusive - nested.tsc_elapsed_inclusive
+        );
+        assert!(request.tsc_elapsed_inclusive >= nested.tsc_elapsed_inclusive);
+    }
+}
@@ -0,0 +1,152 @@
+//! Massif-format memory profiles.
+//!
+//! A [`HeapRecorder`] samples the heap usage counted by a
+//! [`TrackingAllocator`](super::memory::TrackingAllocator) at a fixed interval, and
+//! [`write_massif`] writes the samples in the format produced by Valgrind's massif tool, so
+//! `ms_print` or massif-visualizer can graph memory growth over a profiled run. Only total heap
+//! size is recorded, so every snapshot has an empty heap tree.
+
+use super::memory::heap_usage;
+use std::{
+    io::{self, Write},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Heap usage at a point in time.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct HeapSample {
+    /// Time since recording started.
+    pub time: Duration,
+    /// Bytes allocated at that time.
+    pub heap_bytes: usize,
+}
+
+/// Records heap usage on a background thread until finished.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{fs::File, time::Duration};
+/// use util_lib_rs::performance::massif::{write_massif, HeapRecorder};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let recorder = HeapRecorder::start(Duration::from_millis(10))?;
+/// // run the profiled work...
+/// let samples = recorder.finish();
+/// write_massif(&samples, "my_app", File::create("massif.out.my_app")?)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct HeapRecorder {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<Vec<HeapSample>>>,
+}
+
+impl HeapRecorder {
+    /// Starts sampling heap usage every `interval`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the recorder thread can't be spawned.
+    pub fn start(interval: Duration) -> io::Result<Self> {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-heap-recorder".to_string())
+            .spawn(move || {
+                let start = Instant::now();
+                let sample = || HeapSample {
+                    time: start.elapsed(),
+                    heap_bytes: heap_usage().heap_bytes,
+                };
+                let mut samples = vec![sample()];
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    samples.push(sample());
+                }
+                samples.push(sample());
+                samples
+            })?;
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+
+    /// Stops recording, returning every sample taken.
+    #[must_use]
+    pub fn finish(mut self) -> Vec<HeapSample> {
+        self.stop()
+    }
+
+    fn stop(&mut self) -> Vec<HeapSample> {
+        drop(self.stop.take());
+        self.thread
+            .take()
+            .and_then(|thread| thread.join().ok())
+            .unwrap_or_default()
+    }
+}
+
+impl Drop for HeapRecorder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Writes `samples` to `writer` in massif format, with `cmd` as the profiled command.
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_massif(samples: &[HeapSample], cmd: &str, mut writer: impl Write) -> io::Result<()> {
+    writeln!(writer, "desc: (none)")?;
+    writeln!(writer, "cmd: {cmd}")?;
+    writeln!(writer, "time_unit: ms")?;
+    for (i, sample) in samples.iter().enumerate() {
+        writeln!(writer, "#-----------")?;
+        writeln!(writer, "snapshot={i}")?;
+        writeln!(writer, "#-----------")?;
+        writeln!(writer, "time={}", sample.time.as_millis())?;
+        writeln!(writer, "mem_heap_B={}", sample.heap_bytes)?;
+        writeln!(writer, "mem_heap_extra_B=0")?;
+        writeln!(writer, "mem_stacks_B=0")?;
+        writeln!(writer, "heap_tree=empty")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn massif_output() {
+        let samples = [
+            HeapSample {
+                time: Duration::ZERO,
+                heap_bytes: 0,
+            },
+            HeapSample {
+                time: Duration::from_millis(15),
+                heap_bytes: 4096,
+            },
+        ];
+        let mut output = Vec::new();
+        write_massif(&samples, "app --flag", &mut output).expect("valid write");
+        let output = String::from_utf8(output).expect("valid utf8");
+        assert!(output.starts_with("desc: (none)\ncmd: app --flag\ntime_unit: ms\n"));
+        assert!(output.ends_with(
+            "snapshot=1\n#-----------\ntime=15\nmem_heap_B=4096\nmem_heap_extra_B=0\n\
+             mem_stacks_B=0\nheap_tree=empty\n"
+        ));
+
+        let samples = HeapRecorder::start(Duration::from_millis(1))
+            .expect("recorder thread")
+            .finish();
+        assert!(samples.len() >= 2);
+    }
+}
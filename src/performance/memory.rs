@@ -0,0 +1,172 @@
+//! Heap usage tracking.
+//!
+//! Install a [`TrackingAllocator`] as the `#[global_allocator]` to count every allocation the
+//! program makes, then read the totals with [`heap_usage`] or record them over time with a
+//! [`HeapRecorder`](super::massif::HeapRecorder). Allocations are also counted per thread and
+//! attributed to the innermost active profile block, so reports show which anchors allocate.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+    sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+};
+
+static TRACKING: AtomicBool = AtomicBool::new(false);
+static HEAP_BYTES: AtomicUsize = AtomicUsize::new(0);
+static PEAK_HEAP_BYTES: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+static DEALLOCATIONS: AtomicU64 = AtomicU64::new(0);
+
+/// Allocations counted on a single thread.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(super) struct ThreadAllocations {
+    /// Number of allocations, counting each reallocation as one.
+    pub(super) count: u64,
+    /// Bytes allocated.
+    pub(super) allocated_bytes: u64,
+    /// Bytes freed.
+    pub(super) freed_bytes: u64,
+}
+
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+impl ThreadAllocations {
+    /// Returns the allocations counted between `earlier` and `self`.
+    pub(super) fn since(self, earlier: Self) -> Self {
+        Self {
+            count: self.count.wrapping_sub(earlier.count),
+            allocated_bytes: self.allocated_bytes.wrapping_sub(earlier.allocated_bytes),
+            freed_bytes: self.freed_bytes.wrapping_sub(earlier.freed_bytes),
+        }
+    }
+}
+
+thread_local! {
+    // Constant initialization without a destructor, so the allocator can count without allocating.
+    static THREAD_ALLOCATIONS: Cell<ThreadAllocations> = const {
+        Cell::new(ThreadAllocations {
+            count: 0,
+            allocated_bytes: 0,
+            freed_bytes: 0,
+        })
+    };
+}
+
+/// A global allocator wrapper which counts the allocations made through `A`.
+///
+/// # Examples
+///
+/// ```
+/// use std::alloc::System;
+/// use util_lib_rs::performance::memory::TrackingAllocator;
+///
+/// #[global_allocator]
+/// static ALLOCATOR: TrackingAllocator<System> = TrackingAllocator::new(System);
+/// ```
+#[derive(Debug, Default)]
+pub struct TrackingAllocator<A = System> {
+    inner: A,
+}
+
+impl<A> TrackingAllocator<A> {
+    /// Wraps the allocator `inner`.
+    pub const fn new(inner: A) -> Self {
+        Self { inner }
+    }
+
+    fn allocated(size: usize) {
+        TRACKING.store(true, Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        let heap_bytes = HEAP_BYTES.fetch_add(size, Ordering::Relaxed) + size;
+        PEAK_HEAP_BYTES.fetch_max(heap_bytes, Ordering::Relaxed);
+        let _ = THREAD_ALLOCATIONS.try_with(|counts| {
+            let mut updated = counts.get();
+            updated.count += 1;
+            updated.allocated_bytes += size as u64;
+            counts.set(updated);
+        });
+    }
+
+    fn deallocated(size: usize) {
+        DEALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        HEAP_BYTES.fetch_sub(size, Ordering::Relaxed);
+        let _ = THREAD_ALLOCATIONS.try_with(|counts| {
+            let mut updated = counts.get();
+            updated.freed_bytes += size as u64;
+            counts.set(updated);
+        });
+    }
+}
+
+// SAFETY: Defers every allocation to `A`, only counting calls to it.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for TrackingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc(layout);
+        if !ptr.is_null() {
+            Self::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = self.inner.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            Self::allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout);
+        Self::deallocated(layout.size());
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = self.inner.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            Self::deallocated(layout.size());
+            Self::allocated(new_size);
+        }
+        new_ptr
+    }
+}
+
+/// Heap usage counted by a [`TrackingAllocator`] since the program started.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct HeapUsage {
+    /// Bytes currently allocated.
+    pub heap_bytes: usize,
+    /// Most bytes allocated at once.
+    pub peak_heap_bytes: usize,
+    /// Number of allocations, counting each reallocation as one.
+    pub allocations: u64,
+    /// Number of deallocations, counting each reallocation as one.
+    pub deallocations: u64,
+}
+
+/// Returns whether a [`TrackingAllocator`] is installed and has counted an allocation.
+#[must_use]
+pub fn is_tracking() -> bool {
+    TRACKING.load(Ordering::Relaxed)
+}
+
+/// Returns the allocations counted on the current thread so far, or `None` unless a
+/// [`TrackingAllocator`] is installed.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn thread_allocations() -> Option<ThreadAllocations> {
+    if !is_tracking() {
+        return None;
+    }
+    THREAD_ALLOCATIONS.try_with(Cell::get).ok()
+}
+
+/// Returns the heap usage counted so far, which is all zero unless a [`TrackingAllocator`] is
+/// installed.
+pub fn heap_usage() -> HeapUsage {
+    HeapUsage {
+        heap_bytes: HEAP_BYTES.load(Ordering::Relaxed),
+        peak_heap_bytes: PEAK_HEAP_BYTES.load(Ordering::Relaxed),
+        allocations: ALLOCATIONS.load(Ordering::Relaxed),
+        deallocations: DEALLOCATIONS.load(Ordering::Relaxed),
+    }
+}
@@ -0,0 +1,154 @@
+//! Prometheus exposition format output.
+//!
+//! Renders the anchors of a [`ProfileReport`] as counters in the Prometheus text exposition
+//! format, labelled by anchor name, so a long-running service can serve
+//! [`metrics_text`](super::metrics_text) from its `/metrics` handler and chart profiling data
+//! over time. Every metric is a running total, so dashboards can graph rates with `rate()`.
+
+use super::ProfileReport;
+use std::{
+    borrow::Cow,
+    fmt::{self, Write},
+};
+
+/// Name, help text and value in the report of each exposed counter.
+type Counter = (&'static str, &'static str, fn(&ProfileReport, usize) -> f64);
+
+#[allow(clippy::cast_precision_loss)]
+const COUNTERS: [Counter; 4] = [
+    (
+        "profile_anchor_hits_total",
+        "Number of times the anchor was hit.",
+        |report, index| report.anchors[index].hit_count as f64,
+    ),
+    (
+        "profile_anchor_exclusive_seconds_total",
+        "Time spent in the anchor, excluding its children.",
+        |report, index| seconds(report, report.anchors[index].tsc_elapsed_exclusive),
+    ),
+    (
+        "profile_anchor_inclusive_seconds_total",
+        "Time spent in the anchor, including its children.",
+        |report, index| seconds(report, report.anchors[index].tsc_elapsed_inclusive),
+    ),
+    (
+        "profile_anchor_bytes_total",
+        "Bytes processed by the anchor.",
+        |report, index| report.anchors[index].byte_count as f64,
+    ),
+];
+
+/// Converts `tsc` ticks of the report's timer to seconds, or `0.0` if its frequency is unknown.
+#[allow(clippy::cast_precision_loss)]
+fn seconds(report: &ProfileReport, tsc: u64) -> f64 {
+    if report.timer_freq == 0 {
+        0.0
+    } else {
+        tsc as f64 / report.timer_freq as f64
+    }
+}
+
+/// Writes the anchors of `report` to `writer` in the Prometheus text exposition format.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{metrics::write_metrics, AnchorStats, ProfileReport};
+///
+/// let report = ProfileReport {
+///     timer_freq: 1000,
+///     anchors: vec![AnchorStats {
+///         name: "parse",
+///         hit_count: 2,
+///         tsc_elapsed_exclusive: 500,
+///         tsc_elapsed_inclusive: 1500,
+///         ..AnchorStats::default()
+///     }],
+///     ..ProfileReport::default()
+/// };
+/// let mut output = String::new();
+/// write_metrics(&report, &mut output).unwrap();
+/// assert!(output.contains("profile_anchor_exclusive_seconds_total{anchor=\"parse\"} 0.5\n"));
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_metrics(report: &ProfileReport, mut writer: impl Write) -> fmt::Result {
+    for (name, help, value) in COUNTERS {
+        writeln!(writer, "# HELP {name} {help}")?;
+        writeln!(writer, "# TYPE {name} counter")?;
+        for (index, anchor) in report.anchors.iter().enumerate() {
+            writeln!(
+                writer,
+                "{name}{{anchor=\"{}\"}} {}",
+                escape(anchor.name),
+                value(report, index)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Escapes backslashes, quotes and line breaks in a label value.
+fn escape(value: &str) -> Cow<'_, str> {
+    if value.contains(['\\', '"', '\n']) {
+        Cow::Owned(
+            value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n"),
+        )
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::AnchorStats;
+
+    #[test]
+    fn metrics_output() {
+        let report = ProfileReport {
+            timer_freq: 4,
+            anchors: vec![
+                AnchorStats {
+                    name: "main",
+                    hit_count: 1,
+                    tsc_elapsed_exclusive: 2,
+                    tsc_elapsed_inclusive: 8,
+                    ..AnchorStats::default()
+                },
+                AnchorStats {
+                    name: "parse<\"a\\b\">",
+                    hit_count: 3,
+                    tsc_elapsed_exclusive: 6,
+                    tsc_elapsed_inclusive: 6,
+                    byte_count: 4096,
+                    ..AnchorStats::default()
+                },
+            ],
+            ..ProfileReport::default()
+        };
+
+        let mut output = String::new();
+        write_metrics(&report, &mut output).expect("valid write");
+        assert!(output.starts_with(
+            "# HELP profile_anchor_hits_total Number of times the anchor was hit.\n\
+             # TYPE profile_anchor_hits_total counter\n\
+             profile_anchor_hits_total{anchor=\"main\"} 1\n\
+             profile_anchor_hits_total{anchor=\"parse<\\\"a\\\\b\\\">\"} 3\n"
+        ));
+        assert!(output.contains("profile_anchor_exclusive_seconds_total{anchor=\"main\"} 0.5\n"));
+        assert!(output.contains("profile_anchor_inclusive_seconds_total{anchor=\"main\"} 2\n"));
+        assert!(
+            output.ends_with("profile_anchor_bytes_total{anchor=\"parse<\\\"a\\\\b\\\">\"} 4096\n")
+        );
+
+        let mut empty = String::new();
+        write_metrics(&ProfileReport::default(), &mut empty).expect("valid write");
+        assert_eq!(empty.lines().count(), 2 * COUNTERS.len());
+    }
+}
@@ -0,0 +1,273 @@
+//! Anchor name shortening.
+//!
+//! Names generated by `profile!()` come from [`std::any::type_name`], which for generic functions
+//! can run to hundreds of characters. A [`NamePolicy`] installed with [`set_name_policy`] shortens
+//! every anchor name the same way in all report formats, after renames and filters are applied.
+//!
+//! Generic functions share one `profile!()` anchor for every instantiation unless
+//! [`set_monomorphized_names`] is enabled, which names each anchor after its concrete instantiation
+//! so `parse::<Json>` and `parse::<Toml>` can be compared. [`NamePolicy::roll_up_generics`] merges
+//! them back together.
+
+use crate::intern::intern_static;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex, PoisonError,
+};
+
+/// Elision marker for shortened names.
+const ELLIPSIS: char = '…';
+
+/// How to shorten anchor names in reports.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::names::NamePolicy;
+///
+/// let policy = NamePolicy::new().strip_crates(true).elide_generics(true);
+/// assert_eq!(
+///     policy.shorten("my_app::parse::<alloc::vec::Vec<u8>>"),
+///     "parse::<…>"
+/// );
+/// assert_eq!(NamePolicy::new().max_width(10).shorten("my_app::net::read"), "…net::read");
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct NamePolicy {
+    strip_crates: bool,
+    roll_up_generics: bool,
+    elide_generics: bool,
+    max_width: Option<usize>,
+}
+
+impl NamePolicy {
+    /// Creates a policy which leaves names unchanged.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Strip the leading crate name from every path in a name, including generic arguments, e.g.
+    /// `alloc::vec::Vec` becomes `vec::Vec`.
+    pub fn strip_crates(mut self, strip: bool) -> Self {
+        self.strip_crates = strip;
+        self
+    }
+
+    /// Remove generic arguments, e.g. `parse<Json>` becomes `parse`, merging the statistics of every
+    /// instantiation of a generic function. Qualified paths such as `<T as Trait>::f` are kept.
+    pub fn roll_up_generics(mut self, roll_up: bool) -> Self {
+        self.roll_up_generics = roll_up;
+        self
+    }
+
+    /// Replace generic arguments with `…`, e.g. `parse::<Vec<u8>>` becomes `parse::<…>`.
+    pub fn elide_generics(mut self, elide: bool) -> Self {
+        self.elide_generics = elide;
+        self
+    }
+
+    /// Limit names to `width` characters, eliding the start of longer names so the most specific
+    /// part of the path remains.
+    pub fn max_width(mut self, width: usize) -> Self {
+        self.max_width = Some(width);
+        self
+    }
+
+    /// Returns `name` shortened according to this policy.
+    #[must_use]
+    pub fn shorten(&self, name: &'static str) -> &'static str {
+        let mut shortened = name.to_string();
+        if self.roll_up_generics {
+            shortened = strip_generics(&shortened);
+        }
+        if self.elide_generics {
+            shortened = elide_generics(&shortened);
+        }
+        if self.strip_crates {
+            shortened = strip_crates(&shortened);
+        }
+        if let Some(width) = self.max_width {
+            let len = shortened.chars().count();
+            if len > width {
+                let keep = width.saturating_sub(1);
+                shortened = std::iter::once(ELLIPSIS)
+                    .chain(shortened.chars().skip(len - keep))
+                    .collect();
+            }
+        }
+        if shortened == name {
+            name
+        } else {
+            intern_static(&shortened)
+        }
+    }
+}
+
+/// Replaces the contents of every outermost `<...>` with `…`. The `>` of `->` doesn't close a
+/// generic argument list.
+fn elide_generics(name: &str) -> String {
+    let mut elided = String::with_capacity(name.len());
+    let mut depth = 0usize;
+    let mut prev = None;
+    for c in name.chars() {
+        match c {
+            '<' => {
+                if depth == 0 {
+                    elided.push('<');
+                    elided.push(ELLIPSIS);
+                }
+                depth += 1;
+            }
+            '>' if depth > 0 && prev != Some('-') => {
+                depth -= 1;
+                if depth == 0 {
+                    elided.push('>');
+                }
+            }
+            _ if depth == 0 => elided.push(c),
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    elided
+}
+
+/// Removes every generic argument list, along with the `::` of a turbofish. A `<` only opens a
+/// generic argument list directly after an identifier, since otherwise it starts a qualified path.
+fn strip_generics(name: &str) -> String {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut stripped = String::with_capacity(name.len());
+    let mut depth = 0usize;
+    let mut prev: Option<char> = None;
+    for c in name.chars() {
+        match c {
+            '<' if depth > 0 => depth += 1,
+            '<' if prev.is_some_and(is_ident) => depth = 1,
+            '<' if stripped.ends_with("::") && stripped.len() > 2 => {
+                stripped.truncate(stripped.len() - 2);
+                depth = 1;
+            }
+            '>' if depth > 0 && prev != Some('-') => depth -= 1,
+            _ if depth == 0 => stripped.push(c),
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    stripped
+}
+
+/// Removes the first segment of every path with more than one segment.
+fn strip_crates(name: &str) -> String {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let mut stripped = String::with_capacity(name.len());
+    let mut rest = name;
+    let mut at_path_start = true;
+    while let Some(c) = rest.chars().next() {
+        if at_path_start && is_ident(c) {
+            let len = rest.find(|c| !is_ident(c)).unwrap_or(rest.len());
+            let (ident, after) = rest.split_at(len);
+            match after.strip_prefix("::") {
+                Some(after) if !after.starts_with('<') => rest = after,
+                _ => {
+                    stripped.push_str(ident);
+                    rest = after;
+                }
+            }
+            at_path_start = false;
+            continue;
+        }
+        stripped.push(c);
+        at_path_start = !is_ident(c) && c != ':';
+        rest = &rest[c.len_utf8()..];
+    }
+    stripped
+}
+
+static NAME_POLICY: Mutex<Option<NamePolicy>> = Mutex::new(None);
+
+/// Sets the policy used to shorten anchor names in every report before it is printed or exported.
+/// Pass `None` to keep full names.
+pub fn set_name_policy(policy: Option<NamePolicy>) {
+    *NAME_POLICY.lock().unwrap_or_else(PoisonError::into_inner) = policy;
+}
+
+/// Returns the installed name policy, if any.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn name_policy() -> Option<NamePolicy> {
+    *NAME_POLICY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+static MONOMORPHIZED_NAMES: AtomicBool = AtomicBool::new(false);
+
+/// Name `profile!()` anchors in generic functions after each concrete instantiation, e.g.
+/// `my_app::parse<my_app::Json>`, instead of sharing one anchor between all of them. Anchors hit
+/// before this is enabled keep their shared name.
+pub fn set_monomorphized_names(enabled: bool) {
+    MONOMORPHIZED_NAMES.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether anchors are named after each instantiation of a generic function.
+#[inline]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn monomorphized_names() -> bool {
+    MONOMORPHIZED_NAMES.load(Ordering::Relaxed)
+}
+
+/// Returns whether `name` is an instantiation of the generic function named `generic`, e.g.
+/// `Parser<u8>::parse` of `Parser<_>::parse`.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn is_instantiation_of(name: &str, generic: &str) -> bool {
+    strip_generics(name) == strip_generics(generic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shorten_names() {
+        let strip = NamePolicy::new().strip_crates(true);
+        assert_eq!(strip.shorten("my_app::net::read"), "net::read");
+        assert_eq!(strip.shorten("read"), "read");
+        assert_eq!(
+            strip.shorten("my_app::parse<alloc::vec::Vec<u8>, core::fmt::Error>"),
+            "parse<vec::Vec<u8>, fmt::Error>"
+        );
+        assert_eq!(
+            strip.shorten("<T as core::fmt::Debug>::fmt"),
+            "<T as fmt::Debug>::fmt"
+        );
+
+        let elide = NamePolicy::new().elide_generics(true);
+        assert_eq!(
+            elide.shorten("run::<fn() -> u8>::{{closure}}"),
+            "run::<…>::{{closure}}"
+        );
+        assert_eq!(elide.shorten("a<b<c>>::d<e>"), "a<…>::d<…>");
+
+        let narrow = NamePolicy::new().max_width(8);
+        assert_eq!(narrow.shorten("abcdefgh"), "abcdefgh");
+        assert_eq!(narrow.shorten("abcdefghij"), "…defghij");
+        assert_eq!(NamePolicy::new().shorten("unchanged"), "unchanged");
+    }
+
+    #[test]
+    fn roll_up_generic_names() {
+        let roll_up = NamePolicy::new().roll_up_generics(true);
+        assert_eq!(
+            roll_up.shorten("my_app::parse<my_app::Json>"),
+            "my_app::parse"
+        );
+        assert_eq!(
+            roll_up.shorten("<my_app::Parser<u8> as my_app::Parse>::parse"),
+            "<my_app::Parser as my_app::Parse>::parse"
+        );
+        assert_eq!(roll_up.shorten("collect::<Vec<fn() -> u8>>"), "collect");
+        assert!(is_instantiation_of(
+            "my_app::Parser<u8>::parse",
+            "my_app::Parser<_>::parse"
+        ));
+        assert!(!is_instantiation_of("my_app::parse<u8>", "my_app::emit"));
+    }
+}
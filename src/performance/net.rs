@@ -0,0 +1,262 @@
+//! Instrumented network sockets.
+//!
+//! [`ProfiledTcpStream`] and [`ProfiledUdpSocket`] wrap their `std::net` counterparts and attribute
+//! the time spent in each send/receive call, along with the number of bytes transferred, to a
+//! profile anchor so network throughput shows up in the profiling report.
+
+use std::{
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket},
+};
+
+/// Result of a socket operation which knows how many bytes it transferred.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+trait Transferred {
+    fn byte_count(&self) -> usize;
+}
+
+impl Transferred for usize {
+    fn byte_count(&self) -> usize {
+        *self
+    }
+}
+
+impl Transferred for (usize, SocketAddr) {
+    fn byte_count(&self) -> usize {
+        self.0
+    }
+}
+
+/// Times a single socket operation under the anchor `name`, crediting the anchor with the number of
+/// bytes transferred if the operation succeeds.
+#[inline]
+fn measure<T: Transferred>(
+    name: &'static str,
+    op: impl FnOnce() -> io::Result<T>,
+) -> io::Result<T> {
+    #[cfg(feature = "perf")]
+    {
+        let block = super::ProfileBlock::new(name, 0);
+        let result = op();
+        if let Ok(transferred) = &result {
+            block.add_byte_count(transferred.byte_count() as u64);
+        }
+        result
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        let _ = name;
+        op()
+    }
+}
+
+/// A [`TcpStream`] which profiles every read and write.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::{io::Write, net::TcpStream};
+/// use util_lib_rs::performance::net::ProfiledTcpStream;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let mut stream = ProfiledTcpStream::new(TcpStream::connect("127.0.0.1:8080")?);
+/// stream.write_all(b"hello")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct ProfiledTcpStream {
+    inner: TcpStream,
+    read_name: &'static str,
+    write_name: &'static str,
+}
+
+impl ProfiledTcpStream {
+    /// Wraps a `TcpStream`, profiling reads under `TcpStream::read` and writes under
+    /// `TcpStream::write`.
+    pub fn new(inner: TcpStream) -> Self {
+        Self::with_names(inner, "TcpStream::read", "TcpStream::write")
+    }
+
+    /// Wraps a `TcpStream`, profiling reads and writes under the given anchor names.
+    pub fn with_names(inner: TcpStream, read_name: &'static str, write_name: &'static str) -> Self {
+        Self {
+            inner,
+            read_name,
+            write_name,
+        }
+    }
+
+    /// Returns a reference to the underlying `TcpStream`.
+    #[must_use]
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the underlying `TcpStream`.
+    #[must_use]
+    pub fn get_mut(&mut self) -> &mut TcpStream {
+        &mut self.inner
+    }
+
+    /// Unwraps this `ProfiledTcpStream`, returning the underlying `TcpStream`.
+    #[must_use]
+    pub fn into_inner(self) -> TcpStream {
+        self.inner
+    }
+}
+
+impl Read for ProfiledTcpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        measure(self.read_name, || self.inner.read(buf))
+    }
+}
+
+impl Write for ProfiledTcpStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        measure(self.write_name, || self.inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A [`UdpSocket`] which profiles every send and receive.
+#[derive(Debug)]
+#[must_use]
+pub struct ProfiledUdpSocket {
+    inner: UdpSocket,
+    recv_name: &'static str,
+    send_name: &'static str,
+}
+
+impl ProfiledUdpSocket {
+    /// Wraps a `UdpSocket`, profiling receives under `UdpSocket::recv` and sends under
+    /// `UdpSocket::send`.
+    pub fn new(inner: UdpSocket) -> Self {
+        Self::with_names(inner, "UdpSocket::recv", "UdpSocket::send")
+    }
+
+    /// Wraps a `UdpSocket`, profiling receives and sends under the given anchor names.
+    pub fn with_names(inner: UdpSocket, recv_name: &'static str, send_name: &'static str) -> Self {
+        Self {
+            inner,
+            recv_name,
+            send_name,
+        }
+    }
+
+    /// Returns a reference to the underlying `UdpSocket`.
+    #[must_use]
+    pub fn get_ref(&self) -> &UdpSocket {
+        &self.inner
+    }
+
+    /// Unwraps this `ProfiledUdpSocket`, returning the underlying `UdpSocket`.
+    #[must_use]
+    pub fn into_inner(self) -> UdpSocket {
+        self.inner
+    }
+
+    /// Receives a single datagram from the connected peer. See [`UdpSocket::recv`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying socket fails to receive.
+    pub fn recv(&self, buf: &mut [u8]) -> io::Result<usize> {
+        measure(self.recv_name, || self.inner.recv(buf))
+    }
+
+    /// Receives a single datagram, returning the number of bytes read and the sender's address.
+    /// See [`UdpSocket::recv_from`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying socket fails to receive.
+    pub fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        measure(self.recv_name, || self.inner.recv_from(buf))
+    }
+
+    /// Sends data to the connected peer. See [`UdpSocket::send`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying socket fails to send.
+    pub fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        measure(self.send_name, || self.inner.send(buf))
+    }
+
+    /// Sends data to the given address. See [`UdpSocket::send_to`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the underlying socket fails to send.
+    pub fn send_to(&self, buf: &[u8], addr: impl ToSocketAddrs) -> io::Result<usize> {
+        measure(self.send_name, || self.inner.send_to(buf, addr))
+    }
+}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+    use crate::performance::GLOBAL_PROFILER;
+    use std::net::TcpListener;
+
+    fn anchor_bytes(name: &str) -> u64 {
+        GLOBAL_PROFILER.with(|profiler| {
+            profiler
+                .borrow()
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map_or(0, |anchor| anchor.byte_count)
+        })
+    }
+
+    #[test]
+    fn tcp_byte_counts() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("valid listener");
+        let addr = listener.local_addr().expect("valid address");
+        let server = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("valid connection");
+            stream.write_all(b"pong").expect("valid write");
+        });
+
+        let mut stream = ProfiledTcpStream::with_names(
+            TcpStream::connect(addr).expect("valid connection"),
+            "tcp_read",
+            "tcp_write",
+        );
+        stream.write_all(b"ping!").expect("valid write");
+        let mut buf = [0; 4];
+        stream.read_exact(&mut buf).expect("valid read");
+        server.join().expect("server thread");
+
+        assert_eq!(&buf, b"pong");
+        assert_eq!(anchor_bytes("tcp_write"), 5);
+        assert_eq!(anchor_bytes("tcp_read"), 4);
+    }
+
+    #[test]
+    fn udp_byte_counts() {
+        let a = ProfiledUdpSocket::with_names(
+            UdpSocket::bind("127.0.0.1:0").expect("valid socket"),
+            "udp_recv",
+            "udp_send",
+        );
+        let b = UdpSocket::bind("127.0.0.1:0").expect("valid socket");
+        a.send_to(b"hello", b.local_addr().expect("valid address"))
+            .expect("valid send");
+
+        let mut buf = [0; 16];
+        let (len, from) = b.recv_from(&mut buf).expect("valid recv");
+        b.send_to(&buf[..len], from).expect("valid send");
+        let (len, _) = a.recv_from(&mut buf).expect("valid recv");
+
+        assert_eq!(len, 5);
+        assert_eq!(anchor_bytes("udp_send"), 5);
+        assert_eq!(anchor_bytes("udp_recv"), 5);
+    }
+}
@@ -0,0 +1,183 @@
+//! Report presentation options.
+//!
+//! Anchors are reported in the order they were first hit. [`ReportOptions`] sorts them by time,
+//! hits or name instead, and can drop all but the top anchors or those below a share of the total
+//! time, so large reports stay readable. Pass them to
+//! [`profile_end_and_print_with`](super::profile_end_and_print_with) or
+//! [`ProfileReport::with_options`](super::ProfileReport::with_options).
+//!
+//! The `UTIL_PROFILE_SORT`, `UTIL_PROFILE_TOP` and `UTIL_PROFILE_MIN_PERCENT` environment variables
+//! override the options of printed reports, so a run can be narrowed down without rebuilding.
+
+use crate::env::{self, VarError};
+use std::{error::Error, fmt, str::FromStr};
+
+/// Environment variable overriding the [`SortOrder`] of printed reports, such as `exclusive`.
+pub const SORT_ENV: &str = "UTIL_PROFILE_SORT";
+/// Environment variable overriding how many anchors printed reports keep.
+pub const TOP_ENV: &str = "UTIL_PROFILE_TOP";
+/// Environment variable overriding the share of the total time below which printed reports drop
+/// anchors.
+pub const MIN_PERCENT_ENV: &str = "UTIL_PROFILE_MIN_PERCENT";
+
+/// The order anchors are listed in.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum SortOrder {
+    /// The order anchors were first hit.
+    #[default]
+    Discovery,
+    /// Most exclusive time first.
+    Exclusive,
+    /// Most inclusive time first.
+    Inclusive,
+    /// Most hits first.
+    Hits,
+    /// Alphabetically by name.
+    Name,
+}
+
+impl FromStr for SortOrder {
+    type Err = ParseSortOrderError;
+
+    /// Parses the lower case name of an order, such as `exclusive`, in any case.
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        [
+            ("discovery", Self::Discovery),
+            ("exclusive", Self::Exclusive),
+            ("inclusive", Self::Inclusive),
+            ("hits", Self::Hits),
+            ("name", Self::Name),
+        ]
+        .into_iter()
+        .find(|(name, _)| input.trim().eq_ignore_ascii_case(name))
+        .map(|(_, sort)| sort)
+        .ok_or(ParseSortOrderError)
+    }
+}
+
+/// An unknown [`SortOrder`] name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseSortOrderError;
+
+impl fmt::Display for ParseSortOrderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("expected discovery, exclusive, inclusive, hits or name")
+    }
+}
+
+impl Error for ParseSortOrderError {}
+
+/// How to sort and cut off the anchors of a report.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{
+///     options::{ReportOptions, SortOrder},
+///     profile_begin, profile_end_and_print_with,
+/// };
+///
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_print_with(
+///     ReportOptions::new()
+///         .sort_by(SortOrder::Exclusive)
+///         .top(20)
+///         .min_percent(0.5),
+/// );
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[must_use]
+pub struct ReportOptions {
+    pub(super) sort: SortOrder,
+    pub(super) top: Option<usize>,
+    pub(super) min_percent: f64,
+}
+
+impl ReportOptions {
+    /// Creates options which keep every anchor in discovery order.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the order anchors are listed in.
+    pub fn sort_by(mut self, sort: SortOrder) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    /// Keeps only the first `count` anchors, after sorting.
+    pub fn top(mut self, count: usize) -> Self {
+        self.top = Some(count);
+        self
+    }
+
+    /// Drops anchors whose inclusive time is under `percent` of the total, so anchors which only
+    /// enclose expensive children are kept.
+    pub fn min_percent(mut self, percent: f64) -> Self {
+        self.min_percent = percent;
+        self
+    }
+
+    /// Overrides the options set in `UTIL_PROFILE_SORT`, `UTIL_PROFILE_TOP` and
+    /// `UTIL_PROFILE_MIN_PERCENT`, keeping the others.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables is set to an invalid value.
+    pub fn with_env_overrides(mut self) -> Result<Self, VarError> {
+        if let Some(sort) = env::var_parsed(SORT_ENV)? {
+            self.sort = sort;
+        }
+        if let Some(top) = env::var_parsed(TOP_ENV)? {
+            self.top = Some(top);
+        }
+        self.min_percent = env::var_or(MIN_PERCENT_ENV, self.min_percent)?;
+        Ok(self)
+    }
+
+    /// Returns the options overridden by the environment, reporting and ignoring invalid values.
+    #[cfg_attr(not(feature = "perf"), allow(dead_code))]
+    pub(super) fn or_env(self) -> Self {
+        self.with_env_overrides().unwrap_or_else(|err| {
+            eprintln!("ignoring report options: {err}");
+            self
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::EnvGuard;
+
+    #[test]
+    fn parses_sort_orders() {
+        assert_eq!("exclusive".parse(), Ok(SortOrder::Exclusive));
+        assert_eq!(" Hits".parse(), Ok(SortOrder::Hits));
+        assert_eq!(
+            "slowest"
+                .parse::<SortOrder>()
+                .expect_err("unknown order")
+                .to_string(),
+            "expected discovery, exclusive, inclusive, hits or name"
+        );
+    }
+
+    #[test]
+    fn env_overrides() {
+        // Reports printed by other tests read these too, so only set values which change nothing.
+        let _env = EnvGuard::new()
+            .set(SORT_ENV, " discovery")
+            .remove(TOP_ENV)
+            .set(MIN_PERCENT_ENV, "0");
+        let options = ReportOptions::new().sort_by(SortOrder::Hits).top(5);
+        let overridden = options.with_env_overrides().expect("valid variables");
+        assert_eq!(overridden, ReportOptions::new().top(5));
+
+        let _env = EnvGuard::new().set(TOP_ENV, "many");
+        let err = options.with_env_overrides().expect_err("invalid top");
+        assert_eq!(err.name(), TOP_ENV);
+        assert_eq!(options.or_env(), options);
+    }
+}
@@ -0,0 +1,293 @@
+//! A minimal regular expression matcher for anchor names.
+//!
+//! Supports literals, `.`, character classes (`[a-z_]`, `[^0-9]`), the escapes `\d`, `\w` and `\s`,
+//! the quantifiers `*`, `+` and `?`, the anchors `^` and `$`, groups and alternation. Matching is
+//! unanchored, like `Regex::is_match` in the `regex` crate.
+
+use std::{error::Error, fmt};
+
+/// An invalid anchor name pattern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternError {
+    pattern: String,
+    message: &'static str,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid pattern `{}`: {}", self.pattern, self.message)
+    }
+}
+
+impl Error for PatternError {}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Char(char),
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+    Start,
+    End,
+    Group(Vec<Vec<Node>>),
+    Repeat {
+        node: Box<Node>,
+        min: usize,
+        max: Option<usize>,
+    },
+}
+
+impl Node {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Char(expected) => c == *expected,
+            Self::Any => true,
+            Self::Class { ranges, negated } => {
+                ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c)) != *negated
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A compiled pattern.
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    alternatives: Vec<Vec<Node>>,
+}
+
+impl Pattern {
+    /// Compiles `pattern`.
+    pub(crate) fn new(pattern: &str) -> Result<Self, PatternError> {
+        let error = |message| PatternError {
+            pattern: pattern.to_string(),
+            message,
+        };
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let alternatives = parser.alternatives().map_err(error)?;
+        if parser.pos < parser.chars.len() {
+            return Err(error("unmatched `)`"));
+        }
+        Ok(Self { alternatives })
+    }
+
+    /// Returns whether the pattern matches anywhere in `text`.
+    pub(crate) fn is_match(&self, text: &str) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        let group = [Node::Group(self.alternatives.clone())];
+        (0..=chars.len()).any(|start| match_seq(&group, &chars, start, &mut |_| true))
+    }
+}
+
+/// Matches `nodes` against `input` at `pos`, calling `next` with the end position of each candidate
+/// match until it accepts one.
+fn match_seq(
+    nodes: &[Node],
+    input: &[char],
+    pos: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    let Some((node, rest)) = nodes.split_first() else {
+        return next(pos);
+    };
+    match node {
+        Node::Start => pos == 0 && match_seq(rest, input, pos, next),
+        Node::End => pos == input.len() && match_seq(rest, input, pos, next),
+        Node::Group(alternatives) => alternatives.iter().any(|alternative| {
+            match_seq(alternative, input, pos, &mut |end| {
+                match_seq(rest, input, end, next)
+            })
+        }),
+        Node::Repeat { node, min, max } => {
+            match_repeat(node, *min, *max, 0, rest, input, pos, next)
+        }
+        _ => pos < input.len() && node.matches(input[pos]) && match_seq(rest, input, pos + 1, next),
+    }
+}
+
+/// Greedily matches `node` repeated between `min` and `max` times, having matched it `count` times
+/// so far, followed by `rest`.
+#[allow(clippy::too_many_arguments)]
+fn match_repeat(
+    node: &Node,
+    min: usize,
+    max: Option<usize>,
+    count: usize,
+    rest: &[Node],
+    input: &[char],
+    pos: usize,
+    next: &mut dyn FnMut(usize) -> bool,
+) -> bool {
+    if max.is_none_or(|max| count < max) {
+        let once = std::slice::from_ref(node);
+        let matched = match_seq(once, input, pos, &mut |end| {
+            // An empty repetition can't make progress, so stop repeating.
+            (end != pos || count < min)
+                && match_repeat(node, min, max, count + 1, rest, input, end, next)
+        });
+        if matched {
+            return true;
+        }
+    }
+    count >= min && match_seq(rest, input, pos, next)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.pos += 1;
+        c
+    }
+
+    fn alternatives(&mut self) -> Result<Vec<Vec<Node>>, &'static str> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            alternatives.push(self.sequence()?);
+        }
+        Ok(alternatives)
+    }
+
+    fn sequence(&mut self) -> Result<Vec<Node>, &'static str> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            let node = match c {
+                '|' | ')' => break,
+                '*' | '+' | '?' => {
+                    self.pos += 1;
+                    let node = nodes.pop().ok_or("quantifier without a preceding item")?;
+                    if matches!(node, Node::Start | Node::End | Node::Repeat { .. }) {
+                        return Err("quantifier without a preceding item");
+                    }
+                    let (min, max) = match c {
+                        '*' => (0, None),
+                        '+' => (1, None),
+                        _ => (0, Some(1)),
+                    };
+                    Node::Repeat {
+                        node: Box::new(node),
+                        min,
+                        max,
+                    }
+                }
+                _ => self.atom()?,
+            };
+            nodes.push(node);
+        }
+        Ok(nodes)
+    }
+
+    fn atom(&mut self) -> Result<Node, &'static str> {
+        Ok(match self.bump().ok_or("unexpected end of pattern")? {
+            '.' => Node::Any,
+            '^' => Node::Start,
+            '$' => Node::End,
+            '(' => {
+                let alternatives = self.alternatives()?;
+                if self.bump() != Some(')') {
+                    return Err("unmatched `(`");
+                }
+                Node::Group(alternatives)
+            }
+            '[' => self.class()?,
+            '\\' => self.escape()?,
+            c => Node::Char(c),
+        })
+    }
+
+    fn escape(&mut self) -> Result<Node, &'static str> {
+        let ranges = match self.bump().ok_or("trailing `\\`")? {
+            'd' => vec![('0', '9')],
+            'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+            's' => vec![(' ', ' '), ('\t', '\r')],
+            c if c.is_alphanumeric() => return Err("unsupported escape"),
+            c => return Ok(Node::Char(c)),
+        };
+        Ok(Node::Class {
+            ranges,
+            negated: false,
+        })
+    }
+
+    fn class(&mut self) -> Result<Node, &'static str> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+        let mut ranges = Vec::new();
+        loop {
+            let c = match self.bump().ok_or("unmatched `[`")? {
+                ']' if !ranges.is_empty() => break,
+                '\\' => match self.escape()? {
+                    Node::Char(c) => c,
+                    Node::Class {
+                        ranges: escaped, ..
+                    } => {
+                        ranges.extend(escaped);
+                        continue;
+                    }
+                    _ => unreachable!("escapes are characters or classes"),
+                },
+                c => c,
+            };
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&c| c != ']') {
+                self.pos += 1;
+                let hi = self.bump().ok_or("unmatched `[`")?;
+                if hi < c {
+                    return Err("invalid class range");
+                }
+                ranges.push((c, hi));
+            } else {
+                ranges.push((c, c));
+            }
+        }
+        Ok(Node::Class { ranges, negated })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Pattern::new(pattern).expect("valid pattern").is_match(text)
+    }
+
+    #[test]
+    fn pattern_matching() {
+        assert!(is_match("parse", "json::parse_value"));
+        assert!(is_match("^json::", "json::parse_value"));
+        assert!(!is_match("^parse", "json::parse_value"));
+        assert!(is_match("value$", "json::parse_value"));
+        assert!(is_match("^(net|io)::\\w+$", "io::read_all"));
+        assert!(!is_match("^(net|io)::\\w+$", "io::read::all"));
+        assert!(is_match("a.c", "abc"));
+        assert!(is_match("^ab*c$", "ac"));
+        assert!(is_match("^ab+c$", "abbbc"));
+        assert!(!is_match("^ab+c$", "ac"));
+        assert!(is_match("^colou?r$", "color"));
+        assert!(is_match("^[a-c_]+\\d$", "ab_c7"));
+        assert!(!is_match("^[^0-9]+$", "abc1"));
+        assert!(is_match("<.*>", "Vec<u8>::push"));
+        assert!(is_match("^(a*)*$", "aaaa"));
+        assert!(is_match("", "anything"));
+
+        for invalid in ["(", "a)", "[a", "*a", "a**", "\\", "[z-a]", "\\q"] {
+            assert!(Pattern::new(invalid).is_err(), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,697 @@
+//! SVG charts for benchmark results.
+//!
+//! A [`Chart`] renders one or more series of points as a standalone SVG line chart, e.g. buffer
+//! size against throughput from a bandwidth sweep, or elapsed time per repetition. The output has
+//! no external dependencies, so it can be committed or embedded in documentation directly.
+
+use super::{ProfileReport, ReportExporter};
+use std::{
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const WIDTH: f64 = 720.0;
+const HEIGHT: f64 = 440.0;
+const MARGIN_LEFT: f64 = 72.0;
+const MARGIN_RIGHT: f64 = 160.0;
+const MARGIN_TOP: f64 = 40.0;
+const MARGIN_BOTTOM: f64 = 56.0;
+const PLOT_WIDTH: f64 = WIDTH - MARGIN_LEFT - MARGIN_RIGHT;
+const PLOT_HEIGHT: f64 = HEIGHT - MARGIN_TOP - MARGIN_BOTTOM;
+const COLORS: [&str; 6] = [
+    "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd", "#8c564b",
+];
+
+/// A named series of `(x, y)` points.
+#[derive(Debug, Clone, PartialEq)]
+struct Series {
+    name: String,
+    points: Vec<(f64, f64)>,
+}
+
+/// A line chart rendered to SVG.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::plot::Chart;
+///
+/// let svg = Chart::new("Read bandwidth")
+///     .x_label("buffer size (bytes)")
+///     .y_label("GB/s")
+///     .log_x(true)
+///     .series("read", &[(1024.0, 12.5), (65536.0, 10.1), (16777216.0, 4.2)])
+///     .render();
+/// assert!(svg.starts_with("<svg"));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+#[must_use]
+pub struct Chart {
+    title: String,
+    x_label: String,
+    y_label: String,
+    log_x: bool,
+    series: Vec<Series>,
+}
+
+impl Chart {
+    /// Creates an empty chart with the given title.
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Sets the label of the horizontal axis.
+    pub fn x_label(mut self, label: impl Into<String>) -> Self {
+        self.x_label = label.into();
+        self
+    }
+
+    /// Sets the label of the vertical axis.
+    pub fn y_label(mut self, label: impl Into<String>) -> Self {
+        self.y_label = label.into();
+        self
+    }
+
+    /// Use a logarithmic horizontal axis, e.g. for sizes growing in powers of two. Points with
+    /// non-positive `x` values are skipped.
+    pub fn log_x(mut self, log_x: bool) -> Self {
+        self.log_x = log_x;
+        self
+    }
+
+    /// Adds a series of `(x, y)` points, drawn in the order given.
+    pub fn series(mut self, name: impl Into<String>, points: &[(f64, f64)]) -> Self {
+        self.series.push(Series {
+            name: name.into(),
+            points: points.to_vec(),
+        });
+        self
+    }
+
+    /// Renders the chart as an SVG document.
+    #[must_use]
+    pub fn render(&self) -> String {
+        let frame = Frame::fit(self);
+        let mut svg = String::new();
+        let _ = writeln!(
+            svg,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}" font-family="sans-serif" font-size="12">"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<rect width="{WIDTH}" height="{HEIGHT}" fill="white"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="24" text-anchor="middle" font-size="16">{}</text>"#,
+            MARGIN_LEFT + PLOT_WIDTH / 2.0,
+            escape(&self.title)
+        );
+        frame.write_grid(&mut svg);
+        self.write_axis_labels(&mut svg);
+        for (i, series) in self.series.iter().enumerate() {
+            frame.write_series(&mut svg, i, series);
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn write_axis_labels(&self, svg: &mut String) {
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{MARGIN_LEFT}" y="{MARGIN_TOP}" width="{PLOT_WIDTH}" height="{PLOT_HEIGHT}" fill="none" stroke="black"/>"#
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{}" text-anchor="middle">{}</text>"#,
+            MARGIN_LEFT + PLOT_WIDTH / 2.0,
+            HEIGHT - 12.0,
+            escape(&self.x_label)
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="16" y="{0}" text-anchor="middle" transform="rotate(-90 16 {0})">{1}</text>"#,
+            MARGIN_TOP + PLOT_HEIGHT / 2.0,
+            escape(&self.y_label)
+        );
+    }
+
+    /// Renders the chart as text using braille characters, `width` columns wide and `height` rows
+    /// high, for viewing trends in a terminal. Each character holds 2x4 dots, and series are
+    /// overlaid without distinguishing them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::performance::plot::Chart;
+    ///
+    /// let frame_times = [(0.0, 16.6), (1.0, 16.8), (2.0, 33.1), (3.0, 16.7)];
+    /// println!("{}", Chart::new("Frame time").series("ms", &frame_times).render_braille(40, 8));
+    /// ```
+    #[must_use]
+    pub fn render_braille(&self, width: usize, height: usize) -> String {
+        let (width, height) = (width.max(1), height.max(1));
+        let (dots_x, dots_y) = (width * 2, height * 4);
+        let x_value = |x: f64| if self.log_x { x.log10() } else { x };
+        let points = || {
+            self.series
+                .iter()
+                .flat_map(|series| series.points.iter().copied())
+                .filter(|&point| is_drawable(point, self.log_x))
+        };
+        let (x_min, x_max) = bounds(points().map(|(x, _)| x_value(x)));
+        let (y_min, y_max) = bounds(points().map(|(_, y)| y));
+        #[allow(
+            clippy::cast_possible_truncation,
+            clippy::cast_precision_loss,
+            clippy::cast_sign_loss
+        )]
+        let to_dot = |value: f64, min: f64, max: f64, dots: usize| -> usize {
+            if max <= min {
+                return 0;
+            }
+            let scaled = (value - min) / (max - min) * (dots - 1) as f64;
+            (scaled.round() as usize).min(dots - 1)
+        };
+
+        let mut cells = vec![0u8; width * height];
+        let mut set = |x: usize, y: usize| {
+            // Row 0 is the top of the chart.
+            let y = dots_y - 1 - y;
+            cells[(y / 4) * width + x / 2] |= BRAILLE_DOTS[y % 4][x % 2];
+        };
+        for series in &self.series {
+            let mut prev = None;
+            for (x, y) in series
+                .points
+                .iter()
+                .copied()
+                .filter(|&point| is_drawable(point, self.log_x))
+            {
+                let dot = (
+                    to_dot(x_value(x), x_min, x_max, dots_x),
+                    to_dot(y, y_min, y_max, dots_y),
+                );
+                let (x0, y0) = prev.unwrap_or(dot);
+                for (x, y) in line(x0, y0, dot.0, dot.1) {
+                    set(x, y);
+                }
+                prev = Some(dot);
+            }
+        }
+
+        let labels = [format_value(y_max), format_value(y_min)];
+        let label_width = labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0);
+        let mut text = String::new();
+        if !self.title.is_empty() {
+            let _ = writeln!(text, "{}", self.title);
+        }
+        for (row, cells) in cells.chunks(width).enumerate() {
+            let label = match row {
+                0 => labels[0].as_str(),
+                row if row == height - 1 => labels[1].as_str(),
+                _ => "",
+            };
+            let _ = write!(text, "{label:>label_width$} ┤");
+            text.extend(
+                cells
+                    .iter()
+                    .map(|&dots| char::from_u32(0x2800 + u32::from(dots)).unwrap_or(' ')),
+            );
+            text.push('\n');
+        }
+        let (x_first, x_last) = if self.log_x {
+            (10f64.powf(x_min), 10f64.powf(x_max))
+        } else {
+            (x_min, x_max)
+        };
+        let first = format_value(x_first);
+        let last = format_value(x_last);
+        let gap = (width + 1).saturating_sub(first.chars().count() + last.chars().count());
+        let _ = writeln!(text, "{:label_width$} {first}{:gap$}{last}", "", "");
+        text
+    }
+
+    /// Renders the chart and saves it to the file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.render())
+    }
+
+    /// Writes the chart's data to `<base>.dat` and a gnuplot script plotting it to `<base>.gp`,
+    /// which can be run with `gnuplot -p <base>.gp` from the same directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if either file can't be written.
+    pub fn save_gnuplot(&self, base: impl AsRef<Path>) -> io::Result<()> {
+        let base = base.as_ref();
+        let data_path = base.with_extension("dat");
+        let data_name = data_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        fs::write(&data_path, self.gnuplot_data())?;
+        fs::write(base.with_extension("gp"), self.gnuplot_script(&data_name))
+    }
+
+    /// Returns the points of every series as gnuplot data blocks, one `index` per series.
+    fn gnuplot_data(&self) -> String {
+        let mut data = String::new();
+        for (i, series) in self.series.iter().enumerate() {
+            if i > 0 {
+                data.push_str("\n\n");
+            }
+            let _ = writeln!(data, "# {}", series.name.replace('\n', " "));
+            for &(x, y) in &series.points {
+                let _ = writeln!(data, "{x} {y}");
+            }
+        }
+        data
+    }
+
+    /// Returns a gnuplot script plotting the data file `data_name`.
+    fn gnuplot_script(&self, data_name: &str) -> String {
+        let mut script = String::new();
+        let _ = writeln!(
+            script,
+            "# Run with: gnuplot -p {}",
+            Path::new(data_name).with_extension("gp").display()
+        );
+        let _ = writeln!(script, "set title {}", quote(&self.title));
+        let _ = writeln!(script, "set xlabel {}", quote(&self.x_label));
+        let _ = writeln!(script, "set ylabel {}", quote(&self.y_label));
+        if self.log_x {
+            let _ = writeln!(script, "set logscale x");
+        }
+        let _ = writeln!(script, "set yrange [0:*]");
+        let _ = writeln!(script, "set grid");
+        let _ = writeln!(script, "set key outside right");
+        let _ = writeln!(
+            script,
+            "# set terminal svg size {WIDTH},{HEIGHT}; set output 'chart.svg'"
+        );
+        let plots: Vec<String> = self
+            .series
+            .iter()
+            .enumerate()
+            .map(|(i, series)| {
+                let file = if i == 0 {
+                    quote(data_name)
+                } else {
+                    "''".to_string()
+                };
+                format!(
+                    "{file} index {i} using 1:2 with linespoints title {}",
+                    quote(&series.name)
+                )
+            })
+            .collect();
+        if !plots.is_empty() {
+            let _ = writeln!(script, "plot {}", plots.join(", \\\n     "));
+        }
+        script
+    }
+}
+
+/// Dot bits of a braille character, indexed by dot row and then column.
+const BRAILLE_DOTS: [[u8; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// Returns the dots on the line from `(x0, y0)` to `(x1, y1)`, inclusive.
+fn line(x0: usize, y0: usize, x1: usize, y1: usize) -> Vec<(usize, usize)> {
+    let steps = x0.abs_diff(x1).max(y0.abs_diff(y1));
+    if steps == 0 {
+        return vec![(x0, y0)];
+    }
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    (0..=steps)
+        .map(|step| {
+            let t = step as f64 / steps as f64;
+            let lerp = |a: usize, b: usize| (a as f64 + (b as f64 - a as f64) * t).round() as usize;
+            (lerp(x0, x1), lerp(y0, y1))
+        })
+        .collect()
+}
+
+/// Quotes `text` as a gnuplot string.
+fn quote(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', " ");
+    format!("\"{escaped}\"")
+}
+
+/// Exports the elapsed time of every finished report as a frame-time series, rewriting a gnuplot
+/// data file and script after each one.
+#[derive(Debug)]
+#[must_use]
+pub struct GnuplotExporter {
+    base: PathBuf,
+    frame_times: Vec<(f64, f64)>,
+}
+
+impl GnuplotExporter {
+    /// Creates an exporter writing `<base>.dat` and `<base>.gp`.
+    pub fn new(base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            frame_times: Vec::new(),
+        }
+    }
+}
+
+impl ReportExporter for GnuplotExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        #[allow(clippy::cast_precision_loss)]
+        let frame = self.frame_times.len() as f64;
+        self.frame_times.push((frame, report.elapsed_ms()));
+        Chart::new("Frame time")
+            .x_label("frame")
+            .y_label("ms")
+            .series("total", &self.frame_times)
+            .save_gnuplot(&self.base)
+    }
+}
+
+/// The value ranges of a chart's axes, mapping values to SVG coordinates.
+struct Frame {
+    log_x: bool,
+    x_min: f64,
+    x_max: f64,
+    y_min: f64,
+    y_max: f64,
+    y_ticks: Vec<f64>,
+}
+
+impl Frame {
+    /// Fits the axes to every drawable point of `chart`.
+    fn fit(chart: &Chart) -> Self {
+        let points = || {
+            chart
+                .series
+                .iter()
+                .flat_map(|series| series.points.iter().copied())
+                .filter(|&point| is_drawable(point, chart.log_x))
+        };
+        let x_value = |x: f64| if chart.log_x { x.log10() } else { x };
+        let (mut x_min, mut x_max) = bounds(points().map(|(x, _)| x_value(x)));
+        if chart.log_x {
+            (x_min, x_max) = (x_min.floor(), x_max.ceil());
+        }
+        if x_max <= x_min {
+            x_max = x_min + 1.0;
+        }
+
+        // Throughput and time axes start at zero so differences aren't exaggerated.
+        let (y_min, y_max) = bounds(points().map(|(_, y)| y));
+        let y_ticks = nice_ticks(y_min.min(0.0), y_max, 5);
+        let y_min = y_ticks.first().map_or(0.0, |&first| first.min(y_min));
+        let mut y_max = y_ticks.last().map_or(y_max, |&last| last.max(y_max));
+        if y_max <= y_min {
+            y_max = y_min + 1.0;
+        }
+        Self {
+            log_x: chart.log_x,
+            x_min,
+            x_max,
+            y_min,
+            y_max,
+            y_ticks,
+        }
+    }
+
+    fn map_x(&self, x: f64) -> f64 {
+        let x = if self.log_x { x.log10() } else { x };
+        MARGIN_LEFT + (x - self.x_min) / (self.x_max - self.x_min) * PLOT_WIDTH
+    }
+
+    fn map_y(&self, y: f64) -> f64 {
+        MARGIN_TOP + (self.y_max - y) / (self.y_max - self.y_min) * PLOT_HEIGHT
+    }
+
+    /// Writes grid lines and tick labels for both axes.
+    fn write_grid(&self, svg: &mut String) {
+        let x_ticks = if self.log_x {
+            integer_ticks(self.x_min, self.x_max)
+                .into_iter()
+                .map(|exponent| 10f64.powf(exponent))
+                .collect()
+        } else {
+            nice_ticks(self.x_min, self.x_max, 6)
+        };
+        let bottom = MARGIN_TOP + PLOT_HEIGHT;
+        for x in x_ticks {
+            let px = self.map_x(x);
+            if !(MARGIN_LEFT - 0.5..=MARGIN_LEFT + PLOT_WIDTH + 0.5).contains(&px) {
+                continue;
+            }
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{px:.1}\" y1=\"{MARGIN_TOP}\" x2=\"{px:.1}\" y2=\"{bottom}\" stroke=\"#e0e0e0\"/>"
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{px:.1}" y="{}" text-anchor="middle">{}</text>"#,
+                bottom + 16.0,
+                format_value(x)
+            );
+        }
+        for &y in &self.y_ticks {
+            let py = self.map_y(y);
+            let _ = writeln!(
+                svg,
+                "<line x1=\"{MARGIN_LEFT}\" y1=\"{py:.1}\" x2=\"{}\" y2=\"{py:.1}\" stroke=\"#e0e0e0\"/>",
+                MARGIN_LEFT + PLOT_WIDTH
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{:.1}" text-anchor="end">{}</text>"#,
+                MARGIN_LEFT - 6.0,
+                py + 4.0,
+                format_value(y)
+            );
+        }
+    }
+
+    /// Writes the line, point markers and legend entry of the `index`th series.
+    fn write_series(&self, svg: &mut String, index: usize, series: &Series) {
+        let color = COLORS[index % COLORS.len()];
+        let coordinates: Vec<(f64, f64)> = series
+            .points
+            .iter()
+            .filter(|&&point| is_drawable(point, self.log_x))
+            .map(|&(x, y)| (self.map_x(x), self.map_y(y)))
+            .collect();
+        let points: Vec<String> = coordinates
+            .iter()
+            .map(|(x, y)| format!("{x:.1},{y:.1}"))
+            .collect();
+        let _ = writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{color}" stroke-width="2"/>"#,
+            points.join(" ")
+        );
+        for (x, y) in coordinates {
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{x:.1}" cy="{y:.1}" r="3" fill="{color}"/>"#
+            );
+        }
+
+        #[allow(clippy::cast_precision_loss)]
+        let legend_y = MARGIN_TOP + 12.0 + 20.0 * index as f64;
+        let legend_x = MARGIN_LEFT + PLOT_WIDTH + 16.0;
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{legend_x}" y="{}" width="12" height="12" fill="{color}"/>"#,
+            legend_y - 10.0
+        );
+        let _ = writeln!(
+            svg,
+            r#"<text x="{}" y="{legend_y}">{}</text>"#,
+            legend_x + 18.0,
+            escape(&series.name)
+        );
+    }
+}
+
+/// Returns whether `(x, y)` can be drawn, which excludes non-positive `x` on a logarithmic axis.
+fn is_drawable((x, y): (f64, f64), log_x: bool) -> bool {
+    x.is_finite() && y.is_finite() && (!log_x || x > 0.0)
+}
+
+/// Returns the minimum and maximum of `values`, or `(0.0, 1.0)` if there are none.
+fn bounds(values: impl Iterator<Item = f64>) -> (f64, f64) {
+    values
+        .fold(None, |bounds: Option<(f64, f64)>, value| {
+            Some(bounds.map_or((value, value), |(min, max)| {
+                (min.min(value), max.max(value))
+            }))
+        })
+        .unwrap_or((0.0, 1.0))
+}
+
+/// Returns about `count` evenly spaced round values covering `min..=max`, stepping by 1, 2 or 5
+/// times a power of ten.
+fn nice_ticks(min: f64, max: f64, count: u32) -> Vec<f64> {
+    if max <= min || !min.is_finite() || !max.is_finite() {
+        return vec![min];
+    }
+    let rough = (max - min) / f64::from(count.max(1));
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|factor| factor * magnitude)
+        .find(|&step| step >= rough)
+        .unwrap_or(10.0 * magnitude);
+    let first = (min / step).floor();
+    let last = (max / step).ceil();
+    #[allow(clippy::cast_possible_truncation)]
+    (first as i64..=last as i64)
+        .map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let tick = i as f64 * step;
+            tick
+        })
+        .collect()
+}
+
+/// Returns every integer in `min..=max`.
+fn integer_ticks(min: f64, max: f64) -> Vec<f64> {
+    #[allow(clippy::cast_possible_truncation)]
+    (min.floor() as i64..=max.ceil() as i64)
+        .map(|i| {
+            #[allow(clippy::cast_precision_loss)]
+            let tick = i as f64;
+            tick
+        })
+        .collect()
+}
+
+/// Formats an axis value compactly, with SI suffixes for large magnitudes.
+fn format_value(value: f64) -> String {
+    let (scaled, suffix) = match value.abs() {
+        v if v >= 1e12 => (value / 1e12, "T"),
+        v if v >= 1e9 => (value / 1e9, "G"),
+        v if v >= 1e6 => (value / 1e6, "M"),
+        v if v >= 1e3 => (value / 1e3, "k"),
+        _ => (value, ""),
+    };
+    let formatted = format!("{scaled:.3}");
+    let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+    let formatted = if formatted == "-0" { "0" } else { formatted };
+    format!("{formatted}{suffix}")
+}
+
+/// Escapes text for use in SVG or HTML content.
+pub(super) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_charts() {
+        assert_eq!(nice_ticks(0.0, 10.0, 5), [0.0, 2.0, 4.0, 6.0, 8.0, 10.0]);
+        assert_eq!(nice_ticks(0.0, 700.0, 5), [0.0, 200.0, 400.0, 600.0, 800.0]);
+        assert_eq!(format_value(1_500_000.0), "1.5M");
+        assert_eq!(format_value(0.25), "0.25");
+        assert_eq!(format_value(-0.0), "0");
+
+        let svg = Chart::new("Size <vs> throughput")
+            .x_label("bytes")
+            .y_label("GB/s")
+            .log_x(true)
+            .series("read", &[(1024.0, 12.0), (1_048_576.0, 6.0), (0.0, 1.0)])
+            .series("write", &[(1024.0, 8.0), (1_048_576.0, 4.0)])
+            .render();
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.ends_with("</svg>\n"));
+        assert!(svg.contains("Size &lt;vs&gt; throughput"));
+        assert_eq!(svg.matches("<polyline").count(), 2);
+        // The point at zero can't be drawn on a logarithmic axis.
+        assert_eq!(svg.matches("<circle").count(), 4);
+        assert!(svg.contains(">1k</text>") && svg.contains(">1M</text>"));
+
+        let empty = Chart::new("empty").render();
+        assert!(empty.contains("empty"));
+    }
+
+    #[test]
+    fn braille_plots() {
+        let text = Chart::new("ramp")
+            .series("up", &[(0.0, 0.0), (3.0, 3.0)])
+            .render_braille(2, 1);
+        assert_eq!(text, "ramp\n3 ┤⡠⠊\n  0 3\n");
+
+        let flat = Chart::new("")
+            .series("flat", &[(0.0, 5.0), (1.0, 5.0)])
+            .render_braille(3, 2);
+        assert_eq!(flat.lines().count(), 3);
+        assert!(flat.ends_with("5 ┤⣀⣀⣀\n  0  1\n"));
+    }
+
+    #[test]
+    fn gnuplot_files() {
+        let chart = Chart::new("Say \"hi\"")
+            .log_x(true)
+            .series("read", &[(1.0, 2.0), (4.0, 8.5)])
+            .series("write", &[(1.0, 1.0)]);
+        assert_eq!(
+            chart.gnuplot_data(),
+            "# read\n1 2\n4 8.5\n\n\n# write\n1 1\n"
+        );
+        let script = chart.gnuplot_script("bench.dat");
+        assert!(script.contains("set title \"Say \\\"hi\\\"\"\n"));
+        assert!(script.starts_with("# Run with: gnuplot -p bench.gp\n"));
+        assert!(script.contains("set logscale x\n"));
+        assert!(script.contains(
+            "plot \"bench.dat\" index 0 using 1:2 with linespoints title \"read\", \\\n     \
+             '' index 1 using 1:2 with linespoints title \"write\"\n"
+        ));
+
+        let base = std::env::temp_dir().join(format!("util_lib_rs-gnuplot-{}", std::process::id()));
+        let mut exporter = GnuplotExporter::new(&base);
+        for elapsed_tsc in [10, 20] {
+            let report = ProfileReport {
+                elapsed_tsc,
+                timer_freq: 1000,
+                ..ProfileReport::default()
+            };
+            exporter.export(&report).expect("valid export");
+        }
+        let data = fs::read_to_string(base.with_extension("dat")).expect("data file");
+        assert_eq!(data, "# total\n0 10\n1 20\n");
+        assert!(base.with_extension("gp").exists());
+        let _ = fs::remove_file(base.with_extension("dat"));
+        let _ = fs::remove_file(base.with_extension("gp"));
+    }
+}
@@ -0,0 +1,140 @@
+//! Anchor renaming.
+//!
+//! [`AnchorRenames`] maps anchor names to friendly labels when a report is produced, e.g. to
+//! collapse verbose monomorphized type names, so exported dashboards stay stable when internal
+//! function names change. Install one with [`set_anchor_renames`] and it is applied to every report
+//! before it is filtered, printed or exported.
+
+use super::{filter::PatternError, pattern::Pattern};
+use std::sync::{Mutex, PoisonError};
+
+#[derive(Debug, Clone)]
+enum Rule {
+    Exact(String),
+    Matching(Pattern),
+}
+
+/// An ordered list of anchor rename rules. The first rule matching a name decides its label, and
+/// names matching no rule are kept as they are.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::rename::AnchorRenames;
+///
+/// # fn main() -> Result<(), util_lib_rs::performance::filter::PatternError> {
+/// let renames = AnchorRenames::new()
+///     .exact("app::load_config", "startup")
+///     .matching("^Vec<.*>::push$", "Vec::push")?;
+/// assert_eq!(renames.rename("Vec<alloc::string::String>::push"), "Vec::push");
+/// assert_eq!(renames.rename("app::load_config"), "startup");
+/// assert_eq!(renames.rename("app::run"), "app::run");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Default, Clone)]
+#[must_use]
+pub struct AnchorRenames {
+    rules: Vec<(Rule, &'static str)>,
+}
+
+impl AnchorRenames {
+    /// Creates an empty set of renames.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Renames the anchor named exactly `name` to `label`.
+    pub fn exact(mut self, name: impl Into<String>, label: &'static str) -> Self {
+        self.rules.push((Rule::Exact(name.into()), label));
+        self
+    }
+
+    /// Renames every anchor whose name matches `pattern` to `label`. See [`super::filter`] for the
+    /// supported pattern syntax.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pattern` is invalid.
+    pub fn matching(mut self, pattern: &str, label: &'static str) -> Result<Self, PatternError> {
+        self.rules
+            .push((Rule::Matching(Pattern::new(pattern)?), label));
+        Ok(self)
+    }
+
+    /// Returns the label for the anchor `name`.
+    #[must_use]
+    pub fn rename(&self, name: &'static str) -> &'static str {
+        self.rules
+            .iter()
+            .find(|(rule, _)| match rule {
+                Rule::Exact(exact) => exact == name,
+                Rule::Matching(pattern) => pattern.is_match(name),
+            })
+            .map_or(name, |&(_, label)| label)
+    }
+}
+
+static ANCHOR_RENAMES: Mutex<Option<AnchorRenames>> = Mutex::new(None);
+
+/// Sets the renames applied to every report before it is filtered, printed or exported. Pass `None`
+/// to keep anchor names as they are.
+pub fn set_anchor_renames(renames: Option<AnchorRenames>) {
+    *ANCHOR_RENAMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner) = renames;
+}
+
+/// Returns the installed renames, if any.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn anchor_renames() -> Option<AnchorRenames> {
+    ANCHOR_RENAMES
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::{AnchorStats, LoopStats, ProfileReport};
+
+    #[test]
+    fn rename_reports() {
+        let anchor = |name, hit_count| AnchorStats {
+            name,
+            hit_count,
+            tsc_elapsed_exclusive: 10 * hit_count,
+            tsc_elapsed_inclusive: 10 * hit_count,
+            ..AnchorStats::default()
+        };
+        let report = ProfileReport {
+            anchors: vec![
+                anchor("parse::<u8>", 1),
+                anchor("emit", 2),
+                anchor("parse::<u16>", 3),
+            ],
+            never_hit: vec!["parse::<u32>", "flush"],
+            loops: vec![LoopStats {
+                name: "tokens",
+                parent: Some("parse::<u8>"),
+                ..LoopStats::default()
+            }],
+            ..ProfileReport::default()
+        };
+
+        let renames = AnchorRenames::new()
+            .exact("flush", "io::flush")
+            .matching("^parse::<", "parse")
+            .expect("valid pattern");
+        let report = report.renamed(&renames);
+
+        assert_eq!(report.anchors.len(), 2);
+        assert_eq!(report.anchors[0].name, "parse");
+        assert_eq!(report.anchors[0].hit_count, 4);
+        assert_eq!(report.anchors[0].tsc_elapsed_exclusive, 40);
+        assert_eq!(report.anchors[1].name, "emit");
+        assert_eq!(report.never_hit, ["io::flush"]);
+        assert_eq!(report.loops[0].parent, Some("parse"));
+    }
+}
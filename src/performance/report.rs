@@ -0,0 +1,2319 @@
+//! Structured profiling results.
+
+use super::{
+    color,
+    counters::HardwareCounters,
+    filter::AnchorFilter,
+    histogram::{LatencyHistogram, REPORTED_PERCENTILES},
+    names::NamePolicy,
+    options::{ReportOptions, SortOrder},
+    rename::AnchorRenames,
+    sampling::UNINSTRUMENTED,
+};
+use crate::{
+    fmt::{format_bytes, format_duration, format_rate},
+    sys::{CpuInfo, MemoryUsage},
+    term::{Align, Table},
+};
+use std::{cell::Cell, collections::HashMap, fmt, iter::Sum, ops::AddAssign, time::Duration};
+
+/// Timing statistics accumulated for a single profile anchor.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct AnchorStats {
+    /// Name of the profiled function or block.
+    pub name: &'static str,
+    /// Number of times the block was entered.
+    pub hit_count: u64,
+    /// Total number of bytes processed by the block.
+    pub byte_count: u64,
+    /// Total number of items processed by the block.
+    pub item_count: u64,
+    /// Elapsed timestamp counter excluding time spent in child blocks.
+    pub tsc_elapsed_exclusive: u64,
+    /// Elapsed timestamp counter including time spent in child blocks.
+    pub tsc_elapsed_inclusive: u64,
+    /// Thread CPU time in nanoseconds excluding child blocks, or `0` unless measured with
+    /// [`set_measure_cpu_time`](super::set_measure_cpu_time).
+    pub cpu_ns_exclusive: u64,
+    /// Thread CPU time in nanoseconds including child blocks, or `0` unless measured.
+    pub cpu_ns_inclusive: u64,
+    /// Sum of the squared elapsed timestamp counter of each hit, including child blocks, for the
+    /// variance between hits.
+    pub tsc_elapsed_squares: u128,
+    /// Shortest elapsed timestamp counter of a single hit, including child blocks.
+    pub tsc_min: u64,
+    /// Longest elapsed timestamp counter of a single hit, including child blocks.
+    pub tsc_max: u64,
+    /// Number of heap allocations made by the block excluding child blocks, or `0` unless a
+    /// [`TrackingAllocator`](super::memory::TrackingAllocator) is installed.
+    pub alloc_count: u64,
+    /// Bytes allocated by the block excluding child blocks.
+    pub alloc_bytes: u64,
+    /// Bytes freed by the block excluding child blocks.
+    pub freed_bytes: u64,
+    /// Hardware events counted by the block excluding child blocks, or all `0` unless enabled with
+    /// [`set_hardware_counters`](super::counters::set_hardware_counters).
+    pub counters: HardwareCounters,
+    /// Page faults served without I/O during the block excluding child blocks, or `0` unless
+    /// measured with [`set_measure_page_faults`](super::set_measure_page_faults).
+    pub soft_page_faults: u64,
+    /// Page faults which read from disk during the block excluding child blocks.
+    pub hard_page_faults: u64,
+    /// Context switches away from the block's thread because it blocked, excluding child blocks,
+    /// or `0` unless measured with
+    /// [`set_measure_context_switches`](super::set_measure_context_switches).
+    pub voluntary_context_switches: u64,
+    /// Context switches away from the block's thread because it was preempted, excluding child
+    /// blocks.
+    pub involuntary_context_switches: u64,
+}
+
+/// Fewest hits for the variance between them to say whether an anchor is stable.
+const MIN_VARIANCE_HITS: u64 = 5;
+
+/// Largest coefficient of variation between hits of a stable anchor.
+const MAX_STABLE_VARIATION: f64 = 0.5;
+
+/// Widest anchor name shown in printed reports, past which names are truncated.
+const MAX_NAME_WIDTH: usize = 48;
+
+thread_local! {
+    /// Width of the terminal the report being printed on this thread goes to, if any.
+    static WIDTH: Cell<Option<usize>> = const { Cell::new(None) };
+}
+
+/// Runs `print` with reports formatted on this thread fitting their tables to `width` columns.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn fitted<R>(width: Option<usize>, print: impl FnOnce() -> R) -> R {
+    let previous = WIDTH.replace(width);
+    let result = print();
+    WIDTH.set(previous);
+    result
+}
+
+/// Returns a table whose first column holds anchor names, truncated to fit the terminal the report
+/// is printed to.
+fn name_table() -> Table {
+    let table = Table::new().max_width(0, MAX_NAME_WIDTH);
+    match WIDTH.get() {
+        Some(width) => table.fit_width(0, width),
+        None => table,
+    }
+}
+
+impl AnchorStats {
+    /// Exclusive timestamp counter ticks per byte processed, or `0.0` if no bytes were recorded.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cycles_per_byte(&self) -> f64 {
+        per_unit(self.tsc_elapsed_exclusive as f64, self.byte_count)
+    }
+
+    /// Exclusive timestamp counter ticks per item processed, or `0.0` if no items were recorded.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn cycles_per_item(&self) -> f64 {
+        per_unit(self.tsc_elapsed_exclusive as f64, self.item_count)
+    }
+
+    /// Exclusive nanoseconds per byte processed given a timer frequency in ticks per second, or
+    /// `0.0` if no bytes were recorded.
+    #[must_use]
+    pub fn nanoseconds_per_byte(&self, timer_freq: u64) -> f64 {
+        per_unit(self.exclusive_nanoseconds(timer_freq), self.byte_count)
+    }
+
+    /// Exclusive nanoseconds per item processed given a timer frequency in ticks per second, or
+    /// `0.0` if no items were recorded.
+    #[must_use]
+    pub fn nanoseconds_per_item(&self, timer_freq: u64) -> f64 {
+        per_unit(self.exclusive_nanoseconds(timer_freq), self.item_count)
+    }
+
+    /// Exclusive nanoseconds spent off the CPU, waiting on I/O, locks or the scheduler, given a timer
+    /// frequency in ticks per second. This is the elapsed time not accounted for by thread CPU time,
+    /// so is `0` unless CPU time was measured with
+    /// [`set_measure_cpu_time`](super::set_measure_cpu_time).
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn off_cpu_nanoseconds(&self, timer_freq: u64) -> u64 {
+        if self.cpu_ns_inclusive == 0 {
+            return 0;
+        }
+        let elapsed = self.exclusive_nanoseconds(timer_freq).round() as u64;
+        elapsed.saturating_sub(self.cpu_ns_exclusive)
+    }
+
+    /// Average elapsed timestamp counter of a hit, including child blocks.
+    #[must_use]
+    pub fn tsc_mean(&self) -> u64 {
+        self.tsc_elapsed_inclusive / self.hit_count.max(1)
+    }
+
+    /// Standard deviation of the inclusive timestamp counter ticks of each hit, or `0.0` with fewer
+    /// than two hits. Nested hits of recursive anchors make this an estimate.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn tsc_std_dev(&self) -> f64 {
+        if self.hit_count < 2 {
+            return 0.0;
+        }
+        let mean = self.tsc_elapsed_inclusive as f64 / self.hit_count as f64;
+        let mean_square = self.tsc_elapsed_squares as f64 / self.hit_count as f64;
+        (mean_square - mean * mean).max(0.0).sqrt()
+    }
+
+    /// Standard deviation of the time of each hit relative to its mean, or `0.0` with fewer than two
+    /// hits.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn coefficient_of_variation(&self) -> f64 {
+        if self.tsc_elapsed_inclusive == 0 {
+            0.0
+        } else {
+            self.tsc_std_dev() * self.hit_count as f64 / self.tsc_elapsed_inclusive as f64
+        }
+    }
+
+    /// Whether the time of each hit varies so much that its totals and averages are unreliable,
+    /// i.e. it was hit enough times to tell and its standard deviation exceeds half its mean.
+    #[must_use]
+    pub fn is_unstable(&self) -> bool {
+        self.hit_count >= MIN_VARIANCE_HITS
+            && self.coefficient_of_variation() > MAX_STABLE_VARIATION
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn exclusive_nanoseconds(&self, timer_freq: u64) -> f64 {
+        if timer_freq == 0 {
+            0.0
+        } else {
+            1e9 * self.tsc_elapsed_exclusive as f64 / timer_freq as f64
+        }
+    }
+
+    /// Returns the statistics accumulated between `earlier` and `self`.
+    ///
+    /// Exclusive time and allocations use wrapping arithmetic since child blocks subtract from
+    /// their parent before the parent adds its own. The per-hit minimum and maximum are those of
+    /// `self`, since they can't be separated by interval.
+    pub fn delta(&self, earlier: &Self) -> Self {
+        Self {
+            name: self.name,
+            hit_count: self.hit_count.saturating_sub(earlier.hit_count),
+            byte_count: self.byte_count.saturating_sub(earlier.byte_count),
+            item_count: self.item_count.saturating_sub(earlier.item_count),
+            tsc_elapsed_exclusive: self
+                .tsc_elapsed_exclusive
+                .wrapping_sub(earlier.tsc_elapsed_exclusive),
+            tsc_elapsed_inclusive: self
+                .tsc_elapsed_inclusive
+                .saturating_sub(earlier.tsc_elapsed_inclusive),
+            cpu_ns_exclusive: self.cpu_ns_exclusive.wrapping_sub(earlier.cpu_ns_exclusive),
+            cpu_ns_inclusive: self
+                .cpu_ns_inclusive
+                .saturating_sub(earlier.cpu_ns_inclusive),
+            tsc_elapsed_squares: self
+                .tsc_elapsed_squares
+                .saturating_sub(earlier.tsc_elapsed_squares),
+            tsc_min: self.tsc_min,
+            tsc_max: self.tsc_max,
+            alloc_count: self.alloc_count.wrapping_sub(earlier.alloc_count),
+            alloc_bytes: self.alloc_bytes.wrapping_sub(earlier.alloc_bytes),
+            freed_bytes: self.freed_bytes.wrapping_sub(earlier.freed_bytes),
+            counters: self.counters.zip(earlier.counters, u64::wrapping_sub),
+            soft_page_faults: self.soft_page_faults.wrapping_sub(earlier.soft_page_faults),
+            hard_page_faults: self.hard_page_faults.wrapping_sub(earlier.hard_page_faults),
+            voluntary_context_switches: self
+                .voluntary_context_switches
+                .wrapping_sub(earlier.voluntary_context_switches),
+            involuntary_context_switches: self
+                .involuntary_context_switches
+                .wrapping_sub(earlier.involuntary_context_switches),
+        }
+    }
+
+    /// Adds the counts and elapsed time of `other` to this anchor.
+    pub fn merge(&mut self, other: &AnchorStats) {
+        if self.hit_count == 0 {
+            self.tsc_min = other.tsc_min;
+        } else if other.hit_count > 0 {
+            self.tsc_min = self.tsc_min.min(other.tsc_min);
+        }
+        self.tsc_max = self.tsc_max.max(other.tsc_max);
+        self.hit_count = self.hit_count.saturating_add(other.hit_count);
+        self.byte_count = self.byte_count.saturating_add(other.byte_count);
+        self.item_count = self.item_count.saturating_add(other.item_count);
+        self.tsc_elapsed_exclusive = self
+            .tsc_elapsed_exclusive
+            .saturating_add(other.tsc_elapsed_exclusive);
+        self.tsc_elapsed_inclusive = self
+            .tsc_elapsed_inclusive
+            .saturating_add(other.tsc_elapsed_inclusive);
+        self.cpu_ns_exclusive = self.cpu_ns_exclusive.saturating_add(other.cpu_ns_exclusive);
+        self.cpu_ns_inclusive = self.cpu_ns_inclusive.saturating_add(other.cpu_ns_inclusive);
+        self.tsc_elapsed_squares = self
+            .tsc_elapsed_squares
+            .saturating_add(other.tsc_elapsed_squares);
+        self.alloc_count = self.alloc_count.saturating_add(other.alloc_count);
+        self.alloc_bytes = self.alloc_bytes.saturating_add(other.alloc_bytes);
+        self.freed_bytes = self.freed_bytes.saturating_add(other.freed_bytes);
+        self.counters = self.counters.zip(other.counters, u64::saturating_add);
+        self.soft_page_faults = self.soft_page_faults.saturating_add(other.soft_page_faults);
+        self.hard_page_faults = self.hard_page_faults.saturating_add(other.hard_page_faults);
+        self.voluntary_context_switches = self
+            .voluntary_context_switches
+            .saturating_add(other.voluntary_context_switches);
+        self.involuntary_context_switches = self
+            .involuntary_context_switches
+            .saturating_add(other.involuntary_context_switches);
+    }
+
+    /// Returns this anchor with its counts and elapsed time multiplied by `factor`, rounded to the
+    /// nearest whole number, e.g. `1.0 / iterations` for per-iteration numbers. The per-hit minimum
+    /// and maximum are unchanged.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            name: self.name,
+            hit_count: scale(self.hit_count, factor),
+            byte_count: scale(self.byte_count, factor),
+            item_count: scale(self.item_count, factor),
+            tsc_elapsed_exclusive: scale(self.tsc_elapsed_exclusive, factor),
+            tsc_elapsed_inclusive: scale(self.tsc_elapsed_inclusive, factor),
+            cpu_ns_exclusive: scale(self.cpu_ns_exclusive, factor),
+            cpu_ns_inclusive: scale(self.cpu_ns_inclusive, factor),
+            tsc_elapsed_squares: scale_squares(self.tsc_elapsed_squares, factor),
+            alloc_count: scale(self.alloc_count, factor),
+            alloc_bytes: scale(self.alloc_bytes, factor),
+            freed_bytes: scale(self.freed_bytes, factor),
+            counters: self.counters.map(|count| scale(count, factor)),
+            soft_page_faults: scale(self.soft_page_faults, factor),
+            hard_page_faults: scale(self.hard_page_faults, factor),
+            voluntary_context_switches: scale(self.voluntary_context_switches, factor),
+            involuntary_context_switches: scale(self.involuntary_context_switches, factor),
+            ..*self
+        }
+    }
+}
+
+/// Multiplies `value` by `factor`, rounding to the nearest whole number and saturating at the
+/// bounds of `u64`.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+pub(super) fn scale(value: u64, factor: f64) -> u64 {
+    (value as f64 * factor).round() as u64
+}
+
+/// Like [`scale`] for sums of squares.
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn scale_squares(value: u128, factor: f64) -> u128 {
+    (value as f64 * factor).round() as u128
+}
+
+/// Rescales a sum of squared timestamp counts from timer frequency `from` to `to`.
+#[allow(clippy::cast_precision_loss)]
+fn rescale_squares(squares: u128, from: u64, to: u64) -> u128 {
+    if from == 0 || from == to {
+        squares
+    } else {
+        let ratio = to as f64 / from as f64;
+        scale_squares(squares, ratio * ratio)
+    }
+}
+
+/// Divides `total` evenly among `count` units.
+#[allow(clippy::cast_precision_loss)]
+fn per_unit(total: f64, count: u64) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total / count as f64
+    }
+}
+
+/// Returns the per-anchor statistics accumulated between `earlier` and `later`, omitting anchors
+/// that were not hit in between.
+pub(crate) fn anchors_delta(later: &[AnchorStats], earlier: &[AnchorStats]) -> Vec<AnchorStats> {
+    later
+        .iter()
+        .map(|anchor| {
+            earlier
+                .iter()
+                .find(|prev| prev.name == anchor.name)
+                .map_or(*anchor, |prev| anchor.delta(prev))
+        })
+        .filter(|anchor| anchor.hit_count > 0)
+        .collect()
+}
+
+/// Adds `anchor` to the entry with the same name in `anchors`, appending a new entry if there is
+/// none.
+pub(super) fn accumulate_anchor(anchors: &mut Vec<AnchorStats>, anchor: AnchorStats) {
+    match anchors.iter_mut().find(|merged| merged.name == anchor.name) {
+        Some(merged) => merged.merge(&anchor),
+        None => anchors.push(anchor),
+    }
+}
+
+/// Hit counts for one arm of a decision point recorded with `profile_branch!`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct BranchArm {
+    /// Name of the arm.
+    pub name: &'static str,
+    /// Number of times the arm was taken.
+    pub hit_count: u64,
+}
+
+/// Hit counts for every arm of a decision point recorded with `profile_branch!`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct BranchStats {
+    /// Name of the decision point.
+    pub name: &'static str,
+    /// Each arm taken, in the order they were first hit.
+    pub arms: Vec<BranchArm>,
+}
+
+impl BranchStats {
+    /// Adds the arm hit counts of `other` to this decision point, matching arms by name.
+    pub fn merge(&mut self, other: &BranchStats) {
+        for arm in &other.arms {
+            match self.arms.iter_mut().find(|merged| merged.name == arm.name) {
+                Some(merged) => merged.hit_count = merged.hit_count.saturating_add(arm.hit_count),
+                None => self.arms.push(*arm),
+            }
+        }
+    }
+
+    /// Returns this decision point with its arm hit counts multiplied by `factor`.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            name: self.name,
+            arms: self
+                .arms
+                .iter()
+                .map(|arm| BranchArm {
+                    hit_count: scale(arm.hit_count, factor),
+                    ..*arm
+                })
+                .collect(),
+        }
+    }
+
+    /// Total number of times the decision point was reached.
+    #[must_use]
+    pub fn hit_count(&self) -> u64 {
+        self.arms.iter().map(|arm| arm.hit_count).sum()
+    }
+
+    /// Returns the fraction of hits on this decision point which took `arm`.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn ratio(&self, arm: &BranchArm) -> f64 {
+        match self.hit_count() {
+            0 => 0.0,
+            total => arm.hit_count as f64 / total as f64,
+        }
+    }
+}
+
+/// Total of a counter recorded with `counter!` or `counter_add!`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct CounterStats {
+    /// Name of the counter.
+    pub name: &'static str,
+    /// Sum of every count added.
+    pub count: u64,
+}
+
+/// Iteration statistics for a loop recorded with `profile_loop!`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct LoopStats {
+    /// Name of the loop.
+    pub name: &'static str,
+    /// Name of the enclosing profile block, if any.
+    pub parent: Option<&'static str>,
+    /// Total number of iterations.
+    pub iteration_count: u64,
+    /// Number of times the enclosing profile block was hit, or `1` without an enclosing block.
+    pub parent_hit_count: u64,
+    /// Shortest iteration elapsed timestamp counter.
+    pub tsc_min: u64,
+    /// Longest iteration elapsed timestamp counter.
+    pub tsc_max: u64,
+    /// Total elapsed timestamp counter across all iterations.
+    pub tsc_total: u64,
+}
+
+impl LoopStats {
+    /// Adds the iterations of `other` to this loop.
+    pub fn merge(&mut self, other: &LoopStats) {
+        if other.iteration_count == 0 {
+            return;
+        }
+        if self.iteration_count == 0 {
+            self.tsc_min = other.tsc_min;
+        }
+        self.iteration_count = self.iteration_count.saturating_add(other.iteration_count);
+        self.parent_hit_count = self.parent_hit_count.saturating_add(other.parent_hit_count);
+        self.tsc_min = self.tsc_min.min(other.tsc_min);
+        self.tsc_max = self.tsc_max.max(other.tsc_max);
+        self.tsc_total = self.tsc_total.saturating_add(other.tsc_total);
+    }
+
+    /// Returns this loop with its counts and total elapsed time multiplied by `factor`. The
+    /// per-iteration minimum and maximum are unchanged.
+    pub fn scaled(&self, factor: f64) -> Self {
+        Self {
+            iteration_count: scale(self.iteration_count, factor),
+            parent_hit_count: scale(self.parent_hit_count, factor),
+            tsc_total: scale(self.tsc_total, factor),
+            ..*self
+        }
+    }
+
+    /// Average number of iterations per hit of the enclosing profile block.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn iterations_per_hit(&self) -> f64 {
+        self.iteration_count as f64 / self.parent_hit_count.max(1) as f64
+    }
+
+    /// Average iteration elapsed timestamp counter.
+    #[must_use]
+    pub fn tsc_mean(&self) -> u64 {
+        self.tsc_total / self.iteration_count.max(1)
+    }
+
+    /// Iterations per second, given a timer frequency in ticks per second.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn iterations_per_second(&self, timer_freq: u64) -> f64 {
+        if self.tsc_total == 0 {
+            0.0
+        } else {
+            self.iteration_count as f64 * timer_freq as f64 / self.tsc_total as f64
+        }
+    }
+}
+
+/// Number of samples attributed to an anchor by a [`Sampler`](super::sampling::Sampler).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct SampleStats {
+    /// Name of the innermost anchor when the samples were taken, or
+    /// [`UNINSTRUMENTED`] outside any block.
+    pub name: &'static str,
+    /// Number of samples taken.
+    pub sample_count: u64,
+}
+
+/// Number of calls and elapsed time of blocks of one anchor nested directly inside another.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct CallStats {
+    /// Name of the enclosing anchor.
+    pub caller: &'static str,
+    /// Name of the nested anchor.
+    pub callee: &'static str,
+    /// Number of nested blocks.
+    pub call_count: u64,
+    /// Total elapsed timestamp counter of the nested blocks, including their children.
+    pub tsc_elapsed_inclusive: u64,
+}
+
+/// Adds `call` to the entry with the same caller and callee in `calls`, or appends it.
+fn accumulate_call(calls: &mut Vec<CallStats>, call: CallStats) {
+    match calls
+        .iter_mut()
+        .find(|merged| merged.caller == call.caller && merged.callee == call.callee)
+    {
+        Some(merged) => {
+            merged.call_count = merged.call_count.saturating_add(call.call_count);
+            merged.tsc_elapsed_inclusive = merged
+                .tsc_elapsed_inclusive
+                .saturating_add(call.tsc_elapsed_inclusive);
+        }
+        None => calls.push(call),
+    }
+}
+
+/// Hit count and elapsed time of an anchor reached through one call stack, recorded with the
+/// `callstacks` feature.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct CallStackStats {
+    /// Name of the anchor.
+    pub name: &'static str,
+    /// ID of the call stack, which can be resolved with `callstack::frames`.
+    pub stack_id: u64,
+    /// Number of times the anchor was hit through this call stack.
+    pub hit_count: u64,
+    /// Total elapsed timestamp counter of those hits, including children.
+    pub tsc_elapsed_inclusive: u64,
+}
+
+/// Adds `stats` to the entry with the same name and stack in `call_stacks`, or appends it.
+fn accumulate_call_stack(call_stacks: &mut Vec<CallStackStats>, stats: CallStackStats) {
+    match call_stacks
+        .iter_mut()
+        .find(|merged| merged.name == stats.name && merged.stack_id == stats.stack_id)
+    {
+        Some(merged) => {
+            merged.hit_count = merged.hit_count.saturating_add(stats.hit_count);
+            merged.tsc_elapsed_inclusive = merged
+                .tsc_elapsed_inclusive
+                .saturating_add(stats.tsc_elapsed_inclusive);
+        }
+        None => call_stacks.push(stats),
+    }
+}
+
+/// Where an anchor was first hit, captured when
+/// [`set_capture_backtraces`](super::set_capture_backtraces) is enabled.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct AnchorBacktrace {
+    /// Name of the anchor.
+    pub name: &'static str,
+    /// Backtrace of the anchor's first hit.
+    pub backtrace: String,
+}
+
+/// The slowest hit of an anchor, captured when
+/// [`set_capture_slow_hits`](super::set_capture_slow_hits) is enabled.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct SlowHit {
+    /// Name of the anchor.
+    pub name: &'static str,
+    /// Elapsed timestamp counter of the hit.
+    pub tsc_elapsed: u64,
+    /// Backtrace of the end of the hit.
+    pub backtrace: String,
+}
+
+/// Keeps `hit` in `slow_hits` if it's slower than the entry of the same name, or appends it.
+fn accumulate_slow_hit(slow_hits: &mut Vec<SlowHit>, hit: SlowHit) {
+    match slow_hits
+        .iter_mut()
+        .find(|slowest| slowest.name == hit.name)
+    {
+        Some(slowest) if slowest.tsc_elapsed < hit.tsc_elapsed => *slowest = hit,
+        Some(_) => {}
+        None => slow_hits.push(hit),
+    }
+}
+
+/// Adds the hits of `histogram`, with timestamp counters mapped through `rescale`, to the entry
+/// named `name` in `histograms`, or appends one.
+fn accumulate_histogram(
+    histograms: &mut Vec<LatencyHistogram>,
+    name: &'static str,
+    histogram: &LatencyHistogram,
+    rescale: &dyn Fn(u64) -> u64,
+) {
+    let index = histograms
+        .iter()
+        .position(|merged| merged.name == name)
+        .unwrap_or_else(|| {
+            histograms.push(LatencyHistogram::new(name, histogram.precision()));
+            histograms.len() - 1
+        });
+    histograms[index].merge_rescaled(histogram, rescale);
+}
+
+/// Adds `sample` to the entry with the same name in `samples`, or appends it.
+fn accumulate_sample(samples: &mut Vec<SampleStats>, sample: SampleStats) {
+    match samples.iter_mut().find(|merged| merged.name == sample.name) {
+        Some(merged) => {
+            merged.sample_count = merged.sample_count.saturating_add(sample.sample_count);
+        }
+        None => samples.push(sample),
+    }
+}
+
+/// A named point-in-time copy of the profiler state, taken with
+/// [`profile_snapshot`](super::profile_snapshot).
+///
+/// Anchor statistics are only updated when a block ends, so blocks still active when the snapshot
+/// is taken are not included until a later snapshot.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Name given to this snapshot.
+    pub name: &'static str,
+    /// Timestamp counter when the snapshot was taken.
+    pub tsc: u64,
+    /// Statistics for every anchor seen so far.
+    pub anchors: Vec<AnchorStats>,
+}
+
+impl Snapshot {
+    /// Returns a report of what happened between `earlier` and this snapshot, e.g. for per-request
+    /// or per-batch accounting in a loop.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::{performance::profile_snapshot, profile};
+    ///
+    /// let mut prev = profile_snapshot("batch");
+    /// for _ in 0..3 {
+    ///     {
+    ///         profile!("process_batch");
+    ///     }
+    ///     let next = profile_snapshot("batch");
+    ///     let report = next.delta(&prev);
+    ///     println!("batch took {:.4}ms", report.elapsed_ms());
+    ///     prev = next;
+    /// }
+    /// ```
+    pub fn delta(&self, earlier: &Snapshot) -> ProfileReport {
+        #[cfg(feature = "perf")]
+        let timer_freq = super::Profiler::timer_freq();
+        #[cfg(not(feature = "perf"))]
+        let timer_freq = 0;
+
+        ProfileReport {
+            elapsed_tsc: self.tsc.saturating_sub(earlier.tsc),
+            timer_freq,
+            anchors: anchors_delta(&self.anchors, &earlier.anchors),
+            ..ProfileReport::default()
+        }
+    }
+}
+
+/// The profiling results between two snapshots.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[must_use]
+pub struct Interval {
+    /// Name of the snapshot starting this interval.
+    pub from: &'static str,
+    /// Name of the snapshot ending this interval.
+    pub to: &'static str,
+    /// Results for this interval.
+    pub report: ProfileReport,
+}
+
+/// The thread a report was ended on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ThreadInfo {
+    /// Name of the thread, or the name set with
+    /// [`profile_set_thread_name`](super::profile_set_thread_name).
+    pub name: &'static str,
+    /// ID of the thread.
+    pub id: std::thread::ThreadId,
+}
+
+/// The results of a profiling session.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[must_use]
+pub struct ProfileReport {
+    /// Total elapsed timestamp counter.
+    pub elapsed_tsc: u64,
+    /// Estimated timestamp counter frequency, in ticks per second.
+    pub timer_freq: u64,
+    /// Statistics for each anchor, in the order they were first hit.
+    pub anchors: Vec<AnchorStats>,
+    /// Per-interval results between each snapshot taken during the session, if any.
+    pub intervals: Vec<Interval>,
+    /// Names of registered anchors which were never hit, in or out of a namespace. These are the
+    /// names of their sites, without any namespace.
+    pub never_hit: Vec<&'static str>,
+    /// Arm hit counts for each decision point recorded with `profile_branch!`.
+    pub branches: Vec<BranchStats>,
+    /// Totals of each counter recorded with `counter!` or `counter_add!`, in the order they were
+    /// first added to.
+    pub event_counters: Vec<CounterStats>,
+    /// Iteration statistics for each loop recorded with `profile_loop!`.
+    pub loops: Vec<LoopStats>,
+    /// Samples taken while a [`Sampler`](super::sampling::Sampler) was running, most sampled first.
+    pub samples: Vec<SampleStats>,
+    /// Calls between anchors, for each block nested directly inside another.
+    pub calls: Vec<CallStats>,
+    /// Elapsed time of each anchor per call stack, with the `callstacks` feature.
+    pub call_stacks: Vec<CallStackStats>,
+    /// Backtraces of the first hit of each anchor, if captured.
+    pub backtraces: Vec<AnchorBacktrace>,
+    /// Backtraces of the slowest hit of each anchor over the threshold set with
+    /// [`set_capture_slow_hits`](super::set_capture_slow_hits), slowest first.
+    pub slow_hits: Vec<SlowHit>,
+    /// Latency histogram of each anchor, if enabled with
+    /// [`ProfilerConfig::latency_histograms`](super::config::ProfilerConfig::latency_histograms).
+    pub histograms: Vec<LatencyHistogram>,
+    /// Key-value pairs describing the run, set with
+    /// [`set_run_metadata`](super::set_run_metadata).
+    pub metadata: Vec<(&'static str, String)>,
+    /// Misuses of the profiling API detected during the session, such as unbalanced manual blocks.
+    /// Only checked in debug builds.
+    pub warnings: Vec<String>,
+    /// Memory use of the process when the report was ended, if enabled with
+    /// [`set_report_memory`](super::set_report_memory).
+    pub memory: Option<MemoryUsage>,
+    /// Processor of the machine, if enabled with
+    /// [`set_report_cpu_info`](super::set_report_cpu_info).
+    pub cpu: Option<CpuInfo>,
+    /// Thread the report was ended on, or `None` for reports not ended by a profiler.
+    pub thread: Option<ThreadInfo>,
+}
+
+impl ProfileReport {
+    /// Total elapsed time in milliseconds.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn elapsed_ms(&self) -> f64 {
+        if self.timer_freq == 0 {
+            0.0
+        } else {
+            1000.0 * self.elapsed_tsc as f64 / self.timer_freq as f64
+        }
+    }
+
+    /// Total elapsed time.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.tsc_duration(self.elapsed_tsc)
+    }
+
+    /// Converts `tsc` timestamp counter ticks to a duration at the report's timer frequency.
+    fn tsc_duration(&self, tsc: u64) -> Duration {
+        if self.timer_freq == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = u128::from(tsc) * 1_000_000_000 / u128::from(self.timer_freq);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    /// Adds the elapsed time, anchor, branch and loop statistics, and warnings of `other` to this
+    /// report, matching entries by name. Timestamp counts of `other` are rescaled to this report's
+    /// timer frequency, which is taken from `other` if not yet known. Intervals are not merged.
+    pub fn merge(&mut self, other: &ProfileReport) {
+        if self.timer_freq == 0 {
+            self.timer_freq = other.timer_freq;
+        }
+        let timer_freq = self.timer_freq;
+        let rescale = |tsc: u64| {
+            if other.timer_freq == 0 || other.timer_freq == timer_freq {
+                tsc
+            } else {
+                let scaled =
+                    u128::from(tsc) * u128::from(timer_freq) / u128::from(other.timer_freq);
+                u64::try_from(scaled).unwrap_or(u64::MAX)
+            }
+        };
+
+        self.elapsed_tsc = self.elapsed_tsc.saturating_add(rescale(other.elapsed_tsc));
+        for anchor in &other.anchors {
+            accumulate_anchor(
+                &mut self.anchors,
+                AnchorStats {
+                    tsc_elapsed_exclusive: rescale(anchor.tsc_elapsed_exclusive),
+                    tsc_elapsed_inclusive: rescale(anchor.tsc_elapsed_inclusive),
+                    tsc_elapsed_squares: rescale_squares(
+                        anchor.tsc_elapsed_squares,
+                        other.timer_freq,
+                        self.timer_freq,
+                    ),
+                    tsc_min: rescale(anchor.tsc_min),
+                    tsc_max: rescale(anchor.tsc_max),
+                    ..*anchor
+                },
+            );
+        }
+        for branch in &other.branches {
+            match self
+                .branches
+                .iter_mut()
+                .find(|merged| merged.name == branch.name)
+            {
+                Some(merged) => merged.merge(branch),
+                None => self.branches.push(branch.clone()),
+            }
+        }
+        for counter in &other.event_counters {
+            match self
+                .event_counters
+                .iter_mut()
+                .find(|merged| merged.name == counter.name)
+            {
+                Some(merged) => merged.count = merged.count.saturating_add(counter.count),
+                None => self.event_counters.push(*counter),
+            }
+        }
+        for stats in &other.loops {
+            let stats = LoopStats {
+                tsc_min: rescale(stats.tsc_min),
+                tsc_max: rescale(stats.tsc_max),
+                tsc_total: rescale(stats.tsc_total),
+                ..*stats
+            };
+            match self
+                .loops
+                .iter_mut()
+                .find(|merged| merged.name == stats.name && merged.parent == stats.parent)
+            {
+                Some(merged) => merged.merge(&stats),
+                None => self.loops.push(stats),
+            }
+        }
+        for &name in &other.never_hit {
+            if !self.never_hit.contains(&name) {
+                self.never_hit.push(name);
+            }
+        }
+        let anchors = &self.anchors;
+        self.never_hit
+            .retain(|&name| !anchors.iter().any(|anchor| anchor.name == name));
+        for &sample in &other.samples {
+            accumulate_sample(&mut self.samples, sample);
+        }
+        self.merge_calls(other, &rescale);
+        self.merge_backtraces(other, &rescale);
+        for (key, value) in &other.metadata {
+            if !self.metadata.iter().any(|(merged, _)| merged == key) {
+                self.metadata.push((key, value.clone()));
+            }
+        }
+        self.warnings.extend_from_slice(&other.warnings);
+        self.merge_system(other);
+    }
+
+    /// Keeps the largest memory use of this report and `other`, and the first processor.
+    fn merge_system(&mut self, other: &ProfileReport) {
+        if let Some(other) = other.memory {
+            let memory = self.memory.get_or_insert(other);
+            memory.rss = memory.rss.max(other.rss);
+            memory.peak_rss = memory.peak_rss.max(other.peak_rss);
+            memory.virtual_size = memory.virtual_size.max(other.virtual_size);
+        }
+        if self.cpu.is_none() {
+            self.cpu.clone_from(&other.cpu);
+        }
+    }
+
+    /// Adds the first hit backtraces of anchors not yet listed and the slowest hits of `other`,
+    /// with timestamp counts mapped through `rescale`.
+    fn merge_backtraces(&mut self, other: &ProfileReport, rescale: &dyn Fn(u64) -> u64) {
+        for backtrace in &other.backtraces {
+            if !self
+                .backtraces
+                .iter()
+                .any(|merged| merged.name == backtrace.name)
+            {
+                self.backtraces.push(backtrace.clone());
+            }
+        }
+        for hit in &other.slow_hits {
+            accumulate_slow_hit(
+                &mut self.slow_hits,
+                SlowHit {
+                    tsc_elapsed: rescale(hit.tsc_elapsed),
+                    ..hit.clone()
+                },
+            );
+        }
+        self.slow_hits
+            .sort_by_key(|hit| std::cmp::Reverse(hit.tsc_elapsed));
+    }
+
+    /// Adds the calls, call stacks and latency histograms of `other` to this report, with timestamp
+    /// counters mapped through `rescale`.
+    fn merge_calls(&mut self, other: &ProfileReport, rescale: &dyn Fn(u64) -> u64) {
+        for call in &other.calls {
+            accumulate_call(
+                &mut self.calls,
+                CallStats {
+                    tsc_elapsed_inclusive: rescale(call.tsc_elapsed_inclusive),
+                    ..*call
+                },
+            );
+        }
+        for stats in &other.call_stacks {
+            accumulate_call_stack(
+                &mut self.call_stacks,
+                CallStackStats {
+                    tsc_elapsed_inclusive: rescale(stats.tsc_elapsed_inclusive),
+                    ..*stats
+                },
+            );
+        }
+        for histogram in &other.histograms {
+            accumulate_histogram(&mut self.histograms, histogram.name, histogram, rescale);
+        }
+    }
+
+    /// Returns this report with every count and elapsed time multiplied by `factor`, e.g.
+    /// `1.0 / iterations` to normalize a benchmark run to per-iteration numbers.
+    pub fn scaled(&self, factor: f64) -> ProfileReport {
+        ProfileReport {
+            elapsed_tsc: scale(self.elapsed_tsc, factor),
+            anchors: self
+                .anchors
+                .iter()
+                .map(|anchor| anchor.scaled(factor))
+                .collect(),
+            intervals: self
+                .intervals
+                .iter()
+                .map(|interval| Interval {
+                    report: interval.report.scaled(factor),
+                    ..*interval
+                })
+                .collect(),
+            branches: self
+                .branches
+                .iter()
+                .map(|branch| branch.scaled(factor))
+                .collect(),
+            event_counters: self
+                .event_counters
+                .iter()
+                .map(|counter| CounterStats {
+                    count: scale(counter.count, factor),
+                    ..*counter
+                })
+                .collect(),
+            loops: self
+                .loops
+                .iter()
+                .map(|stats| stats.scaled(factor))
+                .collect(),
+            samples: self
+                .samples
+                .iter()
+                .map(|sample| SampleStats {
+                    sample_count: scale(sample.sample_count, factor),
+                    ..*sample
+                })
+                .collect(),
+            calls: self
+                .calls
+                .iter()
+                .map(|call| CallStats {
+                    call_count: scale(call.call_count, factor),
+                    tsc_elapsed_inclusive: scale(call.tsc_elapsed_inclusive, factor),
+                    ..*call
+                })
+                .collect(),
+            call_stacks: self
+                .call_stacks
+                .iter()
+                .map(|stats| CallStackStats {
+                    hit_count: scale(stats.hit_count, factor),
+                    tsc_elapsed_inclusive: scale(stats.tsc_elapsed_inclusive, factor),
+                    ..*stats
+                })
+                .collect(),
+            histograms: self
+                .histograms
+                .iter()
+                .map(|histogram| histogram.scaled(factor))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this report keeping only the anchors, including those of each interval and
+    /// those never hit, whose names pass `filter`.
+    pub fn filtered(&self, filter: &AnchorFilter) -> ProfileReport {
+        ProfileReport {
+            anchors: self
+                .anchors
+                .iter()
+                .filter(|anchor| filter.matches(anchor.name))
+                .copied()
+                .collect(),
+            intervals: self
+                .intervals
+                .iter()
+                .map(|interval| Interval {
+                    report: interval.report.filtered(filter),
+                    ..*interval
+                })
+                .collect(),
+            never_hit: self
+                .never_hit
+                .iter()
+                .filter(|name| filter.matches(name))
+                .copied()
+                .collect(),
+            samples: self
+                .samples
+                .iter()
+                .filter(|sample| sample.name == UNINSTRUMENTED || filter.matches(sample.name))
+                .copied()
+                .collect(),
+            calls: self
+                .calls
+                .iter()
+                .filter(|call| filter.matches(call.caller) && filter.matches(call.callee))
+                .copied()
+                .collect(),
+            call_stacks: self
+                .call_stacks
+                .iter()
+                .filter(|stats| filter.matches(stats.name))
+                .copied()
+                .collect(),
+            backtraces: self
+                .backtraces
+                .iter()
+                .filter(|backtrace| filter.matches(backtrace.name))
+                .cloned()
+                .collect(),
+            slow_hits: self
+                .slow_hits
+                .iter()
+                .filter(|hit| filter.matches(hit.name))
+                .cloned()
+                .collect(),
+            histograms: self
+                .histograms
+                .iter()
+                .filter(|histogram| filter.matches(histogram.name))
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this report, and of its intervals, with the anchors sorted and cut off by
+    /// `options`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::performance::{
+    ///     options::{ReportOptions, SortOrder},
+    ///     AnchorStats, ProfileReport,
+    /// };
+    ///
+    /// let anchor = |name, hit_count| AnchorStats {
+    ///     name,
+    ///     hit_count,
+    ///     tsc_elapsed_inclusive: 10,
+    ///     ..AnchorStats::default()
+    /// };
+    /// let report = ProfileReport {
+    ///     elapsed_tsc: 100,
+    ///     anchors: vec![anchor("read", 1), anchor("parse", 8), anchor("write", 3)],
+    ///     ..ProfileReport::default()
+    /// };
+    /// let sorted = report.with_options(&ReportOptions::new().sort_by(SortOrder::Hits).top(2));
+    /// let names: Vec<_> = sorted.anchors.iter().map(|anchor| anchor.name).collect();
+    /// assert_eq!(names, ["parse", "write"]);
+    /// ```
+    #[allow(clippy::cast_precision_loss)]
+    pub fn with_options(&self, options: &ReportOptions) -> ProfileReport {
+        let mut anchors: Vec<AnchorStats> = self
+            .anchors
+            .iter()
+            .filter(|anchor| {
+                options.min_percent <= 0.0
+                    || 100.0 * anchor.tsc_elapsed_inclusive as f64 / self.elapsed_tsc as f64
+                        >= options.min_percent
+            })
+            .copied()
+            .collect();
+        match options.sort {
+            SortOrder::Discovery => {}
+            SortOrder::Exclusive => {
+                anchors.sort_by_key(|anchor| std::cmp::Reverse(anchor.tsc_elapsed_exclusive));
+            }
+            SortOrder::Inclusive => {
+                anchors.sort_by_key(|anchor| std::cmp::Reverse(anchor.tsc_elapsed_inclusive));
+            }
+            SortOrder::Hits => anchors.sort_by_key(|anchor| std::cmp::Reverse(anchor.hit_count)),
+            SortOrder::Name => anchors.sort_by_key(|anchor| anchor.name),
+        }
+        if let Some(top) = options.top {
+            anchors.truncate(top);
+        }
+        ProfileReport {
+            anchors,
+            intervals: self
+                .intervals
+                .iter()
+                .map(|interval| Interval {
+                    report: interval.report.with_options(options),
+                    ..*interval
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a copy of this report with anchor names mapped through `renames`. Anchors renamed to
+    /// the same label are combined into one entry.
+    pub fn renamed(&self, renames: &AnchorRenames) -> ProfileReport {
+        self.map_anchor_names(&|name| renames.rename(name))
+    }
+
+    /// Returns a copy of this report with anchor names shortened according to `policy`. Anchors
+    /// shortened to the same name are combined into one entry.
+    pub fn shortened(&self, policy: &NamePolicy) -> ProfileReport {
+        self.map_anchor_names(&|name| policy.shorten(name))
+    }
+
+    /// Returns a copy of this report with `prefix` prepended to every anchor name.
+    #[cfg_attr(not(feature = "perf"), allow(dead_code))]
+    pub(crate) fn prefixed(&self, prefix: &str) -> ProfileReport {
+        self.map_anchor_names(&|name| crate::intern::intern_static(&format!("{prefix}{name}")))
+    }
+
+    fn map_anchor_names(&self, map: &dyn Fn(&'static str) -> &'static str) -> ProfileReport {
+        let mut anchors = Vec::with_capacity(self.anchors.len());
+        for anchor in &self.anchors {
+            accumulate_anchor(
+                &mut anchors,
+                AnchorStats {
+                    name: map(anchor.name),
+                    ..*anchor
+                },
+            );
+        }
+        let mut never_hit: Vec<&'static str> = Vec::with_capacity(self.never_hit.len());
+        for name in self.never_hit.iter().map(|name| map(name)) {
+            if !never_hit.contains(&name) && !anchors.iter().any(|anchor| anchor.name == name) {
+                never_hit.push(name);
+            }
+        }
+        let mut samples = Vec::with_capacity(self.samples.len());
+        for sample in &self.samples {
+            let name = match sample.name {
+                UNINSTRUMENTED => UNINSTRUMENTED,
+                name => map(name),
+            };
+            accumulate_sample(&mut samples, SampleStats { name, ..*sample });
+        }
+        let mut calls = Vec::with_capacity(self.calls.len());
+        for call in &self.calls {
+            accumulate_call(
+                &mut calls,
+                CallStats {
+                    caller: map(call.caller),
+                    callee: map(call.callee),
+                    ..*call
+                },
+            );
+        }
+        let mut call_stacks = Vec::with_capacity(self.call_stacks.len());
+        for stats in &self.call_stacks {
+            accumulate_call_stack(
+                &mut call_stacks,
+                CallStackStats {
+                    name: map(stats.name),
+                    ..*stats
+                },
+            );
+        }
+        let mut backtraces: Vec<AnchorBacktrace> = Vec::with_capacity(self.backtraces.len());
+        for backtrace in &self.backtraces {
+            let name = map(backtrace.name);
+            if !backtraces.iter().any(|mapped| mapped.name == name) {
+                backtraces.push(AnchorBacktrace {
+                    name,
+                    backtrace: backtrace.backtrace.clone(),
+                });
+            }
+        }
+        let mut slow_hits = Vec::with_capacity(self.slow_hits.len());
+        for hit in &self.slow_hits {
+            accumulate_slow_hit(
+                &mut slow_hits,
+                SlowHit {
+                    name: map(hit.name),
+                    ..hit.clone()
+                },
+            );
+        }
+        let mut histograms = Vec::with_capacity(self.histograms.len());
+        for histogram in &self.histograms {
+            accumulate_histogram(&mut histograms, map(histogram.name), histogram, &|tsc| tsc);
+        }
+        ProfileReport {
+            anchors,
+            samples,
+            calls,
+            call_stacks,
+            backtraces,
+            slow_hits,
+            histograms,
+            intervals: self
+                .intervals
+                .iter()
+                .map(|interval| Interval {
+                    report: interval.report.map_anchor_names(map),
+                    ..*interval
+                })
+                .collect(),
+            never_hit,
+            loops: self
+                .loops
+                .iter()
+                .map(|stats| LoopStats {
+                    parent: stats.parent.map(map),
+                    ..*stats
+                })
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns a condensed view of this report containing only the top `count` anchors by exclusive
+    /// time and a one-line totals summary, for CI logs and quick terminal checks.
+    pub fn summary(&self, count: usize) -> Summary<'_> {
+        Summary {
+            report: self,
+            count,
+        }
+    }
+
+    /// Returns a hierarchical view of this report which nests each anchor under the anchors whose
+    /// blocks enclosed it, with its share of its parent's and the total time.
+    pub fn tree(&self) -> Tree<'_> {
+        Tree { report: self }
+    }
+
+    /// Describes the hardware counters, page faults and context switches of `anchor`, if measured.
+    fn system_events(anchor: &AnchorStats, details: &mut Vec<String>) {
+        if !anchor.counters.is_zero() {
+            let counters = &anchor.counters;
+            details.push(format!(
+                "{} cycles, {} instructions ({:.2} IPC), {} cache misses, {} branch misses",
+                counters.cycles,
+                counters.instructions,
+                counters.instructions_per_cycle(),
+                counters.cache_misses,
+                counters.branch_misses
+            ));
+        }
+
+        if anchor.soft_page_faults > 0 || anchor.hard_page_faults > 0 {
+            details.push(format!(
+                "{} soft, {} hard page faults",
+                anchor.soft_page_faults, anchor.hard_page_faults
+            ));
+        }
+
+        if anchor.voluntary_context_switches > 0 || anchor.involuntary_context_switches > 0 {
+            details.push(format!(
+                "{} voluntary, {} involuntary context switches",
+                anchor.voluntary_context_switches, anchor.involuntary_context_switches
+            ));
+        }
+    }
+
+    /// Describes the variance, extremes, percentiles, CPU time, system events, allocations and
+    /// throughput of `anchor`, as far as they were measured.
+    #[allow(clippy::cast_precision_loss)]
+    fn anchor_details(&self, anchor: &AnchorStats) -> String {
+        let mut details = Vec::new();
+        if anchor.is_unstable() {
+            details.push(format!(
+                "unstable ±{:.0}%",
+                100.0 * anchor.coefficient_of_variation()
+            ));
+        }
+        if anchor.hit_count > 1 {
+            details.push(format!(
+                "min {} avg {} max {}",
+                anchor.tsc_min,
+                anchor.tsc_mean(),
+                anchor.tsc_max
+            ));
+        }
+        details.extend(self.percentiles(anchor));
+
+        if anchor.cpu_ns_inclusive > 0 {
+            details.push(format!(
+                "{} CPU, {} off-CPU",
+                format_duration(Duration::from_nanos(anchor.cpu_ns_exclusive)),
+                format_duration(Duration::from_nanos(
+                    anchor.off_cpu_nanoseconds(self.timer_freq)
+                ))
+            ));
+        }
+
+        Self::system_events(anchor, &mut details);
+
+        if anchor.alloc_count > 0 || anchor.freed_bytes > 0 {
+            details.push(format!(
+                "{} allocs, {} allocated, {} freed",
+                anchor.alloc_count,
+                format_bytes(anchor.alloc_bytes),
+                format_bytes(anchor.freed_bytes)
+            ));
+        }
+
+        if anchor.byte_count > 0 {
+            let seconds = anchor.tsc_elapsed_exclusive as f64 / self.timer_freq as f64;
+            let bytes_per_second = anchor.byte_count as f64 / seconds;
+
+            details.push(format!(
+                "{} at {} ({:.3}ns/byte, {:.3}cycles/byte)",
+                format_bytes(anchor.byte_count),
+                format_rate(bytes_per_second),
+                anchor.nanoseconds_per_byte(self.timer_freq),
+                anchor.cycles_per_byte(),
+            ));
+        }
+
+        if anchor.item_count > 0 {
+            let seconds = anchor.tsc_elapsed_exclusive as f64 / self.timer_freq as f64;
+            let items_per_second = anchor.item_count as f64 / seconds;
+
+            details.push(format!(
+                "{} items at {items_per_second:.0} items/s ({:.2}ns/item, {:.2}cycles/item)",
+                anchor.item_count,
+                anchor.nanoseconds_per_item(self.timer_freq),
+                anchor.cycles_per_item(),
+            ));
+        }
+        details.join("  ")
+    }
+
+    /// Describes the latency percentiles of `anchor`, if its histogram counted any hits.
+    fn percentiles(&self, anchor: &AnchorStats) -> Option<String> {
+        let histogram = self
+            .histograms
+            .iter()
+            .find(|histogram| histogram.name == anchor.name && histogram.count() > 0)?;
+        let percentiles: Vec<String> = REPORTED_PERCENTILES
+            .iter()
+            .map(|&(label, percentile)| format!("{label} {}", histogram.percentile(percentile)))
+            .collect();
+        Some(percentiles.join(" "))
+    }
+
+    /// Writes a table of `anchors` with their hits, exclusive ticks, shares of the total time
+    /// without and with their children, and details, in the colors of their hotspots.
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt_anchors<'a>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        anchors: impl IntoIterator<Item = &'a AnchorStats>,
+    ) -> fmt::Result {
+        let percent = |tsc: u64| 100.0 * (tsc as f64 / self.elapsed_tsc as f64);
+        let mut table = name_table()
+            .headers(["Anchor", "Hits", "Ticks", "Self", "Total"])
+            .align(1, Align::Right)
+            .align(2, Align::Right)
+            .align(3, Align::Right)
+            .align(4, Align::Right)
+            .indent(2);
+        let mut hotspots = Vec::new();
+        for anchor in anchors {
+            let exclusive = percent(anchor.tsc_elapsed_exclusive);
+            hotspots.push(color::hotspot(exclusive));
+            table.push_row([
+                anchor.name.to_string(),
+                anchor.hit_count.to_string(),
+                anchor.tsc_elapsed_exclusive.to_string(),
+                format!("{exclusive:.2}%"),
+                format!("{:.2}%", percent(anchor.tsc_elapsed_inclusive)),
+                self.anchor_details(anchor),
+            ]);
+        }
+        if table.is_empty() {
+            return Ok(());
+        }
+
+        let mut lines = table.lines().into_iter();
+        if let Some(header) = lines.next() {
+            writeln!(f, "{header}")?;
+        }
+        for (line, hotspot) in lines.zip(hotspots) {
+            match hotspot {
+                Some(code) => writeln!(f, "{code}{line}{}", color::RESET)?,
+                None => writeln!(f, "{line}")?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the anchors which were timed, in report order.
+    fn timed_anchors(&self) -> impl Iterator<Item = &AnchorStats> {
+        self.anchors
+            .iter()
+            .filter(|anchor| anchor.tsc_elapsed_inclusive > 0)
+    }
+
+    /// Writes the hits of each branch arm, the counter totals and the loop iterations.
+    fn fmt_counts(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.branches.is_empty() {
+            writeln!(f, "\nBranches:")?;
+            for branch in &self.branches {
+                write!(f, "  {}[{}]:", branch.name, branch.hit_count())?;
+                for (i, arm) in branch.arms.iter().enumerate() {
+                    let separator = if i == 0 { " " } else { ", " };
+                    let percent = 100.0 * branch.ratio(arm);
+                    write!(
+                        f,
+                        "{separator}{} {percent:.2}% [{}]",
+                        arm.name, arm.hit_count
+                    )?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        if !self.event_counters.is_empty() {
+            writeln!(f, "\nCounters:")?;
+            let mut table = name_table().align(1, Align::Right).indent(2);
+            for counter in &self.event_counters {
+                table.push_row([counter.name.to_string(), counter.count.to_string()]);
+            }
+            write!(f, "{table}")?;
+        }
+
+        if !self.loops.is_empty() {
+            writeln!(f, "\nLoops:")?;
+            for stats in &self.loops {
+                write!(f, "  {}", stats.name)?;
+                if let Some(parent) = stats.parent {
+                    write!(f, " in {parent}")?;
+                }
+                writeln!(
+                    f,
+                    "[{}]: {:.2} iters/hit, min {} avg {} max {}, {:.0} iters/s",
+                    stats.iteration_count,
+                    stats.iterations_per_hit(),
+                    stats.tsc_min,
+                    stats.tsc_mean(),
+                    stats.tsc_max,
+                    stats.iterations_per_second(self.timer_freq),
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the memory use, run metadata and warnings which close the full report.
+    fn fmt_notes(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(memory) = self.memory {
+            writeln!(
+                f,
+                "\nMemory: {} peak RSS, {} RSS, {} virtual",
+                format_bytes(memory.peak_rss),
+                format_bytes(memory.rss),
+                format_bytes(memory.virtual_size)
+            )?;
+        }
+
+        if !self.metadata.is_empty() {
+            writeln!(f, "\nRun metadata:")?;
+            for (key, value) in &self.metadata {
+                writeln!(f, "  {key}: {value}")?;
+            }
+        }
+
+        if !self.warnings.is_empty() {
+            writeln!(f, "\nWarnings:")?;
+            for warning in &self.warnings {
+                writeln!(f, "  {warning}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AddAssign<&ProfileReport> for ProfileReport {
+    fn add_assign(&mut self, other: &ProfileReport) {
+        self.merge(other);
+    }
+}
+
+impl<'a> Sum<&'a ProfileReport> for ProfileReport {
+    fn sum<I: Iterator<Item = &'a ProfileReport>>(reports: I) -> Self {
+        reports.fold(ProfileReport::default(), |mut sum, report| {
+            sum.merge(report);
+            sum
+        })
+    }
+}
+
+impl Sum for ProfileReport {
+    fn sum<I: Iterator<Item = ProfileReport>>(reports: I) -> Self {
+        reports.fold(ProfileReport::default(), |mut sum, report| {
+            sum.merge(&report);
+            sum
+        })
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.elapsed_tsc > 0 {
+            write!(
+                f,
+                "\nTotal time: {} (timer freq {})",
+                format_duration(self.elapsed()),
+                self.timer_freq
+            )?;
+            if let Some(thread) = &self.thread {
+                write!(f, " on thread {} ({:?})", thread.name, thread.id)?;
+            }
+            writeln!(f)?;
+        }
+        if let Some(cpu) = &self.cpu {
+            writeln!(f, "CPU: {cpu}")?;
+        }
+        self.fmt_anchors(f, self.timed_anchors())?;
+
+        for interval in &self.intervals {
+            writeln!(
+                f,
+                "\nInterval {} -> {}: {}",
+                interval.from,
+                interval.to,
+                format_duration(interval.report.elapsed())
+            )?;
+            interval
+                .report
+                .fmt_anchors(f, interval.report.timed_anchors())?;
+        }
+
+        if !self.samples.is_empty() {
+            let total: u64 = self.samples.iter().map(|sample| sample.sample_count).sum();
+            writeln!(f, "\nSamples[{total}]:")?;
+            let mut table = name_table()
+                .align(1, Align::Right)
+                .align(2, Align::Right)
+                .indent(2);
+            for sample in &self.samples {
+                #[allow(clippy::cast_precision_loss)]
+                let percent = 100.0 * sample.sample_count as f64 / total as f64;
+                table.push_row([
+                    sample.name.to_string(),
+                    format!("{percent:.2}%"),
+                    sample.sample_count.to_string(),
+                ]);
+            }
+            write!(f, "{table}")?;
+        }
+
+        self.fmt_counts(f)?;
+
+        if !self.never_hit.is_empty() {
+            writeln!(f, "\nNever hit:")?;
+            for name in &self.never_hit {
+                writeln!(f, "  {name}")?;
+            }
+        }
+
+        if !self.call_stacks.is_empty() {
+            writeln!(f, "\nCall stacks:")?;
+            let mut table = name_table()
+                .headers(["Anchor", "Stack", "Hits", "Ticks"])
+                .align(2, Align::Right)
+                .align(3, Align::Right)
+                .indent(2);
+            for stats in &self.call_stacks {
+                table.push_row([
+                    stats.name.to_string(),
+                    format!("{:016x}", stats.stack_id),
+                    stats.hit_count.to_string(),
+                    stats.tsc_elapsed_inclusive.to_string(),
+                ]);
+            }
+            write!(f, "{table}")?;
+        }
+
+        if !self.backtraces.is_empty() {
+            writeln!(f, "\nFirst hits:")?;
+            for backtrace in &self.backtraces {
+                writeln!(f, "  {}:", backtrace.name)?;
+                for line in backtrace.backtrace.lines() {
+                    writeln!(f, "    {line}")?;
+                }
+            }
+        }
+
+        if !self.slow_hits.is_empty() {
+            writeln!(f, "\nSlowest hits:")?;
+            for hit in &self.slow_hits {
+                let elapsed = format_duration(self.tsc_duration(hit.tsc_elapsed));
+                writeln!(f, "  {} ({elapsed}):", hit.name)?;
+                for line in hit.backtrace.lines() {
+                    writeln!(f, "    {line}")?;
+                }
+            }
+        }
+
+        self.fmt_notes(f)
+    }
+}
+
+/// A condensed view of a [`ProfileReport`] showing only the hottest anchors. Created by
+/// [`ProfileReport::summary`].
+#[derive(Debug, Copy, Clone)]
+#[must_use]
+pub struct Summary<'a> {
+    report: &'a ProfileReport,
+    count: usize,
+}
+
+impl Summary<'_> {
+    /// Returns the top anchors by exclusive time, hottest first.
+    #[must_use]
+    pub fn anchors(&self) -> Vec<&AnchorStats> {
+        let mut anchors: Vec<&AnchorStats> = self.report.timed_anchors().collect();
+        anchors.sort_by_key(|anchor| std::cmp::Reverse(anchor.tsc_elapsed_exclusive));
+        anchors.truncate(self.count);
+        anchors
+    }
+}
+
+impl fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hit_count: u64 = self
+            .report
+            .anchors
+            .iter()
+            .map(|anchor| anchor.hit_count)
+            .sum();
+        let byte_count: u64 = self
+            .report
+            .anchors
+            .iter()
+            .map(|anchor| anchor.byte_count)
+            .sum();
+        writeln!(
+            f,
+            "Total time: {}, {} anchors, {hit_count} hits, {}",
+            format_duration(self.report.elapsed()),
+            self.report.anchors.len(),
+            format_bytes(byte_count),
+        )?;
+        self.report.fmt_anchors(f, self.anchors())
+    }
+}
+
+/// A hierarchical view of a [`ProfileReport`], nesting anchors under their callers. Created by
+/// [`ProfileReport::tree`].
+///
+/// The report only records time per caller and callee, so when an anchor is called from several
+/// places, the time of its own callees is split between them in proportion to the time each
+/// caller spent in it.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{performance::{profile_begin, profile_end}, profile};
+///
+/// profile_begin();
+/// {
+///     profile!("frame");
+///     profile!("draw");
+/// }
+/// let report = profile_end();
+/// println!("{}", report.tree());
+/// ```
+#[derive(Debug, Copy, Clone)]
+#[must_use]
+pub struct Tree<'a> {
+    report: &'a ProfileReport,
+}
+
+/// An anchor at one position of a [`Tree`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct TreeNode {
+    /// Name of the anchor.
+    pub name: &'static str,
+    /// Name of the enclosing anchor, or `None` at the top level.
+    pub parent: Option<&'static str>,
+    /// Number of enclosing anchors.
+    pub depth: usize,
+    /// Number of blocks at this position.
+    pub hit_count: u64,
+    /// Elapsed timestamp counter at this position, including children.
+    pub tsc_elapsed_inclusive: u64,
+    /// Elapsed timestamp counter of the parent at its position, or of the whole report at the top
+    /// level.
+    pub parent_tsc_elapsed_inclusive: u64,
+}
+
+impl Tree<'_> {
+    /// Returns the nodes of the tree depth-first, with the children of each node ordered by most
+    /// time first. Recursive calls aren't expanded again.
+    #[must_use]
+    pub fn nodes(&self) -> Vec<TreeNode> {
+        let report = self.report;
+        let inclusive: HashMap<&'static str, u64> = report
+            .anchors
+            .iter()
+            .map(|anchor| (anchor.name, anchor.tsc_elapsed_inclusive))
+            .collect();
+        let mut roots: Vec<TreeNode> = report
+            .anchors
+            .iter()
+            .filter_map(|anchor| {
+                // Whatever time and hits weren't spent inside another anchor were at the top level.
+                let (calls, tsc): (u64, u64) = report
+                    .calls
+                    .iter()
+                    .filter(|call| call.callee == anchor.name && call.caller != anchor.name)
+                    .fold((0, 0), |(calls, tsc), call| {
+                        (
+                            calls.saturating_add(call.call_count),
+                            tsc.saturating_add(call.tsc_elapsed_inclusive),
+                        )
+                    });
+                let tsc = anchor.tsc_elapsed_inclusive.saturating_sub(tsc);
+                (tsc > 0).then_some(TreeNode {
+                    name: anchor.name,
+                    parent: None,
+                    depth: 0,
+                    hit_count: anchor.hit_count.saturating_sub(calls),
+                    tsc_elapsed_inclusive: tsc,
+                    parent_tsc_elapsed_inclusive: report.elapsed_tsc,
+                })
+            })
+            .collect();
+        roots.sort_by_key(|node| std::cmp::Reverse(node.tsc_elapsed_inclusive));
+
+        let mut nodes = Vec::new();
+        let mut path = Vec::new();
+        for root in roots {
+            nodes.push(root);
+            path.push(root.name);
+            self.push_children(
+                &inclusive,
+                &mut nodes,
+                &mut path,
+                root.tsc_elapsed_inclusive,
+            );
+            path.pop();
+        }
+        nodes
+    }
+
+    /// Pushes the callees of the last anchor of `path`, which spent `tsc` at that position.
+    fn push_children(
+        self,
+        inclusive: &HashMap<&'static str, u64>,
+        nodes: &mut Vec<TreeNode>,
+        path: &mut Vec<&'static str>,
+        tsc: u64,
+    ) {
+        let Some(&name) = path.last() else {
+            return;
+        };
+        let total = inclusive.get(name).copied().unwrap_or(0);
+        let mut calls: Vec<&CallStats> = self
+            .report
+            .calls
+            .iter()
+            .filter(|call| call.caller == name && !path.contains(&call.callee))
+            .collect();
+        calls.sort_by_key(|call| std::cmp::Reverse(call.tsc_elapsed_inclusive));
+        for call in calls {
+            let child_tsc = if total == 0 {
+                0
+            } else {
+                let share =
+                    u128::from(call.tsc_elapsed_inclusive) * u128::from(tsc) / u128::from(total);
+                u64::try_from(share).unwrap_or(u64::MAX)
+            };
+            nodes.push(TreeNode {
+                name: call.callee,
+                parent: Some(name),
+                depth: path.len(),
+                hit_count: call.call_count,
+                tsc_elapsed_inclusive: child_tsc,
+                parent_tsc_elapsed_inclusive: tsc,
+            });
+            path.push(call.callee);
+            self.push_children(inclusive, nodes, path, child_tsc);
+            path.pop();
+        }
+    }
+}
+
+impl fmt::Display for Tree<'_> {
+    #[allow(clippy::cast_precision_loss)]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |tsc: u64, of: u64| {
+            if of == 0 {
+                0.0
+            } else {
+                100.0 * tsc as f64 / of as f64
+            }
+        };
+        writeln!(
+            f,
+            "\nCall tree: {} (timer freq {})",
+            format_duration(self.report.elapsed()),
+            self.report.timer_freq
+        )?;
+        for node in self.nodes() {
+            write!(
+                f,
+                "{:indent$}{}[{}]: {} ({:.2}%",
+                "",
+                node.name,
+                node.hit_count,
+                node.tsc_elapsed_inclusive,
+                percent(node.tsc_elapsed_inclusive, self.report.elapsed_tsc),
+                indent = 2 * (node.depth + 1),
+            )?;
+            if let Some(parent) = node.parent {
+                write!(
+                    f,
+                    ", {:.2}% of {parent}",
+                    percent(
+                        node.tsc_elapsed_inclusive,
+                        node.parent_tsc_elapsed_inclusive
+                    )
+                )?;
+            }
+            writeln!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn anchor(name: &'static str, tsc_elapsed_exclusive: u64) -> AnchorStats {
+        AnchorStats {
+            name,
+            hit_count: 1,
+            tsc_elapsed_exclusive,
+            tsc_elapsed_inclusive: tsc_elapsed_exclusive,
+            ..AnchorStats::default()
+        }
+    }
+
+    #[test]
+    fn per_unit_costs() {
+        let anchor = AnchorStats {
+            byte_count: 1000,
+            item_count: 10,
+            ..anchor("per_unit", 2000)
+        };
+        assert!((anchor.cycles_per_byte() - 2.0).abs() < f64::EPSILON);
+        assert!((anchor.cycles_per_item() - 200.0).abs() < f64::EPSILON);
+        assert!((anchor.nanoseconds_per_byte(1_000_000_000) - 2.0).abs() < f64::EPSILON);
+        assert!((anchor.nanoseconds_per_item(2_000_000_000) - 100.0).abs() < f64::EPSILON);
+        assert!(AnchorStats::default().cycles_per_byte().abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn off_cpu_time() {
+        let blocked = AnchorStats {
+            cpu_ns_exclusive: 250,
+            cpu_ns_inclusive: 250,
+            ..anchor("blocked", 2000)
+        };
+        assert_eq!(blocked.off_cpu_nanoseconds(1_000_000_000), 1750);
+        assert_eq!(
+            anchor("unmeasured", 2000).off_cpu_nanoseconds(1_000_000_000),
+            0
+        );
+
+        let report = ProfileReport {
+            elapsed_tsc: 2000,
+            timer_freq: 1_000_000_000,
+            anchors: vec![blocked],
+            ..ProfileReport::default()
+        };
+        assert!(report.to_string().contains("250ns CPU, 1.75µs off-CPU"));
+    }
+
+    #[test]
+    fn hit_variance() {
+        let steady = AnchorStats {
+            hit_count: 5,
+            tsc_elapsed_inclusive: 500,
+            tsc_elapsed_squares: 5 * 100 * 100,
+            ..anchor("steady", 500)
+        };
+        assert!(steady.tsc_std_dev().abs() < 1e-9);
+        assert!(!steady.is_unstable());
+
+        // Four hits of 10 ticks and one of 460.
+        let spiky = AnchorStats {
+            tsc_elapsed_squares: 4 * 10 * 10 + 460 * 460,
+            ..steady
+        };
+        assert!((spiky.tsc_std_dev() - 180.0).abs() < 1e-9);
+        assert!((spiky.coefficient_of_variation() - 1.8).abs() < 1e-9);
+        assert!(spiky.is_unstable());
+        assert!(!AnchorStats {
+            hit_count: 2,
+            ..spiky
+        }
+        .is_unstable());
+        assert!((spiky.scaled(2.0).coefficient_of_variation() - 1.8).abs() < 1e-9);
+
+        let report = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1000,
+            anchors: vec![
+                steady,
+                AnchorStats {
+                    name: "spiky",
+                    ..spiky
+                },
+            ],
+            ..ProfileReport::default()
+        };
+        let printed = report.to_string();
+        let line = |name| {
+            printed
+                .lines()
+                .find(|line| line.starts_with(&format!("  {name} ")))
+                .expect("valid anchor line")
+        };
+        assert!(line("spiky").contains("  500  50.00%  50.00%  unstable ±180%  min "));
+        assert!(!line("steady").contains("unstable"));
+    }
+
+    #[test]
+    fn fitted_width() {
+        let report = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1000,
+            anchors: vec![anchor(
+                "a_rather_long_anchor_name_for_a_narrow_terminal",
+                500,
+            )],
+            ..ProfileReport::default()
+        };
+        let wide = report.to_string();
+        assert!(wide.contains("a_rather_long_anchor_name_for_a_narrow_terminal "));
+        let narrow = fitted(Some(60), || report.to_string());
+        let line = narrow
+            .lines()
+            .find(|line| line.starts_with("  a_rather"))
+            .expect("valid anchor line");
+        assert!(line.contains('…') && !line.contains("narrow_terminal"));
+        assert_eq!(report.to_string(), wide);
+    }
+
+    #[test]
+    fn hardware_counter_totals() {
+        let counted = AnchorStats {
+            counters: HardwareCounters {
+                cycles: 1000,
+                instructions: 2500,
+                cache_misses: 4,
+                branch_misses: 7,
+            },
+            ..anchor("counted", 100)
+        };
+        let mut merged = counted;
+        merged.merge(&counted);
+        assert_eq!(merged.counters.cycles, 2000);
+        assert_eq!(merged.delta(&counted).counters, counted.counters);
+        assert_eq!(counted.scaled(0.5).counters.instructions, 1250);
+
+        let report = ProfileReport {
+            elapsed_tsc: 100,
+            timer_freq: 1000,
+            anchors: vec![counted],
+            ..ProfileReport::default()
+        };
+        assert!(report.to_string().contains(
+            "  1000 cycles, 2500 instructions (2.50 IPC), 4 cache misses, 7 branch misses"
+        ));
+    }
+
+    #[test]
+    fn hit_extremes() {
+        let fast = AnchorStats {
+            hit_count: 4,
+            tsc_elapsed_inclusive: 400,
+            tsc_min: 50,
+            tsc_max: 200,
+            ..anchor("extremes", 400)
+        };
+        assert_eq!(fast.tsc_mean(), 100);
+        assert_eq!(AnchorStats::default().tsc_mean(), 0);
+
+        let mut merged = AnchorStats {
+            hit_count: 0,
+            tsc_min: 0,
+            tsc_max: 0,
+            ..fast
+        };
+        merged.merge(&fast);
+        assert_eq!((merged.tsc_min, merged.tsc_max), (50, 200));
+        merged.merge(&AnchorStats {
+            tsc_min: 20,
+            tsc_max: 100,
+            ..fast
+        });
+        assert_eq!((merged.tsc_min, merged.tsc_max), (20, 200));
+        merged.merge(&AnchorStats::default());
+        assert_eq!((merged.tsc_min, merged.tsc_max), (20, 200));
+
+        let report = ProfileReport {
+            elapsed_tsc: 400,
+            timer_freq: 1000,
+            anchors: vec![fast],
+            ..ProfileReport::default()
+        };
+        assert!(report.to_string().contains("  min 50 avg 100 max 200"));
+    }
+
+    #[test]
+    fn summary_top_anchors() {
+        let report = ProfileReport {
+            elapsed_tsc: 100,
+            timer_freq: 1000,
+            anchors: vec![anchor("a", 10), anchor("b", 50), anchor("c", 40)],
+            ..ProfileReport::default()
+        };
+        let summary = report.summary(2);
+        let names: Vec<_> = summary.anchors().iter().map(|anchor| anchor.name).collect();
+        assert_eq!(names, ["b", "c"]);
+
+        let output = summary.to_string();
+        assert!(output.starts_with("Total time: 100ms, 3 anchors, 3 hits, 0 B\n"));
+        assert_eq!(output.lines().count(), 4);
+        assert!(output.contains(
+            "\n  Anchor  Hits  Ticks    Self   Total\n  b          1     50  50.00%  50.00%\n"
+        ));
+    }
+
+    #[test]
+    fn call_tree() {
+        let inclusive = |name, hit_count, tsc_elapsed_inclusive| AnchorStats {
+            name,
+            hit_count,
+            tsc_elapsed_inclusive,
+            ..AnchorStats::default()
+        };
+        let call = |caller, callee, call_count, tsc_elapsed_inclusive| CallStats {
+            caller,
+            callee,
+            call_count,
+            tsc_elapsed_inclusive,
+        };
+        let report = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1000,
+            anchors: vec![
+                inclusive("main", 1, 800),
+                inclusive("parse", 3, 600),
+                inclusive("lex", 4, 300),
+                inclusive("render", 1, 100),
+                inclusive("walk", 2, 50),
+            ],
+            calls: vec![
+                call("main", "parse", 2, 400),
+                call("main", "render", 1, 100),
+                call("parse", "lex", 4, 300),
+                call("walk", "walk", 1, 20),
+                call("lex", "parse", 1, 10),
+            ],
+            ..ProfileReport::default()
+        };
+
+        let nodes: Vec<_> = report
+            .tree()
+            .nodes()
+            .iter()
+            .map(|node| {
+                (
+                    node.depth,
+                    node.name,
+                    node.hit_count,
+                    node.tsc_elapsed_inclusive,
+                )
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            [
+                (0, "main", 1, 800),
+                (1, "parse", 2, 400),
+                (2, "lex", 4, 200),
+                (1, "render", 1, 100),
+                (0, "parse", 0, 190),
+                (1, "lex", 4, 95),
+                (0, "walk", 2, 50),
+            ]
+        );
+
+        let output = report.tree().to_string();
+        assert!(output.starts_with("\nCall tree: 1.00s (timer freq 1000)\n  main[1]: 800"));
+        assert!(output.contains("\n    parse[2]: 400 (40.00%, 50.00% of main)\n"));
+        assert!(output.contains("\n      lex[4]: 200 (20.00%, 50.00% of parse)\n"));
+    }
+
+    #[test]
+    fn report_options() {
+        let stats = |name, hit_count, tsc_elapsed_exclusive, tsc_elapsed_inclusive| AnchorStats {
+            name,
+            hit_count,
+            tsc_elapsed_exclusive,
+            tsc_elapsed_inclusive,
+            ..AnchorStats::default()
+        };
+        let report = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1000,
+            anchors: vec![
+                stats("main", 1, 100, 1000),
+                stats("parse", 50, 600, 700),
+                stats("lex", 500, 100, 100),
+                stats("idle", 2, 5, 5),
+            ],
+            ..ProfileReport::default()
+        };
+        let names = |options: ReportOptions| {
+            report
+                .with_options(&options)
+                .anchors
+                .iter()
+                .map(|anchor| anchor.name)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(ReportOptions::new()),
+            ["main", "parse", "lex", "idle"]
+        );
+        assert_eq!(
+            names(ReportOptions::new().sort_by(SortOrder::Exclusive)),
+            ["parse", "main", "lex", "idle"]
+        );
+        assert_eq!(
+            names(ReportOptions::new().sort_by(SortOrder::Inclusive)),
+            ["main", "parse", "lex", "idle"]
+        );
+        assert_eq!(
+            names(ReportOptions::new().sort_by(SortOrder::Hits).top(2)),
+            ["lex", "parse"]
+        );
+        assert_eq!(
+            names(
+                ReportOptions::new()
+                    .sort_by(SortOrder::Name)
+                    .min_percent(1.0)
+            ),
+            ["lex", "main", "parse"]
+        );
+        assert!(names(ReportOptions::new().top(0)).is_empty());
+    }
+
+    #[test]
+    fn combine_reports() {
+        let run = |tsc, arm| ProfileReport {
+            elapsed_tsc: 100,
+            timer_freq: 1000,
+            anchors: vec![anchor("parse", tsc)],
+            branches: vec![BranchStats {
+                name: "kind",
+                arms: vec![BranchArm {
+                    name: arm,
+                    hit_count: 2,
+                }],
+            }],
+            loops: vec![LoopStats {
+                name: "tokens",
+                parent: Some("parse"),
+                iteration_count: 4,
+                parent_hit_count: 1,
+                tsc_min: tsc / 8,
+                tsc_max: tsc / 2,
+                tsc_total: tsc,
+            }],
+            never_hit: vec!["emit"],
+            ..ProfileReport::default()
+        };
+        let runs = [run(40, "a"), run(80, "b")];
+
+        let total: ProfileReport = runs.iter().sum();
+        assert_eq!(total.elapsed_tsc, 200);
+        assert_eq!(
+            total.anchors,
+            [AnchorStats {
+                hit_count: 2,
+                ..anchor("parse", 120)
+            }]
+        );
+        assert_eq!(total.branches[0].hit_count(), 4);
+        assert_eq!(total.branches[0].arms.len(), 2);
+        assert_eq!(total.loops[0].iteration_count, 8);
+        assert_eq!((total.loops[0].tsc_min, total.loops[0].tsc_max), (5, 40));
+        assert_eq!(total.never_hit, ["emit"]);
+
+        let mut slower = runs[0].clone();
+        slower += &ProfileReport {
+            timer_freq: 2000,
+            anchors: vec![anchor("parse", 80), anchor("emit", 20)],
+            ..ProfileReport::default()
+        };
+        assert_eq!(slower.anchors[0].tsc_elapsed_exclusive, 80);
+        assert_eq!(slower.anchors[1].tsc_elapsed_exclusive, 10);
+        assert!(slower.never_hit.is_empty());
+
+        let per_run = total.scaled(0.5);
+        assert_eq!(per_run.elapsed_tsc, 100);
+        assert_eq!(per_run.anchors, [anchor("parse", 60)]);
+        assert_eq!(per_run.branches[0].hit_count(), 2);
+        assert_eq!(per_run.loops[0].iteration_count, 4);
+        assert_eq!(per_run.loops[0].tsc_total, 60);
+        assert_eq!(per_run.loops[0].tsc_max, 40);
+    }
+
+    #[test]
+    fn system_info() {
+        let usage = |rss, peak_rss| MemoryUsage {
+            rss,
+            peak_rss,
+            virtual_size: 4 << 30,
+        };
+        let mut report = ProfileReport {
+            memory: Some(usage(12 << 20, 16 << 20)),
+            ..ProfileReport::default()
+        };
+        assert!(report
+            .to_string()
+            .ends_with("\nMemory: 16.0 MiB peak RSS, 12.0 MiB RSS, 4.0 GiB virtual\n"));
+        report.merge(&ProfileReport {
+            memory: Some(usage(20 << 20, 24 << 20)),
+            ..ProfileReport::default()
+        });
+        assert_eq!(report.memory, Some(usage(20 << 20, 24 << 20)));
+        assert!(!ProfileReport::default().to_string().contains("Memory"));
+
+        let cpu = CpuInfo {
+            model: Some("Test CPU".to_string()),
+            physical_cores: 2,
+            logical_cores: 4,
+            ..CpuInfo::default()
+        };
+        report.merge(&ProfileReport {
+            cpu: Some(cpu.clone()),
+            ..ProfileReport::default()
+        });
+        assert_eq!(report.cpu.as_ref(), Some(&cpu));
+        assert!(report
+            .to_string()
+            .starts_with("CPU: Test CPU, 2 cores, 4 threads\n"));
+    }
+}
@@ -0,0 +1,208 @@
+//! Repetition testing of small routines.
+//!
+//! A single timing of a routine includes cache misses, page faults and interrupts which say little
+//! about how fast the routine itself can run. A [`RepetitionTester`] runs a routine over and over
+//! until it goes a whole time window without finding a new fastest run, restarting the window each
+//! time one is found, so the minimum converges on the routine's best-case time and throughput.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Runs a routine repeatedly to find its fastest time.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::performance::reptest::RepetitionTester;
+///
+/// let data = vec![1u8; 4096];
+/// let results = RepetitionTester::new(Duration::from_millis(10))
+///     .bytes(data.len() as u64)
+///     .run(|| {
+///         std::hint::black_box(data.iter().map(|&b| u64::from(b)).sum::<u64>());
+///     });
+/// println!("{results}");
+/// assert!(results.min <= results.max);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct RepetitionTester {
+    window: Duration,
+    byte_count: u64,
+}
+
+impl RepetitionTester {
+    /// Creates a tester which stops once `window` passes without a new fastest run.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            byte_count: 0,
+        }
+    }
+
+    /// Sets the number of bytes the routine processes per run, to report its bandwidth.
+    pub fn bytes(mut self, byte_count: u64) -> Self {
+        self.byte_count = byte_count;
+        self
+    }
+
+    /// Runs `routine` until the window passes without a new fastest run, returning the times of
+    /// every run. The routine always runs at least once.
+    pub fn run(&self, mut routine: impl FnMut()) -> RepetitionResults {
+        let mut results = RepetitionResults {
+            byte_count: self.byte_count,
+            min: Duration::MAX,
+            ..RepetitionResults::default()
+        };
+        let mut window_start = Instant::now();
+        loop {
+            let start = Instant::now();
+            routine();
+            let elapsed = start.elapsed();
+
+            results.repetitions += 1;
+            results.total += elapsed;
+            results.squares += elapsed.as_nanos().pow(2);
+            results.max = results.max.max(elapsed);
+            if elapsed < results.min {
+                results.min = elapsed;
+                window_start = Instant::now();
+            } else if window_start.elapsed() >= self.window {
+                break;
+            }
+        }
+        results
+    }
+}
+
+/// Times of every run of a [`RepetitionTester`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct RepetitionResults {
+    /// Number of times the routine ran.
+    pub repetitions: u64,
+    /// Fastest run.
+    pub min: Duration,
+    /// Slowest run.
+    pub max: Duration,
+    /// Total time of every run.
+    pub total: Duration,
+    /// Sum of the squared nanoseconds of every run, for the variation between runs.
+    pub squares: u128,
+    /// Bytes processed per run.
+    pub byte_count: u64,
+}
+
+impl RepetitionResults {
+    /// Average time of a run.
+    #[must_use]
+    pub fn mean(&self) -> Duration {
+        let repetitions = u32::try_from(self.repetitions).unwrap_or(u32::MAX);
+        self.total.checked_div(repetitions).unwrap_or_default()
+    }
+
+    /// Sample standard deviation of the time of a run, or zero with fewer than two runs.
+    #[must_use]
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn std_dev(&self) -> Duration {
+        if self.repetitions < 2 {
+            return Duration::ZERO;
+        }
+        let runs = self.repetitions as f64;
+        let mean = self.total.as_nanos() as f64 / runs;
+        let variance = (self.squares as f64 - runs * mean * mean) / (runs - 1.0);
+        Duration::from_nanos(variance.max(0.0).sqrt() as u64)
+    }
+
+    /// Bytes processed per second by a run taking `elapsed`, or `0.0` if no bytes were set.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn bandwidth(&self, elapsed: Duration) -> f64 {
+        if self.byte_count == 0 || elapsed.is_zero() {
+            0.0
+        } else {
+            self.byte_count as f64 / elapsed.as_secs_f64()
+        }
+    }
+
+    /// Bytes processed per second by the fastest run.
+    #[must_use]
+    pub fn max_bandwidth(&self) -> f64 {
+        self.bandwidth(self.min)
+    }
+
+    fn fmt_time(&self, f: &mut fmt::Formatter<'_>, label: &str, elapsed: Duration) -> fmt::Result {
+        const GB: f64 = 1024.0 * 1024.0 * 1024.0;
+
+        write!(f, "  {label}: {:.4}ms", 1000.0 * elapsed.as_secs_f64())?;
+        if self.byte_count > 0 {
+            write!(f, " {:.3}GB/s", self.bandwidth(elapsed) / GB)?;
+        }
+        writeln!(f)
+    }
+}
+
+impl fmt::Display for RepetitionResults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "\nRepetitions: {}", self.repetitions)?;
+        self.fmt_time(f, "Min", self.min)?;
+        self.fmt_time(f, "Max", self.max)?;
+        self.fmt_time(f, "Avg", self.mean())?;
+        writeln!(
+            f,
+            "  Std dev: {:.4}ms",
+            1000.0 * self.std_dev().as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repetition_test() {
+        let window = Duration::from_millis(10);
+        let mut runs = 0u64;
+        let start = Instant::now();
+        let results = RepetitionTester::new(window).bytes(1 << 20).run(|| {
+            runs += 1;
+            let spin = Instant::now();
+            // The first run is slowest, so later runs can't set a new minimum for long.
+            let duration = Duration::from_micros(if runs == 1 { 500 } else { 50 });
+            while spin.elapsed() < duration {
+                std::hint::black_box(0);
+            }
+        });
+
+        assert!(start.elapsed() >= window);
+        assert_eq!(results.repetitions, runs);
+        assert!(results.repetitions > 1);
+        assert!(results.min >= Duration::from_micros(50));
+        assert!(results.max >= Duration::from_micros(500));
+        assert!(results.min <= results.mean() && results.mean() <= results.max);
+        assert!(results.max_bandwidth() >= results.bandwidth(results.max));
+
+        let printed = results.to_string();
+        assert!(printed.contains(&format!("Repetitions: {runs}")));
+        assert!(printed.contains("GB/s"));
+        assert!(printed.contains("Std dev: "));
+        let fixed = RepetitionResults {
+            // Runs of 4ns, 10ns and 16ns.
+            repetitions: 3,
+            total: Duration::from_nanos(30),
+            squares: 4 * 4 + 10 * 10 + 16 * 16,
+            ..RepetitionResults::default()
+        };
+        assert_eq!(fixed.std_dev(), Duration::from_nanos(6));
+        assert!(results.std_dev() > Duration::ZERO);
+        assert_eq!(RepetitionResults::default().mean(), Duration::ZERO);
+    }
+}
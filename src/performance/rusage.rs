@@ -0,0 +1,154 @@
+//! Page fault and context switch counts read with `getrusage` or `GetProcessMemoryInfo`.
+
+/// Page faults and context switches counted by the operating system.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub(super) struct ResourceUsage {
+    /// Faults served without I/O, such as mapping a page already in the page cache.
+    pub(super) soft_faults: u64,
+    /// Faults which read the page from disk.
+    pub(super) hard_faults: u64,
+    /// Switches away from the thread because it blocked, such as on I/O or a lock.
+    pub(super) voluntary_switches: u64,
+    /// Switches away from the thread because the scheduler preempted it.
+    pub(super) involuntary_switches: u64,
+}
+
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+impl ResourceUsage {
+    /// Returns the counts between `earlier` and `self`.
+    pub(super) fn since(self, earlier: Self) -> Self {
+        Self {
+            soft_faults: self.soft_faults.wrapping_sub(earlier.soft_faults),
+            hard_faults: self.hard_faults.wrapping_sub(earlier.hard_faults),
+            voluntary_switches: self
+                .voluntary_switches
+                .wrapping_sub(earlier.voluntary_switches),
+            involuntary_switches: self
+                .involuntary_switches
+                .wrapping_sub(earlier.involuntary_switches),
+        }
+    }
+}
+
+/// Returns the page faults and context switches of the calling thread on Linux, of the whole
+/// process on macOS and Windows, or `None` elsewhere or if they can't be read. Windows doesn't
+/// tell hard faults apart, so counts every fault as soft, and doesn't count context switches.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn resource_usage() -> Option<ResourceUsage> {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    {
+        use std::os::raw::{c_int, c_long};
+
+        #[cfg(target_os = "linux")]
+        const RUSAGE_THREAD: c_int = 1;
+        #[cfg(target_os = "macos")]
+        const RUSAGE_SELF: c_int = 0;
+
+        #[repr(C)]
+        struct Rusage {
+            /// User and system CPU time, as two `struct timeval`s.
+            times: [c_long; 4],
+            maxrss: c_long,
+            ixrss: c_long,
+            idrss: c_long,
+            isrss: c_long,
+            minflt: c_long,
+            majflt: c_long,
+            /// Swaps, block I/O operations, IPC messages and signals, which aren't used.
+            unused: [c_long; 6],
+            nvcsw: c_long,
+            nivcsw: c_long,
+        }
+
+        extern "C" {
+            fn getrusage(who: c_int, usage: *mut Rusage) -> c_int;
+        }
+
+        #[cfg(target_os = "linux")]
+        let who = RUSAGE_THREAD;
+        #[cfg(target_os = "macos")]
+        let who = RUSAGE_SELF;
+        let mut usage = std::mem::MaybeUninit::<Rusage>::zeroed();
+        // SAFETY: `usage` is a valid, zeroed `struct rusage` for the duration of the call.
+        if unsafe { getrusage(who, usage.as_mut_ptr()) } != 0 {
+            return None;
+        }
+        // SAFETY: Zeroed memory is a valid `Rusage`, and `getrusage` succeeded.
+        let usage = unsafe { usage.assume_init() };
+        Some(ResourceUsage {
+            soft_faults: u64::try_from(usage.minflt).unwrap_or(0),
+            hard_faults: u64::try_from(usage.majflt).unwrap_or(0),
+            voluntary_switches: u64::try_from(usage.nvcsw).unwrap_or(0),
+            involuntary_switches: u64::try_from(usage.nivcsw).unwrap_or(0),
+        })
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::ffi::c_void;
+
+        #[repr(C)]
+        struct ProcessMemoryCounters {
+            cb: u32,
+            page_fault_count: u32,
+            /// The remaining fields, which aren't used.
+            sizes: [usize; 8],
+        }
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentProcess() -> *mut c_void;
+            fn K32GetProcessMemoryInfo(
+                process: *mut c_void,
+                counters: *mut ProcessMemoryCounters,
+                size: u32,
+            ) -> i32;
+        }
+
+        let size = u32::try_from(std::mem::size_of::<ProcessMemoryCounters>()).ok()?;
+        let mut counters = ProcessMemoryCounters {
+            cb: size,
+            page_fault_count: 0,
+            sizes: [0; 8],
+        };
+        // SAFETY: `counters` is a valid `PROCESS_MEMORY_COUNTERS` of `size` bytes for the duration
+        // of the call, and the current process handle needs no closing.
+        if unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &raw mut counters, size) } == 0 {
+            return None;
+        }
+        Some(ResourceUsage {
+            soft_faults: u64::from(counters.page_fault_count),
+            ..ResourceUsage::default()
+        })
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    None
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soft_page_faults() {
+        let start = resource_usage().expect("valid resource usage");
+        // Touching every page of a fresh allocation faults each one in.
+        let mut pages = vec![0u8; 4096 * 4096];
+        for page in pages.chunks_mut(4096) {
+            page[0] = 1;
+        }
+        std::hint::black_box(&pages);
+        let usage = resource_usage().expect("valid resource usage").since(start);
+        assert!(usage.soft_faults >= 1024);
+    }
+
+    #[test]
+    fn voluntary_context_switches() {
+        let start = resource_usage().expect("valid resource usage");
+        // Sleeping blocks the thread, which switches away from it.
+        for _ in 0..4 {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        let usage = resource_usage().expect("valid resource usage").since(start);
+        assert!(usage.voluntary_switches >= 4);
+    }
+}
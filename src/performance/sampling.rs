@@ -0,0 +1,195 @@
+//! Timer-based anchor sampling.
+//!
+//! Instrumented blocks only account for code which happens to contain a `profile!` call. A
+//! [`Sampler`] complements them by waking at a fixed rate and recording which anchor is innermost
+//! on every profiled thread, or [`UNINSTRUMENTED`] if none is, so time spent outside any block
+//! still shows up in [`ProfileReport::samples`](super::ProfileReport::samples).
+//!
+//! Samples are taken on wall-clock time, so threads blocked inside a block are counted too.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Mutex, PoisonError, Weak,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// Name of the samples taken while a thread had no active profile block.
+pub const UNINSTRUMENTED: &str = "<uninstrumented>";
+
+/// Number of running samplers.
+static ACTIVE_SAMPLERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Sample slots of every profiled thread which has been sampled.
+static SLOTS: Mutex<Vec<Weak<SampleSlot>>> = Mutex::new(Vec::new());
+
+/// A profiled thread's current anchor and sample counts, shared with the sampler thread.
+#[derive(Debug, Default)]
+pub(super) struct SampleSlot {
+    /// Index of the innermost active anchor plus one, or zero outside any block.
+    current: AtomicUsize,
+    /// Number of samples, indexed like `current`.
+    counts: Mutex<Vec<u64>>,
+}
+
+impl SampleSlot {
+    /// Creates a slot for the calling thread and registers it with the samplers.
+    #[cfg_attr(not(feature = "perf"), allow(dead_code))]
+    pub(super) fn register() -> Arc<Self> {
+        let slot = Arc::new(Self::default());
+        SLOTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::downgrade(&slot));
+        slot
+    }
+
+    /// Records that the anchor at index `anchor` is now innermost, or no anchor if `None`.
+    #[inline]
+    #[cfg_attr(not(feature = "perf"), allow(dead_code))]
+    pub(super) fn enter(&self, anchor: Option<usize>) {
+        self.current
+            .store(anchor.map_or(0, |anchor| anchor + 1), Ordering::Relaxed);
+    }
+
+    /// Discards all samples taken so far.
+    #[cfg_attr(not(feature = "perf"), allow(dead_code))]
+    pub(super) fn reset(&self) {
+        self.counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Returns the samples taken so far, as pairs of anchor index, or `None` outside any block, and
+    /// sample count.
+    #[cfg_attr(not(feature = "perf"), allow(dead_code))]
+    pub(super) fn counts(&self) -> Vec<(Option<usize>, u64)> {
+        self.counts
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(index, &count)| (index.checked_sub(1), count))
+            .collect()
+    }
+
+    fn sample(&self) {
+        let current = self.current.load(Ordering::Relaxed);
+        let mut counts = self.counts.lock().unwrap_or_else(PoisonError::into_inner);
+        if counts.len() <= current {
+            counts.resize(current + 1, 0);
+        }
+        counts[current] += 1;
+    }
+}
+
+/// Returns whether any sampler is running.
+#[inline]
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn is_active() -> bool {
+    ACTIVE_SAMPLERS.load(Ordering::Relaxed) > 0
+}
+
+/// A running sampler, stopped when dropped.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_print, sampling::Sampler};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let sampler = Sampler::start(1000)?;
+/// profile_begin();
+/// // uninstrumented work...
+/// profile_end_and_print();
+/// drop(sampler);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct Sampler {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Sampler {
+    /// Starts sampling every profiled thread `hz` times per second.
+    ///
+    /// Threads are sampled from their next `profile_begin` or profile block onwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `hz` is zero or the sampler thread can't be spawned.
+    pub fn start(hz: u32) -> io::Result<Self> {
+        if hz == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sampling rate must be at least 1 Hz",
+            ));
+        }
+        let period = Duration::from_secs(1) / hz;
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-sampler".to_string())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(period) {
+                    sample_all();
+                }
+            })?;
+        ACTIVE_SAMPLERS.fetch_add(1, Ordering::Relaxed);
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        ACTIVE_SAMPLERS.fetch_sub(1, Ordering::Relaxed);
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Takes one sample of every live profiled thread, forgetting threads which have exited.
+fn sample_all() {
+    let mut slots = SLOTS.lock().unwrap_or_else(PoisonError::into_inner);
+    slots.retain(|slot| match slot.upgrade() {
+        Some(slot) => {
+            slot.sample();
+            true
+        }
+        None => false,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_slots() {
+        let slot = SampleSlot::default();
+        slot.sample();
+        slot.enter(Some(2));
+        slot.sample();
+        slot.sample();
+        slot.enter(None);
+        slot.sample();
+        assert_eq!(slot.counts(), [(None, 2), (Some(2), 2)]);
+        slot.reset();
+        assert!(slot.counts().is_empty());
+
+        assert!(Sampler::start(0).is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Scheduler latency measurement.
+//!
+//! Every timing includes some operating system jitter: a sleeping thread wakes up late, and a
+//! running thread can be preempted by other work. [`SchedulerLatency::measure`] estimates both on
+//! the current system, and [`SchedulerLatency::record`] adds the results to the metadata of every
+//! report, so readers know how much noise to expect in the numbers.
+
+use std::{
+    fmt,
+    time::{Duration, Instant},
+};
+
+/// Sleep requested for each wake-up measurement.
+const WAKEUP_SLEEP: Duration = Duration::from_micros(100);
+
+/// Shortest gap between consecutive clock reads while spinning which counts as a preemption.
+const PREEMPTION_THRESHOLD: Duration = Duration::from_micros(20);
+
+/// Wake-up latency and preemption measured on the current system.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::performance::scheduler::SchedulerLatency;
+///
+/// let latency = SchedulerLatency::measure(10, Duration::from_millis(20));
+/// latency.record();
+/// println!("{latency}");
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct SchedulerLatency {
+    /// Shortest time a short sleep overran by.
+    pub wakeup_min: Duration,
+    /// Median time a short sleep overran by.
+    pub wakeup_median: Duration,
+    /// Longest time a short sleep overran by.
+    pub wakeup_max: Duration,
+    /// How long the thread spun to detect preemption.
+    pub spin: Duration,
+    /// Number of times the spinning thread was preempted.
+    pub preemptions: u64,
+    /// Total time the spinning thread spent preempted.
+    pub preempted: Duration,
+    /// Longest single preemption.
+    pub preemption_max: Duration,
+}
+
+impl SchedulerLatency {
+    /// Measures wake-up latency over `wakeups` short sleeps, then spins for `spin` to detect
+    /// preemption. Runs on the calling thread, which blocks for about `spin` plus a millisecond
+    /// per 10 wake-ups.
+    pub fn measure(wakeups: usize, spin: Duration) -> Self {
+        let mut latencies: Vec<Duration> = (0..wakeups.max(1))
+            .map(|_| {
+                let start = Instant::now();
+                std::thread::sleep(WAKEUP_SLEEP);
+                start.elapsed().saturating_sub(WAKEUP_SLEEP)
+            })
+            .collect();
+        latencies.sort_unstable();
+
+        let mut latency = Self {
+            wakeup_min: latencies[0],
+            wakeup_median: latencies[latencies.len() / 2],
+            wakeup_max: latencies[latencies.len() - 1],
+            spin,
+            ..Self::default()
+        };
+        let start = Instant::now();
+        let mut last = start;
+        while last - start < spin {
+            let now = Instant::now();
+            let gap = now - last;
+            if gap >= PREEMPTION_THRESHOLD {
+                latency.preemptions += 1;
+                latency.preempted += gap;
+                latency.preemption_max = latency.preemption_max.max(gap);
+            }
+            last = now;
+        }
+        latency
+    }
+
+    /// Fraction of the spin spent preempted.
+    #[must_use]
+    pub fn preempted_ratio(&self) -> f64 {
+        if self.spin.is_zero() {
+            0.0
+        } else {
+            self.preempted.as_secs_f64() / self.spin.as_secs_f64()
+        }
+    }
+
+    /// Adds these measurements to the metadata of every report.
+    pub fn record(&self) {
+        super::set_run_metadata(
+            "scheduler.wakeup_latency",
+            format!(
+                "min {:?}, median {:?}, max {:?}",
+                self.wakeup_min, self.wakeup_median, self.wakeup_max
+            ),
+        );
+        super::set_run_metadata(
+            "scheduler.preemption",
+            format!(
+                "{} preemptions, {:.2}% of {:?}, max {:?}",
+                self.preemptions,
+                100.0 * self.preempted_ratio(),
+                self.spin,
+                self.preemption_max
+            ),
+        );
+    }
+}
+
+impl fmt::Display for SchedulerLatency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Wake-up latency: min {:?}, median {:?}, max {:?}",
+            self.wakeup_min, self.wakeup_median, self.wakeup_max
+        )?;
+        writeln!(
+            f,
+            "Preemption: {} times over {:?}, {:.2}% of the time, max {:?}",
+            self.preemptions,
+            self.spin,
+            100.0 * self.preempted_ratio(),
+            self.preemption_max
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scheduler_latency() {
+        let latency = SchedulerLatency::measure(5, Duration::from_millis(10));
+        assert!(latency.wakeup_min <= latency.wakeup_median);
+        assert!(latency.wakeup_median <= latency.wakeup_max);
+        assert!(latency.preempted <= latency.spin + latency.preemption_max);
+        assert!(latency.preempted_ratio() >= 0.0);
+        assert!(latency.to_string().starts_with("Wake-up latency: min "));
+    }
+}
@@ -0,0 +1,350 @@
+//! Anchors shared by every thread.
+//!
+//! Blocks started with `profile!` record into their thread's profiler, so the same anchor hit on
+//! several threads only adds up once the thread reports are merged. Blocks started with
+//! [`profile_shared!`](crate::profile_shared) instead add to one process-wide table of
+//! [`MAX_SHARED_ANCHORS`] anchors with relaxed atomic adds, so [`shared_report`] can read the
+//! totals of every thread at any time without locks, publishing or merging.
+//!
+//! Shared blocks only know of other shared blocks: exclusive time subtracts the shared blocks
+//! nested in them on the same thread, and blocks started with `profile!` don't see them as
+//! children.
+
+use super::ProfileReport;
+#[cfg(feature = "perf")]
+use super::{AnchorStats, Profiler};
+#[cfg(feature = "perf")]
+use std::{
+    cell::Cell,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+/// Number of shared anchors. Call sites beyond this record nothing, and the report carries a
+/// warning.
+pub const MAX_SHARED_ANCHORS: usize = 256;
+
+/// The totals of one shared anchor, added to by every thread.
+#[cfg(feature = "perf")]
+struct SharedAnchor {
+    name: OnceLock<&'static str>,
+    hit_count: AtomicU64,
+    byte_count: AtomicU64,
+    tsc_elapsed_exclusive: AtomicU64,
+    tsc_elapsed_inclusive: AtomicU64,
+    /// Low and high halves of the sum of squared inclusive ticks of each hit.
+    tsc_elapsed_squares: [AtomicU64; 2],
+    tsc_min: AtomicU64,
+    tsc_max: AtomicU64,
+}
+
+#[cfg(feature = "perf")]
+impl SharedAnchor {
+    const fn new() -> Self {
+        Self {
+            name: OnceLock::new(),
+            hit_count: AtomicU64::new(0),
+            byte_count: AtomicU64::new(0),
+            tsc_elapsed_exclusive: AtomicU64::new(0),
+            tsc_elapsed_inclusive: AtomicU64::new(0),
+            tsc_elapsed_squares: [AtomicU64::new(0), AtomicU64::new(0)],
+            tsc_min: AtomicU64::new(u64::MAX),
+            tsc_max: AtomicU64::new(0),
+        }
+    }
+
+    /// Adds one hit which took `elapsed` ticks, `children` of them in nested shared blocks.
+    #[inline]
+    fn record(&self, elapsed: u64, children: u64, byte_count: u64, outermost: bool) {
+        self.hit_count.fetch_add(1, Ordering::Relaxed);
+        self.byte_count.fetch_add(byte_count, Ordering::Relaxed);
+        self.tsc_elapsed_exclusive
+            .fetch_add(elapsed.saturating_sub(children), Ordering::Relaxed);
+        if outermost {
+            self.tsc_elapsed_inclusive
+                .fetch_add(elapsed, Ordering::Relaxed);
+        }
+        self.tsc_min.fetch_min(elapsed, Ordering::Relaxed);
+        self.tsc_max.fetch_max(elapsed, Ordering::Relaxed);
+        // The carry out of the low half is exact, as each add sees the sum of the adds before it.
+        let square = u128::from(elapsed) * u128::from(elapsed);
+        #[allow(clippy::cast_possible_truncation)]
+        let (low, high) = (square as u64, (square >> 64) as u64);
+        let previous = self.tsc_elapsed_squares[0].fetch_add(low, Ordering::Relaxed);
+        let carry = u64::from(previous.checked_add(low).is_none());
+        if high + carry > 0 {
+            self.tsc_elapsed_squares[1].fetch_add(high + carry, Ordering::Relaxed);
+        }
+    }
+
+    fn stats(&self, name: &'static str) -> AnchorStats {
+        let hit_count = self.hit_count.load(Ordering::Relaxed);
+        let squares = [0, 1].map(|half| self.tsc_elapsed_squares[half].load(Ordering::Relaxed));
+        AnchorStats {
+            name,
+            hit_count,
+            byte_count: self.byte_count.load(Ordering::Relaxed),
+            tsc_elapsed_exclusive: self.tsc_elapsed_exclusive.load(Ordering::Relaxed),
+            tsc_elapsed_inclusive: self.tsc_elapsed_inclusive.load(Ordering::Relaxed),
+            tsc_elapsed_squares: u128::from(squares[1]) << 64 | u128::from(squares[0]),
+            tsc_min: if hit_count == 0 {
+                0
+            } else {
+                self.tsc_min.load(Ordering::Relaxed)
+            },
+            tsc_max: self.tsc_max.load(Ordering::Relaxed),
+            ..AnchorStats::default()
+        }
+    }
+
+    fn reset(&self) {
+        for count in [
+            &self.hit_count,
+            &self.byte_count,
+            &self.tsc_elapsed_exclusive,
+            &self.tsc_elapsed_inclusive,
+            &self.tsc_elapsed_squares[0],
+            &self.tsc_elapsed_squares[1],
+            &self.tsc_max,
+        ] {
+            count.store(0, Ordering::Relaxed);
+        }
+        self.tsc_min.store(u64::MAX, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "perf")]
+static ANCHORS: [SharedAnchor; MAX_SHARED_ANCHORS] =
+    [const { SharedAnchor::new() }; MAX_SHARED_ANCHORS];
+/// Number of shared anchors assigned to call sites.
+#[cfg(feature = "perf")]
+static ANCHOR_COUNT: AtomicUsize = AtomicUsize::new(0);
+/// Whether a call site found every shared anchor taken.
+#[cfg(feature = "perf")]
+static OVERFLOWED: AtomicBool = AtomicBool::new(false);
+/// Block timer reading when shared measurement started, or `0` before the first block.
+#[cfg(feature = "perf")]
+static START_TSC: AtomicU64 = AtomicU64::new(0);
+
+#[cfg(feature = "perf")]
+thread_local! {
+    /// Ticks spent in shared blocks nested in the current thread's innermost shared block.
+    static CHILDREN_TSC: Cell<u64> = const { Cell::new(0) };
+    /// Number of active shared blocks of each anchor on the current thread.
+    static DEPTHS: [Cell<u32>; MAX_SHARED_ANCHORS] =
+        const { [const { Cell::new(0) }; MAX_SHARED_ANCHORS] };
+}
+
+/// The call site of a `profile_shared!` block, created as a static by the macro, which is assigned
+/// a shared anchor on its first hit.
+#[cfg(feature = "perf")]
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct SharedSite(AtomicUsize);
+
+#[cfg(feature = "perf")]
+impl SharedSite {
+    /// Marks a site which found every shared anchor taken.
+    const OVERFLOW: usize = usize::MAX;
+
+    #[must_use]
+    pub const fn new() -> Self {
+        Self(AtomicUsize::new(0))
+    }
+
+    /// Returns the index of this site's shared anchor, or `None` if there were none left.
+    #[inline]
+    fn anchor(&self, name: &'static str) -> Option<usize> {
+        let id = self.0.load(Ordering::Relaxed);
+        if id != 0 {
+            return (id != Self::OVERFLOW).then(|| id - 1);
+        }
+        let index = ANCHOR_COUNT.fetch_add(1, Ordering::Relaxed);
+        let new_id = if index < MAX_SHARED_ANCHORS {
+            ANCHORS[index].name.get_or_init(|| name);
+            let now = Profiler::read_block_timer();
+            let _ = START_TSC.compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+            index + 1
+        } else {
+            OVERFLOWED.store(true, Ordering::Relaxed);
+            Self::OVERFLOW
+        };
+        let id = match self
+            .0
+            .compare_exchange(0, new_id, Ordering::Relaxed, Ordering::Relaxed)
+        {
+            Ok(_) => new_id,
+            // Another thread assigned the site first.
+            Err(id) => id,
+        };
+        (id != Self::OVERFLOW).then(|| id - 1)
+    }
+}
+
+#[cfg(feature = "perf")]
+impl Default for SharedSite {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A block created by `profile_shared!`, which adds its elapsed time to its shared anchor when
+/// dropped.
+#[cfg(feature = "perf")]
+#[derive(Debug)]
+#[must_use]
+pub struct SharedBlock {
+    /// Index of the block's shared anchor, or `None` if the block records nothing.
+    anchor: Option<usize>,
+    byte_count: u64,
+    start_tsc: u64,
+    /// Ticks spent in shared blocks nested in the enclosing block before this one started.
+    outer_children_tsc: u64,
+    /// Whether no other block of the same anchor was active on the thread when this one started.
+    outermost: bool,
+    /// Blocks count their children on their thread, so must end on the thread they began on.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+#[cfg(feature = "perf")]
+impl SharedBlock {
+    /// Creates a block at the `profile_shared!` call site `site`. Prefer the `profile_shared!`
+    /// macro.
+    #[doc(hidden)]
+    #[inline]
+    pub fn with_site(site: &'static SharedSite, name: &'static str, byte_count: u64) -> Self {
+        let anchor = if super::is_enabled() {
+            site.anchor(name)
+        } else {
+            None
+        };
+        let Some(index) = anchor else {
+            return Self {
+                anchor,
+                byte_count,
+                start_tsc: 0,
+                outer_children_tsc: 0,
+                outermost: false,
+                _not_send: std::marker::PhantomData,
+            };
+        };
+        let outermost = DEPTHS.with(|depths| {
+            let depth = &depths[index];
+            depth.set(depth.get() + 1);
+            depth.get() == 1
+        });
+        Self {
+            anchor,
+            byte_count,
+            start_tsc: Profiler::read_block_timer(),
+            outer_children_tsc: CHILDREN_TSC.with(|children| children.replace(0)),
+            outermost,
+            _not_send: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "perf")]
+impl Drop for SharedBlock {
+    #[inline]
+    fn drop(&mut self) {
+        let Some(index) = self.anchor else {
+            return;
+        };
+        let elapsed = Profiler::read_block_timer().wrapping_sub(self.start_tsc);
+        let children = CHILDREN_TSC
+            .with(|children| children.replace(self.outer_children_tsc.wrapping_add(elapsed)));
+        DEPTHS.with(|depths| depths[index].set(depths[index].get() - 1));
+        ANCHORS[index].record(elapsed, children, self.byte_count, self.outermost);
+    }
+}
+
+/// Profile the rest of the enclosing scope as a hit of the shared anchor `name`, optionally
+/// processing `bytes` bytes, adding to totals shared by every thread which [`shared_report`]
+/// reads.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{performance::shared::shared_report, profile_shared};
+///
+/// let workers: Vec<_> = (0..4)
+///     .map(|_| {
+///         std::thread::spawn(|| {
+///             profile_shared!("decode", 4096);
+///             // ...
+///         })
+///     })
+///     .collect();
+/// for worker in workers {
+///     worker.join().unwrap();
+/// }
+/// println!("{}", shared_report());
+/// ```
+#[macro_export]
+macro_rules! profile_shared {
+    ($name:expr) => {
+        $crate::profile_shared!($name, 0);
+    };
+    ($name:expr, $byte_count:expr) => {
+        #[cfg(feature = "perf")]
+        let __psb = {
+            static __SITE: $crate::performance::shared::SharedSite =
+                $crate::performance::shared::SharedSite::new();
+            $crate::performance::shared::SharedBlock::with_site(&__SITE, $name, $byte_count)
+        };
+        // Avoids unused variable warnings without evaluating the count when profiling is disabled.
+        #[cfg(not(feature = "perf"))]
+        let _ = || ($name, $byte_count);
+    };
+}
+
+/// Returns the totals of every shared anchor so far, timed from the first shared block or the last
+/// [`shared_reset`], without stopping measurement. Call sites sharing a name are reported as one
+/// anchor. Returns an empty report without the `perf` feature.
+pub fn shared_report() -> ProfileReport {
+    #[cfg(feature = "perf")]
+    {
+        let mut report = ProfileReport::default();
+        let start_tsc = START_TSC.load(Ordering::Relaxed);
+        report.timer_freq = Profiler::timer_freq();
+        if start_tsc != 0 {
+            report.elapsed_tsc = Profiler::read_block_timer().wrapping_sub(start_tsc);
+        }
+        for anchor in &ANCHORS[..ANCHOR_COUNT.load(Ordering::Relaxed).min(MAX_SHARED_ANCHORS)] {
+            let Some(&name) = anchor.name.get() else {
+                continue;
+            };
+            let stats = anchor.stats(name);
+            match report.anchors.iter_mut().find(|other| other.name == name) {
+                Some(other) => other.merge(&stats),
+                None => report.anchors.push(stats),
+            }
+        }
+        if OVERFLOWED.load(Ordering::Relaxed) {
+            report.warnings.push(format!(
+                "more than {MAX_SHARED_ANCHORS} profile_shared! call sites were hit, so some \
+                 recorded nothing"
+            ));
+        }
+        report
+    }
+    #[cfg(not(feature = "perf"))]
+    ProfileReport::default()
+}
+
+/// Resets the totals of every shared anchor, restarting the elapsed time. Blocks active on other
+/// threads still add their hits when they end.
+pub fn shared_reset() {
+    #[cfg(feature = "perf")]
+    {
+        for anchor in &ANCHORS {
+            anchor.reset();
+        }
+        if START_TSC.load(Ordering::Relaxed) != 0 {
+            START_TSC.store(Profiler::read_block_timer(), Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,56 @@
+//! Output sink for printed reports.
+//!
+//! Reports printed by [`profile_end_and_print`](super::profile_end_and_print),
+//! [`profile_end_summary`](super::profile_end_summary), at thread exit and by watchdogs go to
+//! `stderr` unless a sink is installed with [`set_output_sink`], such as a log file, an in-memory
+//! buffer in tests or a socket. Diagnostics about failed exports are still written to `stderr`.
+
+use super::{color, report};
+use std::{
+    fmt,
+    io::{IsTerminal, Write},
+    sync::{Mutex, PoisonError},
+};
+
+static OUTPUT_SINK: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Sets the writer every printed report is written to. Pass `None` to print to `stderr`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{profile_begin, profile_end_and_print, sink::set_output_sink};
+///
+/// # fn main() -> std::io::Result<()> {
+/// set_output_sink(Some(Box::new(std::fs::File::create("profile.log")?)));
+/// profile_begin();
+/// util_lib_rs::profile!("work");
+/// profile_end_and_print();
+/// # Ok(())
+/// # }
+/// ```
+pub fn set_output_sink(sink: Option<Box<dyn Write + Send>>) {
+    *OUTPUT_SINK.lock().unwrap_or_else(PoisonError::into_inner) = sink;
+}
+
+/// Writes `output` to the installed sink, or to `stderr` if there is none.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn print(output: &dyn fmt::Display) {
+    let mut sink = OUTPUT_SINK.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(sink) = sink.as_mut() {
+        color::colored(color::enabled(false), || {
+            if let Err(err) = write!(sink, "{output}").and_then(|()| sink.flush()) {
+                eprintln!("failed to write profile output: {err}");
+            }
+        });
+    } else {
+        let terminal = std::io::stderr().is_terminal();
+        let width = terminal
+            .then(crate::term::size)
+            .flatten()
+            .map(|size| usize::from(size.columns));
+        color::colored(color::enabled(terminal), || {
+            report::fitted(width, || eprint!("{output}"));
+        });
+    }
+}
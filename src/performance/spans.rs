@@ -0,0 +1,149 @@
+//! Bridge for span-based instrumentation such as the `tracing` crate.
+//!
+//! Libraries instrumented with spans enter and exit them through callbacks rather than lexical
+//! scopes, and may enter the same span several times. [`span_enter`] and [`span_exit`] turn each
+//! enter/exit pair of a span, identified by an ID unique among open spans such as
+//! `tracing::span::Id::into_u64`, into a hit of the anchor named after the span, so one
+//! instrumentation style produces both structured logs and the profile report.
+//!
+//! With the `tracing` feature, [`ProfileLayer`] is a `tracing_subscriber::Layer` forwarding span
+//! enters and exits to the bridge:
+//!
+//! ```ignore
+//! use tracing_subscriber::prelude::*;
+//! use util_lib_rs::performance::spans::ProfileLayer;
+//!
+//! tracing_subscriber::registry().with(ProfileLayer).init();
+//! ```
+
+use super::manual::{block_begin, block_end, BlockId};
+use std::cell::RefCell;
+
+thread_local! {
+    /// Blocks of the spans entered on this thread and not yet exited, innermost last.
+    static ENTERED: RefCell<Vec<(u64, BlockId)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Records that the span `id` named `name` was entered on the current thread, beginning a profile
+/// block which is the parent of blocks started before the span exits.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::spans::{span_enter, span_exit};
+///
+/// span_enter(1, "request");
+/// // ... work done inside the span ...
+/// span_exit(1);
+/// ```
+pub fn span_enter(id: u64, name: &'static str) {
+    let block = block_begin(name);
+    ENTERED.with(|entered| entered.borrow_mut().push((id, block)));
+}
+
+/// Records that the span `id` exited on the current thread, ending the block of its innermost
+/// entry. Exiting a span which wasn't entered on this thread has no effect.
+pub fn span_exit(id: u64) {
+    let block = ENTERED.with(|entered| {
+        let mut entered = entered.borrow_mut();
+        let index = entered.iter().rposition(|&(span, _)| span == id)?;
+        Some(entered.remove(index).1)
+    });
+    if let Some(block) = block {
+        block_end(block);
+    }
+}
+
+/// A `tracing_subscriber::Layer` turning each enter/exit pair of a span into a hit of the anchor
+/// named after the span, keyed by the span's [`Id`](tracing::span::Id).
+#[cfg(feature = "tracing")]
+#[derive(Debug, Default, Copy, Clone)]
+#[must_use]
+pub struct ProfileLayer;
+
+#[cfg(feature = "tracing")]
+impl<S> tracing_subscriber::Layer<S> for ProfileLayer
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_enter(&self, id: &tracing::span::Id, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span_enter(id.into_u64(), span.metadata().name());
+        }
+    }
+
+    fn on_exit(&self, id: &tracing::span::Id, _ctx: tracing_subscriber::layer::Context<'_, S>) {
+        span_exit(id.into_u64());
+    }
+}
+
+#[cfg(all(test, feature = "perf"))]
+mod tests {
+    use super::*;
+    use crate::performance::{profile_begin, GLOBAL_PROFILER};
+
+    #[test]
+    fn span_hits() {
+        profile_begin();
+        for _ in 0..2 {
+            span_enter(7, "tspan_request");
+            span_enter(8, "tspan_query");
+            span_exit(8);
+            span_exit(7);
+        }
+        // Re-entering a span while it's entered nests another hit.
+        span_enter(7, "tspan_request");
+        span_enter(7, "tspan_request");
+        span_exit(7);
+        span_exit(7);
+        span_exit(9);
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let hits = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| anchor.hit_count)
+        };
+        assert_eq!(hits("tspan_request"), Some(4));
+        assert_eq!(hits("tspan_query"), Some(2));
+        assert!(report
+            .calls
+            .iter()
+            .any(|call| call.caller == "tspan_request" && call.callee == "tspan_query"));
+        assert!(ENTERED.with(|entered| entered.borrow().is_empty()));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn layer_hits() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(ProfileLayer);
+        profile_begin();
+        tracing::subscriber::with_default(subscriber, || {
+            for _ in 0..3 {
+                let request = tracing::info_span!("tlayer_request");
+                let _request = request.enter();
+                tracing::info_span!("tlayer_query").in_scope(|| {});
+            }
+        });
+
+        let report = GLOBAL_PROFILER.with(|profiler| profiler.borrow_mut().end());
+        let hits = |name| {
+            report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == name)
+                .map(|anchor| anchor.hit_count)
+        };
+        assert_eq!(hits("tlayer_request"), Some(3));
+        assert_eq!(hits("tlayer_query"), Some(3));
+        assert!(report
+            .calls
+            .iter()
+            .any(|call| call.caller == "tlayer_request" && call.callee == "tlayer_query"));
+        assert!(ENTERED.with(|entered| entered.borrow().is_empty()));
+    }
+}
@@ -0,0 +1,186 @@
+//! Speedscope output.
+//!
+//! [`profile_end_and_write_speedscope`](super::profile_end_and_write_speedscope) writes the trace
+//! events recorded with [`set_record_trace_events`](super::chrome::set_record_trace_events) in the
+//! [speedscope](https://www.speedscope.app) file format, whose time-ordered and left-heavy views
+//! show where each thread spent its time without any other tooling.
+
+use super::flight::TraceEvent;
+use crate::json::JsonWriter;
+use std::io::{self, Write};
+
+/// Writes `events` recorded on the thread named `thread` to `writer` as an evented speedscope
+/// profile, with every event opening and closing its frame. Times are microseconds since
+/// `start_tsc`, converted with the timer frequency `timer_freq`. Events of the same anchor on
+/// different captured call stacks get separate frames, each with its `stackId` as a hex string.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{flight::TraceEvent, speedscope::write_speedscope};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let events = [TraceEvent {
+///     name: "main",
+///     start_tsc: 1_000,
+///     end_tsc: 3_000,
+///     ..TraceEvent::default()
+/// }];
+/// let mut output = Vec::new();
+/// write_speedscope(&events, "main", 0, 1_000_000, &mut output)?;
+/// assert!(String::from_utf8_lossy(&output).contains(r#"{"type":"C","frame":0,"at":3000.000}"#));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_speedscope(
+    events: &[TraceEvent],
+    thread: &str,
+    start_tsc: u64,
+    timer_freq: u64,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let micros = |tsc: u64| {
+        if timer_freq == 0 {
+            0.0
+        } else {
+            #[allow(clippy::cast_precision_loss)]
+            let micros = 1e6 * tsc.saturating_sub(start_tsc) as f64 / timer_freq as f64;
+            micros
+        }
+    };
+
+    let mut frames: Vec<(&str, Option<u64>)> = Vec::new();
+    let mut frame_index = |event: &TraceEvent| {
+        let key = (event.name, event.stack_id);
+        frames
+            .iter()
+            .position(|&frame| frame == key)
+            .unwrap_or_else(|| {
+                frames.push(key);
+                frames.len() - 1
+            })
+    };
+    let mut sorted: Vec<&TraceEvent> = events.iter().collect();
+    sorted.sort_by_key(|event| (event.start_tsc, event.depth));
+
+    // Frames must close in the reverse order they opened, at non-decreasing times.
+    let mut opened: Vec<(usize, u64)> = Vec::new();
+    let mut body: Vec<(&str, usize, f64)> = Vec::new();
+    let mut last_at = 0.0f64;
+    let mut push = |kind, frame, tsc| {
+        last_at = last_at.max(micros(tsc));
+        body.push((kind, frame, last_at));
+    };
+    for event in sorted {
+        while let Some(&(frame, end_tsc)) = opened.last() {
+            if end_tsc > event.start_tsc {
+                break;
+            }
+            push("C", frame, end_tsc);
+            opened.pop();
+        }
+        let frame = frame_index(event);
+        push("O", frame, event.start_tsc);
+        opened.push((frame, event.end_tsc));
+    }
+    while let Some((frame, end_tsc)) = opened.pop() {
+        push("C", frame, end_tsc);
+    }
+    let end_at = last_at;
+
+    let mut json = JsonWriter::new(&mut writer);
+    json.begin_object()?
+        .key("$schema")?
+        .string("https://www.speedscope.app/file-format-schema.json")?
+        .key("exporter")?
+        .string("util_lib_rs")?
+        .key("name")?
+        .string(thread)?
+        .key("activeProfileIndex")?
+        .u64(0)?
+        .key("shared")?
+        .begin_object()?
+        .key("frames")?
+        .begin_array()?;
+    for (name, stack_id) in frames {
+        json.begin_object()?.key("name")?.string(name)?;
+        if let Some(stack_id) = stack_id {
+            json.key("stackId")?.string(&format!("{stack_id:016x}"))?;
+        }
+        json.end_object()?;
+    }
+    json.end_array()?
+        .end_object()?
+        .key("profiles")?
+        .begin_array()?
+        .begin_object()?
+        .key("type")?
+        .string("evented")?
+        .key("name")?
+        .string(thread)?
+        .key("unit")?
+        .string("microseconds")?
+        .key("startValue")?
+        .u64(0)?
+        .key("endValue")?
+        .fixed(end_at, 3)?
+        .key("events")?
+        .begin_array()?;
+    for (kind, frame, at) in body {
+        json.begin_object()?
+            .key("type")?
+            .string(kind)?
+            .key("frame")?
+            .u64(frame as u64)?
+            .key("at")?
+            .fixed(at, 3)?
+            .end_object()?;
+    }
+    json.end_array()?.end_object()?.end_array()?.end_object()?;
+    writeln!(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speedscope_json() {
+        let event = |name, start_tsc, end_tsc, depth| TraceEvent {
+            name,
+            start_tsc,
+            end_tsc,
+            depth,
+            stack_id: None,
+        };
+        // Events are recorded as blocks end, innermost first.
+        let events = [
+            event("inner", 200, 300, 1),
+            event("inner", 300, 350, 1),
+            event("outer \"q\"", 100, 400, 0),
+            event("next", 400, 500, 0),
+        ];
+        let mut output = Vec::new();
+        write_speedscope(&events, "worker", 100, 1_000_000, &mut output).expect("valid write");
+        assert_eq!(
+            String::from_utf8(output).expect("valid utf-8"),
+            concat!(
+                r#"{"$schema":"https://www.speedscope.app/file-format-schema.json","#,
+                r#""exporter":"util_lib_rs","name":"worker","activeProfileIndex":0,"#,
+                r#""shared":{"frames":[{"name":"outer \"q\""},{"name":"inner"},{"name":"next"}]},"#,
+                r#""profiles":[{"type":"evented","name":"worker","unit":"microseconds","#,
+                r#""startValue":0,"endValue":400.000,"events":["#,
+                r#"{"type":"O","frame":0,"at":0.000},"#,
+                r#"{"type":"O","frame":1,"at":100.000},{"type":"C","frame":1,"at":200.000},"#,
+                r#"{"type":"O","frame":1,"at":200.000},{"type":"C","frame":1,"at":250.000},"#,
+                r#"{"type":"C","frame":0,"at":300.000},"#,
+                r#"{"type":"O","frame":2,"at":300.000},{"type":"C","frame":2,"at":400.000}"#,
+                "]}]}\n"
+            )
+        );
+    }
+}
@@ -0,0 +1,391 @@
+//! SQLite results sink.
+//!
+//! Appends run metadata and per-anchor rows to a local SQLite database so profiling history can be
+//! queried with SQL. Requires the `sqlite` feature and links against the system `libsqlite3`.
+//!
+//! The database contains two tables:
+//!
+//! ```sql
+//! CREATE TABLE runs (
+//!     id INTEGER PRIMARY KEY,
+//!     timestamp INTEGER NOT NULL, -- seconds since the Unix Epoch
+//!     program TEXT NOT NULL,
+//!     elapsed_tsc INTEGER NOT NULL,
+//!     timer_freq INTEGER NOT NULL,
+//!     elapsed_ms REAL NOT NULL
+//! );
+//! CREATE TABLE anchors (
+//!     run_id INTEGER NOT NULL REFERENCES runs(id),
+//!     name TEXT NOT NULL,
+//!     hit_count INTEGER NOT NULL,
+//!     byte_count INTEGER NOT NULL,
+//!     item_count INTEGER NOT NULL,
+//!     tsc_elapsed_exclusive INTEGER NOT NULL,
+//!     tsc_elapsed_inclusive INTEGER NOT NULL
+//! );
+//! ```
+
+#![allow(clippy::doc_markdown)]
+
+use super::{ProfileReport, ReportExporter};
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    io,
+    path::{Path, PathBuf},
+    ptr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS runs (
+        id INTEGER PRIMARY KEY,
+        timestamp INTEGER NOT NULL,
+        program TEXT NOT NULL,
+        elapsed_tsc INTEGER NOT NULL,
+        timer_freq INTEGER NOT NULL,
+        elapsed_ms REAL NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS anchors (
+        run_id INTEGER NOT NULL REFERENCES runs(id),
+        name TEXT NOT NULL,
+        hit_count INTEGER NOT NULL,
+        byte_count INTEGER NOT NULL,
+        item_count INTEGER NOT NULL,
+        tsc_elapsed_exclusive INTEGER NOT NULL,
+        tsc_elapsed_inclusive INTEGER NOT NULL
+    );
+";
+
+/// Exports every report to a SQLite database.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::performance::{add_exporter, sqlite::SqliteExporter};
+///
+/// add_exporter(SqliteExporter::new("profile.db"));
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct SqliteExporter {
+    path: PathBuf,
+}
+
+impl SqliteExporter {
+    /// Creates an exporter appending reports to the database at `path`, which is created if it
+    /// doesn't exist.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+impl ReportExporter for SqliteExporter {
+    fn export(&mut self, report: &ProfileReport) -> io::Result<()> {
+        append_report(&self.path, report).map(|_| ())
+    }
+}
+
+/// Append `report` to the SQLite database at `path`, creating it if it doesn't exist. Returns the
+/// ID of the new row in the `runs` table.
+///
+/// # Errors
+///
+/// Returns an error if the database can't be opened or written to.
+pub fn append_report(path: impl AsRef<Path>, report: &ProfileReport) -> io::Result<i64> {
+    let conn = Connection::open(path.as_ref())?;
+    conn.execute(SCHEMA)?;
+    conn.execute("BEGIN")?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    let program = std::env::args().next().unwrap_or_default();
+    let mut run = conn.prepare(
+        "INSERT INTO runs (timestamp, program, elapsed_tsc, timer_freq, elapsed_ms)
+         VALUES (?, ?, ?, ?, ?)",
+    )?;
+    run.bind_u64(1, timestamp)?;
+    run.bind_text(2, &program)?;
+    run.bind_u64(3, report.elapsed_tsc)?;
+    run.bind_u64(4, report.timer_freq)?;
+    run.bind_f64(5, report.elapsed_ms())?;
+    run.step()?;
+    let run_id = conn.last_insert_rowid();
+
+    let mut anchor_row = conn.prepare(
+        "INSERT INTO anchors (run_id, name, hit_count, byte_count, item_count,
+         tsc_elapsed_exclusive, tsc_elapsed_inclusive) VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )?;
+    for anchor in &report.anchors {
+        anchor_row.reset()?;
+        anchor_row.bind_i64(1, run_id)?;
+        anchor_row.bind_text(2, anchor.name)?;
+        anchor_row.bind_u64(3, anchor.hit_count)?;
+        anchor_row.bind_u64(4, anchor.byte_count)?;
+        anchor_row.bind_u64(5, anchor.item_count)?;
+        anchor_row.bind_u64(6, anchor.tsc_elapsed_exclusive)?;
+        anchor_row.bind_u64(7, anchor.tsc_elapsed_inclusive)?;
+        anchor_row.step()?;
+    }
+    drop((run, anchor_row));
+
+    conn.execute("COMMIT")?;
+    Ok(run_id)
+}
+
+#[allow(non_camel_case_types)]
+type sqlite3 = c_void;
+#[allow(non_camel_case_types)]
+type sqlite3_stmt = c_void;
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x0000_0002;
+const SQLITE_OPEN_CREATE: c_int = 0x0000_0004;
+
+#[link(name = "sqlite3")]
+extern "C" {
+    fn sqlite3_open_v2(
+        filename: *const c_char,
+        db: *mut *mut sqlite3,
+        flags: c_int,
+        vfs: *const c_char,
+    ) -> c_int;
+    fn sqlite3_close(db: *mut sqlite3) -> c_int;
+    fn sqlite3_errmsg(db: *mut sqlite3) -> *const c_char;
+    fn sqlite3_exec(
+        db: *mut sqlite3,
+        sql: *const c_char,
+        callback: *const c_void,
+        arg: *mut c_void,
+        errmsg: *mut *mut c_char,
+    ) -> c_int;
+    fn sqlite3_prepare_v2(
+        db: *mut sqlite3,
+        sql: *const c_char,
+        len: c_int,
+        stmt: *mut *mut sqlite3_stmt,
+        tail: *mut *const c_char,
+    ) -> c_int;
+    fn sqlite3_bind_int64(stmt: *mut sqlite3_stmt, index: c_int, value: i64) -> c_int;
+    fn sqlite3_bind_double(stmt: *mut sqlite3_stmt, index: c_int, value: f64) -> c_int;
+    fn sqlite3_bind_text(
+        stmt: *mut sqlite3_stmt,
+        index: c_int,
+        value: *const c_char,
+        len: c_int,
+        destructor: isize,
+    ) -> c_int;
+    fn sqlite3_step(stmt: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_reset(stmt: *mut sqlite3_stmt) -> c_int;
+    #[cfg(test)]
+    fn sqlite3_column_int64(stmt: *mut sqlite3_stmt, column: c_int) -> i64;
+    fn sqlite3_finalize(stmt: *mut sqlite3_stmt) -> c_int;
+    fn sqlite3_last_insert_rowid(db: *mut sqlite3) -> i64;
+}
+
+/// Tells SQLite to make its own copy of bound text.
+const SQLITE_TRANSIENT: isize = -1;
+
+/// An open SQLite database connection.
+struct Connection {
+    db: *mut sqlite3,
+}
+
+impl Connection {
+    fn open(path: &Path) -> io::Result<Self> {
+        let filename = c_string(&path.to_string_lossy())?;
+        let mut db = ptr::null_mut();
+        // SAFETY: `filename` is a valid C string and `db` is a valid out pointer.
+        let rc = unsafe {
+            sqlite3_open_v2(
+                filename.as_ptr(),
+                &raw mut db,
+                SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE,
+                ptr::null(),
+            )
+        };
+        // SQLite allocates a handle even on failure, which needs to be closed.
+        let conn = Self { db };
+        if rc == SQLITE_OK {
+            Ok(conn)
+        } else {
+            Err(conn.error())
+        }
+    }
+
+    fn error(&self) -> io::Error {
+        // SAFETY: `sqlite3_errmsg` always returns a valid C string for a database handle.
+        let message = unsafe { CStr::from_ptr(sqlite3_errmsg(self.db)) };
+        io::Error::other(message.to_string_lossy().into_owned())
+    }
+
+    fn check(&self, rc: c_int) -> io::Result<()> {
+        if rc == SQLITE_OK {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    fn execute(&self, sql: &str) -> io::Result<()> {
+        let sql = c_string(sql)?;
+        // SAFETY: `self.db` is an open handle and `sql` is a valid C string.
+        let rc = unsafe {
+            sqlite3_exec(
+                self.db,
+                sql.as_ptr(),
+                ptr::null(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        self.check(rc)
+    }
+
+    fn prepare(&self, sql: &str) -> io::Result<Statement<'_>> {
+        let sql = c_string(sql)?;
+        let mut stmt = ptr::null_mut();
+        // SAFETY: `self.db` is an open handle, `sql` is a valid C string, and `stmt` is a valid
+        // out pointer.
+        let rc = unsafe {
+            sqlite3_prepare_v2(self.db, sql.as_ptr(), -1, &raw mut stmt, ptr::null_mut())
+        };
+        self.check(rc)?;
+        Ok(Statement { conn: self, stmt })
+    }
+
+    fn last_insert_rowid(&self) -> i64 {
+        // SAFETY: `self.db` is an open handle.
+        unsafe { sqlite3_last_insert_rowid(self.db) }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // SAFETY: `self.db` was returned by `sqlite3_open_v2` and all statements borrowing it have
+        // been finalized.
+        unsafe { sqlite3_close(self.db) };
+    }
+}
+
+/// A prepared SQL statement.
+struct Statement<'a> {
+    conn: &'a Connection,
+    stmt: *mut sqlite3_stmt,
+}
+
+impl Statement<'_> {
+    fn bind_i64(&mut self, index: c_int, value: i64) -> io::Result<()> {
+        // SAFETY: `self.stmt` is a valid prepared statement.
+        self.conn
+            .check(unsafe { sqlite3_bind_int64(self.stmt, index, value) })
+    }
+
+    /// Binds a `u64`, saturating at `i64::MAX` since SQLite integers are signed.
+    fn bind_u64(&mut self, index: c_int, value: u64) -> io::Result<()> {
+        self.bind_i64(index, i64::try_from(value).unwrap_or(i64::MAX))
+    }
+
+    fn bind_f64(&mut self, index: c_int, value: f64) -> io::Result<()> {
+        // SAFETY: `self.stmt` is a valid prepared statement.
+        self.conn
+            .check(unsafe { sqlite3_bind_double(self.stmt, index, value) })
+    }
+
+    fn bind_text(&mut self, index: c_int, value: &str) -> io::Result<()> {
+        let len = c_int::try_from(value.len()).map_err(io::Error::other)?;
+        // SAFETY: `self.stmt` is a valid prepared statement and SQLite copies `value` before
+        // returning since `SQLITE_TRANSIENT` is passed.
+        self.conn.check(unsafe {
+            sqlite3_bind_text(
+                self.stmt,
+                index,
+                value.as_ptr().cast(),
+                len,
+                SQLITE_TRANSIENT,
+            )
+        })
+    }
+
+    /// Steps the statement, returning whether a row is available.
+    fn step(&mut self) -> io::Result<bool> {
+        // SAFETY: `self.stmt` is a valid prepared statement.
+        match unsafe { sqlite3_step(self.stmt) } {
+            SQLITE_ROW => Ok(true),
+            SQLITE_DONE => Ok(false),
+            _ => Err(self.conn.error()),
+        }
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        // SAFETY: `self.stmt` is a valid prepared statement.
+        self.conn.check(unsafe { sqlite3_reset(self.stmt) })
+    }
+
+    #[cfg(test)]
+    fn column_i64(&self, column: c_int) -> i64 {
+        // SAFETY: `self.stmt` is a valid prepared statement with a row available.
+        unsafe { sqlite3_column_int64(self.stmt, column) }
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        // SAFETY: `self.stmt` was returned by `sqlite3_prepare_v2` and is only finalized once.
+        unsafe { sqlite3_finalize(self.stmt) };
+    }
+}
+
+fn c_string(value: &str) -> io::Result<CString> {
+    CString::new(value).map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::performance::AnchorStats;
+
+    #[test]
+    fn append_reports() {
+        let path = std::env::temp_dir().join(format!(
+            "util_lib_rs_append_reports_{}.db",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let report = ProfileReport {
+            elapsed_tsc: 1000,
+            timer_freq: 1_000_000,
+            anchors: vec![
+                AnchorStats {
+                    name: "a",
+                    hit_count: 2,
+                    ..AnchorStats::default()
+                },
+                AnchorStats {
+                    name: "b",
+                    hit_count: 3,
+                    ..AnchorStats::default()
+                },
+            ],
+            ..ProfileReport::default()
+        };
+
+        let first = append_report(&path, &report).expect("valid append");
+        let second = append_report(&path, &report).expect("valid append");
+        assert_eq!(second, first + 1);
+
+        let conn = Connection::open(&path).expect("valid database");
+        let mut query = conn
+            .prepare("SELECT COUNT(*), SUM(hit_count) FROM anchors WHERE run_id = ?")
+            .expect("valid statement");
+        query.bind_i64(1, second).expect("valid bind");
+        assert!(query.step().expect("valid step"));
+        assert_eq!((query.column_i64(0), query.column_i64(1)), (2, 5));
+        drop(query);
+        drop(conn);
+
+        std::fs::remove_file(&path).expect("removed database");
+    }
+}
@@ -0,0 +1,136 @@
+//! Time-to-main startup measurement.
+//!
+//! With the `startup` feature, a constructor in the `.init_array` section runs before `main` and
+//! records when it ran, so the first report includes the startup cost which happens before
+//! `profile_begin` can be called: loading and dynamic linking before the constructor, and static
+//! initialization and the start of `main` after it. Only supported on Linux.
+
+use super::cputime::{boot_time_ns, monotonic_ns, process_cpu_time_ns};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Once,
+    },
+    time::Duration,
+};
+
+/// Time since boot when the constructor ran, in nanoseconds.
+static INIT_BOOT_NS: AtomicU64 = AtomicU64::new(0);
+/// Monotonic time when the constructor ran, in nanoseconds.
+static INIT_MONOTONIC_NS: AtomicU64 = AtomicU64::new(0);
+/// Process CPU time when the constructor ran, in nanoseconds.
+static INIT_CPU_NS: AtomicU64 = AtomicU64::new(0);
+/// Monotonic time when profiling first began, in nanoseconds.
+static BEGIN_MONOTONIC_NS: AtomicU64 = AtomicU64::new(0);
+
+#[used]
+#[link_section = ".init_array"]
+static CONSTRUCTOR: extern "C" fn() = init;
+
+extern "C" fn init() {
+    INIT_BOOT_NS.store(boot_time_ns(), Ordering::Relaxed);
+    INIT_CPU_NS.store(process_cpu_time_ns(), Ordering::Relaxed);
+    INIT_MONOTONIC_NS.store(monotonic_ns(), Ordering::Relaxed);
+}
+
+/// Startup costs of the process before profiling began.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct StartupTime {
+    /// Wall time from the process starting until the constructor ran, covering loading and dynamic
+    /// linking, at the resolution of kernel clock ticks, usually 10ms. `None` if the process start
+    /// time can't be read from `/proc`.
+    pub exec_to_init: Option<Duration>,
+    /// CPU time the process used before the constructor ran.
+    pub cpu_before_init: Duration,
+    /// Wall time from the constructor until profiling first began, covering static initialization
+    /// and the start of `main`. `None` until `profile_begin` has been called.
+    pub init_to_begin: Option<Duration>,
+}
+
+/// Returns the startup costs of the process, or `None` if the constructor didn't run.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::performance::{profile_begin, startup::startup_time};
+///
+/// profile_begin();
+/// if let Some(startup) = startup_time() {
+///     println!("{:?} of CPU time before main", startup.cpu_before_init);
+/// }
+/// ```
+pub fn startup_time() -> Option<StartupTime> {
+    let init_monotonic = INIT_MONOTONIC_NS.load(Ordering::Relaxed);
+    if init_monotonic == 0 {
+        return None;
+    }
+    let begin = BEGIN_MONOTONIC_NS.load(Ordering::Relaxed);
+    Some(StartupTime {
+        exec_to_init: process_start_boot_ns().map(|start| {
+            Duration::from_nanos(INIT_BOOT_NS.load(Ordering::Relaxed).saturating_sub(start))
+        }),
+        cpu_before_init: Duration::from_nanos(INIT_CPU_NS.load(Ordering::Relaxed)),
+        init_to_begin: (begin != 0)
+            .then(|| Duration::from_nanos(begin.saturating_sub(init_monotonic))),
+    })
+}
+
+/// Records that profiling began, adding the startup costs to the run metadata the first time.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn begin() {
+    static FIRST_BEGIN: Once = Once::new();
+    FIRST_BEGIN.call_once(|| {
+        BEGIN_MONOTONIC_NS.store(monotonic_ns(), Ordering::Relaxed);
+        let Some(startup) = startup_time() else {
+            return;
+        };
+        if let Some(exec_to_init) = startup.exec_to_init {
+            super::set_run_metadata("startup.exec_to_init", format!("{exec_to_init:?}"));
+        }
+        let cpu_before_init = startup.cpu_before_init;
+        super::set_run_metadata("startup.cpu_before_init", format!("{cpu_before_init:?}"));
+        if let Some(init_to_begin) = startup.init_to_begin {
+            super::set_run_metadata("startup.init_to_begin", format!("{init_to_begin:?}"));
+        }
+    });
+}
+
+/// Returns when the process started as nanoseconds since boot, from field 22 of `/proc/self/stat`.
+fn process_start_boot_ns() -> Option<u64> {
+    extern "C" {
+        fn sysconf(name: std::os::raw::c_int) -> std::os::raw::c_long;
+    }
+    const SC_CLK_TCK: std::os::raw::c_int = 2;
+
+    let stat = std::fs::read_to_string("/proc/self/stat").ok()?;
+    // The command name may contain spaces, so fields are counted from its closing parenthesis,
+    // after which the state is field 3.
+    let start_ticks: u64 = stat
+        .get(stat.rfind(')')? + 1..)?
+        .split_whitespace()
+        .nth(22 - 3)?
+        .parse()
+        .ok()?;
+    // SAFETY: `sysconf` has no preconditions.
+    let ticks_per_second = u64::try_from(unsafe { sysconf(SC_CLK_TCK) }).ok()?;
+    (ticks_per_second > 0).then(|| start_ticks * 1_000_000_000 / ticks_per_second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_costs() {
+        let startup = startup_time().expect("constructor ran");
+        assert!(startup.cpu_before_init > Duration::ZERO);
+        assert!(startup.exec_to_init.expect("valid /proc/self/stat") < Duration::from_secs(1000));
+
+        begin();
+        let init_to_begin = startup_time()
+            .and_then(|startup| startup.init_to_begin)
+            .expect("profiling began");
+        assert!(init_to_begin > Duration::ZERO);
+    }
+}
@@ -0,0 +1,138 @@
+//! Cross-thread aggregation.
+//!
+//! Each thread profiles into its own thread-local profiler, so by default a report only covers the
+//! thread which ended profiling. With [`set_thread_aggregation`] enabled, every thread's
+//! statistics are registered when it exits and included in the next report ended on any thread,
+//! either merged by anchor name or broken down per thread.
+
+use super::ProfileReport;
+use crate::sync::mpsc::{Queue, Receiver, Sender};
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Mutex, OnceLock, PoisonError,
+};
+
+/// How the statistics of other threads are included in a report.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub enum ThreadAggregation {
+    /// Only report the thread which ended profiling.
+    #[default]
+    Off,
+    /// Merge the anchors of every thread by name.
+    Flattened,
+    /// Keep the anchors of each thread separate, prefixed with the thread's name, e.g.
+    /// `worker-1/parse`.
+    PerThread,
+}
+
+static THREAD_AGGREGATION: AtomicU8 = AtomicU8::new(0);
+
+/// How many exited threads' statistics are queued without taking a lock.
+const QUEUE_CAPACITY: usize = 256;
+
+/// The statistics of an exited thread, with its name.
+type Finished = (&'static str, ProfileReport);
+
+/// Statistics of exited threads not yet included in a report, so exiting threads don't contend
+/// on a lock.
+static FINISHED: OnceLock<(Sender<Finished>, Mutex<Receiver<Finished>>)> = OnceLock::new();
+
+/// Statistics of exited threads which didn't fit in [`FINISHED`].
+static OVERFLOW: Mutex<Vec<Finished>> = Mutex::new(Vec::new());
+
+fn finished() -> &'static (Sender<Finished>, Mutex<Receiver<Finished>>) {
+    FINISHED.get_or_init(|| {
+        let (sender, receiver) = Queue::with_capacity(QUEUE_CAPACITY);
+        (sender, Mutex::new(receiver))
+    })
+}
+
+/// Include the statistics of other threads in reports, as registered when each thread exits.
+/// Threads must have exited, e.g. been joined, before profiling ends to be included, and each
+/// thread's statistics are included in one report only.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{
+///     performance::{
+///         profile_begin, profile_end,
+///         threads::{set_thread_aggregation, ThreadAggregation},
+///     },
+///     profile,
+/// };
+///
+/// set_thread_aggregation(ThreadAggregation::Flattened);
+/// profile_begin();
+/// std::thread::spawn(|| {
+///     profile!("work");
+/// })
+/// .join()
+/// .unwrap();
+/// let report = profile_end();
+/// # #[cfg(feature = "perf")]
+/// assert!(report.anchors.iter().any(|anchor| anchor.name == "work"));
+/// ```
+pub fn set_thread_aggregation(aggregation: ThreadAggregation) {
+    let value = match aggregation {
+        ThreadAggregation::Off => 0,
+        ThreadAggregation::Flattened => 1,
+        ThreadAggregation::PerThread => 2,
+    };
+    THREAD_AGGREGATION.store(value, Ordering::Relaxed);
+}
+
+/// Returns how the statistics of other threads are included in reports.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn thread_aggregation() -> ThreadAggregation {
+    match THREAD_AGGREGATION.load(Ordering::Relaxed) {
+        1 => ThreadAggregation::Flattened,
+        2 => ThreadAggregation::PerThread,
+        _ => ThreadAggregation::Off,
+    }
+}
+
+/// Registers the final statistics of the exiting thread named `thread`.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn register(thread: &'static str, report: ProfileReport) {
+    if let Err(exited) = finished().0.push((thread, report)) {
+        OVERFLOW
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(exited);
+    }
+}
+
+/// Adds the statistics of every exited thread to `report`, ended on the thread named `thread`.
+/// The total elapsed time remains that of `report`, so the times of anchors on other threads may
+/// add up to more than it.
+#[cfg_attr(not(feature = "perf"), allow(dead_code))]
+pub(super) fn aggregate(report: ProfileReport, thread: &str) -> ProfileReport {
+    let aggregation = thread_aggregation();
+    if aggregation == ThreadAggregation::Off {
+        return report;
+    }
+    let mut exited = Vec::new();
+    finished()
+        .1
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .pop_batch(&mut exited, usize::MAX);
+    exited.append(&mut OVERFLOW.lock().unwrap_or_else(PoisonError::into_inner));
+    let mut aggregated = match aggregation {
+        ThreadAggregation::PerThread => report.prefixed(&format!("{thread}/")),
+        _ => report,
+    };
+    for (name, other) in exited {
+        let other = match aggregation {
+            ThreadAggregation::PerThread => other.prefixed(&format!("{name}/")),
+            _ => other,
+        };
+        aggregated.merge(&ProfileReport {
+            elapsed_tsc: 0,
+            ..other
+        });
+    }
+    aggregated
+}
@@ -0,0 +1,184 @@
+//! Deadline watchdog.
+//!
+//! A [`Watchdog`] runs on its own thread and fires if the program is still running when its
+//! deadline passes, e.g. a hung test. It collects every thread's statistics published with
+//! [`profile_publish`](super::profile_publish) into a report, prints it to the
+//! [output sink](super::sink::set_output_sink), optionally saves it as a dump, and then aborts the
+//! process or invokes a callback instead.
+
+use super::{
+    dump::{Compression, ProfileDump},
+    published_snapshots, ProfileReport,
+};
+use std::{
+    fmt, io,
+    path::PathBuf,
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+type TimeoutCallback = Box<dyn FnOnce(&ProfileReport) + Send>;
+
+/// Configuration for a deadline watchdog.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use util_lib_rs::performance::{dump::Compression, watchdog::Watchdog};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let _watchdog = Watchdog::new(Duration::from_secs(60))
+///     .dump_to("hung.dump", Compression::None)
+///     .start()?;
+/// // run the profiled work, calling `profile_publish()` periodically...
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub struct Watchdog {
+    deadline: Duration,
+    dump: Option<(PathBuf, Compression)>,
+    on_timeout: Option<TimeoutCallback>,
+}
+
+impl Watchdog {
+    /// Creates a watchdog which fires once `deadline` has passed since it was started.
+    pub fn new(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            dump: None,
+            on_timeout: None,
+        }
+    }
+
+    /// Saves the report to the file at `path` when the deadline is exceeded.
+    pub fn dump_to(mut self, path: impl Into<PathBuf>, compression: Compression) -> Self {
+        self.dump = Some((path.into(), compression));
+        self
+    }
+
+    /// Calls `callback` with the report when the deadline is exceeded, instead of aborting.
+    pub fn on_timeout(mut self, callback: impl FnOnce(&ProfileReport) + Send + 'static) -> Self {
+        self.on_timeout = Some(Box::new(callback));
+        self
+    }
+
+    /// Starts the watchdog thread. The deadline is cancelled when the returned guard is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread can't be spawned.
+    pub fn start(self) -> io::Result<WatchdogGuard> {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-watchdog".to_string())
+            .spawn(move || {
+                #[cfg(feature = "perf")]
+                let start_tsc = super::Profiler::read_block_timer();
+                #[cfg(not(feature = "perf"))]
+                let start_tsc = 0;
+                if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(self.deadline) {
+                    self.fire(start_tsc);
+                }
+            })?;
+        Ok(WatchdogGuard {
+            cancel: Some(cancel),
+            thread: Some(thread),
+        })
+    }
+
+    fn fire(self, start_tsc: u64) {
+        let mut report = published_report(start_tsc);
+        report.warnings.push(format!(
+            "profiling deadline of {:?} exceeded, only published statistics are included",
+            self.deadline
+        ));
+        super::sink::print(&report);
+        if let Some((path, compression)) = &self.dump {
+            if let Err(err) = ProfileDump::new(report.clone()).save(path, *compression) {
+                eprintln!("failed to dump profile to {}: {err}", path.display());
+            }
+        }
+        match self.on_timeout {
+            Some(callback) => callback(&report),
+            None => std::process::abort(),
+        }
+    }
+}
+
+impl fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("deadline", &self.deadline)
+            .field("dump", &self.dump)
+            .field("on_timeout", &self.on_timeout.is_some())
+            .finish()
+    }
+}
+
+/// Combines every thread's published statistics into one report, timed from `start_tsc`.
+pub(super) fn published_report(start_tsc: u64) -> ProfileReport {
+    let mut report = ProfileReport::default();
+    #[cfg(feature = "perf")]
+    {
+        report.timer_freq = super::Profiler::timer_freq();
+        report.elapsed_tsc = super::Profiler::read_block_timer().wrapping_sub(start_tsc);
+    }
+    #[cfg(not(feature = "perf"))]
+    let _ = start_tsc;
+    for snapshot in published_snapshots() {
+        report.merge(&ProfileReport {
+            anchors: snapshot.anchors,
+            ..ProfileReport::default()
+        });
+    }
+    report
+}
+
+/// A running watchdog, cancelled when dropped.
+#[derive(Debug)]
+#[must_use]
+pub struct WatchdogGuard {
+    cancel: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the watchdog thread before its deadline.
+        drop(self.cancel.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn watchdog_deadline() {
+        let (fired, reports) = channel();
+        let watchdog = Watchdog::new(Duration::from_millis(10))
+            .on_timeout(move |report| fired.send(report.clone()).expect("receiver"))
+            .start()
+            .expect("watchdog thread");
+        let report = reports
+            .recv_timeout(Duration::from_secs(10))
+            .expect("watchdog fired");
+        assert!(report.warnings[0].contains("deadline of 10ms exceeded"));
+        drop(watchdog);
+
+        let (fired, reports) = channel();
+        let watchdog = Watchdog::new(Duration::from_secs(1000))
+            .on_timeout(move |_| fired.send(()).expect("receiver"))
+            .start()
+            .expect("watchdog thread");
+        drop(watchdog);
+        assert!(reports.recv().is_err());
+    }
+}
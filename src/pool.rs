@@ -0,0 +1,357 @@
+//! Reusable object pools.
+//!
+//! An [`ObjectPool`] hands out objects, such as buffers, which go back to the pool instead of being
+//! dropped when their [`Pooled`] guard is, so a hot loop the profiler shows spending its time
+//! allocating can reuse the same few allocations instead.
+
+use std::{
+    fmt,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+/// Creates a new object for a pool.
+type Factory<T> = Box<dyn Fn() -> T + Send + Sync>;
+
+/// Prepares an object to be reused.
+type Reset<T> = Box<dyn Fn(&mut T) + Send + Sync>;
+
+/// A pool of reusable objects, shareable between threads.
+///
+/// [`get`](Self::get) takes an idle object, or creates one with the factory if there are none,
+/// and returns a guard which puts it back when dropped, after running the reset hook if there is
+/// one.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Write;
+/// use util_lib_rs::pool::ObjectPool;
+///
+/// let buffers = ObjectPool::new(|| Vec::with_capacity(4096)).reset(Vec::clear);
+/// for line in ["first", "second"] {
+///     let mut buffer = buffers.get();
+///     writeln!(buffer, "{line}").unwrap();
+///     assert_eq!(buffer.len(), line.len() + 1);
+/// }
+/// let stats = buffers.stats();
+/// assert_eq!((stats.hits, stats.misses, stats.created), (1, 1, 1));
+/// ```
+#[must_use]
+pub struct ObjectPool<T> {
+    idle: Mutex<Vec<T>>,
+    factory: Factory<T>,
+    reset: Option<Reset<T>>,
+    max_size: Option<usize>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    created: AtomicU64,
+    /// Profiler counters hits, misses and created objects are added to, if any.
+    counters: Option<[&'static str; 3]>,
+}
+
+impl<T> ObjectPool<T> {
+    /// Creates an empty pool which creates objects with `factory`.
+    pub fn new(factory: impl Fn() -> T + Send + Sync + 'static) -> Self {
+        Self {
+            idle: Mutex::new(Vec::new()),
+            factory: Box::new(factory),
+            reset: None,
+            max_size: None,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            created: AtomicU64::new(0),
+            counters: None,
+        }
+    }
+
+    /// Runs `reset` on every object returned to the pool, such as `Vec::clear`, so the next user
+    /// gets it in a clean state.
+    pub fn reset(mut self, reset: impl Fn(&mut T) + Send + Sync + 'static) -> Self {
+        self.reset = Some(Box::new(reset));
+        self
+    }
+
+    /// Keeps at most `max_size` idle objects, dropping any more which are returned, so a burst of
+    /// use doesn't hold on to memory for good.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Adds every hit, miss and created object to the profiler counters `hits`, `misses` and
+    /// `created`, as with `counter!`, so the report shows how well the pool works.
+    pub fn counters(
+        mut self,
+        hits: &'static str,
+        misses: &'static str,
+        created: &'static str,
+    ) -> Self {
+        self.counters = Some([hits, misses, created]);
+        self
+    }
+
+    /// Creates `count` objects up front, up to the maximum size, so the first uses are hits.
+    pub fn prefill(self, count: usize) -> Self {
+        let count = count.min(self.max_size.unwrap_or(usize::MAX));
+        let objects: Vec<_> = (0..count).map(|_| self.create()).collect();
+        self.lock().extend(objects);
+        self
+    }
+
+    /// Takes an idle object, or creates one if there are none, returning a guard which puts it
+    /// back in the pool when dropped.
+    pub fn get(&self) -> Pooled<'_, T> {
+        let idle = self.lock().pop();
+        let hit = idle.is_some();
+        if let Some([hits, misses, _]) = self.counters {
+            crate::counter!(if hit { hits } else { misses });
+        }
+        let value = if let Some(value) = idle {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            value
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            self.create()
+        };
+        Pooled {
+            pool: self,
+            value: ManuallyDrop::new(value),
+        }
+    }
+
+    /// Returns `value` to the pool, as if it had come from [`get`](Self::get).
+    pub fn put(&self, mut value: T) {
+        if let Some(reset) = &self.reset {
+            reset(&mut value);
+        }
+        let mut idle = self.lock();
+        if idle.len() < self.max_size.unwrap_or(usize::MAX) {
+            idle.push(value);
+        }
+    }
+
+    /// Number of idle objects in the pool.
+    #[must_use]
+    pub fn idle(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Drops every idle object.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Hits, misses and created objects so far.
+    pub fn stats(&self) -> PoolStats {
+        PoolStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            created: self.created.load(Ordering::Relaxed),
+        }
+    }
+
+    fn create(&self) -> T {
+        self.created.fetch_add(1, Ordering::Relaxed);
+        if let Some([_, _, created]) = self.counters {
+            crate::counter!(created);
+        }
+        (self.factory)()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<T>> {
+        self.idle.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Default + 'static> Default for ObjectPool<T> {
+    /// Creates an empty pool which creates objects with `T::default`.
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+impl<T> fmt::Debug for ObjectPool<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ObjectPool")
+            .field("idle", &self.idle())
+            .field("max_size", &self.max_size)
+            .field("stats", &self.stats())
+            .finish_non_exhaustive()
+    }
+}
+
+/// An object taken from an [`ObjectPool`], returned to it when dropped.
+#[must_use]
+pub struct Pooled<'a, T> {
+    pool: &'a ObjectPool<T>,
+    value: ManuallyDrop<T>,
+}
+
+impl<T> Pooled<'_, T> {
+    /// Takes the object out of the pool for good, so it isn't returned when dropped.
+    pub fn detach(self) -> T {
+        let mut this = ManuallyDrop::new(self);
+        // SAFETY: `this` is never dropped, so the value is taken once and not returned to the
+        // pool.
+        unsafe { ManuallyDrop::take(&mut this.value) }
+    }
+}
+
+impl<T> Deref for Pooled<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Pooled<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T> Drop for Pooled<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: The value is only taken here or in `detach`, which doesn't run this.
+        self.pool
+            .put(unsafe { ManuallyDrop::take(&mut self.value) });
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Pooled<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Pooled").field(&*self.value).finish()
+    }
+}
+
+/// Hits, misses and created objects of an [`ObjectPool`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct PoolStats {
+    /// Calls to [`get`](ObjectPool::get) which reused an idle object.
+    pub hits: u64,
+    /// Calls to [`get`](ObjectPool::get) which had to create an object.
+    pub misses: u64,
+    /// Objects created by the factory, including by [`prefill`](ObjectPool::prefill).
+    pub created: u64,
+}
+
+impl PoolStats {
+    /// Fraction of calls to [`get`](ObjectPool::get) which reused an object, or 0 if there were
+    /// none.
+    #[must_use]
+    pub fn hit_rate(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        match self.hits + self.misses {
+            0 => 0.0,
+            gets => self.hits as f64 / gets as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn reuses_objects() {
+        let pool = ObjectPool::new(|| Vec::<u8>::with_capacity(16)).reset(Vec::clear);
+        {
+            let mut first = pool.get();
+            first.extend_from_slice(b"abc");
+            let second = pool.get();
+            assert!(second.is_empty());
+        }
+        assert_eq!(pool.idle(), 2);
+        let reused = pool.get();
+        assert!(reused.is_empty() && reused.capacity() >= 16);
+        assert_eq!(
+            pool.stats(),
+            PoolStats {
+                hits: 1,
+                misses: 2,
+                created: 2
+            }
+        );
+        assert!((pool.stats().hit_rate() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(reused.detach().capacity(), 16);
+        assert_eq!(pool.idle(), 1);
+        pool.clear();
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[test]
+    fn sizes() {
+        let pool = ObjectPool::<String>::default().max_size(2).prefill(5);
+        assert_eq!(pool.idle(), 2);
+        assert_eq!(pool.stats().created, 2);
+        let objects: Vec<_> = (0..4).map(|_| pool.get()).collect();
+        assert_eq!(pool.stats().hits, 2);
+        assert_eq!(pool.stats().misses, 2);
+        drop(objects);
+        assert_eq!(pool.idle(), 2);
+        pool.put("extra".to_string());
+        assert_eq!(pool.idle(), 2);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let pool = Arc::new(ObjectPool::new(|| 0_u64).reset(|value| *value = 0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let pool = Arc::clone(&pool);
+                thread::spawn(move || {
+                    for _ in 0..1_000 {
+                        let mut value = pool.get();
+                        assert_eq!(*value, 0);
+                        *value += 1;
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().expect("valid thread");
+        }
+        let stats = pool.stats();
+        assert_eq!(stats.hits + stats.misses, 4_000);
+        assert!(stats.created <= 4);
+    }
+
+    #[cfg(feature = "perf")]
+    #[test]
+    fn pool_counters() {
+        use crate::performance::{profile_begin, profile_end};
+
+        profile_begin();
+        let pool = ObjectPool::new(Vec::<u8>::new).counters(
+            "pool_test.hits",
+            "pool_test.misses",
+            "pool_test.created",
+        );
+        drop(pool.get());
+        drop(pool.get());
+        let report = profile_end();
+        let count = |name| {
+            report
+                .event_counters
+                .iter()
+                .find(|counter| counter.name == name)
+                .map(|counter| counter.count)
+        };
+        assert_eq!(
+            (
+                count("pool_test.hits"),
+                count("pool_test.misses"),
+                count("pool_test.created")
+            ),
+            (Some(1), Some(1), Some(1))
+        );
+    }
+}
@@ -0,0 +1,402 @@
+//! Running subprocesses.
+//!
+//! [`run`] starts building a command with its arguments, environment and working directory, and
+//! an optional wall-clock timeout after which the process is killed. [`Run::output`] captures its
+//! standard output and error, and [`Run::stream`] also hands each line to a callback as it's
+//! printed, for showing the progress of long builds or benchmarks.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    fmt,
+    io::{self, BufRead, BufReader, Read},
+    path::PathBuf,
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Longest wait between checks of whether the process exited.
+const MAX_POLL: Duration = Duration::from_millis(50);
+
+/// Starts building a command running `program`, found on the `PATH` if it isn't a path.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::process::run;
+///
+/// # fn main() -> std::io::Result<()> {
+/// # if cfg!(unix) {
+/// let output = run("echo")
+///     .arg("hello")
+///     .timeout(Duration::from_secs(10))
+///     .output()?;
+/// assert!(output.success());
+/// assert_eq!(output.stdout_str(), "hello\n");
+/// # }
+/// # Ok(())
+/// # }
+/// ```
+pub fn run(program: impl AsRef<OsStr>) -> Run {
+    Run {
+        program: program.as_ref().to_os_string(),
+        args: Vec::new(),
+        env: Vec::new(),
+        env_clear: false,
+        current_dir: None,
+        timeout: None,
+    }
+}
+
+/// A command to run. Created by [`run`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Run {
+    program: OsString,
+    args: Vec<OsString>,
+    /// Variables to set, or to remove if `None`.
+    env: Vec<(OsString, Option<OsString>)>,
+    env_clear: bool,
+    current_dir: Option<PathBuf>,
+    timeout: Option<Duration>,
+}
+
+impl Run {
+    /// Adds an argument.
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Adds arguments.
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+        self
+    }
+
+    /// Sets the environment variable `name` to `value` for the process.
+    pub fn env(mut self, name: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.env.push((
+            name.as_ref().to_os_string(),
+            Some(value.as_ref().to_os_string()),
+        ));
+        self
+    }
+
+    /// Removes the environment variable `name` for the process.
+    pub fn env_remove(mut self, name: impl AsRef<OsStr>) -> Self {
+        self.env.push((name.as_ref().to_os_string(), None));
+        self
+    }
+
+    /// Starts the process with only the variables set with [`env`](Self::env), rather than
+    /// inheriting this process's environment.
+    pub fn env_clear(mut self) -> Self {
+        self.env_clear = true;
+        self.env.clear();
+        self
+    }
+
+    /// Runs the process in `dir`.
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    /// Kills the process if it's still running `timeout` after it started.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Runs the process to completion, or until the timeout, capturing its output. Its standard
+    /// input is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the process can't be started or waited on. A process which fails or
+    /// times out isn't an error, see [`Output::success`].
+    pub fn output(&self) -> io::Result<Output> {
+        self.stream(|_, _| {})
+    }
+
+    /// Runs the process like [`output`](Self::output), calling `on_line` with each line it prints
+    /// and the pipe it was printed to, without the line ending, as soon as it's printed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use util_lib_rs::process::{run, Pipe};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// # if cfg!(unix) {
+    /// let mut lines = Vec::new();
+    /// let output = run("sh")
+    ///     .args(["-c", "echo one; echo two >&2"])
+    ///     .stream(|pipe, line| lines.push((pipe, line.to_string())))?;
+    /// assert!(output.success());
+    /// assert!(lines.contains(&(Pipe::Stderr, "two".to_string())));
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the process can't be started or waited on.
+    pub fn stream(&self, mut on_line: impl FnMut(Pipe, &str)) -> io::Result<Output> {
+        let start = Instant::now();
+        let deadline = self.timeout.map(|timeout| start + timeout);
+        let mut child = self.command().spawn()?;
+        let (sender, lines) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            read_lines(stdout, Pipe::Stdout, sender.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            read_lines(stderr, Pipe::Stderr, sender);
+        }
+
+        let mut output = Output {
+            status: None,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+            timed_out: false,
+            elapsed: Duration::ZERO,
+        };
+        let mut poll = Duration::from_millis(1);
+        let mut pipes_open = true;
+        loop {
+            if output.status.is_none() {
+                output.status = child.try_wait()?;
+            }
+            let now = Instant::now();
+            if output.status.is_none() && deadline.is_some_and(|deadline| now >= deadline) {
+                output.timed_out = true;
+                output.status = Some(kill(&mut child)?);
+            }
+            if output.status.is_some() && !pipes_open {
+                break;
+            }
+            // Once the process exits, only wait for output still buffered in the pipes until the
+            // deadline, since processes it started may hold them open.
+            if output.status.is_some() && deadline.is_some_and(|deadline| now >= deadline) {
+                break;
+            }
+            let wait = deadline.map_or(poll, |deadline| poll.min(deadline - now));
+            match lines.recv_timeout(wait) {
+                Ok((pipe, line)) => {
+                    let text = String::from_utf8_lossy(&line);
+                    on_line(pipe, text.trim_end_matches(['\n', '\r']));
+                    match pipe {
+                        Pipe::Stdout => output.stdout.extend_from_slice(&line),
+                        Pipe::Stderr => output.stderr.extend_from_slice(&line),
+                    }
+                    poll = Duration::from_millis(1);
+                }
+                Err(RecvTimeoutError::Timeout) => poll = (poll * 2).min(MAX_POLL),
+                Err(RecvTimeoutError::Disconnected) => {
+                    pipes_open = false;
+                    if output.status.is_none() {
+                        // Nothing more to read, so only the exit is left to wait for.
+                        thread::sleep(wait);
+                    }
+                }
+            }
+        }
+        output.elapsed = start.elapsed();
+        Ok(output)
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if self.env_clear {
+            command.env_clear();
+        }
+        for (name, value) in &self.env {
+            match value {
+                Some(value) => command.env(name, value),
+                None => command.env_remove(name),
+            };
+        }
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+        command
+    }
+}
+
+impl fmt::Display for Run {
+    /// Formats the command as it would be typed, quoting arguments with spaces.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.program.to_string_lossy())?;
+        for arg in &self.args {
+            let arg = arg.to_string_lossy();
+            if arg.is_empty() || arg.contains(char::is_whitespace) {
+                write!(f, " {arg:?}")?;
+            } else {
+                write!(f, " {arg}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends each line read from `pipe` to `sender`, on a thread of its own so a full pipe never
+/// blocks the process.
+fn read_lines(pipe: impl Read + Send + 'static, kind: Pipe, sender: Sender<(Pipe, Vec<u8>)>) {
+    thread::spawn(move || {
+        let mut reader = BufReader::new(pipe);
+        loop {
+            let mut line = Vec::new();
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) | Err(_) => break,
+                Ok(_) => {
+                    if sender.send((kind, line)).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn kill(child: &mut Child) -> io::Result<ExitStatus> {
+    match child.kill() {
+        // The process may have exited since it was last checked.
+        Ok(()) | Err(_) => child.wait(),
+    }
+}
+
+/// Which pipe a line was printed to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Pipe {
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+/// The result of a finished process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Output {
+    /// How the process exited, or `None` if it was still running when its output ended early.
+    pub status: Option<ExitStatus>,
+    /// Everything printed to standard output.
+    pub stdout: Vec<u8>,
+    /// Everything printed to standard error.
+    pub stderr: Vec<u8>,
+    /// Whether the process was killed for running past the timeout.
+    pub timed_out: bool,
+    /// Time from starting the process to it exiting.
+    pub elapsed: Duration,
+}
+
+impl Output {
+    /// Whether the process exited successfully before the timeout.
+    #[must_use]
+    pub fn success(&self) -> bool {
+        !self.timed_out && self.status.is_some_and(|status| status.success())
+    }
+
+    /// Returns the exit code, or `None` if the process was killed by a signal.
+    #[must_use]
+    pub fn code(&self) -> Option<i32> {
+        self.status?.code()
+    }
+
+    /// Returns standard output, with invalid UTF-8 replaced.
+    #[must_use]
+    pub fn stdout_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stdout)
+    }
+
+    /// Returns standard error, with invalid UTF-8 replaced.
+    #[must_use]
+    pub fn stderr_str(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.stderr)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn captures_output() {
+        let dir = crate::fs::TempDir::new().expect("created dir");
+        let output = run("sh")
+            .args([
+                "-c",
+                "echo \"$GREETING\" from $(pwd); echo oops >&2; exit 3",
+            ])
+            .env("GREETING", "hello")
+            .current_dir(dir.path())
+            .output()
+            .expect("ran process");
+        assert!(!output.success() && !output.timed_out);
+        assert_eq!(output.code(), Some(3));
+        let pwd = dir.path().canonicalize().expect("valid dir");
+        assert_eq!(
+            output.stdout_str(),
+            format!("hello from {}\n", pwd.display())
+        );
+        assert_eq!(output.stderr_str(), "oops\n");
+
+        let cleared = run("sh")
+            .args(["-c", "echo ${HOME:-none}"])
+            .env_clear()
+            .output()
+            .expect("ran process");
+        assert_eq!(cleared.stdout_str(), "none\n");
+        assert!(run("util-lib-rs-missing-program").output().is_err());
+    }
+
+    #[test]
+    fn kills_on_timeout() {
+        let output = run("sh")
+            .args(["-c", "echo started; sleep 30"])
+            .timeout(Duration::from_millis(200))
+            .output()
+            .expect("ran process");
+        assert!(output.timed_out && !output.success());
+        assert_eq!(output.stdout_str(), "started\n");
+        assert!(output.elapsed < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn streams_lines() {
+        let mut lines = Vec::new();
+        let output = run("sh")
+            .args(["-c", "printf 'a\\nb\\n'; printf 'c' >&2"])
+            .stream(|pipe, line| lines.push((pipe, line.to_string())))
+            .expect("ran process");
+        assert!(output.success());
+        lines.sort();
+        assert_eq!(
+            lines,
+            [
+                (Pipe::Stdout, "a".to_string()),
+                (Pipe::Stdout, "b".to_string()),
+                (Pipe::Stderr, "c".to_string()),
+            ]
+        );
+        assert_eq!(
+            run("git").args(["diff", "a b", ""]).to_string(),
+            r#"git diff "a b" """#
+        );
+    }
+}
@@ -0,0 +1,333 @@
+//! Seedable pseudo-random numbers.
+//!
+//! [`Rng`] is a xoshiro256++ generator: fast, small and statistically good enough for
+//! benchmarks, tests and simulations, but not for cryptography. The same seed always produces the
+//! same sequence, so a failing randomized test can be replayed.
+//!
+//! The free functions [`u64`](fn@u64), [`range`], [`f64`](fn@f64), [`shuffle`] and
+//! [`fill_bytes`] use a generator local to the current thread, seeded differently in each process
+//! unless [`seed`] is called.
+
+use std::{
+    cell::RefCell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    ops::{Range, RangeInclusive},
+};
+
+/// A xoshiro256++ pseudo-random number generator.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::rand::Rng;
+///
+/// let mut rng = Rng::new(42);
+/// let roll = rng.range(1..7);
+/// assert!((1..7).contains(&roll));
+/// assert_eq!(Rng::new(42).range(1..7), roll);
+///
+/// let mut order = [1, 2, 3, 4];
+/// rng.shuffle(&mut order);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// Creates a generator from `seed`, expanding it into the full state with `SplitMix64` so
+    /// similar seeds still produce unrelated sequences.
+    pub const fn new(seed: u64) -> Self {
+        let mut seed = seed;
+        let mut state = [0; 4];
+        let mut i = 0;
+        while i < state.len() {
+            seed = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            state[i] = z ^ (z >> 31);
+            i += 1;
+        }
+        Self { state }
+    }
+
+    /// Creates a generator with a seed that differs in each process and thread.
+    pub fn from_entropy() -> Self {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        );
+        Self::new(hasher.finish())
+    }
+
+    /// Returns the next 64 random bits.
+    pub fn u64(&mut self) -> u64 {
+        let [s0, s1, s2, s3] = &mut self.state;
+        let result = s0.wrapping_add(*s3).rotate_left(23).wrapping_add(*s0);
+        let t = *s1 << 17;
+        *s2 ^= *s0;
+        *s3 ^= *s1;
+        *s1 ^= *s2;
+        *s0 ^= *s3;
+        *s2 ^= t;
+        *s3 = s3.rotate_left(45);
+        result
+    }
+
+    /// Returns the next 32 random bits.
+    pub fn u32(&mut self) -> u32 {
+        (self.u64() >> 32) as u32
+    }
+
+    /// Returns a uniformly distributed number in `[0, 1)`.
+    pub fn f64(&mut self) -> f64 {
+        // The top 53 bits fill the mantissa exactly, so every result is a multiple of 2^-53.
+        #[allow(clippy::cast_precision_loss)]
+        let mantissa = (self.u64() >> 11) as f64;
+        mantissa / 9_007_199_254_740_992.0
+    }
+
+    /// Returns a uniformly distributed integer in `range`, either `a..b` or `a..=b`, without the
+    /// bias of taking a modulo.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `range` is empty.
+    pub fn range<T: SampleInt>(&mut self, range: impl SampleRange<T>) -> T {
+        range.sample(self)
+    }
+
+    /// Returns a uniformly distributed integer in `[0, span)`, or any integer if `span` is 0.
+    fn below(&mut self, span: u64) -> u64 {
+        if span == 0 {
+            return self.u64();
+        }
+        // Lemire's method: the high half of `u64 * span` is uniform in `[0, span)` once the few
+        // low halves that would over-represent some results are rejected.
+        let threshold = span.wrapping_neg() % span;
+        loop {
+            let product = u128::from(self.u64()) * u128::from(span);
+            #[allow(clippy::cast_possible_truncation)]
+            if (product as u64) >= threshold {
+                return (product >> 64) as u64;
+            }
+        }
+    }
+
+    /// Returns whether a random event with `probability` happened.
+    pub fn chance(&mut self, probability: f64) -> bool {
+        self.f64() < probability
+    }
+
+    /// Shuffles `values` into a uniformly random order.
+    pub fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            values.swap(i, self.range(0..=i));
+        }
+    }
+
+    /// Fills `bytes` with random bytes.
+    pub fn fill_bytes(&mut self, bytes: &mut [u8]) {
+        for chunk in bytes.chunks_mut(8) {
+            let random = self.u64().to_le_bytes();
+            chunk.copy_from_slice(&random[..chunk.len()]);
+        }
+    }
+}
+
+impl Default for Rng {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+/// Integer types [`Rng::range`] can sample.
+pub trait SampleInt: Copy + PartialOrd {
+    /// Distance from `start` up to `self`, as an unsigned number.
+    fn offset_from(self, start: Self) -> u64;
+    /// The value `offset` above `self`.
+    #[must_use]
+    fn add_offset(self, offset: u64) -> Self;
+}
+
+macro_rules! impl_sample_int {
+    ($($ty:ty => $unsigned:ty),* $(,)?) => {$(
+        #[allow(
+            clippy::cast_lossless,
+            clippy::cast_sign_loss,
+            clippy::cast_possible_truncation,
+            clippy::cast_possible_wrap
+        )]
+        impl SampleInt for $ty {
+            fn offset_from(self, start: Self) -> u64 {
+                self.wrapping_sub(start) as $unsigned as u64
+            }
+
+            fn add_offset(self, offset: u64) -> Self {
+                self.wrapping_add(offset as $unsigned as $ty)
+            }
+        }
+    )*};
+}
+
+impl_sample_int!(
+    u8 => u8, u16 => u16, u32 => u32, u64 => u64, usize => usize,
+    i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize,
+);
+
+/// Ranges [`Rng::range`] accepts: half-open `a..b` and inclusive `a..=b`.
+pub trait SampleRange<T> {
+    /// Returns a uniformly distributed value in the range from `rng`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is empty.
+    fn sample(self, rng: &mut Rng) -> T;
+}
+
+impl<T: SampleInt> SampleRange<T> for Range<T> {
+    fn sample(self, rng: &mut Rng) -> T {
+        assert!(self.start < self.end, "empty range");
+        self.start
+            .add_offset(rng.below(self.end.offset_from(self.start)))
+    }
+}
+
+impl<T: SampleInt> SampleRange<T> for RangeInclusive<T> {
+    fn sample(self, rng: &mut Rng) -> T {
+        let (start, end) = self.into_inner();
+        assert!(start <= end, "empty range");
+        // A span wrapping to 0 covers every value of a 64-bit type.
+        start.add_offset(rng.below(end.offset_from(start).wrapping_add(1)))
+    }
+}
+
+thread_local! {
+    static THREAD_RNG: RefCell<Rng> = RefCell::new(Rng::from_entropy());
+}
+
+/// Calls `f` with the current thread's generator.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::rand;
+///
+/// let (x, y) = rand::with(|rng| (rng.f64(), rng.f64()));
+/// assert!(x < 1.0 && y < 1.0);
+/// ```
+pub fn with<R>(f: impl FnOnce(&mut Rng) -> R) -> R {
+    THREAD_RNG.with(|rng| f(&mut rng.borrow_mut()))
+}
+
+/// Reseeds the current thread's generator, making the free functions deterministic.
+pub fn seed(seed: u64) {
+    with(|rng| *rng = Rng::new(seed));
+}
+
+/// Returns the next 64 random bits from the current thread's generator.
+#[must_use]
+pub fn u64() -> u64 {
+    with(Rng::u64)
+}
+
+/// Returns a uniformly distributed integer in `range`, either `a..b` or `a..=b`, from the current
+/// thread's generator.
+///
+/// # Panics
+///
+/// Panics if `range` is empty.
+#[must_use]
+pub fn range<T: SampleInt>(range: impl SampleRange<T>) -> T {
+    with(|rng| rng.range(range))
+}
+
+/// Returns a uniformly distributed number in `[0, 1)` from the current thread's generator.
+#[must_use]
+pub fn f64() -> f64 {
+    with(Rng::f64)
+}
+
+/// Shuffles `values` with the current thread's generator.
+pub fn shuffle<T>(values: &mut [T]) {
+    with(|rng| rng.shuffle(values));
+}
+
+/// Fills `bytes` with random bytes from the current thread's generator.
+pub fn fill_bytes(bytes: &mut [u8]) {
+    with(|rng| rng.fill_bytes(bytes));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_sequence() {
+        let mut rng = Rng {
+            state: [1, 2, 3, 4],
+        };
+        let values: Vec<_> = (0..4).map(|_| rng.u64()).collect();
+        assert_eq!(
+            values,
+            [
+                41_943_041,
+                58_720_359,
+                3_588_806_011_781_223,
+                3_591_011_842_654_386
+            ]
+        );
+        assert_eq!(Rng::new(7), Rng::new(7));
+        assert_ne!(Rng::new(7).u64(), Rng::new(8).u64());
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(1);
+        let mut seen = [false; 6];
+        for _ in 0..1000 {
+            let roll = rng.range(1..7);
+            assert!((1..7).contains(&roll));
+            seen[roll - 1] = true;
+            let signed = rng.range(-3i8..=3);
+            assert!((-3..=3).contains(&signed));
+            let x = rng.f64();
+            assert!((0.0..1.0).contains(&x));
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        assert_eq!(rng.range(5u32..6), 5);
+        rng.range(0..=u64::MAX);
+        rng.range(i64::MIN..i64::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "empty range")]
+    fn empty_range() {
+        Rng::new(1).range(3..3);
+    }
+
+    #[test]
+    fn shuffles_and_fills() {
+        let mut rng = Rng::new(3);
+        let mut values: Vec<u32> = (0..50).collect();
+        rng.shuffle(&mut values);
+        assert_ne!(values, (0..50).collect::<Vec<_>>());
+        values.sort_unstable();
+        assert_eq!(values, (0..50).collect::<Vec<_>>());
+
+        let mut bytes = [0u8; 13];
+        rng.fill_bytes(&mut bytes);
+        assert!(bytes.iter().any(|&byte| byte != 0));
+
+        seed(9);
+        let first = (u64(), range(0..10), f64());
+        seed(9);
+        assert_eq!((u64(), range(0..10), f64()), first);
+    }
+}
@@ -0,0 +1,173 @@
+//! Rate limiting.
+//!
+//! A [`RateLimiter`] is a token bucket: tokens refill continuously at a configured rate up to a
+//! burst size, and every operation spends one. Loops throttled with it run at the configured rate
+//! on average, while bursts up to the bucket's size go through without waiting.
+
+use std::{
+    sync::{Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Tokens left and when they were last refilled.
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled: Instant,
+}
+
+/// A token bucket shared between threads.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::rate::RateLimiter;
+///
+/// // At most 1000 requests a second, in bursts of up to 10.
+/// let limiter = RateLimiter::new(1000.0, 10);
+/// for _ in 0..10 {
+///     assert!(limiter.try_acquire());
+/// }
+/// assert!(!limiter.try_acquire());
+/// limiter.acquire();
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct RateLimiter {
+    /// Tokens added per second.
+    rate: f64,
+    burst: u32,
+    bucket: Mutex<Bucket>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `rate` operations per second on average and up to `burst` at
+    /// once, starting with a full bucket.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` isn't positive and finite, or `burst` is 0.
+    pub fn new(rate: f64, burst: u32) -> Self {
+        assert!(rate > 0.0 && rate.is_finite(), "invalid rate: {rate}");
+        assert!(burst > 0, "invalid burst: 0");
+        Self {
+            rate,
+            burst,
+            bucket: Mutex::new(Bucket {
+                tokens: f64::from(burst),
+                refilled: Instant::now(),
+            }),
+        }
+    }
+
+    /// Operations allowed per second on average.
+    #[must_use]
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Operations allowed at once.
+    #[must_use]
+    pub fn burst(&self) -> u32 {
+        self.burst
+    }
+
+    /// Takes `tokens` if available, or returns how long until they will be.
+    fn take(&self, tokens: u32) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        let refill = now.duration_since(bucket.refilled).as_secs_f64() * self.rate;
+        bucket.tokens = (bucket.tokens + refill).min(f64::from(self.burst));
+        bucket.refilled = now;
+        let missing = f64::from(tokens) - bucket.tokens;
+        if missing <= 0.0 {
+            bucket.tokens -= f64::from(tokens);
+            return Ok(());
+        }
+        // Tiny rates can need longer than a `Duration` holds.
+        Err(Duration::try_from_secs_f64(missing / self.rate).unwrap_or(Duration::MAX))
+    }
+
+    /// Takes a token if one is available, without blocking.
+    #[must_use]
+    pub fn try_acquire(&self) -> bool {
+        self.try_acquire_n(1)
+    }
+
+    /// Takes `tokens` tokens if they're all available, without blocking.
+    #[must_use]
+    pub fn try_acquire_n(&self, tokens: u32) -> bool {
+        self.take(tokens).is_ok()
+    }
+
+    /// Takes a token, sleeping until one is available.
+    pub fn acquire(&self) {
+        self.acquire_n(1);
+    }
+
+    /// Takes `tokens` tokens, sleeping until they're all available, e.g. one per byte of a write
+    /// to throttle bandwidth.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tokens` is more than the burst size, which could never be available at once.
+    pub fn acquire_n(&self, tokens: u32) {
+        assert!(
+            tokens <= self.burst,
+            "{tokens} tokens exceed the burst size of {}",
+            self.burst
+        );
+        while let Err(wait) = self.take(tokens) {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn bursts_then_throttles() {
+        let limiter = RateLimiter::new(1.0, 3);
+        assert!(limiter.try_acquire_n(2));
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+        let wait = limiter.take(1).expect_err("empty bucket");
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1));
+        assert_eq!((limiter.rate(), limiter.burst()), (1.0, 3));
+
+        let limiter = RateLimiter::new(1e-20, 1);
+        assert!(limiter.try_acquire());
+        assert!(!limiter.try_acquire());
+        assert_eq!(limiter.take(1), Err(Duration::MAX));
+    }
+
+    #[test]
+    fn blocks_until_refilled() {
+        let limiter = Arc::new(RateLimiter::new(200.0, 1));
+        let start = Instant::now();
+        let workers: Vec<_> = (0..2)
+            .map(|_| {
+                let limiter = Arc::clone(&limiter);
+                std::thread::spawn(move || {
+                    for _ in 0..5 {
+                        limiter.acquire();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().expect("valid worker");
+        }
+        // The first token was in the bucket, and the other 9 refill every 5ms.
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceed the burst size")]
+    fn oversized_acquire() {
+        RateLimiter::new(1.0, 2).acquire_n(3);
+    }
+}
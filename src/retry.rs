@@ -0,0 +1,277 @@
+//! Retrying with exponential backoff.
+//!
+//! [`retry`] calls an operation until it succeeds, fails with an error the [`RetryPolicy`] doesn't
+//! retry, or runs out of attempts, sleeping between attempts for a delay which doubles each time up
+//! to a maximum. [`Jitter`] randomizes the delays, so clients which failed together don't all
+//! retry at the same moment.
+
+use crate::rand;
+use std::{fmt, sync::Arc, time::Duration};
+
+/// How delays between attempts are randomized.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Jitter {
+    /// Wait exactly the exponential delay.
+    None,
+    /// Wait a random time between zero and the exponential delay.
+    #[default]
+    Full,
+    /// Wait half the exponential delay plus a random time up to the other half.
+    Equal,
+    /// Wait a random time between the base delay and three times the previous delay, which
+    /// spreads retries out more than `Full` while growing about as fast.
+    Decorrelated,
+}
+
+/// Decides whether an error is worth retrying.
+type Retryable<E> = Arc<dyn Fn(&E) -> bool + Send + Sync>;
+
+/// When and how often [`retry`] tries again.
+///
+/// By default an operation is attempted 3 times, waiting around 100ms and then 200ms with
+/// [`Jitter::Full`], and every error is retried.
+///
+/// # Examples
+///
+/// ```
+/// use std::{io, time::Duration};
+/// use util_lib_rs::retry::{Jitter, RetryPolicy};
+///
+/// let policy = RetryPolicy::new()
+///     .max_attempts(5)
+///     .base_delay(Duration::from_millis(50))
+///     .max_delay(Duration::from_secs(2))
+///     .jitter(Jitter::Equal)
+///     .retry_if(|err: &io::Error| err.kind() != io::ErrorKind::NotFound);
+/// ```
+#[must_use]
+pub struct RetryPolicy<E> {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    jitter: Jitter,
+    retryable: Option<Retryable<E>>,
+}
+
+impl<E> Default for RetryPolicy<E> {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: Jitter::default(),
+            retryable: None,
+        }
+    }
+}
+
+impl<E> Clone for RetryPolicy<E> {
+    fn clone(&self) -> Self {
+        Self {
+            retryable: self.retryable.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<E> fmt::Debug for RetryPolicy<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("max_attempts", &self.max_attempts)
+            .field("base_delay", &self.base_delay)
+            .field("max_delay", &self.max_delay)
+            .field("jitter", &self.jitter)
+            .field("retry_if", &self.retryable.is_some())
+            .finish()
+    }
+}
+
+impl<E> RetryPolicy<E> {
+    /// Creates the default policy.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attempt the operation at most `attempts` times in total, at least once.
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    /// Wait `delay` before the first retry, doubling for each retry after it.
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    /// Never wait longer than `delay` between attempts.
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    /// Randomize delays with `jitter`.
+    pub fn jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Only retry errors for which `retryable` returns `true`, returning any other error at once.
+    pub fn retry_if(mut self, retryable: impl Fn(&E) -> bool + Send + Sync + 'static) -> Self {
+        self.retryable = Some(Arc::new(retryable));
+        self
+    }
+
+    /// Whether `err` is worth retrying.
+    fn is_retryable(&self, err: &E) -> bool {
+        self.retryable
+            .as_ref()
+            .is_none_or(|retryable| retryable(err))
+    }
+
+    /// Returns the delay before retry number `retry`, counting from 0, after waiting `previous`
+    /// before the last one.
+    fn delay(&self, retry: u32, previous: Duration) -> Duration {
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_delay);
+        let random_up_to = |max: Duration| {
+            Duration::from_nanos(rand::range(
+                0..=u64::try_from(max.as_nanos()).unwrap_or(u64::MAX),
+            ))
+        };
+        let delay = match self.jitter {
+            Jitter::None => exponential,
+            Jitter::Full => random_up_to(exponential),
+            Jitter::Equal => {
+                exponential / 2 + random_up_to(exponential.saturating_sub(exponential / 2))
+            }
+            Jitter::Decorrelated => {
+                let upper = previous.max(self.base_delay).saturating_mul(3);
+                self.base_delay + random_up_to(upper.saturating_sub(self.base_delay))
+            }
+        };
+        delay.min(self.max_delay)
+    }
+}
+
+/// The result of [`retry`] and how it came about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Retried<T, E> {
+    /// Result of the last attempt.
+    pub result: Result<T, E>,
+    /// Number of times the operation was called.
+    pub attempts: u32,
+    /// Total time slept between attempts.
+    pub delayed: Duration,
+}
+
+impl<T, E> Retried<T, E> {
+    /// Returns the result of the last attempt, dropping the metadata.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the last attempt if it failed.
+    pub fn into_result(self) -> Result<T, E> {
+        self.result
+    }
+}
+
+/// Calls `op` with the attempt number, starting at 1, until it succeeds or `policy` gives up,
+/// sleeping between attempts.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::retry::{retry, RetryPolicy};
+///
+/// let policy = RetryPolicy::new().base_delay(Duration::from_millis(1));
+/// let retried = retry(&policy, |attempt| if attempt < 3 { Err("busy") } else { Ok(attempt) });
+/// assert_eq!(retried.result, Ok(3));
+/// assert_eq!(retried.attempts, 3);
+/// ```
+pub fn retry<T, E>(
+    policy: &RetryPolicy<E>,
+    mut op: impl FnMut(u32) -> Result<T, E>,
+) -> Retried<T, E> {
+    let mut delayed = Duration::ZERO;
+    let mut previous = Duration::ZERO;
+    let mut attempt = 1;
+    loop {
+        let result = op(attempt);
+        match &result {
+            Err(err) if attempt < policy.max_attempts && policy.is_retryable(err) => {
+                previous = policy.delay(attempt - 1, previous);
+                std::thread::sleep(previous);
+                delayed += previous;
+                attempt += 1;
+            }
+            _ => {
+                return Retried {
+                    result,
+                    attempts: attempt,
+                    delayed,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gives_up() {
+        let policy = RetryPolicy::new()
+            .max_attempts(4)
+            .base_delay(Duration::ZERO);
+        let retried: Retried<(), _> = retry(&policy, Err);
+        assert_eq!((retried.result, retried.attempts), (Err(4), 4));
+
+        let policy = policy.retry_if(|&err: &u32| err < 2);
+        let retried: Retried<(), _> = retry(&policy, Err);
+        assert_eq!((retried.result, retried.attempts), (Err(2), 2));
+
+        let retried = retry(&RetryPolicy::<()>::new(), Ok);
+        assert_eq!(retried.into_result(), Ok(1));
+    }
+
+    #[test]
+    fn delays() {
+        rand::seed(5);
+        let policy = RetryPolicy::<()>::new()
+            .base_delay(Duration::from_millis(100))
+            .max_delay(Duration::from_secs(1))
+            .jitter(Jitter::None);
+        let delays: Vec<_> = (0..6)
+            .map(|retry| policy.delay(retry, Duration::ZERO).as_millis())
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+        assert_eq!(policy.delay(u32::MAX, Duration::ZERO).as_millis(), 1000);
+
+        for retry in 0..5 {
+            let exponential = policy.delay(retry, Duration::ZERO);
+            let full = policy
+                .clone()
+                .jitter(Jitter::Full)
+                .delay(retry, Duration::ZERO);
+            assert!(full <= exponential);
+            let equal = policy
+                .clone()
+                .jitter(Jitter::Equal)
+                .delay(retry, Duration::ZERO);
+            assert!(equal >= exponential / 2 && equal <= exponential);
+            let previous = Duration::from_millis(200);
+            let decorrelated = policy
+                .clone()
+                .jitter(Jitter::Decorrelated)
+                .delay(retry, previous);
+            assert!(decorrelated >= Duration::from_millis(100));
+            assert!(decorrelated <= Duration::from_millis(600));
+        }
+    }
+}
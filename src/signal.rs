@@ -0,0 +1,335 @@
+//! Termination signal handling.
+//!
+//! [`on_interrupt`] runs a handler when the process is asked to stop, by `SIGINT` (Ctrl-C) or
+//! `SIGTERM` on Unix, or a console control event such as Ctrl-C or closing the console on Windows.
+//! Handlers run on a thread of their own rather than in the signal handler, so they can lock,
+//! allocate and print. [`shutdown_flag`] returns a [`ShutdownFlag`] set by the same signals, for
+//! long-running loops to check so they can stop cleanly and print a final profile report.
+//!
+//! Once handlers are installed, the first signal no longer stops the process. A second one exits
+//! it immediately with status 130, so a program which doesn't notice the first can still be
+//! stopped.
+
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Condvar, Mutex, OnceLock, PoisonError,
+    },
+    time::Duration,
+};
+
+/// Exit status of a process stopped by a second signal, as shells report `SIGINT`.
+const EXIT_STATUS: i32 = 130;
+
+/// A handler run when a signal arrives.
+type Handler = Box<dyn Fn() + Send + Sync>;
+
+static HANDLERS: Mutex<Vec<Handler>> = Mutex::new(Vec::new());
+
+/// Whether a signal was already received, so the next one exits.
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Runs `handler` when the process first receives `SIGINT` or `SIGTERM`, or a Windows console
+/// control event, installing the signal handlers on the first call. Handlers run in the order
+/// they were added, on a thread of their own, and mustn't add more handlers.
+///
+/// # Examples
+///
+/// ```no_run
+/// use util_lib_rs::signal::on_interrupt;
+///
+/// # fn main() -> std::io::Result<()> {
+/// on_interrupt(|| eprintln!("interrupted, finishing the current batch"))?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the signal handlers can't be installed, or on platforms without signals.
+pub fn on_interrupt(handler: impl Fn() + Send + Sync + 'static) -> io::Result<()> {
+    install()?;
+    HANDLERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(Box::new(handler));
+    Ok(())
+}
+
+/// Returns a flag set when the process receives `SIGINT` or `SIGTERM`, or a Windows console
+/// control event.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use util_lib_rs::{performance, signal::shutdown_flag};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let shutdown = shutdown_flag()?;
+/// performance::profile_begin();
+/// while !shutdown.is_set() {
+///     util_lib_rs::profile!("batch");
+///     std::thread::sleep(Duration::from_millis(10));
+/// }
+/// performance::profile_end_and_print();
+/// # Ok(())
+/// # }
+/// ```
+///
+/// # Errors
+///
+/// Returns an error if the signal handlers can't be installed, or on platforms without signals.
+pub fn shutdown_flag() -> io::Result<ShutdownFlag> {
+    let flag = ShutdownFlag::new();
+    on_interrupt({
+        let flag = flag.clone();
+        move || flag.set()
+    })?;
+    Ok(flag)
+}
+
+/// A flag which threads can check or wait on to learn that the program should stop. Clones share
+/// the same flag.
+#[derive(Debug, Default, Clone)]
+#[must_use]
+pub struct ShutdownFlag {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    set: AtomicBool,
+    lock: Mutex<()>,
+    changed: Condvar,
+}
+
+impl ShutdownFlag {
+    /// Creates a flag which isn't set, and is only set by [`set`](Self::set). Use
+    /// [`shutdown_flag`] for one set by signals.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the flag, waking every thread waiting on it.
+    pub fn set(&self) {
+        let _lock = self
+            .shared
+            .lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.shared.set.store(true, Ordering::Release);
+        self.shared.changed.notify_all();
+    }
+
+    /// Whether the flag is set.
+    #[must_use]
+    pub fn is_set(&self) -> bool {
+        self.shared.set.load(Ordering::Acquire)
+    }
+
+    /// Blocks until the flag is set.
+    pub fn wait(&self) {
+        let lock = self
+            .shared
+            .lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let _lock = self
+            .shared
+            .changed
+            .wait_while(lock, |()| !self.is_set())
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+
+    /// Blocks until the flag is set or `timeout` passes, returning whether it's set.
+    #[must_use]
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let lock = self
+            .shared
+            .lock
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let _lock = self
+            .shared
+            .changed
+            .wait_timeout_while(lock, timeout, |()| !self.is_set())
+            .unwrap_or_else(PoisonError::into_inner);
+        self.is_set()
+    }
+}
+
+/// Runs every handler for the first signal, and exits on the next.
+fn dispatch() {
+    if RECEIVED.swap(true, Ordering::AcqRel) {
+        std::process::exit(EXIT_STATUS);
+    }
+    for handler in HANDLERS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+    {
+        handler();
+    }
+}
+
+/// Installs the signal handlers once, returning the error of the first attempt if it failed.
+fn install() -> io::Result<()> {
+    static INSTALLED: OnceLock<Result<(), (io::ErrorKind, String)>> = OnceLock::new();
+    INSTALLED
+        .get_or_init(|| platform::install().map_err(|err| (err.kind(), err.to_string())))
+        .clone()
+        .map_err(|(kind, message)| io::Error::new(kind, message))
+}
+
+#[cfg(unix)]
+mod platform {
+    use std::{
+        ffi::{c_int, c_void},
+        io,
+        sync::atomic::{AtomicI32, Ordering},
+        thread,
+    };
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+    /// `SIG_ERR`, returned by `signal` when it fails.
+    const SIG_ERR: usize = usize::MAX;
+
+    extern "C" {
+        fn pipe(fds: *mut c_int) -> c_int;
+        fn read(fd: c_int, buf: *mut c_void, count: usize) -> isize;
+        fn write(fd: c_int, buf: *const c_void, count: usize) -> isize;
+        fn signal(signum: c_int, handler: usize) -> usize;
+    }
+
+    /// Write end of the pipe waking the dispatch thread.
+    static WAKE_FD: AtomicI32 = AtomicI32::new(-1);
+
+    extern "C" fn handle_signal(_signum: c_int) {
+        let byte = 0u8;
+        // SAFETY: `write` is async-signal-safe, and `byte` is valid for one byte. A failed write
+        // only loses a wakeup while one is already pending.
+        unsafe {
+            write(WAKE_FD.load(Ordering::Relaxed), (&raw const byte).cast(), 1);
+        }
+    }
+
+    pub(super) fn install() -> io::Result<()> {
+        let mut fds = [0 as c_int; 2];
+        // SAFETY: `fds` is valid for the two descriptors `pipe` writes.
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let [read_fd, write_fd] = fds;
+        WAKE_FD.store(write_fd, Ordering::Relaxed);
+        thread::Builder::new()
+            .name("util_lib_rs-signals".to_string())
+            .spawn(move || loop {
+                let mut byte = 0u8;
+                // SAFETY: `byte` is valid for one byte, and `read_fd` stays open for good.
+                match unsafe { read(read_fd, (&raw mut byte).cast(), 1) } {
+                    1 => super::dispatch(),
+                    _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+                    _ => break,
+                }
+            })?;
+        for signum in [SIGINT, SIGTERM] {
+            // SAFETY: `handle_signal` only calls async-signal-safe functions.
+            if unsafe { signal(signum, handle_signal as extern "C" fn(c_int) as usize) } == SIG_ERR
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub(super) fn raise_terminate() {
+        extern "C" {
+            fn raise(signum: c_int) -> c_int;
+        }
+        // SAFETY: `SIGTERM` is handled by `handle_signal` once installed.
+        assert_eq!(unsafe { raise(SIGTERM) }, 0);
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use std::io;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(
+            handler: Option<unsafe extern "system" fn(u32) -> i32>,
+            add: i32,
+        ) -> i32;
+    }
+
+    /// Runs on a thread Windows creates for each console control event.
+    unsafe extern "system" fn handle_ctrl(_event: u32) -> i32 {
+        super::dispatch();
+        1
+    }
+
+    pub(super) fn install() -> io::Result<()> {
+        // SAFETY: `handle_ctrl` has the signature of a `PHANDLER_ROUTINE`.
+        if unsafe { SetConsoleCtrlHandler(Some(handle_ctrl), 1) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use std::io;
+
+    pub(super) fn install() -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "signals aren't supported on this platform",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn shutdown_flags() {
+        let flag = ShutdownFlag::new();
+        assert!(!flag.is_set());
+        assert!(!flag.wait_timeout(Duration::from_millis(1)));
+        let waiter = thread::spawn({
+            let flag = flag.clone();
+            move || flag.wait()
+        });
+        flag.set();
+        waiter.join().expect("valid thread");
+        assert!(flag.is_set() && flag.wait_timeout(Duration::ZERO));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn handles_signals() {
+        let flag = shutdown_flag().expect("installed handlers");
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        on_interrupt(move || {
+            let _ = sender
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .send(());
+        })
+        .expect("installed handlers");
+        platform::raise_terminate();
+        assert!(flag.wait_timeout(Duration::from_secs(10)));
+        receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("handler ran");
+    }
+}
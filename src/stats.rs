@@ -0,0 +1,814 @@
+//! Summary statistics of measurements.
+//!
+//! [`OnlineStats`] keeps the count, mean, variance, minimum and maximum of a stream of values in
+//! constant space, using Welford's algorithm so the variance stays accurate where the naive sum of
+//! squares would cancel out. [`Ema`] smooths a noisy series, such as frame times, with an
+//! exponential moving average. [`Histogram`] counts values in logarithmic buckets to answer
+//! percentile queries, such as the 99th percentile latency, within a configured relative error.
+//! [`RollingWindow`] keeps the last `N` values, such as recent frame times or request latencies,
+//! with their minimum, maximum and mean in constant time.
+
+use std::collections::VecDeque;
+
+/// Count, mean, variance, minimum and maximum of the values recorded so far.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::stats::OnlineStats;
+///
+/// let stats: OnlineStats = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0].into_iter().collect();
+/// assert_eq!(stats.count(), 8);
+/// assert_eq!(stats.mean(), 5.0);
+/// assert_eq!(stats.std_dev(), 2.0);
+/// assert_eq!((stats.min(), stats.max()), (Some(2.0), Some(9.0)));
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[must_use]
+pub struct OnlineStats {
+    count: u64,
+    mean: f64,
+    /// Sum of the squared differences from the mean.
+    m2: f64,
+    min: f64,
+    max: f64,
+}
+
+impl OnlineStats {
+    /// Creates statistics of no values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `value` to the statistics.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn record(&mut self, value: f64) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Adds the values of `other`, as if they'd been recorded here, e.g. to combine the statistics
+    /// of several threads.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn merge(&mut self, other: &Self) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 {
+            *self = *other;
+            return;
+        }
+        let count = self.count + other.count;
+        let delta = other.mean - self.mean;
+        let weight = other.count as f64 / count as f64;
+        self.mean += delta * weight;
+        self.m2 += other.m2 + delta * delta * self.count as f64 * weight;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.count = count;
+    }
+
+    /// Number of values recorded.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Mean of the values, or `0.0` if none were recorded.
+    #[must_use]
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population variance of the values, or `0.0` if none were recorded.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn variance(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.m2 / self.count as f64
+        }
+    }
+
+    /// Sample variance of the values, estimating the variance of the population they were drawn
+    /// from, or `0.0` with fewer than two values.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn sample_variance(&self) -> f64 {
+        if self.count < 2 {
+            0.0
+        } else {
+            self.m2 / (self.count - 1) as f64
+        }
+    }
+
+    /// Population standard deviation of the values.
+    #[must_use]
+    pub fn std_dev(&self) -> f64 {
+        self.variance().sqrt()
+    }
+
+    /// Sample standard deviation of the values.
+    #[must_use]
+    pub fn sample_std_dev(&self) -> f64 {
+        self.sample_variance().sqrt()
+    }
+
+    /// Smallest value, if any were recorded.
+    #[must_use]
+    pub fn min(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.min)
+    }
+
+    /// Largest value, if any were recorded.
+    #[must_use]
+    pub fn max(&self) -> Option<f64> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+impl Extend<f64> for OnlineStats {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, values: I) {
+        for value in values {
+            self.record(value);
+        }
+    }
+}
+
+impl FromIterator<f64> for OnlineStats {
+    fn from_iter<I: IntoIterator<Item = f64>>(values: I) -> Self {
+        let mut stats = Self::new();
+        stats.extend(values);
+        stats
+    }
+}
+
+/// An exponential moving average, weighting each new value by a fixed `alpha` and the previous
+/// average by `1 - alpha`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::stats::Ema;
+///
+/// let mut frame_ms = Ema::with_span(10);
+/// for ms in [16.0, 17.0, 16.5, 40.0, 16.2] {
+///     frame_ms.record(ms);
+/// }
+/// assert!(frame_ms.value().is_some_and(|ms| ms > 16.0 && ms < 40.0));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[must_use]
+pub struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    /// Creates an average weighting each new value by `alpha`, where higher values follow changes
+    /// faster and smooth less.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `alpha` is greater than 0 and at most 1.
+    pub fn new(alpha: f64) -> Self {
+        assert!(alpha > 0.0 && alpha <= 1.0, "invalid EMA alpha: {alpha}");
+        Self { alpha, value: None }
+    }
+
+    /// Creates an average which roughly follows the mean of the last `span` values, with an alpha
+    /// of `2 / (span + 1)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `span` is 0.
+    pub fn with_span(span: u32) -> Self {
+        assert!(span > 0, "invalid EMA span: 0");
+        Self::new(2.0 / (f64::from(span) + 1.0))
+    }
+
+    /// Weight of each new value.
+    #[must_use]
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    /// Adds `value`, returning the new average. The first value becomes the average as is.
+    pub fn record(&mut self, value: f64) -> f64 {
+        let average = match self.value {
+            Some(average) => average + self.alpha * (value - average),
+            None => value,
+        };
+        self.value = Some(average);
+        average
+    }
+
+    /// Current average, if any values were recorded.
+    #[must_use]
+    pub fn value(&self) -> Option<f64> {
+        self.value
+    }
+
+    /// Forgets every value recorded.
+    pub fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// Counts of values in log-linear buckets, for percentiles within a fixed relative error.
+///
+/// Values below `2^precision` have a bucket each, and every power of two above is split into
+/// `2^precision` buckets, so each percentile is within `1 / 2^precision` of the true value, at a
+/// cost of `8 * (65 - precision) * 2^precision` bytes once the first value is recorded: about
+/// 4 KiB at precision 3 and 116 KiB at the maximum of 8.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::stats::Histogram;
+///
+/// let mut latencies_us = Histogram::new(3);
+/// for latency in 1..=1000 {
+///     latencies_us.record(latency);
+/// }
+/// assert_eq!(latencies_us.count(), 1000);
+/// let p99 = latencies_us.percentile(99.0);
+/// assert!((960..=1040).contains(&p99));
+/// assert_eq!(latencies_us.max(), Some(1000));
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Histogram {
+    precision: u32,
+    /// Values per bucket, empty until the first value.
+    counts: Vec<u64>,
+    total: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Histogram {
+    /// Highest supported precision.
+    pub const MAX_PRECISION: u32 = 8;
+
+    /// Creates an empty histogram splitting every power of two into `2^precision` buckets, up to
+    /// [`MAX_PRECISION`](Self::MAX_PRECISION).
+    pub fn new(precision: u32) -> Self {
+        Self {
+            precision: precision.min(Self::MAX_PRECISION),
+            ..Self::default()
+        }
+    }
+
+    /// Returns the number of buckets per power of two, as a power of two.
+    #[must_use]
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    /// Counts `value` once.
+    #[inline]
+    pub fn record(&mut self, value: u64) {
+        self.record_n(value, 1);
+    }
+
+    /// Counts `value` `count` times.
+    pub fn record_n(&mut self, value: u64, count: u64) {
+        if count == 0 {
+            return;
+        }
+        if self.counts.is_empty() {
+            self.counts = vec![0; (65 - self.precision as usize) << self.precision];
+        }
+        if self.total == 0 {
+            self.min = value;
+            self.max = value;
+        }
+        let bucket = &mut self.counts[bucket_index(value, self.precision)];
+        *bucket = bucket.saturating_add(count);
+        self.total = self.total.saturating_add(count);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Number of values counted.
+    #[must_use]
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    /// Whether no values have been counted.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Smallest value counted, exactly.
+    #[must_use]
+    pub fn min(&self) -> Option<u64> {
+        (!self.is_empty()).then_some(self.min)
+    }
+
+    /// Largest value counted, exactly.
+    #[must_use]
+    pub fn max(&self) -> Option<u64> {
+        (!self.is_empty()).then_some(self.max)
+    }
+
+    /// Mean of the values counted, from their buckets, or `0.0` if none were.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        let sum: f64 = self
+            .buckets()
+            .map(|(value, count)| value as f64 * count as f64)
+            .sum();
+        sum / self.total as f64
+    }
+
+    /// Returns the value which `percentile` percent of the values are at most, e.g. `99.0` for the
+    /// 99th percentile, or `0` if no values were counted.
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.is_empty() {
+            return 0;
+        }
+        let rank =
+            ((percentile.clamp(0.0, 100.0) / 100.0 * self.total as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen == self.total {
+                // The extremes are known exactly, unlike the rest of their buckets.
+                return self.max;
+            }
+            if seen >= rank {
+                return bucket_value(index, self.precision).max(self.min);
+            }
+        }
+        self.max
+    }
+
+    /// Returns the midpoint and count of every non-empty bucket, smallest first.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .map(|(index, &count)| (bucket_value(index, self.precision), count))
+    }
+
+    /// Adds the values of `other` to this histogram. Values of a histogram with a different
+    /// precision are counted at their bucket's midpoint.
+    pub fn merge(&mut self, other: &Self) {
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            self.min = other.min;
+            self.max = other.max;
+        }
+        if other.precision == self.precision && !self.counts.is_empty() {
+            for (count, &other_count) in self.counts.iter_mut().zip(&other.counts) {
+                *count = count.saturating_add(other_count);
+            }
+            self.total = self.total.saturating_add(other.total);
+        } else {
+            for (value, count) in other.buckets() {
+                self.record_n(value, count);
+            }
+        }
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Forgets every value counted, keeping the buckets allocated.
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+        self.total = 0;
+    }
+}
+
+/// A value which can be kept in a [`RollingWindow`].
+pub trait Sample: Copy + PartialOrd {
+    /// Returns the value as a float, for means.
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_sample {
+    ($($ty:ty),*) => {
+        $(
+            impl Sample for $ty {
+                #[inline]
+                #[allow(clippy::cast_precision_loss, clippy::cast_lossless)]
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_sample!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64);
+
+/// The last `N` values recorded, with their minimum, maximum and mean in constant time.
+///
+/// Candidates for the minimum and maximum are kept in monotonic queues, so each value is added
+/// and dropped from them once, and a running sum gives the mean. Percentiles sort a copy of the
+/// window, so take `O(N)` time. A window of floats containing NaN has no meaningful minimum or
+/// maximum.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::stats::RollingWindow;
+///
+/// let mut latencies_us = RollingWindow::<u64, 4>::new();
+/// for latency in [120, 80, 950, 100, 90] {
+///     latencies_us.record(latency);
+/// }
+/// // The first value has left the window.
+/// assert_eq!(latencies_us.len(), 4);
+/// assert_eq!((latencies_us.min(), latencies_us.max()), (Some(80), Some(950)));
+/// assert_eq!(latencies_us.mean(), 305.0);
+/// assert_eq!(latencies_us.percentile(50.0), Some(90));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[must_use]
+pub struct RollingWindow<T, const N: usize> {
+    values: VecDeque<T>,
+    /// Indices and values of the candidates for the minimum, increasing from the front.
+    mins: VecDeque<(u64, T)>,
+    /// Indices and values of the candidates for the maximum, decreasing from the front.
+    maxes: VecDeque<(u64, T)>,
+    /// Number of values recorded, the index of the next one.
+    recorded: u64,
+    sum: f64,
+}
+
+impl<T: Sample, const N: usize> RollingWindow<T, N> {
+    /// Number of values the window keeps.
+    pub const CAPACITY: usize = N;
+
+    /// Creates an empty window. A window of no values fails to compile.
+    pub fn new() -> Self {
+        const { assert!(N > 0, "rolling window must keep at least one value") };
+        Self {
+            values: VecDeque::with_capacity(N),
+            mins: VecDeque::new(),
+            maxes: VecDeque::new(),
+            recorded: 0,
+            sum: 0.0,
+        }
+    }
+
+    /// Adds `value` to the window, returning the oldest value if it was full and dropped it.
+    pub fn record(&mut self, value: T) -> Option<T> {
+        let dropped = (self.values.len() == N).then(|| {
+            let oldest = self.recorded - N as u64;
+            if self.mins.front().is_some_and(|&(index, _)| index == oldest) {
+                self.mins.pop_front();
+            }
+            if self
+                .maxes
+                .front()
+                .is_some_and(|&(index, _)| index == oldest)
+            {
+                self.maxes.pop_front();
+            }
+            self.values.pop_front()
+        });
+        let dropped = dropped.flatten();
+
+        while self.mins.back().is_some_and(|&(_, min)| min >= value) {
+            self.mins.pop_back();
+        }
+        self.mins.push_back((self.recorded, value));
+        while self.maxes.back().is_some_and(|&(_, max)| max <= value) {
+            self.maxes.pop_back();
+        }
+        self.maxes.push_back((self.recorded, value));
+        self.values.push_back(value);
+        self.recorded += 1;
+
+        if self.recorded.is_multiple_of(N as u64) {
+            // Resum every time the window turns over, so rounding errors don't accumulate.
+            self.sum = self.values.iter().map(|value| value.to_f64()).sum();
+        } else {
+            self.sum += value.to_f64() - dropped.map_or(0.0, Sample::to_f64);
+        }
+        dropped
+    }
+
+    /// Number of values in the window, at most `N`.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Whether no values have been recorded.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Whether the window holds `N` values, so recording another drops the oldest.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.values.len() == N
+    }
+
+    /// Smallest value in the window.
+    #[must_use]
+    pub fn min(&self) -> Option<T> {
+        self.mins.front().map(|&(_, min)| min)
+    }
+
+    /// Largest value in the window.
+    #[must_use]
+    pub fn max(&self) -> Option<T> {
+        self.maxes.front().map(|&(_, max)| max)
+    }
+
+    /// Most recently recorded value.
+    #[must_use]
+    pub fn latest(&self) -> Option<T> {
+        self.values.back().copied()
+    }
+
+    /// Sum of the values in the window.
+    #[must_use]
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    /// Mean of the values in the window, or `0.0` if it's empty.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn mean(&self) -> f64 {
+        if self.is_empty() {
+            0.0
+        } else {
+            self.sum / self.values.len() as f64
+        }
+    }
+
+    /// Returns the value which `percentile` percent of the values in the window are at most, e.g.
+    /// `99.0` for the 99th percentile, or `None` if it's empty.
+    #[must_use]
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn percentile(&self, percentile: f64) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        let len = self.values.len();
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0 * len as f64).ceil() as usize).max(1);
+        let mut sorted: Vec<T> = self.values.iter().copied().collect();
+        let (_, &mut value, _) = sorted.select_nth_unstable_by(rank.min(len) - 1, |a, b| {
+            a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        Some(value)
+    }
+
+    /// Returns the values in the window, oldest first.
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = T> + ExactSizeIterator + '_ {
+        self.values.iter().copied()
+    }
+
+    /// Forgets every value recorded.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.mins.clear();
+        self.maxes.clear();
+        self.recorded = 0;
+        self.sum = 0.0;
+    }
+}
+
+impl<T: Sample, const N: usize> Default for RollingWindow<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Sample, const N: usize> Extend<T> for RollingWindow<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        for value in values {
+            self.record(value);
+        }
+    }
+}
+
+impl<T: Sample, const N: usize> FromIterator<T> for RollingWindow<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+        let mut window = Self::new();
+        window.extend(values);
+        window
+    }
+}
+
+/// Returns the bucket counting `value`. Values below `2^precision` have a bucket each, and every
+/// power of two above is split into `2^precision` buckets.
+#[allow(clippy::cast_possible_truncation)]
+fn bucket_index(value: u64, precision: u32) -> usize {
+    let sub_buckets = 1u64 << precision;
+    if value < sub_buckets {
+        return value as usize;
+    }
+    let exponent = value.ilog2();
+    let shift = exponent - precision;
+    let mantissa = (value >> shift) - sub_buckets;
+    ((u64::from(shift) + 1) * sub_buckets + mantissa) as usize
+}
+
+/// Returns the midpoint of the values counted by bucket `index`.
+fn bucket_value(index: usize, precision: u32) -> u64 {
+    let sub_buckets = 1u64 << precision;
+    let index = index as u64;
+    if index < sub_buckets {
+        return index;
+    }
+    let shift = index / sub_buckets - 1;
+    let lower = (sub_buckets + index % sub_buckets) << shift;
+    lower + ((1 << shift) >> 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn welford() {
+        let mut stats = OnlineStats::new();
+        assert_eq!(
+            (stats.mean(), stats.variance(), stats.min()),
+            (0.0, 0.0, None)
+        );
+        stats.record(3.0);
+        assert_eq!((stats.mean(), stats.sample_variance()), (3.0, 0.0));
+
+        // Values with a huge offset lose every digit of their variance in a naive sum of squares.
+        let offset = 1e9;
+        let stats: OnlineStats = [4.0, 7.0, 13.0, 16.0]
+            .into_iter()
+            .map(|value| offset + value)
+            .collect();
+        assert!((stats.mean() - (offset + 10.0)).abs() < 1e-6);
+        assert!((stats.sample_variance() - 30.0).abs() < 1e-6);
+        assert!((stats.sample_std_dev() - 30f64.sqrt()).abs() < 1e-6);
+        assert!((stats.variance() - 22.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn merges() {
+        let values = [1.0, 5.0, 2.0, 8.0, 3.0, 9.0, 4.0];
+        let all: OnlineStats = values.into_iter().collect();
+        let mut merged: OnlineStats = values[..3].iter().copied().collect();
+        merged.merge(&values[3..].iter().copied().collect());
+        merged.merge(&OnlineStats::new());
+        assert_eq!(merged.count(), all.count());
+        assert!((merged.mean() - all.mean()).abs() < 1e-12);
+        assert!((merged.variance() - all.variance()).abs() < 1e-12);
+        assert_eq!((merged.min(), merged.max()), (Some(1.0), Some(9.0)));
+
+        let mut empty = OnlineStats::new();
+        empty.merge(&all);
+        assert_eq!(empty, all);
+    }
+
+    #[test]
+    fn moving_average() {
+        let mut ema = Ema::new(0.5);
+        assert_eq!(ema.value(), None);
+        let averages: Vec<_> = [10.0, 20.0, 20.0]
+            .into_iter()
+            .map(|value| ema.record(value))
+            .collect();
+        assert_eq!(averages, [10.0, 15.0, 17.5]);
+        ema.reset();
+        ema.record(1.0);
+        assert_eq!(ema.value(), Some(1.0));
+        assert!((Ema::with_span(3).alpha() - 0.5).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        for precision in [0, 3, Histogram::MAX_PRECISION] {
+            let buckets = (65 - precision as usize) << precision;
+            let mut previous = 0;
+            for value in (0..4096).chain([u64::MAX / 3, u64::MAX - 1, u64::MAX]) {
+                let index = bucket_index(value, precision);
+                assert!(index >= previous && index < buckets);
+                previous = index;
+                // The midpoint is in the same bucket, within its relative error.
+                let midpoint = bucket_value(index, precision);
+                assert_eq!(bucket_index(midpoint, precision), index);
+                assert!(midpoint.abs_diff(value) <= value >> precision);
+            }
+        }
+    }
+
+    #[test]
+    fn histogram_percentiles() {
+        let mut histogram = Histogram::new(3);
+        assert_eq!((histogram.percentile(50.0), histogram.min()), (0, None));
+        histogram.record_n(100, 990);
+        histogram.record_n(10_000, 10);
+        assert_eq!(histogram.count(), 1000);
+        assert!((96..=104).contains(&histogram.percentile(50.0)));
+        assert!((96..=104).contains(&histogram.percentile(99.0)));
+        assert_eq!(histogram.percentile(100.0), 10_000);
+        assert_eq!(histogram.percentile(0.0), 100);
+        assert!((histogram.mean() - 199.0).abs() < 10.0);
+
+        let mut other = Histogram::new(3);
+        other.record(1);
+        histogram.merge(&other);
+        assert_eq!((histogram.count(), histogram.min()), (1001, Some(1)));
+        let mut coarse = Histogram::new(0);
+        coarse.merge(&histogram);
+        assert_eq!(coarse.count(), 1001);
+        assert_eq!(coarse.buckets().map(|(_, count)| count).sum::<u64>(), 1001);
+        assert_eq!(Histogram::new(99).precision(), Histogram::MAX_PRECISION);
+
+        histogram.clear();
+        assert!(histogram.is_empty());
+    }
+
+    #[test]
+    fn rolling_window() {
+        let mut window = RollingWindow::<u32, 3>::new();
+        assert_eq!((window.min(), window.percentile(50.0)), (None, None));
+        assert!(window.mean().abs() < f64::EPSILON);
+        assert_eq!(window.record(5), None);
+        assert_eq!(window.record(1), None);
+        assert_eq!(window.record(3), None);
+        assert!(window.is_full());
+        assert_eq!((window.min(), window.max()), (Some(1), Some(5)));
+        assert_eq!(window.record(4), Some(5));
+        assert_eq!((window.min(), window.max()), (Some(1), Some(4)));
+        assert_eq!(window.record(2), Some(1));
+        assert_eq!((window.min(), window.max()), (Some(2), Some(4)));
+        assert_eq!(window.iter().collect::<Vec<_>>(), [3, 4, 2]);
+        assert_eq!(window.latest(), Some(2));
+        assert!((window.mean() - 3.0).abs() < f64::EPSILON);
+        assert_eq!(window.percentile(0.0), Some(2));
+        assert_eq!(window.percentile(50.0), Some(3));
+        assert_eq!(window.percentile(100.0), Some(4));
+        window.clear();
+        assert!(window.is_empty());
+        assert_eq!(window.max(), None);
+        assert_eq!(RollingWindow::<u8, 7>::CAPACITY, 7);
+    }
+
+    #[test]
+    fn rolling_window_matches_naive() {
+        let mut window = RollingWindow::<f64, 16>::new();
+        let mut state = 1u64;
+        let mut values = Vec::new();
+        for _ in 0..1000 {
+            state = state
+                .wrapping_mul(6_364_136_223_846_793_005)
+                .wrapping_add(1);
+            #[allow(clippy::cast_precision_loss)]
+            let value = (state >> 40) as f64 / 7.0;
+            window.record(value);
+            values.push(value);
+            let recent = &values[values.len().saturating_sub(16)..];
+            let min = recent.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = recent.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            #[allow(clippy::cast_precision_loss)]
+            let mean = recent.iter().sum::<f64>() / recent.len() as f64;
+            assert_eq!((window.min(), window.max()), (Some(min), Some(max)));
+            assert!((window.mean() - mean).abs() < 1e-6 * mean.abs().max(1.0));
+        }
+        let window: RollingWindow<i32, 2> = [-1, 7, -3].into_iter().collect();
+        assert_eq!(
+            (window.min(), window.max(), window.mean()),
+            (Some(-3), Some(7), 2.0)
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! String helpers.
+//!
+//! Widths and distances here count characters rather than bytes, so text with accents or symbols
+//! such as `µs` is measured as it's displayed, though wide characters such as CJK aren't counted
+//! as two columns. [`levenshtein`] and [`closest_match`] suggest what a mistyped name was meant to
+//! be, and the case conversions turn identifiers between `snake_case`, `kebab-case`, `camelCase`
+//! and `PascalCase`.
+
+use std::borrow::Cow;
+
+/// Cuts `text` to at most `width` characters, ending it with `…` if anything was cut.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::truncate_with_ellipsis;
+///
+/// assert_eq!(truncate_with_ellipsis("parse_json", 6), "parse…");
+/// assert_eq!(truncate_with_ellipsis("parse", 6), "parse");
+/// ```
+#[must_use]
+pub fn truncate_with_ellipsis(text: &str, width: usize) -> Cow<'_, str> {
+    match text.char_indices().nth(width) {
+        None => Cow::Borrowed(text),
+        Some(_) if width == 0 => Cow::Borrowed(""),
+        Some(_) => {
+            let end = text
+                .char_indices()
+                .nth(width - 1)
+                .map_or(text.len(), |(end, _)| end);
+            Cow::Owned(format!("{}…", &text[..end]))
+        }
+    }
+}
+
+/// Wraps `text` into lines of at most `width` characters, breaking between words and splitting
+/// words longer than a line. Line breaks in `text` are kept, and other runs of whitespace become
+/// one space.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::wrap;
+///
+/// assert_eq!(
+///     wrap("Threads to use for parsing input files", 16),
+///     ["Threads to use", "for parsing", "input files"]
+/// );
+/// ```
+#[must_use]
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut line = String::new();
+        let mut line_width = 0;
+        for word in paragraph.split_whitespace() {
+            let mut word = word;
+            let mut word_width = word.chars().count();
+            if line_width > 0 && line_width + 1 + word_width <= width {
+                line.push(' ');
+                line.push_str(word);
+                line_width += 1 + word_width;
+                continue;
+            }
+            if line_width > 0 {
+                lines.push(std::mem::take(&mut line));
+            }
+            while word_width > width {
+                let end = word
+                    .char_indices()
+                    .nth(width)
+                    .map_or(word.len(), |(end, _)| end);
+                lines.push(word[..end].to_string());
+                word = &word[end..];
+                word_width -= width;
+            }
+            line.push_str(word);
+            line_width = word_width;
+        }
+        lines.push(line);
+    }
+    lines
+}
+
+/// Returns the number of single-character insertions, deletions and substitutions turning `a`
+/// into `b`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::levenshtein;
+///
+/// assert_eq!(levenshtein("kitten", "sitting"), 3);
+/// assert_eq!(levenshtein("threads", "threads"), 0);
+/// ```
+#[must_use]
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != b);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+/// Returns the candidate closest to `target`, if any is close enough to be what was meant: within
+/// a third of its length in [`levenshtein`] distance, and at least one edit. Ties go to the first.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::closest_match;
+///
+/// let options = ["threads", "verbose", "include"];
+/// assert_eq!(closest_match("thread", options), Some("threads"));
+/// assert_eq!(closest_match("fast", options), None);
+/// ```
+#[must_use]
+pub fn closest_match<'a>(
+    target: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let max_distance = (target.chars().count() / 3).max(1);
+    candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(target, candidate), candidate))
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Splits an identifier into lowercase words, at non-alphanumeric characters and at changes of
+/// case, keeping acronyms together: `HTTPServer_v2` becomes `http`, `server` and `v2`.
+fn words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let chars: Vec<char> = text.chars().collect();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase() && i > 0 && !word.is_empty() {
+            let previous = chars[i - 1];
+            let next_lower = chars.get(i + 1).is_some_and(|next| next.is_lowercase());
+            // `aB` starts a word, as does the `S` of `HTTPServer`.
+            if previous.is_lowercase()
+                || previous.is_numeric()
+                || (previous.is_uppercase() && next_lower)
+            {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Capitalizes the first character of `word`.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+/// Converts an identifier to `snake_case`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::to_snake_case;
+///
+/// assert_eq!(to_snake_case("parseHTTPHeader"), "parse_http_header");
+/// assert_eq!(to_snake_case("max-retries"), "max_retries");
+/// ```
+#[must_use]
+pub fn to_snake_case(text: &str) -> String {
+    words(text).join("_")
+}
+
+/// Converts an identifier to `kebab-case`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::to_kebab_case;
+///
+/// assert_eq!(to_kebab_case("MaxRetries"), "max-retries");
+/// ```
+#[must_use]
+pub fn to_kebab_case(text: &str) -> String {
+    words(text).join("-")
+}
+
+/// Converts an identifier to `camelCase`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::to_camel_case;
+///
+/// assert_eq!(to_camel_case("timeout_ms"), "timeoutMs");
+/// ```
+#[must_use]
+pub fn to_camel_case(text: &str) -> String {
+    let words = words(text);
+    let mut words = words.iter();
+    let first = words.next().cloned().unwrap_or_default();
+    words.fold(first, |camel, word| camel + &capitalize(word))
+}
+
+/// Converts an identifier to `PascalCase`.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::to_pascal_case;
+///
+/// assert_eq!(to_pascal_case("chrome-trace"), "ChromeTrace");
+/// ```
+#[must_use]
+pub fn to_pascal_case(text: &str) -> String {
+    words(text).iter().map(|word| capitalize(word)).collect()
+}
+
+/// Returns the longest prefix shared by every string, or `""` if there are none.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::common_prefix;
+///
+/// assert_eq!(common_prefix(["net::read", "net::write"]), "net::");
+/// assert_eq!(common_prefix(["parse", "lex"]), "");
+/// ```
+#[must_use]
+pub fn common_prefix<'a>(strings: impl IntoIterator<Item = &'a str>) -> &'a str {
+    let mut strings = strings.into_iter();
+    let Some(mut prefix) = strings.next() else {
+        return "";
+    };
+    for string in strings {
+        let len = prefix
+            .char_indices()
+            .zip(string.chars())
+            .find(|&((_, a), b)| a != b)
+            .map_or(prefix.len().min(string.len()), |((end, _), _)| end);
+        prefix = &prefix[..len];
+    }
+    prefix
+}
+
+/// Returns the length in bytes of the prefix `a` and `b` share, which ends on a character
+/// boundary of both.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::str_util::common_prefix_len;
+///
+/// assert_eq!(common_prefix_len("profile_begin", "profile_end"), 8);
+/// ```
+#[must_use]
+pub fn common_prefix_len(a: &str, b: &str) -> usize {
+    common_prefix([a, b]).len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates() {
+        assert_eq!(truncate_with_ellipsis("µs/call", 3), "µs…");
+        assert_eq!(truncate_with_ellipsis("abc", 3), "abc");
+        assert_eq!(truncate_with_ellipsis("abcd", 1), "…");
+        assert_eq!(truncate_with_ellipsis("abcd", 0), "");
+        assert_eq!(truncate_with_ellipsis("", 0), "");
+        assert!(matches!(truncate_with_ellipsis("abc", 5), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn wraps() {
+        assert_eq!(
+            wrap("one two  three\n\nfour", 7),
+            ["one two", "three", "", "four"]
+        );
+        assert_eq!(wrap("abcdefgh ij", 3), ["abc", "def", "gh", "ij"]);
+        assert_eq!(wrap("a bcdefg", 3), ["a", "bcd", "efg"]);
+        assert_eq!(wrap("", 10), Vec::<String>::new());
+        assert_eq!(wrap("ab", 0), ["a", "b"]);
+        assert_eq!(wrap("émigré café", 6), ["émigré", "café"]);
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("abc", ""), 3);
+        assert_eq!(levenshtein("flaw", "lawn"), 2);
+        assert_eq!(levenshtein("µs", "ms"), 1);
+        assert_eq!(
+            closest_match("verbosee", ["threads", "verbose"]),
+            Some("verbose")
+        );
+        assert_eq!(closest_match("ab", ["xy", "ac", "ad"]), Some("ac"));
+        assert_eq!(closest_match("x", std::iter::empty()), None);
+    }
+
+    #[test]
+    fn cases() {
+        assert_eq!(to_snake_case("HTTPServer_v2"), "http_server_v2");
+        assert_eq!(to_snake_case("  already_snake  "), "already_snake");
+        assert_eq!(to_snake_case("Version2Beta"), "version2_beta");
+        assert_eq!(to_kebab_case("displayTimeUnit"), "display-time-unit");
+        assert_eq!(to_camel_case("Display time unit"), "displayTimeUnit");
+        assert_eq!(to_camel_case("URL"), "url");
+        assert_eq!(to_pascal_case("json_value"), "JsonValue");
+        assert_eq!(to_pascal_case(""), "");
+        assert_eq!(to_snake_case("ÉcoleNormale"), "école_normale");
+    }
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(common_prefix(["net::read"]), "net::read");
+        assert_eq!(common_prefix(std::iter::empty()), "");
+        assert_eq!(common_prefix(["ab", "abc", "a"]), "a");
+        assert_eq!(common_prefix(["abc", "abd"]), "ab");
+        assert_eq!(common_prefix(["é", "è"]), "");
+        assert_eq!(common_prefix_len("abc", "abc"), 3);
+    }
+}
@@ -0,0 +1,17 @@
+//! Synchronization primitives.
+//!
+//! [`Lazy`] and [`OnceValue`] hold values initialized once, on first use, and can be used in
+//! `static`s; [`OnceValue::get_or_try_init`] leaves the value unset if initialization fails, so it
+//! can be retried.
+//!
+//! [`spsc::Ring`] is a wait-free ring buffer for exactly one producer and one consumer, and
+//! [`mpsc::Queue`] is a lock-free queue for any number of producers and one consumer. Both have a
+//! fixed capacity, never allocate after they're created, and hand values back instead of blocking
+//! when full, so the caller decides whether to retry, drop or fall back. Batch pushes and pops
+//! move many values with one synchronizing store.
+
+mod lazy;
+pub mod mpsc;
+pub mod spsc;
+
+pub use lazy::{Lazy, OnceValue};
@@ -0,0 +1,293 @@
+//! Values initialized once, on first use.
+
+use std::{
+    fmt,
+    ops::Deref,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+/// A value initialized by a closure the first time it's used, from any thread.
+///
+/// `Lazy::new` is `const`, so a `static` can hold a value which needs allocating or computing,
+/// such as a lock around a table, without wrapping it in an `Option` to fill in later.
+///
+/// # Examples
+///
+/// ```
+/// use std::{collections::HashMap, sync::Mutex};
+/// use util_lib_rs::sync::Lazy;
+///
+/// static UNITS: Lazy<HashMap<&str, u64>> = Lazy::new(|| HashMap::from([("KiB", 1 << 10)]));
+/// static SEEN: Lazy<Mutex<Vec<u32>>> = Lazy::new(|| Mutex::new(Vec::new()));
+///
+/// assert!(Lazy::get(&UNITS).is_none());
+/// assert_eq!(UNITS["KiB"], 1024);
+/// SEEN.lock().unwrap().push(1);
+/// ```
+///
+/// # Panics
+///
+/// Using the value panics if an earlier initialization panicked.
+pub struct Lazy<T, F = fn() -> T> {
+    value: OnceLock<T>,
+    init: Mutex<Option<F>>,
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Creates a value to be initialized by `init` on first use.
+    pub const fn new(init: F) -> Self {
+        Self {
+            value: OnceLock::new(),
+            init: Mutex::new(Some(init)),
+        }
+    }
+
+    /// Initializes the value if it isn't yet, and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an earlier initialization panicked.
+    pub fn force(this: &Self) -> &T {
+        this.value.get_or_init(|| {
+            let init = this
+                .init
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+                .expect("lazy value's initialization panicked");
+            init()
+        })
+    }
+
+    /// Returns the value if it's been initialized, without initializing it.
+    #[must_use]
+    pub fn get(this: &Self) -> Option<&T> {
+        this.value.get()
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        Self::force(self)
+    }
+}
+
+impl<T: Default> Default for Lazy<T> {
+    /// Creates a value initialized with `T::default` on first use.
+    fn default() -> Self {
+        Self::new(T::default)
+    }
+}
+
+impl<T: fmt::Debug, F> fmt::Debug for Lazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some(value) => f.debug_tuple("Lazy").field(value).finish(),
+            None => f.write_str("Lazy(<uninit>)"),
+        }
+    }
+}
+
+/// A value set at most once, by whichever thread gets to it first.
+///
+/// Unlike [`Lazy`], the closure initializing it is given where it's used, and
+/// [`get_or_try_init`](Self::get_or_try_init) returns an error from it instead of storing a value,
+/// so a later call can try again.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::sync::OnceValue;
+///
+/// static CONFIG_PATH: OnceValue<String> = OnceValue::new();
+///
+/// let err = CONFIG_PATH.get_or_try_init(|| std::env::var("NO_SUCH_VARIABLE_SET"));
+/// assert!(err.is_err() && CONFIG_PATH.get().is_none());
+/// let path = CONFIG_PATH.get_or_try_init(|| Ok::<_, std::env::VarError>("app.toml".into()));
+/// assert_eq!(path.map(String::as_str), Ok("app.toml"));
+/// assert_eq!(CONFIG_PATH.set("other.toml".into()), Err("other.toml".into()));
+/// ```
+pub struct OnceValue<T> {
+    value: OnceLock<T>,
+    /// Held while a fallible initialization runs, so only one runs at a time.
+    init: Mutex<()>,
+}
+
+impl<T> OnceValue<T> {
+    /// Creates an empty value.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            value: OnceLock::new(),
+            init: Mutex::new(()),
+        }
+    }
+
+    /// Returns the value, if it's been set.
+    #[must_use]
+    pub fn get(&self) -> Option<&T> {
+        self.value.get()
+    }
+
+    /// Returns the value for modification, if it's been set.
+    #[must_use]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.value.get_mut()
+    }
+
+    /// Sets the value if it hasn't been set yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns `value` if the value was already set.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        self.value.set(value)
+    }
+
+    /// Returns the value, setting it to the result of `init` first if it isn't set. If several
+    /// threads call this at once, one runs `init` and the others wait for it.
+    ///
+    /// `init` mustn't use this value itself, which deadlocks.
+    pub fn get_or_init(&self, init: impl FnOnce() -> T) -> &T {
+        self.value.get_or_init(init)
+    }
+
+    /// Returns the value, setting it to the result of `init` first if it isn't set. If several
+    /// threads call this at once, one runs `init` and the others wait for it, then run their own
+    /// `init` if it failed.
+    ///
+    /// `init` mustn't use this value itself, which deadlocks.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `init`, leaving the value unset.
+    pub fn get_or_try_init<E>(&self, init: impl FnOnce() -> Result<T, E>) -> Result<&T, E> {
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let _init = self.init.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(value) = self.value.get() {
+            return Ok(value);
+        }
+        let value = init()?;
+        Ok(self.value.get_or_init(|| value))
+    }
+
+    /// Takes the value out, leaving it unset.
+    pub fn take(&mut self) -> Option<T> {
+        self.value.take()
+    }
+
+    /// Returns the value, if it's been set.
+    pub fn into_inner(self) -> Option<T> {
+        self.value.into_inner()
+    }
+}
+
+impl<T> Default for OnceValue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> From<T> for OnceValue<T> {
+    /// Creates a value which is already set.
+    fn from(value: T) -> Self {
+        Self {
+            value: OnceLock::from(value),
+            init: Mutex::new(()),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for OnceValue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.value.get() {
+            Some(value) => f.debug_tuple("OnceValue").field(value).finish(),
+            None => f.write_str("OnceValue(<unset>)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    #[test]
+    fn lazy() {
+        static CALLS: AtomicUsize = AtomicUsize::new(0);
+        static VALUE: Lazy<Vec<u32>> = Lazy::new(|| {
+            CALLS.fetch_add(1, Ordering::Relaxed);
+            vec![1, 2, 3]
+        });
+        assert_eq!(format!("{VALUE:?}"), "Lazy(<uninit>)");
+        let threads: Vec<_> = (0..4).map(|_| thread::spawn(|| VALUE.len())).collect();
+        for thread in threads {
+            assert_eq!(thread.join().expect("valid thread"), 3);
+        }
+        assert_eq!(CALLS.load(Ordering::Relaxed), 1);
+        assert_eq!(Lazy::get(&VALUE), Some(&vec![1, 2, 3]));
+        assert_eq!(format!("{VALUE:?}"), "Lazy([1, 2, 3])");
+
+        let captured = String::from("owned");
+        let lazy = Lazy::new(move || captured.len());
+        assert_eq!(*lazy, 5);
+        assert_eq!(*Lazy::<u8>::default(), 0);
+    }
+
+    #[test]
+    fn lazy_after_panic() {
+        let lazy: Lazy<u8> = Lazy::new(|| panic!("failed"));
+        assert!(std::panic::catch_unwind(|| *lazy).is_err());
+        assert!(std::panic::catch_unwind(|| *lazy).is_err());
+        assert_eq!(Lazy::get(&lazy), None);
+    }
+
+    #[test]
+    fn once_value() {
+        let value = OnceValue::new();
+        assert_eq!(value.get_or_try_init(|| Err("not yet")), Err("not yet"));
+        assert_eq!(value.get(), None);
+        assert_eq!(value.get_or_init(|| 1), &1);
+        assert_eq!(value.get_or_try_init(|| Err::<u8, _>("unused")), Ok(&1));
+        assert_eq!(value.set(2), Err(2));
+        assert_eq!(format!("{value:?}"), "OnceValue(1)");
+
+        let mut value = OnceValue::from(3);
+        *value.get_mut().expect("set") += 1;
+        assert_eq!(value.take(), Some(4));
+        assert_eq!(format!("{value:?}"), "OnceValue(<unset>)");
+        assert_eq!(value.into_inner(), None);
+
+        let value = Arc::new(OnceValue::new());
+        let calls = Arc::new(AtomicUsize::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|id| {
+                let (value, calls) = (Arc::clone(&value), Arc::clone(&calls));
+                thread::spawn(move || {
+                    *value
+                        .get_or_try_init(|| {
+                            calls.fetch_add(1, Ordering::Relaxed);
+                            Ok::<_, ()>(id)
+                        })
+                        .expect("initialized")
+                })
+            })
+            .collect();
+        let ids: Vec<_> = threads
+            .into_iter()
+            .map(|thread| thread.join().expect("valid thread"))
+            .collect();
+        assert!(ids.windows(2).all(|pair| pair[0] == pair[1]));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,432 @@
+//! A bounded, lock-free, multi-producer single-consumer queue.
+//!
+//! [`Queue::with_capacity`] returns a [`Sender`], which can be cloned and shared between any
+//! number of threads, and a [`Receiver`] for one thread to pop from. Producers claim slots with a
+//! compare-and-swap, so a push is lock-free rather than wait-free: it may retry while other
+//! producers race for the same slot, but never waits on a thread which has stalled. Popping is
+//! wait-free.
+
+use std::{
+    cell::UnsafeCell,
+    fmt, hint,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+struct Slot<T> {
+    value: UnsafeCell<MaybeUninit<T>>,
+    /// Whether `value` was written and not yet popped.
+    ready: AtomicBool,
+}
+
+/// The storage shared by the halves of a queue.
+///
+/// # Examples
+///
+/// ```
+/// use std::thread;
+/// use util_lib_rs::sync::mpsc::Queue;
+///
+/// let (sender, mut receiver) = Queue::with_capacity(64);
+/// let producers: Vec<_> = (0..4)
+///     .map(|id| {
+///         let sender = sender.clone();
+///         thread::spawn(move || sender.push(id).unwrap())
+///     })
+///     .collect();
+/// for producer in producers {
+///     producer.join().unwrap();
+/// }
+/// let mut ids = Vec::new();
+/// receiver.pop_batch(&mut ids, usize::MAX);
+/// ids.sort_unstable();
+/// assert_eq!(ids, [0, 1, 2, 3]);
+/// ```
+pub struct Queue<T> {
+    slots: Box<[Slot<T>]>,
+    /// Total number of values popped, wrapping on overflow. Only written by the consumer.
+    head: AtomicUsize,
+    /// Total number of slots claimed by producers, wrapping on overflow, some of which may not be
+    /// written yet.
+    tail: AtomicUsize,
+}
+
+// SAFETY: A slot is written only by the producer which claimed it, and read only by the consumer
+// once `ready` publishes it. Claims only cover slots the consumer has released through `head`.
+unsafe impl<T: Send> Sync for Queue<T> {}
+
+impl<T> Queue<T> {
+    /// Creates a queue holding up to `capacity` values, at least one, rounded up to a power of
+    /// two, returning its two halves.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        let queue = Arc::new(Self {
+            slots: (0..capacity.max(1).next_power_of_two())
+                .map(|_| Slot {
+                    value: UnsafeCell::new(MaybeUninit::uninit()),
+                    ready: AtomicBool::new(false),
+                })
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        });
+        (Sender(Arc::clone(&queue)), Receiver(queue))
+    }
+
+    /// Returns the slot for the wrapping counter `index`, which stays in place across the wrap
+    /// since the number of slots is a power of two.
+    fn slot(&self, index: usize) -> &Slot<T> {
+        &self.slots[index & (self.slots.len() - 1)]
+    }
+
+    /// Claims up to `wanted` consecutive free slots, returning the position of the first and how
+    /// many were claimed, or `None` if the queue is full.
+    fn claim(&self, wanted: usize) -> Option<(usize, usize)> {
+        let capacity = self.slots.len();
+        loop {
+            let tail = self.tail.load(Ordering::Acquire);
+            let used = tail.wrapping_sub(self.head.load(Ordering::Acquire));
+            // A `tail` loaded before the consumer moved past it is stale, and claiming from it
+            // would fail anyway.
+            if used > capacity {
+                hint::spin_loop();
+                continue;
+            }
+            let count = wanted.min(capacity - used);
+            if count == 0 {
+                return None;
+            }
+            if self
+                .tail
+                .compare_exchange_weak(
+                    tail,
+                    tail.wrapping_add(count),
+                    Ordering::AcqRel,
+                    Ordering::Relaxed,
+                )
+                .is_ok()
+            {
+                return Some((tail, count));
+            }
+            hint::spin_loop();
+        }
+    }
+}
+
+impl<T> Drop for Queue<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.get_mut(), *self.tail.get_mut());
+        let mut index = head;
+        while index != tail {
+            let mask = self.slots.len() - 1;
+            let slot = &mut self.slots[index & mask];
+            if *slot.ready.get_mut() {
+                // SAFETY: A ready slot holds a value which was pushed but never popped.
+                unsafe { slot.value.get_mut().assume_init_drop() };
+            }
+            index = index.wrapping_add(1);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Queue<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("capacity", &self.slots.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The sending half of a queue, which can be cloned to push from several threads.
+pub struct Sender<T>(Arc<Queue<T>>);
+
+impl<T> Sender<T> {
+    /// Pushes `value` without blocking, handing it back if the queue is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns `value` if the queue is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let Some((position, _)) = self.0.claim(1) else {
+            return Err(value);
+        };
+        self.write(position, value);
+        Ok(())
+    }
+
+    /// Moves as many values from the front of `values` as there's room for, claiming their slots
+    /// together, and returns how many were pushed. Values from one batch are popped in order, with
+    /// no values from other producers between them.
+    pub fn push_batch(&self, values: &mut Vec<T>) -> usize {
+        let Some((position, count)) = self.0.claim(values.len()) else {
+            return 0;
+        };
+        for (offset, value) in values.drain(..count).enumerate() {
+            self.write(position.wrapping_add(offset), value);
+        }
+        count
+    }
+
+    /// Writes `value` into the claimed slot at `position` and publishes it.
+    fn write(&self, position: usize, value: T) {
+        let slot = self.0.slot(position);
+        // SAFETY: The slot was claimed by this producer alone, after the consumer released it.
+        unsafe { (*slot.value.get()).write(value) };
+        slot.ready.store(true, Ordering::Release);
+    }
+
+    /// The most values the queue holds.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.0.slots.len()
+    }
+
+    /// How many slots are claimed and not yet popped, including any still being written. Other
+    /// threads may push or pop at any time.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        let head = self.0.head.load(Ordering::Acquire);
+        self.0
+            .tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(head)
+            .min(self.capacity())
+    }
+
+    /// Whether no slots are claimed and waiting to be popped.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the next push would fail, unless the consumer pops first.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// The receiving half of a queue.
+pub struct Receiver<T>(Arc<Queue<T>>);
+
+impl<T> Receiver<T> {
+    /// Pops the oldest value without blocking, if it's been written. Returns `None` while the
+    /// producer which claimed the oldest slot is still writing it, even if later values are ready.
+    pub fn pop(&mut self) -> Option<T> {
+        let queue = &*self.0;
+        let head = queue.head.load(Ordering::Relaxed);
+        let value = Self::take(queue, head)?;
+        queue.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Pops up to `max` of the oldest written values onto the end of `out`, releasing their slots
+    /// together, and returns how many were popped.
+    pub fn pop_batch(&mut self, out: &mut Vec<T>, max: usize) -> usize {
+        let queue = &*self.0;
+        let head = queue.head.load(Ordering::Relaxed);
+        let mut count = 0;
+        while count < max {
+            let Some(value) = Self::take(queue, head.wrapping_add(count)) else {
+                break;
+            };
+            out.push(value);
+            count += 1;
+        }
+        queue
+            .head
+            .store(head.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// Reads the value at `position` if it's been written, marking its slot empty.
+    fn take(queue: &Queue<T>, position: usize) -> Option<T> {
+        let slot = queue.slot(position);
+        if !slot.ready.load(Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: `ready` shows a producer wrote the slot, and only this consumer reads from it.
+        let value = unsafe { (*slot.value.get()).assume_init_read() };
+        slot.ready.store(false, Ordering::Relaxed);
+        Some(value)
+    }
+
+    /// The most values the queue holds.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.0.slots.len()
+    }
+
+    /// How many slots are claimed and not yet popped, including any still being written.
+    /// Producers may push more at any time.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0
+            .tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.0.head.load(Ordering::Relaxed))
+    }
+
+    /// Whether no slots are claimed and waiting to be popped.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether every sender was dropped, so no more values will be pushed.
+    #[must_use]
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn queue() {
+        let (sender, mut receiver) = Queue::with_capacity(2);
+        assert_eq!(sender.push(1), Ok(()));
+        assert_eq!(sender.clone().push(2), Ok(()));
+        assert!(sender.is_full());
+        assert_eq!(sender.push(3), Err(3));
+        assert_eq!(receiver.pop(), Some(1));
+        assert_eq!(sender.push(3), Ok(()));
+        assert_eq!(receiver.pop(), Some(2));
+        assert_eq!(receiver.pop(), Some(3));
+        assert_eq!(receiver.pop(), None);
+        assert!(receiver.is_empty() && !receiver.is_abandoned());
+        drop(sender);
+        assert!(receiver.is_abandoned());
+    }
+
+    #[test]
+    fn batches() {
+        let (sender, mut receiver) = Queue::with_capacity(4);
+        let mut values = vec![1, 2, 3, 4, 5, 6];
+        assert_eq!(sender.push_batch(&mut values), 4);
+        assert_eq!(values, [5, 6]);
+        assert_eq!(sender.push_batch(&mut values), 0);
+
+        let mut out = Vec::new();
+        assert_eq!(receiver.pop_batch(&mut out, 3), 3);
+        assert_eq!(out, [1, 2, 3]);
+        assert_eq!(sender.push_batch(&mut values), 2);
+        assert_eq!(receiver.pop_batch(&mut out, usize::MAX), 3);
+        assert_eq!(out, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(receiver.pop_batch(&mut out, usize::MAX), 0);
+    }
+
+    #[test]
+    fn wrapping_counters() {
+        let (sender, mut receiver) = Queue::with_capacity(3);
+        assert_eq!(sender.capacity(), 4);
+        let start = usize::MAX - 5;
+        sender.0.head.store(start, Ordering::Relaxed);
+        sender.0.tail.store(start, Ordering::Relaxed);
+
+        let mut out = Vec::new();
+        for i in 0..5 {
+            let mut values = vec![3 * i, 3 * i + 1, 3 * i + 2];
+            assert_eq!(sender.push_batch(&mut values), 3);
+            assert_eq!(sender.len(), 3);
+            assert_eq!(receiver.pop(), Some(3 * i));
+            assert_eq!(receiver.pop_batch(&mut out, usize::MAX), 2);
+        }
+        assert_eq!(out, [1, 2, 4, 5, 7, 8, 10, 11, 13, 14]);
+        assert!(sender.0.tail.load(Ordering::Relaxed) < start);
+    }
+
+    #[test]
+    fn many_producers() {
+        const PRODUCERS: usize = 4;
+        const VALUES: usize = 5_000;
+        let (sender, mut receiver) = Queue::with_capacity(32);
+        let producers: Vec<_> = (0..PRODUCERS)
+            .map(|producer| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    let mut values: Vec<_> = (0..VALUES).map(|i| (producer, vec![i])).collect();
+                    for round in 0.. {
+                        if values.is_empty() {
+                            break;
+                        }
+                        let pushed = if round % 2 == 0 {
+                            sender.push_batch(&mut values)
+                        } else {
+                            match sender.push(values.remove(0)) {
+                                Ok(()) => 1,
+                                Err(value) => {
+                                    values.insert(0, value);
+                                    0
+                                }
+                            }
+                        };
+                        if pushed == 0 {
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        drop(sender);
+
+        let mut next = [0; PRODUCERS];
+        let mut out = Vec::new();
+        while next.iter().sum::<usize>() < PRODUCERS * VALUES {
+            out.clear();
+            if receiver.pop_batch(&mut out, 5) == 0 {
+                thread::yield_now();
+            }
+            for (producer, value) in out.drain(..) {
+                assert_eq!(value, [next[producer]]);
+                next[producer] += 1;
+            }
+        }
+        for producer in producers {
+            producer.join().expect("producer thread");
+        }
+        assert!(receiver.is_abandoned() && receiver.pop().is_none());
+    }
+
+    #[test]
+    fn drops_unpopped_values() {
+        let value = Arc::new(());
+        let (sender, mut receiver) = Queue::with_capacity(3);
+        for _ in 0..3 {
+            sender.push(Arc::clone(&value)).expect("room");
+        }
+        drop(receiver.pop());
+        drop((sender, receiver));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
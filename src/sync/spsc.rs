@@ -0,0 +1,357 @@
+//! A bounded, wait-free, single-producer single-consumer ring buffer.
+//!
+//! [`Ring::with_capacity`] returns the two halves of a buffer: a [`Producer`] for one thread to
+//! push into, and a [`Consumer`] for another to pop from. Neither side ever blocks or retries;
+//! each push and pop is a few loads and one store, which suits handing data off from a hot loop
+//! to a background thread.
+
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    mem::MaybeUninit,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// The storage shared by the halves of a ring buffer.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::sync::spsc::Ring;
+///
+/// let (mut producer, mut consumer) = Ring::with_capacity(2);
+/// assert_eq!(producer.push(1), Ok(()));
+/// assert_eq!(producer.push(2), Ok(()));
+/// assert_eq!(producer.push(3), Err(3));
+/// std::thread::spawn(move || {
+///     assert_eq!(consumer.pop(), Some(1));
+///     assert_eq!(consumer.pop(), Some(2));
+///     assert_eq!(consumer.pop(), None);
+/// })
+/// .join()
+/// .unwrap();
+/// ```
+pub struct Ring<T> {
+    slots: Box<[UnsafeCell<MaybeUninit<T>>]>,
+    /// Total number of values popped, wrapping on overflow. Only written by the consumer.
+    head: AtomicUsize,
+    /// Total number of values pushed, wrapping on overflow. Only written by the producer.
+    tail: AtomicUsize,
+}
+
+// SAFETY: Each slot is only accessed by one side at a time, a hand-off synchronized by the
+// release/acquire pairs on `head` and `tail`.
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    /// Creates a ring buffer holding up to `capacity` values, at least one, rounded up to a power
+    /// of two, returning its two halves.
+    #[must_use]
+    pub fn with_capacity(capacity: usize) -> (Producer<T>, Consumer<T>) {
+        let ring = Arc::new(Self {
+            slots: (0..capacity.max(1).next_power_of_two())
+                .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
+                .collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        });
+        (Producer(Arc::clone(&ring)), Consumer(ring))
+    }
+
+    /// Returns the slot for the wrapping counter `index`, which stays in place across the wrap
+    /// since the number of slots is a power of two.
+    fn slot(&self, index: usize) -> *mut MaybeUninit<T> {
+        self.slots[index & (self.slots.len() - 1)].get()
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        let (head, tail) = (*self.head.get_mut(), *self.tail.get_mut());
+        for offset in 0..tail.wrapping_sub(head) {
+            // SAFETY: Slots between `head` and `tail` hold values which were pushed but never
+            // popped.
+            unsafe { (*self.slot(head.wrapping_add(offset))).assume_init_drop() };
+        }
+    }
+}
+
+impl<T> fmt::Debug for Ring<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ring")
+            .field("capacity", &self.slots.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The sending half of a ring buffer.
+pub struct Producer<T>(Arc<Ring<T>>);
+
+impl<T> Producer<T> {
+    /// Pushes `value` without blocking, handing it back if the buffer is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns `value` if the buffer is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let ring = &*self.0;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        if tail.wrapping_sub(ring.head.load(Ordering::Acquire)) == ring.slots.len() {
+            return Err(value);
+        }
+        // SAFETY: The slot at `tail` is empty since the consumer has popped everything before it,
+        // and only this producer writes to it.
+        unsafe { (*ring.slot(tail)).write(value) };
+        ring.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Moves as many values from the front of `values` as there's room for, publishing them
+    /// together, and returns how many were pushed.
+    pub fn push_batch(&mut self, values: &mut Vec<T>) -> usize {
+        let ring = &*self.0;
+        let tail = ring.tail.load(Ordering::Relaxed);
+        let free = ring.slots.len() - tail.wrapping_sub(ring.head.load(Ordering::Acquire));
+        let count = free.min(values.len());
+        for (offset, value) in values.drain(..count).enumerate() {
+            // SAFETY: The `free` slots from `tail` are empty, and only this producer writes to
+            // them.
+            unsafe { (*ring.slot(tail.wrapping_add(offset))).write(value) };
+        }
+        ring.tail.store(tail.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// The most values the buffer holds.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.0.slots.len()
+    }
+
+    /// How many values are waiting to be popped. The consumer may pop more at any time.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0
+            .tail
+            .load(Ordering::Relaxed)
+            .wrapping_sub(self.0.head.load(Ordering::Acquire))
+    }
+
+    /// Whether no values are waiting to be popped.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the next push would fail.
+    #[must_use]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+}
+
+impl<T> fmt::Debug for Producer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+/// The receiving half of a ring buffer.
+pub struct Consumer<T>(Arc<Ring<T>>);
+
+impl<T> Consumer<T> {
+    /// Pops the oldest value without blocking, if any.
+    pub fn pop(&mut self) -> Option<T> {
+        let ring = &*self.0;
+        let head = ring.head.load(Ordering::Relaxed);
+        if head == ring.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        // SAFETY: The slot at `head` was initialized by the producer before it published `tail`,
+        // and only this consumer reads from it.
+        let value = unsafe { (*ring.slot(head)).assume_init_read() };
+        ring.head.store(head.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+
+    /// Pops up to `max` of the oldest values onto the end of `out`, freeing their slots
+    /// together, and returns how many were popped.
+    pub fn pop_batch(&mut self, out: &mut Vec<T>, max: usize) -> usize {
+        let ring = &*self.0;
+        let head = ring.head.load(Ordering::Relaxed);
+        let count = ring
+            .tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(head)
+            .min(max);
+        out.reserve(count);
+        for offset in 0..count {
+            // SAFETY: Slots from `head` up to the published `tail` were initialized by the
+            // producer, and only this consumer reads from them.
+            out.push(unsafe { (*ring.slot(head.wrapping_add(offset))).assume_init_read() });
+        }
+        ring.head.store(head.wrapping_add(count), Ordering::Release);
+        count
+    }
+
+    /// The most values the buffer holds.
+    #[must_use]
+    pub fn capacity(&self) -> usize {
+        self.0.slots.len()
+    }
+
+    /// How many values are waiting to be popped. The producer may push more at any time.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.0
+            .tail
+            .load(Ordering::Acquire)
+            .wrapping_sub(self.0.head.load(Ordering::Relaxed))
+    }
+
+    /// Whether no values are waiting to be popped.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the other half was dropped, so no more values will be pushed.
+    #[must_use]
+    pub fn is_abandoned(&self) -> bool {
+        Arc::strong_count(&self.0) == 1
+    }
+}
+
+impl<T> fmt::Debug for Consumer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer")
+            .field("capacity", &self.capacity())
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn ring_buffer() {
+        let (mut producer, mut consumer) = Ring::with_capacity(2);
+        assert_eq!(producer.push(1), Ok(()));
+        assert_eq!(producer.push(2), Ok(()));
+        assert!(producer.is_full());
+        assert_eq!(producer.push(3), Err(3));
+        assert_eq!(consumer.pop(), Some(1));
+        assert_eq!(producer.push(3), Ok(()));
+        assert_eq!(consumer.pop(), Some(2));
+        assert_eq!(consumer.pop(), Some(3));
+        assert_eq!(consumer.pop(), None);
+        assert!(consumer.is_empty() && !consumer.is_abandoned());
+        drop(producer);
+        assert!(consumer.is_abandoned());
+
+        let (mut producer, mut consumer) = Ring::with_capacity(16);
+        let sender = thread::spawn(move || {
+            for i in 0..10_000 {
+                let mut value = vec![i];
+                while let Err(rejected) = producer.push(value) {
+                    value = rejected;
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < 10_000 {
+            if let Some(value) = consumer.pop() {
+                assert_eq!(value, [expected]);
+                expected += 1;
+            } else {
+                thread::yield_now();
+            }
+        }
+        sender.join().expect("producer thread");
+    }
+
+    #[test]
+    fn batches() {
+        let (mut producer, mut consumer) = Ring::with_capacity(4);
+        let mut values = vec![1, 2, 3, 4, 5, 6];
+        assert_eq!(producer.push_batch(&mut values), 4);
+        assert_eq!(values, [5, 6]);
+        assert_eq!(producer.push_batch(&mut values), 0);
+
+        let mut out = Vec::new();
+        assert_eq!(consumer.pop_batch(&mut out, 3), 3);
+        assert_eq!(out, [1, 2, 3]);
+        assert_eq!(producer.push_batch(&mut values), 2);
+        assert!(values.is_empty());
+        assert_eq!(consumer.pop_batch(&mut out, usize::MAX), 3);
+        assert_eq!(out, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(consumer.pop_batch(&mut out, usize::MAX), 0);
+
+        let (mut producer, mut consumer) = Ring::with_capacity(64);
+        let sender = thread::spawn(move || {
+            let mut values: Vec<_> = (0..10_000).map(|i| vec![i]).collect();
+            while !values.is_empty() {
+                if producer.push_batch(&mut values) == 0 {
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut out = Vec::new();
+        while out.len() < 10_000 {
+            if consumer.pop_batch(&mut out, 7) == 0 {
+                thread::yield_now();
+            }
+        }
+        sender.join().expect("producer thread");
+        assert!(out.iter().enumerate().all(|(i, value)| value == &[i]));
+    }
+
+    #[test]
+    fn wrapping_counters() {
+        let (mut producer, mut consumer) = Ring::with_capacity(3);
+        assert_eq!(producer.capacity(), 4);
+        let start = usize::MAX - 5;
+        producer.0.head.store(start, Ordering::Relaxed);
+        producer.0.tail.store(start, Ordering::Relaxed);
+
+        let mut out = Vec::new();
+        for i in 0..5 {
+            let mut values = vec![3 * i, 3 * i + 1, 3 * i + 2];
+            assert_eq!(producer.push_batch(&mut values), 3);
+            assert_eq!(producer.len(), 3);
+            assert_eq!(consumer.pop(), Some(3 * i));
+            assert_eq!(consumer.pop_batch(&mut out, usize::MAX), 2);
+        }
+        assert_eq!(out, [1, 2, 4, 5, 7, 8, 10, 11, 13, 14]);
+        assert!(producer.0.tail.load(Ordering::Relaxed) < start);
+
+        let mut values = vec![20, 21, 22, 23, 24];
+        assert_eq!(producer.push_batch(&mut values), 4);
+        assert!(producer.is_full());
+        out.clear();
+        assert_eq!(consumer.pop_batch(&mut out, usize::MAX), 4);
+        assert_eq!(out, [20, 21, 22, 23]);
+    }
+
+    #[test]
+    fn drops_unpopped_values() {
+        let value = Arc::new(());
+        let (mut producer, mut consumer) = Ring::with_capacity(3);
+        for _ in 0..3 {
+            producer.push(Arc::clone(&value)).expect("room");
+        }
+        drop(consumer.pop());
+        drop((producer, consumer));
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
@@ -0,0 +1,39 @@
+//! Process and machine information from the operating system.
+//!
+//! [`memory_usage`] reads how much memory the process has resident and mapped, from
+//! `/proc/self/status` on Linux, `task_info` on macOS and `GetProcessMemoryInfo` on Windows, so
+//! memory can be watched alongside timing while optimizing. [`cpu_info`] describes the processor,
+//! its core counts, frequencies and cache sizes, for sizing buffers to cache levels and giving
+//! benchmark results context.
+
+pub mod cpu;
+pub mod memory;
+
+pub use cpu::{cpu_info, CpuInfo};
+pub use memory::{memory_usage, MemoryUsage};
+
+/// Parses a kernel CPU list such as `1-3,8`, skipping malformed entries.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub(crate) fn parse_core_list(list: &str) -> Vec<usize> {
+    list.trim()
+        .split(',')
+        .filter_map(|range| {
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            Some(start.parse().ok()?..=end.parse().ok()?)
+        })
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn core_lists() {
+        assert_eq!(parse_core_list("1-3,8\n"), [1, 2, 3, 8]);
+        assert_eq!(parse_core_list("0"), [0]);
+        assert!(parse_core_list("\n").is_empty());
+        assert_eq!(parse_core_list("x,4-5"), [4, 5]);
+    }
+}
@@ -0,0 +1,396 @@
+//! Processor and cache topology.
+//!
+//! Read from sysfs on Linux and `sysctl` on macOS, falling back to `cpuid` on x86 for what the
+//! operating system doesn't tell, such as on Windows.
+
+use crate::fmt::format_bytes;
+use std::{fmt, num::NonZeroUsize, sync::OnceLock};
+
+/// The processor of the machine.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct CpuInfo {
+    /// Model name, such as `AMD Ryzen 9 7950X 16-Core Processor`.
+    pub model: Option<String>,
+    /// Cores, or the logical count where it can't be told apart.
+    pub physical_cores: usize,
+    /// Hardware threads across every core.
+    pub logical_cores: usize,
+    /// Base frequency, in MHz.
+    pub base_frequency_mhz: Option<u32>,
+    /// Maximum boost frequency, in MHz.
+    pub max_frequency_mhz: Option<u32>,
+    /// Size of the level 1 data cache of each core, in bytes.
+    pub l1_data_cache: Option<usize>,
+    /// Size of the level 2 cache, in bytes.
+    pub l2_cache: Option<usize>,
+    /// Size of the level 3 cache, in bytes.
+    pub l3_cache: Option<usize>,
+}
+
+impl CpuInfo {
+    /// Returns the level and size in bytes of each data cache detected, innermost first.
+    pub fn caches(&self) -> impl Iterator<Item = (u8, usize)> {
+        [
+            (1, self.l1_data_cache),
+            (2, self.l2_cache),
+            (3, self.l3_cache),
+        ]
+        .into_iter()
+        .filter_map(|(level, size)| Some((level, size?)))
+    }
+}
+
+impl fmt::Display for CpuInfo {
+    /// Formats the processor on one line, such as
+    /// `Intel(R) Core(TM) i7-8700, 6 cores, 12 threads, 3.2-4.6 GHz, L1d 32.0 KiB, L2 256.0 KiB,
+    /// L3 12.0 MiB`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ghz = |mhz: u32| f64::from(mhz) / 1000.0;
+        write!(
+            f,
+            "{}, {} cores, {} threads",
+            self.model.as_deref().unwrap_or("Unknown processor"),
+            self.physical_cores,
+            self.logical_cores
+        )?;
+        match (self.base_frequency_mhz, self.max_frequency_mhz) {
+            (Some(base), Some(max)) if max > base => {
+                write!(f, ", {:.1}-{:.1} GHz", ghz(base), ghz(max))?;
+            }
+            (Some(mhz), _) | (None, Some(mhz)) => write!(f, ", {:.1} GHz", ghz(mhz))?,
+            (None, None) => {}
+        }
+        for (level, size) in self.caches() {
+            let data = if level == 1 { "d" } else { "" };
+            write!(f, ", L{level}{data} {}", format_bytes(size as u64))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the processor of the machine, detected on the first call.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::sys::cpu_info;
+///
+/// let cpu = cpu_info();
+/// assert!(cpu.logical_cores >= cpu.physical_cores.min(1));
+/// // Size a working set to stay within the level 2 cache.
+/// let chunk = cpu.l2_cache.unwrap_or(256 << 10) / 2;
+/// println!("{cpu}");
+/// ```
+pub fn cpu_info() -> CpuInfo {
+    static INFO: OnceLock<CpuInfo> = OnceLock::new();
+    INFO.get_or_init(detect).clone()
+}
+
+fn detect() -> CpuInfo {
+    let logical = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+    #[allow(unused_mut)]
+    let mut info = CpuInfo {
+        physical_cores: logical,
+        logical_cores: logical,
+        ..CpuInfo::default()
+    };
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    x86::detect(&mut info);
+    #[cfg(target_os = "linux")]
+    linux::detect(&mut info);
+    #[cfg(target_os = "macos")]
+    macos::detect(&mut info);
+    info
+}
+
+/// Parses a sysfs cache size such as `32K` or `8M`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_cache_size(size: &str) -> Option<usize> {
+    let size = size.trim();
+    let (digits, scale) = match size.as_bytes().last()? {
+        b'K' => (&size[..size.len() - 1], 1 << 10),
+        b'M' => (&size[..size.len() - 1], 1 << 20),
+        _ => (size, 1),
+    };
+    Some(digits.parse::<usize>().ok()? * scale)
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::{parse_cache_size, CpuInfo};
+    use std::{collections::HashSet, fs, path::Path};
+
+    const CPU_DIR: &str = "/sys/devices/system/cpu";
+
+    fn read(path: impl AsRef<Path>) -> Option<String> {
+        fs::read_to_string(path)
+            .ok()
+            .map(|text| text.trim().to_string())
+    }
+
+    /// Fills in what sysfs and `/proc/cpuinfo` tell, over what `cpuid` told.
+    pub(super) fn detect(info: &mut CpuInfo) {
+        let dir = Path::new(CPU_DIR);
+        let online = read(dir.join("online"))
+            .map_or_else(Vec::new, |list| crate::sys::parse_core_list(&list));
+        if !online.is_empty() {
+            info.logical_cores = online.len();
+            let cores: HashSet<(String, String)> = online
+                .iter()
+                .filter_map(|cpu| {
+                    let topology = dir.join(format!("cpu{cpu}/topology"));
+                    Some((
+                        read(topology.join("physical_package_id"))?,
+                        read(topology.join("core_id"))?,
+                    ))
+                })
+                .collect();
+            info.physical_cores = if cores.is_empty() {
+                info.logical_cores
+            } else {
+                cores.len()
+            };
+        }
+
+        let khz = |name: &str| {
+            let khz: u32 = read(dir.join("cpu0/cpufreq").join(name))?.parse().ok()?;
+            Some(khz / 1000).filter(|&mhz| mhz > 0)
+        };
+        info.base_frequency_mhz = khz("base_frequency").or(info.base_frequency_mhz);
+        info.max_frequency_mhz = khz("cpuinfo_max_freq").or(info.max_frequency_mhz);
+
+        for index in 0.. {
+            let cache = dir.join(format!("cpu0/cache/index{index}"));
+            let Some(level) = read(cache.join("level")) else {
+                break;
+            };
+            if read(cache.join("type")).as_deref() == Some("Instruction") {
+                continue;
+            }
+            let size = read(cache.join("size")).and_then(|size| parse_cache_size(&size));
+            match level.as_str() {
+                "1" => info.l1_data_cache = size.or(info.l1_data_cache),
+                "2" => info.l2_cache = size.or(info.l2_cache),
+                "3" => info.l3_cache = size.or(info.l3_cache),
+                _ => {}
+            }
+        }
+
+        if info.model.is_none() {
+            info.model = read("/proc/cpuinfo").and_then(|cpuinfo| {
+                cpuinfo.lines().find_map(|line| {
+                    let (key, value) = line.split_once(':')?;
+                    (key.trim() == "model name").then(|| value.trim().to_string())
+                })
+            });
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::CpuInfo;
+    use std::{
+        ffi::{c_char, c_int, c_void, CStr},
+        ptr,
+    };
+
+    extern "C" {
+        fn sysctlbyname(
+            name: *const c_char,
+            old: *mut c_void,
+            old_len: *mut usize,
+            new: *mut c_void,
+            new_len: usize,
+        ) -> c_int;
+    }
+
+    /// Reads an integer `sysctl` of 4 or 8 bytes, treating 0 as unknown.
+    fn sysctl_u64(name: &CStr) -> Option<u64> {
+        let mut value = 0u64;
+        let mut len = std::mem::size_of::<u64>();
+        // SAFETY: `value` is valid for `len` bytes for the duration of the call, and smaller
+        // values fill its low bytes, which are its first on every Apple target.
+        let result = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                (&raw mut value).cast(),
+                &raw mut len,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        (result == 0 && value > 0).then_some(value)
+    }
+
+    fn sysctl_string(name: &CStr) -> Option<String> {
+        let mut len = 0;
+        // SAFETY: A null buffer asks for the length only.
+        let result = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                ptr::null_mut(),
+                &raw mut len,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if result != 0 || len == 0 {
+            return None;
+        }
+        let mut buffer = vec![0u8; len];
+        // SAFETY: `buffer` is valid for `len` bytes for the duration of the call.
+        let result = unsafe {
+            sysctlbyname(
+                name.as_ptr(),
+                buffer.as_mut_ptr().cast(),
+                &raw mut len,
+                ptr::null_mut(),
+                0,
+            )
+        };
+        if result != 0 {
+            return None;
+        }
+        let text = CStr::from_bytes_until_nul(&buffer).ok()?.to_str().ok()?;
+        Some(text.trim().to_string()).filter(|text| !text.is_empty())
+    }
+
+    /// Fills in what `sysctl` tells, over what `cpuid` told.
+    pub(super) fn detect(info: &mut CpuInfo) {
+        let count = |name| sysctl_u64(name).and_then(|count| usize::try_from(count).ok());
+        let mhz = |name| sysctl_u64(name).and_then(|hz| u32::try_from(hz / 1_000_000).ok());
+        info.model = sysctl_string(c"machdep.cpu.brand_string").or(info.model.take());
+        info.physical_cores = count(c"hw.physicalcpu").unwrap_or(info.physical_cores);
+        info.logical_cores = count(c"hw.logicalcpu").unwrap_or(info.logical_cores);
+        info.base_frequency_mhz = mhz(c"hw.cpufrequency").or(info.base_frequency_mhz);
+        info.max_frequency_mhz = mhz(c"hw.cpufrequency_max").or(info.max_frequency_mhz);
+        info.l1_data_cache = count(c"hw.l1dcachesize").or(info.l1_data_cache);
+        info.l2_cache = count(c"hw.l2cachesize").or(info.l2_cache);
+        info.l3_cache = count(c"hw.l3cachesize").or(info.l3_cache);
+    }
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+mod x86 {
+    use super::CpuInfo;
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86 as arch;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64 as arch;
+
+    /// Fills in the model, frequencies and cache sizes reported by `cpuid`.
+    pub(super) fn detect(info: &mut CpuInfo) {
+        let vendor = arch::__cpuid(0);
+        let max_leaf = vendor.eax;
+        let amd = (vendor.ebx, vendor.edx, vendor.ecx)
+            == (
+                u32::from_le_bytes(*b"Auth"),
+                u32::from_le_bytes(*b"enti"),
+                u32::from_le_bytes(*b"cAMD"),
+            );
+        let max_extended_leaf = arch::__cpuid(0x8000_0000).eax;
+
+        if max_extended_leaf >= 0x8000_0004 {
+            let brand: Vec<u8> = (0x8000_0002..=0x8000_0004)
+                .flat_map(|leaf| {
+                    let regs = arch::__cpuid(leaf);
+                    [regs.eax, regs.ebx, regs.ecx, regs.edx]
+                })
+                .flat_map(u32::to_le_bytes)
+                .take_while(|&byte| byte != 0)
+                .collect();
+            let brand = String::from_utf8_lossy(&brand).trim().to_string();
+            info.model = Some(brand).filter(|brand| !brand.is_empty());
+        }
+
+        if max_leaf >= 0x16 {
+            let frequencies = arch::__cpuid(0x16);
+            info.base_frequency_mhz = Some(frequencies.eax & 0xffff).filter(|&mhz| mhz > 0);
+            info.max_frequency_mhz = Some(frequencies.ebx & 0xffff).filter(|&mhz| mhz > 0);
+        }
+
+        // Both leaves describe one cache per subleaf, until one of type 0.
+        let cache_leaf = if amd && max_extended_leaf >= 0x8000_001d {
+            0x8000_001d
+        } else if !amd && max_leaf >= 4 {
+            4
+        } else {
+            return;
+        };
+        for subleaf in 0..16 {
+            let regs = arch::__cpuid_count(cache_leaf, subleaf);
+            let kind = regs.eax & 0x1f;
+            if kind == 0 {
+                break;
+            }
+            // Instruction caches are type 2.
+            if kind == 2 {
+                continue;
+            }
+            let ways = u64::from(regs.ebx >> 22) + 1;
+            let partitions = u64::from((regs.ebx >> 12) & 0x3ff) + 1;
+            let line = u64::from(regs.ebx & 0xfff) + 1;
+            let sets = u64::from(regs.ecx) + 1;
+            let size = usize::try_from(ways * partitions * line * sets).ok();
+            match (regs.eax >> 5) & 0x7 {
+                1 => info.l1_data_cache = size,
+                2 => info.l2_cache = size,
+                3 => info.l3_cache = size,
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_cpus() {
+        let cpu = CpuInfo {
+            model: Some("Test CPU".to_string()),
+            physical_cores: 4,
+            logical_cores: 8,
+            base_frequency_mhz: Some(2100),
+            max_frequency_mhz: Some(3900),
+            l1_data_cache: Some(48 << 10),
+            l2_cache: None,
+            l3_cache: Some(30 << 20),
+        };
+        assert_eq!(
+            cpu.to_string(),
+            "Test CPU, 4 cores, 8 threads, 2.1-3.9 GHz, L1d 48.0 KiB, L3 30.0 MiB"
+        );
+        assert_eq!(
+            cpu.caches().collect::<Vec<_>>(),
+            [(1, 48 << 10), (3, 30 << 20)]
+        );
+        assert_eq!(
+            CpuInfo {
+                logical_cores: 1,
+                physical_cores: 1,
+                max_frequency_mhz: Some(1000),
+                ..CpuInfo::default()
+            }
+            .to_string(),
+            "Unknown processor, 1 cores, 1 threads, 1.0 GHz"
+        );
+        assert_eq!(parse_cache_size("32K\n"), Some(32 << 10));
+        assert_eq!(parse_cache_size("8M"), Some(8 << 20));
+        assert_eq!(parse_cache_size("512"), Some(512));
+        assert_eq!(parse_cache_size("big"), None);
+    }
+
+    #[test]
+    fn detects_cpu() {
+        let cpu = cpu_info();
+        assert!(cpu.logical_cores >= 1);
+        assert!(cpu.physical_cores >= 1 && cpu.physical_cores <= cpu.logical_cores);
+        assert_eq!(cpu_info(), cpu);
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64", target_os = "linux"))]
+        assert!(cpu.caches().all(|(_, size)| size >= 1024));
+    }
+}
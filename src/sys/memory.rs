@@ -0,0 +1,164 @@
+//! Process memory use.
+
+/// Memory use of the process, in bytes.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Resident set size: memory currently in RAM.
+    pub rss: u64,
+    /// Largest resident set size since the process started.
+    pub peak_rss: u64,
+    /// Virtual memory mapped by the process. On Windows, the memory committed for it.
+    pub virtual_size: u64,
+}
+
+/// Returns the current memory use of the process, or `None` on other platforms or if it can't be
+/// read.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{fmt::format_bytes, sys::memory_usage};
+///
+/// if let Some(usage) = memory_usage() {
+///     println!("peak RSS: {}", format_bytes(usage.peak_rss));
+/// }
+/// ```
+#[must_use]
+pub fn memory_usage() -> Option<MemoryUsage> {
+    #[cfg(target_os = "linux")]
+    {
+        parse_status(&std::fs::read_to_string("/proc/self/status").ok()?)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        const MACH_TASK_BASIC_INFO: u32 = 20;
+
+        #[repr(C, packed(4))]
+        struct MachTaskBasicInfo {
+            virtual_size: u64,
+            resident_size: u64,
+            resident_size_max: u64,
+            /// User and system CPU time, and the scheduling policy and suspend count, which aren't
+            /// used.
+            unused: [i32; 6],
+        }
+
+        extern "C" {
+            static mach_task_self_: u32;
+            fn task_info(task: u32, flavor: u32, info: *mut i32, count: *mut u32) -> i32;
+        }
+
+        let mut info = std::mem::MaybeUninit::<MachTaskBasicInfo>::zeroed();
+        let mut count = u32::try_from(std::mem::size_of::<MachTaskBasicInfo>() / 4).ok()?;
+        // SAFETY: `info` is a valid, zeroed `mach_task_basic_info` of `count` words for the
+        // duration of the call, and `mach_task_self_` is the task port of this process.
+        let result = unsafe {
+            task_info(
+                mach_task_self_,
+                MACH_TASK_BASIC_INFO,
+                info.as_mut_ptr().cast(),
+                &raw mut count,
+            )
+        };
+        if result != 0 {
+            return None;
+        }
+        // SAFETY: Zeroed memory is a valid `MachTaskBasicInfo`, and `task_info` succeeded.
+        let info = unsafe { info.assume_init() };
+        Some(MemoryUsage {
+            rss: info.resident_size,
+            peak_rss: info.resident_size_max,
+            virtual_size: info.virtual_size,
+        })
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use std::ffi::c_void;
+
+        #[repr(C)]
+        #[derive(Default)]
+        struct ProcessMemoryCounters {
+            cb: u32,
+            page_fault_count: u32,
+            peak_working_set_size: usize,
+            working_set_size: usize,
+            /// Peak and current paged and non-paged pool usage, which aren't used.
+            pools: [usize; 4],
+            pagefile_usage: usize,
+            peak_pagefile_usage: usize,
+        }
+
+        #[link(name = "kernel32")]
+        extern "system" {
+            fn GetCurrentProcess() -> *mut c_void;
+            fn K32GetProcessMemoryInfo(
+                process: *mut c_void,
+                counters: *mut ProcessMemoryCounters,
+                size: u32,
+            ) -> i32;
+        }
+
+        let size = u32::try_from(std::mem::size_of::<ProcessMemoryCounters>()).ok()?;
+        let mut counters = ProcessMemoryCounters {
+            cb: size,
+            ..ProcessMemoryCounters::default()
+        };
+        // SAFETY: `counters` is a valid `PROCESS_MEMORY_COUNTERS` of `size` bytes for the duration
+        // of the call, and the current process handle needs no closing.
+        if unsafe { K32GetProcessMemoryInfo(GetCurrentProcess(), &raw mut counters, size) } == 0 {
+            return None;
+        }
+        Some(MemoryUsage {
+            rss: counters.working_set_size as u64,
+            peak_rss: counters.peak_working_set_size as u64,
+            virtual_size: counters.pagefile_usage as u64,
+        })
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+    None
+}
+
+/// Parses the `VmRSS`, `VmHWM` and `VmSize` lines of `/proc/self/status`, which are in KiB.
+#[cfg(target_os = "linux")]
+fn parse_status(status: &str) -> Option<MemoryUsage> {
+    let field = |name: &str| {
+        status.lines().find_map(|line| {
+            let kib = line.strip_prefix(name)?.strip_prefix(':')?;
+            kib.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()
+        })
+    };
+    let rss = field("VmRSS")?;
+    Some(MemoryUsage {
+        rss: rss * 1024,
+        peak_rss: field("VmHWM").unwrap_or(rss) * 1024,
+        virtual_size: field("VmSize").unwrap_or(0) * 1024,
+    })
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_status() {
+        let status = "Name:\ttest\nVmPeak:\t  20000 kB\nVmSize:\t  18000 kB\nVmHWM:\t    9000 kB\n\
+                      VmRSS:\t    8000 kB\n";
+        assert_eq!(
+            parse_status(status),
+            Some(MemoryUsage {
+                rss: 8000 * 1024,
+                peak_rss: 9000 * 1024,
+                virtual_size: 18000 * 1024,
+            })
+        );
+        assert_eq!(parse_status("Name:\ttest\n"), None);
+    }
+
+    #[test]
+    fn reads_memory_usage() {
+        let usage = memory_usage().expect("valid memory usage");
+        assert!(usage.rss > 0);
+        assert!(usage.peak_rss >= usage.rss);
+        assert!(usage.virtual_size >= usage.rss);
+    }
+}
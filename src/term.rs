@@ -0,0 +1,11 @@
+//! Terminal output helpers.
+
+pub mod progress;
+pub mod size;
+pub mod style;
+pub mod table;
+
+pub use progress::ProgressBar;
+pub use size::{size, TermSize};
+pub use style::{Color, Style};
+pub use table::{Align, Table};
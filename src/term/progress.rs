@@ -0,0 +1,377 @@
+//! Progress bars.
+//!
+//! A [`ProgressBar`] shows how far a long-running job has got on `stderr`: a bar with the
+//! percentage, rate and estimated time left when the total is known, or a spinner with the count
+//! and rate when it isn't. Redraws are throttled, so updating it from a hot loop costs an atomic
+//! add. When `stderr` isn't a terminal, such as in CI logs, it prints a plain line every few
+//! seconds instead of redrawing in place.
+
+use crate::{
+    fmt::{format_bytes, format_duration, format_rate},
+    time::Throttler,
+};
+use std::{
+    fmt::Write as _,
+    io::{IsTerminal, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+/// Frames of the spinner shown without a total.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+
+/// Where a bar is drawn, and how it was last drawn.
+struct Output {
+    writer: Box<dyn Write + Send>,
+    is_terminal: bool,
+    message: String,
+    frame: usize,
+    finished: bool,
+}
+
+/// A progress bar or spinner, which can be advanced from multiple threads.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::term::ProgressBar;
+///
+/// let files = ["a.csv", "b.csv", "c.csv"];
+/// let progress = ProgressBar::new(files.len() as u64).message("Parsing");
+/// for _file in files {
+///     // ...
+///     progress.inc(1);
+/// }
+/// progress.finish();
+/// ```
+#[must_use]
+pub struct ProgressBar {
+    position: AtomicU64,
+    total: Option<u64>,
+    start: Instant,
+    width: usize,
+    bytes: bool,
+    redraw_interval: Duration,
+    /// Minimum time between lines when not drawing to a terminal.
+    log_interval: Duration,
+    /// Limits draws to the redraw or log interval, whichever applies to the output.
+    throttle: Throttler,
+    output: Mutex<Output>,
+}
+
+impl std::fmt::Debug for ProgressBar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressBar")
+            .field("position", &self.position())
+            .field("total", &self.total)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ProgressBar {
+    /// Creates a bar counting up to `total`, drawn to `stderr`.
+    pub fn new(total: u64) -> Self {
+        Self::with_total(Some(total))
+    }
+
+    /// Creates a spinner for jobs without a known total, drawn to `stderr`.
+    pub fn spinner() -> Self {
+        Self::with_total(None)
+    }
+
+    fn with_total(total: Option<u64>) -> Self {
+        let is_terminal = std::io::stderr().is_terminal();
+        Self {
+            position: AtomicU64::new(0),
+            total,
+            start: Instant::now(),
+            width: 30,
+            bytes: false,
+            redraw_interval: Duration::from_millis(100),
+            log_interval: Duration::from_secs(5),
+            throttle: Throttler::new(Duration::ZERO),
+            output: Mutex::new(Output {
+                writer: Box::new(std::io::stderr()),
+                is_terminal,
+                message: String::new(),
+                frame: 0,
+                finished: false,
+            }),
+        }
+        .with_throttle(is_terminal)
+    }
+
+    /// Throttles draws to the interval for a terminal or a log.
+    fn with_throttle(mut self, is_terminal: bool) -> Self {
+        self.throttle = Throttler::new(if is_terminal {
+            self.redraw_interval
+        } else {
+            self.log_interval
+        });
+        self
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.lock().is_terminal
+    }
+
+    /// Show `message` before the bar.
+    pub fn message(self, message: impl Into<String>) -> Self {
+        self.set_message(message);
+        self
+    }
+
+    /// Draw the bar `width` characters wide, 30 by default.
+    pub fn width(mut self, width: usize) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Count bytes, shown like `3.5 MiB` with rates like `2.1 GB/s`.
+    pub fn bytes(mut self, bytes: bool) -> Self {
+        self.bytes = bytes;
+        self
+    }
+
+    /// Redraw at most once per `interval` on a terminal, 100ms by default.
+    pub fn redraw_interval(mut self, interval: Duration) -> Self {
+        self.redraw_interval = interval;
+        let is_terminal = self.is_terminal();
+        self.with_throttle(is_terminal)
+    }
+
+    /// Print a line at most once per `interval` when not drawing to a terminal, 5s by default.
+    pub fn log_interval(mut self, interval: Duration) -> Self {
+        self.log_interval = interval;
+        let is_terminal = self.is_terminal();
+        self.with_throttle(is_terminal)
+    }
+
+    /// Draw to `writer` instead of `stderr`, redrawing in place if `is_terminal`.
+    pub fn output(self, writer: impl Write + Send + 'static, is_terminal: bool) -> Self {
+        {
+            let mut output = self.lock();
+            output.writer = Box::new(writer);
+            output.is_terminal = is_terminal;
+        }
+        self.with_throttle(is_terminal)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Output> {
+        self.output.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replaces the message shown before the bar.
+    pub fn set_message(&self, message: impl Into<String>) {
+        self.lock().message = message.into();
+    }
+
+    /// Current count.
+    #[must_use]
+    pub fn position(&self) -> u64 {
+        self.position.load(Ordering::Relaxed)
+    }
+
+    /// Adds `delta` to the count, redrawing if it's been long enough since the last draw.
+    pub fn inc(&self, delta: u64) {
+        self.position.fetch_add(delta, Ordering::Relaxed);
+        self.tick();
+    }
+
+    /// Sets the count to `position`, redrawing if it's been long enough since the last draw.
+    pub fn set_position(&self, position: u64) {
+        self.position.store(position, Ordering::Relaxed);
+        self.tick();
+    }
+
+    /// Redraws if it's been long enough since the last draw, e.g. to keep a spinner turning
+    /// while the count doesn't change.
+    pub fn tick(&self) {
+        if !self.throttle.ready() {
+            return;
+        }
+        // Another thread drawing will show this count too, so there's no need to wait for it.
+        let Ok(mut output) = self.output.try_lock() else {
+            return;
+        };
+        if !output.finished {
+            self.draw(&mut output, false);
+        }
+    }
+
+    /// Draws the final count and ends the line. Later updates aren't drawn.
+    pub fn finish(&self) {
+        let mut output = self.lock();
+        if !output.finished {
+            output.finished = true;
+            self.draw(&mut output, true);
+        }
+    }
+
+    /// Replaces the message, then draws the final count as [`finish`](Self::finish) does.
+    pub fn finish_with_message(&self, message: impl Into<String>) {
+        self.set_message(message);
+        self.finish();
+    }
+
+    fn draw(&self, output: &mut Output, finished: bool) {
+        let mut line = String::new();
+        if output.is_terminal {
+            line.push('\r');
+        }
+        if self.total.is_none() && !finished {
+            line.push(SPINNER[output.frame % SPINNER.len()]);
+            line.push(' ');
+            output.frame += 1;
+        }
+        line.push_str(&self.render(&output.message, finished));
+        if output.is_terminal {
+            // Clears the rest of a longer previous line.
+            line.push_str("\x1b[K");
+        }
+        if finished || !output.is_terminal {
+            line.push('\n');
+        }
+        let _ = output
+            .writer
+            .write_all(line.as_bytes())
+            .and_then(|()| output.writer.flush());
+    }
+
+    /// Formats the message, bar, count, rate and time of the current state.
+    #[allow(
+        clippy::cast_precision_loss,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    fn render(&self, message: &str, finished: bool) -> String {
+        let position = self.position();
+        let elapsed = self.start.elapsed();
+        let rate = position as f64 / elapsed.as_secs_f64().max(1e-9);
+        let count = |count: u64| {
+            if self.bytes {
+                format_bytes(count)
+            } else {
+                count.to_string()
+            }
+        };
+
+        let mut line = String::new();
+        if !message.is_empty() {
+            let _ = write!(line, "{message} ");
+        }
+        if let Some(total) = self.total {
+            let fraction = if total == 0 {
+                1.0
+            } else {
+                (position as f64 / total as f64).min(1.0)
+            };
+            let filled = (fraction * self.width as f64).round() as usize;
+            let _ = write!(
+                line,
+                "[{}{}] {}/{} {:.0}%",
+                "#".repeat(filled),
+                "-".repeat(self.width - filled),
+                count(position),
+                count(total),
+                fraction * 100.0
+            );
+        } else {
+            let _ = write!(line, "{}", count(position));
+        }
+        if self.bytes {
+            let _ = write!(line, " {}", format_rate(rate));
+        } else {
+            let _ = write!(line, " {rate:.1}/s");
+        }
+        match self.total {
+            Some(total) if !finished && rate > 0.0 && position < total => {
+                let left = Duration::from_secs_f64((total - position) as f64 / rate);
+                let _ = write!(line, " ETA {}", format_duration(left));
+            }
+            _ => {
+                let _ = write!(line, " in {}", format_duration(elapsed));
+            }
+        }
+        line
+    }
+}
+
+impl Drop for ProgressBar {
+    /// Ends the line, so later output doesn't overwrite the bar.
+    fn drop(&mut self) {
+        self.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    /// A writer whose output can be read while a bar owns it.
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().expect("valid buffer").extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Shared {
+        fn text(&self) -> String {
+            String::from_utf8(self.0.lock().expect("valid buffer").clone()).expect("valid UTF-8")
+        }
+    }
+
+    #[test]
+    fn terminal_bar() {
+        let buffer = Shared::default();
+        let progress = ProgressBar::new(4)
+            .message("Parsing")
+            .width(4)
+            .redraw_interval(Duration::ZERO)
+            .output(buffer.clone(), true);
+        progress.inc(1);
+        progress.inc(1);
+        assert!(buffer.text().contains("\rParsing [##--] 2/4 50% "));
+        assert!(buffer.text().contains(" ETA "));
+        progress.set_position(4);
+        progress.finish_with_message("Parsed");
+        progress.inc(1);
+        let text = buffer.text();
+        assert!(text.contains("\rParsed [####] 4/4 100% "));
+        assert!(text.ends_with("\x1b[K\n"));
+        assert_eq!(text.matches('\n').count(), 1);
+        drop(progress);
+        assert_eq!(buffer.text(), text);
+    }
+
+    #[test]
+    fn plain_lines() {
+        let buffer = Shared::default();
+        let progress = ProgressBar::spinner()
+            .bytes(true)
+            .log_interval(Duration::from_hours(1))
+            .output(buffer.clone(), false);
+        progress.inc(1024);
+        progress.inc(1024);
+        drop(progress);
+        let text = buffer.text();
+        assert!(!text.contains('\r') && !text.contains('\x1b'));
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 2, "{text}");
+        assert!(lines[0].starts_with("| 1.0 KiB "));
+        assert!(lines[1].starts_with("2.0 KiB "));
+        assert!(lines[1].contains("B/s in "));
+    }
+}
@@ -0,0 +1,151 @@
+//! Terminal size detection.
+
+use crate::env;
+
+/// The size of a terminal, in character cells.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct TermSize {
+    /// Number of columns, the width of a line.
+    pub columns: u16,
+    /// Number of rows.
+    pub rows: u16,
+}
+
+/// Returns the size of the terminal attached to `stdout`, `stderr` or `stdin`, whichever is one
+/// first, or otherwise the size given by the `COLUMNS` and `LINES` environment variables, if
+/// `COLUMNS` is set. Returns `None` if output isn't going to a terminal and neither is set.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::term;
+///
+/// let width = term::size().map_or(80, |size| usize::from(size.columns));
+/// assert!(width > 0);
+/// ```
+#[must_use]
+pub fn size() -> Option<TermSize> {
+    platform::size().or_else(|| {
+        let columns = env::var_parsed("COLUMNS").ok().flatten()?;
+        let rows = env::var_parsed("LINES").ok().flatten().unwrap_or(24);
+        (columns > 0).then_some(TermSize { columns, rows })
+    })
+}
+
+#[cfg(unix)]
+mod platform {
+    use super::TermSize;
+    use std::ffi::{c_int, c_ulong};
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const TIOCGWINSZ: c_ulong = 0x5413;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const TIOCGWINSZ: c_ulong = 0x4008_7468;
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct WinSize {
+        row: u16,
+        col: u16,
+        xpixel: u16,
+        ypixel: u16,
+    }
+
+    extern "C" {
+        fn ioctl(fd: c_int, request: c_ulong, ...) -> c_int;
+    }
+
+    pub(super) fn size() -> Option<TermSize> {
+        [1, 2, 0].into_iter().find_map(|fd| {
+            let mut size = WinSize::default();
+            // SAFETY: `TIOCGWINSZ` writes a `winsize` to the pointer, which `size` is valid for.
+            let result = unsafe { ioctl(fd, TIOCGWINSZ, &raw mut size) };
+            (result == 0 && size.col > 0).then_some(TermSize {
+                columns: size.col,
+                rows: size.row,
+            })
+        })
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::TermSize;
+    use std::ffi::c_void;
+
+    /// `STD_OUTPUT_HANDLE`, `STD_ERROR_HANDLE` and `STD_INPUT_HANDLE`.
+    const STD_HANDLES: [u32; 3] = [u32::MAX - 10, u32::MAX - 11, u32::MAX - 9];
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct Coord {
+        x: i16,
+        y: i16,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct SmallRect {
+        left: i16,
+        top: i16,
+        right: i16,
+        bottom: i16,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    #[allow(dead_code)]
+    struct ConsoleScreenBufferInfo {
+        size: Coord,
+        cursor_position: Coord,
+        attributes: u16,
+        window: SmallRect,
+        maximum_window_size: Coord,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetStdHandle(handle: u32) -> *mut c_void;
+        fn GetConsoleScreenBufferInfo(
+            console: *mut c_void,
+            info: *mut ConsoleScreenBufferInfo,
+        ) -> i32;
+    }
+
+    pub(super) fn size() -> Option<TermSize> {
+        STD_HANDLES.into_iter().find_map(|handle| {
+            let mut info = ConsoleScreenBufferInfo::default();
+            // SAFETY: `GetStdHandle` takes any value, and `info` is valid for the structure
+            // `GetConsoleScreenBufferInfo` writes, which fails for handles other than consoles.
+            let result = unsafe { GetConsoleScreenBufferInfo(GetStdHandle(handle), &raw mut info) };
+            let window = &info.window;
+            let columns = u16::try_from(window.right - window.left + 1).ok()?;
+            let rows = u16::try_from(window.bottom - window.top + 1).ok()?;
+            (result != 0 && columns > 0).then_some(TermSize { columns, rows })
+        })
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod platform {
+    use super::TermSize;
+
+    pub(super) fn size() -> Option<TermSize> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_size() {
+        // Tests may or may not run in a terminal, so only check what's found is plausible.
+        if let Some(size) = size() {
+            assert!(size.columns > 0);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! ANSI text styles.
+//!
+//! A [`Style`] combines a foreground [`Color`] with bold and dim, and [`Style::paint`] wraps a
+//! value in its escape codes. Whether to style at all is up to the stream: [`enabled`] says so for
+//! `stdout` or `stderr` following the `NO_COLOR` and `CLICOLOR_FORCE` conventions, and
+//! [`Style::when`] drops the codes of a style when it says no.
+
+use std::{
+    ffi::OsStr,
+    fmt,
+    io::{self, IsTerminal},
+};
+
+/// Escape code restoring the default style.
+pub const RESET: &str = "\x1b[0m";
+
+/// A foreground color of the basic 8-color palette.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+impl Color {
+    /// Select Graphic Rendition parameter setting this color.
+    const fn code(self) -> u8 {
+        30 + self as u8
+    }
+}
+
+/// A standard stream which may be a terminal.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Stream {
+    /// Standard output.
+    Stdout,
+    /// Standard error.
+    Stderr,
+}
+
+impl Stream {
+    /// Whether the stream is a terminal.
+    #[must_use]
+    pub fn is_terminal(self) -> bool {
+        match self {
+            Self::Stdout => io::stdout().is_terminal(),
+            Self::Stderr => io::stderr().is_terminal(),
+        }
+    }
+}
+
+/// Whether text written to `stream` should be styled: never if `NO_COLOR` is set to anything but
+/// an empty string, always if `CLICOLOR_FORCE` is set to anything but `0`, and otherwise if the
+/// stream is a terminal.
+#[must_use]
+pub fn enabled(stream: Stream) -> bool {
+    should_style(stream.is_terminal())
+}
+
+/// Whether text written to a terminal if `is_terminal` should be styled, following the same
+/// environment variables as [`enabled`], for writers other than the standard streams.
+#[must_use]
+pub fn should_style(is_terminal: bool) -> bool {
+    decide(
+        is_terminal,
+        std::env::var_os("NO_COLOR").as_deref(),
+        std::env::var_os("CLICOLOR_FORCE").as_deref(),
+    )
+}
+
+fn decide(is_terminal: bool, no_color: Option<&OsStr>, force: Option<&OsStr>) -> bool {
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        false
+    } else if force.is_some_and(|value| value != "0") {
+        true
+    } else {
+        is_terminal
+    }
+}
+
+/// A combination of foreground color, bold and dim. The default style is plain.
+///
+/// Formatting a style writes its escape codes, which apply until [`RESET`].
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::term::style::{self, Color, Stream, Style};
+///
+/// const ERROR: Style = Style::new().fg(Color::Red).bold();
+/// assert_eq!(ERROR.paint("failed").to_string(), "\x1b[1;31mfailed\x1b[0m");
+///
+/// let error = ERROR.when(style::enabled(Stream::Stderr));
+/// eprintln!("{}: missing input", error.paint("error"));
+/// ```
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+#[must_use]
+pub struct Style {
+    fg: Option<Color>,
+    bold: bool,
+    dim: bool,
+}
+
+impl Style {
+    /// Creates a plain style.
+    pub const fn new() -> Self {
+        Self {
+            fg: None,
+            bold: false,
+            dim: false,
+        }
+    }
+
+    /// Color text `color`.
+    pub const fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    /// Make text bold.
+    pub const fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    /// Make text dim.
+    pub const fn dim(mut self) -> Self {
+        self.dim = true;
+        self
+    }
+
+    /// Returns this style if `enabled`, or a plain style otherwise.
+    pub const fn when(self, enabled: bool) -> Self {
+        if enabled {
+            self
+        } else {
+            Self::new()
+        }
+    }
+
+    /// Whether the style leaves text as is.
+    #[must_use]
+    pub const fn is_plain(self) -> bool {
+        self.fg.is_none() && !self.bold && !self.dim
+    }
+
+    /// Wraps `value` to be formatted in this style.
+    pub const fn paint<T>(self, value: T) -> Styled<T> {
+        Styled { style: self, value }
+    }
+}
+
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_plain() {
+            return Ok(());
+        }
+        f.write_str("\x1b[")?;
+        let mut separator = "";
+        for (set, code) in [(self.bold, 1), (self.dim, 2)] {
+            if set {
+                write!(f, "{separator}{code}")?;
+                separator = ";";
+            }
+        }
+        if let Some(color) = self.fg {
+            write!(f, "{separator}{}", color.code())?;
+        }
+        f.write_str("m")
+    }
+}
+
+/// A value formatted in a [`Style`], honoring the width and alignment it's formatted with.
+/// Created by [`Style::paint`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Styled<T> {
+    style: Style,
+    value: T,
+}
+
+impl<T: fmt::Display> fmt::Display for Styled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.style.is_plain() {
+            return self.value.fmt(f);
+        }
+        write!(f, "{}", self.style)?;
+        self.value.fmt(f)?;
+        f.write_str(RESET)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_codes() {
+        assert_eq!(Style::new().to_string(), "");
+        assert_eq!(Style::new().fg(Color::Yellow).to_string(), "\x1b[33m");
+        assert_eq!(Style::new().bold().dim().to_string(), "\x1b[1;2m");
+        assert_eq!(
+            format!("[{:>4}]", Style::new().dim().paint("ab")),
+            "[\x1b[2m  ab\x1b[0m]"
+        );
+        let plain = Style::new().fg(Color::Red).when(false);
+        assert!(plain.is_plain());
+        assert_eq!(plain.paint(42).to_string(), "42");
+    }
+
+    #[test]
+    fn environment() {
+        let set = |value| Some(OsStr::new(value));
+        assert!(decide(true, None, None));
+        assert!(!decide(false, None, None));
+        assert!(!decide(true, set("1"), None));
+        assert!(decide(true, set(""), None));
+        assert!(decide(false, None, set("1")));
+        assert!(!decide(false, None, set("0")));
+        assert!(!decide(false, set("1"), set("1")));
+    }
+}
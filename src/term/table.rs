@@ -0,0 +1,303 @@
+//! Column-aligned tables.
+//!
+//! A [`Table`] lays out rows of text in columns padded to their widest cell, optionally under a
+//! header row, which is how the profile report lists its anchors. Columns can be right-aligned for
+//! numbers, and capped to a maximum width past which their cells are truncated with `…`. A table
+//! can also shrink one column to fit the width of the terminal it's printed to.
+
+use crate::str_util::truncate_with_ellipsis;
+use std::fmt;
+
+/// Narrowest a column is shrunk to by [`Table::fit_width`], unless its header is wider.
+const MIN_FIT_WIDTH: usize = 10;
+
+/// How the cells of a [`Table`] column are padded.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum Align {
+    /// Pad on the right, for text.
+    #[default]
+    Left,
+    /// Pad on the left, for numbers.
+    Right,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+struct Column {
+    align: Align,
+    max_width: Option<usize>,
+}
+
+/// Rows of text aligned in columns.
+///
+/// Widths count `char`s, so cells are expected to be free of wide characters and escape codes.
+/// Lines don't end with padding, and rows may have fewer cells than there are columns.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::term::{Align, Table};
+///
+/// let table = Table::new()
+///     .headers(["Name", "Hits"])
+///     .align(1, Align::Right)
+///     .row(["parse", "12"])
+///     .row(["lex", "1024"]);
+/// assert_eq!(table.to_string(), "Name   Hits\nparse    12\nlex    1024\n");
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+    columns: Vec<Column>,
+    indent: usize,
+    /// Column to shrink so lines fit, and the width they should fit in.
+    fit: Option<(usize, usize)>,
+}
+
+impl Table {
+    /// Creates an empty table without headers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Show `headers` above the rows.
+    pub fn headers<S: Into<String>>(mut self, headers: impl IntoIterator<Item = S>) -> Self {
+        self.headers = headers.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Pad the cells of `column`, counting from 0, as `align` says.
+    pub fn align(mut self, column: usize, align: Align) -> Self {
+        self.column(column).align = align;
+        self
+    }
+
+    /// Truncate the cells of `column`, counting from 0, to `width` characters, ending them with
+    /// `…` when cut.
+    pub fn max_width(mut self, column: usize, width: usize) -> Self {
+        self.column(column).max_width = Some(width);
+        self
+    }
+
+    /// Truncate the cells of `column`, counting from 0, further so lines are no wider than
+    /// `width`, e.g. the columns of the terminal from [`term::size`](fn@super::size). The column
+    /// is shrunk to no fewer than 10 characters, or the width of its header if that's wider. Cells
+    /// past the last header, such as free-form notes, aren't counted and may still run past
+    /// `width`.
+    pub fn fit_width(mut self, column: usize, width: usize) -> Self {
+        self.fit = Some((column, width));
+        self
+    }
+
+    /// Start every line with `indent` spaces.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Adds a row of `cells`.
+    pub fn row<S: Into<String>>(mut self, cells: impl IntoIterator<Item = S>) -> Self {
+        self.push_row(cells);
+        self
+    }
+
+    /// Adds a row of `cells`, for filling a table in a loop.
+    pub fn push_row<S: Into<String>>(&mut self, cells: impl IntoIterator<Item = S>) {
+        self.rows.push(cells.into_iter().map(Into::into).collect());
+    }
+
+    /// Number of rows, not counting the headers.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Whether the table has no rows.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns the lines of the table, the headers first if any, without line endings.
+    #[must_use]
+    pub fn lines(&self) -> Vec<String> {
+        let mut rows: Vec<Vec<String>> = self
+            .header_row()
+            .chain(self.rows.iter())
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(column, cell)| self.truncate(column, cell))
+                    .collect()
+            })
+            .collect();
+        let mut widths = Vec::new();
+        for row in &rows {
+            if widths.len() < row.len() {
+                widths.resize(row.len(), 0);
+            }
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        if let Some((column, width)) = self.fit {
+            self.shrink_to_fit(&mut rows, &mut widths, column, width);
+        }
+
+        rows.iter()
+            .map(|row| {
+                let mut line = " ".repeat(self.indent);
+                for (column, (cell, &width)) in row.iter().zip(&widths).enumerate() {
+                    if column > 0 {
+                        line.push_str("  ");
+                    }
+                    let padding = " ".repeat(width - cell.chars().count());
+                    if self.columns.get(column).map(|column| column.align) == Some(Align::Right) {
+                        line.push_str(&padding);
+                        line.push_str(cell);
+                    } else {
+                        line.push_str(cell);
+                        line.push_str(&padding);
+                    }
+                }
+                line.truncate(line.trim_end().len());
+                line
+            })
+            .collect()
+    }
+
+    /// Truncates the cells of `column` so the counted columns of each line fit in `width`.
+    fn shrink_to_fit(
+        &self,
+        rows: &mut [Vec<String>],
+        widths: &mut [usize],
+        column: usize,
+        width: usize,
+    ) {
+        let counted = match self.headers.len() {
+            0 => widths.len(),
+            headers => headers.min(widths.len()),
+        };
+        if column >= counted {
+            return;
+        }
+        let line_width = self.indent + widths[..counted].iter().sum::<usize>() + 2 * (counted - 1);
+        let Some(excess) = line_width.checked_sub(width).filter(|&excess| excess > 0) else {
+            return;
+        };
+        let header = self
+            .headers
+            .get(column)
+            .map_or(0, |header| header.chars().count());
+        let narrowest = widths[column].min(MIN_FIT_WIDTH.max(header));
+        let shrunk = widths[column].saturating_sub(excess).max(narrowest);
+        if shrunk == widths[column] {
+            return;
+        }
+        widths[column] = shrunk;
+        for cell in rows.iter_mut().filter_map(|row| row.get_mut(column)) {
+            if let std::borrow::Cow::Owned(truncated) = truncate_with_ellipsis(cell, shrunk) {
+                *cell = truncated;
+            }
+        }
+    }
+
+    fn header_row(&self) -> impl Iterator<Item = &Vec<String>> {
+        Some(&self.headers)
+            .filter(|headers| !headers.is_empty())
+            .into_iter()
+    }
+
+    fn column(&mut self, column: usize) -> &mut Column {
+        if self.columns.len() <= column {
+            self.columns.resize(column + 1, Column::default());
+        }
+        &mut self.columns[column]
+    }
+
+    fn truncate(&self, column: usize, cell: &str) -> String {
+        match self.columns.get(column).and_then(|column| column.max_width) {
+            Some(width) => truncate_with_ellipsis(cell, width).into_owned(),
+            None => cell.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in self.lines() {
+            writeln!(f, "{line}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_columns() {
+        let mut table = Table::new()
+            .headers(["Anchor", "Hits", ""])
+            .align(1, Align::Right)
+            .indent(2);
+        assert!(table.is_empty());
+        table.push_row(["main", "1", "root"]);
+        table.push_row(["parse_file", "120"]);
+        table.push_row(["lex", "40000", "hot", "extra"]);
+        assert_eq!(table.len(), 3);
+        assert_eq!(
+            table.lines(),
+            [
+                "  Anchor       Hits",
+                "  main            1  root",
+                "  parse_file    120",
+                "  lex         40000  hot   extra",
+            ]
+        );
+        assert_eq!(Table::new().to_string(), "");
+    }
+
+    #[test]
+    fn truncates_cells() {
+        let table = Table::new()
+            .max_width(0, 5)
+            .max_width(1, 0)
+            .row(["parse_file", "dropped", "kept"])
+            .row(["lex", "", "µs"]);
+        assert_eq!(table.to_string(), "pars…    kept\nlex      µs\n");
+    }
+
+    #[test]
+    fn fits_width() {
+        let table = Table::new()
+            .headers(["Anchor", "Hits"])
+            .align(1, Align::Right)
+            .indent(2)
+            .row([
+                "util_lib_rs::parse::read_document",
+                "12",
+                "notes past the headers",
+            ])
+            .row(["lex", "4"]);
+        assert_eq!(
+            table.clone().fit_width(0, 24).lines(),
+            [
+                "  Anchor            Hits",
+                "  util_lib_rs::pa…    12  notes past the headers",
+                "  lex                  4",
+            ]
+        );
+        assert_eq!(
+            table.clone().fit_width(0, 4).lines()[1],
+            "  util_lib_…    12  notes past the headers"
+        );
+        assert_eq!(
+            table.clone().fit_width(0, 80).lines(),
+            table.clone().lines()
+        );
+        assert_eq!(table.clone().fit_width(5, 4).lines(), table.lines());
+    }
+}
@@ -0,0 +1,295 @@
+//! Helpers for tests which change process-wide state.
+//!
+//! Tests run in parallel threads of one process, so a test which sets an environment variable or
+//! changes the working directory can break another which reads it at the same time, and one which
+//! panics before undoing its change breaks every test after it. An [`EnvGuard`] sets variables and
+//! a [`CwdGuard`] changes the working directory, restoring what was there before when dropped,
+//! even while unwinding. Each guard holds a lock while it lives, so tests using guards take turns.
+//! A thread can create several guards at once, so helpers can make their own.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt, io,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex, PoisonError},
+    thread::{self, ThreadId},
+};
+
+/// Serializes every [`EnvGuard`].
+static ENV_LOCK: ReentrantLock = ReentrantLock::new();
+/// Serializes every [`CwdGuard`].
+static CWD_LOCK: ReentrantLock = ReentrantLock::new();
+
+/// A lock which the thread holding it can take again.
+struct ReentrantLock {
+    /// The thread holding the lock and how many times it has taken it.
+    owner: Mutex<(Option<ThreadId>, usize)>,
+    released: Condvar,
+}
+
+impl ReentrantLock {
+    const fn new() -> Self {
+        Self {
+            owner: Mutex::new((None, 0)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until no other thread holds the lock and takes it.
+    fn lock(&'static self) -> LockHold {
+        let thread = thread::current().id();
+        let mut owner = self.owner.lock().unwrap_or_else(PoisonError::into_inner);
+        while owner.0.is_some_and(|holder| holder != thread) {
+            owner = self
+                .released
+                .wait(owner)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *owner = (Some(thread), owner.1 + 1);
+        LockHold {
+            lock: self,
+            _thread: PhantomData,
+        }
+    }
+}
+
+/// Releases a [`ReentrantLock`] once when dropped.
+struct LockHold {
+    lock: &'static ReentrantLock,
+    /// Releasing from another thread would release the wrong thread's hold.
+    _thread: PhantomData<*const ()>,
+}
+
+impl Drop for LockHold {
+    fn drop(&mut self) {
+        let mut owner = self
+            .lock
+            .owner
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        owner.1 -= 1;
+        if owner.1 == 0 {
+            owner.0 = None;
+            self.lock.released.notify_one();
+        }
+    }
+}
+
+/// Sets and removes environment variables, restoring their previous values when dropped.
+///
+/// Guards on other threads wait for this one to be dropped, but code which reads variables without
+/// a guard isn't stopped. A guard which changes nothing still holds the lock, for tests which read
+/// variables other tests change.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::test_util::EnvGuard;
+///
+/// {
+///     let _env = EnvGuard::new()
+///         .set("EXAMPLE_LOG_LEVEL", "debug")
+///         .remove("EXAMPLE_NO_COLOR");
+///     assert_eq!(std::env::var("EXAMPLE_LOG_LEVEL").as_deref(), Ok("debug"));
+/// }
+/// assert!(std::env::var_os("EXAMPLE_LOG_LEVEL").is_none());
+/// ```
+#[must_use = "variables are restored immediately if the guard isn't bound to a variable"]
+pub struct EnvGuard {
+    /// Each changed variable with its value before the first change, in the order changed.
+    saved: Vec<(OsString, Option<OsString>)>,
+    _lock: LockHold,
+}
+
+impl EnvGuard {
+    /// Creates a guard which hasn't changed anything yet, waiting for guards on other threads to
+    /// be dropped.
+    pub fn new() -> Self {
+        Self {
+            saved: Vec::new(),
+            _lock: ENV_LOCK.lock(),
+        }
+    }
+
+    /// Sets the variable `name` to `value` until the guard is dropped.
+    pub fn set(mut self, name: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Self {
+        self.save(name.as_ref());
+        std::env::set_var(name, value);
+        self
+    }
+
+    /// Removes the variable `name` until the guard is dropped.
+    pub fn remove(mut self, name: impl AsRef<OsStr>) -> Self {
+        self.save(name.as_ref());
+        std::env::remove_var(name);
+        self
+    }
+
+    fn save(&mut self, name: &OsStr) {
+        if !self.saved.iter().any(|(saved, _)| saved == name) {
+            self.saved.push((name.to_owned(), std::env::var_os(name)));
+        }
+    }
+}
+
+impl Default for EnvGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (name, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(value) => std::env::set_var(name, value),
+                None => std::env::remove_var(name),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for EnvGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvGuard")
+            .field("saved", &self.saved)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Changes the working directory, changing it back when dropped.
+///
+/// Guards on other threads wait for this one to be dropped, but relative paths used without a
+/// guard still resolve against whichever directory is current.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::{fs::TempDir, test_util::CwdGuard};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let dir = TempDir::new()?;
+/// {
+///     let _cwd = CwdGuard::new(dir.path())?;
+///     std::fs::write("notes.txt", "relative to the temporary directory")?;
+/// }
+/// assert!(dir.path().join("notes.txt").exists());
+/// # Ok(())
+/// # }
+/// ```
+#[must_use = "the directory is changed back immediately if the guard isn't bound to a variable"]
+pub struct CwdGuard {
+    previous: PathBuf,
+    _lock: LockHold,
+}
+
+impl CwdGuard {
+    /// Changes the working directory to `dir`, waiting for guards on other threads to be dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the working directory can't be read or changed to `dir`.
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let lock = CWD_LOCK.lock();
+        let previous = std::env::current_dir()?;
+        std::env::set_current_dir(dir)?;
+        Ok(Self {
+            previous,
+            _lock: lock,
+        })
+    }
+
+    /// The working directory before the guard changed it, which it's changed back to when dropped.
+    #[must_use]
+    pub fn previous(&self) -> &Path {
+        &self.previous
+    }
+}
+
+impl Drop for CwdGuard {
+    fn drop(&mut self) {
+        if let Err(err) = std::env::set_current_dir(&self.previous) {
+            eprintln!(
+                "failed to restore working directory {}: {err}",
+                self.previous.display()
+            );
+        }
+    }
+}
+
+impl fmt::Debug for CwdGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CwdGuard")
+            .field("previous", &self.previous)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::TempDir;
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    #[test]
+    fn restores_variables() {
+        let var = |name| std::env::var(name).ok();
+        {
+            let _env = EnvGuard::new()
+                .set("UTIL_TEST_UTIL_SET", "before")
+                .remove("UTIL_TEST_UTIL_REMOVED");
+            let result = std::panic::catch_unwind(|| {
+                let _env = EnvGuard::new()
+                    .set("UTIL_TEST_UTIL_SET", "first")
+                    .set("UTIL_TEST_UTIL_SET", "second")
+                    .set("UTIL_TEST_UTIL_REMOVED", "added");
+                assert_eq!(var("UTIL_TEST_UTIL_SET").as_deref(), Some("second"));
+                panic!("failed test");
+            });
+            assert!(result.is_err());
+            assert_eq!(var("UTIL_TEST_UTIL_SET").as_deref(), Some("before"));
+            assert_eq!(var("UTIL_TEST_UTIL_REMOVED"), None);
+        }
+        assert_eq!(var("UTIL_TEST_UTIL_SET"), None);
+    }
+
+    #[test]
+    fn guards_take_turns() {
+        let guard = EnvGuard::new().set("UTIL_TEST_UTIL_TURN", "main");
+        let done = Arc::new(AtomicBool::new(false));
+        let other = thread::spawn({
+            let done = Arc::clone(&done);
+            move || {
+                let _env = EnvGuard::new();
+                let seen = std::env::var("UTIL_TEST_UTIL_TURN").ok();
+                done.store(true, Ordering::Relaxed);
+                seen
+            }
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(!done.load(Ordering::Relaxed));
+        drop(guard);
+        assert_eq!(other.join().expect("valid thread"), None);
+    }
+
+    #[test]
+    fn restores_working_directory() {
+        let dir = TempDir::new().expect("temporary directory");
+        let original = std::env::current_dir().expect("working directory");
+        {
+            let cwd = CwdGuard::new(dir.path()).expect("changed directory");
+            assert_eq!(cwd.previous(), original);
+            let current = std::env::current_dir().expect("working directory");
+            assert_eq!(current.canonicalize().ok(), dir.path().canonicalize().ok());
+            assert!(CwdGuard::new(dir.path().join("missing")).is_err());
+        }
+        assert_eq!(std::env::current_dir().ok(), Some(original));
+    }
+}
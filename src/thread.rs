@@ -0,0 +1,365 @@
+//! Thread pools.
+//!
+//! A [`Pool`] runs jobs on a fixed set of worker threads. [`Pool::execute`] queues jobs that own
+//! their data, and [`Pool::scope`] runs jobs borrowing from the caller, waiting for all of them
+//! before it returns. Dropping the pool finishes every queued job and joins the workers.
+
+use std::{
+    any::Any,
+    marker::PhantomData,
+    num::NonZeroUsize,
+    panic::{self, AssertUnwindSafe},
+    sync::{mpsc, Arc, Condvar, Mutex, PoisonError},
+    thread::{self, JoinHandle},
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Number of jobs queued or running, and a signal for when it reaches zero.
+#[derive(Debug, Default)]
+struct Pending {
+    count: Mutex<usize>,
+    idle: Condvar,
+}
+
+impl Pending {
+    fn add(&self) {
+        *self.count.lock().unwrap_or_else(PoisonError::into_inner) += 1;
+    }
+
+    fn done(&self) {
+        let mut count = self.count.lock().unwrap_or_else(PoisonError::into_inner);
+        *count -= 1;
+        if *count == 0 {
+            self.idle.notify_all();
+        }
+    }
+
+    fn wait(&self) {
+        let count = self.count.lock().unwrap_or_else(PoisonError::into_inner);
+        drop(
+            self.idle
+                .wait_while(count, |count| *count > 0)
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+    }
+}
+
+/// Configures and starts a [`Pool`].
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct PoolBuilder {
+    threads: usize,
+    name: String,
+    profiled: bool,
+}
+
+impl Default for PoolBuilder {
+    fn default() -> Self {
+        Self {
+            threads: 0,
+            name: "pool".to_string(),
+            profiled: false,
+        }
+    }
+}
+
+impl PoolBuilder {
+    /// Creates a builder for a pool with a worker per available CPU, named `pool`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start `threads` workers, or one per available CPU if 0.
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+
+    /// Name the workers `{name}-0`, `{name}-1` and so on, which is also how the profiler labels
+    /// their reports.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    /// Time every job under a profiler anchor named after the pool. With
+    /// [thread aggregation](crate::performance::threads::set_thread_aggregation) enabled, the
+    /// workers' statistics are included in the first report ended after the pool is dropped.
+    pub fn profiled(mut self, profiled: bool) -> Self {
+        self.profiled = profiled;
+        self
+    }
+
+    /// Starts the workers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a worker thread can't be spawned.
+    pub fn build(self) -> std::io::Result<Pool> {
+        let threads = match self.threads {
+            0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+            threads => threads,
+        };
+        let anchor = self
+            .profiled
+            .then(|| crate::intern::intern_static(&self.name));
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let pending = Arc::new(Pending::default());
+        let mut pool = Pool {
+            sender: Some(sender),
+            workers: Vec::with_capacity(threads),
+            pending: Arc::clone(&pending),
+        };
+        for index in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            let pending = Arc::clone(&pending);
+            let worker = thread::Builder::new()
+                .name(format!("{}-{index}", self.name))
+                .spawn(move || loop {
+                    // The lock is released before running the job, so other workers can take the
+                    // next one meanwhile.
+                    let job = receiver
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    let Ok(job) = job else {
+                        break;
+                    };
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| match anchor {
+                        Some(anchor) => {
+                            crate::profile!(anchor);
+                            job();
+                        }
+                        None => job(),
+                    }));
+                    pending.done();
+                })?;
+            pool.workers.push(worker);
+        }
+        Ok(pool)
+    }
+}
+
+/// A fixed set of worker threads running queued jobs in order.
+///
+/// A job which panics doesn't take its worker down: the panic is printed by the panic hook as
+/// usual, then the worker moves on to the next job.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::thread::Pool;
+/// use std::sync::atomic::{AtomicU64, Ordering};
+///
+/// let pool = Pool::new(4);
+/// let chunks = [[1, 2], [3, 4], [5, 6]];
+/// let total = AtomicU64::new(0);
+/// pool.scope(|scope| {
+///     for chunk in &chunks {
+///         let total = &total;
+///         scope.execute(move || {
+///             total.fetch_add(chunk.iter().sum(), Ordering::Relaxed);
+///         });
+///     }
+/// });
+/// assert_eq!(total.into_inner(), 21);
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct Pool {
+    /// Queue of jobs, closed when the pool shuts down.
+    sender: Option<mpsc::Sender<Job>>,
+    workers: Vec<JoinHandle<()>>,
+    pending: Arc<Pending>,
+}
+
+impl Pool {
+    /// Starts a pool of `threads` workers, or one per available CPU if 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a worker thread can't be spawned.
+    pub fn new(threads: usize) -> Self {
+        PoolBuilder::new()
+            .threads(threads)
+            .build()
+            .expect("valid worker threads")
+    }
+
+    /// Creates a builder to name the workers or profile their jobs.
+    pub fn builder() -> PoolBuilder {
+        PoolBuilder::new()
+    }
+
+    /// Number of worker threads.
+    #[must_use]
+    pub fn threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queues `job` to run on the next free worker.
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        self.send(Box::new(job));
+    }
+
+    fn send(&self, job: Job) {
+        self.pending.add();
+        let sender = self.sender.as_ref().expect("valid job queue");
+        // Workers only exit once the queue is closed, so sending can't fail.
+        let _ = sender.send(job);
+    }
+
+    /// Blocks until every job queued so far, including those of other threads, has finished.
+    pub fn wait(&self) {
+        self.pending.wait();
+    }
+
+    /// Calls `f` with a [`Scope`] for queuing jobs that borrow from the caller, then blocks until
+    /// all of them have finished.
+    ///
+    /// Don't call `scope` from a job of the same pool: if every worker waits for a scope, none is
+    /// left to run the scoped jobs.
+    ///
+    /// # Panics
+    ///
+    /// Resumes the panic of `f` or of the first scoped job which panicked, after every job has
+    /// finished.
+    pub fn scope<'scope, R>(&self, f: impl FnOnce(&Scope<'_, 'scope>) -> R) -> R {
+        let scope = Scope {
+            pool: self,
+            pending: Arc::new(Pending::default()),
+            panic: Arc::new(Mutex::new(None)),
+            _borrows: PhantomData,
+        };
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        scope.pending.wait();
+        let job_panic = scope
+            .panic
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        match (result, job_panic) {
+            (Err(panic), _) | (Ok(_), Some(panic)) => panic::resume_unwind(panic),
+            (Ok(result), None) => result,
+        }
+    }
+
+    /// Finishes every queued job and joins the workers, as dropping the pool does.
+    pub fn shutdown(self) {}
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Queues jobs borrowing data that outlives a [`Pool::scope`] call.
+#[derive(Debug)]
+pub struct Scope<'pool, 'scope> {
+    pool: &'pool Pool,
+    pending: Arc<Pending>,
+    /// Panic of the first scoped job which panicked.
+    panic: Arc<Mutex<Option<Box<dyn Any + Send>>>>,
+    /// Makes `'scope` invariant, so it can't shrink to less than the data jobs borrow.
+    _borrows: PhantomData<&'scope mut &'scope ()>,
+}
+
+impl<'scope> Scope<'_, 'scope> {
+    /// Queues `job` to run on the next free worker before the scope ends.
+    pub fn execute(&self, job: impl FnOnce() + Send + 'scope) {
+        self.pending.add();
+        let pending = Arc::clone(&self.pending);
+        let panic = Arc::clone(&self.panic);
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || {
+            if let Err(err) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                panic
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .get_or_insert(err);
+            }
+            pending.done();
+        });
+        // SAFETY: `Pool::scope` waits for every scoped job to finish before returning, even if
+        // its closure panics, so the job never outlives `'scope`.
+        let job = unsafe { std::mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Job>(job) };
+        self.pool.send(job);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn runs_jobs() {
+        let pool = Pool::builder()
+            .threads(3)
+            .name("test")
+            .build()
+            .expect("valid pool");
+        assert_eq!(pool.threads(), 3);
+        let count = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let count = Arc::clone(&count);
+            pool.execute(move || {
+                count.fetch_add(1, Ordering::Relaxed);
+            });
+        }
+        pool.wait();
+        assert_eq!(count.load(Ordering::Relaxed), 100);
+
+        let (sender, receiver) = mpsc::channel();
+        pool.execute(move || {
+            sender
+                .send(thread::current().name().map(str::to_string))
+                .expect("valid channel");
+        });
+        let name = receiver.recv().expect("valid name").expect("named worker");
+        assert!(name.starts_with("test-"));
+
+        pool.execute(|| panic!("job panic"));
+        let count_after = Arc::clone(&count);
+        pool.execute(move || {
+            count_after.fetch_add(1, Ordering::Relaxed);
+        });
+        pool.shutdown();
+        assert_eq!(count.load(Ordering::Relaxed), 101);
+    }
+
+    #[test]
+    fn scoped_jobs() {
+        let pool = Pool::new(2);
+        let mut values = vec![1, 2, 3, 4];
+        let sum = AtomicUsize::new(0);
+        let result = pool.scope(|scope| {
+            for value in &mut values {
+                let sum = &sum;
+                scope.execute(move || {
+                    *value *= 10;
+                    sum.fetch_add(*value, Ordering::Relaxed);
+                });
+            }
+            "done"
+        });
+        assert_eq!(result, "done");
+        assert_eq!(values, [10, 20, 30, 40]);
+        assert_eq!(sum.into_inner(), 100);
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|scope| {
+                scope.execute(|| panic!("scoped panic"));
+                scope.execute(|| {});
+            });
+        }));
+        let panic = panicked.expect_err("resumed panic");
+        assert_eq!(panic.downcast_ref::<&str>(), Some(&"scoped panic"));
+        pool.scope(|scope| scope.execute(|| {}));
+    }
+}
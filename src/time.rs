@@ -0,0 +1,993 @@
+//! Ad-hoc timing.
+//!
+//! A [`Stopwatch`] times a region without the anchors and reports of the profiler. With the `perf`
+//! feature it reads the same timestamp counter as profile blocks, and otherwise the operating
+//! system's monotonic clock.
+//!
+//! A [`Throttler`] lets a closure run at most once per interval, on the first call of each
+//! interval, and a [`Debouncer`] runs the last closure it was given once calls stop for a delay,
+//! for redrawing output or rerunning work without doing it for every update.
+//!
+//! A [`Ticker`] ticks at a fixed interval, scheduling each tick from the start rather than from
+//! when the previous one was handled, so slow work doesn't make it drift. A [`Scheduler`] runs
+//! closures at their own intervals on a background thread.
+//!
+//! [`with_timeout`] runs a blocking closure on a helper thread and gives up waiting for it after a
+//! time limit, and a [`TimeLimit`] configures what happens to the closure when it does.
+
+use crate::signal::ShutdownFlag;
+use std::{
+    error::Error,
+    fmt, io, panic,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, RecvTimeoutError, Sender},
+        Arc, Condvar, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+/// Reads the timer, in ticks of [`frequency`].
+fn now() -> u64 {
+    #[cfg(feature = "perf")]
+    {
+        crate::performance::timestamp()
+    }
+    #[cfg(not(feature = "perf"))]
+    {
+        static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        let elapsed = EPOCH.get_or_init(std::time::Instant::now).elapsed();
+        u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX)
+    }
+}
+
+/// Returns the timer frequency in ticks per second.
+fn frequency() -> u64 {
+    #[cfg(feature = "perf")]
+    {
+        crate::performance::timer_frequency()
+    }
+    #[cfg(not(feature = "perf"))]
+    1_000_000_000
+}
+
+/// One lap recorded with [`Stopwatch::lap`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Lap {
+    /// Running time since the previous lap, or since the stopwatch first started.
+    pub duration: Duration,
+    /// Total running time when the lap was recorded.
+    pub split: Duration,
+}
+
+/// Measures running time, which can be paused with [`stop`](Stopwatch::stop) and divided into laps.
+///
+/// # Examples
+///
+/// ```
+/// use util_lib_rs::time::Stopwatch;
+///
+/// let mut stopwatch = Stopwatch::start_new();
+/// for _ in 0..3 {
+///     // ...
+///     stopwatch.lap();
+/// }
+/// stopwatch.stop();
+/// assert_eq!(stopwatch.laps().len(), 3);
+/// assert!(stopwatch.laps()[2].split <= stopwatch.elapsed());
+/// println!("{:?}", stopwatch.elapsed());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[must_use]
+pub struct Stopwatch {
+    /// Timer frequency in ticks per second.
+    frequency: u64,
+    /// Timer reading when the stopwatch last started, while running.
+    started: Option<u64>,
+    /// Running time in ticks before the stopwatch last started.
+    accumulated: u64,
+    /// Running time in ticks when the last lap was recorded.
+    last_split: u64,
+    laps: Vec<Lap>,
+}
+
+impl Default for Stopwatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Stopwatch {
+    /// Creates a stopped stopwatch with no running time.
+    pub fn new() -> Self {
+        Self {
+            frequency: frequency(),
+            started: None,
+            accumulated: 0,
+            last_split: 0,
+            laps: Vec::new(),
+        }
+    }
+
+    /// Creates a stopwatch and starts it.
+    pub fn start_new() -> Self {
+        let mut stopwatch = Self::new();
+        stopwatch.start();
+        stopwatch
+    }
+
+    /// Starts the stopwatch, or resumes it after [`stop`](Stopwatch::stop). Does nothing if it's
+    /// already running.
+    pub fn start(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(now());
+        }
+    }
+
+    /// Stops the stopwatch, returning its total running time. Time passing while stopped isn't
+    /// counted towards the running time or the current lap.
+    pub fn stop(&mut self) -> Duration {
+        self.accumulated = self.ticks();
+        self.started = None;
+        self.to_duration(self.accumulated)
+    }
+
+    /// Records a lap ending now, returning its duration.
+    pub fn lap(&mut self) -> Duration {
+        let split = self.ticks();
+        let lap = Lap {
+            duration: self
+                .to_duration(split)
+                .saturating_sub(self.to_duration(self.last_split)),
+            split: self.to_duration(split),
+        };
+        self.last_split = split;
+        self.laps.push(lap);
+        lap.duration
+    }
+
+    /// Stops the stopwatch, clearing its running time and laps.
+    pub fn reset(&mut self) {
+        self.started = None;
+        self.accumulated = 0;
+        self.last_split = 0;
+        self.laps.clear();
+    }
+
+    /// Total running time.
+    #[must_use]
+    pub fn elapsed(&self) -> Duration {
+        self.to_duration(self.ticks())
+    }
+
+    /// Whether the stopwatch is running.
+    #[must_use]
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Every lap recorded since the stopwatch was created or reset, oldest first.
+    pub fn laps(&self) -> &[Lap] {
+        &self.laps
+    }
+
+    /// Total running time in timer ticks.
+    fn ticks(&self) -> u64 {
+        self.accumulated.saturating_add(
+            self.started
+                .map_or(0, |started| now().saturating_sub(started)),
+        )
+    }
+
+    fn to_duration(&self, ticks: u64) -> Duration {
+        if self.frequency == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = u128::from(ticks) * 1_000_000_000 / u128::from(self.frequency);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+/// Converts `duration` to whole nanoseconds, saturating at `u64::MAX`.
+fn as_nanos(duration: Duration) -> u64 {
+    u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX)
+}
+
+/// Limits something to happening at most once per interval, letting through the first attempt of
+/// each interval and rejecting the rest: a leading-edge throttle. Checking it is one atomic load
+/// when it rejects, so it can be called from hot loops and any thread.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::time::Throttler;
+///
+/// let throttle = Throttler::new(Duration::from_secs(60));
+/// assert_eq!(throttle.call(|| "saved"), Some("saved"));
+/// assert_eq!(throttle.call(|| "saved"), None);
+/// ```
+#[derive(Debug)]
+#[must_use]
+pub struct Throttler {
+    interval: u64,
+    epoch: Instant,
+    /// Nanoseconds after `epoch` of the last attempt let through, plus one, or zero before the
+    /// first.
+    last: AtomicU64,
+}
+
+impl Throttler {
+    /// Creates a throttle letting through one attempt per `interval`, starting with the next.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval: as_nanos(interval),
+            epoch: Instant::now(),
+            last: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the minimum time between attempts let through.
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        Duration::from_nanos(self.interval)
+    }
+
+    /// Whether at least the interval has passed since the last attempt let through, counting
+    /// this as one if so.
+    #[must_use]
+    pub fn ready(&self) -> bool {
+        let now = as_nanos(self.epoch.elapsed()).saturating_add(1);
+        let mut last = self.last.load(Ordering::Relaxed);
+        loop {
+            if last != 0 && now.saturating_sub(last) < self.interval {
+                return false;
+            }
+            match self.last.compare_exchange_weak(
+                last,
+                now.max(last),
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => last = actual,
+            }
+        }
+    }
+
+    /// Runs `f` if the throttle is [ready](Self::ready), returning its result.
+    pub fn call<R>(&self, f: impl FnOnce() -> R) -> Option<R> {
+        self.ready().then(f)
+    }
+
+    /// Lets the next attempt through, however soon it comes.
+    pub fn reset(&self) {
+        self.last.store(0, Ordering::Relaxed);
+    }
+}
+
+/// A closure waiting for the debounce delay to pass.
+type Pending = Box<dyn FnOnce() + Send>;
+
+struct DebounceState {
+    /// The latest closure and when it's due.
+    pending: Option<(Instant, Pending)>,
+    stopped: bool,
+}
+
+struct DebounceShared {
+    state: Mutex<DebounceState>,
+    changed: Condvar,
+}
+
+impl DebounceShared {
+    fn lock(&self) -> MutexGuard<'_, DebounceState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Runs the latest closure it's given once it hasn't been given another for a delay: a
+/// trailing-edge debounce. Closures run on a thread of its own, so it can be called from any
+/// thread, or from callbacks such as a [`Watcher`](crate::fs::Watcher) handler.
+///
+/// Dropping it discards a pending closure without running it. Call [`flush`](Self::flush) first
+/// to run it.
+///
+/// # Examples
+///
+/// ```
+/// use std::{sync::mpsc, time::Duration};
+/// use util_lib_rs::time::Debouncer;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let (sender, rebuilds) = mpsc::channel();
+/// let debouncer = Debouncer::new(Duration::from_millis(20))?;
+/// for file in ["a.rs", "b.rs", "c.rs"] {
+///     let sender = sender.clone();
+///     debouncer.call(move || sender.send(file).unwrap());
+/// }
+/// assert_eq!(rebuilds.recv_timeout(Duration::from_secs(10)), Ok("c.rs"));
+/// # Ok(())
+/// # }
+/// ```
+#[must_use]
+pub struct Debouncer {
+    delay: Duration,
+    shared: Arc<DebounceShared>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Debouncer {
+    /// Creates a debouncer running closures once `delay` passes without another call.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if its thread can't be spawned.
+    pub fn new(delay: Duration) -> io::Result<Self> {
+        let shared = Arc::new(DebounceShared {
+            state: Mutex::new(DebounceState {
+                pending: None,
+                stopped: false,
+            }),
+            changed: Condvar::new(),
+        });
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-debouncer".to_string())
+            .spawn({
+                let shared = Arc::clone(&shared);
+                move || run_debounced(&shared)
+            })?;
+        Ok(Self {
+            delay,
+            shared,
+            thread: Some(thread),
+        })
+    }
+
+    /// Returns the delay after the last call before its closure runs.
+    #[must_use]
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Runs `f` after the delay, unless another call replaces it first.
+    pub fn call(&self, f: impl FnOnce() + Send + 'static) {
+        self.shared.lock().pending = Some((Instant::now() + self.delay, Box::new(f)));
+        self.shared.changed.notify_all();
+    }
+
+    /// Whether a closure is waiting for the delay to pass.
+    #[must_use]
+    pub fn is_pending(&self) -> bool {
+        self.shared.lock().pending.is_some()
+    }
+
+    /// Runs the pending closure now, on this thread, returning whether there was one.
+    #[allow(clippy::must_use_candidate)]
+    pub fn flush(&self) -> bool {
+        let pending = self.shared.lock().pending.take();
+        pending.map(|(_, f)| f()).is_some()
+    }
+
+    /// Discards the pending closure, returning whether there was one.
+    #[allow(clippy::must_use_candidate)]
+    pub fn cancel(&self) -> bool {
+        self.shared.lock().pending.take().is_some()
+    }
+}
+
+/// Runs each pending closure once it's due, until the debouncer is dropped.
+fn run_debounced(shared: &DebounceShared) {
+    let mut state = shared.lock();
+    while !state.stopped {
+        let Some(due) = state.pending.as_ref().map(|(due, _)| *due) else {
+            state = shared
+                .changed
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+            continue;
+        };
+        let now = Instant::now();
+        if now < due {
+            state = shared
+                .changed
+                .wait_timeout(state, due - now)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        } else if let Some((_, f)) = state.pending.take() {
+            drop(state);
+            f();
+            state = shared.lock();
+        }
+    }
+}
+
+impl fmt::Debug for Debouncer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Debouncer")
+            .field("delay", &self.delay)
+            .field("pending", &self.is_pending())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Debouncer {
+    fn drop(&mut self) {
+        self.shared.lock().stopped = true;
+        self.shared.changed.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Ticks at a fixed interval. Each tick is due a whole number of intervals after the ticker was
+/// created, however long handling the previous one took. Ticks missed because handling took
+/// longer than an interval are skipped rather than delivered in a burst, and counted by
+/// [`missed`](Self::missed).
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::time::Ticker;
+///
+/// let mut ticker = Ticker::new(Duration::from_millis(5));
+/// for _ in 0..3 {
+///     ticker.tick();
+///     // sample, flush or redraw...
+/// }
+/// assert_eq!(ticker.missed(), 0);
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Ticker {
+    interval: Duration,
+    next: Instant,
+    missed: u64,
+}
+
+impl Ticker {
+    /// Creates a ticker whose first tick is due one `interval` from now.
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            next: Instant::now() + interval,
+            missed: 0,
+        }
+    }
+
+    /// Returns the time between ticks.
+    #[must_use]
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns when the next tick is due.
+    #[must_use]
+    pub fn next_tick(&self) -> Instant {
+        self.next
+    }
+
+    /// Returns how long until the next tick is due, or zero if it's due already.
+    #[must_use]
+    pub fn until_next(&self) -> Duration {
+        self.next.saturating_duration_since(Instant::now())
+    }
+
+    /// Returns how many ticks were skipped because they were overdue by more than an interval.
+    #[must_use]
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    /// Blocks until the next tick is due, returning when it was due.
+    pub fn tick(&mut self) -> Instant {
+        loop {
+            if let Some(due) = self.try_tick() {
+                return due;
+            }
+            thread::sleep(self.until_next());
+        }
+    }
+
+    /// Returns when the next tick was due if it's due already, moving on to the one after,
+    /// without blocking.
+    pub fn try_tick(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        if now < self.next {
+            return None;
+        }
+        let due = self.next;
+        let interval = as_nanos(self.interval);
+        if let Some(behind) = as_nanos(now - due).checked_div(interval) {
+            self.missed += behind;
+            self.next += Duration::from_nanos(interval.saturating_mul(behind + 1));
+        } else {
+            self.next = now;
+        }
+        Some(due)
+    }
+
+    /// Schedules the next tick one interval from now.
+    pub fn reset(&mut self) {
+        self.next = Instant::now() + self.interval;
+    }
+}
+
+impl Iterator for Ticker {
+    type Item = Instant;
+
+    /// Blocks until the next tick, as [`tick`](Self::tick) does. Never returns `None`.
+    fn next(&mut self) -> Option<Instant> {
+        Some(self.tick())
+    }
+}
+
+/// A closure a [`Scheduler`] runs at every tick of its ticker.
+type Task = (Ticker, Box<dyn FnMut() + Send>);
+
+/// Runs closures at their own intervals on a background thread, scheduled like a [`Ticker`], so
+/// daemons can flush, sample and report periodically without a thread for each. Closures run one
+/// at a time, so a slow one delays the others.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use util_lib_rs::{performance, time::Scheduler};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let _scheduler = Scheduler::new()
+///     .every(Duration::from_secs(1), || eprintln!("still running"))
+///     .every(Duration::from_secs(60), performance::profile_publish)
+///     .start()?;
+/// // serve requests...
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+#[must_use]
+pub struct Scheduler {
+    tasks: Vec<Task>,
+}
+
+impl Scheduler {
+    /// Creates a scheduler with no closures.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `task` every `interval`, starting one interval after the scheduler starts.
+    pub fn every(mut self, interval: Duration, task: impl FnMut() + Send + 'static) -> Self {
+        self.tasks.push((Ticker::new(interval), Box::new(task)));
+        self
+    }
+
+    /// Starts the scheduler thread. Closures stop running when the returned guard is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the thread can't be spawned.
+    pub fn start(mut self) -> io::Result<SchedulerGuard> {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        for (ticker, _) in &mut self.tasks {
+            ticker.reset();
+        }
+        let thread = thread::Builder::new()
+            .name("util_lib_rs-scheduler".to_string())
+            .spawn(move || loop {
+                let wait = self
+                    .tasks
+                    .iter()
+                    .map(|(ticker, _)| ticker.until_next())
+                    .min();
+                let result = match wait {
+                    Some(wait) => cancelled.recv_timeout(wait),
+                    None => cancelled.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                if !matches!(result, Err(RecvTimeoutError::Timeout)) {
+                    break;
+                }
+                for (ticker, task) in &mut self.tasks {
+                    if ticker.try_tick().is_some() {
+                        task();
+                    }
+                }
+            })?;
+        Ok(SchedulerGuard {
+            cancel: Some(cancel),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl fmt::Debug for Scheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scheduler")
+            .field(
+                "intervals",
+                &self
+                    .tasks
+                    .iter()
+                    .map(|(ticker, _)| ticker.interval())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// A running [`Scheduler`], stopped when dropped.
+#[derive(Debug)]
+#[must_use]
+pub struct SchedulerGuard {
+    cancel: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for SchedulerGuard {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the scheduler thread before its next tick.
+        drop(self.cancel.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Runs `f` on a helper thread, returning its result, or [`Timeout`] if it doesn't finish within
+/// `limit`. A thread can't be stopped from outside, so `f` keeps running in the background after
+/// a timeout and its result is dropped. Use a [`TimeLimit`] to let `f` notice the timeout and stop
+/// early, or to wait for it to stop.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::time::with_timeout;
+///
+/// assert_eq!(with_timeout(Duration::from_secs(10), || 2 + 2), Ok(4));
+/// let slow = with_timeout(Duration::from_millis(10), || {
+///     std::thread::sleep(Duration::from_millis(200));
+/// });
+/// assert!(slow.is_err());
+/// ```
+///
+/// # Errors
+///
+/// Returns [`Timeout`] if `f` runs for longer than `limit`.
+///
+/// # Panics
+///
+/// Panics if the helper thread can't be spawned, and resumes the panic of `f` if it panics before
+/// the limit.
+pub fn with_timeout<R: Send + 'static>(
+    limit: Duration,
+    f: impl FnOnce() -> R + Send + 'static,
+) -> Result<R, Timeout> {
+    TimeLimit::new(limit).run(f)
+}
+
+/// What happens to a closure run by a [`TimeLimit`] once it times out.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum OnTimeout {
+    /// Return the timeout at once, leaving the closure running in the background.
+    #[default]
+    Detach,
+    /// Wait for the closure to return before returning the timeout, so no work outlives the call.
+    /// Closures which don't check for cancellation make this wait as long as they run.
+    Join,
+}
+
+/// A time limit for blocking closures, each run on a helper thread.
+///
+/// Closures run with [`run_cancellable`](Self::run_cancellable) are given a [`ShutdownFlag`],
+/// which is set when they time out, so long-running work can check it and stop.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use util_lib_rs::time::{OnTimeout, TimeLimit};
+///
+/// let limit = TimeLimit::new(Duration::from_millis(20)).on_timeout(OnTimeout::Join);
+/// let result = limit.run_cancellable(|cancelled| {
+///     let mut polls = 0;
+///     while !cancelled.wait_timeout(Duration::from_millis(5)) {
+///         polls += 1;
+///     }
+///     polls
+/// });
+/// // The closure has stopped by the time the timeout is returned.
+/// assert_eq!(result.unwrap_err().limit(), Duration::from_millis(20));
+/// ```
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct TimeLimit {
+    limit: Duration,
+    on_timeout: OnTimeout,
+    name: Option<String>,
+}
+
+impl TimeLimit {
+    /// Creates a limit of `limit` which leaves closures running when they time out.
+    pub fn new(limit: Duration) -> Self {
+        Self {
+            limit,
+            on_timeout: OnTimeout::default(),
+            name: None,
+        }
+    }
+
+    /// Sets what happens to closures once they time out.
+    pub fn on_timeout(mut self, on_timeout: OnTimeout) -> Self {
+        self.on_timeout = on_timeout;
+        self
+    }
+
+    /// Names the helper threads, for debuggers and panic messages.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Returns the time limit.
+    #[must_use]
+    pub fn limit(&self) -> Duration {
+        self.limit
+    }
+
+    /// Runs `f` on a helper thread, returning its result, or [`Timeout`] if it doesn't finish in
+    /// time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Timeout`] if `f` runs for longer than the limit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the helper thread can't be spawned, and resumes the panic of `f` if it panics
+    /// before the limit.
+    pub fn run<R: Send + 'static>(
+        &self,
+        f: impl FnOnce() -> R + Send + 'static,
+    ) -> Result<R, Timeout> {
+        self.run_cancellable(|_| f())
+    }
+
+    /// Runs `f` on a helper thread with a flag set once it times out, returning its result, or
+    /// [`Timeout`] if it doesn't finish in time. A panic of `f` after the limit is dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Timeout`] if `f` runs for longer than the limit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the helper thread can't be spawned, and resumes the panic of `f` if it panics
+    /// before the limit.
+    pub fn run_cancellable<R: Send + 'static>(
+        &self,
+        f: impl FnOnce(&ShutdownFlag) -> R + Send + 'static,
+    ) -> Result<R, Timeout> {
+        let cancelled = ShutdownFlag::new();
+        let (sender, result) = mpsc::sync_channel(1);
+        let mut builder = thread::Builder::new();
+        if let Some(name) = &self.name {
+            builder = builder.name(name.clone());
+        }
+        let thread = builder
+            .spawn({
+                let cancelled = cancelled.clone();
+                move || {
+                    let _ = sender.send(f(&cancelled));
+                }
+            })
+            .expect("failed to spawn timeout thread");
+
+        match result.recv_timeout(self.limit) {
+            Ok(value) => Ok(value),
+            Err(RecvTimeoutError::Disconnected) => {
+                // The result is only dropped without being sent if `f` panicked.
+                let payload = thread
+                    .join()
+                    .expect_err("timeout thread ended without a result");
+                panic::resume_unwind(payload)
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                cancelled.set();
+                if self.on_timeout == OnTimeout::Join {
+                    let _ = thread.join();
+                }
+                Err(Timeout { limit: self.limit })
+            }
+        }
+    }
+}
+
+/// A closure ran for longer than its time limit.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Timeout {
+    limit: Duration,
+}
+
+impl Timeout {
+    /// Returns the time limit which was exceeded.
+    #[must_use]
+    pub const fn limit(&self) -> Duration {
+        self.limit
+    }
+}
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out after {:?}", self.limit)
+    }
+}
+
+impl Error for Timeout {}
+
+impl From<Timeout> for io::Error {
+    fn from(err: Timeout) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn laps() {
+        let mut stopwatch = Stopwatch::new();
+        assert!(!stopwatch.is_running());
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+
+        stopwatch.start();
+        std::thread::sleep(Duration::from_millis(2));
+        let first = stopwatch.lap();
+        assert!(first >= Duration::from_millis(1));
+        let stopped = stopwatch.stop();
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(stopwatch.elapsed(), stopped);
+
+        stopwatch.start();
+        std::thread::sleep(Duration::from_millis(1));
+        stopwatch.lap();
+        let laps = stopwatch.laps();
+        assert_eq!(laps.len(), 2);
+        assert_eq!(laps[0].split, first);
+        assert_eq!(laps[1].split, laps[0].split + laps[1].duration);
+        // The time stopped between the laps isn't counted.
+        assert!(laps[1].duration < Duration::from_millis(20));
+
+        stopwatch.reset();
+        assert!(stopwatch.laps().is_empty());
+        assert_eq!(stopwatch.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn throttles() {
+        let throttle = Throttler::new(Duration::from_hours(1));
+        assert_eq!(throttle.interval(), Duration::from_hours(1));
+        assert!(throttle.ready());
+        assert!(!throttle.ready());
+        assert_eq!(throttle.call(|| 1), None);
+        throttle.reset();
+        assert_eq!(throttle.call(|| 1), Some(1));
+
+        let unlimited = Throttler::new(Duration::ZERO);
+        assert!(unlimited.ready() && unlimited.ready());
+    }
+
+    #[test]
+    fn debounces() {
+        let (sender, received) = std::sync::mpsc::channel();
+        let debouncer = Debouncer::new(Duration::from_millis(20)).expect("spawned thread");
+        for i in 0..3 {
+            let sender = sender.clone();
+            debouncer.call(move || sender.send(i).expect("valid channel"));
+        }
+        assert_eq!(received.recv_timeout(Duration::from_secs(10)), Ok(2));
+        assert!(!debouncer.is_pending());
+
+        let debouncer = Debouncer::new(Duration::from_hours(1)).expect("spawned thread");
+        debouncer.call(move || sender.send(3).expect("valid channel"));
+        assert!(debouncer.is_pending());
+        assert!(debouncer.flush());
+        assert_eq!(received.try_recv(), Ok(3));
+        assert!(!debouncer.flush());
+        debouncer.call(|| panic!("cancelled"));
+        assert!(debouncer.cancel() && !debouncer.cancel());
+        drop(debouncer);
+        assert!(received.try_recv().is_err());
+    }
+
+    #[test]
+    fn ticks() {
+        let start = Instant::now();
+        let mut ticker = Ticker::new(Duration::from_millis(10));
+        assert_eq!(ticker.try_tick(), None);
+        let first = ticker.tick();
+        assert!(first >= start + Duration::from_millis(10));
+        // Ticks are due at whole intervals, however late they're handled.
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(ticker.tick(), first + Duration::from_millis(10));
+        assert_eq!(ticker.missed(), 0);
+
+        std::thread::sleep(Duration::from_millis(35));
+        let late = ticker.try_tick().expect("overdue tick");
+        assert_eq!(late, first + Duration::from_millis(20));
+        assert!(ticker.missed() >= 2);
+        assert!(ticker.next_tick() > Instant::now());
+        assert_eq!(ticker.take(2).count(), 2);
+    }
+
+    #[test]
+    fn schedules() {
+        let (sender, received) = std::sync::mpsc::channel();
+        let fast = sender.clone();
+        let scheduler = Scheduler::new()
+            .every(Duration::from_millis(5), move || {
+                let _ = fast.send("fast");
+            })
+            .every(Duration::from_hours(1), move || {
+                let _ = sender.send("slow");
+            })
+            .start()
+            .expect("spawned thread");
+        for _ in 0..3 {
+            assert_eq!(received.recv_timeout(Duration::from_secs(10)), Ok("fast"));
+        }
+        drop(scheduler);
+        assert!(received.try_iter().all(|task| task == "fast"));
+        assert!(received.recv().is_err());
+
+        drop(Scheduler::new().start().expect("spawned thread"));
+    }
+
+    #[test]
+    fn times_out() {
+        assert_eq!(with_timeout(Duration::from_secs(10), || "done"), Ok("done"));
+        let (sender, finished) = mpsc::channel();
+        let err = with_timeout(Duration::from_millis(10), move || {
+            thread::sleep(Duration::from_millis(50));
+            let _ = sender.send(());
+        })
+        .expect_err("timed out");
+        assert_eq!(err.to_string(), "timed out after 10ms");
+        assert_eq!(io::Error::from(err).kind(), io::ErrorKind::TimedOut);
+        // Detached closures keep running after the timeout.
+        assert_eq!(finished.recv_timeout(Duration::from_secs(10)), Ok(()));
+
+        let panicked =
+            panic::catch_unwind(|| with_timeout(Duration::from_secs(10), || panic!("x")));
+        assert!(panicked.is_err());
+    }
+
+    #[test]
+    fn cancels_on_timeout() {
+        let stopped = Arc::new(AtomicU64::new(0));
+        let limit = TimeLimit::new(Duration::from_millis(10))
+            .on_timeout(OnTimeout::Join)
+            .name("cancellable");
+        let result = limit.run_cancellable({
+            let stopped = Arc::clone(&stopped);
+            move |cancelled| {
+                assert_eq!(thread::current().name(), Some("cancellable"));
+                cancelled.wait();
+                stopped.store(1, Ordering::Relaxed);
+            }
+        });
+        assert_eq!(
+            result,
+            Err(Timeout {
+                limit: limit.limit()
+            })
+        );
+        // Joined closures have returned by the time the timeout is.
+        assert_eq!(stopped.load(Ordering::Relaxed), 1);
+    }
+}
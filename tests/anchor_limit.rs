@@ -0,0 +1,61 @@
+//! Anchors beyond the configured limit are aggregated or grow the table.
+#![cfg(feature = "perf")]
+
+use util_lib_rs::{
+    performance::{
+        self,
+        config::{set_profiler_config, AnchorOverflow, ProfilerConfig, OVERFLOW_ANCHOR},
+        ProfileReport,
+    },
+    profile,
+};
+
+const NAMES: [&str; 5] = ["limit_a", "limit_b", "limit_c", "limit_d", "limit_e"];
+
+fn profile_names() -> ProfileReport {
+    std::thread::spawn(|| {
+        performance::profile_begin();
+        for _ in 0..2 {
+            for name in NAMES {
+                profile!(name);
+            }
+        }
+        performance::profile_end()
+    })
+    .join()
+    .expect("profiled thread")
+}
+
+#[test]
+fn anchor_overflow() {
+    set_profiler_config(ProfilerConfig::new().max_anchors(3));
+    let report = profile_names();
+    let anchors: Vec<_> = report
+        .anchors
+        .iter()
+        .map(|anchor| (anchor.name, anchor.hit_count))
+        .collect();
+    assert_eq!(
+        anchors,
+        [
+            ("limit_a", 2),
+            ("limit_b", 2),
+            ("limit_c", 2),
+            (OVERFLOW_ANCHOR, 4)
+        ]
+    );
+    assert!(report
+        .warnings
+        .iter()
+        .any(|warning| warning.contains("more than 3 anchors were hit")));
+
+    set_profiler_config(
+        ProfilerConfig::new()
+            .max_anchors(3)
+            .overflow(AnchorOverflow::Grow),
+    );
+    let report = profile_names();
+    assert_eq!(report.anchors.len(), NAMES.len());
+    assert!(report.warnings.is_empty());
+    set_profiler_config(ProfilerConfig::new());
+}
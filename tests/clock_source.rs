@@ -0,0 +1,45 @@
+//! Blocks read an installed clock source instead of the timestamp counter.
+#![cfg(feature = "perf")]
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use util_lib_rs::{
+    performance::{
+        self,
+        clock::{set_clock_source, ClockSource, TimestampCounter},
+    },
+    profile,
+};
+
+/// Advances a millisecond on every read.
+struct Stepping(AtomicU64);
+
+impl ClockSource for Stepping {
+    fn read(&self) -> u64 {
+        self.0.fetch_add(1000, Ordering::Relaxed)
+    }
+
+    fn frequency(&self) -> u64 {
+        1_000_000
+    }
+}
+
+#[test]
+fn custom_clock_source() {
+    assert!(set_clock_source(Stepping(AtomicU64::new(0))));
+    assert!(!set_clock_source(TimestampCounter));
+
+    performance::profile_begin();
+    {
+        profile!("clock_source_block");
+    }
+    let report = performance::profile_end();
+    assert_eq!(report.timer_freq, 1_000_000);
+    let anchor = report
+        .anchors
+        .iter()
+        .find(|anchor| anchor.name == "clock_source_block")
+        .expect("block anchor");
+    assert!(anchor.tsc_elapsed_inclusive >= 1000);
+    assert_eq!(anchor.tsc_elapsed_inclusive % 1000, 0);
+    assert!(report.elapsed_ms() >= 1.0);
+}
@@ -0,0 +1,48 @@
+//! Hardware counters are attributed to the innermost active block.
+#![cfg(feature = "perf-counters")]
+
+use util_lib_rs::{
+    performance::{self, counters::set_hardware_counters},
+    profile,
+};
+
+fn spin() {
+    std::hint::black_box((0..200_000u64).fold(0, |acc, n| acc ^ std::hint::black_box(n)));
+}
+
+#[test]
+fn hardware_counters_per_anchor() {
+    set_hardware_counters(true);
+    performance::profile_begin();
+    {
+        profile!("counters_outer");
+        spin();
+        {
+            profile!("counters_inner");
+            spin();
+        }
+    }
+    let report = performance::profile_end();
+    set_hardware_counters(false);
+
+    let find = |name| {
+        report
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == name)
+            .expect("valid anchor")
+    };
+    let (outer, inner) = (find("counters_outer"), find("counters_inner"));
+    if report
+        .warnings
+        .iter()
+        .any(|warning| warning.starts_with("hardware counters are unavailable"))
+    {
+        // Counters are unavailable in many containers and virtual machines.
+        assert!(outer.counters.is_zero() && inner.counters.is_zero());
+        return;
+    }
+    assert!(inner.counters.cycles > 0);
+    assert!(outer.counters.cycles > 0);
+    assert!(report.to_string().contains(" cycles, "));
+}
@@ -0,0 +1,34 @@
+//! Profiling can be turned off and on at runtime.
+#![cfg(feature = "perf")]
+
+use util_lib_rs::{
+    performance::{self, is_enabled, set_enabled},
+    profile, profile_loop,
+};
+
+#[test]
+fn runtime_toggle() {
+    assert!(is_enabled());
+    performance::profile_begin();
+    set_enabled(false);
+    {
+        profile!("toggle_disabled");
+        for _ in 0..3 {
+            profile_loop!("toggle_loop");
+        }
+        performance::span_end(performance::span_begin("toggle_span"));
+        // Blocks started while disabled stay inert after enabling.
+        set_enabled(true);
+    }
+    {
+        profile!("toggle_enabled");
+        // Blocks started while enabled still record after disabling.
+        set_enabled(false);
+    }
+    set_enabled(true);
+
+    let report = performance::profile_end();
+    let names: Vec<_> = report.anchors.iter().map(|anchor| anchor.name).collect();
+    assert_eq!(names, ["toggle_enabled"]);
+    assert!(report.loops.is_empty());
+}
@@ -0,0 +1,48 @@
+//! Latency histograms count every block and list percentiles in the report.
+#![cfg(feature = "perf")]
+
+use util_lib_rs::{
+    performance::{
+        self,
+        config::{set_profiler_config, ProfilerConfig},
+    },
+    profile,
+};
+
+#[test]
+fn latency_percentiles() {
+    set_profiler_config(ProfilerConfig::new().latency_histograms(true));
+    let report = std::thread::spawn(|| {
+        performance::profile_begin();
+        for i in 0..100 {
+            profile!("histogram_request");
+            if i == 99 {
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+        }
+        performance::profile_end()
+    })
+    .join()
+    .expect("profiled thread");
+    set_profiler_config(ProfilerConfig::new());
+
+    let histogram = report
+        .histograms
+        .iter()
+        .find(|histogram| histogram.name == "histogram_request")
+        .expect("request histogram");
+    assert_eq!(histogram.count(), 100);
+    let anchor = report
+        .anchors
+        .iter()
+        .find(|anchor| anchor.name == "histogram_request")
+        .expect("request anchor");
+    // Only the slow hit is above the 99th percentile.
+    assert!(histogram.percentile(99.0) < anchor.tsc_max / 2);
+    assert!(histogram.percentile(100.0) > anchor.tsc_max / 2);
+    assert!(report.to_string().contains(" p50 "));
+
+    let mut merged = performance::ProfileReport::default();
+    merged += &report;
+    assert_eq!(merged.histograms[0].count(), 100);
+}
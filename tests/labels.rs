@@ -0,0 +1,54 @@
+//! Blocks named with `fmt =` get an anchor per distinct label, up to the configured limit.
+#![cfg(feature = "perf")]
+
+use util_lib_rs::{
+    performance::{
+        self,
+        config::{set_profiler_config, ProfilerConfig},
+        ProfileReport,
+    },
+    profile,
+};
+
+fn query(tables: &[&str]) -> ProfileReport {
+    let tables: Vec<String> = tables.iter().map(ToString::to_string).collect();
+    std::thread::spawn(move || {
+        performance::profile_begin();
+        for table in &tables {
+            profile!(fmt = "label_query:{table}", items = 2);
+        }
+        performance::profile_end()
+    })
+    .join()
+    .expect("profiled thread")
+}
+
+fn hits(report: &ProfileReport) -> Vec<(&'static str, u64, u64)> {
+    report
+        .anchors
+        .iter()
+        .map(|anchor| (anchor.name, anchor.hit_count, anchor.item_count))
+        .collect()
+}
+
+#[test]
+fn formatted_labels() {
+    let report = query(&["users", "orders", "users"]);
+    assert_eq!(
+        hits(&report),
+        [("label_query:users", 2, 4), ("label_query:orders", 1, 2)]
+    );
+    assert!(report.warnings.is_empty());
+
+    set_profiler_config(ProfilerConfig::new().max_labels(2));
+    let report = query(&["users", "items", "carts"]);
+    set_profiler_config(ProfilerConfig::new());
+    assert_eq!(
+        hits(&report),
+        [("label_query:users", 1, 2), ("label_query:{table}", 2, 4)]
+    );
+    assert!(report
+        .warnings
+        .iter()
+        .any(|warning| warning.contains("more than 2 distinct labels")));
+}
@@ -0,0 +1,42 @@
+//! Live reports include statistics threads publish while they keep running.
+#![cfg(feature = "perf")]
+
+use std::{sync::mpsc::channel, time::Duration};
+use util_lib_rs::{performance::live::LiveReport, profile};
+
+#[test]
+fn live_reports() {
+    let (send, reports) = channel();
+    let reporter = LiveReport::new(Duration::from_millis(10))
+        .on_report(move |report| {
+            let _ = send.send(report.clone());
+        })
+        .start()
+        .expect("reporter thread");
+
+    let mut hits = None;
+    for _ in 0..1000 {
+        {
+            profile!("live_work");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        if let Ok(report) = reports.try_recv() {
+            hits = report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == "live_work")
+                .map(|anchor| anchor.hit_count);
+            if hits.is_some() {
+                assert!(report.elapsed_tsc > 0);
+                break;
+            }
+        }
+    }
+    assert!(hits.is_some_and(|hits| hits > 0));
+
+    drop(reporter);
+    // No reports are sent once the reporter is stopped.
+    while reports.try_recv().is_ok() {}
+    std::thread::sleep(Duration::from_millis(30));
+    assert!(reports.try_recv().is_err());
+}
@@ -0,0 +1,72 @@
+//! Log messages go to the installed writer, filtered by level.
+#![cfg(not(any(
+    feature = "log-max-level-off",
+    feature = "log-max-level-error",
+    feature = "log-max-level-warn",
+    feature = "log-max-level-info",
+    feature = "log-max-level-debug"
+)))]
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+use util_lib_rs::{
+    debug, error, info,
+    log::{max_level, set_max_level, set_writer, LevelFilter},
+    trace, warn,
+};
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().expect("valid lock").clone()).expect("valid utf-8")
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().expect("valid lock").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn leveled_messages() {
+    let buffer = SharedBuffer::default();
+    set_writer(Some(Box::new(buffer.clone())));
+    assert_eq!(max_level(), LevelFilter::Info);
+
+    error!("failed {}", 1);
+    warn!("careful");
+    info!("started");
+    debug!("hidden");
+    set_max_level(LevelFilter::Trace);
+    trace!("step {}", 2);
+    set_max_level(LevelFilter::Off);
+    error!("silenced");
+    set_writer(None);
+
+    let logged = buffer.contents();
+    let lines: Vec<_> = logged
+        .lines()
+        .map(|line| line.split_once(' ').expect("timestamp").1)
+        .collect();
+    assert_eq!(
+        lines,
+        [
+            "ERROR log: failed 1",
+            "WARN  log: careful",
+            "INFO  log: started",
+            "TRACE log: step 2",
+        ]
+    );
+    let timestamp = logged.split(' ').next().expect("timestamp");
+    assert_eq!(timestamp.len(), "2026-01-01T00:00:00.000Z".len());
+    assert!(timestamp.ends_with('Z'));
+}
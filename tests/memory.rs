@@ -0,0 +1,69 @@
+//! Heap usage counted by the tracking allocator, recorded over time for massif output.
+
+use std::{alloc::System, time::Duration};
+use util_lib_rs::performance::{
+    massif::{write_massif, HeapRecorder},
+    memory::{heap_usage, is_tracking, TrackingAllocator},
+};
+
+#[global_allocator]
+static ALLOCATOR: TrackingAllocator<System> = TrackingAllocator::new(System);
+
+#[test]
+fn heap_growth_is_recorded() {
+    let recorder = HeapRecorder::start(Duration::from_millis(1)).expect("recorder thread");
+    let before = heap_usage();
+    let buffer = vec![0u8; 1 << 20];
+    let during = heap_usage();
+    std::thread::sleep(Duration::from_millis(20));
+    let samples = recorder.finish();
+    drop(buffer);
+
+    assert!(is_tracking());
+    assert!(during.heap_bytes >= before.heap_bytes + (1 << 20));
+    assert!(during.peak_heap_bytes >= during.heap_bytes);
+    assert!(during.allocations > before.allocations);
+    assert!(samples.iter().any(|sample| sample.heap_bytes >= 1 << 20));
+
+    let mut output = Vec::new();
+    write_massif(&samples, "memory", &mut output).expect("valid write");
+    assert!(String::from_utf8_lossy(&output).contains("heap_tree=empty"));
+}
+
+#[cfg(feature = "perf")]
+#[test]
+fn allocations_per_anchor() {
+    use util_lib_rs::{performance, profile};
+
+    let _ = Box::new(0u8);
+    performance::profile_begin();
+    {
+        profile!("alloc_outer");
+        let outer = std::hint::black_box(vec![0u8; 1000]);
+        {
+            profile!("alloc_inner");
+            for _ in 0..3 {
+                drop(std::hint::black_box(vec![0u8; 4096]));
+            }
+        }
+        drop(outer);
+    }
+    let report = performance::profile_end();
+
+    let find = |name| {
+        report
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == name)
+            .expect("valid anchor")
+    };
+    let (outer, inner) = (find("alloc_outer"), find("alloc_inner"));
+    assert_eq!(inner.alloc_count, 3);
+    assert_eq!((inner.alloc_bytes, inner.freed_bytes), (3 * 4096, 3 * 4096));
+    // The inner block's allocations aren't counted again for the outer block.
+    assert!(outer.alloc_bytes >= 1000 && outer.alloc_bytes < 4096);
+    assert!(outer.freed_bytes >= 1000);
+    assert!(report
+        .to_string()
+        .contains("3 allocs, 12.0 KiB allocated, 12.0 KiB freed"));
+}
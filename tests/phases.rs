@@ -0,0 +1,66 @@
+//! Phases and resets restart the statistics of the current thread.
+#![cfg(feature = "perf")]
+
+use util_lib_rs::{performance, profile};
+
+fn hits(report: &performance::ProfileReport, name: &str) -> Option<u64> {
+    report
+        .anchors
+        .iter()
+        .find(|anchor| anchor.name == name)
+        .map(|anchor| anchor.hit_count)
+}
+
+#[test]
+fn phase_reports() {
+    performance::profile_begin();
+    {
+        profile!("phase_setup");
+    }
+    let unnamed = performance::profile_phase("startup");
+    assert_eq!(hits(&unnamed, "phase_setup"), Some(1));
+    assert!(unnamed.metadata.iter().all(|(key, _)| *key != "phase"));
+
+    for _ in 0..2 {
+        profile!("phase_load");
+    }
+    let startup = performance::profile_phase("frame");
+    assert_eq!(hits(&startup, "phase_load"), Some(2));
+    assert_eq!(hits(&startup, "phase_setup"), None);
+    assert!(startup.metadata.contains(&("phase", "startup".to_string())));
+
+    {
+        profile!("phase_render");
+        profile!("phase_load");
+    }
+    let frame = performance::profile_end();
+    assert_eq!(hits(&frame, "phase_load"), Some(1));
+    assert_eq!(hits(&frame, "phase_render"), Some(1));
+    assert_eq!(
+        frame
+            .calls
+            .iter()
+            .map(|call| call.callee)
+            .collect::<Vec<_>>(),
+        ["phase_load"]
+    );
+    assert!(frame.metadata.contains(&("phase", "frame".to_string())));
+    assert!(frame.elapsed_tsc >= frame.anchors[0].tsc_elapsed_inclusive);
+}
+
+#[test]
+fn reset_clears_anchors() {
+    performance::profile_begin();
+    performance::record_branch("phase_branch", "taken");
+    {
+        profile!("phase_before_reset");
+    }
+    performance::profile_reset();
+    {
+        profile!("phase_after_reset");
+    }
+    let report = performance::profile_end();
+    assert_eq!(hits(&report, "phase_before_reset"), None);
+    assert_eq!(hits(&report, "phase_after_reset"), Some(1));
+    assert!(report.branches.is_empty());
+}
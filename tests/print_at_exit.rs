@@ -0,0 +1,38 @@
+//! Reports requested with `profile_print_at_exit` are produced when the thread exits.
+#![cfg(feature = "perf")]
+
+use std::sync::Mutex;
+use util_lib_rs::{performance, profile};
+
+static REPORTED: Mutex<Vec<Vec<&'static str>>> = Mutex::new(Vec::new());
+
+#[test]
+fn reports_at_thread_exit() {
+    performance::add_exporter(|report: &performance::ProfileReport| {
+        REPORTED
+            .lock()
+            .expect("valid lock")
+            .push(report.anchors.iter().map(|anchor| anchor.name).collect());
+        Ok(())
+    });
+
+    std::thread::spawn(|| {
+        performance::profile_begin();
+        performance::profile_print_at_exit();
+        profile!("at_exit");
+    })
+    .join()
+    .expect("valid thread");
+    assert_eq!(*REPORTED.lock().expect("valid lock"), [vec!["at_exit"]]);
+
+    // Ending profiling explicitly cancels the report at exit.
+    std::thread::spawn(|| {
+        performance::profile_begin();
+        performance::profile_print_at_exit();
+        profile!("ended");
+        let _ = performance::profile_end();
+    })
+    .join()
+    .expect("valid thread");
+    assert_eq!(REPORTED.lock().expect("valid lock").len(), 2);
+}
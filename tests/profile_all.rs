@@ -0,0 +1,64 @@
+//! Every function in a `#[profile_all]` impl block or module gets its own anchor.
+#![cfg(all(feature = "perf", feature = "macros"))]
+
+use util_lib_rs::{performance, profile_all};
+
+struct Parser;
+
+#[profile_all]
+impl Parser {
+    fn parse(&self, input: &str) -> usize {
+        self.tokens(input).len() + usize::from(Self::is_empty(input))
+    }
+
+    fn tokens<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        input.split_whitespace().collect()
+    }
+
+    #[no_profile]
+    fn is_empty(input: &str) -> bool {
+        input.is_empty()
+    }
+
+    const fn limit() -> usize {
+        16
+    }
+}
+
+#[profile_all]
+mod net {
+    pub fn send() -> u8 {
+        inner::encode()
+    }
+
+    #[no_profile]
+    pub fn receive() {}
+
+    pub mod inner {
+        pub fn encode() -> u8 {
+            1
+        }
+    }
+}
+
+#[test]
+fn instruments_every_function() {
+    performance::profile_begin();
+    assert_eq!(Parser.parse("a b"), 2);
+    assert_eq!(Parser::limit(), 16);
+    assert_eq!(net::send(), 1);
+    net::receive();
+    let snapshot = performance::profile_snapshot("profile_all");
+
+    let mut names: Vec<&str> = snapshot.anchors.iter().map(|anchor| anchor.name).collect();
+    names.sort_unstable();
+    assert_eq!(
+        names,
+        [
+            "profile_all::Parser::parse",
+            "profile_all::Parser::tokens",
+            "profile_all::net::inner::encode",
+            "profile_all::net::send",
+        ]
+    );
+}
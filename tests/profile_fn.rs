@@ -0,0 +1,56 @@
+//! A `#[profile_fn]` function gets an anchor named after it.
+#![cfg(all(feature = "perf", feature = "macros"))]
+
+use util_lib_rs::{performance, profile_fn};
+
+struct Parser;
+
+impl Parser {
+    #[profile_fn]
+    fn parse(&self, input: &str) -> usize {
+        self.tokens(input).len()
+    }
+
+    #[profile_fn]
+    pub(crate) fn tokens<'a>(&self, input: &'a str) -> Vec<&'a str> {
+        input.split_whitespace().collect()
+    }
+}
+
+#[profile_fn]
+fn sum<T: Copy + Into<u64>>(values: &[T]) -> u64 {
+    values.iter().map(|&value| value.into()).sum()
+}
+
+#[profile_fn]
+#[inline]
+unsafe fn unchecked(values: &[u8]) -> u8 {
+    *values.get_unchecked(0)
+}
+
+#[test]
+fn instruments_functions() {
+    performance::profile_begin();
+    assert_eq!(Parser.parse("a b"), 2);
+    assert_eq!(sum(&[1u8, 2]), 3);
+    assert_eq!(sum(&[3u32]), 3);
+    // SAFETY: The slice isn't empty.
+    assert_eq!(unsafe { unchecked(&[7]) }, 7);
+    let snapshot = performance::profile_snapshot("profile_fn");
+
+    let mut hits: Vec<(&str, u64)> = snapshot
+        .anchors
+        .iter()
+        .map(|anchor| (anchor.name, anchor.hit_count))
+        .collect();
+    hits.sort_unstable();
+    assert_eq!(
+        hits,
+        [
+            ("profile_fn::Parser::parse", 1),
+            ("profile_fn::Parser::tokens", 1),
+            ("profile_fn::sum", 2),
+            ("profile_fn::unchecked", 1),
+        ]
+    );
+}
@@ -0,0 +1,83 @@
+//! The `profview` binary prints, filters, sorts and compares saved dumps.
+#![cfg(feature = "cli")]
+
+use std::{path::PathBuf, process::Command};
+use util_lib_rs::performance::{
+    dump::{Compression, ProfileDump},
+    AnchorStats, ProfileReport,
+};
+
+fn save(name: &str, anchors: &[(&'static str, u64)]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("profview_{}_{name}.dump", std::process::id()));
+    let report = ProfileReport {
+        elapsed_tsc: anchors.iter().map(|&(_, tsc)| tsc).sum(),
+        timer_freq: 1000,
+        anchors: anchors
+            .iter()
+            .map(|&(name, tsc)| AnchorStats {
+                name,
+                hit_count: 1,
+                tsc_elapsed_exclusive: tsc,
+                tsc_elapsed_inclusive: tsc,
+                ..AnchorStats::default()
+            })
+            .collect(),
+        ..ProfileReport::default()
+    };
+    ProfileDump::new(report)
+        .save(&path, Compression::None)
+        .expect("valid save");
+    path
+}
+
+fn profview(args: &[&str]) -> (bool, String, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_profview"))
+        .args(args)
+        .output()
+        .expect("valid command");
+    (
+        output.status.success(),
+        String::from_utf8_lossy(&output.stdout).into_owned(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn profview_views() {
+    let before = save(
+        "before",
+        &[("view_lex", 100), ("view_parse", 300), ("view_emit", 200)],
+    );
+    let after = save(
+        "after",
+        &[("view_lex", 100), ("view_parse", 600), ("view_emit", 200)],
+    );
+    let before = before.to_str().expect("valid path");
+    let after = after.to_str().expect("valid path");
+
+    let (success, output, _) = profview(&[before, "--sort", "exclusive", "--top", "2"]);
+    assert!(success);
+    let parse = output.find("  view_parse ").expect("valid parse anchor");
+    let emit = output.find("  view_emit ").expect("valid emit anchor");
+    assert!(parse < emit);
+    assert!(!output.contains("view_lex"));
+
+    let (success, output, _) = profview(&[before, "--exclude", "^view_(lex|emit)$"]);
+    assert!(success);
+    assert!(output.contains("  view_parse ") && !output.contains("view_emit"));
+
+    let (success, output, _) = profview(&["--diff", before, after]);
+    assert!(success);
+    assert!(output.contains("\n  view_parse: 300.0000ms -> 600.0000ms (+100.00%)"));
+
+    let (success, _, errors) = profview(&[before, after]);
+    assert!(!success);
+    assert!(errors.contains("--diff"));
+    let (success, _, errors) = profview(&[before, "--sort", "fastest"]);
+    assert!(!success);
+    assert!(errors.contains("invalid --sort order: fastest"));
+
+    for path in [before, after] {
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -0,0 +1,73 @@
+//! Shared anchors add up the hits of every thread without merging reports.
+#![cfg(feature = "perf")]
+
+use util_lib_rs::{
+    performance::shared::{shared_report, shared_reset},
+    profile_shared,
+};
+
+fn expensive() {
+    std::hint::black_box((0..10_000u64).fold(0, |acc, n| acc ^ std::hint::black_box(n)));
+}
+
+fn recurse(depth: u32) {
+    profile_shared!("shared_recurse");
+    expensive();
+    if depth > 0 {
+        recurse(depth - 1);
+    }
+}
+
+#[test]
+fn shared_anchors() {
+    let workers: Vec<_> = (0..4)
+        .map(|_| {
+            std::thread::spawn(|| {
+                for _ in 0..10 {
+                    profile_shared!("shared_outer", 100);
+                    expensive();
+                    profile_shared!("shared_inner");
+                    expensive();
+                }
+                recurse(2);
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker.join().expect("valid worker");
+    }
+
+    let report = shared_report();
+    let anchor = |name| {
+        report
+            .anchors
+            .iter()
+            .find(|anchor| anchor.name == name)
+            .copied()
+            .expect("valid anchor")
+    };
+    let outer = anchor("shared_outer");
+    let inner = anchor("shared_inner");
+    assert_eq!((outer.hit_count, outer.byte_count), (40, 4000));
+    assert_eq!(inner.hit_count, 40);
+    assert!(report.timer_freq > 0 && report.elapsed_tsc >= outer.tsc_elapsed_inclusive / 4);
+    assert_eq!(
+        outer.tsc_elapsed_exclusive + inner.tsc_elapsed_inclusive,
+        outer.tsc_elapsed_inclusive
+    );
+    assert!(outer.tsc_min > 0 && outer.tsc_min <= outer.tsc_max);
+    assert!(outer.tsc_elapsed_squares >= u128::from(outer.tsc_min) * u128::from(outer.tsc_min));
+
+    // Only the outermost block of each recursion adds to the inclusive time.
+    let recursive = anchor("shared_recurse");
+    assert_eq!(recursive.hit_count, 12);
+    assert_eq!(
+        recursive.tsc_elapsed_inclusive,
+        recursive.tsc_elapsed_exclusive
+    );
+
+    shared_reset();
+    let report = shared_report();
+    assert!(report.anchors.iter().all(|anchor| anchor.hit_count == 0));
+    assert!(report.warnings.is_empty());
+}
@@ -0,0 +1,56 @@
+//! Printed reports can be redirected to any writer.
+#![cfg(feature = "perf")]
+
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+use util_lib_rs::{
+    performance::{self, sink::set_output_sink},
+    profile,
+};
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.lock().expect("valid lock").clone()).expect("valid utf-8")
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().expect("valid lock").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn output_sink() {
+    let buffer = SharedBuffer::default();
+    set_output_sink(Some(Box::new(buffer.clone())));
+    performance::profile_begin();
+    {
+        profile!("sink_printed");
+    }
+    performance::profile_end_and_print();
+    set_output_sink(None);
+    let printed = buffer.contents();
+    assert!(printed.contains("Total time:"));
+    assert!(printed.contains("  sink_printed "));
+
+    performance::profile_begin();
+    {
+        profile!("sink_written");
+    }
+    let mut written = Vec::new();
+    performance::profile_end_and_write(&mut written).expect("valid write");
+    let written = String::from_utf8(written).expect("valid utf-8");
+    assert!(written.contains("  sink_written "));
+    // Removing the sink restores printing to `stderr`.
+    assert_eq!(buffer.contents(), printed);
+}
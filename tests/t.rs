@@ -8,3 +8,11 @@ fn my_function() {
         profile!("loop");
     }
 }
+
+#[test]
+fn named_counts() {
+    let bytes = 1024;
+    util_lib_rs::profile!("bytes_and_items", bytes = bytes, items = 4);
+    profile!("items_only", items = 4);
+    profile!("positional_bytes", bytes);
+}
@@ -0,0 +1,68 @@
+//! Statistics of exited threads are aggregated into the next report.
+#![cfg(feature = "perf")]
+
+use util_lib_rs::{
+    performance::{
+        self,
+        threads::{set_thread_aggregation, ThreadAggregation},
+    },
+    profile,
+};
+
+fn spawn_workers() {
+    let workers: Vec<_> = (0..2)
+        .map(|i| {
+            std::thread::Builder::new()
+                .name(format!("worker-{i}"))
+                .spawn(|| {
+                    profile!("thread_work");
+                })
+                .expect("valid thread")
+        })
+        .collect();
+    for worker in workers {
+        worker.join().expect("valid thread");
+    }
+}
+
+#[test]
+fn aggregate_threads() {
+    std::thread::Builder::new()
+        .name("main-test".into())
+        .spawn(|| {
+            set_thread_aggregation(ThreadAggregation::Flattened);
+            performance::profile_begin();
+            profile!("thread_main");
+            spawn_workers();
+            let report = performance::profile_end();
+            let work = report
+                .anchors
+                .iter()
+                .find(|anchor| anchor.name == "thread_work")
+                .expect("merged anchor");
+            assert_eq!(work.hit_count, 2);
+            // Each thread is only included once.
+            assert!(performance::profile_end()
+                .anchors
+                .iter()
+                .all(|anchor| anchor.name != "thread_work"));
+
+            set_thread_aggregation(ThreadAggregation::PerThread);
+            performance::profile_begin();
+            spawn_workers();
+            let report = performance::profile_end();
+            let mut names: Vec<_> = report.anchors.iter().map(|anchor| anchor.name).collect();
+            names.sort_unstable();
+            assert_eq!(
+                names,
+                [
+                    "main-test/thread_main",
+                    "worker-0/thread_work",
+                    "worker-1/thread_work"
+                ]
+            );
+        })
+        .expect("valid thread")
+        .join()
+        .expect("valid thread");
+}
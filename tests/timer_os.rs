@@ -0,0 +1,30 @@
+//! Blocks can read the operating system clock instead of the timestamp counter.
+#![cfg(feature = "perf")]
+
+use std::time::Duration;
+use util_lib_rs::{
+    performance::{self, TimerRead},
+    profile,
+};
+
+#[test]
+fn os_timer_read() {
+    performance::set_timer_read(TimerRead::Os);
+    performance::profile_begin();
+    {
+        profile!("os_timer_sleep");
+        std::thread::sleep(Duration::from_millis(20));
+    }
+    let report = performance::profile_end();
+
+    #[cfg(not(target_os = "windows"))]
+    assert_eq!(report.timer_freq, 1_000_000_000);
+    let anchor = report
+        .anchors
+        .iter()
+        .find(|anchor| anchor.name == "os_timer_sleep")
+        .expect("valid anchor");
+    let elapsed = anchor.tsc_elapsed_inclusive as f64 / report.timer_freq as f64;
+    assert!((0.02..1.0).contains(&elapsed), "{elapsed}");
+    assert!(report.elapsed_tsc >= anchor.tsc_elapsed_inclusive);
+}
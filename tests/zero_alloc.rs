@@ -0,0 +1,74 @@
+//! The profiling hot path must not allocate once warmed up, so it can be used inside allocators and
+//! latency-sensitive loops.
+#![cfg(feature = "perf")]
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    cell::Cell,
+};
+use util_lib_rs::{performance, profile, profile_branch, profile_loop};
+
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// SAFETY: Defers to the system allocator, only counting calls.
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|count| count.set(count.get() + 1));
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(Cell::get)
+}
+
+fn inner(i: u64) {
+    profile!("inner", bytes = i, items = 1);
+    profile_branch!("parity", if i.is_multiple_of(2) { "even" } else { "odd" });
+}
+
+fn workload() {
+    profile!();
+    for i in 0..100 {
+        profile_loop!("iteration");
+        inner(i);
+    }
+    let block = performance::block_begin("manual");
+    let span = performance::span_begin("span");
+    performance::span_end(span);
+    performance::block_end(block);
+
+    let _ns = performance::profile_namespace("namespaced");
+    inner(0);
+}
+
+#[test]
+fn hot_path_does_not_allocate() {
+    performance::profile_begin();
+
+    // The first run warms up from the same call site, since call stacks are interned per path.
+    let mut before = 0;
+    for run in 0..11 {
+        if run == 1 {
+            before = allocations();
+        }
+        workload();
+    }
+    assert_eq!(allocations() - before, 0);
+}